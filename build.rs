@@ -7,4 +7,11 @@ fn main() {
         .all_git()
         .emit()
         .expect("Failed to generate build information");
+
+    // grpc 特性：由 proto/multidown.proto 生成服务端和客户端代码，使用随 crate 提供的 protoc
+    #[cfg(feature = "grpc")]
+    {
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path().expect("找不到 protoc"));
+        tonic_build::compile_protos("proto/multidown.proto").expect("生成 gRPC 代码失败");
+    }
 } 
\ No newline at end of file
@@ -0,0 +1,63 @@
+/*
+ * multidown 的 C 接口，见 multidown-ffi/src/lib.rs
+ *
+ * 所有函数都可以在任意线程调用；失败时返回 -1（multidown_manager_new 返回 NULL），
+ * 用 multidown_last_error() 取得当前线程最近一次错误的说明。字符串均为 UTF-8。
+ */
+#ifndef MULTIDOWN_H
+#define MULTIDOWN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* 任务 ID 字符串（含结尾的 NUL）的长度 */
+#define MULTIDOWN_TASK_ID_LEN 37
+
+/* multidown_progress.status */
+#define MULTIDOWN_PENDING 0
+#define MULTIDOWN_QUEUED 1
+#define MULTIDOWN_RUNNING 2
+#define MULTIDOWN_PAUSED 3
+#define MULTIDOWN_COMPLETED 4
+#define MULTIDOWN_FAILED 5
+#define MULTIDOWN_CANCELLED 6
+
+typedef struct Manager multidown_manager;
+
+typedef struct multidown_progress {
+    int status;          /* MULTIDOWN_PENDING 等 */
+    float progress;      /* 0-100 */
+    uint64_t downloaded;
+    uint64_t total;      /* 未知时为 0 */
+    uint64_t speed;      /* 字节/秒 */
+} multidown_progress;
+
+/* 创建管理器，config_path 为 NULL 时使用默认配置；会话中未完成的任务随之加载 */
+multidown_manager *multidown_manager_new(const char *config_path);
+
+/* 添加任务并立即开始。file_name 是下载目录下的相对路径，为 NULL 时取 URL 的最后一段；
+ * 成功时把任务 ID 写入 task_id_out（可以为 NULL，否则 task_id_len >= MULTIDOWN_TASK_ID_LEN） */
+int multidown_add_url(const multidown_manager *manager, const char *url, const char *file_name,
+                      char *task_id_out, size_t task_id_len);
+
+/* 查询进度；任务失败时失败原因可以用 multidown_last_error() 取得 */
+int multidown_poll_progress(const multidown_manager *manager, const char *task_id, multidown_progress *out);
+
+/* 取消任务并删除已下载的临时数据 */
+int multidown_cancel(const multidown_manager *manager, const char *task_id);
+
+/* 暂停正在下载的任务、保存会话并停止下载线程，manager 为 NULL 时不做任何事 */
+void multidown_manager_free(multidown_manager *manager);
+
+/* 当前线程最近一次错误的说明，没有时为 NULL；在本线程下一次调用前有效，不需要释放 */
+const char *multidown_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* MULTIDOWN_H */
@@ -0,0 +1,293 @@
+//! multidown 的 C 接口：在 C/C++ 程序（例如 Qt 界面）中嵌入下载引擎
+//!
+//! 头文件为 `include/multidown.h`，构建后得到 `libmultidown_ffi.so` / `.dylib` / `multidown_ffi.dll`
+//! 以及静态库。典型用法：
+//!
+//! ```c
+//! multidown_manager *m = multidown_manager_new("multidown.conf");
+//! char id[MULTIDOWN_TASK_ID_LEN];
+//! if (multidown_add_url(m, "https://example.com/a.iso", NULL, id, sizeof id) != 0)
+//!     fprintf(stderr, "%s\n", multidown_last_error());
+//! multidown_progress p;
+//! while (multidown_poll_progress(m, id, &p) == 0 && p.status < MULTIDOWN_COMPLETED) { ... }
+//! multidown_manager_free(m);
+//! ```
+//!
+//! - 管理器是 [`EmbeddedManager`]，在自己的线程中运行 actix 系统，所有函数都可以在任意线程调用，调用期间阻塞到管理器处理完消息；
+//! - 任务用 UUID 字符串标识，与 `multidown status` 等命令中的 ID 相同；
+//! - 失败的函数返回 -1（创建管理器失败时返回 NULL），`multidown_last_error` 取得当前线程最近一次错误的说明（UTF-8）；
+//! - 字符串参数都是以 NUL 结尾的 UTF-8，函数返回后不再引用。
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+
+use multidown::config::Config;
+use multidown::core::embedded::EmbeddedManager;
+use multidown::core::error::DownloadError;
+use multidown::core::task::TaskStatus;
+use uuid::Uuid;
+
+/// 任务 ID 字符串（含结尾的 NUL）的长度
+pub const MULTIDOWN_TASK_ID_LEN: usize = 37;
+
+pub const MULTIDOWN_PENDING: c_int = 0;
+pub const MULTIDOWN_QUEUED: c_int = 1;
+pub const MULTIDOWN_RUNNING: c_int = 2;
+pub const MULTIDOWN_PAUSED: c_int = 3;
+pub const MULTIDOWN_COMPLETED: c_int = 4;
+pub const MULTIDOWN_FAILED: c_int = 5;
+pub const MULTIDOWN_CANCELLED: c_int = 6;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: impl std::fmt::Display) {
+    let message = CString::new(message.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+/// 记录错误并返回 -1
+fn fail(message: impl std::fmt::Display) -> c_int {
+    set_last_error(message);
+    -1
+}
+
+/// 读取 C 字符串参数，NULL 时返回 None
+///
+/// # Safety
+/// `ptr` 为 NULL 或指向以 NUL 结尾的字符串
+unsafe fn read_str<'a>(ptr: *const c_char, name: &str) -> Result<Option<&'a str>, String> {
+    if ptr.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(ptr).to_str().map(Some).map_err(|_| format!("{} 不是有效的 UTF-8", name))
+}
+
+/// 嵌入的下载管理器
+pub type Manager = EmbeddedManager;
+
+/// 一个任务的进度
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Progress {
+    /// `MULTIDOWN_PENDING` 等状态之一
+    pub status: c_int,
+    /// 0-100
+    pub progress: f32,
+    pub downloaded: u64,
+    /// 未知时为 0
+    pub total: u64,
+    /// 字节/秒
+    pub speed: u64,
+}
+
+fn status_code(status: &TaskStatus) -> c_int {
+    match status {
+        TaskStatus::Pending => MULTIDOWN_PENDING,
+        TaskStatus::Queued => MULTIDOWN_QUEUED,
+        TaskStatus::Running => MULTIDOWN_RUNNING,
+        TaskStatus::Paused => MULTIDOWN_PAUSED,
+        TaskStatus::Completed => MULTIDOWN_COMPLETED,
+        TaskStatus::Failed(_) => MULTIDOWN_FAILED,
+        TaskStatus::Cancelled => MULTIDOWN_CANCELLED,
+    }
+}
+
+fn progress(manager: &Manager, task_id: Uuid) -> Result<Progress, DownloadError> {
+    let meta = manager.task(task_id)?;
+    // 失败原因通过 multidown_last_error 取得
+    if let TaskStatus::Failed(reason) = &meta.status {
+        set_last_error(reason);
+    }
+    Ok(Progress {
+        status: status_code(&meta.status),
+        progress: meta.progress,
+        downloaded: meta.downloaded,
+        total: meta.total,
+        speed: meta.speed,
+    })
+}
+
+/// 读取任务 ID 参数
+///
+/// # Safety
+/// 同 [`read_str`]
+unsafe fn read_task_id(ptr: *const c_char) -> Result<Uuid, String> {
+    let id = read_str(ptr, "task_id")?.ok_or("task_id 为 NULL")?;
+    Uuid::parse_str(id).map_err(|_| format!("无效的任务 ID: {}", id))
+}
+
+/// 创建管理器，`config_path` 为 NULL 时使用默认配置
+///
+/// 配置文件按严格模式读取（解析失败时不会改写文件），不存在时使用默认配置。失败时返回 NULL。
+///
+/// # Safety
+/// `config_path` 为 NULL 或指向以 NUL 结尾的字符串
+#[no_mangle]
+pub unsafe extern "C" fn multidown_manager_new(config_path: *const c_char) -> *mut Manager {
+    let config = match read_str(config_path, "config_path") {
+        Ok(Some(path)) => Config::load_with_mode(path, true).map_err(|e| e.to_string()),
+        Ok(None) => Ok(Config::default()),
+        Err(e) => Err(e),
+    };
+    match config.and_then(|config| Manager::start(config).map_err(|e| e.to_string())) {
+        Ok(manager) => Box::into_raw(Box::new(manager)),
+        Err(e) => {
+            set_last_error(e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// 添加下载任务并立即开始（超过并发数时排队）
+///
+/// `file_name` 是下载目录下的相对路径，为 NULL 时取 URL 的最后一段。成功时把任务 ID 写入
+/// `task_id_out`（至少 `MULTIDOWN_TASK_ID_LEN` 字节，可以为 NULL）并返回 0。
+///
+/// # Safety
+/// `manager` 由 [`multidown_manager_new`] 返回且未释放；字符串参数为 NULL 或以 NUL 结尾；
+/// `task_id_out` 为 NULL 或至少有 `task_id_len` 字节可写
+#[no_mangle]
+pub unsafe extern "C" fn multidown_add_url(
+    manager: *const Manager,
+    url: *const c_char,
+    file_name: *const c_char,
+    task_id_out: *mut c_char,
+    task_id_len: usize,
+) -> c_int {
+    let Some(manager) = manager.as_ref() else {
+        return fail("manager 为 NULL");
+    };
+    if !task_id_out.is_null() && task_id_len < MULTIDOWN_TASK_ID_LEN {
+        return fail(format!("task_id_out 至少需要 {} 字节", MULTIDOWN_TASK_ID_LEN));
+    }
+    let result = read_str(url, "url")
+        .and_then(|url| url.ok_or_else(|| "url 为 NULL".to_string()))
+        .and_then(|url| Ok((url, read_str(file_name, "file_name")?)))
+        .and_then(|(url, file_name)| manager.add_url(url, file_name).map_err(|e| e.to_string()));
+    match result {
+        Ok(task_id) => {
+            if !task_id_out.is_null() {
+                let id = CString::new(task_id.to_string()).unwrap_or_default();
+                std::ptr::copy_nonoverlapping(id.as_ptr(), task_id_out, MULTIDOWN_TASK_ID_LEN);
+            }
+            0
+        }
+        Err(e) => fail(e),
+    }
+}
+
+/// 查询任务进度，写入 `out` 并返回 0；任务失败时失败原因可以用 `multidown_last_error` 取得
+///
+/// # Safety
+/// `manager` 同 [`multidown_add_url`]；`task_id` 以 NUL 结尾；`out` 指向可写的 `multidown_progress`
+#[no_mangle]
+pub unsafe extern "C" fn multidown_poll_progress(manager: *const Manager, task_id: *const c_char, out: *mut Progress) -> c_int {
+    let (Some(manager), false) = (manager.as_ref(), out.is_null()) else {
+        return fail("manager 或 out 为 NULL");
+    };
+    match read_task_id(task_id).and_then(|id| progress(manager, id).map_err(|e| e.to_string())) {
+        Ok(progress) => {
+            *out = progress;
+            0
+        }
+        Err(e) => fail(e),
+    }
+}
+
+/// 取消任务并删除已下载的临时数据，任务已结束时不做任何事
+///
+/// # Safety
+/// 同 [`multidown_poll_progress`]
+#[no_mangle]
+pub unsafe extern "C" fn multidown_cancel(manager: *const Manager, task_id: *const c_char) -> c_int {
+    let Some(manager) = manager.as_ref() else {
+        return fail("manager 为 NULL");
+    };
+    match read_task_id(task_id).and_then(|id| manager.cancel(id).map_err(|e| e.to_string())) {
+        Ok(()) => 0,
+        Err(e) => fail(e),
+    }
+}
+
+/// 释放管理器：暂停正在下载的任务并保存会话，等待下载线程退出。`manager` 为 NULL 时不做任何事
+///
+/// # Safety
+/// `manager` 由 [`multidown_manager_new`] 返回且未释放，调用后不能再使用
+#[no_mangle]
+pub unsafe extern "C" fn multidown_manager_free(manager: *mut Manager) {
+    if !manager.is_null() {
+        drop(Box::from_raw(manager));
+    }
+}
+
+/// 当前线程最近一次错误的说明，没有错误时返回 NULL
+///
+/// 返回的字符串在本线程下一次调用 multidown 函数前有效，不需要释放。
+#[no_mangle]
+pub extern "C" fn multidown_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(std::ptr::null(), |e| e.as_ptr()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(multidown_last_error()) }.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_embedded_download() {
+        // 只响应一次的 HTTP 服务器
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten().take(4) {
+                let mut stream = stream;
+                let mut request = [0u8; 4096];
+                let _ = stream.read(&mut request);
+                let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello");
+            }
+        });
+
+        let dir = std::env::temp_dir().join(format!("multidown-ffi-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("multidown.conf");
+        std::fs::write(&config_path, format!("download_dir = {:?}\n", dir.join("out").to_string_lossy())).unwrap();
+        let config_path = CString::new(config_path.to_string_lossy().as_ref()).unwrap();
+
+        unsafe {
+            let manager = multidown_manager_new(config_path.as_ptr());
+            assert!(!manager.is_null(), "{}", last_error());
+
+            let bad = CString::new("not a url").unwrap();
+            assert_eq!(multidown_add_url(manager, bad.as_ptr(), std::ptr::null(), std::ptr::null_mut(), 0), -1);
+            assert!(!last_error().is_empty());
+
+            let url = CString::new(format!("http://127.0.0.1:{}/hello.txt", port)).unwrap();
+            let mut id = [0 as c_char; MULTIDOWN_TASK_ID_LEN];
+            assert_eq!(multidown_add_url(manager, url.as_ptr(), std::ptr::null(), id.as_mut_ptr(), id.len()), 0, "{}", last_error());
+
+            let mut progress = Progress::default();
+            for _ in 0..100 {
+                assert_eq!(multidown_poll_progress(manager, id.as_ptr(), &mut progress), 0);
+                if progress.status >= MULTIDOWN_COMPLETED {
+                    break;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(50));
+            }
+            assert_eq!(progress.status, MULTIDOWN_COMPLETED, "{}", last_error());
+            assert_eq!(std::fs::read(dir.join("out/hello.txt")).unwrap(), b"hello");
+
+            let unknown = CString::new(Uuid::new_v4().to_string()).unwrap();
+            assert_eq!(multidown_cancel(manager, unknown.as_ptr()), -1);
+            assert!(last_error().contains("任务不存在"));
+            multidown_manager_free(manager);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,244 @@
+//! multidown 的 Python 模块：数据处理脚本可以直接使用多线程下载引擎，不需要调用命令行
+//!
+//! ```python
+//! import multidown
+//!
+//! multidown.init("multidown.conf")          # 可选，不调用时使用默认配置
+//! d = multidown.add("https://example.com/a.iso", on_progress=lambda done, total, speed: print(done, total))
+//! path = d.wait()                            # 或在协程中 await d
+//! ```
+//!
+//! - 下载在 [`EmbeddedManager`] 的线程中进行，不占用 GIL；`wait` 等待期间释放 GIL，并响应 Ctrl-C；
+//! - `on_progress(downloaded, total, speed)` 在后台线程中调用，调用期间持有 GIL，应尽快返回；
+//! - 下载失败或被取消时 `wait` 抛出 `multidown.DownloadError`；
+//! - 解释器退出时（或调用 `shutdown`）暂停未完成的任务并保存会话，下次可以续传。
+
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use multidown::config::Config;
+use multidown::core::embedded::EmbeddedManager;
+use multidown::core::events::TaskEvent;
+use multidown::core::task::TaskStatus;
+use pyo3::exceptions::{PyException, PyRuntimeError, PyTimeoutError};
+use pyo3::prelude::*;
+use tokio::sync::broadcast::{self, error::RecvError};
+use uuid::Uuid;
+
+pyo3::create_exception!(multidown, DownloadError, PyException, "下载失败或被取消");
+
+/// `wait` 每次释放 GIL 等待的时长，之后检查一次 Ctrl-C
+const WAIT_SLICE: Duration = Duration::from_millis(100);
+
+/// 模块共用的管理器，第一次使用时创建
+static ENGINE: Mutex<Option<Arc<EmbeddedManager>>> = Mutex::new(None);
+
+fn runtime_error(e: impl std::fmt::Display) -> PyErr {
+    PyRuntimeError::new_err(e.to_string())
+}
+
+fn engine() -> PyResult<Arc<EmbeddedManager>> {
+    let mut engine = ENGINE.lock().map_err(runtime_error)?;
+    if engine.is_none() {
+        *engine = Some(Arc::new(EmbeddedManager::start(Config::default()).map_err(runtime_error)?));
+    }
+    Ok(engine.as_ref().map(Arc::clone).expect("已创建管理器"))
+}
+
+/// 任务的结果，后台线程收到完成、失败或取消事件后写入
+#[derive(Default)]
+struct Outcome {
+    result: Mutex<Option<Result<(), String>>>,
+    done: Condvar,
+}
+
+impl Outcome {
+    fn finish(&self, result: Result<(), String>) {
+        if let Ok(mut slot) = self.result.lock() {
+            *slot = Some(result);
+        }
+        self.done.notify_all();
+    }
+
+    /// 最多等待 `timeout`，任务还没有结束时返回 None
+    fn wait_for(&self, timeout: Duration) -> Option<Result<(), String>> {
+        let slot = self.result.lock().ok()?;
+        let (slot, _) = self.done.wait_timeout_while(slot, timeout, |slot| slot.is_none()).ok()?;
+        slot.clone()
+    }
+}
+
+/// 后台线程：转发进度事件给回调，直到任务结束
+fn watch(
+    mut events: broadcast::Receiver<TaskEvent>,
+    task_id: Uuid,
+    outcome: Arc<Outcome>,
+    on_progress: Option<Py<PyAny>>,
+    engine: Weak<EmbeddedManager>,
+) {
+    let result = loop {
+        let event = match events.blocking_recv() {
+            Ok(event) if event.task_id() == task_id => event,
+            Ok(_) => continue,
+            // 跳过了积压的事件，结束事件可能也在其中，以元数据为准
+            Err(RecvError::Lagged(_)) => match engine.upgrade().and_then(|engine| engine.task(task_id).ok()).map(|meta| meta.status) {
+                Some(TaskStatus::Completed) => break Ok(()),
+                Some(TaskStatus::Failed(error)) => break Err(error),
+                Some(TaskStatus::Cancelled) => break Err("任务已取消".to_string()),
+                _ => continue,
+            },
+            Err(RecvError::Closed) => break Err("下载管理器已停止，任务已暂停".to_string()),
+        };
+        match event {
+            TaskEvent::Progress { downloaded, total, speed, .. } => {
+                if let Some(callback) = &on_progress {
+                    Python::attach(|py| {
+                        if let Err(e) = callback.call1(py, (downloaded, total, speed)) {
+                            e.write_unraisable(py, Some(callback.bind(py)));
+                        }
+                    });
+                }
+            }
+            TaskEvent::Completed { .. } => break Ok(()),
+            TaskEvent::Failed { error, .. } => break Err(error),
+            TaskEvent::StatusChanged { to: TaskStatus::Cancelled, .. } => break Err("任务已取消".to_string()),
+            TaskEvent::Removed { .. } => break Err("任务已删除".to_string()),
+            _ => {}
+        }
+    };
+    outcome.finish(result);
+}
+
+/// 一个下载任务，由 `multidown.add` 返回
+#[pyclass(module = "multidown", frozen)]
+struct Download {
+    id: Uuid,
+    #[pyo3(get)]
+    url: String,
+    /// 保存路径
+    #[pyo3(get)]
+    path: String,
+    outcome: Arc<Outcome>,
+}
+
+#[pymethods]
+impl Download {
+    /// 任务 ID，与 `multidown status` 中的相同
+    #[getter]
+    fn id(&self) -> String {
+        self.id.to_string()
+    }
+
+    /// 当前状态：pending、queued、running、paused、completed、failed、cancelled
+    #[getter]
+    fn status(&self, py: Python<'_>) -> PyResult<&'static str> {
+        let engine = engine()?;
+        let meta = py.detach(|| engine.task(self.id)).map_err(runtime_error)?;
+        Ok(meta.status.as_str())
+    }
+
+    /// 当前进度 `(downloaded, total, speed)`，总大小未知时 total 为 0
+    fn progress(&self, py: Python<'_>) -> PyResult<(u64, u64, u64)> {
+        let engine = engine()?;
+        let meta = py.detach(|| engine.task(self.id)).map_err(runtime_error)?;
+        Ok((meta.downloaded, meta.total, meta.speed))
+    }
+
+    /// 取消任务并删除已下载的临时数据
+    fn cancel(&self, py: Python<'_>) -> PyResult<()> {
+        let engine = engine()?;
+        py.detach(|| engine.cancel(self.id)).map_err(runtime_error)
+    }
+
+    /// 等待任务结束并返回保存路径，失败或取消时抛出 DownloadError，超时抛出 TimeoutError
+    #[pyo3(signature = (timeout=None))]
+    fn wait(&self, py: Python<'_>, timeout: Option<f64>) -> PyResult<String> {
+        let deadline = timeout.map(|seconds| Instant::now() + Duration::from_secs_f64(seconds.max(0.0)));
+        loop {
+            let slice = deadline.map_or(WAIT_SLICE, |deadline| deadline.saturating_duration_since(Instant::now()).min(WAIT_SLICE));
+            match py.detach(|| self.outcome.wait_for(slice)) {
+                Some(Ok(())) => return Ok(self.path.clone()),
+                Some(Err(error)) => return Err(DownloadError::new_err(error)),
+                None => {}
+            }
+            py.check_signals()?;
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Err(PyTimeoutError::new_err(format!("等待任务超时: {}", self.url)));
+            }
+        }
+    }
+
+    /// 在协程中 `await download`：在线程池中执行 `wait`，不阻塞事件循环
+    fn __await__<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyAny>> {
+        let py = slf.py();
+        let event_loop = py.import("asyncio")?.call_method0("get_running_loop")?;
+        event_loop.call_method1("run_in_executor", (py.None(), slf.getattr("wait")?))?.call_method0("__await__")
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<multidown.Download {} {}>", self.id, self.url)
+    }
+}
+
+/// 用配置文件创建管理器，必须在第一次 `add` 之前调用；不调用时使用默认配置
+///
+/// 配置文件按严格模式读取（解析失败时不会改写文件），不存在时使用默认配置。
+#[pyfunction]
+#[pyo3(signature = (config=None))]
+fn init(py: Python<'_>, config: Option<&str>) -> PyResult<()> {
+    let mut engine = ENGINE.lock().map_err(runtime_error)?;
+    if engine.is_some() {
+        return Err(PyRuntimeError::new_err("下载管理器已经创建，init 需要在 add 之前调用"));
+    }
+    let config = match config {
+        Some(path) => Config::load_with_mode(path, true).map_err(runtime_error)?,
+        None => Config::default(),
+    };
+    *engine = Some(Arc::new(py.detach(|| EmbeddedManager::start(config)).map_err(runtime_error)?));
+    Ok(())
+}
+
+/// 添加下载任务并立即开始（超过并发数时排队），返回 `Download`
+///
+/// `file_name` 是下载目录下的相对路径，默认取 URL 的最后一段；
+/// `on_progress(downloaded, total, speed)` 在收到进度时调用。
+#[pyfunction]
+#[pyo3(signature = (url, file_name=None, on_progress=None))]
+fn add(py: Python<'_>, url: String, file_name: Option<String>, on_progress: Option<Py<PyAny>>) -> PyResult<Download> {
+    let engine = engine()?;
+    // 先订阅再创建任务，不会漏掉任何事件
+    let events = engine.events().subscribe();
+    let (id, path) = py
+        .detach(|| {
+            let id = engine.add_url(&url, file_name.as_deref())?;
+            Ok::<_, multidown::core::error::DownloadError>((id, engine.task(id)?.file))
+        })
+        .map_err(|e| DownloadError::new_err(e.to_string()))?;
+    let outcome = Arc::new(Outcome::default());
+    let watcher = (Arc::clone(&outcome), Arc::downgrade(&engine));
+    std::thread::Builder::new()
+        .name(format!("multidown-watch-{}", id))
+        .spawn(move || watch(events, id, watcher.0, on_progress, watcher.1))
+        .map_err(runtime_error)?;
+    Ok(Download { id, url, path, outcome })
+}
+
+/// 暂停未完成的任务、保存会话并停止管理器，解释器退出时自动调用
+#[pyfunction]
+fn shutdown(py: Python<'_>) -> PyResult<()> {
+    let engine = ENGINE.lock().map_err(runtime_error)?.take();
+    py.detach(|| drop(engine));
+    Ok(())
+}
+
+#[pymodule]
+#[pyo3(name = "multidown")]
+fn multidown_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(init, m)?)?;
+    m.add_function(wrap_pyfunction!(add, m)?)?;
+    m.add_function(wrap_pyfunction!(shutdown, m)?)?;
+    m.add_class::<Download>()?;
+    m.add("DownloadError", m.py().get_type::<DownloadError>())?;
+    m.py().import("atexit")?.call_method1("register", (m.getattr("shutdown")?,))?;
+    Ok(())
+}
@@ -0,0 +1,91 @@
+"""multidown Python 模块的测试
+
+先构建扩展：`maturin develop -m multidown-py/Cargo.toml`，然后 `python -m unittest discover multidown-py/tests`
+"""
+
+import asyncio
+import os
+import re
+import tempfile
+import threading
+import unittest
+from http.server import BaseHTTPRequestHandler, ThreadingHTTPServer
+
+import multidown
+
+DATA = os.urandom(64 * 1024)
+
+
+class Handler(BaseHTTPRequestHandler):
+    protocol_version = "HTTP/1.1"
+
+    def log_message(self, *args):
+        pass
+
+    def do_HEAD(self):
+        if self.path != "/data.bin":
+            self.send_response(404)
+            self.send_header("Content-Length", "0")
+            self.end_headers()
+            return b""
+        start, end = 0, len(DATA) - 1
+        match = re.match(r"bytes=(\d+)-(\d*)", self.headers.get("Range", ""))
+        if match:
+            start, end = int(match.group(1)), int(match.group(2) or end)
+            self.send_response(206)
+            self.send_header("Content-Range", "bytes %d-%d/%d" % (start, end, len(DATA)))
+        else:
+            self.send_response(200)
+        self.send_header("Accept-Ranges", "bytes")
+        self.send_header("Content-Length", str(end - start + 1))
+        self.end_headers()
+        return DATA[start : end + 1]
+
+    def do_GET(self):
+        self.wfile.write(self.do_HEAD())
+
+
+class MultidownTest(unittest.TestCase):
+    @classmethod
+    def setUpClass(cls):
+        cls.server = ThreadingHTTPServer(("127.0.0.1", 0), Handler)
+        threading.Thread(target=cls.server.serve_forever, daemon=True).start()
+        cls.base = "http://127.0.0.1:%d" % cls.server.server_port
+        cls.dir = tempfile.mkdtemp()
+        config = os.path.join(cls.dir, "multidown.conf")
+        with open(config, "w") as f:
+            f.write('download_dir = "%s"\nspeed_limit_kb = 0\n' % os.path.join(cls.dir, "out"))
+        multidown.init(config)
+
+    @classmethod
+    def tearDownClass(cls):
+        multidown.shutdown()
+        cls.server.shutdown()
+
+    def test_add_and_wait(self):
+        progress = []
+        d = multidown.add(self.base + "/data.bin", file_name="a.bin", on_progress=lambda *p: progress.append(p))
+        path = d.wait(timeout=30)
+        with open(path, "rb") as f:
+            self.assertEqual(f.read(), DATA)
+        self.assertEqual(d.status, "completed")
+        self.assertEqual(d.progress()[0], len(DATA))
+        self.assertTrue(all(total in (0, len(DATA)) for _, total, _ in progress))
+
+    def test_await(self):
+        async def main():
+            return await multidown.add(self.base + "/data.bin", file_name="b.bin")
+
+        self.assertTrue(asyncio.run(main()).endswith("b.bin"))
+
+    def test_errors(self):
+        with self.assertRaises(multidown.DownloadError):
+            multidown.add("not a url")
+        with self.assertRaises(multidown.DownloadError):
+            multidown.add(self.base + "/missing.bin").wait(timeout=30)
+        with self.assertRaises(RuntimeError):
+            multidown.init()
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,1136 @@
+//! 子命令实现
+
+use crate::cli::exit_code::ExitCode;
+use crate::cli::url_list::UrlEntry;
+use crate::cli::{Args, Command, ConfigAction, DaemonArgs, SecretAction, ServiceAction};
+use crate::config::clients::{ClientKeys, Credentials};
+use crate::config::{edit, Config};
+use crate::core::actor_manager::{
+    find_tasks, load_session, purge_task_data, save_session, ClientStats, DownloadManagerActor, DownloadTaskMeta, PauseAllAndSave,
+    WaitForBackgroundJobs, SESSION_FILE,
+};
+use crate::core::session_lock::{LockError, SessionLock, LOCK_FILE};
+use crate::core::bench;
+use crate::core::check::{self, CheckResult};
+use crate::core::history::{HistoryEntry, HistoryStore, HISTORY_FILE};
+use crate::core::ipc;
+use crate::core::lfs::{self, LfsPointer};
+use crate::core::oci::{self, ImageReference};
+use crate::core::queue;
+use crate::core::relocate::{self, Relocation};
+use crate::core::stream;
+use crate::core::usage::{self, UsageStore, USAGE_FILE};
+use crate::core::verify::{self, VerifyStatus};
+use crate::core::task::dns::Resolver;
+use crate::core::task::handlers::{get_file_info, get_file_info_with_fallback};
+use crate::core::task::http::{self, SocketOptions};
+use crate::core::task::transport::{AwcTransport, RequestSettings};
+use crate::core::task::{TaskOptions, TaskStatus};
+use crate::i18n::{t, tf, Msg};
+use crate::ui::human_size;
+use crate::ui::task_list::{self, ListFilter, SortKey};
+use crate::core::error::DownloadError;
+use crate::utils::{secrets, systemd, tls, windows_service};
+use crate::web;
+use actix::Actor;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io::IsTerminal;
+use std::path::Path;
+
+/// 执行子命令，返回进程退出码（`retry-failed`、`import-queue`、`lfs` 需要下载，由 main 处理）
+pub async fn run(command: &Command, config_path: &str, config: &Config) -> ExitCode {
+    match command {
+        Command::History { search, limit } => history(search.as_deref(), *limit, config),
+        Command::Status { json, tag } => status(*json, tag.as_deref(), config),
+        Command::List { status, sort, reverse, tag, search, json } => {
+            let filter = ListFilter { statuses: status.clone(), tag: tag.clone(), search: search.clone() };
+            list(&filter, *sort, *reverse, *json, config)
+        }
+        Command::Pause { ids, tag } => pause(ids, tag.as_deref(), config),
+        Command::Remove { ids, with_data } => remove(ids, *with_data, config),
+        Command::Move { id, path, temp_dir } => move_task(id, path, temp_dir.as_deref(), config),
+        Command::Stats { since } => stats(since, config),
+        Command::Config { action } => self::config(action, config_path),
+        Command::Secret { action } => secret(action),
+        Command::Verify { targets, remote } => self::verify(targets, *remote, config).await,
+        Command::Serve { daemon } => serve(config, daemon, crate::utils::signal::wait_for_termination(), || ()).await,
+        Command::Service { action } => service(action, config_path, config),
+        Command::ExportQueue { path, tag } => export_queue(path, tag.as_deref(), config),
+        Command::Bench { url, connections, chunk_sizes, duration, save } => {
+            let cases = bench::cases(connections, chunk_sizes);
+            let duration = std::time::Duration::from_secs((*duration).max(1));
+            match run_bench(url, &cases, duration, config).await {
+                Ok(None) => ExitCode::AllFailed,
+                Ok(Some(_)) if !*save => {
+                    println!("{}", t(Msg::BenchSaveHint));
+                    ExitCode::Success
+                }
+                Ok(Some(best)) => match bench::recommended_rule(url, &best)
+                    .and_then(|rule| edit::upsert_rule(config_path, &rule).map(|_| rule))
+                {
+                    Ok(rule) => {
+                        println!("{}", tf(Msg::BenchSaved, &[&config_path, &rule.pattern]));
+                        ExitCode::Success
+                    }
+                    Err(e) => {
+                        eprintln!("{}", tf(Msg::ConfigFailed, &[&e]));
+                        ExitCode::ConfigError
+                    }
+                },
+                Err(e) => {
+                    eprintln!("{}", tf(Msg::BenchFailed, &[&e]));
+                    ExitCode::AllFailed
+                }
+            }
+        }
+    }
+}
+
+/// `multidown bench <url>`：逐组测试并打印结果，返回最快的一组
+async fn run_bench(
+    url: &str,
+    cases: &[bench::BenchCase],
+    duration: std::time::Duration,
+    config: &Config,
+) -> Result<Option<bench::BenchResult>, DownloadError> {
+    crate::utils::validator::parse_url(url)?;
+    let (url_config, settings) = request_settings(url, config)?;
+    let transport = AwcTransport::new(&url_config);
+
+    let info = get_file_info(&transport, url, &settings).await?;
+    if !info.supports_range || info.size == 0 {
+        return Err(DownloadError::Unknown(t(Msg::BenchNoRange).into()));
+    }
+    println!("{}", tf(Msg::BenchStart, &[&url, &human_size(info.size), &cases.len(), &duration.as_secs()]));
+    println!("{}", t(Msg::BenchHeader));
+    let mut results = Vec::new();
+    for case in cases {
+        let result = bench::run_case(&transport, url, &settings, info.size, *case, duration).await;
+        println!(
+            "{:>6}   {:>10}   {:>10}/s   {:>5}",
+            case.connections,
+            human_size(case.chunk_size),
+            human_size(result.speed()),
+            result.errors,
+        );
+        results.push(result);
+    }
+
+    let Some(best) = bench::best(&results).cloned() else {
+        println!("{}", t(Msg::BenchNoResult));
+        return Ok(None);
+    };
+    println!(
+        "{}",
+        tf(Msg::BenchBest, &[&best.case.connections, &human_size(best.case.chunk_size), &human_size(best.speed())])
+    );
+    Ok(Some(best))
+}
+
+/// 与下载时一样应用 URL 规则中的超时、User-Agent 和请求头
+fn request_settings(url: &str, config: &Config) -> Result<(Config, RequestSettings), DownloadError> {
+    let url_config = config.for_url(url);
+    let headers = config
+        .headers_for_url(url)
+        .into_iter()
+        .map(|(name, value)| secrets::resolve(&value).map(|value| (name, value)))
+        .collect::<Result<_, _>>()?;
+    let settings = RequestSettings::new(&url_config, &TaskOptions { headers, ..Default::default() });
+    Ok((url_config, settings))
+}
+
+/// `multidown --check-only [--json] <url>...`：有 URL 不可用时退出码非 0，请求头与下载时相同（包括 `--referer`）
+pub async fn check_urls(args: &Args, urls: &[String], config: &Config) -> ExitCode {
+    let client = http::manual_redirect_client(&SocketOptions::new(config), &Resolver::new(config));
+    let results = check::check_all(&client, urls, config.max_concurrent_downloads, |url| {
+        request_settings(url, config).map(|(_, mut settings)| {
+            for (name, value) in args.task_options(url).headers {
+                settings.headers.retain(|(n, _)| !n.eq_ignore_ascii_case(&name));
+                settings.headers.push((name, value));
+            }
+            settings
+        })
+    })
+    .await;
+
+    if args.json {
+        match serde_json::to_string_pretty(&results) {
+            Ok(text) => println!("{}", text),
+            Err(e) => {
+                eprintln!("{}", e);
+                return ExitCode::AllFailed;
+            }
+        }
+    } else {
+        print_check_table(&results);
+    }
+
+    match results.iter().filter(|r| r.is_ok()).count() {
+        ok if ok == results.len() => ExitCode::Success,
+        0 => ExitCode::AllFailed,
+        _ => ExitCode::PartialFailure,
+    }
+}
+
+/// `multidown lfs`：读取指针文件并向仓库的 LFS 接口查询下载地址，转换成下载列表的条目
+///
+/// 无效的指针和查询失败的对象逐个报告后跳过，一个都没有时返回退出码。
+pub async fn lfs_entries(pointers: &[String], repo: &str, token: Option<&str>, config: &Config) -> Result<Vec<UrlEntry>, ExitCode> {
+    let mut parsed = Vec::new();
+    for path in pointers {
+        let pointer = std::fs::metadata(path)
+            .map_err(|e| DownloadError::io_error_with_context("读取指针文件", e))
+            .and_then(|meta| match meta.len() <= lfs::MAX_POINTER_SIZE {
+                true => std::fs::read_to_string(path).map_err(|e| DownloadError::io_error_with_context("读取指针文件", e)),
+                false => Err(DownloadError::unknown("文件太大，不是 Git LFS 指针文件")),
+            })
+            .and_then(|text| LfsPointer::parse(&text));
+        match pointer {
+            Ok(pointer) => parsed.push((path, pointer)),
+            Err(e) => eprintln!("{}", tf(Msg::LfsPointerInvalid, &[path, &e])),
+        }
+    }
+    if parsed.is_empty() {
+        return Err(ExitCode::ConfigError);
+    }
+
+    let token = match token.map(secrets::resolve).transpose() {
+        Ok(token) => token,
+        Err(e) => {
+            eprintln!("{}", tf(Msg::LfsResolveFailed, &[&e]));
+            return Err(ExitCode::ConfigError);
+        }
+    };
+    let objects: Vec<LfsPointer> = parsed.iter().map(|(_, pointer)| pointer.clone()).collect();
+    let resolved = match lfs::resolve(repo, &objects, token.as_deref(), config).await {
+        Ok(resolved) => resolved,
+        Err(e) => {
+            tracing::error!(error = %e, repo, "查询 LFS 对象失败");
+            eprintln!("{}", tf(Msg::LfsResolveFailed, &[&e]));
+            return Err(ExitCode::from_error(&e));
+        }
+    };
+    let entries: Vec<UrlEntry> = parsed
+        .into_iter()
+        .zip(resolved)
+        .filter_map(|((path, pointer), object)| match object {
+            Ok(object) => Some(UrlEntry {
+                out: Some(lfs::output_name(path)),
+                sha256: Some(pointer.oid),
+                headers: object.headers,
+                ..UrlEntry::new(object.href)
+            }),
+            Err(e) => {
+                eprintln!("{}", tf(Msg::LfsPointerInvalid, &[path, &e]));
+                None
+            }
+        })
+        .collect();
+    if entries.is_empty() {
+        return Err(ExitCode::AllFailed);
+    }
+    Ok(entries)
+}
+
+/// 把下载列表中的 `docker://` 镜像展开成配置和各层的条目：获取清单并写出 OCI 镜像布局目录，
+/// 内容按摘要保存到布局目录的 `blobs/sha256/` 下，已存在且大小一致的跳过
+///
+/// 无法获取清单的镜像逐个报告后跳过，展开后没有任何条目时返回退出码。
+pub async fn oci_entries(entries: Vec<UrlEntry>, download_dir: &str, quiet: bool, config: &Config) -> Result<Vec<UrlEntry>, ExitCode> {
+    if !entries.iter().any(|entry| oci::is_reference(&entry.url)) {
+        return Ok(entries);
+    }
+    let mut expanded = Vec::new();
+    let mut last_error = None;
+    for entry in entries {
+        if !oci::is_reference(&entry.url) {
+            expanded.push(entry);
+            continue;
+        }
+        let resolved = match ImageReference::parse(&entry.url) {
+            Ok(image) => oci::resolve(&image, config).await.map(|resolved| (image, resolved)),
+            Err(e) => Err(e),
+        };
+        let (image, resolved) = match resolved {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                tracing::error!(error = %e, url = %entry.url, "获取镜像清单失败");
+                eprintln!("{}", tf(Msg::OciResolveFailed, &[&entry.url, &e]));
+                last_error = Some(ExitCode::from_error(&e));
+                continue;
+            }
+        };
+        let name = entry.out.clone().unwrap_or_else(|| image.layout_name());
+        let relative = match &entry.dir {
+            Some(dir) => std::path::Path::new(dir).join(&name),
+            None => std::path::PathBuf::from(&name),
+        };
+        let layout = std::path::Path::new(download_dir).join(&relative);
+        if let Err(e) = oci::write_layout(&layout, &image, &resolved) {
+            eprintln!("{}", tf(Msg::OciResolveFailed, &[&entry.url, &e]));
+            last_error = Some(ExitCode::from_error(&e));
+            continue;
+        }
+        let api_base = image.api_base();
+        let mut blobs = Vec::new();
+        for blob in &resolved.blobs {
+            let Ok(hex) = oci::digest_hex(&blob.digest) else { continue };
+            let out = format!("{}/blobs/sha256/{}", name, hex);
+            let existing = std::fs::metadata(layout.join("blobs").join("sha256").join(hex)).map(|meta| meta.len()).ok();
+            if existing == Some(blob.size) {
+                continue;
+            }
+            blobs.push(UrlEntry {
+                out: Some(out),
+                dir: entry.dir.clone(),
+                sha256: Some(hex.to_string()),
+                weight: entry.weight,
+                headers: resolved.token.iter().map(|token| ("Authorization".to_string(), format!("Bearer {}", token))).collect(),
+                ..UrlEntry::new(format!("{}/blobs/{}", api_base, blob.digest))
+            });
+        }
+        tracing::info!(url = %entry.url, manifest = %resolved.manifest.digest, blobs = blobs.len(), "镜像清单已获取");
+        if !quiet {
+            println!("{}", tf(Msg::OciResolved, &[&entry.url, &blobs.len(), &layout.display()]));
+        }
+        expanded.extend(blobs);
+    }
+    match (expanded.is_empty(), last_error) {
+        (true, Some(exit_code)) => Err(exit_code),
+        (true, None) => Err(ExitCode::Success),
+        (false, _) => Ok(expanded),
+    }
+}
+
+/// `multidown -O - <url>`：标准输出只写文件内容，进度和错误都写到标准错误
+pub async fn stream_to_stdout(args: &Args, url: &str, config: &Config) -> ExitCode {
+    let result = async {
+        let (url_config, mut settings) = request_settings(url, config)?;
+        for (name, value) in args.task_options(url).headers {
+            settings.headers.retain(|(n, _)| !n.eq_ignore_ascii_case(&name));
+            settings.headers.push((name, value));
+        }
+        let show_progress = !args.quiet && !args.no_progress && std::io::stderr().is_terminal();
+        let start = std::time::Instant::now();
+        let mut last_report = start;
+        let progress = |written: u64, total: u64| {
+            if !show_progress || last_report.elapsed() < std::time::Duration::from_millis(500) {
+                return;
+            }
+            last_report = std::time::Instant::now();
+            let total = if total > 0 { human_size(total) } else { "?".to_string() };
+            let speed = (written as f64 / start.elapsed().as_secs_f64().max(0.001)) as u64;
+            eprint!("\r\x1b[2K{}", tf(Msg::StreamProgress, &[&human_size(written), &total, &human_size(speed)]));
+        };
+        let mut stdout = std::io::BufWriter::new(std::io::stdout().lock());
+        let transport = AwcTransport::new(&url_config);
+        let written = stream::stream_to(&transport, url, &mut settings, &url_config, args.range, &mut stdout, progress).await;
+        if show_progress {
+            eprint!("\r\x1b[2K");
+        }
+        written
+    }
+    .await;
+
+    match result {
+        Ok(written) => {
+            if !args.quiet {
+                eprintln!("{}", tf(Msg::StreamDone, &[&human_size(written)]));
+            }
+            ExitCode::Success
+        }
+        Err(e) => {
+            tracing::error!(error = %e, url, "管道下载失败");
+            eprintln!("{}", tf(Msg::StreamFailed, &[&e]));
+            ExitCode::from_error(&e)
+        }
+    }
+}
+
+fn print_check_table(results: &[CheckResult]) {
+    println!("{}", t(Msg::CheckHeader));
+    for result in results {
+        let status = result.status.map_or_else(|| "-".to_string(), |s| s.to_string());
+        let size = result.size.map_or_else(|| "-".to_string(), human_size);
+        let range = if result.supports_range { "yes" } else { "no" };
+        println!("{:<6}  {:<10}  {:<5}  {:>6}  {}", status, size, range, result.redirects, result.url);
+        if result.final_url != result.url {
+            println!("{:<34}-> {}", "", result.final_url);
+        }
+        if let Some(error) = &result.error {
+            println!("{:<34}   {}", "", error);
+        }
+    }
+    let ok = results.iter().filter(|r| r.is_ok()).count();
+    println!("{}", tf(Msg::CheckSummary, &[&results.len(), &ok, &(results.len() - ok)]));
+}
+
+/// `multidown serve [--listen <地址>] [--web <地址>]`：守护进程，持有会话锁，通过本地控制通道、网页控制台（和 gRPC）
+/// 接受命令；收到终止信号时暂停所有任务并保存会话
+async fn serve(
+    config: &Config,
+    daemon: &DaemonArgs,
+    shutdown: impl std::future::Future<Output = &'static str>,
+    ready: impl FnOnce(),
+) -> ExitCode {
+    let _lock = match lock_session(config) {
+        Ok(lock) => lock,
+        Err(code) => return code,
+    };
+    // systemd 套接字激活传入的套接字优先于命令行给出的地址；只传入一个未命名的套接字时交给 gRPC
+    let mut fds = systemd::ListenFds::take();
+    let web = daemon.web().map(|addr| take_or_bind(&mut fds, "web", !cfg!(feature = "grpc"), addr));
+    let web = match web.transpose() {
+        Ok(web) => web,
+        Err(e) => {
+            eprintln!("{}", tf(Msg::DaemonFailed, &[&e]));
+            return ExitCode::AllFailed;
+        }
+    };
+    #[cfg(feature = "grpc")]
+    let grpc = take_or_bind(&mut fds, "grpc", true, daemon.listen).and_then(|grpc| Ok((grpc, tls_acceptor(config, &["h2"])?)));
+    #[cfg(feature = "grpc")]
+    let (grpc, grpc_tls) = match grpc {
+        Ok(grpc) => grpc,
+        Err(e) => {
+            eprintln!("{}", tf(Msg::DaemonFailed, &[&e]));
+            return ExitCode::AllFailed;
+        }
+    };
+    let remote: Vec<std::net::SocketAddr> = [
+        web.as_ref(),
+        #[cfg(feature = "grpc")]
+        Some(&grpc),
+    ]
+    .into_iter()
+    .flatten()
+    .filter_map(|listener| listener.local_addr().ok())
+    .collect();
+    let credentials = match remote_credentials(config, &remote) {
+        Ok(credentials) => credentials,
+        Err(e) => {
+            eprintln!("{}", tf(Msg::DaemonFailed, &[&e]));
+            return ExitCode::ConfigError;
+        }
+    };
+    let endpoint = ipc::endpoint(Path::new(&config.state_dir));
+    let server = match ipc::Server::bind(&endpoint) {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("{}", tf(Msg::DaemonFailed, &[&e]));
+            return ExitCode::AllFailed;
+        }
+    };
+    let web = web.map(|listener| tls_acceptor(config, &["http/1.1"]).and_then(|tls| web::Server::from_listener(listener, tls)));
+    let web = match web.transpose() {
+        Ok(web) => web,
+        Err(e) => {
+            eprintln!("{}", tf(Msg::DaemonFailed, &[&e]));
+            return ExitCode::AllFailed;
+        }
+    };
+    let manager = DownloadManagerActor::new(config.clone());
+    let events = manager.events();
+    let manager = manager.start();
+    let stop = tokio_util::sync::CancellationToken::new();
+    let dispatcher =
+        ipc::Dispatcher { manager: manager.clone(), download_dir: config.download_dir.clone(), keys: credentials.keys.clone() };
+    let control = actix::spawn(server.run(dispatcher.clone(), stop.clone()));
+    println!("{}", tf(Msg::DaemonListening, &[&endpoint]));
+    let console = web.map(|web| {
+        println!("{}", tf(Msg::WebListening, &[&web.url()]));
+        let console = web::Console { dispatcher, events: events.clone(), credentials: credentials.clone() };
+        actix::spawn(web.run(console, stop.clone()))
+    });
+
+    let signal = shutdown;
+    #[cfg(feature = "grpc")]
+    let result = {
+        let service = crate::grpc::GrpcService::new(manager.clone(), events, config.download_dir.clone())
+            .with_credentials(credentials);
+        let addr = grpc.local_addr().map_or_else(|_| daemon.listen.to_string(), |addr| addr.to_string());
+        let grpc = crate::grpc::serve(grpc, service, grpc_tls, stop.clone().cancelled_owned());
+        tokio::pin!(grpc);
+        println!("{}", tf(Msg::GrpcListening, &[&addr]));
+        systemd::notify("READY=1\nSTATUS=正在运行");
+        ready();
+        tokio::select! {
+            signal = signal => {
+                systemd::notify("STOPPING=1");
+                stop.cancel();
+                grpc.await.map(|()| signal)
+            }
+            result = &mut grpc => result.map(|()| "?"),
+        }
+    };
+    #[cfg(not(feature = "grpc"))]
+    let result: Result<&str, DownloadError> = {
+        let _ = (events, credentials);
+        systemd::notify("READY=1\nSTATUS=正在运行");
+        ready();
+        let signal = signal.await;
+        systemd::notify("STOPPING=1");
+        Ok(signal)
+    };
+
+    stop.cancel();
+    let _ = control.await;
+    if let Some(console) = console {
+        let _ = console.await;
+    }
+    let _ = manager.send(PauseAllAndSave).await;
+    let _ = manager.send(WaitForBackgroundJobs).await;
+    match result {
+        Ok(signal) => {
+            println!("{}", tf(Msg::DaemonStopped, &[&signal]));
+            ExitCode::Success
+        }
+        Err(e) => {
+            eprintln!("{}", tf(Msg::GrpcFailed, &[&e]));
+            ExitCode::NetworkError
+        }
+    }
+}
+
+/// `service` 子命令
+fn service(action: &ServiceAction, config_path: &str, config: &Config) -> ExitCode {
+    let result = match action {
+        ServiceAction::Install { name, manual, daemon } => service_command_line(name, config_path, config, daemon)
+            .and_then(|command_line| windows_service::install(name, &command_line, !manual))
+            .map(|()| println!("{}", tf(Msg::ServiceInstalled, &[name, name]))),
+        ServiceAction::Uninstall { name } => {
+            windows_service::uninstall(name).map(|()| println!("{}", tf(Msg::ServiceUninstalled, &[name])))
+        }
+        ServiceAction::Run { name, daemon, .. } => {
+            let (config, daemon) = (config.clone(), daemon.clone());
+            windows_service::run(name, std::path::Path::new("logs/service.log"), move |service| {
+                // 服务线程有自己的 actix 系统，主线程阻塞在服务控制管理器的分发循环中
+                let exit_code = actix::System::new().block_on(serve(&config, &daemon, service.stopped(), || service.ready()));
+                exit_code.code()
+            })
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::Success,
+        Err(e) => {
+            eprintln!("{}", tf(Msg::ServiceFailed, &[&e]));
+            ExitCode::ConfigError
+        }
+    }
+}
+
+/// 服务的启动命令：配置文件、下载目录和工作目录都换成绝对路径，服务管理器启动服务时的工作目录是系统目录
+fn service_command_line(name: &str, config_path: &str, config: &Config, daemon: &DaemonArgs) -> Result<String, DownloadError> {
+    let absolute = |path: &str| {
+        std::path::absolute(path)
+            .map(|path| path.display().to_string())
+            .map_err(|e| DownloadError::io_error_with_context(format!("无法解析路径 {}", path), e))
+    };
+    let exe = std::env::current_exe().map_err(|e| DownloadError::io_error_with_context("无法确定程序路径", e))?;
+    let work_dir = std::env::current_dir().map_err(|e| DownloadError::io_error_with_context("无法确定工作目录", e))?;
+    let mut args = vec![
+        exe.display().to_string(),
+        "--config".to_string(),
+        absolute(config_path)?,
+        "--download-dir".to_string(),
+        absolute(&config.download_dir)?,
+        "service".to_string(),
+        "run".to_string(),
+        "--name".to_string(),
+        name.to_string(),
+        "--work-dir".to_string(),
+        work_dir.display().to_string(),
+    ];
+    args.extend(daemon.to_args());
+    Ok(args.iter().map(|arg| windows_service::quote_arg(arg)).collect::<Vec<_>>().join(" "))
+}
+
+/// 取出 systemd 传入的名为 `name` 的监听套接字，没有时监听 `addr`
+fn take_or_bind(
+    fds: &mut systemd::ListenFds,
+    name: &str,
+    fallback: bool,
+    addr: std::net::SocketAddr,
+) -> Result<std::net::TcpListener, DownloadError> {
+    match fds.listener(name, fallback) {
+        Some(listener) => {
+            tracing::info!(name, listen = ?listener.local_addr().ok(), "使用 systemd 传入的监听套接字");
+            Ok(listener)
+        }
+        None => std::net::TcpListener::bind(addr)
+            .map_err(|e| DownloadError::io_error_with_context(format!("无法监听 {}", addr), e)),
+    }
+}
+
+/// 网页控制台和 gRPC 接口的认证
+///
+/// 监听本机以外的地址时必须设置 `serve_token` 或客户端，否则任何能连上的人都可以让守护进程下载任意地址；
+/// 没有启用 TLS 时令牌以明文传输，只记录警告。
+fn remote_credentials(config: &Config, listen: &[std::net::SocketAddr]) -> Result<Credentials, DownloadError> {
+    let credentials = Credentials::new(secrets::resolve(&config.serve_token)?, ClientKeys::resolve(&config.clients)?);
+    for addr in listen.iter().filter(|addr| !addr.ip().is_loopback()) {
+        if credentials.is_open() {
+            return Err(DownloadError::PermissionError(
+                format!("监听非本机地址 {} 时必须设置 serve_token 或客户端", addr).into(),
+            ));
+        }
+        if config.serve_tls_cert.is_empty() {
+            tracing::warn!(listen = %addr, "没有启用 TLS，访问令牌和 API 密钥以明文传输");
+        }
+    }
+    Ok(credentials)
+}
+
+/// 配置了 `serve_tls_cert`、`serve_tls_key` 时的 TLS 接受器，`alpn` 为协商的应用层协议
+fn tls_acceptor(config: &Config, alpn: &[&str]) -> Result<Option<tokio_rustls::TlsAcceptor>, DownloadError> {
+    if config.serve_tls_cert.is_empty() {
+        return Ok(None);
+    }
+    tls::TlsFiles { cert: &config.serve_tls_cert, key: &config.serve_tls_key }.acceptor(alpn).map(Some)
+}
+
+/// `multidown verify [<path|task-id>...] [--remote]`：损坏、缺失或过期的文件会让退出码非 0
+async fn verify(targets: &[String], remote: bool, config: &Config) -> ExitCode {
+    let history = HistoryStore::new(config.state_path(HISTORY_FILE)).load();
+    let mut entries = Vec::new();
+    let mut not_found = 0;
+    if targets.is_empty() {
+        entries = verify::latest_entries(&history);
+    }
+    for target in targets {
+        match verify::find_entry(&history, target) {
+            Some(entry) => entries.push(entry),
+            None => {
+                eprintln!("{}", tf(Msg::VerifyNotFound, &[target]));
+                not_found += 1;
+            }
+        }
+    }
+    if entries.is_empty() && not_found == 0 {
+        println!("{}", t(Msg::HistoryEmpty));
+        return ExitCode::Success;
+    }
+
+    let (mut ok, mut corrupted, mut stale, mut unverified) = (0, 0, 0, not_found);
+    for entry in entries {
+        let status = verify_entry(entry, remote, config).await;
+        match &status {
+            VerifyStatus::Ok => ok += 1,
+            VerifyStatus::Stale(_) => stale += 1,
+            VerifyStatus::Error(_) => unverified += 1,
+            _ => corrupted += 1,
+        }
+        let text = match &status {
+            VerifyStatus::Ok => t(Msg::VerifyOk).to_string(),
+            VerifyStatus::Missing => t(Msg::VerifyMissing).to_string(),
+            VerifyStatus::SizeMismatch { expected, actual } => tf(Msg::VerifySizeMismatch, &[actual, expected]),
+            VerifyStatus::ChecksumMismatch => t(Msg::VerifyChecksumMismatch).to_string(),
+            VerifyStatus::Stale(reason) => tf(Msg::VerifyStale, &[reason]),
+            VerifyStatus::Error(e) => tf(Msg::VerifyError, &[e]),
+        };
+        println!("{:<40}  {}", text, entry.path);
+    }
+    let total = ok + corrupted + stale + unverified;
+    println!("{}", tf(Msg::VerifySummary, &[&total, &ok, &corrupted, &stale, &unverified]));
+    match ok {
+        _ if ok == total => ExitCode::Success,
+        0 => ExitCode::AllFailed,
+        _ => ExitCode::PartialFailure,
+    }
+}
+
+/// 先检查本地文件，完好时再按需检查源站
+async fn verify_entry(entry: &HistoryEntry, remote: bool, config: &Config) -> VerifyStatus {
+    let local = {
+        let entry = entry.clone();
+        tokio::task::spawn_blocking(move || verify::check_local(&entry))
+            .await
+            .unwrap_or_else(|e| VerifyStatus::Error(e.to_string()))
+    };
+    if !remote || !local.is_ok() {
+        return local;
+    }
+    let info = match request_settings(&entry.url, config) {
+        Ok((url_config, mut settings)) => {
+            get_file_info_with_fallback(&AwcTransport::new(&url_config), &entry.url, &mut settings).await
+        }
+        Err(e) => Err(e),
+    };
+    match info {
+        Ok(info) => verify::check_remote(entry, &info),
+        Err(e) => VerifyStatus::Error(e.to_string()),
+    }
+}
+
+/// `multidown secret set|check|delete <name>`
+fn secret(action: &SecretAction) -> ExitCode {
+    let result = match action {
+        SecretAction::Set { name } => read_secret_value(name)
+            .and_then(|value| secrets::set_secret(name, &value))
+            .map(|()| println!("{}", tf(Msg::SecretSaved, &[name]))),
+        SecretAction::Check { name } => secrets::get_secret(name)
+            .map(|_| println!("{}", tf(Msg::SecretFound, &[name]))),
+        SecretAction::Delete { name } => secrets::delete_secret(name)
+            .map(|()| println!("{}", tf(Msg::SecretDeleted, &[name]))),
+    };
+    match result {
+        Ok(()) => ExitCode::Success,
+        Err(e) => {
+            eprintln!("{}", tf(Msg::SecretFailed, &[&e]));
+            ExitCode::ConfigError
+        }
+    }
+}
+
+/// 读取密钥值：终端下不回显输入，否则从标准输入读取一行（便于管道传入）
+fn read_secret_value(name: &str) -> Result<String, DownloadError> {
+    let value = if std::io::stdin().is_terminal() {
+        rpassword::prompt_password(tf(Msg::SecretPrompt, &[&name]))
+    } else {
+        let mut line = String::new();
+        std::io::stdin().read_line(&mut line).map(|_| line)
+    }
+    .map_err(|e| DownloadError::io_error_with_context("读取标准输入", e))?;
+    let value = value.trim_end_matches(['\r', '\n']).to_string();
+    if value.is_empty() {
+        return Err(DownloadError::Unknown(Cow::Borrowed("密钥值不能为空")));
+    }
+    Ok(value)
+}
+
+/// `multidown config show|get|set|path`
+fn config(action: &ConfigAction, path: &str) -> ExitCode {
+    let result = match action {
+        ConfigAction::Path => {
+            println!("{}", path);
+            Ok(())
+        }
+        ConfigAction::Show => edit::read_config(path).and_then(|config| {
+            toml::to_string_pretty(&config)
+                .map(|text| print!("{}", text))
+                .map_err(|e| DownloadError::Unknown(e.to_string().into()))
+        }),
+        ConfigAction::Get { key } => edit::read_config(path)
+            .and_then(|config| edit::get_value(&config, key))
+            .map(|value| println!("{}", value)),
+        ConfigAction::Set { key, value } => edit::set_value(path, key, value)
+            .and_then(|config| edit::get_value(&config, key))
+            .map(|value| println!("{}", tf(Msg::ConfigSet, &[key, &value]))),
+    };
+    match result {
+        Ok(()) => ExitCode::Success,
+        Err(e) => {
+            eprintln!("{}", tf(Msg::ConfigFailed, &[&e]));
+            ExitCode::ConfigError
+        }
+    }
+}
+
+/// 连接当前会话目录中正在运行的守护进程，没有时返回 None
+fn daemon(config: &Config) -> Option<ipc::Client> {
+    ipc::Client::connect(&ipc::endpoint(Path::new(&config.state_dir))).ok()
+}
+
+/// 会话中带有 `tag` 标签（None 为全部）的任务：守护进程运行时向它查询实时状态，否则读取会话文件
+fn session_tasks(config: &Config, tag: Option<&str>) -> Vec<DownloadTaskMeta> {
+    let live = daemon(config).map(|mut client| {
+        client
+            .call(&ipc::Request::List { tag: tag.map(str::to_string) })
+            .and_then(|value| serde_json::from_value(value).map_err(|e| ipc::CallError::Io(e.into())))
+    });
+    let mut metas = match live {
+        Some(Ok(metas)) => return metas,
+        Some(Err(e)) => {
+            tracing::warn!(error = %e, "无法从守护进程获取任务，改为读取会话文件");
+            load_session(config.state_path(SESSION_FILE)).unwrap_or_default()
+        }
+        None => load_session(config.state_path(SESSION_FILE)).unwrap_or_default(),
+    };
+    metas.retain(|m| m.has_tag(tag));
+    metas
+}
+
+/// 按完整 ID 或前缀（至少 8 位）查找一个任务，找不到或不唯一时输出错误
+fn find_task(metas: &[DownloadTaskMeta], id: &str) -> Option<uuid::Uuid> {
+    match find_tasks(metas, id).as_slice() {
+        [meta] => Some(meta.id),
+        [] => {
+            eprintln!("{}", tf(Msg::TaskNotFound, &[&id]));
+            None
+        }
+        _ => {
+            eprintln!("{}", tf(Msg::TaskIdAmbiguous, &[&id]));
+            None
+        }
+    }
+}
+
+/// `multidown status [--tag <标签>]`：守护进程运行时显示实时状态，否则读取会话文件（运行中的会话会定期刷新该文件）
+fn status(json: bool, tag: Option<&str>, config: &Config) -> ExitCode {
+    let mut metas = session_tasks(config, tag);
+    metas.sort_by(|a, b| a.started_at.cmp(&b.started_at).then_with(|| a.file.cmp(&b.file)));
+
+    if json {
+        match serde_json::to_string_pretty(&metas) {
+            Ok(text) => println!("{}", text),
+            Err(e) => {
+                eprintln!("{}", e);
+                return ExitCode::AllFailed;
+            }
+        }
+        return ExitCode::Success;
+    }
+
+    if metas.is_empty() {
+        println!("{}", t(Msg::StatusEmpty));
+        return ExitCode::Success;
+    }
+    println!("{}", t(Msg::StatusHeader));
+    for meta in &metas {
+        let id = meta.id.to_string();
+        let speed = if meta.status == TaskStatus::Running {
+            format!("{}/s", human_size(meta.speed))
+        } else {
+            "-".to_string()
+        };
+        // 合并、校验阶段显示阶段名称和该阶段的进度
+        let (state, progress) = match (meta.phase, meta.phase_progress) {
+            (Some(phase), Some(progress)) => (phase.as_str(), progress),
+            _ => (meta.status.as_str(), meta.progress),
+        };
+        println!(
+            "{:<9} {:<10} {:>6.1}% {:>11} / {:<11} {:>12}  {}",
+            &id[..8],
+            state,
+            progress,
+            human_size(meta.downloaded),
+            human_size(meta.total),
+            speed,
+            meta.file,
+        );
+        if !meta.tags.is_empty() {
+            println!("          [{}]", meta.tags.join(", "));
+        }
+        if let TaskStatus::Failed(e) = &meta.status {
+            println!("          {}", e);
+        }
+    }
+
+    // 守护进程作为共享服务时，按提交任务的客户端分组统计
+    let mut clients: BTreeMap<&str, ClientStats> = BTreeMap::new();
+    for meta in &metas {
+        if let Some(client) = &meta.client {
+            clients.entry(client).or_default().add(meta);
+        }
+    }
+    if !clients.is_empty() {
+        println!();
+    }
+    for (client, stats) in &clients {
+        println!(
+            "{}",
+            tf(
+                Msg::StatusClient,
+                &[
+                    &client,
+                    &stats.total,
+                    &stats.running,
+                    &human_size(stats.downloaded_bytes),
+                    &human_size(stats.total_bytes),
+                    &human_size(stats.speed),
+                ]
+            )
+        );
+    }
+    ExitCode::Success
+}
+
+/// `multidown list [--status <状态>] [--sort <排序>] [--json]`：列出会话中的任务
+fn list(filter: &ListFilter, sort: SortKey, reverse: bool, json: bool, config: &Config) -> ExitCode {
+    let rows = task_list::select(&session_tasks(config, filter.tag.as_deref()), filter, sort, reverse);
+    if json {
+        match serde_json::to_string_pretty(&rows) {
+            Ok(text) => println!("{}", text),
+            Err(e) => {
+                eprintln!("{}", e);
+                return ExitCode::AllFailed;
+            }
+        }
+        return ExitCode::Success;
+    }
+    if rows.is_empty() {
+        println!("{}", t(Msg::ListEmpty));
+        return ExitCode::Success;
+    }
+    println!("{}", task_list::format_table(&rows));
+    ExitCode::Success
+}
+
+/// `multidown pause <id>... | --tag <标签>`：让守护进程暂停任务，之后可以在网页控制台或通过控制通道继续
+fn pause(ids: &[String], tag: Option<&str>, config: &Config) -> ExitCode {
+    let Some(mut client) = daemon(config) else {
+        eprintln!("{}", t(Msg::PauseNeedsDaemon));
+        return ExitCode::AllFailed;
+    };
+    if let Some(tag) = tag {
+        return match client.call(&ipc::Request::PauseTagged { tag: tag.to_string() }) {
+            Ok(result) => {
+                let paused = result["task_ids"].as_array().cloned().unwrap_or_default();
+                if paused.is_empty() {
+                    println!("{}", t(Msg::ListEmpty));
+                }
+                for id in paused.iter().filter_map(|id| id.as_str()) {
+                    println!("{}", tf(Msg::TaskPaused, &[&id.get(..8).unwrap_or(id)]));
+                }
+                ExitCode::Success
+            }
+            Err(e) => {
+                eprintln!("{}", tf(Msg::DaemonRequestFailed, &[&e]));
+                ExitCode::AllFailed
+            }
+        };
+    }
+    let metas = session_tasks(config, None);
+    let mut failed = 0;
+    for id in ids {
+        let Some(task_id) = find_task(&metas, id) else {
+            failed += 1;
+            continue;
+        };
+        match client.call(&ipc::Request::Pause { task_id: task_id.to_string() }) {
+            Ok(_) => println!("{}", tf(Msg::TaskPaused, &[&&task_id.to_string()[..8]])),
+            Err(e) => {
+                eprintln!("{}", tf(Msg::DaemonRequestFailed, &[&e]));
+                failed += 1;
+            }
+        }
+    }
+    failure_code(failed, ids.len())
+}
+
+/// `multidown remove <id>... [--with-data]`：从会话中删除任务和它的临时数据
+///
+/// 守护进程运行时交给它删除（下载中的任务先被取消）；否则直接修改会话文件，
+/// 另一个进程正在下载时会话被锁住，不能删除。
+fn remove(ids: &[String], with_data: bool, config: &Config) -> ExitCode {
+    if let Some(client) = daemon(config) {
+        return remove_via_daemon(client, ids, with_data, config);
+    }
+    let _lock = match lock_session(config) {
+        Ok(lock) => lock,
+        Err(code) => return code,
+    };
+    let mut metas = load_session(config.state_path(SESSION_FILE)).unwrap_or_default();
+    let mut failed = 0;
+    for id in ids {
+        let Some(task_id) = find_task(&metas, id) else {
+            failed += 1;
+            continue;
+        };
+        let Some(index) = metas.iter().position(|m| m.id == task_id) else { continue };
+        let meta = metas.remove(index);
+        let removed = purge_task_data(config, &meta, with_data);
+        println!("{}", tf(Msg::TaskRemoved, &[&&meta.id.to_string()[..8], &meta.file, &removed.len()]));
+    }
+    if failed < ids.len() {
+        if let Err(e) = save_session(config.state_path(SESSION_FILE), &metas) {
+            eprintln!("{}", tf(Msg::SessionSaveFailed, &[&e]));
+            return ExitCode::AllFailed;
+        }
+    }
+    failure_code(failed, ids.len())
+}
+
+fn failure_code(failed: usize, total: usize) -> ExitCode {
+    match failed {
+        0 => ExitCode::Success,
+        n if n == total => ExitCode::AllFailed,
+        _ => ExitCode::PartialFailure,
+    }
+}
+
+fn remove_via_daemon(mut client: ipc::Client, ids: &[String], with_data: bool, config: &Config) -> ExitCode {
+    let metas = session_tasks(config, None);
+    let mut failed = 0;
+    for id in ids {
+        let Some(task_id) = find_task(&metas, id) else {
+            failed += 1;
+            continue;
+        };
+        match client.call(&ipc::Request::Remove { task_id: task_id.to_string(), with_data }) {
+            Ok(result) => {
+                let file = result["file"].as_str().unwrap_or_default();
+                println!("{}", tf(Msg::TaskRemovedByDaemon, &[&&task_id.to_string()[..8], &file]));
+            }
+            Err(e) => {
+                eprintln!("{}", tf(Msg::DaemonRequestFailed, &[&e]));
+                failed += 1;
+            }
+        }
+    }
+    failure_code(failed, ids.len())
+}
+
+/// 离线修改会话前加锁，另一个进程正在下载时返回 `SessionLocked`
+fn lock_session(config: &Config) -> Result<SessionLock, ExitCode> {
+    let path = config.state_path(LOCK_FILE);
+    SessionLock::acquire(&path).map_err(|e| {
+        match e {
+            LockError::Held(owner) => {
+                let pid = owner.map_or_else(|| "?".to_string(), |o| o.pid.to_string());
+                eprintln!("{}", tf(Msg::SessionLocked, &[&pid, &path.display()]));
+            }
+            LockError::Io(e) => eprintln!("{}", tf(Msg::SessionLockFailed, &[&e])),
+        }
+        ExitCode::SessionLocked
+    })
+}
+
+/// `multidown move <任务ID> <新路径> [--temp-dir <目录>]`：修改未完成任务的保存路径，
+/// 已下载的部分文件、分块和断点续传信息一起移动，下次恢复会话时在新位置继续下载；
+/// 守护进程运行时交给它移动，下载中的任务先暂停，移动后重新排队
+fn move_task(id: &str, path: &str, temp_dir: Option<&str>, config: &Config) -> ExitCode {
+    if let Some(client) = daemon(config) {
+        return move_via_daemon(client, id, path, temp_dir, config);
+    }
+    let _lock = match lock_session(config) {
+        Ok(lock) => lock,
+        Err(code) => return code,
+    };
+    let mut metas = load_session(config.state_path(SESSION_FILE)).unwrap_or_default();
+    let Some(task_id) = find_task(&metas, id) else { return ExitCode::AllFailed };
+    let Some(meta) = metas.iter_mut().find(|m| m.id == task_id) else { return ExitCode::AllFailed };
+    if matches!(meta.status, TaskStatus::Completed | TaskStatus::Cancelled) {
+        eprintln!("{}", tf(Msg::TaskFinishedNoMove, &[&id]));
+        return ExitCode::AllFailed;
+    }
+    let mut task_config = config.for_url(&meta.url);
+    meta.options.apply(&mut task_config);
+    let temp_from = task_config.temp_dir_path();
+    if let Some(dir) = temp_dir {
+        task_config.temp_dir = dir.to_string();
+    }
+    let moved = relocate::target_path(&task_config.download_dir, path).and_then(|to| {
+        let relocation = Relocation { task_id, from: meta.file.clone(), to, temp_from, temp_to: task_config.temp_dir_path() };
+        relocation.run().map(|()| relocation.to)
+    });
+    match moved {
+        Ok(to) => {
+            println!("{}", tf(Msg::TaskMoved, &[&&task_id.to_string()[..8], &meta.file, &to]));
+            meta.file = to;
+            if let Some(dir) = temp_dir {
+                meta.options.temp_dir = Some(dir.to_string());
+            }
+        }
+        Err(e) => {
+            eprintln!("{}", tf(Msg::TaskMoveFailed, &[&id, &e]));
+            return ExitCode::AllFailed;
+        }
+    }
+    match save_session(config.state_path(SESSION_FILE), &metas) {
+        Ok(()) => ExitCode::Success,
+        Err(e) => {
+            eprintln!("{}", tf(Msg::SessionSaveFailed, &[&e]));
+            ExitCode::AllFailed
+        }
+    }
+}
+
+fn move_via_daemon(mut client: ipc::Client, id: &str, path: &str, temp_dir: Option<&str>, config: &Config) -> ExitCode {
+    let metas = session_tasks(config, None);
+    let Some(task_id) = find_task(&metas, id) else { return ExitCode::AllFailed };
+    if metas.iter().any(|m| m.id == task_id && matches!(m.status, TaskStatus::Completed | TaskStatus::Cancelled)) {
+        eprintln!("{}", tf(Msg::TaskFinishedNoMove, &[&id]));
+        return ExitCode::AllFailed;
+    }
+    let request =
+        ipc::Request::Move { task_id: task_id.to_string(), path: path.to_string(), temp_dir: temp_dir.map(str::to_string) };
+    match client.call(&request) {
+        Ok(result) => {
+            let (from, to) = (result["from"].as_str().unwrap_or_default(), result["path"].as_str().unwrap_or_default());
+            println!("{}", tf(Msg::TaskMoved, &[&&task_id.to_string()[..8], &from, &to]));
+            ExitCode::Success
+        }
+        Err(e) => {
+            eprintln!("{}", tf(Msg::TaskMoveFailed, &[&id, &e]));
+            ExitCode::AllFailed
+        }
+    }
+}
+
+/// `multidown export-queue <path> [--tag <标签>]`：导出会话中未完成的任务
+fn export_queue(path: &str, tag: Option<&str>, config: &Config) -> ExitCode {
+    let queue = queue::export(&load_session(config.state_path(SESSION_FILE)).unwrap_or_default(), &config.download_dir, tag);
+    match queue::write(path, &queue) {
+        Ok(()) => {
+            println!("{}", tf(Msg::QueueExported, &[&queue.tasks.len(), &path]));
+            ExitCode::Success
+        }
+        Err(e) => {
+            eprintln!("{}", tf(Msg::QueueExportFailed, &[&e]));
+            ExitCode::AllFailed
+        }
+    }
+}
+
+/// `multidown stats [--since 7d]`
+fn stats(since: &str, config: &Config) -> ExitCode {
+    let since = match usage::parse_since(since, chrono::Local::now().date_naive()) {
+        Ok(date) => date,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::ConfigError;
+        }
+    };
+    let summary = UsageStore::new(config.state_path(USAGE_FILE)).summary(since);
+    if summary.total == 0 {
+        println!("{}", tf(Msg::UsageEmpty, &[&since]));
+        return ExitCode::Success;
+    }
+
+    println!("{}", tf(Msg::UsageHeader, &[&since]));
+    println!("{}", t(Msg::UsageByHost));
+    let mut hosts: Vec<_> = summary.by_host.iter().collect();
+    hosts.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    for (host, bytes) in hosts {
+        println!("  {:<40} {:>10}", host, human_size(*bytes));
+    }
+    println!("{}", t(Msg::UsageByDay));
+    for (day, bytes) in &summary.by_day {
+        println!("  {:<40} {:>10}", day.to_string(), human_size(*bytes));
+    }
+    println!("{}", tf(Msg::UsageTotal, &[&human_size(summary.total)]));
+    ExitCode::Success
+}
+
+/// `multidown history [--search <keyword>]`
+fn history(search: Option<&str>, limit: usize, config: &Config) -> ExitCode {
+    let store = HistoryStore::new(config.state_path(HISTORY_FILE));
+    let entries = match search {
+        Some(keyword) => store.search(keyword),
+        None => store.load(),
+    };
+    if entries.is_empty() {
+        println!("{}", t(Msg::HistoryEmpty));
+        return ExitCode::Success;
+    }
+    for entry in entries.iter().rev().take(limit) {
+        println!(
+            "{}  {:<9}  {:>10}  {:>7.1}s  {} -> {}",
+            entry.timestamp.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S"),
+            entry.status,
+            human_size(entry.size),
+            entry.duration_secs,
+            entry.url,
+            entry.path,
+        );
+        if let Some(checksum) = &entry.checksum {
+            println!("    sha256: {}", checksum);
+        }
+        if let Some(error) = &entry.error {
+            println!("    {}", error);
+        }
+    }
+    ExitCode::Success
+}
@@ -0,0 +1,122 @@
+//! 目标文件已存在时的处理：`--on-conflict ask|overwrite|rename|resume|skip|fail`
+//!
+//! 没有指定时，在终端中对每个冲突询问一次，回答大写字母时应用到之后所有的冲突；
+//! 不在终端中运行（脚本、管道）或使用 `-q` 时不询问，沿用原来的行为：任务以“文件已存在”失败，
+//! 启用了 `--continue`（`continue_partial`）时从已有文件的末尾续传。
+
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use crate::i18n::{t, tf, Msg};
+
+/// 冲突处理策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ConflictPolicy {
+    /// 逐个询问
+    Ask,
+    /// 删除已有的文件后重新下载
+    Overwrite,
+    /// 另存为 `name (1).ext`
+    Rename,
+    /// 从已有文件的末尾续传
+    Resume,
+    /// 跳过这个 URL
+    Skip,
+    /// 任务以“文件已存在”失败
+    Fail,
+}
+
+/// 在创建任务前决定如何处理已存在的目标文件
+#[derive(Debug)]
+pub struct ConflictResolver {
+    policy: ConflictPolicy,
+}
+
+impl ConflictResolver {
+    /// `policy` 为命令行指定的策略；没有指定时在终端中询问，否则按 `continue_partial` 续传或失败
+    pub fn new(policy: Option<ConflictPolicy>, continue_partial: bool, interactive: bool) -> Self {
+        let policy = policy.unwrap_or(match (interactive, continue_partial) {
+            (_, true) => ConflictPolicy::Resume,
+            (true, false) => ConflictPolicy::Ask,
+            (false, false) => ConflictPolicy::Fail,
+        });
+        Self { policy }
+    }
+
+    /// 决定如何处理已存在的 `path`，返回的策略不会是 `Ask`；需要询问时从 `input` 读取回答
+    pub fn resolve(&mut self, path: &Path, input: &mut impl BufRead, output: &mut impl Write) -> ConflictPolicy {
+        while self.policy == ConflictPolicy::Ask {
+            let _ = write!(output, "{}", tf(Msg::ConflictPrompt, &[&path.display()]));
+            let _ = output.flush();
+            let mut line = String::new();
+            // 输入已关闭时不再询问
+            if input.read_line(&mut line).unwrap_or(0) == 0 {
+                self.policy = ConflictPolicy::Fail;
+                break;
+            }
+            let answer = line.trim();
+            let choice = match answer.to_ascii_lowercase().as_str() {
+                "o" => ConflictPolicy::Overwrite,
+                "r" => ConflictPolicy::Rename,
+                "c" => ConflictPolicy::Resume,
+                "s" | "" => ConflictPolicy::Skip,
+                _ => {
+                    let _ = writeln!(output, "{}", t(Msg::ConflictInvalidChoice));
+                    continue;
+                }
+            };
+            if answer.chars().any(|c| c.is_ascii_uppercase()) {
+                self.policy = choice;
+            }
+            return choice;
+        }
+        self.policy
+    }
+}
+
+/// 不与已有文件重名的路径：`a.zip` → `a (1).zip`、`a (2).zip` ...
+pub fn renamed_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let ext = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    (1..)
+        .map(|n| path.with_file_name(format!("{} ({}){}", stem, n, ext)))
+        .find(|candidate| !candidate.exists())
+        .expect("总能找到不存在的文件名")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_and_apply_to_all() {
+        let path = Path::new("a.zip");
+        let mut output = Vec::new();
+        let mut resolver = ConflictResolver::new(None, false, true);
+        // 无效的回答重新询问；小写只处理这一个冲突
+        let mut input = "x\nr\n".as_bytes();
+        assert_eq!(resolver.resolve(path, &mut input, &mut output), ConflictPolicy::Rename);
+        let mut input = "\n".as_bytes();
+        assert_eq!(resolver.resolve(path, &mut input, &mut output), ConflictPolicy::Skip);
+        // 大写应用到之后所有冲突，不再询问
+        let mut input = "O\n".as_bytes();
+        assert_eq!(resolver.resolve(path, &mut input, &mut output), ConflictPolicy::Overwrite);
+        assert_eq!(resolver.resolve(path, &mut "".as_bytes(), &mut output), ConflictPolicy::Overwrite);
+
+        // 非交互时的默认行为；输入关闭时不再询问
+        assert_eq!(ConflictResolver::new(None, false, false).resolve(path, &mut "".as_bytes(), &mut output), ConflictPolicy::Fail);
+        assert_eq!(ConflictResolver::new(None, true, true).resolve(path, &mut "".as_bytes(), &mut output), ConflictPolicy::Resume);
+        assert_eq!(ConflictResolver::new(None, false, true).resolve(path, &mut "".as_bytes(), &mut output), ConflictPolicy::Fail);
+    }
+
+    #[test]
+    fn test_renamed_path() {
+        let dir = std::env::temp_dir().join(format!("multidown_conflict_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.tar.gz"), b"").unwrap();
+        std::fs::write(dir.join("a.tar (1).gz"), b"").unwrap();
+        assert_eq!(renamed_path(&dir.join("a.tar.gz")), dir.join("a.tar (2).gz"));
+        assert_eq!(renamed_path(&dir.join("README")), dir.join("README (1)"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,120 @@
+//! 进程退出码定义
+//!
+//! | 退出码 | 含义 |
+//! |--------|------|
+//! | 0 | 所有任务下载成功 |
+//! | 1 | 部分任务失败 |
+//! | 2 | 参数或配置错误（与 clap 的用法错误一致） |
+//! | 3 | 所有任务均失败 |
+//! | 4 | 所有任务均因网络错误（连接失败、超时）失败 |
+//! | 5 | 另一个 multidown 进程正在使用当前会话 |
+//! | 6 | 所有任务均因服务器返回错误状态码（4xx/5xx）失败 |
+//! | 130 | 被 SIGINT/SIGTERM（或 Ctrl+C）中断，任务已暂停，重新运行即可继续 |
+
+use crate::core::actor_manager::DownloadTaskMeta;
+use crate::core::error::DownloadError;
+use crate::core::task::TaskStatus;
+
+/// 进程退出码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    Success = 0,
+    PartialFailure = 1,
+    ConfigError = 2,
+    AllFailed = 3,
+    NetworkError = 4,
+    SessionLocked = 5,
+    HttpError = 6,
+    Interrupted = 130,
+}
+
+impl ExitCode {
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    /// 根据本次运行的任务结果计算退出码
+    ///
+    /// 未完成（暂停、取消、仍在运行）的任务同样视为失败。
+    pub fn from_tasks(metas: &[DownloadTaskMeta]) -> Self {
+        if metas.is_empty() {
+            return ExitCode::AllFailed;
+        }
+        let succeeded = metas.iter().filter(|m| m.status == TaskStatus::Completed).count();
+        if succeeded == metas.len() {
+            return ExitCode::Success;
+        }
+        if succeeded > 0 {
+            return ExitCode::PartialFailure;
+        }
+        let all_network = metas.iter().all(|m| {
+            matches!(m.error_kind.as_deref(), Some("network") | Some("timeout"))
+        });
+        if all_network {
+            ExitCode::NetworkError
+        } else if metas.iter().all(|m| m.error_kind.as_deref() == Some("server")) {
+            ExitCode::HttpError
+        } else {
+            ExitCode::AllFailed
+        }
+    }
+
+    /// 单个操作失败时的退出码
+    pub fn from_error(error: &DownloadError) -> Self {
+        if error.is_network() {
+            ExitCode::NetworkError
+        } else if error.kind() == "server" {
+            ExitCode::HttpError
+        } else {
+            ExitCode::AllFailed
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn meta(status: TaskStatus, error_kind: Option<&str>) -> DownloadTaskMeta {
+        DownloadTaskMeta {
+            id: Uuid::new_v4(),
+            url: "https://example.com/file.zip".to_string(),
+            file: "file.zip".to_string(),
+            status,
+            progress: 0.0,
+            downloaded: 0,
+            total: 0,
+            speed: 0,
+            started_at: None,
+            finished_at: None,
+            retries: 0,
+            error_kind: error_kind.map(str::to_string),
+            error_code: None,
+            http_status: None,
+            options: Default::default(),
+            metrics: None,
+            tags: Vec::new(),
+            phase: None,
+            phase_progress: None,
+            client: None,
+        }
+    }
+
+    #[test]
+    fn test_exit_code_from_tasks() {
+        let ok = meta(TaskStatus::Completed, None);
+        let net = meta(TaskStatus::Failed("网络错误".into()), Some("network"));
+        let io = meta(TaskStatus::Failed("IO错误".into()), Some("io"));
+
+        assert_eq!(ExitCode::from_tasks(std::slice::from_ref(&ok)), ExitCode::Success);
+        assert_eq!(ExitCode::from_tasks(&[ok, io.clone()]), ExitCode::PartialFailure);
+        assert_eq!(ExitCode::from_tasks(&[net.clone(), io]), ExitCode::AllFailed);
+        assert_eq!(ExitCode::from_tasks(&[net]), ExitCode::NetworkError);
+        let http = meta(TaskStatus::Failed("服务器错误: HTTP 404".into()), Some("server"));
+        assert_eq!(ExitCode::from_tasks(&[http.clone(), http]), ExitCode::HttpError);
+        assert_eq!(ExitCode::from_error(&DownloadError::http_status(503, "http://example.com/")), ExitCode::HttpError);
+        assert_eq!(ExitCode::from_error(&DownloadError::Timeout), ExitCode::NetworkError);
+        assert_eq!(ExitCode::from_tasks(&[]), ExitCode::AllFailed);
+    }
+}
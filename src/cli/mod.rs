@@ -11,10 +11,41 @@
 //! ## 支持的命令
 //! 
 //! - 基本下载：`multidown <url>`
-//! - 批量下载：`multidown -f urls.txt`
+//! - 批量下载：`multidown -f urls.txt`，每行可以附加选项：`url | out=name | sha256=... | dir=sub/`
 //! - 编辑配置：`multidown -e`
 //! - 指定配置：`multidown -c config.conf <url>`
-//! - 速度限制：`multidown -l 1024 <url>`
+//! - 严格配置：`multidown --strict-config <url>`
+//! - 速度限制：`multidown -l 1024 <url>`、`multidown --limit 2M <url>`
+//! - 静默/无进度输出：`multidown -q <url>`、`multidown --no-progress <url>`
+//! - 导出报告：`multidown --report report.json <url>`
+//! - 英文界面：`multidown --lang en <url>`
+//! - JSON 日志：`multidown --log-format json <url>`
+//! - HTTP 调试：`multidown --trace-http <url>`
+//! - 完成后处理：`multidown --on-complete 'unzip {path}' <url>`
+//! - 下载历史：`multidown history --search example.com`
+//! - 校验文件：`multidown verify downloads/file.zip --remote`
+//! - 重试失败任务：`multidown retry-failed`
+//! - 任务标签：`multidown --tag nightly -f urls.txt`、`multidown status --tag nightly`、`multidown retry-failed --tag nightly`
+//! - 迁移任务队列：`multidown export-queue queue.json`、`multidown import-queue queue.json`
+//! - 会话状态：`multidown status`
+//! - 删除任务：`multidown remove 1a2b3c4d --with-data`
+//! - 移动任务：`multidown move 1a2b3c4d /mnt/disk2/file.iso --temp-dir /mnt/disk2/.tmp`
+//! - 列出任务：`multidown list --status failed --sort size`、`multidown list --json`
+//! - 流量统计：`multidown stats --since 7d`
+//! - 跳过已下载：`multidown --no-redownload -f urls.txt`
+//! - 恢复时校验：`multidown --verify-resume <url>`
+//! - 续传已有文件：`multidown --continue <url>`
+//! - 文件冲突：`multidown --on-conflict rename -f urls.txt`
+//! - 边下边播：`multidown --stream-order <url>`
+//! - 管道输出：`multidown -O - <url> | tar x`
+//! - 部分下载：`multidown --range 100M-200M <url>`
+//! - 时间限制：`multidown --timeout-total 30m <url>`
+//! - 并发预设：`multidown --fast <url>`、`multidown --polite -f urls.txt`
+//! - 大小限制：`multidown --max-file-size 2G --quota 50G -f urls.txt`
+//! - 设置来源页：`multidown --referer https://example.com/page <url>`、`multidown --referer auto <url>`
+//! - 只检查不下载：`multidown --check-only -f urls.txt`、`multidown --check-only --json -f urls.txt`
+//! - 密钥管理：`multidown secret set example-token`
+//! - 脚本化配置：`multidown config get thread_count`、`multidown config set thread_count 8`
 //! 
 //! ## 平台支持
 //! 
@@ -22,11 +53,23 @@
 //! - macOS: `~/Library/Application Support/multidown/multidown.conf`
 //! - Linux: `~/.config/multidown/multidown.conf`
 
-use clap::Parser;
+pub mod commands;
+pub mod conflict;
+pub mod exit_code;
+pub mod url_list;
+
+use clap::{Parser, Subcommand};
 use std::fs;
-use crate::config::Config;
+use crate::config::{Config, Preset};
+use crate::i18n::{self, Lang};
+use crate::utils::logger::LogFormat;
+use crate::utils::validator;
+use url_list::UrlEntry;
+use conflict::ConflictPolicy;
+use crate::ui::task_list::{SortKey, STATUS_NAMES};
 use actix::prelude::*;
 use crate::core::error::DownloadError;
+use crate::core::task::{ByteRange, TaskOptions};
 use std::path::Path;
 use std::env;
 use std::borrow::Cow;
@@ -106,42 +149,476 @@ pub struct Args {
     #[arg(short = 'c', long, default_value_t = default_config_path(), help = "配置文件路径，默认为平台推荐路径。")]
     pub config: String,
 
+    /// 配置文件格式错误时拒绝启动
+    #[arg(long = "strict-config", help = "配置文件格式错误时报告出错的行和字段并拒绝启动，而不是备份后用默认配置重新生成。")]
+    pub strict_config: bool,
+
     /// 编辑配置文件（-e 或 --edit）
     #[arg(short = 'e', long = "edit", help = "用系统默认编辑器打开配置文件并退出。")]
     pub edit_config: bool,
 
     /// 下载速度限制（KB/s），0 表示不限速
-    #[arg(long, short = 'l', help = "下载速度限制（KB/s），0 表示不限速。")]
+    #[arg(long, short = 'l', visible_alias = "limit", value_parser = crate::utils::size::parse_rate_kb, help = "下载速度限制，不带单位时为 KB/s，也可以写成 2M、512K/s 等，0 表示不限速。")]
     pub speed_limit_kb: Option<u64>,
 
+    /// 所有任务合计的速度限制（KB/s）
+    #[arg(long = "total-limit", value_name = "RATE", value_parser = crate::utils::size::parse_rate_kb, help = "所有任务合计的速度限制，单位同 --limit；按任务的权重分配（URL 列表中用 weight= 指定），没用完的份额分给其他任务。")]
+    pub total_speed_limit_kb: Option<u64>,
+
     /// 指定下载目录（默认：当前工作目录）
     #[arg(long, short = 'd', default_value_t = get_default_download_dir(), help = "指定下载目录，覆盖配置文件中的设置，默认当前工作目录。")]
     pub download_dir: String,
 
+    /// 临时文件目录
+    #[arg(long = "temp-dir", value_name = "DIR", help = "分块临时文件和断点续传信息的存放目录，默认是下载目录下的 .multidown。")]
+    pub temp_dir: Option<String>,
+
     /// 指定下载文件名
     #[arg(long, short = 'n', help = "指定下载文件名，覆盖URL自动推断。")]
     pub file_name: Option<String>,
 
+    /// 按 URL 的主机和路径保存
+    #[arg(long = "preserve-path", help = "按 URL 的主机和路径保存，如 https://host/a/b/c.zip 保存为 <下载目录>/host/a/b/c.zip，自动创建目录；批量下载同名文件时使用。指定 --file-name 时以它为准。")]
+    pub preserve_path: bool,
+
+    /// 高速预设
+    #[arg(long, conflicts_with = "polite", help = "高速预设：适合带宽充足、服务器允许多连接的情况，16 个连接、大分块、不限速并自动增加连接数。-t、-l 等参数依然优先。")]
+    pub fast: bool,
+
+    /// 礼貌预设
+    #[arg(long, help = "礼貌预设：适合公共镜像和小站点，每个任务 2 个连接、同时只下载一个文件、限速 2 MB/s 并放慢重试。-t、-l 等参数依然优先。")]
+    pub polite: bool,
+
+    /// 小文件模式
+    #[arg(long = "small-files", conflicts_with = "polite", help = "小文件模式：适合一次下载成千上万个小文件，不探测文件信息、不分块，直接用 GET 请求并复用 keep-alive 连接，同时下载 small_files_concurrency（默认 32）个文件。")]
+    pub small_files: bool,
+
     /// 指定下载线程数
     #[arg(long, short = 't', help = "指定下载线程数，覆盖配置文件中的设置。")]
     pub thread_count: Option<usize>,
 
+    /// 静默模式，只输出错误信息
+    #[arg(long, short = 'q', help = "静默模式：不显示进度和提示信息，只输出错误。")]
+    pub quiet: bool,
+
+    /// 不显示进度
+    #[arg(long = "no-progress", help = "不显示下载进度（非终端环境下会自动改为定期输出纯文本进度行）。")]
+    pub no_progress: bool,
+
+    /// 下载结束后导出汇总报告
+    #[arg(long, value_name = "PATH", help = "下载结束后导出汇总报告，按扩展名选择格式（.json 或 .csv）。")]
+    pub report: Option<String>,
+
+    /// 任务完成或失败时发送桌面通知
+    #[arg(long, help = "任务完成或失败时发送桌面通知，覆盖配置文件中的 notify_on_finish。")]
+    pub notify: bool,
+
+    /// 任务成功后执行的命令
+    #[arg(long = "on-complete", value_name = "CMD", help = "任务成功后执行的命令，支持 {path} {url} {status} {error} 变量，例如 'unzip {path}'。")]
+    pub on_complete: Option<String>,
+
+    /// 任务失败后执行的命令
+    #[arg(long = "on-failure", value_name = "CMD", help = "任务失败后执行的命令，支持的变量同 --on-complete。")]
+    pub on_failure: Option<String>,
+
+    /// 跳过历史记录中已成功下载的 URL
+    #[arg(long = "no-redownload", help = "跳过下载历史中已成功下载过的 URL。")]
+    pub no_redownload: bool,
+
+    /// 输出位置，`-` 表示标准输出
+    #[arg(short = 'O', long, value_name = "-", value_parser = parse_output, help = "-O - 把文件按顺序单连接写到标准输出，可以直接接 tar x、ffmpeg 等，进度和提示输出到标准错误；只能下载一个 URL。")]
+    pub output: Option<String>,
+
+    /// 恢复时校验已下载的块
+    #[arg(long = "verify-resume", help = "恢复分块下载前重新计算已完成块的 CRC32，与下载时记录的不一致（上次崩溃时写坏）的块重新下载。")]
+    pub verify_resume: bool,
+
+    /// 目标文件已存在时的处理
+    #[arg(long = "on-conflict", value_enum, value_name = "POLICY", help = "目标文件已存在时的处理：ask（逐个询问）、overwrite（覆盖）、rename（另存为 name (1).ext）、resume（续传）、skip（跳过）、fail（任务失败）。不指定时在终端中询问，非交互运行时任务失败（指定了 --continue 时续传）。")]
+    pub on_conflict: Option<ConflictPolicy>,
+
+    /// 续传已有的部分文件
+    #[arg(long = "continue", help = "目标文件已存在且小于服务器上的文件时，从它的末尾续传（同 wget -c），而不是报“文件已存在”；服务器上的文件更新过时拒绝续传。")]
+    pub continue_partial: bool,
+
+    /// 按文件顺序下载
+    #[arg(long = "stream-order", help = "分块大致按文件顺序下载，已下载完成的开头部分写入 <文件名>.part，视频可以边下边播；并行效率略有下降。")]
+    pub stream_order: bool,
+
+    /// 任务标签
+    #[arg(long = "tag", value_name = "TAG", value_parser = parse_tag, help = "给本次创建的任务加上标签（可以指定多次），之后可以用 status、retry-failed、export-queue 的 --tag 只处理这些任务。")]
+    pub tags: Vec<String>,
+
+    /// 只下载文件的一部分
+    #[arg(long, value_name = "START-END", value_parser = ByteRange::parse, help = "只下载文件的一部分，如 100M-200M（包含起点、不包含终点）或 1G-（到文件末尾），仍然分块并行下载，需要服务器支持 Range 请求。")]
+    pub range: Option<ByteRange>,
+
+    /// 单个任务的总时间限制
+    #[arg(long = "timeout-total", value_name = "DURATION", value_parser = crate::utils::size::parse_duration_secs, help = "单个任务的总时间限制，如 30m、1h30m，超过时任务失败，适合不能无限等待的 CI 任务。")]
+    pub timeout_total: Option<u64>,
+
+    /// 单个文件的大小上限
+    #[arg(long = "max-file-size", value_name = "SIZE", value_parser = crate::utils::size::parse_size, help = "单个文件的大小上限，如 2G、500M，超过的任务在下载前失败，大小未知时下载到上限即中止。")]
+    pub max_file_size: Option<u64>,
+
+    /// 本次运行的下载总量上限
+    #[arg(long, value_name = "SIZE", value_parser = crate::utils::size::parse_size, help = "本次运行下载总量的上限，如 50G，超出配额的任务失败。")]
+    pub quota: Option<u64>,
+
+    /// 只检查 URL，不下载
+    #[arg(long = "check-only", help = "只对每个 URL 发送 HEAD 请求，报告状态码、大小、是否支持 Range 和重定向后的最终地址，不下载。")]
+    pub check_only: bool,
+
+    /// 以 JSON 输出检查结果
+    #[arg(long, requires = "check_only", help = "与 --check-only 一起使用，以 JSON 格式输出检查结果。")]
+    pub json: bool,
+
+    /// Referer 请求头
+    #[arg(long, value_name = "URL|auto", value_parser = parse_referer, help = "请求时带上的 Referer（很多文件站没有它会返回 403）；auto 表示链接所在的页面，来源未知时取文件所在的目录。")]
+    pub referer: Option<Referer>,
+
+    /// 日志格式
+    #[arg(long = "log-format", value_enum, default_value_t = LogFormat::Text, help = "日志文件格式：text 或 json（每行一个 JSON 对象，包含 task_id、url、chunk_index 等 span 字段）。")]
+    pub log_format: LogFormat,
+
+    /// 记录 HTTP 请求细节
+    #[arg(long = "trace-http", help = "在日志中记录每个请求的请求头/响应头（凭据已隐藏）、重定向链和 TLS 握手信息，用于排查服务器问题。")]
+    pub trace_http: bool,
+
+    /// 界面语言
+    #[arg(long, value_enum, help = "界面语言（zh 或 en），默认根据 LANG 等环境变量检测。")]
+    pub lang: Option<Lang>,
+
+    /// 子命令
+    #[command(subcommand)]
+    pub command: Option<Subcommands>,
+}
+
+/// 子命令：`Command` 执行后直接退出，`DownloadCommand` 创建任务后进入下载流程
+#[derive(Subcommand, Debug, Clone)]
+pub enum Subcommands {
+    #[command(flatten)]
+    Command(Command),
+    #[command(flatten)]
+    Download(DownloadCommand),
+}
+
+/// 执行后直接退出、不进入下载流程的子命令
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// 查询下载历史
+    History {
+        /// 按 URL 或保存路径中的关键字过滤
+        #[arg(long, short = 's', help = "按 URL 或保存路径中的关键字过滤（不区分大小写）。")]
+        search: Option<String>,
+
+        /// 最多显示的条数（最新的在前）
+        #[arg(long, short = 'n', default_value_t = 20, help = "最多显示的条数，最新的在前。")]
+        limit: usize,
+    },
+    /// 显示当前（或上次中断的）会话中的任务状态
+    Status {
+        /// 以 JSON 输出
+        #[arg(long, help = "以 JSON 格式输出任务元数据。")]
+        json: bool,
+
+        /// 只显示带有该标签的任务
+        #[arg(long, help = "只显示带有该标签的任务。")]
+        tag: Option<String>,
+    },
+    /// 列出会话中的任务，可以按状态、标签、关键字筛选并排序
+    List {
+        /// 只列出这些状态的任务
+        #[arg(long, value_name = "STATUS", value_delimiter = ',', value_parser = parse_list_status, help = "只列出这些状态的任务，可以用逗号分隔多个：pending、queued、running、paused、completed、failed、cancelled，active 表示所有未结束的任务。")]
+        status: Vec<String>,
+
+        /// 排序方式
+        #[arg(long, value_enum, default_value_t = SortKey::Added, help = "排序方式：added（开始时间）、size、progress、speed（从大到小）、status、file、url。")]
+        sort: SortKey,
+
+        /// 倒序
+        #[arg(long, short = 'r', help = "反转排序顺序。")]
+        reverse: bool,
+
+        /// 只列出带有该标签的任务
+        #[arg(long, help = "只列出带有该标签的任务。")]
+        tag: Option<String>,
+
+        /// 按 URL 或保存路径中的关键字过滤
+        #[arg(long, short = 's', help = "按 URL 或保存路径中的关键字过滤（不区分大小写）。")]
+        search: Option<String>,
+
+        /// 以 JSON 输出
+        #[arg(long, help = "以 JSON 数组输出，每个任务包含 id、url、file、status、progress、downloaded、total、speed、error 和 tags。")]
+        json: bool,
+    },
+    /// 暂停守护进程中的任务，已下载的数据保留
+    Pause {
+        /// 任务 ID（可以只写前 8 位），可以指定多个
+        #[arg(required_unless_present = "tag", help = "任务 ID，可以只写前 8 位（见 multidown list），可以指定多个。")]
+        ids: Vec<String>,
+
+        /// 暂停带有该标签的所有任务
+        #[arg(long, conflicts_with = "ids", help = "暂停带有该标签且尚未结束的所有任务。")]
+        tag: Option<String>,
+    },
+    /// 从会话中删除任务，同时删除临时分块和断点续传信息
+    Remove {
+        /// 任务 ID（可以只写前 8 位），可以指定多个
+        #[arg(required = true, help = "任务 ID，可以只写前 8 位（见 multidown list），可以指定多个。")]
+        ids: Vec<String>,
+
+        /// 同时删除未完成的输出文件
+        #[arg(long = "with-data", help = "同时删除未完成的输出文件（不分块下载或续传中写了一部分的文件）；已完成任务的文件不会删除。")]
+        with_data: bool,
+    },
+    /// 修改未完成任务的保存路径，已下载的数据一起移动
+    Move {
+        /// 任务 ID（可以只写前 8 位）
+        #[arg(help = "任务 ID，可以只写前 8 位（见 multidown list）。")]
+        id: String,
+
+        /// 新的保存路径
+        #[arg(help = "新的保存路径；相对路径放在下载目录下，绝对路径可以换到其他磁盘。")]
+        path: String,
+
+        /// 新的临时目录
+        #[arg(long = "temp-dir", help = "同时把分块临时文件和断点续传信息移到这个目录（原来的磁盘快满时使用）。")]
+        temp_dir: Option<String>,
+    },
+    /// 按主机和日期汇总下载流量
+    Stats {
+        /// 统计范围
+        #[arg(long, default_value = "7d", help = "统计范围：最近 N 天（7d）、N 周（2w）或起始日期（2024-05-01）。")]
+        since: String,
+    },
+    /// 查看或修改配置文件
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// 管理保存在系统密钥环中的密钥（在配置中以 {secret:<名称>} 引用）
+    Secret {
+        #[command(subcommand)]
+        action: SecretAction,
+    },
+    /// 测试不同连接数和分块大小的下载速度，给出最快的组合
+    Bench {
+        /// 用于测试的文件 URL，服务器需要支持 Range 请求
+        url: String,
+
+        /// 测试的连接数
+        #[arg(long, value_delimiter = ',', default_values_t = [1, 2, 4, 8], help = "测试的连接数，逗号分隔。")]
+        connections: Vec<usize>,
+
+        /// 测试的分块大小
+        #[arg(long = "chunk-sizes", value_delimiter = ',', default_values = ["1M", "4M"], value_parser = crate::utils::size::parse_size, help = "测试的分块大小，逗号分隔，支持 512K、4M 等单位。")]
+        chunk_sizes: Vec<u64>,
+
+        /// 每组参数的测试时间（秒）
+        #[arg(long, default_value_t = 5, help = "每组参数的测试时间（秒）。")]
+        duration: u64,
+
+        /// 把推荐值写入配置文件
+        #[arg(long, help = "把推荐的连接数和分块大小写成该主机的 URL 规则，保存到配置文件。")]
+        save: bool,
+    },
+    /// 把会话中未完成的任务（含任务选项和进度）导出到文件
+    ExportQueue {
+        /// 队列文件路径
+        path: String,
+
+        /// 只导出带有该标签的任务
+        #[arg(long, help = "只导出带有该标签的任务。")]
+        tag: Option<String>,
+    },
+    /// 按下载历史校验已下载的文件是否损坏或过期
+    Verify {
+        /// 文件路径或任务 ID（可以只写前 8 位），不指定时校验历史中的所有文件
+        targets: Vec<String>,
+
+        /// 同时检查源站上的文件是否已更新
+        #[arg(long, help = "向源站发送 HEAD 请求，按 ETag、Last-Modified 和大小判断本地文件是否已过期。")]
+        remote: bool,
+    },
+    /// 作为守护进程运行：通过本地控制通道、网页控制台（启用 grpc 特性时还有 gRPC 接口）接受命令，直到收到终止信号
+    Serve {
+        #[command(flatten)]
+        daemon: DaemonArgs,
+    },
+    /// 把守护进程注册为 Windows 服务（仅 Windows）
+    Service {
+        #[command(subcommand)]
+        action: ServiceAction,
+    },
+}
+
+/// 产生下载任务、进入下载流程的子命令
+#[derive(Subcommand, Debug, Clone)]
+pub enum DownloadCommand {
+    /// 重新下载会话中失败的任务，沿用原来的选项和已下载的分块
+    RetryFailed {
+        /// 只重试带有该标签的任务
+        #[arg(long, help = "只重试带有该标签的失败任务。")]
+        tag: Option<String>,
+    },
+    /// 导入 export-queue 导出的队列并开始下载，会话中已有的任务不重复添加
+    ImportQueue {
+        /// 队列文件路径
+        path: String,
+    },
+    /// 按 Git LFS 指针文件下载对象（模型权重等），完成后按指针中的 SHA-256 校验
+    Lfs {
+        /// 指针文件路径，可以指定多个；相对路径原样作为下载目录下的保存路径
+        #[arg(required = true)]
+        pointers: Vec<String>,
+
+        /// 指针文件所在的仓库地址
+        #[arg(long, help = "指针文件所在的 Git 仓库地址，如 https://huggingface.co/org/model；地址中的用户名和密码按 Basic 认证发送。")]
+        repo: String,
+
+        /// 访问 LFS 接口的令牌
+        #[arg(long, help = "访问 LFS 接口的令牌（Bearer），可以写成 {secret:<名称>} 从密钥环读取。")]
+        token: Option<String>,
+    },
+}
+
+/// `serve` 和 `service` 共用的守护进程参数
+#[derive(clap::Args, Debug, Clone, PartialEq, Eq)]
+pub struct DaemonArgs {
+    /// 监听地址
+    #[cfg(feature = "grpc")]
+    #[arg(long, default_value = "127.0.0.1:50051", help = "gRPC 服务的监听地址。")]
+    pub listen: std::net::SocketAddr,
+    /// 网页控制台的监听地址
+    #[arg(long, default_value = crate::web::DEFAULT_ADDR, help = "网页控制台的监听地址。")]
+    pub web: std::net::SocketAddr,
+    /// 不提供网页控制台
+    #[arg(long, help = "不提供网页控制台。")]
+    pub no_web: bool,
+}
+
+impl DaemonArgs {
+    /// 网页控制台的监听地址，`--no-web` 时为 None
+    pub fn web(&self) -> Option<std::net::SocketAddr> {
+        Some(self.web).filter(|_| !self.no_web)
+    }
+
+    /// 还原为命令行参数，注册服务时写入启动命令
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        #[cfg(feature = "grpc")]
+        args.extend(["--listen".to_string(), self.listen.to_string()]);
+        match self.web() {
+            Some(web) => args.extend(["--web".to_string(), web.to_string()]),
+            None => args.push("--no-web".to_string()),
+        }
+        args
+    }
+}
+
+/// `service` 子命令的操作
+#[derive(Subcommand, Debug, Clone)]
+pub enum ServiceAction {
+    /// 注册服务，启动命令记录当前的配置文件、下载目录和工作目录；需要管理员权限
+    Install {
+        /// 服务名称
+        #[arg(long, default_value = crate::utils::windows_service::DEFAULT_NAME, help = "服务名称。")]
+        name: String,
+        /// 手动启动
+        #[arg(long, help = "服务改为手动启动（默认随系统自动启动）。")]
+        manual: bool,
+        #[command(flatten)]
+        daemon: DaemonArgs,
+    },
+    /// 停止并删除服务；需要管理员权限
+    Uninstall {
+        /// 服务名称
+        #[arg(long, default_value = crate::utils::windows_service::DEFAULT_NAME, help = "服务名称。")]
+        name: String,
+    },
+    /// 由服务控制管理器调用，向它报告服务状态；输出写入工作目录下的 logs/service.log
+    Run {
+        /// 服务名称
+        #[arg(long, default_value = crate::utils::windows_service::DEFAULT_NAME, help = "服务名称。")]
+        name: String,
+        /// 工作目录
+        #[arg(long, help = "服务的工作目录，会话文件、控制通道和日志都在这里（服务管理器启动时的工作目录是系统目录）。")]
+        work_dir: String,
+        #[command(flatten)]
+        daemon: DaemonArgs,
+    },
+}
+
+/// `secret` 子命令的操作
+#[derive(Subcommand, Debug, Clone)]
+pub enum SecretAction {
+    /// 保存密钥，值从终端输入（不回显）或标准输入读取，不经过命令行参数
+    Set {
+        /// 密钥名称
+        name: String,
+    },
+    /// 检查密钥是否存在（不输出密钥值）
+    Check {
+        /// 密钥名称
+        name: String,
+    },
+    /// 删除密钥
+    Delete {
+        /// 密钥名称
+        name: String,
+    },
+}
+
+/// `config` 子命令的操作
+#[derive(Subcommand, Debug, Clone)]
+pub enum ConfigAction {
+    /// 显示配置文件中的全部配置项
+    Show,
+    /// 读取单个配置项
+    Get {
+        /// 配置项名称，例如 thread_count
+        key: String,
+    },
+    /// 修改单个配置项（校验后写回，保留文件中的注释）
+    Set {
+        /// 配置项名称，例如 thread_count
+        key: String,
+        /// 新的值，按配置项原有类型解析
+        value: String,
+    },
+    /// 显示配置文件路径
+    Path,
 }
 
 impl Args {
     pub fn parse_args() -> Result<(Self, Config), DownloadError> { 
         // 解析命令行参数，并返回配置文件路径和配置，DownloadError是自定义错误类型
         let args = Args::parse();
+
+        // 尽早确定界面语言，后续生成的配置教程和提示信息都依赖它
+        i18n::init(args.lang);
         
+        // 服务管理器启动服务时的工作目录是系统目录，先切换到注册服务时记录的目录再读取配置
+        if let Some(Subcommands::Command(Command::Service { action: ServiceAction::Run { work_dir, .. } })) = &args.command {
+            std::env::set_current_dir(work_dir)
+                .map_err(|e| DownloadError::io_error_with_context(format!("无法进入工作目录 {}", work_dir), e))?;
+        }
+
         // --edit-config 逻辑
         if args.edit_config {
             open_config_in_editor(&args.config);
             std::process::exit(0); // 退出程序
         }
 
+        // config 子命令自行严格读写配置文件，避免无效配置让命令本身无法执行；secret 子命令不需要配置
+        if matches!(args.command, Some(Subcommands::Command(Command::Config { .. } | Command::Secret { .. }))) {
+            return Ok((args, Config::default()));
+        }
+
         // 加载或创建配置文件
         let mut config = if Path::new(&args.config).exists() {
-            Config::load(&args.config).map_err(|e| DownloadError::permission_error(format!("无法读取配置文件: {}", e)))?
+            Config::load_with_mode(&args.config, args.strict_config)?
         } else {
             // 确保配置文件所在目录存在
             if let Some(parent) = Path::new(&args.config).parent() { 
@@ -163,12 +640,23 @@ impl Args {
         Ok((args, config))
     }
 
-    // 定义从文件中读取URL的方法
-    pub fn get_urls(&self) -> Result<Vec<String>, DownloadError> {
+    /// 命令行和列表文件中的下载地址（`docker://` 镜像地址不是 URL，不在其中）
+    pub fn get_urls(&self) -> Result<Vec<url::Url>, DownloadError> {
+        self.get_entries()?
+            .iter()
+            .filter(|entry| !crate::core::oci::is_reference(&entry.url))
+            .map(|entry| validator::parse_url(&entry.url))
+            .collect()
+    }
+
+    /// 命令行和列表文件中的 URL，列表文件中每行附加的选项一并返回
+    pub fn get_entries(&self) -> Result<Vec<UrlEntry>, DownloadError> {
         let mut urls = Vec::new(); // vec是一个动态数组，可以存储任意类型的元素
 
-        // 如果提供了URL列表，添加到结果中
-        urls.extend_from_slice(&self.urls); // extend_from_slice方法将切片中的元素添加到vec中，不使用clone提升性能
+        // 如果提供了URL列表，添加到结果中；与列表文件一样在这里校验并规范化，无效的直接报错
+        for url in &self.urls {
+            urls.push(url_list::entry(url)?);
+        }
 
         // 如果提供了文件，从文件中读取URL
         if let Some(file_path) = &self.file {
@@ -178,16 +666,8 @@ impl Args {
             let content = fs::read_to_string(file_path)
                 .map_err(|e| DownloadError::permission_error(std::borrow::Cow::Owned(format!("无法读取URL文件: {}", e))))?;
             
-            // 按行读取URL，忽略空行和注释
-            for line in content.lines() {
-                let line = line.trim();
-                if !line.is_empty() && !line.starts_with('#') {
-                    if !crate::utils::validator::is_valid_url(line) {
-                        return Err(DownloadError::invalid_url(line.to_string()));
-                    }
-                    urls.push(line.to_string());
-                }
-            }
+            // 按行读取URL和附加的选项，忽略空行和注释
+            urls.extend(url_list::parse(&content)?);
         }
 
         // 验证URL列表不为空
@@ -197,6 +677,80 @@ impl Args {
 
         Ok(urls)
     }
+
+    /// `--fast`、`--polite` 选择的预设
+    pub fn preset(&self) -> Option<Preset> {
+        match (self.fast, self.polite) {
+            (true, _) => Some(Preset::Fast),
+            (_, true) => Some(Preset::Polite),
+            _ => None,
+        }
+    }
+
+    /// 命令行参数对应的任务选项
+    pub fn task_options(&self, url: &str) -> TaskOptions {
+        let headers = self
+            .referer
+            .as_ref()
+            .and_then(|referer| referer.for_url(url, None))
+            .map(|value| vec![("Referer".to_string(), value)])
+            .unwrap_or_default();
+        TaskOptions { headers, range: self.range, ..Default::default() }
+    }
+}
+
+/// `--referer` 的取值
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Referer {
+    /// 链接所在的页面
+    Auto,
+    /// 固定的地址
+    Url(String),
+}
+
+impl Referer {
+    /// 下载 `url` 时使用的 Referer；`source` 是链接所在的页面（已知时），
+    /// 自动模式下来源未知则取文件所在的目录，如 `https://example.com/files/a.zip` 对应 `https://example.com/files/`
+    pub fn for_url(&self, url: &str, source: Option<&str>) -> Option<String> {
+        match self {
+            Referer::Url(value) => Some(value.clone()),
+            Referer::Auto => match source {
+                Some(source) => Some(source.to_string()),
+                None => url::Url::parse(url).ok()?.join("./").ok().map(|u| u.to_string()),
+            },
+        }
+    }
+}
+
+fn parse_output(value: &str) -> Result<String, String> {
+    match value {
+        "-" => Ok(value.to_string()),
+        _ => Err("目前只支持 -（标准输出），保存到文件请使用 -d 和 -n".to_string()),
+    }
+}
+
+/// `list --status` 的取值
+fn parse_list_status(value: &str) -> Result<String, String> {
+    let value = value.trim().to_ascii_lowercase();
+    if !STATUS_NAMES.contains(&value.as_str()) {
+        return Err(format!("无效的状态: {}（可选 {}）", value, STATUS_NAMES.join("、")));
+    }
+    Ok(value)
+}
+
+/// 标签不能为空，也不能包含空白字符
+fn parse_tag(value: &str) -> Result<String, String> {
+    if value.is_empty() || value.chars().any(char::is_whitespace) {
+        return Err("标签不能为空，也不能包含空白字符".to_string());
+    }
+    Ok(value.to_string())
+}
+
+fn parse_referer(value: &str) -> Result<Referer, String> {
+    if value.eq_ignore_ascii_case("auto") {
+        return Ok(Referer::Auto);
+    }
+    validator::parse_url(value).map(|url| Referer::Url(url.to_string())).map_err(|e| e.to_string())
 }
 
 // ========== actix集成 ==========
@@ -207,7 +761,7 @@ impl Message for ParseArgs { type Result = Result<(Args, Config), DownloadError>
 
 /// 消息：获取URL列表
 pub struct GetUrls(pub Args);
-impl Message for GetUrls { type Result = Result<Vec<String>, DownloadError>; }
+impl Message for GetUrls { type Result = Result<Vec<url::Url>, DownloadError>; }
 
 /// CLI参数解析Actor
 /// CLI 是Command Line Interface 的缩写，表示命令行界面。
@@ -218,7 +772,7 @@ impl Actor for CliActor { type Context = actix::Context<Self>; }
 impl Handler<ParseArgs> for CliActor {
     type Result = MessageResult<ParseArgs>;
     fn handle(&mut self, _msg: ParseArgs, _ctx: &mut Self::Context) -> Self::Result {
-        MessageResult(Args::parse_args().map_err(DownloadError::from))
+        MessageResult(Args::parse_args())
     }
 }
 
@@ -226,7 +780,7 @@ impl Handler<ParseArgs> for CliActor {
 impl Handler<GetUrls> for CliActor {
     type Result = MessageResult<GetUrls>;
     fn handle(&mut self, msg: GetUrls, _ctx: &mut Self::Context) -> Self::Result {
-        MessageResult(msg.0.get_urls().map_err(DownloadError::from))
+        MessageResult(msg.0.get_urls())
     }
 }
 
@@ -243,6 +797,227 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_output_flags() {
+        let args = Args::try_parse_from(["multidown", "https://example.com/file.zip"]).unwrap();
+        assert!(!args.quiet);
+        assert!(!args.no_progress);
+
+        let args = Args::try_parse_from(["multidown", "-q", "--no-progress", "https://example.com/file.zip"]).unwrap();
+        assert!(args.quiet);
+        assert!(args.no_progress);
+    }
+
+    #[test]
+    fn test_history_subcommand() {
+        let args = Args::try_parse_from(["multidown", "history", "--search", "example"]).unwrap();
+        assert!(args.urls.is_empty());
+        assert!(matches!(args.command, Some(Subcommands::Command(Command::History { search: Some(_), limit: 20 }))));
+
+        let args = Args::try_parse_from(["multidown", "https://example.com/history"]).unwrap();
+        assert!(args.command.is_none());
+    }
+
+    #[test]
+    fn test_bench_subcommand() {
+        let args = Args::try_parse_from(["multidown", "bench", "https://example.com/a.iso", "--chunk-sizes", "512K,8M"]).unwrap();
+        let Some(Subcommands::Command(Command::Bench { connections, chunk_sizes, duration, save, .. })) = args.command else {
+            panic!("应解析为 bench 子命令");
+        };
+        assert_eq!(connections, vec![1, 2, 4, 8]);
+        assert_eq!(chunk_sizes, vec![512 * 1024, 8 << 20]);
+        assert_eq!((duration, save), (5, false));
+    }
+
+    #[test]
+    fn test_verify_subcommand() {
+        let args = Args::try_parse_from(["multidown", "verify", "downloads/a.iso", "1a2b3c4d", "--remote"]).unwrap();
+        let Some(Subcommands::Command(Command::Verify { targets, remote })) = args.command else {
+            panic!("应解析为 verify 子命令");
+        };
+        assert_eq!(targets, vec!["downloads/a.iso", "1a2b3c4d"]);
+        assert!(remote);
+    }
+
+    #[test]
+    fn test_list_subcommand() {
+        let args = Args::try_parse_from(["multidown", "list", "--status", "failed,Paused", "--sort", "size", "-r"]).unwrap();
+        let Some(Subcommands::Command(Command::List { status, sort, reverse, json, .. })) = args.command else {
+            panic!("应解析为 list 子命令");
+        };
+        assert_eq!(status, vec!["failed", "paused"]);
+        assert_eq!((sort, reverse, json), (SortKey::Size, true, false));
+        assert!(Args::try_parse_from(["multidown", "list", "--status", "broken"]).is_err());
+        let args = Args::try_parse_from(["multidown", "list", "--tag", "nightly"]).unwrap();
+        assert!(matches!(args.command, Some(Subcommands::Command(Command::List { tag: Some(tag), .. })) if tag == "nightly"));
+    }
+
+    #[test]
+    fn test_pause_subcommand() {
+        let args = Args::try_parse_from(["multidown", "pause", "--tag", "nightly"]).unwrap();
+        let Some(Subcommands::Command(Command::Pause { ids, tag })) = args.command else {
+            panic!("应解析为 pause 子命令");
+        };
+        assert_eq!((ids.is_empty(), tag.as_deref()), (true, Some("nightly")));
+        let args = Args::try_parse_from(["multidown", "pause", "1a2b3c4d", "5e6f7a8b"]).unwrap();
+        assert!(matches!(args.command, Some(Subcommands::Command(Command::Pause { ids, tag: None })) if ids.len() == 2));
+        assert!(Args::try_parse_from(["multidown", "pause"]).is_err());
+        assert!(Args::try_parse_from(["multidown", "pause", "1a2b3c4d", "--tag", "nightly"]).is_err());
+    }
+
+    #[test]
+    fn test_service_subcommand() {
+        let args = Args::try_parse_from(["multidown", "service", "install", "--manual", "--web", "0.0.0.0:6801"]).unwrap();
+        let Some(Subcommands::Command(Command::Service { action: ServiceAction::Install { name, manual, daemon } })) = args.command else {
+            panic!("应解析为 service install 子命令");
+        };
+        assert_eq!((name.as_str(), manual), ("multidown", true));
+        // 写入启动命令的参数能被 service run 原样解析
+        let mut run = vec!["multidown".to_string(), "service".into(), "run".into(), "--work-dir".into(), "C:\\md".into()];
+        run.extend(daemon.to_args());
+        let Some(Subcommands::Command(Command::Service { action: ServiceAction::Run { work_dir, daemon: parsed, .. } })) = Args::try_parse_from(run).unwrap().command else {
+            panic!("应解析为 service run 子命令");
+        };
+        assert_eq!((work_dir.as_str(), &parsed), ("C:\\md", &daemon));
+
+        let args = Args::try_parse_from(["multidown", "service", "run", "--work-dir", "/srv", "--no-web"]).unwrap();
+        let Some(Subcommands::Command(Command::Service { action: ServiceAction::Run { daemon, .. } })) = args.command else {
+            panic!("应解析为 service run 子命令");
+        };
+        assert_eq!(daemon.web(), None);
+        assert!(daemon.to_args().contains(&"--no-web".to_string()));
+        assert!(Args::try_parse_from(["multidown", "service", "run"]).is_err());
+    }
+
+    #[test]
+    fn test_move_subcommand() {
+        let args = Args::try_parse_from(["multidown", "move", "1a2b3c4d", "/mnt/b.iso", "--temp-dir", "/mnt/.tmp"]).unwrap();
+        let Some(Subcommands::Command(Command::Move { id, path, temp_dir })) = args.command else {
+            panic!("应解析为 move 子命令");
+        };
+        assert_eq!((id.as_str(), path.as_str(), temp_dir.as_deref()), ("1a2b3c4d", "/mnt/b.iso", Some("/mnt/.tmp")));
+        assert!(Args::try_parse_from(["multidown", "move", "1a2b3c4d"]).is_err());
+    }
+
+    #[test]
+    fn test_lfs_subcommand() {
+        let args = Args::try_parse_from(["multidown", "lfs", "unet/model.safetensors", "vae/model.safetensors", "--repo", "https://huggingface.co/org/model"]).unwrap();
+        let Some(Subcommands::Download(DownloadCommand::Lfs { pointers, repo, token })) = args.command else {
+            panic!("应解析为 lfs 子命令");
+        };
+        assert_eq!(pointers, vec!["unet/model.safetensors", "vae/model.safetensors"]);
+        assert_eq!((repo.as_str(), token), ("https://huggingface.co/org/model", None));
+        assert!(Args::try_parse_from(["multidown", "lfs", "--repo", "https://huggingface.co/org/model"]).is_err());
+        assert!(Args::try_parse_from(["multidown", "lfs", "a.bin"]).is_err());
+    }
+
+    #[test]
+    fn test_retry_failed_subcommand() {
+        let args = Args::try_parse_from(["multidown", "retry-failed"]).unwrap();
+        assert!(matches!(args.command, Some(Subcommands::Download(DownloadCommand::RetryFailed { tag: None }))));
+        assert!(args.get_urls().is_err());
+
+        let args = Args::try_parse_from(["multidown", "--tag", "nightly", "--tag", "iso", "https://example.com/a"]).unwrap();
+        assert_eq!(args.tags, vec!["nightly", "iso"]);
+        assert!(Args::try_parse_from(["multidown", "--tag", "a b", "https://example.com/a"]).is_err());
+
+        let args = Args::try_parse_from(["multidown", "retry-failed", "--tag", "nightly"]).unwrap();
+        assert!(matches!(args.command, Some(Subcommands::Download(DownloadCommand::RetryFailed { tag: Some(ref tag) })) if tag == "nightly"));
+    }
+
+    #[test]
+    fn test_check_only_args() {
+        let args = Args::try_parse_from(["multidown", "--check-only", "--json", "https://example.com/a"]).unwrap();
+        assert!(args.check_only && args.json);
+        // --json 只能与 --check-only 一起使用
+        assert!(Args::try_parse_from(["multidown", "--json", "https://example.com/a"]).is_err());
+    }
+
+    #[test]
+    fn test_size_limits() {
+        let args = Args::try_parse_from(["multidown", "--max-file-size", "2G", "--quota", "50G", "https://example.com/a"]).unwrap();
+        let mut config = Config::default();
+        config.merge_from_args(&args);
+        assert_eq!(config.max_file_size, 2 << 30);
+        assert_eq!(config.run_quota, 50 << 30);
+        assert!(Args::try_parse_from(["multidown", "--quota", "lots", "https://example.com/a"]).is_err());
+
+        let args = Args::try_parse_from(["multidown", "--timeout-total", "1h30m", "https://example.com/a"]).unwrap();
+        config.merge_from_args(&args);
+        assert_eq!(config.timeout_total, 5400);
+    }
+
+    #[test]
+    fn test_stdout_output() {
+        let args = Args::try_parse_from(["multidown", "-O", "-", "https://example.com/a.tar"]).unwrap();
+        assert_eq!(args.output.as_deref(), Some("-"));
+        assert!(Args::try_parse_from(["multidown", "-O", "a.tar", "https://example.com/a.tar"]).is_err());
+    }
+
+    #[test]
+    fn test_referer() {
+        let url = "https://example.com/files/a.zip?token=1";
+        let args = Args::try_parse_from(["multidown", "--referer", "auto", url]).unwrap();
+        assert_eq!(args.referer, Some(Referer::Auto));
+        assert_eq!(args.task_options(url).headers, vec![("Referer".to_string(), "https://example.com/files/".to_string())]);
+        assert_eq!(Referer::Auto.for_url(url, Some("https://example.com/page")).unwrap(), "https://example.com/page");
+
+        let args = Args::try_parse_from(["multidown", "--referer", "https://example.com/page", url]).unwrap();
+        assert_eq!(args.task_options(url).headers[0].1, "https://example.com/page");
+        assert!(Args::try_parse_from(["multidown", "--referer", "not a url", url]).is_err());
+        assert!(Args::try_parse_from(["multidown", url]).unwrap().task_options(url).headers.is_empty());
+
+        let args = Args::try_parse_from(["multidown", "--range", "1M-", url]).unwrap();
+        assert_eq!(args.task_options(url).range, Some(ByteRange { start: 1 << 20, end: None }));
+    }
+
+    #[test]
+    fn test_presets() {
+        let args = Args::try_parse_from(["multidown", "--polite", "-t", "3", "https://example.com/a"]).unwrap();
+        assert_eq!(args.preset(), Some(Preset::Polite));
+        let mut config = Config::default();
+        config.merge_from_args(&args);
+        // 单独指定的参数优先于预设
+        assert_eq!(config.thread_count, 3);
+        assert_eq!(config.max_concurrent_downloads, 1);
+        assert!(Args::try_parse_from(["multidown", "--fast", "--polite", "https://example.com/a"]).is_err());
+
+        // 小文件模式按 small_files_concurrency 同时下载
+        let args = Args::try_parse_from(["multidown", "--small-files", "-f", "urls.txt"]).unwrap();
+        let mut config = Config::default();
+        config.merge_from_args(&args);
+        assert!(config.small_files);
+        assert_eq!(config.download_slots(), config.small_files_concurrency);
+        assert!(Args::try_parse_from(["multidown", "--small-files", "--polite", "https://example.com/a"]).is_err());
+        assert_eq!(Args::try_parse_from(["multidown", "https://example.com/a"]).unwrap().preset(), None);
+    }
+
+    #[test]
+    fn test_speed_limit_units() {
+        let args = Args::try_parse_from(["multidown", "--limit", "2M", "https://example.com/a"]).unwrap();
+        assert_eq!(args.speed_limit_kb, Some(2048));
+        let args = Args::try_parse_from(["multidown", "-l", "300", "https://example.com/a"]).unwrap();
+        assert_eq!(args.speed_limit_kb, Some(300));
+        assert!(Args::try_parse_from(["multidown", "-l", "fast", "https://example.com/a"]).is_err());
+        let args = Args::try_parse_from(["multidown", "--total-limit", "10M", "https://example.com/a"]).unwrap();
+        assert_eq!(args.total_speed_limit_kb, Some(10240));
+    }
+
+    #[test]
+    fn test_config_subcommand() {
+        let args = Args::try_parse_from(["multidown", "config", "set", "thread_count", "8"]).unwrap();
+        match args.command {
+            Some(Subcommands::Command(Command::Config { action: ConfigAction::Set { key, value } })) => {
+                assert_eq!(key, "thread_count");
+                assert_eq!(value, "8");
+            }
+            other => panic!("unexpected command: {:?}", other),
+        }
+        let args = Args::try_parse_from(["multidown", "-c", "my.conf", "config", "path"]).unwrap();
+        assert_eq!(args.config, "my.conf");
+        assert!(matches!(args.command, Some(Subcommands::Command(Command::Config { action: ConfigAction::Path }))));
+    }
+
     #[test]
     fn test_config_loading() {
         // 创建临时配置文件
@@ -263,7 +1038,7 @@ mod tests {
     fn test_url_file_parsing() {
         // 创建临时URL文件
         let temp_url_file = "temp_urls.txt";
-        let content = "# 这是一个注释\nhttps://example.com/file1.zip\nhttps://example.com/file2.zip\n";
+        let content = "# 这是一个注释\nhttps://example.com/file1.zip\nhttps://example.com/file2.zip | out=two.zip | dir=sub\n";
         fs::write(temp_url_file, content).unwrap();
 
         // 测试从文件读取URL
@@ -274,10 +1049,21 @@ mod tests {
         let args = result.unwrap();
         let urls = args.get_urls().unwrap();
         assert_eq!(urls.len(), 2);
-        assert_eq!(urls[0], "https://example.com/file1.zip");
-        assert_eq!(urls[1], "https://example.com/file2.zip");
+        assert_eq!(urls[0].as_str(), "https://example.com/file1.zip");
+        assert_eq!(urls[1].as_str(), "https://example.com/file2.zip");
+        let entries = args.get_entries().unwrap();
+        assert_eq!(entries[1].out.as_deref(), Some("two.zip"));
+        assert_eq!(entries[1].dir.as_deref(), Some("sub"));
 
         // 清理临时文件
         fs::remove_file(temp_url_file).unwrap();
+
+        // 命令行中的地址同样在解析参数时校验，镜像地址不在 URL 列表中
+        let args = Args::try_parse_from(["multidown", "https://example.com/a#x", "docker://registry.example.com/app:1"]).unwrap();
+        assert_eq!(args.get_entries().unwrap().len(), 2);
+        let urls = args.get_urls().unwrap();
+        assert_eq!(urls.iter().map(url::Url::as_str).collect::<Vec<_>>(), vec!["https://example.com/a"]);
+        let args = Args::try_parse_from(["multidown", "https://example.com/a", "not a url"]).unwrap();
+        assert!(args.get_entries().is_err());
     }
 }
\ No newline at end of file
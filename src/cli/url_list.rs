@@ -0,0 +1,130 @@
+//! URL 列表文件：`multidown -f urls.txt`
+//!
+//! 每行一个 URL，`#` 开头的行和空行忽略。URL 后面可以用 `|` 分隔附加该任务的选项：
+//!
+//! ```text
+//! https://example.com/a.iso | out=debian.iso | sha256=3b1f… | dir=iso/
+//! https://example.com/b.zip | dir=archives
+//! ```
+//!
+//! - `out`：保存的文件名，优先于 `--file-name`
+//! - `dir`：下载目录下的子目录，不存在时自动创建
+//! - `sha256`：期望的 SHA-256，下载完成后校验
+//! - `weight`：设置了总限速（`--total-limit`）时分配带宽的权重，默认 2（普通优先级）
+//!
+//! `docker://` 镜像地址也可以写在列表中，`out` 是保存镜像布局的目录名。
+
+use crate::core::error::DownloadError;
+use crate::core::oci::{self, ImageReference};
+use crate::utils::validator;
+
+/// 列表中的一个 URL 及其选项
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UrlEntry {
+    pub url: String,
+    /// 保存的文件名
+    pub out: Option<String>,
+    /// 下载目录下的子目录
+    pub dir: Option<String>,
+    /// 期望的 SHA-256（小写十六进制）
+    pub sha256: Option<String>,
+    /// 分配总限速时的权重
+    pub weight: Option<u32>,
+    /// 下载时附带的请求头，列表文件中不能指定（由 `multidown lfs` 等生成条目时使用）
+    pub headers: Vec<(String, String)>,
+}
+
+impl UrlEntry {
+    /// 只有 URL、没有选项的条目
+    pub fn new(url: impl Into<String>) -> Self {
+        Self { url: url.into(), ..Default::default() }
+    }
+}
+
+/// 解析列表文件的内容，出错时指明行号
+pub fn parse(content: &str) -> Result<Vec<UrlEntry>, DownloadError> {
+    let mut entries = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let entry = parse_line(line)
+            .map_err(|e| DownloadError::invalid_url(format!("URL 列表第 {} 行: {}", index + 1, e)))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// 校验并规范化一个地址（命令行参数或列表中的一行），`docker://` 镜像地址原样保留
+pub fn entry(url: &str) -> Result<UrlEntry, DownloadError> {
+    match oci::is_reference(url) {
+        true => ImageReference::parse(url).map(|_| UrlEntry::new(url)),
+        false => validator::parse_url(url).map(UrlEntry::new),
+    }
+}
+
+/// 解析一行：`url | key=value | ...`
+fn parse_line(line: &str) -> Result<UrlEntry, String> {
+    let mut columns = line.split('|').map(str::trim);
+    let mut entry = entry(columns.next().unwrap_or_default()).map_err(|e| e.to_string())?;
+    for column in columns.filter(|c| !c.is_empty()) {
+        let Some((key, value)) = column.split_once('=') else {
+            return Err(format!("选项应写成 key=value: {}", column));
+        };
+        let value = value.trim();
+        if value.is_empty() {
+            return Err(format!("选项 {} 的值为空", key.trim()));
+        }
+        match key.trim() {
+            "out" if value.contains(['/', '\\']) => return Err(format!("out 只能是文件名，子目录请用 dir: {}", value)),
+            "out" => entry.out = Some(value.to_string()),
+            "dir" => entry.dir = Some(value.to_string()),
+            "sha256" if value.len() != 64 || !value.chars().all(|c| c.is_ascii_hexdigit()) => {
+                return Err(format!("sha256 应为 64 位十六进制: {}", value));
+            }
+            "sha256" => entry.sha256 = Some(value.to_ascii_lowercase()),
+            "weight" => match value.parse::<u32>() {
+                Ok(weight @ 1..=1000) => entry.weight = Some(weight),
+                _ => return Err(format!("weight 应为 1 到 1000 的整数: {}", value)),
+            },
+            other => return Err(format!("未知的选项: {}（支持 out、dir、sha256、weight）", other)),
+        }
+    }
+    Ok(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_option_columns() {
+        let sha = "AB".repeat(32);
+        let content = format!(
+            "# 镜像列表\nhttps://example.com/a.iso | out=debian.iso | sha256={} | dir=iso/\n\nhttps://example.com/b.zip|dir=archives|weight=8\nhttps://example.com/c.bin\n",
+            sha
+        );
+        let entries = parse(&content).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].out.as_deref(), Some("debian.iso"));
+        assert_eq!(entries[0].dir.as_deref(), Some("iso/"));
+        assert_eq!(entries[0].sha256, Some(sha.to_ascii_lowercase()));
+        assert_eq!(entries[1], UrlEntry { dir: Some("archives".to_string()), weight: Some(8), ..UrlEntry::new("https://example.com/b.zip") });
+        assert_eq!(entries[2], UrlEntry::new("https://example.com/c.bin"));
+
+        // 出错时指明行号
+        let err = parse("https://example.com/a\nhttps://example.com/b | md5=1\n").unwrap_err().to_string();
+        assert!(err.contains("第 2 行") && err.contains("md5"), "{}", err);
+        assert!(parse("https://example.com/a | sha256=123").is_err());
+        assert!(parse("https://example.com/a | out=x/y.bin").is_err());
+        assert!(parse("https://example.com/a | out").is_err());
+        assert!(parse("https://example.com/a | weight=0").is_err());
+        assert!(parse("not a url | out=a.bin").is_err());
+
+        // docker:// 镜像地址原样保留
+        let entries = parse("docker://ghcr.io/org/tool:v1 | out=tool").unwrap();
+        assert_eq!(entries[0], UrlEntry { out: Some("tool".to_string()), ..UrlEntry::new("docker://ghcr.io/org/tool:v1") });
+        assert!(parse("docker://ghcr.io/org/tool@sha256:123").is_err());
+    }
+}
@@ -0,0 +1,168 @@
+//! 客户端配额：守护进程作为共享服务时，按 API 密钥区分提交任务的客户端
+//!
+//! ```toml
+//! [[clients]]
+//! name = "ci"
+//! api_key = "{secret:multidown-ci}"
+//! max_concurrent_downloads = 2
+//! speed_limit_kb = "4M"
+//! ```
+//!
+//! 任务记录提交它的客户端；同一客户端同时下载的任务不超过 `max_concurrent_downloads`，
+//! 合计速度不超过 `speed_limit_kb`（都是 0 表示不限制），同时仍受全局并发数和总限速的约束。
+//! gRPC 请求在 `x-api-key` 元数据中、网页控制台的请求在请求头中、本地控制通道的请求在 `api_key` 字段中携带密钥。
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::core::error::DownloadError;
+use crate::utils::{secrets, size};
+
+/// 一个客户端和它的配额
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ClientQuota {
+    /// 客户端名称，显示在任务和统计中
+    pub name: String,
+    /// API 密钥，可以写成 `{secret:<名称>}` 从密钥环读取
+    pub api_key: String,
+    /// 同时下载的任务数上限，0 表示只受全局并发数限制
+    #[serde(default)]
+    pub max_concurrent_downloads: usize,
+    /// 所有任务合计的速度限制（KB/s），0 表示不限速，也可以写成 "2M" 等带单位的字符串
+    #[serde(default, deserialize_with = "size::deserialize_rate_kb")]
+    pub speed_limit_kb: u64,
+}
+
+/// 校验客户端列表：名称和密钥不能为空，也不能重复
+pub fn validate(clients: &[ClientQuota]) -> Result<(), DownloadError> {
+    let mut names = HashSet::new();
+    let mut keys = HashSet::new();
+    for client in clients {
+        if client.name.trim().is_empty() {
+            return Err(DownloadError::Unknown("客户端名称不能为空".into()));
+        }
+        if client.api_key.is_empty() {
+            return Err(DownloadError::Unknown(format!("客户端 '{}' 没有设置 api_key", client.name).into()));
+        }
+        if !names.insert(client.name.as_str()) {
+            return Err(DownloadError::Unknown(format!("客户端名称重复: {}", client.name).into()));
+        }
+        if !keys.insert(client.api_key.as_str()) {
+            return Err(DownloadError::Unknown(format!("客户端 '{}' 的 api_key 与其他客户端相同", client.name).into()));
+        }
+    }
+    Ok(())
+}
+
+/// 解析后的 API 密钥，守护进程启动时读取一次，之后按密钥查找客户端
+#[derive(Debug, Clone, Default)]
+pub struct ClientKeys {
+    /// (密钥, 客户端名称)
+    keys: Vec<(String, String)>,
+}
+
+impl ClientKeys {
+    /// 读取所有客户端的密钥，`{secret:<名称>}` 从密钥环读取
+    pub fn resolve(clients: &[ClientQuota]) -> Result<Self, DownloadError> {
+        let keys = clients
+            .iter()
+            .map(|client| secrets::resolve(&client.api_key).map(|key| (key, client.name.clone())))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { keys })
+    }
+
+    /// 没有配置客户端，不需要认证
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// 按密钥查找客户端名称，用 [`secrets::constant_time_eq`] 比较
+    pub fn authenticate(&self, key: &str) -> Option<&str> {
+        self.keys.iter().find(|(k, _)| secrets::constant_time_eq(k.as_bytes(), key.as_bytes())).map(|(_, name)| name.as_str())
+    }
+}
+
+/// 远程接口（gRPC、网页控制台）的认证：访问令牌（`serve_token`）代表守护进程的所有者，API 密钥代表客户端
+#[derive(Debug, Clone, Default)]
+pub struct Credentials {
+    pub token: Option<String>,
+    pub keys: ClientKeys,
+}
+
+/// 认证通过的调用者
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Caller<'a> {
+    /// 持有访问令牌，或者没有配置任何认证
+    Owner,
+    /// 配置中的客户端
+    Client(&'a str),
+}
+
+impl Caller<'_> {
+    /// 客户端名称，所有者为 None
+    pub fn client(self) -> Option<String> {
+        match self {
+            Caller::Owner => None,
+            Caller::Client(name) => Some(name.to_string()),
+        }
+    }
+}
+
+impl Credentials {
+    /// `token` 为空字符串表示不使用访问令牌
+    pub fn new(token: String, keys: ClientKeys) -> Self {
+        Self { token: Some(token).filter(|token| !token.is_empty()), keys }
+    }
+
+    /// 没有配置令牌和客户端，任何请求都视为所有者
+    pub fn is_open(&self) -> bool {
+        self.token.is_none() && self.keys.is_empty()
+    }
+
+    /// 按请求携带的令牌或密钥（任意一个匹配即可）确定调用者，都不匹配时返回 None
+    pub fn authenticate<'a>(&'a self, presented: &[Option<&str>]) -> Option<Caller<'a>> {
+        if self.is_open() {
+            return Some(Caller::Owner);
+        }
+        presented.iter().flatten().find_map(|secret| {
+            if self.token.as_ref().is_some_and(|token| secrets::constant_time_eq(token.as_bytes(), secret.as_bytes())) {
+                return Some(Caller::Owner);
+            }
+            self.keys.authenticate(secret).map(Caller::Client)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(name: &str, api_key: &str) -> ClientQuota {
+        ClientQuota { name: name.to_string(), api_key: api_key.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn test_validate_and_authenticate() {
+        let clients = vec![client("ci", "k1"), client("batch", "k2")];
+        assert!(validate(&clients).is_ok());
+        assert!(validate(&[client("ci", "k1"), client("ci", "k2")]).is_err());
+        assert!(validate(&[client("ci", "k1"), client("batch", "k1")]).is_err());
+        assert!(validate(&[client("ci", "")]).is_err());
+        assert!(validate(&[client(" ", "k1")]).is_err());
+
+        let keys = ClientKeys::resolve(&clients).unwrap();
+        assert_eq!(keys.authenticate("k2"), Some("batch"));
+        assert_eq!(keys.authenticate("k"), None);
+        assert_eq!(keys.authenticate("k10"), None);
+        assert!(ClientKeys::default().is_empty());
+
+        let credentials = Credentials::new("t0ken".to_string(), keys);
+        assert_eq!(credentials.authenticate(&[Some("t0ken")]), Some(Caller::Owner));
+        assert_eq!(credentials.authenticate(&[None, Some("k1")]), Some(Caller::Client("ci")));
+        assert_eq!(credentials.authenticate(&[Some("bad"), None]), None);
+        assert_eq!(Credentials::default().authenticate(&[]), Some(Caller::Owner));
+
+        let quota: ClientQuota = toml::from_str("name = \"ci\"\napi_key = \"k\"\nspeed_limit_kb = \"2M\"").unwrap();
+        assert_eq!((quota.speed_limit_kb, quota.max_concurrent_downloads), (2048, 0));
+    }
+}
@@ -0,0 +1,201 @@
+//! 配置文件的脚本化读写（`multidown config get/set`、`multidown bench --save`）
+//!
+//! 写入时用 toml_edit 只替换目标键的值，文件中的教程注释和排版保持不变。
+
+use std::borrow::Cow;
+use std::fs;
+use std::path::Path;
+use toml_edit::Document;
+
+use super::{Config, UrlRule};
+use crate::core::error::DownloadError;
+
+/// 严格读取配置文件，不会像 [`Config::load`] 那样在出错时回退并覆盖文件
+pub fn read_config(path: &str) -> Result<Config, DownloadError> {
+    if !Path::new(path).exists() {
+        return Ok(Config::default());
+    }
+    let content = fs::read_to_string(path)
+        .map_err(|e| DownloadError::io_error_with_context("读取配置文件", e))?;
+    toml::from_str(&content)
+        .map_err(|e| DownloadError::Unknown(format!("配置文件格式错误: {}", e).into()))
+}
+
+/// 所有配置项的名称
+pub fn keys() -> Vec<String> {
+    to_table(&Config::default())
+        .map(|table| table.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// 读取单个配置项，字符串不带引号输出，便于脚本使用
+pub fn get_value(config: &Config, key: &str) -> Result<String, DownloadError> {
+    let table = to_table(config)?;
+    match table.get(key) {
+        Some(toml::Value::String(s)) => Ok(s.clone()),
+        Some(value) => Ok(value.to_string()),
+        None => Err(unknown_key(key)),
+    }
+}
+
+/// 修改单个配置项并写回文件
+///
+/// 值按原有类型解析，修改后的完整配置需通过 [`Config::validate`] 才会写入。
+pub fn set_value(path: &str, key: &str, raw: &str) -> Result<Config, DownloadError> {
+    if !Path::new(path).exists() {
+        Config::default().save_with_tutorial(path)?;
+    }
+    let content = fs::read_to_string(path)
+        .map_err(|e| DownloadError::io_error_with_context("读取配置文件", e))?;
+    let mut doc = content.parse::<Document>()
+        .map_err(|e| DownloadError::Unknown(format!("配置文件格式错误: {}", e).into()))?;
+    let config: Config = toml::from_str(&content)
+        .map_err(|e| DownloadError::Unknown(format!("配置文件格式错误: {}", e).into()))?;
+
+    let mut table = to_table(&config)?;
+    let current = table.get(key).ok_or_else(|| unknown_key(key))?;
+    let value = parse_value(key, current, raw)?;
+    table.insert(key.to_string(), value.clone());
+    let updated: Config = toml::Value::Table(table).try_into()
+        .map_err(|e| DownloadError::Unknown(format!("配置项 {} 的值无效: {}", key, e).into()))?;
+    updated.validate()?;
+
+    let mut new_value = value.to_string().parse::<toml_edit::Value>()
+        .map_err(|e| DownloadError::Unknown(format!("配置项 {} 的值无效: {}", key, e).into()))?;
+    // 保留原值前后的空白和行尾注释
+    if let Some(old) = doc.get(key).and_then(|item| item.as_value()) {
+        *new_value.decor_mut() = old.decor().clone();
+    }
+    doc[key] = toml_edit::Item::Value(new_value);
+
+    fs::write(path, doc.to_string())
+        .map_err(|e| DownloadError::io_error_with_context("写入配置文件", e))?;
+    Ok(updated)
+}
+
+/// 写入一条 URL 规则：已有相同 pattern 的规则时更新其中设置的选项，否则追加到末尾
+pub fn upsert_rule(path: &str, rule: &UrlRule) -> Result<Config, DownloadError> {
+    if !Path::new(path).exists() {
+        Config::default().save_with_tutorial(path)?;
+    }
+    let content = fs::read_to_string(path)
+        .map_err(|e| DownloadError::io_error_with_context("读取配置文件", e))?;
+    let mut doc = content.parse::<Document>()
+        .map_err(|e| DownloadError::Unknown(format!("配置文件格式错误: {}", e).into()))?;
+
+    let new_table = toml::to_string(rule)
+        .map_err(|e| DownloadError::Unknown(format!("无法序列化规则: {}", e).into()))?
+        .parse::<Document>()
+        .map_err(|e| DownloadError::Unknown(format!("无法序列化规则: {}", e).into()))?
+        .as_table()
+        .clone();
+    let rules = doc
+        .entry("rules")
+        .or_insert_with(|| toml_edit::Item::ArrayOfTables(Default::default()))
+        .as_array_of_tables_mut()
+        .ok_or(DownloadError::Unknown(Cow::Borrowed("配置项 rules 不是规则列表")))?;
+    let existing = rules
+        .iter_mut()
+        .find(|table| table.get("pattern").and_then(|p| p.as_str()) == Some(rule.pattern.as_str()));
+    match existing {
+        Some(table) => {
+            for (key, item) in new_table.iter() {
+                table[key] = item.clone();
+            }
+        }
+        None => rules.push(new_table),
+    }
+
+    let updated: Config = toml::from_str(&doc.to_string())
+        .map_err(|e| DownloadError::Unknown(format!("规则无效: {}", e).into()))?;
+    updated.validate()?;
+    fs::write(path, doc.to_string())
+        .map_err(|e| DownloadError::io_error_with_context("写入配置文件", e))?;
+    Ok(updated)
+}
+
+fn to_table(config: &Config) -> Result<toml::Table, DownloadError> {
+    match toml::Value::try_from(config) {
+        Ok(toml::Value::Table(table)) => Ok(table),
+        Ok(_) => Err(DownloadError::Unknown(Cow::Borrowed("无法序列化配置"))),
+        Err(e) => Err(DownloadError::Unknown(format!("无法序列化配置: {}", e).into())),
+    }
+}
+
+fn unknown_key(key: &str) -> DownloadError {
+    DownloadError::Unknown(format!("未知配置项: {}（可用: {}）", key, keys().join(", ")).into())
+}
+
+/// 按配置项当前的类型解析命令行给出的值
+fn parse_value(key: &str, current: &toml::Value, raw: &str) -> Result<toml::Value, DownloadError> {
+    let invalid = |expected: &str| {
+        DownloadError::Unknown(format!("配置项 {} 需要{}，实际为: {}", key, expected, raw).into())
+    };
+    match current {
+        toml::Value::String(_) => Ok(toml::Value::String(raw.to_string())),
+        // 大小、速率类配置项可以写成 "4MiB" 这样的字符串，由反序列化负责校验
+        toml::Value::Integer(_) => Ok(raw.trim().parse().map(toml::Value::Integer)
+            .unwrap_or_else(|_| toml::Value::String(raw.trim().to_string()))),
+        toml::Value::Float(_) => raw.trim().parse().map(toml::Value::Float).map_err(|_| invalid("数字")),
+        toml::Value::Boolean(_) => raw.trim().parse().map(toml::Value::Boolean).map_err(|_| invalid(" true 或 false")),
+        _ => toml::from_str::<toml::Table>(&format!("value = {}", raw))
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+            .ok_or_else(|| invalid(" TOML 值")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_value_preserves_comments() {
+        let path = "./test_config_edit.toml";
+        Config::default().save_with_tutorial(path).unwrap();
+
+        let updated = set_value(path, "thread_count", "8").unwrap();
+        assert_eq!(updated.thread_count, 8);
+        set_value(path, "user_agent", "curl/8.0").unwrap();
+
+        let content = fs::read_to_string(path).unwrap();
+        assert!(content.contains("故障排除"));
+        let loaded = read_config(path).unwrap();
+        assert_eq!(loaded.thread_count, 8);
+        assert_eq!(get_value(&loaded, "user_agent").unwrap(), "curl/8.0");
+
+        assert_eq!(set_value(path, "chunk_size", "64K").unwrap().chunk_size, 64 * 1024);
+
+        // 类型错误、校验失败和未知键都不会写入
+        assert!(set_value(path, "thread_count", "many").is_err());
+        assert!(set_value(path, "thread_count", "0").is_err());
+        assert!(set_value(path, "no_such_key", "1").is_err());
+        assert_eq!(read_config(path).unwrap().thread_count, 8);
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_upsert_rule() {
+        let path = "./test_config_rules.toml";
+        Config::default().save_with_tutorial(path).unwrap();
+
+        let rule = UrlRule {
+            pattern: r"^https?://cdn\.example\.com/".to_string(),
+            thread_count: Some(4),
+            chunk_size: Some(1 << 20),
+            ..Default::default()
+        };
+        upsert_rule(path, &rule).unwrap();
+        let updated = upsert_rule(path, &UrlRule { thread_count: Some(8), ..rule.clone() }).unwrap();
+        assert_eq!(updated.rules.len(), 1);
+        assert_eq!(updated.rules[0].thread_count, Some(8));
+        assert_eq!(updated.rules[0].chunk_size, Some(1 << 20));
+
+        let updated = upsert_rule(path, &UrlRule { pattern: "mirror".to_string(), ..rule }).unwrap();
+        assert_eq!(updated.rules.len(), 2);
+        assert!(fs::read_to_string(path).unwrap().contains("故障排除"));
+
+        let _ = fs::remove_file(path);
+    }
+}
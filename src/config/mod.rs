@@ -3,31 +3,132 @@ use std::fs;
 use std::path::Path;
 use anyhow::{Result};
 use crate::core::error::DownloadError;
+use crate::core::task::resolver;
+use crate::i18n::{self, t, tf, Lang, Msg};
+use crate::utils::{secrets, size};
 use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+pub mod clients;
+pub mod edit;
+pub mod preset;
+pub mod push;
+pub mod retry;
+pub mod rules;
+
+pub use clients::ClientQuota;
+pub use preset::Preset;
+pub use push::PushTarget;
+pub use retry::{RetryPolicies, RetryPolicy, RetryRule};
+pub use rules::UrlRule;
 
 /// 配置结构体
+///
+/// 缺失的字段使用默认值，保证旧版本的配置文件可以继续加载。
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct Config {
-    /// 下载速度限制（KB/s），0 表示不限速
+    /// 下载速度限制（KB/s），0 表示不限速，也可以写成 "2M" 等带单位的字符串
+    #[serde(deserialize_with = "size::deserialize_rate_kb")]
     pub speed_limit_kb: u64,
+    /// 所有任务合计的速度限制（KB/s），按任务的权重分配，0 表示不限速
+    #[serde(deserialize_with = "size::deserialize_rate_kb")]
+    pub total_speed_limit_kb: u64,
+    /// 下载完成后合并分块、计算校验和时读写磁盘的速度限制（KB/s），0 表示不限速
+    #[serde(deserialize_with = "size::deserialize_rate_kb")]
+    pub finalize_speed_limit_kb: u64,
     /// 默认下载目录
     pub download_dir: String,
+    /// 分块临时文件和断点续传信息的存放目录，空字符串表示下载目录下的 `.multidown`
+    pub temp_dir: String,
+    /// 会话目录：会话文件、会话锁、控制通道、下载历史、流量统计和各种缓存的存放目录，相对路径从当前目录算起
+    pub state_dir: String,
     /// 默认线程数
     pub thread_count: usize,
     /// 最大并发下载数
     pub max_concurrent_downloads: usize,
+    /// 小文件模式：不探测文件信息、不分块，每个文件直接一个 GET 请求，复用同一主机的 keep-alive 连接
+    pub small_files: bool,
+    /// 小文件模式下同时下载的文件数，代替 `max_concurrent_downloads`
+    pub small_files_concurrency: usize,
+    /// 文件信息探测结果的缓存时间（秒），这段时间内再次下载同一个 URL 时不再发送 HEAD，0 表示不缓存
+    #[serde(deserialize_with = "size::deserialize_duration_secs")]
+    pub probe_cache_ttl: u64,
     /// 网络超时时间（秒）
     pub timeout: u64,
+    /// 单个任务从开始下载起的总时间限制（秒），暂停期间不计时，超过时任务失败，0 表示不限制，也可以写成 "30m" 等
+    #[serde(deserialize_with = "size::deserialize_duration_secs")]
+    pub timeout_total: u64,
     /// User-Agent
     pub user_agent: String,
+    /// 请求返回 403 时依次换用的 User-Agent，`"browser"` 表示内置的浏览器 User-Agent
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fallback_user_agents: Vec<String>,
+    /// 下载 rsync:// 地址时调用的 rsync 程序
+    pub rsync_program: String,
+    /// 建立连接后设置 TCP_NODELAY
+    pub tcp_nodelay: bool,
+    /// TCP 接收缓冲区大小（字节），0 表示使用系统默认值，支持带单位的字符串
+    #[serde(deserialize_with = "size::deserialize_size")]
+    pub tcp_recv_buffer: usize,
+    /// 连接绑定的网络接口（仅 Linux），空字符串表示不绑定
+    pub bind_interface: String,
+    /// 连接使用的本地源地址，空字符串表示由系统选择
+    pub source_address: String,
+    /// DNS-over-HTTPS 服务地址（JSON 格式），空字符串表示使用系统解析器
+    pub dns_resolver: String,
+    /// DNS 解析结果缓存的时间（秒），0 表示不缓存
+    pub dns_cache_ttl: u64,
+    /// 主机有多个地址时，新连接轮流使用不同的地址
+    pub spread_addresses: bool,
+    /// HTTP 代理地址 `http://[用户名:密码@]主机:端口`，所有请求通过 CONNECT 隧道发送，
+    /// 密码可以写成 `{secret:<名称>}`；空字符串表示直接连接
+    pub proxy: String,
     /// 是否启用断点续传
     pub enable_resume: bool,
     /// 是否启用分块下载
     pub enable_chunked_download: bool,
-    /// 分块大小（字节）
+    /// 上报完成前 fsync 文件和所在目录，保证完成的文件在崩溃或断电后依然存在
+    pub fsync_on_complete: bool,
+    /// 完成后把文件的修改时间设为服务器返回的 Last-Modified
+    pub preserve_mtime: bool,
+    /// 完成后把文件设为只读
+    pub read_only_output: bool,
+    /// 完成后设置的文件权限（八进制，如 "644"），空字符串表示不修改，只在 Unix 上生效
+    pub output_file_mode: String,
+    /// 分块大小（字节），也可以写成 "4MiB" 等带单位的字符串
+    #[serde(deserialize_with = "size::deserialize_size")]
     pub chunk_size: usize,
-    /// 最小分块大小（字节）
+    /// 最小分块大小（字节），同样支持带单位的字符串
+    #[serde(deserialize_with = "size::deserialize_size")]
     pub min_chunk_size: usize,
+    /// 根据实测吞吐量按主机调整分块大小和连接数，默认关闭（`--fast` 预设会开启）
+    pub adaptive_chunking: bool,
+    /// 自适应调整时分块大小的上限（字节），下限为 `chunk_size`
+    #[serde(deserialize_with = "size::deserialize_size")]
+    pub max_chunk_size: usize,
+    /// 自适应调整时每个任务连接数的上限
+    pub max_thread_count: usize,
+    /// 恢复分块下载时重新计算已完成块的 CRC32，与记录不一致的块重新下载
+    pub verify_resume: bool,
+    /// 校验和文件的地址，`{url}` 替换为下载地址（如 `{url}.sha256`）；设置后没有指定校验和的任务完成时
+    /// 从这里读取 SHA-256 并校验，空字符串表示不读取，通常写在 URL 规则中
+    pub checksum_url: String,
+    /// 目标文件已存在且小于服务器上的文件时，从它的末尾续传而不是报“文件已存在”
+    pub continue_partial: bool,
+    /// 分块大致按文件顺序下载，开头部分边下载边写入 `<文件名>.part`，可以先用播放器打开
+    pub stream_order: bool,
+    /// 单个文件的大小上限（字节），超过时拒绝或中止下载，0 表示不限制，支持带单位的字符串
+    #[serde(deserialize_with = "size::deserialize_size")]
+    pub max_file_size: u64,
+    /// 单次运行下载总量的上限（字节），0 表示不限制，支持带单位的字符串
+    #[serde(deserialize_with = "size::deserialize_size")]
+    pub run_quota: u64,
+    /// 同一主机在所有任务中连续失败多少次后暂停发往它的请求，0 表示不熔断
+    pub circuit_breaker_threshold: usize,
+    /// 熔断后暂停请求的时间（秒），也可以写成 "1m" 等
+    #[serde(deserialize_with = "size::deserialize_duration_secs")]
+    pub circuit_breaker_cooldown: u64,
     /// 重试次数
     pub retry_count: usize,
     /// 重试延迟（秒）
@@ -36,40 +137,140 @@ pub struct Config {
     pub retry_max_delay: u64,
     /// 启动时自动恢复
     pub auto_resume_on_startup: bool,
+    /// 会话中最多保留的已结束（完成或取消）任务数，超出时最早结束的移入下载历史，0 表示不限制
+    pub keep_finished_tasks: usize,
+    /// 已结束的任务在会话中保留的天数，0 表示不限制
+    pub keep_finished_days: u64,
+    /// 任务完成或失败时发送桌面通知
+    pub notify_on_finish: bool,
+    /// 任务成功后执行的命令，空字符串表示不执行
+    pub on_complete: String,
+    /// 任务失败后执行的命令，空字符串表示不执行
+    pub on_failure: String,
+    /// Rhai 脚本的路径，可以改写 URL、加请求头、拒绝任务和响应任务结束，空字符串表示不使用
+    pub script: String,
+    /// 守护进程远程接口（网页控制台、gRPC）的访问令牌，请求在 `authorization: Bearer <令牌>` 中携带，
+    /// 可以写成 `{secret:<名称>}`；空字符串表示不需要令牌
+    pub serve_token: String,
+    /// 守护进程远程接口的 TLS 证书链（PEM），与 `serve_tls_key` 一起设置，空字符串表示不使用 TLS
+    pub serve_tls_cert: String,
+    /// 与 `serve_tls_cert` 对应的私钥（PEM，PKCS#8、PKCS#1 或 SEC1）
+    pub serve_tls_key: String,
+    /// 按错误分类的重试策略，未设置的项沿用 `retry_count` 等全局设置（表要写在普通键之后）
+    #[serde(skip_serializing_if = "RetryPolicies::is_empty")]
+    pub retry_policies: RetryPolicies,
+    /// 错误分类规则，按顺序匹配，调整错误归入的重试分类
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub retry_rules: Vec<RetryRule>,
+    /// 按主机指定的分享链接解析器（`google-drive`、`dropbox`、`sourceforge`、`none`），补充或覆盖内置的主机
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub resolvers: BTreeMap<String, String>,
+    /// 守护进程的客户端和它们的配额，按 API 密钥区分提交任务的客户端，见 [`clients`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub clients: Vec<ClientQuota>,
+    /// 任务完成或失败时推送到 Telegram、Matrix 的目标，可以按标签筛选，见 [`push`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub push: Vec<PushTarget>,
+    /// URL 规则，按顺序匹配并覆盖部分配置（必须放在最后，TOML 的表数组要写在普通键之后）
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<UrlRule>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             speed_limit_kb: 0, // 默认不限速
+            total_speed_limit_kb: 0,
+            finalize_speed_limit_kb: 0,
             download_dir: "./downloads".to_string(),
+            temp_dir: String::new(),
+            state_dir: "downloads".to_string(),
             thread_count: 4,
             max_concurrent_downloads: 3,
+            small_files: false,
+            small_files_concurrency: 32,
+            probe_cache_ttl: 3600,
             timeout: 30,
+            timeout_total: 0,
             user_agent: "MultiDown/1.0".to_string(),
+            fallback_user_agents: Vec::new(),
+            rsync_program: "rsync".to_string(),
+            tcp_nodelay: false,
+            tcp_recv_buffer: 0,
+            bind_interface: String::new(),
+            source_address: String::new(),
+            dns_resolver: String::new(),
+            dns_cache_ttl: 300,
+            spread_addresses: false,
+            proxy: String::new(),
             enable_resume: true,
             enable_chunked_download: true,
+            fsync_on_complete: true,
+            preserve_mtime: false,
+            read_only_output: false,
+            output_file_mode: String::new(),
             chunk_size: 8192,
             min_chunk_size: 1024,
+            adaptive_chunking: false,
+            max_chunk_size: 16 * 1024 * 1024,
+            max_thread_count: 16,
+            stream_order: false,
+            continue_partial: false,
+            verify_resume: false,
+            checksum_url: String::new(),
+            max_file_size: 0,
+            run_quota: 0,
+            circuit_breaker_threshold: 8,
+            circuit_breaker_cooldown: 30,
             retry_count: 3,
             retry_delay: 5,
             retry_max_delay: 60,
             auto_resume_on_startup: true,
+            keep_finished_tasks: 500,
+            keep_finished_days: 0,
+            notify_on_finish: false,
+            on_complete: String::new(),
+            on_failure: String::new(),
+            script: String::new(),
+            serve_token: String::new(),
+            serve_tls_cert: String::new(),
+            serve_tls_key: String::new(),
+            retry_policies: RetryPolicies::default(),
+            retry_rules: Vec::new(),
+            resolvers: BTreeMap::new(),
+            clients: Vec::new(),
+            push: Vec::new(),
+            rules: Vec::new(),
         }
     }
 }
 
 impl Config {
-    /// 加载配置文件
+    /// 加载配置文件（宽松模式）
     pub fn load(path: &str) -> Result<Self, DownloadError> {
+        Config::load_with_mode(path, false)
+    }
+
+    /// 加载配置文件
+    ///
+    /// 解析失败时，严格模式直接返回包含出错行列的错误，不修改文件；
+    /// 宽松模式先把原文件备份为 `<path>.broken-<时间>`，再用默认配置重新生成。
+    pub fn load_with_mode(path: &str, strict: bool) -> Result<Self, DownloadError> {
         if Path::new(path).exists() {
             let content = fs::read_to_string(path)
-                .map_err(|e| DownloadError::IoError(e.to_string().into()))?;
-            // 尝试解析TOML
+                .map_err(|e| DownloadError::io_error_with_context("读取配置文件", e))?;
+            // 尝试解析TOML，错误信息中包含出错的行、列和字段
             match toml::from_str(&content) {
                 Ok(config) => Ok(config),
+                Err(e) if strict => Err(DownloadError::Unknown(
+                    tf(Msg::ConfigParseFailed, &[&path, &e.to_string().trim_end()]).into(),
+                )),
                 Err(e) => {
-                    eprintln!("配置文件格式错误: {}，将使用默认配置", e);
+                    eprintln!("{}", tf(Msg::ConfigParseFailed, &[&path, &e.to_string().trim_end()]));
+                    let backup = format!("{}.broken-{}", path, chrono::Local::now().format("%Y%m%d%H%M%S"));
+                    fs::copy(path, &backup)
+                        .map_err(|e| DownloadError::io_error_with_context("无法备份配置文件", e))?;
+                    eprintln!("{}", tf(Msg::ConfigBackedUp, &[&backup]));
                     let config = Config::default();
                     Config::save_with_tutorial(&config, path)?;
                     Ok(config)
@@ -86,19 +287,26 @@ impl Config {
     pub fn save_with_tutorial(&self, path: &str) -> Result<(), DownloadError> {
         if let Some(parent) = Path::new(path).parent() {
             std::fs::create_dir_all(parent)
-                .map_err(|e| DownloadError::IoError(e.to_string().into()))?;
+                .map_err(|e| DownloadError::io_error_with_context("创建配置目录", e))?;
         }
         let tutorial_content = Config::generate_tutorial_content();
         let config_content = toml::to_string_pretty(self)
             .map_err(|e| DownloadError::Unknown(format!("无法序列化配置: {}", e).into()))?;
         let full_content = format!("{}\n\n{}", tutorial_content, config_content);
         std::fs::write(path, full_content)
-            .map_err(|e| DownloadError::IoError(e.to_string().into()))?;
+            .map_err(|e| DownloadError::io_error_with_context("写入配置文件", e))?;
         Ok(())
     }
 
-    /// 生成配置文件教程内容（静态方法）
+    /// 生成配置文件教程内容（静态方法），语言随界面语言变化
     fn generate_tutorial_content() -> String {
+        match i18n::current() {
+            Lang::Zh => Config::tutorial_zh(),
+            Lang::En => Config::tutorial_en(),
+        }
+    }
+
+    fn tutorial_zh() -> String {
         r#"# MultiDown 配置文件
 # ====================
 # 
@@ -112,6 +320,9 @@ impl Config {
 #
 # 命令行参数会覆盖配置文件中的设置，优先级：命令行 > 配置文件 > 默认值
 #
+# 文件格式错误时，原文件会备份为 multidown.conf.broken-<时间> 后重新生成；
+# 使用 --strict-config 时则直接报错（包含出错的行和字段）并拒绝启动，不修改文件
+#
 # 使用示例：
 #   multidown https://example.com/file.zip                    # 使用默认配置
 #   multidown -l 1000 https://example.com/file.zip           # 限制速度1MB/s
@@ -121,68 +332,312 @@ impl Config {
 # ==================== 下载设置 ====================
 
 # 下载速度限制（KB/s），0 表示不限速
-# 示例：1024 = 1MB/s, 5120 = 5MB/s
-speed_limit_kb = 0
+# 示例：1024 = 1MB/s, 5120 = 5MB/s，也可以写成 "1M"、"512K" 等带单位的字符串
+# speed_limit_kb = 0
+
+# 所有任务合计的速度限制（KB/s），0 表示不限速
+# 同时下载多个文件时按任务的权重分配（默认由优先级决定，URL 列表中可以用 weight= 单独指定），
+# 没用完的份额分给其他任务，大文件不会挤占小文件的带宽；speed_limit_kb 仍是单个任务的上限
+# total_speed_limit_kb = 0
+
+# 合并分块、计算校验和时读写磁盘的速度限制（KB/s），0 表示不限速
+# 大文件下载完成后的合并和校验会占满磁盘，限速后其他正在下载的任务不会被拖慢
+# finalize_speed_limit_kb = 0
 
 # 默认下载目录
 # 支持相对路径和绝对路径
-download_dir = "./downloads"
+# download_dir = "./downloads"
+
+# 分块临时文件和断点续传信息的存放目录
+# 留空时使用下载目录下的 .multidown，与目标文件在同一文件系统，合并后可以直接重命名；
+# 也可以指定专门的临时磁盘
+# temp_dir = ""
+
+# 会话目录：会话文件（tasks.json）、会话锁、控制通道、下载历史、流量统计和探测缓存都放在这里
+# 相对路径从当前目录算起；同一会话目录同时只能有一个进程在下载
+# state_dir = "downloads"
 
 # 默认线程数（每个下载任务使用的线程数）
 # 建议值：2-16，根据网络环境调整
-thread_count = 4
+# thread_count = 4
 
 # 最大并发下载数（同时进行的下载任务数）
 # 建议值：1-5，避免过多任务影响性能
-max_concurrent_downloads = 3
+# max_concurrent_downloads = 3
+
+# 小文件模式（--small-files）：下载成千上万个小文件时使用
+# 不发送 HEAD 探测文件信息、不分块，每个文件直接发送一个 GET 请求，复用同一主机的 keep-alive 连接；
+# 同时下载 small_files_concurrency 个文件（代替 max_concurrent_downloads）。混在其中的大文件只用一个连接下载
+# small_files = false
+# small_files_concurrency = 32
+
+# 文件信息探测结果的缓存时间（秒，也可以写成 "30m"、"2h"），0 表示不缓存
+# 获取到的文件大小、ETag、是否支持 Range 按 URL 保存在会话目录的 probes.json，
+# 这段时间内重新下载同一批 URL 或恢复任务时不再逐个发送 HEAD；任务失败时删除该 URL 的缓存
+# probe_cache_ttl = 3600
 
 # ==================== 网络设置 ====================
 
 # 网络超时时间（秒）
 # 如果下载在指定时间内没有响应，会重试
-timeout = 30
+# timeout = 30
+
+# 单个任务的总时间限制，从开始下载算起，暂停期间不计时，超过时任务失败（分块下载的进度保留，可以用 retry-failed 继续）
+# 0 表示不限制，可以写成 "30m"、"1h30m"，适合不能无限等待的 CI 任务
+# timeout_total = 0
 
 # User-Agent 字符串
 # 某些服务器可能需要特定的 User-Agent
-user_agent = "MultiDown/1.0"
+# user_agent = "MultiDown/1.0"
+
+# 获取文件信息返回 403 时依次换用的 User-Agent，成功的那个会用于整个下载并记录在日志中
+# "browser" 表示内置的浏览器 User-Agent，留空表示不换用
+# fallback_user_agents = ["browser", "Wget/1.21"]
+
+# 下载 rsync:// 地址时调用的 rsync 程序，可以写完整路径
+# rsync_program = "rsync"
+
+# 分享链接解析器：开始下载前把分享页面的链接转换成直链，按主机（含子域名）选择
+# 内置 drive.google.com、docs.google.com → google-drive，dropbox.com → dropbox，sourceforge.net → sourceforge
+# 可以为其他主机指定解析器，"none" 关闭某个主机的解析。表需要写在文件末尾，示例：
+#   [resolvers]
+#   "drive.example.com" = "google-drive"
+#   "sourceforge.net" = "none"
+
+# TCP 选项
+# tcp_nodelay：关闭 Nagle 算法，请求立即发出
+# tcp_recv_buffer：接收缓冲区大小，高延迟链路上调大可以提高单连接速度，0 表示系统默认
+# bind_interface：连接绑定到指定网卡（仅 Linux，通常需要 CAP_NET_RAW 权限），如 "eth1"
+# source_address：连接使用的本地地址，多网卡/多出口时指定走哪条链路，如 "192.168.1.10"
+# tcp_nodelay = false
+# tcp_recv_buffer = "4MiB"
+# bind_interface = ""
+# source_address = ""
+
+# DNS 设置
+# 任务排队时会提前解析主机名，结果（包括解析失败，最多 30 秒）按主机缓存 dns_cache_ttl 秒，0 表示不缓存
+# dns_resolver 设置为 DNS-over-HTTPS 地址时通过 DoH 解析，空字符串表示使用系统解析器，
+# 例如 "https://cloudflare-dns.com/dns-query" 或 "https://dns.google/resolve"
+# dns_resolver = ""
+# dns_cache_ttl = 300
+
+# 主机有多个 A/AAAA 记录时，让同一主机的新连接轮流从不同的地址开始连接，
+# 分块下载的多个连接分散到多台服务器上，可以绕过部分 CDN 按连接的限速；
+# 连接数仍然受 thread_count 等限制，某个地址连不上时自动尝试其余地址
+# spread_addresses = false
+
+# HTTP 代理，格式为 http://[用户名:密码@]主机:端口，所有请求（包括 HTTPS）通过 CONNECT 隧道发送；
+# 密码不要明文写在这里，先用 multidown secret set <名称> 存入系统密钥环，再用 {secret:<名称>} 引用，
+# 例如 "http://alice:{secret:proxy-password}@proxy.example.com:3128"
+# proxy = ""
 
 # ==================== 高级功能 ====================
 
 # 是否启用断点续传
 # 启用后，下载中断可以从断点继续
-enable_resume = true
+# enable_resume = true
 
 # 是否启用分块下载
 # 启用后，大文件会被分成多个块并行下载
-enable_chunked_download = true
+# enable_chunked_download = true
+
+# 完成时是否 fsync 落盘
+# 启用后，任务报告完成前会把文件和所在目录同步到磁盘，崩溃或断电后已完成的文件不会丢失
+# 下游有自动化处理流程时建议保持开启；对速度敏感且可以接受风险时可以关闭
+# fsync_on_complete = true
+
+# 完成后设置文件属性（镜像脚本常用）
+# preserve_mtime：把修改时间设为服务器返回的 Last-Modified，与 wget --timestamping 一致
+# output_file_mode：文件权限，八进制，如 "644"，空字符串表示不修改，只在 Unix 上生效
+# read_only_output：把文件设为只读（在 output_file_mode 之后应用）
+# preserve_mtime = false
+# output_file_mode = ""
+# read_only_output = false
 
 # 分块大小（字节）
 # 建议值：4096-32768，太小影响性能，太大会占用更多内存
-chunk_size = 8192
+# 支持单位（按 1024 进制）：chunk_size = "32K"、"4MiB"
+# chunk_size = 8192
 
 # 最小分块大小（字节）
 # 只有文件大小超过此值才会使用分块下载
-min_chunk_size = 1024
+# min_chunk_size = 1024
+
+# 自适应分块
+# 启用后按实测吞吐量调整：分块完成得太快时加大分块，太慢时减小；
+# 增加连接数能提高总速度时继续增加，否则退回。调整结果按主机保存，下次下载同一主机时沿用。
+# 默认关闭，--fast 预设会开启
+# adaptive_chunking = false
+
+# 自适应调整的上限：分块大小不超过 max_chunk_size（不小于 chunk_size），
+# 连接数不超过 max_thread_count
+# max_chunk_size = "16MiB"
+# max_thread_count = 16
+
+# 恢复时校验已下载的块
+# 每个块下载完成时记录它的 CRC32 和 SHA-256；启用后恢复下载前重新计算已完成的块，
+# 与记录不一致（例如上次崩溃时没有写完）的块重新下载。块文件较大时恢复前需要读一遍已下载的数据
+# verify_resume = false
+
+# 从校验和文件读取 SHA-256，下载完成后校验（URL 列表中已经用 sha256= 给出校验和的任务不读取）
+# {url} 替换为下载地址，例如 "{url}.sha256"；也可以是 sha256sum 格式的列表，如 "https://mirror.example.org/SHA256SUMS"，
+# 按下载地址最后一段的文件名查找。一般只对部分地址设置，写在下面的 URL 规则中
+# checksum_url = ""
+
+# 续传已有的部分文件（其他工具中断留下的文件等）
+# 目标文件已存在且小于服务器上的文件时，用 Range 请求从它的末尾继续，而不是报“文件已存在”；
+# 需要服务器支持 Range 请求，Last-Modified 晚于本地文件时拒绝续传
+# continue_partial = false
+
+# 按文件顺序下载（边下边播）
+# 分块大致按文件顺序调度，已连续下载完成的开头部分边下载边写入 <文件名>.part，
+# 可以先用播放器打开；并行效率略有下降
+# stream_order = false
+
+# 大小限制，0 表示不限制，支持单位
+# max_file_size：单个文件的上限，获取文件信息时超过的任务直接失败，大小未知的下载超过时中止
+# run_quota：本次运行所有任务下载量之和的上限，失败和取消的任务不计入
+# max_file_size = "10GiB"
+# run_quota = "50GiB"
 
 # ==================== 重试设置 ====================
 
 # 重试次数
 # 网络错误时的重试次数
-retry_count = 3
+# retry_count = 3
 
 # 重试延迟（秒）
 # 第一次重试前的等待时间
-retry_delay = 5
+# retry_delay = 5
 
 # 最大重试延迟（秒）
 # 重试延迟的最大值（使用指数退避）
-retry_max_delay = 60
+# retry_max_delay = 60
+
+# 按主机熔断
+# 同一主机在所有任务中连续失败（连接失败、超时、5xx、408、429）circuit_breaker_threshold 次后，
+# 发往该主机的新请求等待 circuit_breaker_cooldown 秒再发出，而不是让排队的任务各自把重试次数耗光；
+# 冷却后的请求成功即恢复。circuit_breaker_threshold = 0 表示不熔断
+# circuit_breaker_threshold = 8
+# circuit_breaker_cooldown = 30
+
+# 按错误分类的重试策略（可选），每类可以设置 count、delay、max_delay，未设置的沿用上面三项
+# 分类：network（连接失败、连接中断）、timeout（超时）、server（5xx、408、429）、
+#       client（其他 4xx，默认不重试）、io（本地文件读写错误）
+# 与 URL 规则一样需要写在文件末尾，示例：
+#   [retry_policies.network]
+#   count = 10
+#   delay = 2
+#
+#   [retry_policies.client]
+#   count = 0
+
+# 错误分类规则（可选），调整错误归入上面哪个分类，按顺序匹配，第一条匹配的规则生效
+# status 为状态码或范围，kinds 为错误类别（与报告中的 error_kind 相同），满足其一即匹配；
+# hosts 限定只对某些主机生效；class 为分类名，never 表示不重试。同样写在文件末尾，示例：
+#   [[retry_rules]]
+#   hosts = ["flaky.example.com"]
+#   status = ["403"]
+#   class = "server"
+#
+#   [[retry_rules]]
+#   status = ["501", "505-599"]
+#   class = "never"
 
 # ==================== 启动设置 ====================
 
 # 启动时自动恢复未完成的下载
 # 启用后，程序启动时会自动恢复上次未完成的下载
-auto_resume_on_startup = true
+# auto_resume_on_startup = true
+
+# 会话中保留的已结束任务
+# 完成和取消的任务在会话（multidown status）中保留到超出 keep_finished_tasks 个或超过 keep_finished_days 天，
+# 之后移入下载历史（multidown history 仍可查询）；失败的任务一直保留，供 retry-failed 使用。0 表示不限制
+# keep_finished_tasks = 500
+# keep_finished_days = 0
+
+# ==================== 通知设置 ====================
+
+# 任务完成或失败时发送桌面通知
+# Linux 需要 notify-send，macOS 使用 osascript，Windows 使用系统 Toast
+# notify_on_finish = false
+
+# 任务完成或失败时推送到手机（可选，可以配置多个，与桌面通知互不影响）
+# service 为 telegram 时需要 bot_token 和 chat_id，为 matrix 时需要 webhook_url（例如 matrix-hookshot 的通用 webhook）；
+# 令牌和 webhook 地址建议以 {secret:<名称>} 引用。tags 不为空时只推送带有其中任一标签的任务，
+# failures_only = true 时只推送失败的任务。同样写在文件末尾，示例：
+#   [[push]]
+#   service = "telegram"
+#   bot_token = "{secret:telegram-bot}"
+#   chat_id = "123456789"
+#   tags = ["important"]
+
+# ==================== 钩子命令 ====================
+
+# 任务结束后通过系统 shell 执行的命令（on_complete 成功后执行，on_failure 失败后执行）
+# 可用变量：{path} 保存路径，{url} 下载地址，{status} 任务状态，{error} 错误信息
+# 变量会自动加引号，也可以通过环境变量 MULTIDOWN_PATH 等读取
+# 示例：
+#   on_complete = "unzip -o {path} -d ~/media"
+#   on_failure = "echo {url} >> failed.txt"
+
+# Rhai 脚本（https://rhai.rs），处理不适合写进配置的站点逻辑，定义了哪些函数就调用哪些：
+#   fn rewrite_url(url)      创建任务时改写下载地址，返回新的地址
+#   fn headers(url)          返回要附加的请求头，例如 #{ "X-Token": "..." }
+#   fn allow(url, file)      返回 false 或一段说明时拒绝创建任务
+#   fn on_complete(task)     任务完成后调用，task 包含 id、url、path、status、error
+#   fn on_failure(task)      任务失败后调用
+# 脚本中可以使用 regex_match(text, pattern)、regex_replace(text, pattern, replacement)，print 写入日志
+# script = "hooks.rhai"
+
+# ==================== 守护进程 ====================
+
+# multidown serve 提供的远程接口（网页控制台 --web、gRPC --listen）默认不认证，只应监听本机地址；
+# 监听其他地址时必须设置访问令牌或下面的客户端，否则守护进程拒绝启动
+# 令牌通过 authorization: Bearer <令牌> 携带（网页控制台在页面上输入），持有令牌的请求与守护进程的所有者一样不受限制，
+# 建议用 multidown secret set 存入系统密钥环后以 {secret:<名称>} 引用
+# serve_token = "{secret:multidown-serve}"
+
+# 远程接口的 TLS 证书链和私钥（PEM），两项一起设置后只接受 TLS 连接，令牌不会以明文传输
+# serve_tls_cert = "/etc/multidown/server.crt"
+# serve_tls_key = "/etc/multidown/server.key"
+
+# ==================== 客户端配额 ====================
+
+# 守护进程（multidown serve）作为共享服务时，按 API 密钥区分提交任务的客户端（可选）
+# 任务记录提交它的客户端，统计按客户端分开；同一客户端同时下载的任务不超过 max_concurrent_downloads，
+# 合计速度不超过 speed_limit_kb（0 表示不限制），同时仍受上面的全局设置约束
+# 配置了客户端后，gRPC 请求必须在 x-api-key 元数据中携带密钥；本地控制通道的请求可以在 api_key 字段中携带，
+# 不带密钥的请求属于守护进程的所有者，不受配额限制。同样写在文件末尾，示例：
+#   [[clients]]
+#   name = "ci"
+#   api_key = "{secret:multidown-ci}"
+#   max_concurrent_downloads = 2
+#   speed_limit_kb = "4M"
+
+# ==================== URL 规则 ====================
+
+# 按正则表达式匹配 URL，为匹配的任务覆盖部分配置，创建任务时按顺序求值（后面的覆盖前面的）
+# 可覆盖：thread_count、speed_limit_kb、enable_chunked_download、chunk_size、timeout、retry_count、user_agent、checksum_url、headers、basic_auth
+# 请求头中的令牌等敏感信息不要明文写在这里：先用 multidown secret set <名称> 存入系统密钥环，
+# 再用 {secret:<名称>} 引用
+# 规则需要写在文件末尾（TOML 的表数组必须位于普通配置项之后），示例：
+#   [[rules]]
+#   pattern = '\.iso$'
+#   thread_count = 16
+#   checksum_url = "{url}.sha256"
+#
+#   [[rules]]
+#   pattern = 'slow-host\.com'
+#   enable_chunked_download = false
+#
+#   [[rules]]
+#   pattern = '^https://api\.example\.com/'
+#   headers = { Authorization = "Bearer {secret:example-token}" }
+#
+#   [[rules]]
+#   pattern = '^https://files\.example\.org/'
+#   basic_auth = "alice:{secret:files-password}"
 
 # ==================== 使用说明 ====================
 #
@@ -242,18 +697,454 @@ auto_resume_on_startup = true
 #   max_concurrent_downloads = 1-2
 
 # ==================== 配置项说明 ====================
+# 上面的示例值仅供参考，实际生效的配置项如下（可用 multidown config set 修改）
 "#.to_string()
     }
 
+    fn tutorial_en() -> String {
+        r#"# MultiDown configuration file
+# =============================
+#
+# This is a TOML file that controls how the MultiDown download manager behaves.
+# Edit the settings below as needed and save the file.
+#
+# Config file locations:
+# - Windows: %APPDATA%/multidown/multidown.conf
+# - macOS: ~/Library/Application Support/multidown/multidown.conf
+# - Linux: ~/.config/multidown/multidown.conf
+#
+# Command-line options override this file. Precedence: command line > config file > defaults
+#
+# If this file cannot be parsed it is backed up as multidown.conf.broken-<time> and regenerated;
+# with --strict-config MultiDown refuses to start instead and reports the failing line and field
+#
+# Examples:
+#   multidown https://example.com/file.zip                    # use the defaults
+#   multidown -l 1000 https://example.com/file.zip           # limit speed to ~1MB/s
+#   multidown -t 8 https://example.com/file.zip              # use 8 threads
+#   multidown -d /path/to/downloads https://example.com/file.zip  # set the download directory
+
+# ==================== Download ====================
+
+# Speed limit (KB/s), 0 means unlimited
+# Examples: 1024 = 1MB/s, 5120 = 5MB/s; strings with units such as "1M" or "512K" also work
+# speed_limit_kb = 0
+
+# Combined speed limit for all tasks (KB/s), 0 means unlimited
+# Shared among simultaneous downloads by weight (from the priority by default, or weight= in URL lists);
+# unused shares go to other tasks, so a huge file does not starve small ones. speed_limit_kb still caps each task
+# total_speed_limit_kb = 0
+
+# Disk read/write limit (KB/s) while merging chunks and computing checksums, 0 means unlimited
+# Merging and verifying a large file can saturate the disk; a limit keeps other active downloads going
+# finalize_speed_limit_kb = 0
+
+# Default download directory
+# Relative and absolute paths are both supported
+# download_dir = "./downloads"
+
+# Where chunk temp files and resume metadata are kept
+# Empty means .multidown inside the download directory, on the same filesystem as the target
+# so the merged file can simply be renamed into place; a dedicated scratch disk also works
+# temp_dir = ""
+
+# Session directory: the session file (tasks.json), session lock, control channel, download history,
+# usage log and probe cache live here. Relative paths start from the current directory;
+# only one process at a time can download with the same session directory
+# state_dir = "downloads"
+
+# Default thread count (threads per download task)
+# Suggested: 2-16, depending on your network
+# thread_count = 4
+
+# Maximum concurrent downloads (tasks running at the same time)
+# Suggested: 1-5, too many tasks hurt performance
+# max_concurrent_downloads = 3
+
+# Small-file mode (--small-files), for batches of thousands of small files:
+# no HEAD probe and no chunking; each file is a single GET over the host's pooled keep-alive connections,
+# with small_files_concurrency files at a time (instead of max_concurrent_downloads). Large files in the batch use one connection
+# small_files = false
+# small_files_concurrency = 32
+
+# How long probe results are cached (seconds, or "30m", "2h"); 0 disables the cache
+# File size, ETag and range support are kept per URL in probes.json in the session directory, so re-running the same batch
+# or resuming tasks within this time skips the HEAD requests; a failed task drops its URL from the cache
+# probe_cache_ttl = 3600
+
+# ==================== Network ====================
+
+# Network timeout (seconds)
+# A download that does not respond within this time is retried
+# timeout = 30
+
+# Time limit for a single task, counted from the start of the download (paused time excluded); the task fails
+# when it is exceeded (chunk progress is kept, so retry-failed can continue it)
+# 0 disables the limit; "30m" and "1h30m" are accepted. Useful for CI jobs that must not hang
+# timeout_total = 0
+
+# User-Agent string
+# Some servers require a specific User-Agent
+# user_agent = "MultiDown/1.0"
+
+# User-Agents tried in order when fetching file info returns 403; the one that works is
+# used for the whole download and logged. "browser" is a built-in browser User-Agent;
+# empty disables the fallback
+# fallback_user_agents = ["browser", "Wget/1.21"]
+
+# rsync program used for rsync:// URLs; a full path also works
+# rsync_program = "rsync"
+
+# Share-link resolvers turn share-page links into direct URLs before downloading, chosen by
+# host (subdomains included). Built in: drive.google.com and docs.google.com → google-drive,
+# dropbox.com → dropbox, sourceforge.net → sourceforge. Other hosts can be mapped to a resolver
+# and "none" turns one off. The table goes at the end of the file, for example:
+#   [resolvers]
+#   "drive.example.com" = "google-drive"
+#   "sourceforge.net" = "none"
+
+# TCP options
+# tcp_nodelay: disable Nagle's algorithm so requests go out immediately
+# tcp_recv_buffer: receive buffer size; a larger buffer speeds up single connections on
+#   high-latency links, 0 keeps the system default
+# bind_interface: bind connections to a network interface (Linux only, usually needs
+#   CAP_NET_RAW), e.g. "eth1"
+# source_address: local address for connections, picks the link on multi-homed hosts,
+#   e.g. "192.168.1.10"
+# tcp_nodelay = false
+# tcp_recv_buffer = "4MiB"
+# bind_interface = ""
+# source_address = ""
+
+# DNS
+# Hostnames are resolved ahead of time while tasks are queued; results (failures for at most
+# 30 seconds) are cached per host for dns_cache_ttl seconds, 0 disables the cache
+# Set dns_resolver to a DNS-over-HTTPS endpoint to resolve over DoH, empty uses the system resolver,
+# e.g. "https://cloudflare-dns.com/dns-query" or "https://dns.google/resolve"
+# dns_resolver = ""
+# dns_cache_ttl = 300
+
+# When a host has several A/AAAA records, start each new connection to it at a different
+# address so the connections of a chunked download spread across servers; this works around
+# per-connection throttling by some CDNs. Connection limits such as thread_count still apply,
+# and the remaining addresses are tried if one does not answer
+# spread_addresses = false
+
+# HTTP proxy as http://[user:password@]host:port; every request (HTTPS included) is sent through
+# a CONNECT tunnel. Keep the password out of this file: store it with multidown secret set <name>
+# and reference it as {secret:<name>}, e.g. "http://alice:{secret:proxy-password}@proxy.example.com:3128"
+# proxy = ""
+
+# ==================== Advanced ====================
+
+# Enable resuming interrupted downloads
+# enable_resume = true
+
+# Enable chunked download
+# Large files are split into chunks that are downloaded in parallel
+# enable_chunked_download = true
+
+# fsync on completion
+# When enabled, the file and its directory are flushed to disk before the task is reported
+# as completed, so finished downloads survive a crash or power loss
+# Keep it on when an automated pipeline consumes the downloads
+# fsync_on_complete = true
+
+# File attributes set on completion (useful for mirroring scripts)
+# preserve_mtime: set the modification time from the server's Last-Modified, like wget --timestamping
+# output_file_mode: permissions in octal such as "644", empty leaves them alone; Unix only
+# read_only_output: mark the file read-only (applied after output_file_mode)
+# preserve_mtime = false
+# output_file_mode = ""
+# read_only_output = false
+
+# Chunk size (bytes)
+# Suggested: 4096-32768; too small hurts throughput, too large uses more memory
+# Units are accepted (powers of 1024): chunk_size = "32K", "4MiB"
+# chunk_size = 8192
+
+# Minimum size for chunked download (bytes)
+# Only files larger than this are downloaded in chunks
+# min_chunk_size = 1024
+
+# Adaptive chunking
+# Tunes downloads from measured throughput: chunks that finish too quickly grow, slow ones shrink;
+# connections are added while they raise the total speed and removed otherwise.
+# The result is remembered per host and reused for the next download from the same host.
+# Off by default; the --fast preset turns it on
+# adaptive_chunking = false
+
+# Limits for adaptive tuning: chunks stay between chunk_size and max_chunk_size,
+# connections never exceed max_thread_count
+# max_chunk_size = "16MiB"
+# max_thread_count = 16
+
+# Verify downloaded chunks on resume
+# The CRC32 and SHA-256 of every chunk are recorded when it finishes; when enabled, finished chunks are
+# re-checked with CRC32 before resuming and any that no longer match (e.g. half-written during a crash)
+# are downloaded again. Resuming then has to read back everything already downloaded
+# verify_resume = false
+
+# Read the expected SHA-256 from a checksum file and verify it after the download (skipped for tasks
+# that already have a sha256= checksum in the URL list). {url} is replaced by the download URL,
+# e.g. "{url}.sha256"; sha256sum-style lists such as "https://mirror.example.org/SHA256SUMS" also work
+# and are searched by the last segment of the download URL. Usually set for some URLs in a URL rule below
+# checksum_url = ""
+
+# Continue existing partial files (e.g. left behind by another tool)
+# When the target file exists and is smaller than the remote file, resume from its end
+# with a Range request instead of failing with "file exists"; needs Range support and
+# is refused when the server's Last-Modified is newer than the local file
+# continue_partial = false
+
+# Download in file order (play while downloading)
+# Chunks are scheduled roughly in file order and the contiguous finished prefix is
+# written to <file name>.part as it arrives, so a player can open it early;
+# parallel efficiency drops slightly
+# stream_order = false
+
+# Size limits; 0 means unlimited, units are accepted
+# max_file_size: per-file limit; tasks over it fail once the file info is known,
+#   downloads of unknown size are aborted when they pass it
+# run_quota: limit on the total size of all downloads in one run; failed and cancelled tasks do not count
+# max_file_size = "10GiB"
+# run_quota = "50GiB"
+
+# ==================== Retry ====================
+
+# Retry count on network errors
+# retry_count = 3
+
+# Retry delay (seconds) before the first retry
+# retry_delay = 5
+
+# Maximum retry delay (seconds) when backing off exponentially
+# retry_max_delay = 60
+
+# Per-host circuit breaker
+# After circuit_breaker_threshold consecutive failures (connect failures, timeouts, 5xx, 408, 429)
+# against one host across all tasks, new requests to that host wait circuit_breaker_cooldown seconds
+# instead of every queued task burning its own retries; a successful request after the cooldown
+# closes the breaker again. circuit_breaker_threshold = 0 disables it
+# circuit_breaker_threshold = 8
+# circuit_breaker_cooldown = 30
+
+# Optional per-error-class retry policies; each class takes count, delay and max_delay and
+# falls back to the three settings above
+# Classes: network (connect failures, dropped connections), timeout, server (5xx, 408, 429),
+#          client (other 4xx, not retried by default), io (local file read/write errors)
+# Like URL rules they go at the end of the file, for example:
+#   [retry_policies.network]
+#   count = 10
+#   delay = 2
+#
+#   [retry_policies.client]
+#   count = 0
+
+# Optional classification rules that move errors into one of the classes above; they are
+# evaluated in order and the first match wins
+# status takes status codes or ranges, kinds takes error kinds (the error_kind in reports), and
+# either one matching is enough; hosts limits a rule to some hosts; class is a class name or
+# never (do not retry). These go at the end of the file too, for example:
+#   [[retry_rules]]
+#   hosts = ["flaky.example.com"]
+#   status = ["403"]
+#   class = "server"
+#
+#   [[retry_rules]]
+#   status = ["501", "505-599"]
+#   class = "never"
+
+# ==================== Startup ====================
+
+# Resume unfinished downloads automatically on startup
+# auto_resume_on_startup = true
+
+# Finished tasks kept in the session
+# Completed and cancelled tasks stay in the session (multidown status) until there are more than
+# keep_finished_tasks of them or they are older than keep_finished_days, then move to the download
+# history (still shown by multidown history); failed tasks are kept for retry-failed. 0 means unlimited
+# keep_finished_tasks = 500
+# keep_finished_days = 0
+
+# ==================== Notifications ====================
+
+# Show a desktop notification when a task completes or fails
+# Linux needs notify-send, macOS uses osascript, Windows uses system toasts
+# notify_on_finish = false
+
+# Push a message to your phone when a task completes or fails (optional, may be repeated, independent
+# of desktop notifications)
+# service = "telegram" needs bot_token and chat_id; service = "matrix" needs webhook_url (for example a
+# matrix-hookshot generic webhook). Reference the token and webhook URL as {secret:<name>}. A non-empty
+# tags list only pushes tasks carrying one of those tags, and failures_only = true only pushes failures.
+# These go at the end of the file too, for example:
+#   [[push]]
+#   service = "telegram"
+#   bot_token = "{secret:telegram-bot}"
+#   chat_id = "123456789"
+#   tags = ["important"]
+
+# ==================== Hooks ====================
+
+# Commands run through the system shell after a task ends (on_complete after success, on_failure after failure)
+# Variables: {path} output path, {url} source URL, {status} task status, {error} error message
+# Values are quoted automatically and are also available as MULTIDOWN_PATH etc. environment variables
+# Examples:
+#   on_complete = "unzip -o {path} -d ~/media"
+#   on_failure = "echo {url} >> failed.txt"
+
+# Rhai script (https://rhai.rs) for site-specific logic that doesn't fit in the config; each
+# function is called only if the script defines it:
+#   fn rewrite_url(url)      rewrite the URL when a task is created, return the new URL
+#   fn headers(url)          return extra request headers, e.g. #{ "X-Token": "..." }
+#   fn allow(url, file)      return false or a reason to refuse the task
+#   fn on_complete(task)     called after a task completes; task has id, url, path, status, error
+#   fn on_failure(task)      called after a task fails
+# Scripts can use regex_match(text, pattern) and regex_replace(text, pattern, replacement);
+# print goes to the log
+# script = "hooks.rhai"
+
+# ==================== Daemon ====================
+
+# The remote interfaces of multidown serve (web dashboard --web, gRPC --listen) are unauthenticated
+# by default and should only listen on a local address; to listen on any other address, set an access
+# token or configure clients below, otherwise the daemon refuses to start
+# The token is sent as authorization: Bearer <token> (entered on the page for the web dashboard);
+# requests carrying it are treated like the
+# daemon's owner. Store it in the OS keyring with multidown secret set and reference it as {secret:<name>}
+# serve_token = "{secret:multidown-serve}"
+
+# TLS certificate chain and private key (PEM) for the remote interface; once both are set only TLS
+# connections are accepted and the token is never sent in clear text
+# serve_tls_cert = "/etc/multidown/server.crt"
+# serve_tls_key = "/etc/multidown/server.key"
+
+# ==================== Client quotas ====================
+
+# When the daemon (multidown serve) is a shared service, tell the clients that submit tasks apart
+# by API key (optional)
+# Tasks record the client that submitted them and stats are split per client; a client never has
+# more than max_concurrent_downloads tasks downloading and its tasks together stay under
+# speed_limit_kb (0 means unlimited), on top of the global settings above
+# Once clients are configured, gRPC requests must carry a key in the x-api-key metadata; requests
+# on the local control channel may carry one in the api_key field, and requests without a key
+# belong to the daemon's owner and have no quota. These go at the end of the file too, for example:
+#   [[clients]]
+#   name = "ci"
+#   api_key = "{secret:multidown-ci}"
+#   max_concurrent_downloads = 2
+#   speed_limit_kb = "4M"
+
+# ==================== URL rules ====================
+
+# Override some settings for URLs matching a regular expression; rules are evaluated in order
+# when a task is created (later rules win)
+# Overridable: thread_count, speed_limit_kb, enable_chunked_download, chunk_size, timeout, retry_count, user_agent, checksum_url, headers, basic_auth
+# Keep tokens out of this file: store them in the OS keyring with multidown secret set <name>
+# and reference them as {secret:<name>}
+# Rules must go at the end of the file (TOML arrays of tables come after plain keys), for example:
+#   [[rules]]
+#   pattern = '\.iso$'
+#   thread_count = 16
+#   checksum_url = "{url}.sha256"
+#
+#   [[rules]]
+#   pattern = 'slow-host\.com'
+#   enable_chunked_download = false
+#
+#   [[rules]]
+#   pattern = '^https://api\.example\.com/'
+#   headers = { Authorization = "Bearer {secret:example-token}" }
+#
+#   [[rules]]
+#   pattern = '^https://files\.example\.org/'
+#   basic_auth = "alice:{secret:files-password}"
+
+# ==================== Usage ====================
+#
+# 1. Basic:
+#    multidown https://example.com/file.zip
+#
+# 2. Batch download:
+#    multidown -f urls.txt
+#    # urls.txt contains one URL per line:
+#    # https://example.com/file1.zip
+#    # https://example.com/file2.zip
+#
+# 3. Speed limit:
+#    multidown -l 1000 https://example.com/file.zip
+#
+# 4. Thread count:
+#    multidown -t 8 https://example.com/file.zip
+#
+# 5. Download directory:
+#    multidown -d /path/to/downloads https://example.com/file.zip
+#
+# 6. Edit this file:
+#    multidown -e
+#
+# 7. Help:
+#    multidown --help
+#
+# ==================== Troubleshooting ====================
+#
+# Problem: downloads are slow
+# Fix: increase thread_count or check speed_limit_kb
+#
+# Problem: downloads fail often
+# Fix: increase retry_count or timeout
+#
+# Problem: large downloads get interrupted
+# Fix: make sure enable_resume = true
+#
+# Problem: memory usage is high
+# Fix: reduce chunk_size or max_concurrent_downloads
+#
+# ==================== Tuning ====================
+#
+# Fast networks (100Mbps+):
+#   thread_count = 8-16
+#   chunk_size = 16384
+#   max_concurrent_downloads = 3-5
+#
+# Medium networks (10-100Mbps):
+#   thread_count = 4-8
+#   chunk_size = 8192
+#   max_concurrent_downloads = 2-3
+#
+# Slow networks (<10Mbps):
+#   thread_count = 2-4
+#   chunk_size = 4096
+#   max_concurrent_downloads = 1-2
+
+# ==================== Settings ====================
+# The values above are examples only; the effective settings follow (change them with multidown config set)
+"#.to_string()
+    }
+
+    /// 解析 `output_file_mode`，为空时返回 None
+    pub fn file_mode(&self) -> Result<Option<u32>, DownloadError> {
+        let mode = self.output_file_mode.trim();
+        if mode.is_empty() {
+            return Ok(None);
+        }
+        match u32::from_str_radix(mode.trim_start_matches("0o"), 8) {
+            Ok(mode) if mode <= 0o7777 => Ok(Some(mode)),
+            _ => Err(DownloadError::Unknown(format!("无效的文件权限: {}，应为八进制，如 \"644\"", mode).into())),
+        }
+    }
+
     /// 校验配置合法性
     pub fn validate(&self) -> Result<(), DownloadError> {
+        self.file_mode()?;
         // 验证线程数
         if self.thread_count == 0 {
             return Err(DownloadError::Unknown(Cow::Borrowed("线程数必须大于0")));
         }
 
         // 验证并发下载数
-        if self.max_concurrent_downloads == 0 {
+        if self.max_concurrent_downloads == 0 || self.small_files_concurrency == 0 {
             return Err(DownloadError::Unknown(Cow::Borrowed("并发下载数必须大于0")));
         }
 
@@ -262,10 +1153,59 @@ auto_resume_on_startup = true
             return Err(DownloadError::Unknown(Cow::Borrowed("超时时间必须大于0")));
         }
 
+        if self.fallback_user_agents.iter().any(|ua| ua.trim().is_empty()) {
+            return Err(DownloadError::Unknown(Cow::Borrowed("fallback_user_agents 不能包含空字符串")));
+        }
+        if self.rsync_program.trim().is_empty() {
+            return Err(DownloadError::Unknown(Cow::Borrowed("rsync_program 不能为空")));
+        }
+        if !self.script.is_empty() && !Path::new(&self.script).is_file() {
+            return Err(DownloadError::Unknown(format!("脚本文件不存在: {}", self.script).into()));
+        }
+        for (host, name) in &self.resolvers {
+            if name != resolver::NONE && !resolver::BUILTIN.contains(&name.as_str()) {
+                return Err(DownloadError::Unknown(
+                    format!("主机 {} 的解析器 '{}' 无效，可选: {}、{}", host, name, resolver::BUILTIN.join("、"), resolver::NONE).into(),
+                ));
+            }
+        }
+
+        // 验证 TCP 选项
+        if !self.source_address.is_empty() && self.source_address.parse::<std::net::IpAddr>().is_err() {
+            return Err(DownloadError::Unknown(
+                format!("源地址 '{}' 不是有效的 IP 地址", self.source_address).into(),
+            ));
+        }
+        if !self.bind_interface.is_empty() && !cfg!(target_os = "linux") {
+            return Err(DownloadError::Unknown(Cow::Borrowed("绑定网络接口仅支持 Linux")));
+        }
+
+        // 验证 DoH 地址
+        if !self.dns_resolver.is_empty()
+            && !url::Url::parse(&self.dns_resolver).is_ok_and(|u| matches!(u.scheme(), "https" | "http"))
+        {
+            return Err(DownloadError::Unknown(
+                format!("DNS 解析服务地址 '{}' 不是有效的 HTTP(S) 地址", self.dns_resolver).into(),
+            ));
+        }
+        // 验证代理地址，只检查格式，不读取密钥环
+        crate::core::task::proxy::Proxy::from_setting_with(&self.proxy, |_| Ok(String::new()))?;
+        secrets::check(&self.proxy)?;
+
+        let checksum_url = crate::core::task::checksum::checksum_url(&self.checksum_url, "https://example.com/file");
+        if !self.checksum_url.is_empty() && !url::Url::parse(&checksum_url).is_ok_and(|u| matches!(u.scheme(), "https" | "http")) {
+            return Err(DownloadError::Unknown(
+                format!("校验和文件地址 '{}' 不是有效的 HTTP(S) 地址", self.checksum_url).into(),
+            ));
+        }
+
         // 验证下载目录
         if self.download_dir.is_empty() {
             return Err(DownloadError::Unknown(Cow::Borrowed("下载目录不能为空")));
         }
+        if self.state_dir.is_empty() {
+            return Err(DownloadError::Unknown(Cow::Borrowed("会话目录不能为空")));
+        }
 
         // 验证分块大小
         if self.chunk_size == 0 {
@@ -277,51 +1217,193 @@ auto_resume_on_startup = true
             return Err(DownloadError::Unknown(Cow::Borrowed("最小分块大小必须大于0")));
         }
 
+        // 验证自适应调整的上限
+        if self.max_thread_count == 0 {
+            return Err(DownloadError::Unknown(Cow::Borrowed("最大线程数必须大于0")));
+        }
+
+        if self.circuit_breaker_threshold > 0 && self.circuit_breaker_cooldown == 0 {
+            return Err(DownloadError::Unknown(Cow::Borrowed("熔断冷却时间必须大于0")));
+        }
+
         // 验证重试次数
         if self.retry_count == 0 {
             return Err(DownloadError::Unknown(Cow::Borrowed("重试次数必须大于0")));
         }
+        self.retry_policies.validate(self)?;
+        for rule in &self.retry_rules {
+            rule.validate()?;
+        }
+        clients::validate(&self.clients)?;
+        push::validate(&self.push)?;
+        if self.serve_tls_cert.is_empty() != self.serve_tls_key.is_empty() {
+            return Err(DownloadError::Unknown(Cow::Borrowed("serve_tls_cert 和 serve_tls_key 需要同时设置")));
+        }
+
+        // 验证 URL 规则：正则可编译，且覆盖后的配置依然合法
+        for rule in &self.rules {
+            rule.regex()?;
+            rule.check_secrets()?;
+            let mut overridden = Config { rules: Vec::new(), ..self.clone() };
+            rule.apply(&mut overridden);
+            overridden.validate().map_err(|e| {
+                DownloadError::Unknown(format!("URL 规则 '{}' 无效: {}", rule.pattern, e).into())
+            })?;
+        }
 
         Ok(())
     }
 
+    /// 分块临时文件和断点续传信息实际使用的目录
+    pub fn temp_dir_path(&self) -> std::path::PathBuf {
+        if self.temp_dir.is_empty() {
+            Path::new(&self.download_dir).join(".multidown")
+        } else {
+            std::path::PathBuf::from(&self.temp_dir)
+        }
+    }
+
+    /// 会话目录中名为 `name` 的文件
+    pub fn state_path(&self, name: &str) -> std::path::PathBuf {
+        Path::new(&self.state_dir).join(name)
+    }
+
+    /// 同时下载的任务数：小文件模式下为 `small_files_concurrency`，否则为 `max_concurrent_downloads`
+    pub fn download_slots(&self) -> usize {
+        if self.small_files {
+            self.small_files_concurrency
+        } else {
+            self.max_concurrent_downloads
+        }
+    }
+
+    /// 获取某个 URL 匹配的规则中设置的请求头，同名请求头以后面的规则为准
+    pub fn headers_for_url(&self, url: &str) -> Vec<(String, String)> {
+        let mut headers: Vec<(String, String)> = Vec::new();
+        for rule in &self.rules {
+            if !rule.regex().is_ok_and(|re| re.is_match(url)) {
+                continue;
+            }
+            for (name, value) in &rule.headers {
+                headers.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+                headers.push((name.clone(), value.clone()));
+            }
+        }
+        headers
+    }
+
+    /// 获取某个 URL 匹配的规则中设置的 Basic 认证（`用户名:密码`），以最后一条为准
+    pub fn basic_auth_for_url(&self, url: &str) -> Option<&str> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.regex().is_ok_and(|re| re.is_match(url)))
+            .filter_map(|rule| rule.basic_auth.as_deref())
+            .next_back()
+    }
+
+    /// 获取某个 URL 实际使用的配置（依次应用匹配的 URL 规则）
+    pub fn for_url(&self, url: &str) -> Config {
+        let mut config = self.clone();
+        for rule in &self.rules {
+            if rule.regex().is_ok_and(|re| re.is_match(url)) {
+                rule.apply(&mut config);
+            }
+        }
+        config
+    }
+
     /// 合并命令行参数到配置
     pub fn merge_from_args(&mut self, args: &crate::cli::Args) {
-        // 命令行参数覆盖配置文件
+        // 命令行参数覆盖配置文件；预设先应用，单独指定的参数优先于预设
+        if let Some(preset) = args.preset() {
+            preset.apply(self);
+        }
+
         if let Some(speed_limit) = args.speed_limit_kb {
             self.speed_limit_kb = speed_limit;
         }
+
+        if args.small_files {
+            self.small_files = true;
+        }
+
+        if let Some(total_limit) = args.total_speed_limit_kb {
+            self.total_speed_limit_kb = total_limit;
+        }
         
         if !args.download_dir.is_empty() {
             self.download_dir = args.download_dir.clone();
         }
+
+        if let Some(dir) = &args.temp_dir {
+            self.temp_dir = dir.clone();
+        }
         
         if let Some(thread_count) = args.thread_count {
             self.thread_count = thread_count;
         }
+
+        if let Some(secs) = args.timeout_total {
+            self.timeout_total = secs;
+        }
+
+        if args.verify_resume {
+            self.verify_resume = true;
+        }
+
+        if args.continue_partial {
+            self.continue_partial = true;
+        }
+
+        if args.stream_order {
+            self.stream_order = true;
+        }
+
+        if let Some(size) = args.max_file_size {
+            self.max_file_size = size;
+        }
+
+        if let Some(size) = args.quota {
+            self.run_quota = size;
+        }
+
+        if args.notify {
+            self.notify_on_finish = true;
+        }
+
+        if let Some(cmd) = &args.on_complete {
+            self.on_complete = cmd.clone();
+        }
+
+        if let Some(cmd) = &args.on_failure {
+            self.on_failure = cmd.clone();
+        }
+    }
+
+    /// 重新读取配置文件（热重载），命令行参数依然优先
+    ///
+    /// 文件格式错误或校验失败时返回错误，调用方应继续使用旧配置。
+    pub fn reload(path: &str, args: &crate::cli::Args) -> Result<Self, DownloadError> {
+        let mut config = edit::read_config(path)?;
+        config.merge_from_args(args);
+        config.validate()?;
+        Ok(config)
     }
 
     /// 获取配置摘要信息
     pub fn get_summary(&self) -> String {
-        format!(
-            "配置摘要:\n\
-            - 下载目录: {}\n\
-            - 线程数: {}\n\
-            - 并发数: {}\n\
-            - 速度限制: {} KB/s\n\
-            - 超时时间: {} 秒\n\
-            - 重试次数: {}\n\
-            - 断点续传: {}\n\
-            - 分块下载: {}",
-            self.download_dir,
-            self.thread_count,
-            self.max_concurrent_downloads,
-            if self.speed_limit_kb == 0 { "不限速".to_string() } else { self.speed_limit_kb.to_string() },
-            self.timeout,
-            self.retry_count,
-            if self.enable_resume { "启用" } else { "禁用" },
-            if self.enable_chunked_download { "启用" } else { "禁用" }
-        )
+        let speed_limit = if self.speed_limit_kb == 0 { t(Msg::Unlimited).to_string() } else { self.speed_limit_kb.to_string() };
+        let on_off = |flag: bool| if flag { t(Msg::Enabled) } else { t(Msg::Disabled) };
+        tf(Msg::ConfigSummary, &[
+            &self.download_dir,
+            &self.thread_count,
+            &self.download_slots(),
+            &speed_limit,
+            &self.timeout,
+            &self.retry_count,
+            &on_off(self.enable_resume),
+            &on_off(self.enable_chunked_download),
+        ])
     }
 }
 
@@ -337,6 +1419,9 @@ mod tests {
         assert_eq!(config.max_concurrent_downloads, 3);
         assert_eq!(config.timeout, 30);
         assert_eq!(config.retry_count, 3);
+        assert!(config.fsync_on_complete);
+        assert_eq!(config.temp_dir_path(), Path::new("./downloads").join(".multidown"));
+        assert_eq!(config.state_path("tasks.json"), Path::new("downloads/tasks.json"));
     }
 
     #[test]
@@ -350,6 +1435,20 @@ mod tests {
         config = Config::default();
         config.max_concurrent_downloads = 0;
         assert!(config.validate().is_err());
+
+        config = Config::default();
+        config.source_address = "192.168.1.300".to_string();
+        assert!(config.validate().is_err());
+        config.source_address = "fe80::1".to_string();
+        assert!(config.validate().is_ok());
+
+        config.dns_resolver = "8.8.8.8".to_string();
+        assert!(config.validate().is_err());
+        config.dns_resolver = "https://dns.google/resolve".to_string();
+        assert!(config.validate().is_ok());
+
+        config.state_dir = String::new();
+        assert!(config.validate().is_err());
     }
 
     #[test]
@@ -367,6 +1466,33 @@ mod tests {
         let _ = std::fs::remove_file(path);
     }
 
+    #[test]
+    fn test_config_strict_and_lenient() {
+        let path = "./test_config_broken.toml";
+        let broken = "thread_count = 8\ntimeout = \"thirty\"\n";
+        std::fs::write(path, broken).unwrap();
+
+        // 严格模式：报告出错位置，不修改文件
+        let err = Config::load_with_mode(path, true).unwrap_err().to_string();
+        assert!(err.contains("line 2"), "{}", err);
+        assert!(err.contains("timeout"), "{}", err);
+        assert_eq!(std::fs::read_to_string(path).unwrap(), broken);
+
+        // 宽松模式：备份原文件后使用默认配置
+        let config = Config::load_with_mode(path, false).unwrap();
+        assert_eq!(config.thread_count, Config::default().thread_count);
+        let backups: Vec<_> = std::fs::read_dir(".").unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().starts_with("test_config_broken.toml.broken-"))
+            .collect();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(std::fs::read_to_string(backups[0].path()).unwrap(), broken);
+        assert!(Config::load_with_mode(path, true).is_ok());
+
+        let _ = std::fs::remove_file(backups[0].path());
+        let _ = std::fs::remove_file(path);
+    }
+
     #[test]
     fn test_config_save_with_tutorial() {
         let config = Config::default();
@@ -379,6 +1505,43 @@ mod tests {
         let _ = std::fs::remove_file(path);
     }
 
+    #[test]
+    fn test_config_reload() {
+        use clap::Parser;
+        let path = "./test_config_reload.toml";
+        Config::default().save_with_tutorial(path).unwrap();
+        edit::set_value(path, "max_concurrent_downloads", "5").unwrap();
+
+        // 命令行参数在重载后依然优先
+        let args = crate::cli::Args::try_parse_from(["multidown", "-c", path, "-l", "100", "https://example.com/a"]).unwrap();
+        let config = Config::reload(path, &args).unwrap();
+        assert_eq!(config.max_concurrent_downloads, 5);
+        assert_eq!(config.speed_limit_kb, 100);
+
+        // 无效的修改不会被应用
+        std::fs::write(path, "thread_count = 0\n").unwrap();
+        assert!(Config::reload(path, &args).is_err());
+        std::fs::write(path, "thread_count = \"four\"\n").unwrap();
+        assert!(Config::reload(path, &args).is_err());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_config_units() {
+        let config: Config = toml::from_str("speed_limit_kb = \"2M\"\nchunk_size = \"4MiB\"\nmin_chunk_size = 2048\n").unwrap();
+        assert_eq!(config.speed_limit_kb, 2048);
+        assert_eq!(config.chunk_size, 4 * 1024 * 1024);
+        assert_eq!(config.min_chunk_size, 2048);
+        assert!(toml::from_str::<Config>("chunk_size = \"4 lightyears\"").is_err());
+    }
+
+    #[test]
+    fn test_tutorial_languages() {
+        assert!(Config::tutorial_zh().contains("MultiDown 配置文件"));
+        assert!(Config::tutorial_en().contains("MultiDown configuration file"));
+    }
+
     #[test]
     fn test_config_summary() {
         let config = Config::default();
@@ -0,0 +1,80 @@
+//! 并发预设：`multidown --fast <url>`、`multidown --polite -f urls.txt`
+//!
+//! 大多数用户不会逐个调整线程数、分块大小和限速，预设按网络环境一次设置这几项：
+//!
+//! - `fast`：带宽充足、服务器允许多连接（千兆宽带、CDN、机房之间），多连接、大分块、不限速，
+//!   连接数由自适应调整继续往上加；
+//! - `polite`：公共镜像和小站点，每个任务 2 个连接、同时只下载一个文件（对同一主机最多 2 个连接），
+//!   限速并放慢重试，避免被当成滥用而封禁。
+//!
+//! 预设先于其他命令行参数应用，`-t`、`-l` 等显式指定的参数依然优先。
+
+use super::Config;
+
+const MIB: usize = 1024 * 1024;
+
+/// 并发预设
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    Fast,
+    Polite,
+}
+
+impl Preset {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Preset::Fast => "fast",
+            Preset::Polite => "polite",
+        }
+    }
+
+    /// 用预设的取值覆盖配置
+    pub fn apply(self, config: &mut Config) {
+        match self {
+            Preset::Fast => {
+                config.thread_count = 16;
+                config.max_thread_count = 32;
+                config.max_concurrent_downloads = 6;
+                config.chunk_size = 4 * MIB;
+                config.min_chunk_size = 256 * 1024;
+                config.max_chunk_size = 64 * MIB;
+                config.adaptive_chunking = true;
+                config.spread_addresses = true;
+                config.speed_limit_kb = 0;
+            }
+            Preset::Polite => {
+                config.thread_count = 2;
+                config.max_thread_count = 2;
+                config.max_concurrent_downloads = 1;
+                config.chunk_size = 8 * MIB;
+                config.min_chunk_size = MIB;
+                config.max_chunk_size = config.max_chunk_size.max(8 * MIB);
+                // 自适应调整会逐个增加连接数，礼貌模式下固定不变
+                config.adaptive_chunking = false;
+                config.speed_limit_kb = 2048;
+                config.retry_delay = config.retry_delay.max(10);
+                config.circuit_breaker_threshold = 3;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets_are_valid() {
+        for preset in [Preset::Fast, Preset::Polite] {
+            let mut config = Config::default();
+            preset.apply(&mut config);
+            assert!(config.validate().is_ok(), "{}", preset.as_str());
+            assert!(config.min_chunk_size <= config.chunk_size && config.chunk_size <= config.max_chunk_size);
+            assert!(config.thread_count <= config.max_thread_count);
+        }
+        let mut config = Config::default();
+        Preset::Polite.apply(&mut config);
+        assert_eq!(config.thread_count * config.max_concurrent_downloads, 2);
+        assert!(config.speed_limit_kb > 0);
+    }
+}
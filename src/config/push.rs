@@ -0,0 +1,135 @@
+//! 推送通知：任务完成或失败时通过 Telegram 机器人或 Matrix webhook 发消息到手机
+//!
+//! ```toml
+//! [[push]]
+//! service = "telegram"
+//! bot_token = "{secret:telegram-bot}"
+//! chat_id = "123456789"
+//! tags = ["important"]
+//!
+//! [[push]]
+//! service = "matrix"
+//! webhook_url = "{secret:matrix-hook}"
+//! ```
+//!
+//! `tags` 不为空时只推送带有其中任一标签的任务，`failures_only` 为 true 时只推送失败的任务。
+//! 与桌面通知（`notify_on_finish`）互不影响。
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::error::DownloadError;
+use crate::utils::secrets;
+
+/// Telegram Bot API 的地址
+const TELEGRAM_API: &str = "https://api.telegram.org";
+
+/// 推送服务
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PushService {
+    /// Telegram 机器人，调用 `sendMessage`
+    Telegram,
+    /// Matrix webhook（例如 matrix-hookshot 的通用 webhook），POST `{"text": ...}`
+    Matrix,
+}
+
+/// 一个推送目标
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PushTarget {
+    /// 推送服务：`telegram` 或 `matrix`
+    pub service: PushService,
+    /// Telegram 机器人令牌，可以写成 `{secret:<名称>}` 从密钥环读取
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub bot_token: String,
+    /// Telegram 的会话 ID（用户、群组或 `@频道名`）
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub chat_id: String,
+    /// Matrix webhook 地址，地址中通常带有令牌，同样可以写成 `{secret:<名称>}`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub webhook_url: String,
+    /// 只推送带有其中任一标签的任务，为空时推送所有任务
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// 只推送失败的任务
+    #[serde(default)]
+    pub failures_only: bool,
+}
+
+/// 校验推送目标：Telegram 需要 `bot_token` 和 `chat_id`，Matrix 需要 `webhook_url`
+pub fn validate(targets: &[PushTarget]) -> Result<(), DownloadError> {
+    for target in targets {
+        match target.service {
+            PushService::Telegram if target.bot_token.is_empty() || target.chat_id.is_empty() => {
+                return Err(DownloadError::Unknown("Telegram 推送需要设置 bot_token 和 chat_id".into()));
+            }
+            PushService::Matrix if target.webhook_url.is_empty() => {
+                return Err(DownloadError::Unknown("Matrix 推送需要设置 webhook_url".into()));
+            }
+            PushService::Matrix if !target.webhook_url.contains(secrets::PLACEHOLDER_PREFIX) => {
+                let url = url::Url::parse(&target.webhook_url)
+                    .map_err(|e| DownloadError::Unknown(format!("无效的 Matrix webhook 地址: {}", e).into()))?;
+                if !matches!(url.scheme(), "http" | "https") {
+                    return Err(DownloadError::Unknown("Matrix webhook 地址必须是 http 或 https".into()));
+                }
+            }
+            _ => {}
+        }
+        if target.tags.iter().any(|tag| tag.trim().is_empty()) {
+            return Err(DownloadError::Unknown("推送目标的标签不能为空".into()));
+        }
+    }
+    Ok(())
+}
+
+impl PushTarget {
+    /// 带有 `tags` 的任务结束（`failed` 表示失败）时是否推送到这里
+    pub fn matches(&self, tags: &[String], failed: bool) -> bool {
+        (failed || !self.failures_only) && (self.tags.is_empty() || self.tags.iter().any(|tag| tags.contains(tag)))
+    }
+
+    /// 发送 `text` 的请求地址和 JSON 请求体，令牌和 webhook 地址中的密钥占位符在这里读取
+    pub fn request(&self, text: &str) -> Result<(String, serde_json::Value), DownloadError> {
+        match self.service {
+            PushService::Telegram => {
+                let token = secrets::resolve(&self.bot_token)?;
+                let body = serde_json::json!({ "chat_id": self.chat_id, "text": text, "disable_web_page_preview": true });
+                Ok((format!("{}/bot{}/sendMessage", TELEGRAM_API, token), body))
+            }
+            PushService::Matrix => {
+                let url = secrets::resolve(&self.webhook_url)?;
+                Ok((url, serde_json::json!({ "text": text, "username": "MultiDown" })))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_targets() {
+        let telegram: PushTarget =
+            toml::from_str("service = \"telegram\"\nbot_token = \"123:abc\"\nchat_id = \"42\"\ntags = [\"important\"]").unwrap();
+        let matrix: PushTarget =
+            toml::from_str("service = \"matrix\"\nwebhook_url = \"https://hook.example.org/webhook/x\"\nfailures_only = true").unwrap();
+        assert!(validate(&[telegram.clone(), matrix.clone()]).is_ok());
+
+        assert!(telegram.matches(&["iso".to_string(), "important".to_string()], false));
+        assert!(!telegram.matches(&["iso".to_string()], true));
+        assert!(matrix.matches(&[], true));
+        assert!(!matrix.matches(&["important".to_string()], false));
+
+        let (url, body) = telegram.request("下载完成").unwrap();
+        assert_eq!(url, "https://api.telegram.org/bot123:abc/sendMessage");
+        assert_eq!((body["chat_id"].as_str(), body["text"].as_str()), (Some("42"), Some("下载完成")));
+        let (url, body) = matrix.request("下载失败").unwrap();
+        assert_eq!((url.as_str(), body["text"].as_str()), ("https://hook.example.org/webhook/x", Some("下载失败")));
+
+        let missing = PushTarget { chat_id: String::new(), ..telegram };
+        assert!(validate(&[missing]).is_err());
+        assert!(validate(&[PushTarget { webhook_url: "ftp://hook".to_string(), ..matrix.clone() }]).is_err());
+        assert!(validate(&[PushTarget { tags: vec![" ".to_string()], ..matrix }]).is_err());
+        assert!(toml::from_str::<PushTarget>("service = \"slack\"").is_err());
+    }
+}
@@ -0,0 +1,256 @@
+//! 按错误分类的重试策略：为不同的错误设置不同的重试次数和退避时间
+//!
+//! ```toml
+//! [retry_policies.network]
+//! count = 10
+//! delay = 2
+//!
+//! [retry_policies.client]
+//! count = 0
+//! ```
+//!
+//! 未设置的项沿用全局的 `retry_count`、`retry_delay`、`retry_max_delay`；
+//! `client`（4xx）默认不重试，不稳定的 CDN 可以多给几次机会，而 403 这类错误立即失败。
+//!
+//! 错误归入哪个分类也可以用 `retry_rules` 调整，按顺序匹配，第一条匹配的规则生效，
+//! 没有规则匹配时使用内置的分类（见 `DownloadError::retry_class`）：
+//!
+//! ```toml
+//! [[retry_rules]]
+//! hosts = ["flaky.example.com"]
+//! status = ["403"]
+//! class = "server"
+//!
+//! [[retry_rules]]
+//! status = ["501", "505-599"]
+//! class = "never"
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::time::Duration;
+
+use super::Config;
+use crate::core::error::{DownloadError, ErrorClass};
+
+/// 单个错误分类的重试设置
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// 重试次数，0 表示不重试
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>,
+    /// 第一次重试前的等待时间（秒）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delay: Option<u64>,
+    /// 指数退避时等待时间的上限（秒）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_delay: Option<u64>,
+}
+
+impl RetryPolicy {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// 各错误分类的重试设置
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct RetryPolicies {
+    /// 连接失败、连接中断
+    #[serde(skip_serializing_if = "RetryPolicy::is_empty")]
+    pub network: RetryPolicy,
+    /// 连接或响应超时
+    #[serde(skip_serializing_if = "RetryPolicy::is_empty")]
+    pub timeout: RetryPolicy,
+    /// 5xx 以及 408、429
+    #[serde(skip_serializing_if = "RetryPolicy::is_empty")]
+    pub server: RetryPolicy,
+    /// 其他 4xx，默认不重试
+    #[serde(skip_serializing_if = "RetryPolicy::is_empty")]
+    pub client: RetryPolicy,
+    /// 本地文件读写错误
+    #[serde(skip_serializing_if = "RetryPolicy::is_empty")]
+    pub io: RetryPolicy,
+}
+
+/// 错误分类规则，`status` 和 `kinds` 满足其一即匹配
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct RetryRule {
+    /// 只对这些主机生效（可以带端口），为空时对所有主机生效
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hosts: Vec<String>,
+    /// HTTP 状态码或范围，如 "429"、"500-599"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub status: Vec<String>,
+    /// 错误类别，与下载报告中的 `error_kind` 相同，如 "network"、"io"、"size_mismatch"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub kinds: Vec<String>,
+    /// 归入的重试分类，"never" 表示不重试
+    pub class: String,
+}
+
+impl RetryRule {
+    /// 规则归入的分类，`None` 表示不重试
+    pub fn target(&self) -> Result<Option<ErrorClass>, DownloadError> {
+        if self.class == "never" {
+            return Ok(None);
+        }
+        ErrorClass::from_name(&self.class)
+            .map(Some)
+            .ok_or_else(|| DownloadError::Unknown(format!("retry_rules 中无效的分类: {}", self.class).into()))
+    }
+
+    /// 解析 `status` 中的状态码范围（闭区间）
+    pub fn status_ranges(&self) -> Result<Vec<(u16, u16)>, DownloadError> {
+        self.status
+            .iter()
+            .map(|s| {
+                let (start, end) = s.split_once('-').unwrap_or((s, s));
+                match (start.trim().parse::<u16>(), end.trim().parse::<u16>()) {
+                    (Ok(start), Ok(end)) if start <= end => Ok((start, end)),
+                    _ => Err(DownloadError::Unknown(format!("retry_rules 中无效的状态码范围: {}", s).into())),
+                }
+            })
+            .collect()
+    }
+
+    /// 规则是否适用于主机 `host`（`host_key` 的格式）
+    pub fn applies_to(&self, host: Option<&str>) -> bool {
+        self.hosts.is_empty() || host.is_some_and(|host| self.hosts.iter().any(|h| h.eq_ignore_ascii_case(host)))
+    }
+
+    /// 错误是否匹配规则中的状态码或错误类别
+    pub fn matches(&self, error: &DownloadError) -> bool {
+        let status = error.status_code().is_some_and(|code| {
+            self.status_ranges().unwrap_or_default().iter().any(|(start, end)| (*start..=*end).contains(&code))
+        });
+        status || self.kinds.iter().any(|kind| kind == error.kind())
+    }
+
+    pub fn validate(&self) -> Result<(), DownloadError> {
+        self.target()?;
+        self.status_ranges()?;
+        if self.status.is_empty() && self.kinds.is_empty() {
+            return Err(DownloadError::Unknown(Cow::Borrowed("retry_rules 中的规则至少要设置 status 或 kinds")));
+        }
+        if let Some(kind) = self.kinds.iter().find(|k| DownloadError::code_for_kind(k) == 999 && *k != "unknown") {
+            return Err(DownloadError::Unknown(format!("retry_rules 中无效的错误类别: {}", kind).into()));
+        }
+        Ok(())
+    }
+}
+
+/// 合并全局设置后的重试参数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolvedPolicy {
+    pub count: usize,
+    pub delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicies {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn get(&self, class: ErrorClass) -> &RetryPolicy {
+        match class {
+            ErrorClass::Network => &self.network,
+            ErrorClass::Timeout => &self.timeout,
+            ErrorClass::Server => &self.server,
+            ErrorClass::Client => &self.client,
+            ErrorClass::Io => &self.io,
+        }
+    }
+
+    /// 某一分类实际使用的重试参数
+    pub fn resolve(&self, class: ErrorClass, config: &Config) -> ResolvedPolicy {
+        let policy = self.get(class);
+        let default_count = if class == ErrorClass::Client { 0 } else { config.retry_count };
+        ResolvedPolicy {
+            count: policy.count.unwrap_or(default_count),
+            delay: Duration::from_secs(policy.delay.unwrap_or(config.retry_delay)),
+            max_delay: Duration::from_secs(policy.max_delay.unwrap_or(config.retry_max_delay)),
+        }
+    }
+
+    pub fn validate(&self, config: &Config) -> Result<(), DownloadError> {
+        for class in ErrorClass::ALL {
+            let policy = self.resolve(class, config);
+            if policy.max_delay < policy.delay {
+                return Err(DownloadError::Unknown(
+                    format!("retry_policies.{} 的最大重试延迟不能小于重试延迟", class.as_str()).into(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_policies() {
+        let config: Config = toml::from_str(
+            "retry_count = 3\nretry_delay = 5\n[retry_policies.network]\ncount = 10\ndelay = 1\n[retry_policies.io]\ncount = 0\n",
+        )
+        .unwrap();
+        let policies = &config.retry_policies;
+        assert_eq!(
+            policies.resolve(ErrorClass::Network, &config),
+            ResolvedPolicy { count: 10, delay: Duration::from_secs(1), max_delay: Duration::from_secs(60) }
+        );
+        assert_eq!(policies.resolve(ErrorClass::Server, &config).count, 3);
+        assert_eq!(policies.resolve(ErrorClass::Server, &config).delay, Duration::from_secs(5));
+        assert_eq!(policies.resolve(ErrorClass::Client, &config).count, 0);
+        assert_eq!(policies.resolve(ErrorClass::Io, &config).count, 0);
+        assert!(config.validate().is_ok());
+
+        // 未设置时不写入配置文件
+        assert!(!toml::to_string(&Config::default()).unwrap().contains("retry_policies"));
+        let text = toml::to_string(&config).unwrap();
+        assert!(text.contains("[retry_policies.network]") && !text.contains("[retry_policies.server]"));
+
+        let invalid = Config {
+            retry_policies: RetryPolicies {
+                timeout: RetryPolicy { delay: Some(120), ..Default::default() },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_retry_rules() {
+        let config: Config = toml::from_str(
+            "[[retry_rules]]\nhosts = [\"cdn.example.com\"]\nstatus = [\"403\"]\nclass = \"server\"\n\n\
+             [[retry_rules]]\nstatus = [\"501\", \"505-599\"]\nkinds = [\"io\"]\nclass = \"never\"\n",
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        let (host_rule, never) = (&config.retry_rules[0], &config.retry_rules[1]);
+        assert_eq!(host_rule.target().unwrap(), Some(ErrorClass::Server));
+        assert!(host_rule.applies_to(Some("CDN.example.com")) && !host_rule.applies_to(Some("example.com")));
+        assert!(!host_rule.applies_to(None) && never.applies_to(None));
+        assert_eq!(never.target().unwrap(), None);
+        assert_eq!(never.status_ranges().unwrap(), vec![(501, 501), (505, 599)]);
+        assert!(never.matches(&DownloadError::http_status(507, "http://a.com/")));
+        assert!(!never.matches(&DownloadError::http_status(503, "http://a.com/")));
+        assert!(never.matches(&DownloadError::io_error("磁盘错误")));
+
+        for invalid in [
+            RetryRule { hosts: vec![], status: vec!["5xx".into()], kinds: vec![], class: "server".into() },
+            RetryRule { hosts: vec![], status: vec!["599-500".into()], kinds: vec![], class: "server".into() },
+            RetryRule { hosts: vec![], status: vec![], kinds: vec!["bogus".into()], class: "server".into() },
+            RetryRule { hosts: vec![], status: vec!["404".into()], kinds: vec![], class: "sometimes".into() },
+            RetryRule { hosts: vec!["a.com".into()], status: vec![], kinds: vec![], class: "never".into() },
+        ] {
+            assert!(invalid.validate().is_err(), "{:?}", invalid);
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! URL 规则：按正则匹配 URL，为匹配的任务覆盖部分配置
+//!
+//! ```toml
+//! [[rules]]
+//! pattern = '\.iso$'
+//! thread_count = 16
+//!
+//! [[rules]]
+//! pattern = 'slow-host\.com'
+//! enable_chunked_download = false
+//! ```
+//!
+//! 规则在创建任务时按顺序求值，多条规则匹配时后面的覆盖前面的。
+//! 请求头的值可以引用系统密钥环中的密钥，例如
+//! `headers = { Authorization = "Bearer {secret:example-token}" }`；
+//! 需要 Basic 认证的主机可以写 `basic_auth = "alice:{secret:files-password}"`。
+
+use regex::Regex;
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+
+use super::Config;
+use crate::core::error::DownloadError;
+use crate::utils::{secrets, size};
+
+/// 单条 URL 规则，未设置的选项沿用全局配置
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct UrlRule {
+    /// 匹配 URL 的正则表达式
+    pub pattern: String,
+    /// 线程数（分块下载时同时进行的连接数）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread_count: Option<usize>,
+    /// 下载速度限制（KB/s），支持 "2M" 等带单位的字符串
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "deserialize_opt_rate_kb")]
+    pub speed_limit_kb: Option<u64>,
+    /// 是否启用分块下载，false 表示单连接下载
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enable_chunked_download: Option<bool>,
+    /// 分块大小（字节），支持 "4MiB" 等带单位的字符串
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "deserialize_opt_size")]
+    pub chunk_size: Option<usize>,
+    /// 网络超时时间（秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// 重试次数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_count: Option<usize>,
+    /// User-Agent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    /// 校验和文件的地址，`{url}` 替换为下载地址，完成后按其中的 SHA-256 校验
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum_url: Option<String>,
+    /// 附加的请求头，值中的 `{secret:<名称>}` 在创建任务时从密钥环读取
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    /// Basic 认证的 `用户名:密码`，密码可以写成 `{secret:<名称>}`；请求头中已有 Authorization 时不使用
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub basic_auth: Option<String>,
+}
+
+impl UrlRule {
+    /// 编译规则中的正则表达式
+    pub fn regex(&self) -> Result<Regex, DownloadError> {
+        Regex::new(&self.pattern).map_err(|e| {
+            DownloadError::Unknown(format!("URL 规则 '{}' 的正则表达式无效: {}", self.pattern, e).into())
+        })
+    }
+
+    /// 检查请求头和 Basic 认证中密钥占位符的格式
+    pub fn check_secrets(&self) -> Result<(), DownloadError> {
+        let invalid = |e: DownloadError| DownloadError::Unknown(format!("URL 规则 '{}' 无效: {}", self.pattern, e).into());
+        for value in self.headers.values() {
+            secrets::check(value).map_err(invalid)?;
+        }
+        if let Some(auth) = &self.basic_auth {
+            if !auth.contains(':') {
+                return Err(invalid(DownloadError::unknown("basic_auth 的格式应为 用户名:密码")));
+            }
+            secrets::check(auth).map_err(invalid)?;
+        }
+        Ok(())
+    }
+
+    /// 将规则中设置的选项覆盖到配置上
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(v) = self.thread_count {
+            config.thread_count = v;
+        }
+        if let Some(v) = self.speed_limit_kb {
+            config.speed_limit_kb = v;
+        }
+        if let Some(v) = self.enable_chunked_download {
+            config.enable_chunked_download = v;
+        }
+        if let Some(v) = self.chunk_size {
+            config.chunk_size = v;
+        }
+        if let Some(v) = self.timeout {
+            config.timeout = v;
+        }
+        if let Some(v) = self.retry_count {
+            config.retry_count = v;
+        }
+        if let Some(v) = &self.user_agent {
+            config.user_agent = v.clone();
+        }
+        if let Some(v) = &self.checksum_url {
+            config.checksum_url = v.clone();
+        }
+    }
+}
+
+fn deserialize_opt_rate_kb<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    size::deserialize_rate_kb(deserializer).map(Some)
+}
+
+fn deserialize_opt_size<'de, D>(deserializer: D) -> Result<Option<usize>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    size::deserialize_size(deserializer).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rules_for_url() {
+        let config: Config = toml::from_str(
+            r#"
+thread_count = 4
+
+[[rules]]
+pattern = '\.iso$'
+thread_count = 16
+speed_limit_kb = "2M"
+checksum_url = "{url}.sha256"
+
+[[rules]]
+pattern = 'slow-host\.com'
+enable_chunked_download = false
+"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+
+        let iso = config.for_url("https://example.com/distro.iso");
+        assert_eq!(iso.thread_count, 16);
+        assert_eq!(iso.speed_limit_kb, 2048);
+        assert!(iso.enable_chunked_download);
+        assert_eq!(iso.checksum_url, "{url}.sha256");
+
+        let slow = config.for_url("https://slow-host.com/distro.iso");
+        assert_eq!(slow.thread_count, 16);
+        assert!(!slow.enable_chunked_download);
+
+        let other = config.for_url("https://example.com/file.zip");
+        assert_eq!(other.thread_count, 4);
+        assert_eq!(other.speed_limit_kb, 0);
+        assert!(other.checksum_url.is_empty());
+
+        let invalid = UrlRule { pattern: "x".to_string(), checksum_url: Some("sha256.txt".to_string()), ..Default::default() };
+        assert!(Config { rules: vec![invalid], ..config }.validate().is_err());
+    }
+
+    #[test]
+    fn test_rule_headers() {
+        let config: Config = toml::from_str(
+            r#"
+[[rules]]
+pattern = 'example\.com'
+headers = { Authorization = "Bearer {secret:example}", X-Client = "a" }
+
+[[rules]]
+pattern = 'api\.example\.com'
+headers = { X-Client = "b" }
+"#,
+        )
+        .unwrap();
+        let headers = config.headers_for_url("https://api.example.com/v1");
+        assert_eq!(headers.len(), 2);
+        assert!(headers.contains(&("X-Client".to_string(), "b".to_string())));
+        assert!(config.headers_for_url("https://other.org/").is_empty());
+    }
+
+    #[test]
+    fn test_rule_basic_auth() {
+        let mut config: Config = toml::from_str(
+            r#"
+[[rules]]
+pattern = 'example\.com'
+basic_auth = "alice:{secret:example}"
+
+[[rules]]
+pattern = 'files\.example\.com'
+basic_auth = "bob:plain"
+"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.basic_auth_for_url("https://www.example.com/a"), Some("alice:{secret:example}"));
+        assert_eq!(config.basic_auth_for_url("https://files.example.com/a"), Some("bob:plain"));
+        assert_eq!(config.basic_auth_for_url("https://other.org/"), None);
+
+        config.rules[0].basic_auth = Some("alice".to_string());
+        assert!(config.validate().is_err());
+        config.rules[0].basic_auth = Some("alice:{secret:example".to_string());
+        assert!(config.validate().is_err());
+        config.rules[0].basic_auth = None;
+        config.rules[0].headers.insert("Authorization".to_string(), "Bearer {secret:".to_string());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        let mut config = Config::default();
+        config.rules.push(UrlRule { pattern: "(".to_string(), ..Default::default() });
+        assert!(config.validate().is_err());
+
+        config.rules = vec![UrlRule { pattern: "x".to_string(), thread_count: Some(0), ..Default::default() }];
+        assert!(config.validate().is_err());
+    }
+}
@@ -1,19 +1,118 @@
 use crate::config::Config;
+use crate::core::bandwidth::{self, Demand, GroupLimit};
 use crate::core::error::DownloadError;
+use crate::core::events::{EventBus, TaskEvent};
+use crate::core::history::{self, HistoryEntry, HistoryStore, HISTORY_FILE};
+use crate::core::probe_cache::{ProbeCache, PROBE_CACHE_FILE};
+use crate::core::queue::QueuedTask;
+use crate::core::relocate::{self, Relocation};
+use crate::core::script::ScriptHooks;
+use crate::i18n::{t, tf, Msg};
+use crate::utils::hooks::{self, HookContext};
+use crate::utils::{notify, push};
+use crate::utils::secrets;
+use crate::utils::systemd;
 use crate::core::task::{
+    breaker::CircuitBreakerTransport,
+    chunk_manager::{ChunkDownloadStats, ChunkedDownloadManager},
+    http::SocketOptions,
+    resolver::{UrlResolver, UrlResolvers},
+    protocol::{ProtocolHandler, ProtocolHandlers},
+    transport::{AwcTransport, HttpTransport},
+    util::DownloadQuota,
     messages as task_messages,
-    state::TaskStatus,
+    state::{TaskPhase, TaskStatus},
+    ChunkChecksum,
+    DownloadRequest,
     DownloadTaskActor,
+    FileInfo,
+    TaskOptions,
 };
 use actix::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::io::Write;
+use std::rc::Rc;
 use std::sync::Arc;
 use tokio::sync::Semaphore;
 use uuid::Uuid;
 use futures::future::LocalBoxFuture;
+use base64::Engine;
+
+/// 会话文件名：保存所有任务的元数据，位于会话目录（[`Config::state_path`]）中
+pub const SESSION_FILE: &str = "tasks.json";
+
+/// 会话文件的保存间隔
+const SESSION_SAVE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+/// 按保留策略清理已结束任务的间隔
+const RETENTION_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+/// 设置了总限速时重新分配带宽的间隔
+const BANDWIDTH_REBALANCE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// 读取会话文件中的任务元数据，文件不存在或格式错误时返回 None
+pub fn load_session(path: impl AsRef<std::path::Path>) -> Option<Vec<DownloadTaskMeta>> {
+    let data = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&data).ok()
+}
+
+/// 写入会话文件
+pub fn save_session<'a>(
+    path: impl AsRef<std::path::Path>,
+    metas: impl IntoIterator<Item = &'a DownloadTaskMeta>,
+) -> Result<(), DownloadError> {
+    let path = path.as_ref();
+    let metas: Vec<&DownloadTaskMeta> = metas.into_iter().collect();
+    let json = serde_json::to_string_pretty(&metas).map_err(|e| DownloadError::unknown(format!("序列化失败: {}", e)))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| DownloadError::io_error_with_context("创建会话目录", e))?;
+    }
+    fs::write(path, json).map_err(|e| DownloadError::io_error_with_context("写入会话文件", e))
+}
+
+/// 按完整 ID 或 ID 前缀（至少 8 位）查找会话中的任务，返回所有匹配的任务
+pub fn find_tasks<'a>(metas: &'a [DownloadTaskMeta], id: &str) -> Vec<&'a DownloadTaskMeta> {
+    let id = id.to_ascii_lowercase();
+    if id.len() < 8 {
+        return Vec::new();
+    }
+    metas.iter().filter(|m| m.id.to_string().starts_with(&id)).collect()
+}
+
+/// 删除任务的临时分块和断点续传信息，`with_data` 时连同未完成的输出文件；返回实际删除的路径
+///
+/// 已完成任务的输出文件是下载结果，因“文件已存在”失败的任务的输出文件不是它写的，都不会删除。
+pub fn purge_task_data(config: &Config, meta: &DownloadTaskMeta, with_data: bool) -> Vec<std::path::PathBuf> {
+    let mut config = config.for_url(&meta.url);
+    meta.options.apply(&mut config);
+    let mut paths = ChunkedDownloadManager::temp_paths(&config.temp_dir_path(), meta.id, &meta.file);
+    if with_data && meta.status != TaskStatus::Completed && meta.error_kind.as_deref() != Some("file_exists") {
+        paths.push(std::path::PathBuf::from(&meta.file));
+    }
+    paths
+        .into_iter()
+        .filter(|path| {
+            let removed = if path.is_dir() { fs::remove_dir_all(path) } else { fs::remove_file(path) };
+            match removed {
+                Ok(()) => true,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => false,
+                Err(e) => {
+                    tracing::warn!(task_id = %meta.id, path = %path.display(), error = %e, "无法删除任务数据");
+                    false
+                }
+            }
+        })
+        .collect()
+}
+
+/// 为限制了并发数的客户端各建一组名额
+fn client_slots(config: &Config) -> HashMap<String, Arc<Semaphore>> {
+    config
+        .clients
+        .iter()
+        .filter(|client| client.max_concurrent_downloads > 0)
+        .map(|client| (client.name.clone(), Arc::new(Semaphore::new(client.max_concurrent_downloads))))
+        .collect()
+}
 
 /// ================== 任务元数据结构体 ==================
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -25,15 +124,68 @@ pub struct DownloadTaskMeta {
     pub progress: f32,
     pub downloaded: u64,
     pub total: u64,
+    /// 最近一次上报的下载速度（B/s）
+    #[serde(default)]
+    pub speed: u64,
+    /// 开始下载时间
+    #[serde(default)]
+    pub started_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// 结束时间（完成或失败）
+    #[serde(default)]
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// 累计重试次数
+    #[serde(default)]
+    pub retries: u32,
+    /// 失败时的错误类别（见 `DownloadError::kind`）
+    #[serde(default)]
+    pub error_kind: Option<String>,
+    /// 失败时的数字错误码（见 `DownloadError::code`）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<u16>,
+    /// 因服务器返回错误状态码失败时的 HTTP 状态码
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_status: Option<u16>,
+    /// 创建任务时指定的选项，恢复任务时沿用
+    #[serde(default)]
+    pub options: TaskOptions,
+    /// 最近一次运行的性能指标，任务开始后才有
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<PerformanceMetrics>,
+    /// 创建任务时指定的标签，用于按标签筛选查看、重试和导出
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// 正在进行的收尾阶段（合并、校验），状态变化时清除
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phase: Option<TaskPhase>,
+    /// 收尾阶段的进度（0-100）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phase_progress: Option<f32>,
+    /// 提交任务的客户端，见 [`crate::config::clients`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
 }
 
-/// 添加下载任务
+impl DownloadTaskMeta {
+    /// 是否带有指定标签；`tag` 为 None 时总是成立
+    pub fn has_tag(&self, tag: Option<&str>) -> bool {
+        tag.is_none_or(|tag| self.tags.iter().any(|t| t == tag))
+    }
+
+    /// 从开始到结束的耗时（秒），未结束时为 0
+    pub fn duration_secs(&self) -> f64 {
+        match (self.started_at, self.finished_at) {
+            (Some(start), Some(end)) => {
+                end.signed_duration_since(start).num_milliseconds().max(0) as f64 / 1000.0
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+/// 添加下载任务，请求由 [`DownloadRequest::builder`] 构造
 #[derive(Message)]
 #[rtype(result = "Result<Uuid, DownloadError>")]
-pub struct CreateTask {
-    pub url: String,
-    pub file: String,
-}
+pub struct CreateTask(pub DownloadRequest);
 
 /// 启动指定任务
 #[derive(Message)]
@@ -52,6 +204,51 @@ pub struct PauseTask(pub Uuid);
 #[rtype(result = "()")]
 pub struct CancelTask(pub Uuid);
 
+/// 取消任务并从会话中删除，同时删除临时分块和断点续传信息；`with_data` 时连同未完成的输出文件。
+/// 返回任务是否存在
+#[derive(Message)]
+#[rtype(result = "bool")]
+pub struct RemoveTask {
+    pub task_id: Uuid,
+    pub with_data: bool,
+}
+
+/// 修改未完成任务的保存路径，已下载的数据一起移动（见 [`crate::core::relocate`]）；
+/// `temp_dir` 指定时分块和断点续传信息换到这个临时目录。下载中的任务先暂停，移动后重新排队。
+/// 返回新的保存路径
+#[derive(Message)]
+#[rtype(result = "Result<String, DownloadError>")]
+pub struct MoveTask {
+    pub task_id: Uuid,
+    pub path: String,
+    pub temp_dir: Option<String>,
+}
+
+/// 临时优先下载一个正在下载的任务：它使用 `max_thread_count` 个连接且不限速，
+/// 其他任务暂时降为 1 个连接；它不再处于下载中（完成、失败、暂停）时恢复正常调度。
+/// `None` 取消优先；返回是否设置成功（任务不在下载中时不设置）
+#[derive(Message)]
+#[rtype(result = "bool")]
+pub struct BoostTask(pub Option<Uuid>);
+
+/// 重新排队失败的任务，沿用原来的选项和已下载的分块；返回重新排队的任务
+#[derive(Message)]
+#[rtype(result = "Vec<Uuid>")]
+pub struct RetryFailedTasks {
+    /// 只重试其中的任务，None 表示会话中的所有失败任务
+    pub task_ids: Option<Vec<Uuid>>,
+}
+
+/// 把导出的队列合并进会话并开始下载，跳过会话中已有的任务；返回导入的任务
+#[derive(Message)]
+#[rtype(result = "Vec<Uuid>")]
+pub struct ImportQueue(pub Vec<QueuedTask>);
+
+/// 按保留策略清理已结束的任务时跳过这些任务（命令行在运行结束时还要汇总它们的结果）
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct HoldTasks(pub Vec<Uuid>);
+
 /// 查询指定任务进度百分比
 #[derive(Message)]
 #[rtype(result = "Result<f32, ()>")]
@@ -72,6 +269,36 @@ pub struct QueryTaskDetail(pub Uuid);
 #[rtype(result = "TaskStats")]
 pub struct GetStats;
 
+/// 获取所有任务元数据
+#[derive(Message)]
+#[rtype(result = "Vec<DownloadTaskMeta>")]
+pub struct ListTasks;
+
+/// 获取正在分块下载的任务的块统计（含块分布图）
+#[derive(Message)]
+#[rtype(result = "HashMap<Uuid, ChunkDownloadStats>")]
+pub struct QueryChunkStats;
+
+/// 获取事件总线，用于在管理器启动后订阅任务事件
+#[derive(Message)]
+#[rtype(result = "EventBus")]
+pub struct SubscribeEvents;
+
+/// 等待所有后台工作（钩子命令、历史记录）结束
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct WaitForBackgroundJobs;
+
+/// 暂停所有未结束的任务并立即保存会话（收到终止信号时使用）
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct PauseAllAndSave;
+
+/// 热重载配置：之后创建的任务使用新配置，运行中的任务立即应用限速，并发数随之调整
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ReloadConfig(pub Config);
+
 /// 内部消息：更新任务进度
 #[derive(Message)]
 #[rtype(result = "()")]
@@ -81,6 +308,17 @@ pub struct UpdateTaskProgress {
     pub downloaded: u64,
     pub total: u64,
     pub speed: u64,
+    /// 分块下载时的块统计，单连接下载时为 None
+    pub chunks: Option<ChunkDownloadStats>,
+}
+
+/// 内部消息：收尾阶段（合并、校验）的进度
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct UpdateTaskPhase {
+    pub task_id: Uuid,
+    pub phase: TaskPhase,
+    pub progress: f32,
 }
 
 /// 内部消息：标记任务完成
@@ -88,6 +326,33 @@ pub struct UpdateTaskProgress {
 #[rtype(result = "()")]
 pub struct MarkTaskCompleted {
     pub task_id: Uuid,
+    /// 服务器返回的文件信息，写入下载历史供 `multidown verify` 比对
+    pub file_info: Option<FileInfo>,
+}
+
+/// 内部消息：任务 Actor 拿到名额后真正开始下载
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct MarkTaskStarted {
+    pub task_id: Uuid,
+}
+
+/// 内部消息：记录一次重试
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RecordTaskRetry {
+    pub task_id: Uuid,
+    /// 导致重试的错误类别（见 `DownloadError::kind`），未知时为 None
+    pub error_kind: Option<&'static str>,
+}
+
+/// 内部消息：记录一个分块在重试用完后失败
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RecordChunkFailed {
+    pub task_id: Uuid,
+    pub chunk_index: usize,
+    pub error: String,
 }
 
 /// 内部消息：标记任务失败
@@ -106,19 +371,30 @@ pub struct ResumeInfo {
     pub file: String,
     pub downloaded_chunks: Vec<(u64, u64)>, // (start, end) 已下载的块
     pub total_size: u64,
+    /// 分块大小，恢复时沿用同样的分块边界；旧文件中没有时为 0，使用配置中的值
+    #[serde(default)]
+    pub chunk_size: u64,
+    /// 全部分块的边界 (start, end)，按块索引排列；分割过的块与按分块大小切出的不同
+    #[serde(default)]
+    pub chunks: Vec<(u64, u64)>,
+    /// 已完成块的校验值，按块的起点索引；恢复时用于检查块文件是否损坏
+    #[serde(default)]
+    pub checksums: HashMap<u64, ChunkChecksum>,
     pub last_modified: Option<String>,
     pub etag: Option<String>,
 }
 
 /// 性能指标
-#[derive(Debug, Clone)]
-#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PerformanceMetrics {
     pub task_id: Uuid,
     pub start_time: chrono::DateTime<chrono::Utc>,
     pub end_time: Option<chrono::DateTime<chrono::Utc>>,
     pub total_bytes: u64,
     pub downloaded_bytes: u64,
+    /// 本次运行开始前已经下载的字节数（断点续传），不计入平均速度
+    #[serde(default)]
+    pub resumed_bytes: u64,
     pub average_speed: f64, // B/s
     pub peak_speed: u64,    // B/s
     pub retry_count: usize,
@@ -128,7 +404,6 @@ pub struct PerformanceMetrics {
     pub timeouts: usize,
 }
 
-#[allow(dead_code)]
 impl PerformanceMetrics {
     pub fn new(task_id: Uuid) -> Self {
         Self {
@@ -137,6 +412,7 @@ impl PerformanceMetrics {
             end_time: None,
             total_bytes: 0,
             downloaded_bytes: 0,
+            resumed_bytes: 0,
             average_speed: 0.0,
             peak_speed: 0,
             retry_count: 0,
@@ -153,12 +429,28 @@ impl PerformanceMetrics {
         }
     }
     
+    /// 记录一次错误，按类别计数
+    pub fn record_error(&mut self, kind: &str) {
+        self.error_count += 1;
+        match kind {
+            "network" => self.network_errors += 1,
+            "io" => self.io_errors += 1,
+            "timeout" => self.timeouts += 1,
+            _ => {}
+        }
+    }
+
+    /// 记录结束时间并计算平均速度
+    pub fn finish(&mut self) {
+        self.end_time = Some(chrono::Utc::now());
+        self.calculate_average_speed();
+    }
+
     pub fn calculate_average_speed(&mut self) {
-        if let Some(end_time) = self.end_time {
-            let duration = end_time.signed_duration_since(self.start_time);
-            let duration_secs = duration.num_seconds() as f64;
+        if let Some(duration) = self.get_duration() {
+            let duration_secs = duration.num_milliseconds() as f64 / 1000.0;
             if duration_secs > 0.0 {
-                self.average_speed = self.downloaded_bytes as f64 / duration_secs;
+                self.average_speed = self.downloaded_bytes.saturating_sub(self.resumed_bytes) as f64 / duration_secs;
             }
         }
     }
@@ -169,10 +461,11 @@ impl PerformanceMetrics {
 }
 
 /// 任务优先级
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum TaskPriority {
     Low = 0,
+    #[default]
     Normal = 1,
     High = 2,
     Critical = 3,
@@ -193,48 +486,413 @@ pub struct DownloadManagerActor {
     pub tasks: HashMap<Uuid, Addr<DownloadTaskActor>>,
     pub metas: HashMap<Uuid, DownloadTaskMeta>,
     pub semaphore: Arc<Semaphore>, // 并发控制
+    pub queue: Vec<Uuid>, // 等待下载名额的任务，按优先级出队
+    waiting: HashMap<Uuid, SpawnHandle>, // 排队任务各自的名额申请，任务离开队列时取消
+    pub background_jobs: Vec<tokio::task::JoinHandle<()>>, // 尚未结束的后台工作（钩子命令、历史记录）
+    pub dirty: bool, // 元数据有未保存的修改
+    pub transport: Rc<dyn HttpTransport>, // 所有任务共享的 HTTP 后端，按主机复用连接
+    pub protocols: Rc<ProtocolHandlers>, // 所有任务共享的协议处理器，见 register_protocol
+    pub resolvers: Rc<UrlResolvers>, // 所有任务共享的分享链接解析器，可以注册自己的实现
+    script: Option<ScriptHooks>, // 配置中的 Rhai 脚本，创建任务和任务结束时调用
+    pub chunk_stats: HashMap<Uuid, ChunkDownloadStats>, // 运行中任务最近一次上报的块统计，只用于显示
+    pub quota: Arc<DownloadQuota>, // 本次运行的下载配额，所有任务共享
+    pub probes: Arc<ProbeCache>, // 文件信息探测结果的缓存，与会话文件一起保存
+    pub events: EventBus, // 任务事件，供库的使用者订阅
+    held: HashSet<Uuid>, // 不按保留策略清理的任务
+    moving: HashSet<Uuid>, // 正在移动数据的任务，移动完成前不启动、不删除
+    boosted: Option<Uuid>, // 优先下载的任务，见 BoostTask
+    shares: HashMap<Uuid, u64>, // 按总限速分给正在下载的任务的带宽（B/s）
+    client_slots: HashMap<String, Arc<Semaphore>>, // 限制了并发数的客户端各自的名额
 }
 
 impl DownloadManagerActor {
     // 创建一个新的任务管理器
     pub fn new(config: Config) -> Self {
-        let semaphore = Arc::new(Semaphore::new(config.max_concurrent_downloads));
+        let semaphore = Arc::new(Semaphore::new(config.download_slots()));
+        let client_slots = client_slots(&config);
+        let transport = CircuitBreakerTransport::wrap(Rc::new(AwcTransport::new(&config)), &config);
+        let quota = Arc::new(DownloadQuota::new(config.run_quota));
+        let probes = Arc::new(ProbeCache::load(config.state_path(PROBE_CACHE_FILE)));
+        let protocols = Rc::new(ProtocolHandlers::new(&config));
+        let resolvers = Rc::new(UrlResolvers::new(&config));
+        let script = ScriptHooks::load(&config.script).unwrap_or_else(|e| {
+            tracing::error!(error = %e, "加载脚本失败，不使用脚本");
+            None
+        });
         let mut mgr = Self {
             config,
             tasks: HashMap::new(),
             metas: HashMap::new(),
             semaphore,
+            queue: Vec::new(),
+            waiting: HashMap::new(),
+            background_jobs: Vec::new(),
+            dirty: false,
+            transport,
+            protocols,
+            resolvers,
+            script,
+            chunk_stats: HashMap::new(),
+            quota,
+            probes,
+            events: EventBus::default(),
+            held: HashSet::new(),
+            moving: HashSet::new(),
+            boosted: None,
+            shares: HashMap::new(),
+            client_slots,
         };
         mgr.load_tasks_from_file();
         mgr
     }
-    pub fn save_tasks_to_file(&self) {
-        let path = "downloads/tasks.json";
-        if let Ok(json) = serde_json::to_string_pretty(&self.metas.values().collect::<Vec<_>>()) {
-            let _ = fs::create_dir_all("downloads");
-            let _ = fs::File::create(path).and_then(|mut f| f.write_all(json.as_bytes()));
+
+    /// 注册协议处理器，所有任务（包括从会话恢复的）都可以下载它认领的地址，见 [`ProtocolHandler`]
+    pub fn register_protocol(&self, handler: Rc<dyn ProtocolHandler>) {
+        self.protocols.register(handler);
+    }
+
+    /// 为主机（及其子域名）注册分享链接解析器，优先于内置的和配置中的，见 [`UrlResolver`]
+    pub fn register_resolver(&self, host: &str, resolver: Rc<dyn UrlResolver>) {
+        self.resolvers.register(host, resolver);
+    }
+
+    /// 事件总线，启动管理器前取出即可订阅，之后也可以发送 [`SubscribeEvents`] 获取
+    pub fn events(&self) -> EventBus {
+        self.events.clone()
+    }
+    pub fn save_tasks_to_file(&mut self) {
+        if let Err(e) = save_session(self.config.state_path(SESSION_FILE), self.metas.values()) {
+            tracing::warn!(error = %e, "保存会话文件失败");
+        }
+        if let Err(e) = self.probes.save(self.config.probe_cache_ttl) {
+            tracing::warn!(error = %e, "保存探测缓存失败");
+        }
+        self.dirty = false;
+    }
+
+    /// 任务实际使用的配置：全局配置 < URL 规则 < 任务选项
+    fn task_config(&self, url: &str, options: &TaskOptions) -> Config {
+        let mut config = self.config.for_url(url);
+        options.apply(&mut config);
+        config
+    }
+
+    /// 任务实际使用的选项：合并 URL 规则中的请求头，并从密钥环读取 `{secret:<名称>}`
+    ///
+    /// 解析后的密钥只交给任务 Actor，元数据和会话文件中保存的仍是原始选项。
+    fn task_options(&self, url: &str, options: &TaskOptions) -> Result<TaskOptions, DownloadError> {
+        let mut resolved = options.clone();
+        let mut headers = self.config.headers_for_url(url);
+        for (name, value) in &options.headers {
+            headers.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+            headers.push((name.clone(), value.clone()));
+        }
+        // URL 规则的 Basic 认证，请求头中已经有 Authorization 时不覆盖
+        if let Some(auth) = self.config.basic_auth_for_url(url) {
+            if !headers.iter().any(|(n, _)| n.eq_ignore_ascii_case("authorization")) {
+                let credentials = secrets::resolve(auth)?;
+                let encoded = base64::engine::general_purpose::STANDARD.encode(credentials);
+                headers.push(("Authorization".to_string(), format!("Basic {}", encoded)));
+            }
+        }
+        resolved.headers = headers
+            .into_iter()
+            .map(|(name, value)| secrets::resolve(&value).map(|value| (name, value)))
+            .collect::<Result<_, _>>()?;
+        Ok(resolved)
+    }
+
+    /// 任务所属的、限制了并发数的客户端，名额只能在同一客户端的任务之间转让
+    fn slot_group(&self, task_id: &Uuid) -> Option<&str> {
+        let client = self.metas.get(task_id)?.client.as_deref()?;
+        self.client_slots.contains_key(client).then_some(client)
+    }
+
+    /// 取出与 `group` 相同的排队任务中优先级最高的一个，同优先级先到先得
+    fn next_queued_task(&mut self, group: Option<&str>) -> Option<Uuid> {
+        let priority = |id: &Uuid| self.metas.get(id).map(|m| m.options.priority).unwrap_or_default();
+        let mut best: Option<usize> = None;
+        for (i, id) in self.queue.iter().enumerate() {
+            if self.slot_group(id) != group {
+                continue;
+            }
+            if best.is_none_or(|b| priority(id) > priority(&self.queue[b])) {
+                best = Some(i);
+            }
+        }
+        best.map(|i| self.queue.remove(i))
+    }
+
+    /// 按状态机修改任务状态并发布事件；不允许的转换记录警告后忽略，返回是否修改了状态
+    ///
+    /// 任务状态只能经由这里修改，其他地方只读。
+    fn transition(&mut self, task_id: Uuid, next: TaskStatus) -> bool {
+        let Some(meta) = self.metas.get_mut(&task_id) else { return false };
+        if !meta.status.can_transition_to(&next) {
+            tracing::warn!(task_id = %task_id, from = meta.status.as_str(), to = next.as_str(), "忽略不允许的状态转换");
+            return false;
+        }
+        let from = std::mem::replace(&mut meta.status, next.clone());
+        meta.phase = None;
+        meta.phase_progress = None;
+        tracing::debug!(task_id = %task_id, from = from.as_str(), to = next.as_str(), "任务状态变化");
+        self.dirty = true;
+        let rebalance = from == TaskStatus::Running || next == TaskStatus::Running;
+        self.events.emit(TaskEvent::StatusChanged { task_id, from, to: next.clone() });
+        if rebalance {
+            self.rebalance_bandwidth();
+        }
+        if self.boosted == Some(task_id) && next != TaskStatus::Running {
+            tracing::info!(task_id = %task_id, "优先下载的任务已不在下载中，恢复正常调度");
+            self.boosted = None;
+            self.push_task_configs();
+        } else if self.boosted.is_some() && next == TaskStatus::Running {
+            // 优先期间开始下载的任务同样降级
+            if let Some(addr) = self.tasks.get(&task_id) {
+                addr.do_send(task_messages::UpdateConfig(self.effective_config(&task_id)));
+            }
+        }
+        true
+    }
+
+    /// 任务当前应使用的配置：任务选项之上再叠加优先下载的调整
+    fn effective_config(&self, task_id: &Uuid) -> Config {
+        let mut config = match self.metas.get(task_id) {
+            Some(meta) => self.task_config(&meta.url, &meta.options),
+            None => self.config.clone(),
+        };
+        match self.boosted {
+            // 自适应调整会从原来的连接数开始慢慢增加，优先期间直接用满
+            Some(boosted) if boosted == *task_id => {
+                config.thread_count = config.max_thread_count.max(config.thread_count);
+                config.adaptive_chunking = false;
+                config.speed_limit_kb = 0;
+            }
+            Some(_) => {
+                config.thread_count = 1;
+                config.max_thread_count = 1;
+            }
+            None => {}
+        }
+        config
+    }
+
+    /// 设置了总限速时按权重把它分给正在下载的任务（见 [`crate::core::bandwidth`]），份额变化时通知任务；
+    /// 限制了速度的客户端先在自己的任务之间分配它的限速。
+    /// 不再下载的任务和取消总限速后的所有任务恢复使用自己的限速
+    fn rebalance_bandwidth(&mut self) {
+        let total = self.config.total_speed_limit_kb * 1024;
+        let client_limits: HashMap<&str, u64> = self
+            .config
+            .clients
+            .iter()
+            .filter(|client| client.speed_limit_kb > 0)
+            .map(|client| (client.name.as_str(), client.speed_limit_kb * 1024))
+            .collect();
+        let limited = |meta: &DownloadTaskMeta| meta.client.as_deref().is_some_and(|c| client_limits.contains_key(c));
+        let running: Vec<Uuid> = self
+            .metas
+            .values()
+            .filter(|m| m.status == TaskStatus::Running && m.phase.is_none() && (total > 0 || limited(m)))
+            .map(|m| m.id)
+            .collect();
+        let demands: Vec<Demand> = running
+            .iter()
+            .map(|id| {
+                let meta = &self.metas[id];
+                let boost = if self.boosted == Some(*id) { bandwidth::BOOST_FACTOR } else { 1 };
+                Demand {
+                    weight: bandwidth::weight(&meta.options) * boost,
+                    share: self.shares.get(id).copied().unwrap_or(0),
+                    speed: meta.speed,
+                    own_limit: self.effective_config(id).speed_limit_kb * 1024,
+                }
+            })
+            .collect();
+        let groups: Vec<GroupLimit> = client_limits
+            .iter()
+            .map(|(&client, &limit)| GroupLimit {
+                limit,
+                members: (0..running.len()).filter(|&i| self.metas[&running[i]].client.as_deref() == Some(client)).collect(),
+            })
+            .filter(|group| !group.members.is_empty())
+            .collect();
+        let shares: HashMap<Uuid, u64> =
+            running.into_iter().zip(bandwidth::allocate_grouped(total, &demands, &groups)).collect();
+        for (id, addr) in &self.tasks {
+            let (old, new) = (self.shares.get(id).copied(), shares.get(id).copied());
+            if old != new {
+                addr.do_send(task_messages::SetBandwidthShare(new.unwrap_or(0)));
+            }
+        }
+        self.shares = shares;
+    }
+
+    /// 把配置重新下发给所有任务 Actor，进行中的下载立即按新的连接数和限速调整
+    fn push_task_configs(&self) {
+        for (id, addr) in &self.tasks {
+            addr.do_send(task_messages::UpdateConfig(self.effective_config(id)));
+        }
+    }
+
+    /// 按 `keep_finished_tasks`、`keep_finished_days` 把超出的已结束任务移出会话
+    ///
+    /// 完成的任务结束时已经写入下载历史，取消的任务在这里补写；失败的任务保留，供 retry-failed 使用。
+    fn apply_retention(&mut self) {
+        let expired = expired_tasks(
+            self.metas.values().filter(|m| !self.held.contains(&m.id)),
+            self.config.keep_finished_tasks,
+            self.config.keep_finished_days,
+            chrono::Utc::now(),
+        );
+        if expired.is_empty() {
+            return;
+        }
+        for id in &expired {
+            if self.metas.get(id).is_some_and(|m| m.status == TaskStatus::Cancelled) {
+                self.record_history(*id, None);
+            }
+            self.metas.remove(id);
+            self.tasks.remove(id);
+            self.chunk_stats.remove(id);
+        }
+        tracing::info!(count = expired.len(), "已结束的任务移入下载历史");
+        self.save_tasks_to_file();
+    }
+
+    /// 任务暂停或取消时移出队列，并取消它的名额申请，申请不会在之后拿到名额启动任务
+    fn dequeue(&mut self, task_id: &Uuid, ctx: &mut Context<Self>) {
+        self.queue.retain(|id| id != task_id);
+        if let Some(handle) = self.waiting.remove(task_id) {
+            ctx.cancel_future(handle);
         }
     }
+
     pub fn load_tasks_from_file(&mut self) {
-        let path = "downloads/tasks.json";
-        if let Ok(data) = fs::read_to_string(path) {
-            if let Ok(list) = serde_json::from_str::<Vec<DownloadTaskMeta>>(&data) {
-                for mut meta in list {
-                    // 只恢复未完成任务
-                    match meta.status {
-                        TaskStatus::Pending | TaskStatus::Paused | TaskStatus::Running => {
-                            let addr = DownloadTaskActor::new(self.config.clone(), meta.url.clone(), meta.file.clone()).start();
-                            self.tasks.insert(meta.id, addr);
-                        },
-                        _ => {}
-                    }
-                    if meta.total == 0 { meta.total = 0; } // 兼容老数据
-                    self.metas.insert(meta.id, meta);
+        if let Some(list) = load_session(self.config.state_path(SESSION_FILE)) {
+            for mut meta in list {
+                // 只恢复未完成任务
+                // 上次运行中的任务已经没有 Actor 在下载，恢复为暂停，重新排队时从 Paused 转为 Queued
+                if matches!(meta.status, TaskStatus::Queued | TaskStatus::Running) {
+                    meta.status = TaskStatus::Paused;
+                }
+                match meta.status {
+                    TaskStatus::Pending | TaskStatus::Paused => {
+                        let config = self.task_config(&meta.url, &meta.options);
+                        let options = self.task_options(&meta.url, &meta.options).unwrap_or_else(|e| {
+                            tracing::error!(task_id = %meta.id, error = %e, "恢复任务时无法解析请求头");
+                            meta.options.clone()
+                        });
+                        let addr = DownloadTaskActor::new(meta.id, config, meta.url.clone(), meta.file.clone())
+                            .with_options(options)
+                            .with_transport(self.transport.clone())
+                            .with_protocols(self.protocols.clone())
+                            .with_resolvers(self.resolvers.clone())
+                            .with_quota(self.quota.clone())
+                            .with_probe_cache(self.probes.clone())
+                            .start();
+                        self.tasks.insert(meta.id, addr);
+                    },
+                    _ => {}
                 }
+                if meta.total == 0 { meta.total = 0; } // 兼容老数据
+                self.metas.insert(meta.id, meta);
             }
         }
     }
 
+    /// 任务结束后调用脚本，并执行配置的钩子命令
+    fn run_finish_hook(&mut self, task_id: Uuid) {
+        let Some(meta) = self.metas.get(&task_id) else { return };
+        let template = match meta.status {
+            TaskStatus::Completed => &self.config.on_complete,
+            TaskStatus::Failed(_) => &self.config.on_failure,
+            _ => return,
+        };
+        if template.is_empty() && self.script.is_none() {
+            return;
+        }
+        let template = template.clone();
+        let ctx = HookContext {
+            path: meta.file.clone(),
+            url: meta.url.clone(),
+            status: meta.status.as_str().to_string(),
+            error: match &meta.status {
+                TaskStatus::Failed(e) => e.clone(),
+                _ => String::new(),
+            },
+        };
+        if let Some(script) = &self.script {
+            script.after_task(&task_id.to_string(), &ctx);
+        }
+        if template.is_empty() {
+            return;
+        }
+        self.spawn_background_job(async move {
+            hooks::run_hook(&template, &ctx).await;
+        });
+    }
+
+    /// 任务结束后推送到标签匹配的 Telegram、Matrix 目标，内容与桌面通知相同
+    fn send_push(&mut self, task_id: Uuid) {
+        let Some(meta) = self.metas.get(&task_id) else { return };
+        let failed = matches!(meta.status, TaskStatus::Failed(_));
+        let targets: Vec<_> = self.config.push.iter().filter(|target| target.matches(&meta.tags, failed)).cloned().collect();
+        if targets.is_empty() {
+            return;
+        }
+        let text = match &meta.status {
+            TaskStatus::Completed => format!("{}\n{}", t(Msg::NotifyCompletedTitle), meta.file),
+            TaskStatus::Failed(error) => format!("{}\n{}", t(Msg::NotifyFailedTitle), tf(Msg::NotifyFailedBody, &[&meta.file, error])),
+            _ => return,
+        };
+        let transport = AwcTransport::new(&self.config);
+        let timeout = std::time::Duration::from_secs(self.config.timeout);
+        self.spawn_background_job(async move {
+            push::send_all(&targets, &text, &transport, timeout).await;
+        });
+    }
+
+    /// 任务结束后写入下载历史，校验和在阻塞线程池中计算
+    fn record_history(&mut self, task_id: Uuid, file_info: Option<&FileInfo>) {
+        let Some(meta) = self.metas.get(&task_id) else { return };
+        let success = meta.status == TaskStatus::Completed;
+        let mut entry = HistoryEntry {
+            task_id: Some(task_id),
+            url: meta.url.clone(),
+            path: meta.file.clone(),
+            status: meta.status.as_str().to_string(),
+            size: meta.total.max(meta.downloaded),
+            duration_secs: meta.duration_secs(),
+            checksum: None,
+            etag: file_info.and_then(|info| info.etag.clone()),
+            last_modified: file_info.and_then(|info| info.last_modified.clone()),
+            error: match &meta.status {
+                TaskStatus::Failed(e) => Some(e.clone()),
+                _ => None,
+            },
+            timestamp: chrono::Utc::now(),
+        };
+        let store = HistoryStore::new(self.config.state_path(HISTORY_FILE));
+        self.spawn_background_job(async move {
+            if success {
+                let path = entry.path.clone();
+                entry.checksum = tokio::task::spawn_blocking(move || history::sha256_file(path).ok())
+                    .await
+                    .ok()
+                    .flatten();
+            }
+            if let Err(e) = store.append(&entry) {
+                tracing::error!("写入下载历史失败: {}", e);
+            }
+        });
+    }
+
+    fn spawn_background_job(&mut self, job: impl std::future::Future<Output = ()> + 'static) {
+        self.background_jobs.retain(|h| !h.is_finished());
+        self.background_jobs.push(actix::spawn(job));
+    }
+
     /// 获取所有任务统计信息
     pub fn get_stats(&self) -> TaskStats {
         let mut stats = TaskStats {
@@ -246,6 +904,8 @@ impl DownloadManagerActor {
             total_bytes: 0,
             downloaded_bytes: 0,
             speed: 0,
+            boosted: self.boosted,
+            clients: BTreeMap::new(),
         };
         let mut total_speed = 0u64;
         for meta in self.metas.values() {
@@ -258,30 +918,33 @@ impl DownloadManagerActor {
             }
             stats.total_bytes += meta.total;
             stats.downloaded_bytes += meta.downloaded;
-            // 这里假设 meta.progress 代表当前速度（需任务actor定期更新）
-            // 可根据实际情况调整
-            total_speed += meta.progress as u64; // 临时用progress字段模拟速度
+            if meta.status == TaskStatus::Running {
+                total_speed += meta.speed;
+            }
+            if let Some(client) = &meta.client {
+                stats.clients.entry(client.clone()).or_default().add(meta);
+            }
         }
-        stats.speed = total_speed; // 真实实现应由任务actor定期上报速度
+        stats.speed = total_speed;
         stats
     }
 
     /// 保存断点续传信息
     #[allow(dead_code)]
     pub fn save_resume_info(&self, resume_info: &ResumeInfo) -> Result<(), DownloadError> {
-        let path = format!("downloads/resume_{}.json", resume_info.task_id);
+        let path = self.config.temp_dir_path().join(format!("resume_{}.json", resume_info.task_id));
         let json = serde_json::to_string_pretty(&resume_info)
             .map_err(|e| DownloadError::Unknown(format!("序列化失败: {}", e).into()))?;
         
         std::fs::write(path, json)
-            .map_err(|e| DownloadError::IoError(e.to_string().into()))?;
+            .map_err(|e| DownloadError::io_error_with_context("写入断点续传信息", e))?;
         Ok(())
     }
     
     /// 加载断点续传信息
     #[allow(dead_code)]
     pub fn load_resume_info(&self, task_id: Uuid) -> Option<ResumeInfo> {
-        let path = format!("downloads/resume_{}.json", task_id);
+        let path = self.config.temp_dir_path().join(format!("resume_{}.json", task_id));
         std::fs::read_to_string(path)
             .ok()
             .and_then(|content| serde_json::from_str(&content).ok())
@@ -336,11 +999,11 @@ impl DownloadManagerActor {
 
     /// 从 resume_*.json 文件加载并恢复任务
     fn load_tasks_from_resume_files(&mut self) {
-        let resume_dir = "downloads/";
+        let resume_dir = self.config.temp_dir_path();
         if let Ok(entries) = fs::read_dir(resume_dir) {
             for entry in entries.filter_map(Result::ok) {
                 let path = entry.path();
-                if path.is_file() && path.to_str().map_or(false, |s| s.ends_with(".json") && s.contains("resume_")) {
+                if path.is_file() && path.to_str().is_some_and(|s| s.ends_with(".json") && s.contains("resume_")) {
                     if let Ok(content) = fs::read_to_string(&path) {
                         if let Ok(resume_info) = serde_json::from_str::<ResumeInfo>(&content) {
                             // 避免重复添加已存在的任务
@@ -348,14 +1011,21 @@ impl DownloadManagerActor {
                                 continue;
                             }
 
-                            println!("[actor_manager] 正在恢复任务: {}", resume_info.task_id);
+                            tracing::info!(task_id = %resume_info.task_id, "正在恢复任务");
 
                             // 创建 Actor 和 Meta
                             let task_actor = DownloadTaskActor::new(
-                                self.config.clone(), 
+                                resume_info.task_id,
+                                self.config.for_url(&resume_info.url),
                                 resume_info.url.clone(), 
                                 resume_info.file.clone()
-                            ).start();
+                            )
+                            .with_transport(self.transport.clone())
+                            .with_protocols(self.protocols.clone())
+                            .with_resolvers(self.resolvers.clone())
+                            .with_quota(self.quota.clone())
+                            .with_probe_cache(self.probes.clone())
+                            .start();
                             
                             let meta = DownloadTaskMeta {
                                 id: resume_info.task_id,
@@ -365,6 +1035,19 @@ impl DownloadManagerActor {
                                 progress: 0.0, // 进度将在任务启动后更新
                                 downloaded: 0, // 同样，将在启动后更新
                                 total: resume_info.total_size,
+                                speed: 0,
+                                started_at: None,
+                                finished_at: None,
+                                retries: 0,
+                                error_kind: None,
+                                error_code: None,
+                                http_status: None,
+                                metrics: None,
+                                options: TaskOptions::default(),
+                                tags: Vec::new(),
+                                phase: None,
+                                phase_progress: None,
+                                client: None,
                             };
 
                             self.tasks.insert(resume_info.task_id, task_actor);
@@ -377,6 +1060,29 @@ impl DownloadManagerActor {
     }
 }
 
+/// 按保留策略应移出会话的已结束（完成或取消）任务：结束时间早于 `keep_days` 天前的，
+/// 以及按结束时间从新到旧排在 `keep_count` 个之后的；0 表示该项不限制，没有结束时间的视为最早结束
+fn expired_tasks<'a>(
+    metas: impl Iterator<Item = &'a DownloadTaskMeta>,
+    keep_count: usize,
+    keep_days: u64,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Vec<Uuid> {
+    let mut finished: Vec<&DownloadTaskMeta> = metas
+        .filter(|m| matches!(m.status, TaskStatus::Completed | TaskStatus::Cancelled))
+        .collect();
+    finished.sort_by_key(|m| std::cmp::Reverse(m.finished_at));
+    let cutoff = (keep_days > 0).then(|| now - chrono::Duration::days(keep_days as i64));
+    finished
+        .into_iter()
+        .enumerate()
+        .filter(|(i, m)| {
+            keep_count > 0 && *i >= keep_count || cutoff.is_some_and(|cutoff| m.finished_at.is_none_or(|t| t < cutoff))
+        })
+        .map(|(_, m)| m.id)
+        .collect()
+}
+
 /// 任务统计信息
 #[derive(Debug, Clone, Serialize)]
 pub struct TaskStats {
@@ -388,38 +1094,130 @@ pub struct TaskStats {
     pub total_bytes: u64,
     pub downloaded_bytes: u64,
     pub speed: u64, // B/s
+    pub boosted: Option<Uuid>, // 优先下载的任务
+    /// 按提交任务的客户端分开的统计，没有客户端的任务不计入
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub clients: BTreeMap<String, ClientStats>,
+}
+
+/// 一个客户端的任务统计
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ClientStats {
+    pub total: usize,
+    pub running: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub paused: usize,
+    pub total_bytes: u64,
+    pub downloaded_bytes: u64,
+    pub speed: u64, // B/s
+}
+
+impl ClientStats {
+    /// 把一个任务计入统计
+    pub fn add(&mut self, meta: &DownloadTaskMeta) {
+        self.total += 1;
+        match meta.status {
+            TaskStatus::Running => {
+                self.running += 1;
+                self.speed += meta.speed;
+            }
+            TaskStatus::Completed => self.completed += 1,
+            TaskStatus::Failed(_) => self.failed += 1,
+            TaskStatus::Paused => self.paused += 1,
+            _ => {}
+        }
+        self.total_bytes += meta.total;
+        self.downloaded_bytes += meta.downloaded;
+    }
 }
 
 impl Actor for DownloadManagerActor {
     type Context = Context<Self>;
 
-    fn started(&mut self, _ctx: &mut Self::Context) {
+    fn started(&mut self, ctx: &mut Self::Context) {
         if self.config.auto_resume_on_startup {
-            println!("[actor_manager] 启动时自动恢复任务...");
+            tracing::info!("启动时自动恢复任务");
             self.load_tasks_from_resume_files();
         }
+        // 定期保存进度，供 `multidown status` 读取
+        ctx.run_interval(SESSION_SAVE_INTERVAL, |act, _ctx| {
+            if act.dirty {
+                act.save_tasks_to_file();
+            }
+        });
+        self.apply_retention();
+        ctx.run_interval(RETENTION_INTERVAL, |act, _ctx| act.apply_retention());
+        ctx.run_interval(BANDWIDTH_REBALANCE_INTERVAL, |act, _ctx| act.rebalance_bandwidth());
+        // 在 systemd 的看门狗下运行时由管理器自己的事件循环发送心跳，管理器卡住时服务会被重启
+        if let Some(interval) = systemd::watchdog_interval() {
+            ctx.run_interval(interval, |_act, _ctx| {
+                systemd::notify("WATCHDOG=1");
+            });
+        }
     }
 }
 
 impl Handler<CreateTask> for DownloadManagerActor {
     type Result = Result<Uuid, DownloadError>;
 
-    fn handle(&mut self, msg: CreateTask, _ctx: &mut Self::Context) -> Self::Result {
-        let config = self.config.clone();
+    fn handle(&mut self, CreateTask(mut msg): CreateTask, _ctx: &mut Self::Context) -> Self::Result {
+        // 脚本可以改写地址、附加请求头或拒绝任务，结果随任务一起保存
+        if let Some(script) = &self.script {
+            let scripted = script.before_task(&msg.url, &msg.file)?;
+            msg.url = scripted.url;
+            // 任务自己指定的同名请求头优先
+            for (name, value) in scripted.headers {
+                if !msg.options.headers.iter().any(|(n, _)| n.eq_ignore_ascii_case(&name)) {
+                    msg.options.headers.push((name, value));
+                }
+            }
+        }
+        // 创建任务时应用 URL 规则和任务选项
+        let config = self.task_config(&msg.url, &msg.options);
+        config.validate()?;
+        let options = self.task_options(&msg.url, &msg.options)?;
+        let file = match &msg.options.output_name {
+            Some(name) => std::path::Path::new(&msg.file).with_file_name(name).to_string_lossy().to_string(),
+            None => msg.file,
+        };
+        // 文件名可能来自命令行、任务选项或 URL，确保最终路径没有逃出下载目录，并且在当前平台上可以创建
+        let file = crate::utils::validator::output_path(&config.download_dir, &file)?;
         let id = Uuid::new_v4();
-        let actor = DownloadTaskActor::new(config, msg.url.clone(), msg.file.clone());
+        let actor = DownloadTaskActor::new(id, config, msg.url.clone(), file.clone())
+            .with_options(options)
+            .with_transport(self.transport.clone())
+            .with_protocols(self.protocols.clone())
+            .with_resolvers(self.resolvers.clone())
+            .with_quota(self.quota.clone())
+            .with_probe_cache(self.probes.clone());
         let addr = actor.start();
         self.tasks.insert(id, addr);
 
         let meta = DownloadTaskMeta {
             id,
             url: msg.url,
-            file: msg.file,
+            file,
             status: TaskStatus::Pending,
             progress: 0.0,
             downloaded: 0,
             total: 0,
+            speed: 0,
+            started_at: None,
+            finished_at: None,
+            retries: 0,
+            error_kind: None,
+            error_code: None,
+            http_status: None,
+            options: msg.options,
+            metrics: None,
+            tags: msg.tags,
+            phase: None,
+            phase_progress: None,
+            client: msg.client,
         };
+        tracing::info!(task_id = %id, url = %meta.url, file = %meta.file, "创建下载任务");
+        self.events.emit(TaskEvent::Created { task_id: id, url: meta.url.clone(), file: meta.file.clone() });
         self.metas.insert(id, meta);
         self.save_tasks_to_file();
         Ok(id)
@@ -429,33 +1227,64 @@ impl Handler<CreateTask> for DownloadManagerActor {
 #[derive(Message)]
 #[rtype(result = "()")]
 struct InternalStartTask {
+    /// 发起这次申请的任务，拿到的名额不一定给它
     task_id: Uuid,
-    permit: tokio::sync::OwnedSemaphorePermit,
+    permit: task_messages::DownloadPermit,
 }
 
 impl Handler<StartTaskFromMeta> for DownloadManagerActor {
     type Result = ();
 
     fn handle(&mut self, msg: StartTaskFromMeta, ctx: &mut Self::Context) -> Self::Result {
+        if self.queue.contains(&msg.task_id) || self.moving.contains(&msg.task_id) || !self.transition(msg.task_id, TaskStatus::Queued) {
+            return;
+        }
+        self.queue.push(msg.task_id);
+        // 排队期间提前解析主机名，拿到名额后不必再等 DNS
+        if let Some(meta) = self.metas.get(&msg.task_id) {
+            self.transport.prefetch(&meta.url);
+        }
         let sem = self.semaphore.clone();
-        let addr = ctx.address();
-        
-        async move {
-            let permit = sem.acquire_owned().await.unwrap();
-            addr.do_send(InternalStartTask { task_id: msg.task_id, permit });
+        let client_sem = self.slot_group(&msg.task_id).and_then(|client| self.client_slots.get(client)).cloned();
+        let task_id = msg.task_id;
+
+        // 拿到名额后再决定启动哪个任务，这样高优先级的任务可以插队；
+        // 名额直接交给 Actor 处理，不经过邮箱，申请被取消后不会再有名额送达。
+        // 先取客户端的名额再取全局名额，达到客户端上限的任务不会占着全局名额等待
+        let acquire = async move {
+            let client = match client_sem {
+                Some(client_sem) => Some(client_sem.acquire_owned().await.ok()?),
+                None => None,
+            };
+            let slot = sem.acquire_owned().await.ok()?;
+            Some(task_messages::DownloadPermit { slot, client })
         }
-        .into_actor(self)
-        .spawn(ctx);
+            .into_actor(self)
+            .map(move |permit, act, ctx| {
+                if let Some(permit) = permit {
+                    act.handle(InternalStartTask { task_id, permit }, ctx);
+                }
+            });
+        let handle = ctx.spawn(acquire);
+        self.waiting.insert(task_id, handle);
     }
 }
 
 impl Handler<InternalStartTask> for DownloadManagerActor {
     type Result = ();
     fn handle(&mut self, msg: InternalStartTask, ctx: &mut Self::Context) {
-        if let Some(task_addr) = self.tasks.get(&msg.task_id) {
-            if let Some(meta) = self.metas.get_mut(&msg.task_id) {
-                meta.status = TaskStatus::Running;
+        self.waiting.remove(&msg.task_id);
+        // 每个排队任务都有一个申请，队列为空说明状态不一致，直接释放名额
+        let group = self.slot_group(&msg.task_id).map(str::to_string);
+        let Some(task_id) = self.next_queued_task(group.as_deref()) else { return };
+        // 名额给了别的任务时，被选中任务的申请转给发起者，保证每个排队任务仍然各有一个申请
+        if task_id != msg.task_id {
+            if let Some(handle) = self.waiting.remove(&task_id) {
+                self.waiting.insert(msg.task_id, handle);
             }
+        }
+        // 任务在 Actor 真正开始下载后（MarkTaskStarted）才转为 Running
+        if let Some(task_addr) = self.tasks.get(&task_id) {
             task_addr.do_send(task_messages::StartTask {
                 manager_addr: ctx.address(),
                 permit: msg.permit,
@@ -464,10 +1293,40 @@ impl Handler<InternalStartTask> for DownloadManagerActor {
     }
 }
 
+impl Handler<MarkTaskStarted> for DownloadManagerActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: MarkTaskStarted, _ctx: &mut Self::Context) {
+        // 名额在途时任务可能已被暂停，这时 Actor 随后会处理暂停消息，状态保持不变
+        if self.metas.get(&msg.task_id).is_none_or(|m| m.status != TaskStatus::Queued) {
+            return;
+        }
+        self.transition(msg.task_id, TaskStatus::Running);
+        if let Some(meta) = self.metas.get_mut(&msg.task_id) {
+            meta.started_at = Some(chrono::Utc::now());
+            meta.finished_at = None;
+            let mut metrics = PerformanceMetrics::new(msg.task_id);
+            metrics.resumed_bytes = meta.downloaded;
+            metrics.downloaded_bytes = meta.downloaded;
+            metrics.total_bytes = meta.total;
+            meta.metrics = Some(metrics);
+        }
+        self.events.emit(TaskEvent::Started { task_id: msg.task_id });
+    }
+}
+
 impl Handler<PauseTask> for DownloadManagerActor {
     type Result = ();
 
-    fn handle(&mut self, msg: PauseTask, _ctx: &mut Self::Context) {
+    fn handle(&mut self, msg: PauseTask, ctx: &mut Self::Context) {
+        self.dequeue(&msg.0, ctx);
+        self.chunk_stats.remove(&msg.0);
+        if !self.transition(msg.0, TaskStatus::Paused) {
+            return;
+        }
+        if let Some(meta) = self.metas.get_mut(&msg.0) {
+            meta.speed = 0;
+        }
         if let Some(addr) = self.tasks.get(&msg.0) {
             addr.do_send(task_messages::PauseTask);
         }
@@ -477,31 +1336,295 @@ impl Handler<PauseTask> for DownloadManagerActor {
 impl Handler<CancelTask> for DownloadManagerActor {
     type Result = ();
 
-    fn handle(&mut self, msg: CancelTask, _ctx: &mut Self::Context) {
-        if let Some(addr) = self.tasks.get(&msg.0) {
-            if let Some(meta) = self.metas.get_mut(&msg.0) {
-                meta.status = TaskStatus::Cancelled;
-            }
+    fn handle(&mut self, msg: CancelTask, ctx: &mut Self::Context) {
+        self.dequeue(&msg.0, ctx);
+        self.chunk_stats.remove(&msg.0);
+        if !self.transition(msg.0, TaskStatus::Cancelled) {
+            return;
+        }
+        if let Some(meta) = self.metas.get_mut(&msg.0) {
+            meta.speed = 0;
+            meta.finished_at = Some(chrono::Utc::now());
+        }
+        // 任务 Actor 清理临时文件后自行停止
+        if let Some(addr) = self.tasks.remove(&msg.0) {
             addr.do_send(task_messages::CancelTask);
         }
     }
 }
 
+impl Handler<RetryFailedTasks> for DownloadManagerActor {
+    type Result = Vec<Uuid>;
+
+    fn handle(&mut self, msg: RetryFailedTasks, ctx: &mut Self::Context) -> Self::Result {
+        let mut failed: Vec<&DownloadTaskMeta> = self
+            .metas
+            .values()
+            .filter(|m| matches!(m.status, TaskStatus::Failed(_)))
+            .filter(|m| msg.task_ids.as_ref().is_none_or(|ids| ids.contains(&m.id)))
+            .collect();
+        failed.sort_by(|a, b| a.started_at.cmp(&b.started_at).then_with(|| a.file.cmp(&b.file)));
+        let failed: Vec<Uuid> = failed.into_iter().map(|m| m.id).collect();
+
+        for id in &failed {
+            let Some(meta) = self.metas.get(id) else { continue };
+            let config = self.task_config(&meta.url, &meta.options);
+            let options = match self.task_options(&meta.url, &meta.options) {
+                Ok(options) => options,
+                Err(e) => {
+                    tracing::error!(task_id = %id, error = %e, "重试任务时无法解析请求头");
+                    continue;
+                }
+            };
+            // 分块下载的进度保存在临时目录中，用同一个任务 ID 重建 Actor 即可续传；
+            // 失败任务留下的输出文件（不分块的下载或合并到一半）不完整，删除后重新下载，
+            // 启用 continue_partial 时保留，从它的末尾续传
+            if !config.continue_partial && std::path::Path::new(&meta.file).exists() {
+                if let Err(e) = fs::remove_file(&meta.file) {
+                    tracing::error!(task_id = %id, file = %meta.file, error = %e, "无法删除不完整的文件");
+                    continue;
+                }
+            }
+            let addr = DownloadTaskActor::new(*id, config, meta.url.clone(), meta.file.clone())
+                .with_options(options)
+                .with_transport(self.transport.clone())
+                .with_protocols(self.protocols.clone())
+                .with_resolvers(self.resolvers.clone())
+                .with_quota(self.quota.clone())
+                .with_probe_cache(self.probes.clone())
+                .start();
+            self.tasks.insert(*id, addr);
+            self.transition(*id, TaskStatus::Pending);
+            if let Some(meta) = self.metas.get_mut(id) {
+                tracing::info!(task_id = %id, url = %meta.url, retries = meta.retries, "重新排队失败的任务");
+                meta.speed = 0;
+                meta.finished_at = None;
+                meta.error_kind = None;
+                meta.error_code = None;
+                meta.http_status = None;
+            }
+            ctx.notify(StartTaskFromMeta { task_id: *id });
+        }
+        self.save_tasks_to_file();
+        failed.into_iter().filter(|id| self.metas.get(id).is_some_and(|m| m.status == TaskStatus::Pending)).collect()
+    }
+}
+
+impl Handler<ImportQueue> for DownloadManagerActor {
+    type Result = Vec<Uuid>;
+
+    fn handle(&mut self, msg: ImportQueue, ctx: &mut Self::Context) -> Self::Result {
+        let mut imported = Vec::new();
+        for task in msg.0 {
+            let file = task.local_path(&self.config.download_dir);
+            if self.metas.values().any(|m| task.is_duplicate(&file, m)) {
+                tracing::info!(url = %task.url, file = %file, "会话中已有该任务，跳过");
+                continue;
+            }
+            let config = self.task_config(&task.url, &task.options);
+            let result = crate::utils::validator::parse_url(&task.url)
+                .and_then(|_| config.validate())
+                .and_then(|_| self.task_options(&task.url, &task.options))
+                .and_then(|options| Ok((options, crate::utils::validator::output_path(&config.download_dir, &file)?)));
+            let (options, file) = match result {
+                Ok(result) => result,
+                Err(e) => {
+                    tracing::error!(url = %task.url, error = %e, "无法导入任务");
+                    continue;
+                }
+            };
+            // 沿用原任务 ID，临时目录中的分块一起拷贝过来时可以续传
+            let id = task.id.unwrap_or_else(Uuid::new_v4);
+            let addr = DownloadTaskActor::new(id, config, task.url.clone(), file.clone())
+                .with_options(options)
+                .with_transport(self.transport.clone())
+                .with_protocols(self.protocols.clone())
+                .with_resolvers(self.resolvers.clone())
+                .with_quota(self.quota.clone())
+                .with_probe_cache(self.probes.clone())
+                .start();
+            self.tasks.insert(id, addr);
+            tracing::info!(task_id = %id, url = %task.url, file = %file, "导入任务");
+            self.events.emit(TaskEvent::Created { task_id: id, url: task.url.clone(), file: file.clone() });
+            self.metas.insert(id, DownloadTaskMeta {
+                id,
+                url: task.url,
+                file,
+                status: TaskStatus::Pending,
+                progress: if task.total > 0 { task.downloaded as f32 / task.total as f32 * 100.0 } else { 0.0 },
+                downloaded: task.downloaded,
+                total: task.total,
+                speed: 0,
+                started_at: None,
+                finished_at: None,
+                retries: 0,
+                error_kind: None,
+                error_code: None,
+                http_status: None,
+                options: task.options,
+                metrics: None,
+                tags: task.tags,
+                phase: None,
+                phase_progress: None,
+                client: None,
+            });
+            ctx.notify(StartTaskFromMeta { task_id: id });
+            imported.push(id);
+        }
+        self.save_tasks_to_file();
+        imported
+    }
+}
+
+impl Handler<RemoveTask> for DownloadManagerActor {
+    type Result = bool;
+
+    fn handle(&mut self, msg: RemoveTask, ctx: &mut Self::Context) -> bool {
+        if !self.metas.contains_key(&msg.task_id) || self.moving.contains(&msg.task_id) {
+            return false;
+        }
+        // 先按取消处理（释放名额、停止任务 Actor），再删除剩下的数据和元数据
+        <Self as Handler<CancelTask>>::handle(self, CancelTask(msg.task_id), ctx);
+        self.held.remove(&msg.task_id);
+        if let Some(meta) = self.metas.remove(&msg.task_id) {
+            let removed = purge_task_data(&self.config, &meta, msg.with_data);
+            tracing::info!(task_id = %msg.task_id, removed = removed.len(), "已从会话中删除任务");
+        }
+        self.events.emit(TaskEvent::Removed { task_id: msg.task_id });
+        self.save_tasks_to_file();
+        true
+    }
+}
+
+impl DownloadManagerActor {
+    /// 检查任务能否移动，返回要做的移动和移动前是否在下载
+    fn prepare_move(&self, msg: &MoveTask) -> Result<(Relocation, bool), DownloadError> {
+        let meta = self
+            .metas
+            .get(&msg.task_id)
+            .ok_or_else(|| DownloadError::unknown(format!("任务不存在: {}", msg.task_id)))?;
+        if matches!(meta.status, TaskStatus::Completed | TaskStatus::Cancelled) {
+            return Err(DownloadError::unknown("任务已经结束，请直接移动文件"));
+        }
+        if meta.phase.is_some() || self.moving.contains(&msg.task_id) {
+            return Err(DownloadError::unknown("任务正在合并、校验或移动，请稍后再试"));
+        }
+        let config = self.task_config(&meta.url, &meta.options);
+        let mut moved = config.clone();
+        if let Some(dir) = &msg.temp_dir {
+            moved.temp_dir = dir.clone();
+        }
+        let relocation = Relocation {
+            task_id: msg.task_id,
+            from: meta.file.clone(),
+            to: relocate::target_path(&config.download_dir, &msg.path)?,
+            temp_from: config.temp_dir_path(),
+            temp_to: moved.temp_dir_path(),
+        };
+        let active = matches!(meta.status, TaskStatus::Pending | TaskStatus::Queued | TaskStatus::Running);
+        Ok((relocation, active))
+    }
+
+    /// 数据移动完成后更新元数据，并用新路径重建任务 Actor
+    fn finish_move(&mut self, relocation: &Relocation, temp_dir: Option<String>) -> Result<(), DownloadError> {
+        let Some(meta) = self.metas.get_mut(&relocation.task_id) else { return Ok(()) };
+        meta.file = relocation.to.clone();
+        if temp_dir.is_some() {
+            meta.options.temp_dir = temp_dir;
+        }
+        let (url, options) = (meta.url.clone(), meta.options.clone());
+        let config = self.task_config(&url, &options);
+        let resolved = self.task_options(&url, &options)?;
+        let addr = DownloadTaskActor::new(relocation.task_id, config, url, relocation.to.clone())
+            .with_options(resolved)
+            .with_transport(self.transport.clone())
+            .with_protocols(self.protocols.clone())
+            .with_resolvers(self.resolvers.clone())
+            .with_quota(self.quota.clone())
+            .with_probe_cache(self.probes.clone())
+            .start();
+        self.tasks.insert(relocation.task_id, addr);
+        self.events.emit(TaskEvent::Moved { task_id: relocation.task_id, file: relocation.to.clone() });
+        self.save_tasks_to_file();
+        Ok(())
+    }
+}
+
+impl Handler<MoveTask> for DownloadManagerActor {
+    type Result = ResponseActFuture<Self, Result<String, DownloadError>>;
+
+    fn handle(&mut self, msg: MoveTask, ctx: &mut Self::Context) -> Self::Result {
+        let (relocation, active) = match self.prepare_move(&msg) {
+            Ok(prepared) => prepared,
+            Err(e) => return Box::pin(fut::ready(Err(e))),
+        };
+        let task_id = msg.task_id;
+        if active {
+            self.handle(PauseTask(task_id), ctx);
+        }
+        self.moving.insert(task_id);
+        let old = self.tasks.get(&task_id).cloned();
+        tracing::info!(task_id = %task_id, from = %relocation.from, to = %relocation.to, "移动任务数据");
+        let work = async move {
+            // 邮箱按顺序处理，查询返回时任务 Actor 已经处理完暂停消息，不再写入
+            if let Some(addr) = old {
+                let _ = addr.send(task_messages::QueryStatus).await;
+            }
+            tokio::task::spawn_blocking(move || relocation.run().map(|()| relocation))
+                .await
+                .map_err(|e| DownloadError::unknown(format!("移动任务数据失败: {}", e)))?
+        };
+        Box::pin(work.into_actor(self).map(move |result, act, ctx| {
+            act.moving.remove(&task_id);
+            let moved = result.and_then(|relocation| {
+                act.finish_move(&relocation, msg.temp_dir)?;
+                Ok(relocation.to)
+            });
+            if let Err(e) = &moved {
+                tracing::error!(task_id = %task_id, error = %e, "移动任务数据失败");
+            }
+            // 失败时数据还在原处，同样恢复下载
+            if active {
+                act.handle(StartTaskFromMeta { task_id }, ctx);
+            }
+            moved
+        }))
+    }
+}
+
+impl Handler<BoostTask> for DownloadManagerActor {
+    type Result = bool;
+
+    fn handle(&mut self, msg: BoostTask, _ctx: &mut Self::Context) -> bool {
+        if let Some(task_id) = msg.0 {
+            if !self.metas.get(&task_id).is_some_and(|m| m.status == TaskStatus::Running) {
+                return false;
+            }
+        }
+        if self.boosted != msg.0 {
+            tracing::info!(task_id = ?msg.0, "设置优先下载的任务");
+            self.boosted = msg.0;
+            self.push_task_configs();
+        }
+        true
+    }
+}
+
+impl Handler<HoldTasks> for DownloadManagerActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: HoldTasks, _ctx: &mut Self::Context) {
+        self.held.extend(msg.0);
+    }
+}
+
 impl Handler<QueryTaskProgress> for DownloadManagerActor {
     type Result = LocalBoxFuture<'static, Result<f32, ()>>;
 
     fn handle(&mut self, msg: QueryTaskProgress, _ctx: &mut Self::Context) -> Self::Result {
-        if let Some(addr) = self.tasks.get(&msg.0) {
-            let addr = addr.clone();
-            Box::pin(async move {
-                match addr.send(task_messages::QueryProgress).await {
-                    Ok(val) => Ok(val),
-                    Err(_) => Err(()),
-                }
-            })
-        } else {
-            Box::pin(async { Err(()) })
-        }
+        // 已结束任务的 Actor 已经停止，以元数据为准
+        let progress = self.metas.get(&msg.0).map(|m| m.progress).ok_or(());
+        Box::pin(async move { progress })
     }
 }
 
@@ -509,16 +1632,8 @@ impl Handler<QueryTaskStatus> for DownloadManagerActor {
     type Result = LocalBoxFuture<'static, Result<TaskStatus, ()>>;
 
     fn handle(&mut self, msg: QueryTaskStatus, _ctx: &mut Self::Context) -> Self::Result {
-        if let Some(addr) = self.tasks.get(&msg.0) {
-            let addr = addr.clone();
-            Box::pin(async move {
-                addr.send(task_messages::QueryStatus)
-                    .await
-                    .unwrap_or(Err(()))
-            })
-        } else {
-            Box::pin(async { Err(()) })
-        }
+        let status = self.metas.get(&msg.0).map(|m| m.status.clone()).ok_or(());
+        Box::pin(async move { status })
     }
 }
 
@@ -530,6 +1645,113 @@ impl Handler<QueryTaskDetail> for DownloadManagerActor {
     }
 }
 
+impl Handler<ListTasks> for DownloadManagerActor {
+    type Result = MessageResult<ListTasks>;
+
+    fn handle(&mut self, _msg: ListTasks, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.metas.values().cloned().collect())
+    }
+}
+
+impl Handler<QueryChunkStats> for DownloadManagerActor {
+    type Result = MessageResult<QueryChunkStats>;
+
+    fn handle(&mut self, _msg: QueryChunkStats, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.chunk_stats.clone())
+    }
+}
+
+impl Handler<PauseAllAndSave> for DownloadManagerActor {
+    type Result = ();
+
+    fn handle(&mut self, _msg: PauseAllAndSave, ctx: &mut Self::Context) {
+        let active: Vec<Uuid> = self
+            .metas
+            .values()
+            .filter(|m| m.status.can_transition_to(&TaskStatus::Paused))
+            .map(|m| m.id)
+            .collect();
+        for id in active {
+            self.handle(PauseTask(id), ctx);
+        }
+        self.save_tasks_to_file();
+    }
+}
+
+impl Handler<SubscribeEvents> for DownloadManagerActor {
+    type Result = MessageResult<SubscribeEvents>;
+
+    fn handle(&mut self, _msg: SubscribeEvents, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.events.clone())
+    }
+}
+
+impl Handler<WaitForBackgroundJobs> for DownloadManagerActor {
+    type Result = ResponseFuture<()>;
+
+    fn handle(&mut self, _msg: WaitForBackgroundJobs, _ctx: &mut Self::Context) -> Self::Result {
+        let handles = std::mem::take(&mut self.background_jobs);
+        Box::pin(async move {
+            futures::future::join_all(handles).await;
+        })
+    }
+}
+
+impl Handler<ReloadConfig> for DownloadManagerActor {
+    type Result = ();
+
+    fn handle(&mut self, mut msg: ReloadConfig, _ctx: &mut Self::Context) {
+        if msg.0.state_dir != self.config.state_dir {
+            // 会话锁和控制通道在启动时已经建立在原来的目录中
+            tracing::warn!(state_dir = %msg.0.state_dir, "会话目录的修改在重新启动后生效");
+            msg.0.state_dir = self.config.state_dir.clone();
+        }
+        let old = self.config.download_slots();
+        let new = msg.0.download_slots();
+        if new > old {
+            self.semaphore.add_permits(new - old);
+        } else if new < old {
+            // 运行中的任务不会被中断，等它们释放许可后再回收多余的许可
+            let sem = self.semaphore.clone();
+            let excess = (old - new) as u32;
+            actix::spawn(async move {
+                if let Ok(permits) = sem.acquire_many_owned(excess).await {
+                    permits.forget();
+                }
+            });
+        }
+        let network_changed = SocketOptions::new(&msg.0) != SocketOptions::new(&self.config)
+            || msg.0.dns_resolver != self.config.dns_resolver
+            || msg.0.dns_cache_ttl != self.config.dns_cache_ttl
+            || msg.0.circuit_breaker_threshold != self.config.circuit_breaker_threshold
+            || msg.0.circuit_breaker_cooldown != self.config.circuit_breaker_cooldown;
+        if network_changed {
+            // 之后创建的连接使用新的 TCP 选项、DNS 和熔断设置，进行中的任务继续使用原来的连接
+            self.transport = CircuitBreakerTransport::wrap(Rc::new(AwcTransport::new(&msg.0)), &msg.0);
+        }
+        if msg.0.rsync_program != self.config.rsync_program || msg.0.timeout != self.config.timeout {
+            self.protocols.reconfigure(&msg.0);
+        }
+        if msg.0.resolvers != self.config.resolvers {
+            self.resolvers.reconfigure(&msg.0);
+        }
+        if msg.0.clients != self.config.clients {
+            // 已经拿到或正在申请名额的任务沿用原来的限制，之后排队的任务使用新的限制
+            self.client_slots = client_slots(&msg.0);
+        }
+        if msg.0.script != self.config.script {
+            // 新脚本有错误时继续使用原来的
+            match ScriptHooks::load(&msg.0.script) {
+                Ok(script) => self.script = script,
+                Err(e) => tracing::error!(error = %e, "重新加载脚本失败，继续使用原来的脚本"),
+            }
+        }
+        self.quota.set_limit(msg.0.run_quota);
+        self.config = msg.0;
+        self.push_task_configs();
+    }
+}
+
 impl Handler<GetStats> for DownloadManagerActor {
     type Result = MessageResult<GetStats>;
 
@@ -546,19 +1768,72 @@ impl Handler<UpdateTaskProgress> for DownloadManagerActor {
             meta.progress = msg.progress;
             meta.downloaded = msg.downloaded;
             meta.total = msg.total;
+            meta.speed = msg.speed;
+            match msg.chunks {
+                Some(chunks) if meta.status == TaskStatus::Running => {
+                    self.chunk_stats.insert(msg.task_id, chunks);
+                }
+                _ => {
+                    self.chunk_stats.remove(&msg.task_id);
+                }
+            }
+            if let Some(metrics) = &mut meta.metrics {
+                metrics.downloaded_bytes = msg.downloaded;
+                metrics.total_bytes = msg.total;
+                metrics.update_speed(msg.speed);
+            }
+            self.events.emit(TaskEvent::Progress {
+                task_id: msg.task_id,
+                downloaded: msg.downloaded,
+                total: msg.total,
+                speed: msg.speed,
+            });
+            self.dirty = true;
         }
     }
 }
 
+impl Handler<UpdateTaskPhase> for DownloadManagerActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: UpdateTaskPhase, _ctx: &mut Self::Context) {
+        let Some(meta) = self.metas.get_mut(&msg.task_id) else { return };
+        if meta.status != TaskStatus::Running {
+            return;
+        }
+        meta.phase = Some(msg.phase);
+        meta.phase_progress = Some(msg.progress);
+        self.events.emit(TaskEvent::Phase { task_id: msg.task_id, phase: msg.phase, progress: msg.progress });
+        self.dirty = true;
+    }
+}
+
 impl Handler<MarkTaskCompleted> for DownloadManagerActor {
     type Result = ();
 
     fn handle(&mut self, msg: MarkTaskCompleted, _ctx: &mut Self::Context) {
+        self.chunk_stats.remove(&msg.task_id);
+        if !self.transition(msg.task_id, TaskStatus::Completed) {
+            return;
+        }
+        self.tasks.remove(&msg.task_id);
         if let Some(meta) = self.metas.get_mut(&msg.task_id) {
-            meta.status = TaskStatus::Completed;
             meta.progress = 100.0;
-            println!("[actor_manager] MarkTaskCompleted: 任务 {:?} 状态已设为 Completed", msg.task_id);
+            meta.speed = 0;
+            meta.finished_at = Some(chrono::Utc::now());
+            if let Some(metrics) = &mut meta.metrics {
+                metrics.downloaded_bytes = metrics.downloaded_bytes.max(meta.downloaded);
+                metrics.finish();
+            }
+            tracing::debug!(task_id = %msg.task_id, "任务已完成");
+            if self.config.notify_on_finish {
+                notify::send_notification(t(Msg::NotifyCompletedTitle), &meta.file);
+            }
         }
+        self.events.emit(TaskEvent::Completed { task_id: msg.task_id });
+        self.run_finish_hook(msg.task_id);
+        self.send_push(msg.task_id);
+        self.record_history(msg.task_id, msg.file_info.as_ref());
         self.save_tasks_to_file();
     }
 }
@@ -567,9 +1842,143 @@ impl Handler<MarkTaskFailed> for DownloadManagerActor {
     type Result = ();
 
     fn handle(&mut self, msg: MarkTaskFailed, _ctx: &mut Self::Context) {
+        self.chunk_stats.remove(&msg.task_id);
+        // 暂停或取消后中止的请求也可能以失败告终，这时保持原状态
+        if !self.transition(msg.task_id, TaskStatus::Failed(msg.error.to_string())) {
+            return;
+        }
+        // 重试时会创建新的任务 Actor，并重新探测文件信息
+        self.tasks.remove(&msg.task_id);
         if let Some(meta) = self.metas.get_mut(&msg.task_id) {
-            meta.status = TaskStatus::Failed(msg.error.to_string());
+            self.probes.remove(&meta.url);
+            meta.speed = 0;
+            meta.error_kind = Some(msg.error.kind().to_string());
+            meta.error_code = Some(msg.error.code());
+            meta.http_status = msg.error.status_code();
+            meta.finished_at = Some(chrono::Utc::now());
+            if let Some(metrics) = &mut meta.metrics {
+                metrics.record_error(msg.error.kind());
+                metrics.finish();
+            }
+            if self.config.notify_on_finish {
+                let body = tf(Msg::NotifyFailedBody, &[&meta.file, &msg.error]);
+                notify::send_notification(t(Msg::NotifyFailedTitle), &body);
+            }
         }
+        self.events.emit(TaskEvent::Failed { task_id: msg.task_id, error: msg.error.to_string() });
+        self.run_finish_hook(msg.task_id);
+        self.send_push(msg.task_id);
+        self.record_history(msg.task_id, None);
         self.save_tasks_to_file();
     }
-} 
\ No newline at end of file
+}
+
+impl Handler<RecordTaskRetry> for DownloadManagerActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: RecordTaskRetry, _ctx: &mut Self::Context) {
+        if let Some(meta) = self.metas.get_mut(&msg.task_id) {
+            meta.retries += 1;
+            if let Some(metrics) = &mut meta.metrics {
+                metrics.retry_count += 1;
+                if let Some(kind) = msg.error_kind {
+                    metrics.record_error(kind);
+                }
+            }
+        }
+        self.events.emit(TaskEvent::Retrying { task_id: msg.task_id, error_kind: msg.error_kind });
+    }
+}
+
+impl Handler<RecordChunkFailed> for DownloadManagerActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: RecordChunkFailed, _ctx: &mut Self::Context) {
+        self.events.emit(TaskEvent::ChunkFailed { task_id: msg.task_id, chunk_index: msg.chunk_index, error: msg.error });
+    }
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    fn meta(status: TaskStatus, finished_days_ago: Option<i64>) -> DownloadTaskMeta {
+        DownloadTaskMeta {
+            id: Uuid::new_v4(),
+            url: "https://example.com/a.bin".to_string(),
+            file: "downloads/a.bin".to_string(),
+            status,
+            progress: 100.0,
+            downloaded: 1024,
+            total: 1024,
+            speed: 0,
+            started_at: None,
+            finished_at: finished_days_ago.map(|days| Utc::now() - Duration::days(days)),
+            retries: 0,
+            error_kind: None,
+            error_code: None,
+            http_status: None,
+            options: TaskOptions::default(),
+            metrics: None,
+            tags: Vec::new(),
+            phase: None,
+            phase_progress: None,
+            client: None,
+        }
+    }
+
+    #[test]
+    fn test_find_and_purge_task() {
+        let root = std::env::temp_dir().join(format!("multidown_purge_{}", Uuid::new_v4()));
+        let config = Config { download_dir: root.to_string_lossy().into_owned(), ..Default::default() };
+        let mut task = meta(TaskStatus::Paused, None);
+        task.file = root.join("a.bin").to_string_lossy().into_owned();
+        let temp = config.temp_dir_path();
+        let chunks = temp.join(crate::utils::validator::sanitize_file_name(&task.file));
+        fs::create_dir_all(&chunks).unwrap();
+        fs::write(chunks.join("chunk_0000"), b"ab").unwrap();
+        fs::write(temp.join(format!("resume_{}.bin", task.id)), b"MDRS").unwrap();
+        fs::write(&task.file, b"partial").unwrap();
+
+        let metas = vec![task.clone(), meta(TaskStatus::Completed, None)];
+        let prefix = task.id.to_string()[..8].to_uppercase();
+        assert_eq!(find_tasks(&metas, &prefix).len(), 1);
+        assert!(find_tasks(&metas, &prefix[..4]).is_empty());
+
+        // 不带 --with-data 时保留输出文件；已完成任务的输出文件始终保留
+        assert_eq!(purge_task_data(&config, &task, false).len(), 2);
+        assert!(!chunks.exists() && std::path::Path::new(&task.file).exists());
+        task.status = TaskStatus::Completed;
+        assert!(purge_task_data(&config, &task, true).is_empty());
+        task.status = TaskStatus::Failed("文件已存在".to_string());
+        task.error_kind = Some("file_exists".to_string());
+        assert!(purge_task_data(&config, &task, true).is_empty());
+        task.error_kind = Some("timeout".to_string());
+        assert_eq!(purge_task_data(&config, &task, true).len(), 1);
+        assert!(!std::path::Path::new(&task.file).exists());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_expired_tasks() {
+        let metas = [
+            meta(TaskStatus::Completed, Some(1)),
+            meta(TaskStatus::Cancelled, Some(5)),
+            meta(TaskStatus::Completed, Some(10)),
+            meta(TaskStatus::Completed, None),
+            meta(TaskStatus::Failed("timeout".to_string()), Some(30)),
+            meta(TaskStatus::Paused, None),
+        ];
+        let ids = |expired: Vec<Uuid>| -> Vec<usize> {
+            let mut idx: Vec<usize> = expired.iter().map(|id| metas.iter().position(|m| m.id == *id).unwrap()).collect();
+            idx.sort();
+            idx
+        };
+        let now = Utc::now();
+        // 失败和未结束的任务不清理，没有结束时间的视为最早结束
+        assert_eq!(ids(expired_tasks(metas.iter(), 2, 0, now)), vec![2, 3]);
+        assert_eq!(ids(expired_tasks(metas.iter(), 0, 7, now)), vec![2, 3]);
+        assert_eq!(ids(expired_tasks(metas.iter(), 1, 7, now)), vec![1, 2, 3]);
+        assert!(expired_tasks(metas.iter(), 0, 0, now).is_empty());
+    }
+}
@@ -0,0 +1,188 @@
+//! 总限速在任务之间的分配：`total_speed_limit_kb`（`--total-limit`）
+//!
+//! 每个任务的限速器各自取令牌时，连接多、文件大的任务总是抢到更多带宽，小文件排在后面迟迟下不完。
+//! 设置了总限速时，管理器每秒按权重重新分配一次，把每个任务的份额写进它的限速器：
+//!
+//! - 权重默认由优先级决定（低 1、普通 2、高 4、紧急 8），也可以用 `weight` 单独指定；
+//!   优先下载（TUI 中按 `b`）的任务权重再乘以 [`BOOST_FACTOR`]；
+//! - 上一秒没有用完份额的任务（服务器慢、自身限速）只分到它实际需要的部分，
+//!   省下的按权重分给其他任务，总限速不会因为个别慢任务而浪费；
+//! - 所有任务都用不完时，剩余部分仍按权重分给所有任务，速度上升时不必等下一次分配；
+//! - 限制了速度的客户端（见 [`crate::config::clients`]）先按同样的方式把它的限速分给自己的任务，
+//!   分到的份额作为这些任务的上限参与总限速的分配，客户端用不完的部分留给其他任务。
+
+use crate::core::actor_manager::TaskPriority;
+use crate::core::task::TaskOptions;
+
+/// 优先下载的任务权重放大的倍数
+pub const BOOST_FACTOR: u64 = 16;
+
+/// 每个任务至少分到的份额（B/s），避免份额过小时连接超时
+pub const MIN_SHARE: u64 = 16 * 1024;
+
+/// 速度达到份额的这个比例时认为任务还想要更多带宽
+const SATURATED_RATIO: f64 = 0.8;
+
+/// 没有用完份额的任务下一次分到实际速度的这个倍数，留出上升的余地
+const HEADROOM: f64 = 1.25;
+
+/// 任务的权重：指定了 `weight` 时使用它，否则由优先级决定
+pub fn weight(options: &TaskOptions) -> u64 {
+    options.weight.map(u64::from).unwrap_or(match options.priority {
+        TaskPriority::Low => 1,
+        TaskPriority::Normal => 2,
+        TaskPriority::High => 4,
+        TaskPriority::Critical => 8,
+    })
+}
+
+/// 参与分配的一个任务
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Demand {
+    pub weight: u64,
+    /// 上一次分到的份额（B/s），0 表示刚开始下载
+    pub share: u64,
+    /// 最近的下载速度（B/s），0 表示还不知道
+    pub speed: u64,
+    /// 任务自己的限速（B/s），0 表示不限速
+    pub own_limit: u64,
+}
+
+impl Demand {
+    /// 任务这一轮最多能用掉的带宽，`None` 表示不受限制
+    fn cap(&self) -> Option<u64> {
+        // 刚开始下载或还没有上报过速度时按需要更多处理
+        let saturated = self.share == 0 || self.speed == 0 || self.speed as f64 >= self.share as f64 * SATURATED_RATIO;
+        let estimate = (!saturated).then(|| ((self.speed as f64 * HEADROOM) as u64).max(MIN_SHARE));
+        match (estimate, self.own_limit) {
+            (estimate, 0) => estimate,
+            (Some(estimate), own) => Some(estimate.min(own)),
+            (None, own) => Some(own),
+        }
+    }
+}
+
+/// 把总限速 `total`（B/s）按权重分给各个任务，返回每个任务的份额
+///
+/// 先按权重计算公平份额，需要的比公平份额少的任务只分到它需要的部分，
+/// 剩下的在其余任务之间重新按权重分配，直到没有任务需要的比份额少（加权最大最小公平）。
+pub fn allocate(total: u64, demands: &[Demand]) -> Vec<u64> {
+    let mut shares = vec![0u64; demands.len()];
+    let mut open: Vec<usize> = (0..demands.len()).filter(|&i| demands[i].weight > 0).collect();
+    let mut remaining = total;
+    while !open.is_empty() {
+        let weights: u64 = open.iter().map(|&i| demands[i].weight).sum();
+        let fair = |i: usize| (remaining as u128 * demands[i].weight as u128 / weights as u128) as u64;
+        let (capped, uncapped): (Vec<usize>, Vec<usize>) =
+            open.iter().partition(|&&i| demands[i].cap().is_some_and(|cap| cap <= fair(i)));
+        if capped.is_empty() {
+            for &i in &uncapped {
+                shares[i] = fair(i);
+            }
+            remaining = 0;
+            break;
+        }
+        for &i in &capped {
+            shares[i] = demands[i].cap().unwrap_or_default();
+            remaining -= shares[i];
+        }
+        open = uncapped;
+    }
+    // 所有任务都用不完时，剩余的部分按权重分给所有任务（仍不超过任务自己的限速）
+    let weights: u64 = demands.iter().map(|d| d.weight).sum();
+    if remaining > 0 && weights > 0 {
+        for (share, demand) in shares.iter_mut().zip(demands) {
+            *share += (remaining as u128 * demand.weight as u128 / weights as u128) as u64;
+            if demand.own_limit > 0 {
+                *share = (*share).min(demand.own_limit);
+            }
+        }
+    }
+    shares.iter().map(|&share| share.max(MIN_SHARE.min(total))).collect()
+}
+
+/// 一组共享限速的任务（同一客户端的任务）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupLimit {
+    /// 这组任务合计的限速（B/s）
+    pub limit: u64,
+    /// 这组任务在 `demands` 中的下标
+    pub members: Vec<usize>,
+}
+
+/// 先把每组的限速分给组内的任务，再把总限速 `total` 分给所有任务，组内分到的份额是任务的上限
+///
+/// `total` 为 0 时只按组分配，不属于任何组的任务份额为 0（使用自己的限速）。
+pub fn allocate_grouped(total: u64, demands: &[Demand], groups: &[GroupLimit]) -> Vec<u64> {
+    let mut demands = demands.to_vec();
+    let mut group_shares = vec![0u64; demands.len()];
+    for group in groups {
+        let members: Vec<Demand> = group.members.iter().map(|&i| demands[i]).collect();
+        for (&i, share) in group.members.iter().zip(allocate(group.limit, &members)) {
+            group_shares[i] = share;
+            demands[i].own_limit = match demands[i].own_limit {
+                0 => share,
+                own => own.min(share),
+            };
+        }
+    }
+    if total == 0 {
+        return group_shares;
+    }
+    allocate(total, &demands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn demand(weight: u64, share: u64, speed: u64) -> Demand {
+        Demand { weight, share, speed, own_limit: 0 }
+    }
+
+    #[test]
+    fn test_weighted_allocation() {
+        let total = 1000 * 1024;
+        // 都想要更多带宽时按权重分
+        let shares = allocate(1024 * 1024, &[demand(2, 0, 0), demand(2, 0, 0), demand(4, 0, 0)]);
+        assert_eq!(shares, vec![256 * 1024, 256 * 1024, 512 * 1024]);
+
+        // 慢任务只用 100K，省下的给其他任务（留 25% 余量）
+        let shares = allocate(total, &[demand(2, 500 * 1024, 100 * 1024), demand(2, 500 * 1024, 500 * 1024)]);
+        assert_eq!(shares, vec![125 * 1024, 875 * 1024]);
+
+        // 任务自己的限速是上限
+        let limited = Demand { own_limit: 50 * 1024, ..demand(2, 0, 0) };
+        let shares = allocate(total, &[limited, demand(2, 0, 0)]);
+        assert_eq!(shares, vec![50 * 1024, 950 * 1024]);
+
+        // 都用不完时剩余的按权重分给所有任务，总和不超过总限速
+        let shares = allocate(total, &[demand(1, 400 * 1024, 10 * 1024), demand(1, 400 * 1024, 10 * 1024)]);
+        assert_eq!(shares[0], shares[1]);
+        assert!(shares.iter().sum::<u64>() <= total && shares[0] > 400 * 1024);
+
+        // 每个任务至少分到 MIN_SHARE
+        let shares = allocate(total, &[demand(1, 0, 0), demand(1000, 0, 0)]);
+        assert_eq!(shares[0], MIN_SHARE);
+    }
+
+    #[test]
+    fn test_grouped_allocation() {
+        let demands = [demand(2, 0, 0), demand(2, 0, 0), demand(2, 0, 0)];
+        let group = GroupLimit { limit: 100 * 1024, members: vec![0, 1] };
+
+        // 只有客户端限速：组内平分，组外不限速
+        assert_eq!(allocate_grouped(0, &demands, std::slice::from_ref(&group)), vec![50 * 1024, 50 * 1024, 0]);
+
+        // 同时有总限速：客户端的任务不超过组内份额，省下的给其他任务
+        let shares = allocate_grouped(1000 * 1024, &demands, &[group]);
+        assert_eq!(shares, vec![50 * 1024, 50 * 1024, 900 * 1024]);
+    }
+
+    #[test]
+    fn test_task_weight() {
+        assert_eq!(weight(&TaskOptions::default()), 2);
+        assert_eq!(weight(&TaskOptions { priority: TaskPriority::High, ..Default::default() }), 4);
+        assert_eq!(weight(&TaskOptions { weight: Some(10), priority: TaskPriority::Low, ..Default::default() }), 10);
+    }
+}
@@ -0,0 +1,170 @@
+//! 网络基准测试：`multidown bench <url>`
+//!
+//! 对每一组（连接数，分块大小）在限定时间内从文件开头依次请求字节范围，数据直接丢弃不写盘，
+//! 按平均速度给出推荐组合。推荐值可以写成该主机的 URL 规则，之后下载同一主机时自动使用。
+
+use futures::StreamExt;
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+use crate::config::UrlRule;
+use crate::core::error::DownloadError;
+use crate::core::task::transport::{HttpRequest, HttpTransport, RequestSettings};
+
+/// 一个连接连续失败这么多次后不再继续测试
+const MAX_CONSECUTIVE_ERRORS: usize = 3;
+
+/// 一组测试参数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BenchCase {
+    pub connections: usize,
+    pub chunk_size: u64,
+}
+
+/// 一组测试的结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchResult {
+    pub case: BenchCase,
+    pub bytes: u64,
+    pub elapsed: Duration,
+    pub errors: usize,
+}
+
+impl BenchResult {
+    /// 平均速度（B/s）
+    pub fn speed(&self) -> u64 {
+        if self.elapsed.is_zero() {
+            return 0;
+        }
+        (self.bytes as f64 / self.elapsed.as_secs_f64()) as u64
+    }
+}
+
+/// 连接数和分块大小的全部组合，0 被忽略
+pub fn cases(connections: &[usize], chunk_sizes: &[u64]) -> Vec<BenchCase> {
+    connections
+        .iter()
+        .filter(|&&c| c > 0)
+        .flat_map(|&connections| {
+            chunk_sizes
+                .iter()
+                .filter(|&&s| s > 0)
+                .map(move |&chunk_size| BenchCase { connections, chunk_size })
+        })
+        .collect()
+}
+
+/// 速度最快的一组，速度相同时选连接数少的
+pub fn best(results: &[BenchResult]) -> Option<&BenchResult> {
+    results
+        .iter()
+        .filter(|r| r.bytes > 0)
+        .max_by(|a, b| a.speed().cmp(&b.speed()).then(b.case.connections.cmp(&a.case.connections)))
+}
+
+/// 把推荐值写成只匹配该主机的 URL 规则
+pub fn recommended_rule(url: &str, result: &BenchResult) -> Result<UrlRule, DownloadError> {
+    let parsed = crate::utils::validator::parse_url(url)?;
+    let host = parsed
+        .host_str()
+        .ok_or_else(|| DownloadError::InvalidUrl(format!("URL 中没有主机名: {}", url).into()))?;
+    let authority = match parsed.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    };
+    Ok(UrlRule {
+        pattern: format!("^https?://{}/", regex::escape(&authority)),
+        thread_count: Some(result.case.connections),
+        chunk_size: Some(result.case.chunk_size as usize),
+        ..Default::default()
+    })
+}
+
+/// 按给定参数测试 `duration`，文件大小为 `size`，服务器需要支持 Range 请求
+pub async fn run_case(
+    transport: &dyn HttpTransport,
+    url: &str,
+    settings: &RequestSettings,
+    size: u64,
+    case: BenchCase,
+    duration: Duration,
+) -> BenchResult {
+    let started = Instant::now();
+    let next = Cell::new(0u64);
+    let bytes = Cell::new(0u64);
+    let errors = Cell::new(0usize);
+
+    let worker = || async {
+        let mut consecutive_errors = 0;
+        while started.elapsed() < duration && consecutive_errors < MAX_CONSECUTIVE_ERRORS {
+            // 读到文件末尾后从头开始
+            let start = next.get();
+            let end = (start + case.chunk_size).min(size) - 1;
+            next.set(if end + 1 >= size { 0 } else { end + 1 });
+
+            match fetch_range(transport, url, settings, start, end, started + duration, &bytes).await {
+                Ok(()) => consecutive_errors = 0,
+                Err(e) => {
+                    tracing::warn!(start, end, error = %e, "基准测试请求失败");
+                    errors.set(errors.get() + 1);
+                    consecutive_errors += 1;
+                }
+            }
+        }
+    };
+    futures::future::join_all((0..case.connections).map(|_| worker())).await;
+
+    BenchResult { case, bytes: bytes.get(), elapsed: started.elapsed(), errors: errors.get() }
+}
+
+/// 请求一个字节范围并丢弃数据，到达截止时间时提前结束
+async fn fetch_range(
+    transport: &dyn HttpTransport,
+    url: &str,
+    settings: &RequestSettings,
+    start: u64,
+    end: u64,
+    deadline: Instant,
+    bytes: &Cell<u64>,
+) -> Result<(), DownloadError> {
+    let mut response = transport.send(HttpRequest::get(url, settings).range(start, end)).await?;
+    if response.status != 206 {
+        return Err(DownloadError::ServerError(format!("服务器不支持 Range 请求: {}", response.status).into()));
+    }
+    while let Some(chunk) = response.body.next().await {
+        bytes.set(bytes.get() + chunk?.len() as u64);
+        if Instant::now() >= deadline {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(connections: usize, chunk_size: u64, bytes: u64) -> BenchResult {
+        BenchResult {
+            case: BenchCase { connections, chunk_size },
+            bytes,
+            elapsed: Duration::from_secs(1),
+            errors: 0,
+        }
+    }
+
+    #[test]
+    fn test_best_and_rule() {
+        assert_eq!(cases(&[1, 0, 4], &[1024, 4096]).len(), 4);
+
+        let results = vec![result(1, 1024, 100), result(4, 1024, 400), result(2, 4096, 400), result(8, 4096, 0)];
+        let best = best(&results).unwrap();
+        assert_eq!(best.case, BenchCase { connections: 2, chunk_size: 4096 });
+
+        let rule = recommended_rule("https://cdn.example.com:8443/a.iso", best).unwrap();
+        assert_eq!(rule.pattern, r"^https?://cdn\.example\.com:8443/");
+        assert!(rule.regex().unwrap().is_match("https://cdn.example.com:8443/b.iso"));
+        assert!(!rule.regex().unwrap().is_match("https://cdn.example.com.evil/b.iso"));
+        assert_eq!((rule.thread_count, rule.chunk_size), (Some(2), Some(4096)));
+    }
+}
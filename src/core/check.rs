@@ -0,0 +1,157 @@
+//! 只检查不下载：`multidown --check-only -f urls.txt`
+//!
+//! 对每个 URL 发送 HEAD 请求并逐跳跟随重定向，记录状态码、文件大小、是否支持 Range 请求
+//! 和最终地址，用于在开始长时间的批量下载之前检查镜像列表。
+
+use futures::StreamExt;
+use serde::Serialize;
+
+use crate::core::error::DownloadError;
+use crate::core::task::http::{self, CROSS_ORIGIN_STRIP, MAX_REDIRECTS};
+use crate::core::task::transport::RequestSettings;
+
+/// 单个 URL 的检查结果
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CheckResult {
+    pub url: String,
+    /// 最后一跳的状态码，请求失败时为空
+    pub status: Option<u16>,
+    /// `Content-Length`，服务器没有返回时为空
+    pub size: Option<u64>,
+    pub supports_range: bool,
+    /// 跟随重定向后的最终地址
+    pub final_url: String,
+    pub redirects: usize,
+    pub error: Option<String>,
+}
+
+impl CheckResult {
+    fn failed(url: &str, error: impl ToString) -> Self {
+        Self {
+            url: url.to_string(),
+            final_url: url.to_string(),
+            error: Some(error.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// 请求成功且最终返回 2xx
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none() && self.status.is_some_and(|s| (200..300).contains(&s))
+    }
+}
+
+/// 按输入顺序检查所有 URL，同时最多 `concurrency` 个请求；`settings` 给出每个 URL 的请求设置
+pub async fn check_all(
+    client: &awc::Client,
+    urls: &[String],
+    concurrency: usize,
+    settings: impl Fn(&str) -> Result<RequestSettings, DownloadError>,
+) -> Vec<CheckResult> {
+    futures::stream::iter(urls)
+        .map(|url| {
+            let settings = settings(url);
+            async move {
+                match settings {
+                    Ok(settings) => probe(client, url, &settings).await,
+                    Err(e) => CheckResult::failed(url, e),
+                }
+            }
+        })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await
+}
+
+/// 检查单个 URL，`client` 需要关闭自动重定向（见 [`http::manual_redirect_client`]）
+pub async fn probe(client: &awc::Client, url: &str, settings: &RequestSettings) -> CheckResult {
+    if let Err(e) = crate::utils::validator::parse_url(url) {
+        return CheckResult::failed(url, e);
+    }
+    let mut headers = settings.headers();
+    let mut current = url.to_string();
+    let mut redirects = 0;
+    loop {
+        let mut request = client.head(current.as_str()).timeout(settings.timeout);
+        for (name, value) in &headers {
+            request = request.insert_header((name.as_str(), value.as_str()));
+        }
+        let response = match request.send().await {
+            Ok(response) => response,
+            Err(awc::error::SendRequestError::Timeout) => return CheckResult::failed(url, DownloadError::Timeout),
+            Err(e) => return CheckResult::failed(url, e),
+        };
+        let header = |name: &str| response.headers().get(name).and_then(|v| v.to_str().ok());
+
+        let next = match header("location") {
+            Some(location) if response.status().is_redirection() => {
+                url::Url::parse(&current).and_then(|base| base.join(location)).ok()
+            }
+            _ => None,
+        };
+        let Some(next) = next else {
+            return CheckResult {
+                url: url.to_string(),
+                status: Some(response.status().as_u16()),
+                size: header("content-length").and_then(|s| s.parse().ok()),
+                supports_range: header("accept-ranges") == Some("bytes"),
+                final_url: current,
+                redirects,
+                error: None,
+            };
+        };
+        if redirects == MAX_REDIRECTS {
+            return CheckResult::failed(url, format!("重定向超过 {} 次", MAX_REDIRECTS));
+        }
+        if !http::same_origin(&current, &next) {
+            headers.retain(|(name, _)| !CROSS_ORIGIN_STRIP.contains(&name.to_ascii_lowercase().as_str()));
+        }
+        current = next.to_string();
+        redirects += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::core::task::dns::Resolver;
+    use crate::core::task::http::SocketOptions;
+    use crate::core::task::TaskOptions;
+    use std::io::{Read, Write};
+
+    /// 按顺序对每个连接返回一个响应
+    fn serve(responses: Vec<&'static str>) -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf);
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        base
+    }
+
+    #[actix_rt::test]
+    async fn test_probe_follows_redirects() {
+        let base = serve(vec![
+            "HTTP/1.1 302 Found\r\nLocation: /mirror/a.iso\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Length: 1024\r\nAccept-Ranges: bytes\r\nConnection: close\r\n\r\n",
+        ]);
+        let config = Config::default();
+        let client = http::manual_redirect_client(&SocketOptions::new(&config), &Resolver::new(&config));
+        let settings = RequestSettings::new(&config, &TaskOptions::default());
+        let urls = vec![format!("{}/a.iso", base), "not a url".to_string()];
+        let results = check_all(&client, &urls, 2, |_| Ok(settings.clone())).await;
+
+        assert_eq!(results[0].status, Some(200));
+        assert_eq!(results[0].size, Some(1024));
+        assert!(results[0].supports_range && results[0].is_ok());
+        assert_eq!(results[0].final_url, format!("{}/mirror/a.iso", base));
+        assert_eq!(results[0].redirects, 1);
+        assert!(!results[1].is_ok() && results[1].error.is_some());
+    }
+}
@@ -0,0 +1,114 @@
+//! 嵌入式管理器：在普通线程中使用下载引擎
+//!
+//! 管理器需要 actix 系统，而 C/C++、Python 等宿主程序的线程上没有。[`EmbeddedManager`] 在自己的线程中运行
+//! actix 系统和 [`DownloadManagerActor`]，对外的方法都是阻塞的，可以在任意线程调用：
+//!
+//! ```ignore
+//! let manager = EmbeddedManager::start(Config::load("multidown.conf")?)?;
+//! let events = manager.events().subscribe();
+//! let task_id = manager.add_url("https://example.com/a.iso", None)?;
+//! while let Ok(event) = events.blocking_recv() { ... }
+//! ```
+//!
+//! `multidown-ffi`（C 接口）和 `multidown-py`（Python 模块）都基于它。
+
+use std::path::Path;
+use std::thread::JoinHandle;
+
+use actix::prelude::*;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::core::actor_manager::{
+    CancelTask, CreateTask, DownloadManagerActor, DownloadTaskMeta, PauseAllAndSave, QueryTaskDetail, StartTaskFromMeta,
+    WaitForBackgroundJobs,
+};
+use crate::core::error::DownloadError;
+use crate::core::events::EventBus;
+use crate::core::task::DownloadRequest;
+use crate::utils::validator;
+
+/// 在独立线程中运行的下载管理器，释放时暂停正在下载的任务并保存会话
+pub struct EmbeddedManager {
+    addr: Addr<DownloadManagerActor>,
+    system: actix_rt::System,
+    thread: Option<JoinHandle<()>>,
+    events: EventBus,
+    download_dir: String,
+}
+
+impl EmbeddedManager {
+    /// 在新线程中启动 actix 系统和管理器，会话中未完成的任务与命令行一样被加载
+    pub fn start(config: Config) -> Result<Self, DownloadError> {
+        config.validate()?;
+        let download_dir = config.download_dir.clone();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let thread = std::thread::Builder::new()
+            .name("multidown".to_string())
+            .spawn(move || {
+                let system = actix_rt::System::new();
+                let (addr, events) = system.block_on(async {
+                    let manager = DownloadManagerActor::new(config);
+                    let events = manager.events();
+                    (manager.start(), events)
+                });
+                let _ = tx.send((addr, events, actix_rt::System::current()));
+                let _ = system.run();
+            })
+            .map_err(|e| DownloadError::io_error_with_context("无法创建下载线程", e))?;
+        let (addr, events, system) = rx.recv().map_err(|_| DownloadError::unknown("下载管理器启动失败"))?;
+        Ok(Self { addr, system, thread: Some(thread), events, download_dir })
+    }
+
+    /// 任务事件总线，需要在添加任务之前订阅才能收到它的全部事件
+    pub fn events(&self) -> EventBus {
+        self.events.clone()
+    }
+
+    /// 向管理器发送消息并等待结果
+    pub fn send<M>(&self, msg: M) -> Result<M::Result, DownloadError>
+    where
+        M: Message + Send + 'static,
+        M::Result: Send,
+        DownloadManagerActor: Handler<M>,
+    {
+        futures::executor::block_on(self.addr.send(msg)).map_err(|e| DownloadError::unknown(format!("下载管理器已停止: {}", e)))
+    }
+
+    /// 添加任务并立即开始（超过并发数时排队）
+    ///
+    /// `file_name` 是下载目录下的相对路径，为 None 时取 URL 的最后一段。
+    pub fn add_url(&self, url: &str, file_name: Option<&str>) -> Result<Uuid, DownloadError> {
+        let url = validator::parse_url(url)?;
+        let file_name = file_name.map_or_else(|| validator::file_name_from_url(&url), str::to_string);
+        let request = DownloadRequest::builder()
+            .url(url.to_string())
+            .output(Path::new(&self.download_dir).join(file_name).to_string_lossy())
+            .build()?;
+        let task_id = self.send(CreateTask(request))??;
+        self.addr.do_send(StartTaskFromMeta { task_id });
+        Ok(task_id)
+    }
+
+    /// 任务的元数据（状态、进度、保存路径等）
+    pub fn task(&self, task_id: Uuid) -> Result<DownloadTaskMeta, DownloadError> {
+        self.send(QueryTaskDetail(task_id))?.ok_or_else(|| DownloadError::unknown(format!("任务不存在: {}", task_id)))
+    }
+
+    /// 取消任务并删除已下载的临时数据，任务已结束时不做任何事
+    pub fn cancel(&self, task_id: Uuid) -> Result<(), DownloadError> {
+        self.task(task_id)?;
+        self.send(CancelTask(task_id))
+    }
+}
+
+impl Drop for EmbeddedManager {
+    fn drop(&mut self) {
+        let _ = self.send(PauseAllAndSave);
+        let _ = self.send(WaitForBackgroundJobs);
+        self.system.stop();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -2,7 +2,10 @@ use thiserror::Error;
 use actix::prelude::*;
 use anyhow;
 use std::borrow::Cow;
+use std::io::ErrorKind;
+use std::sync::Arc;
 use crate::utils::logger::LoggerExt;
+use crate::i18n::{t, Msg};
 
 /// 下载相关错误类型
 #[derive(Error, Debug, Clone)]
@@ -12,6 +15,12 @@ pub enum DownloadError {
     NetworkError(Cow<'static, str>),
     #[error("IO错误: {0}")]
     IoError(Cow<'static, str>),
+    /// 文件读写失败，保留原始的 `std::io::Error`，按其 `ErrorKind` 判断能否重试
+    #[error("IO错误: {context}: {source}")]
+    Io { context: Cow<'static, str>, #[source] source: Arc<std::io::Error> },
+    /// 连接层面的 IO 错误（连接被拒绝、被重置、读取超时等），同样保留原始错误
+    #[error("网络错误: {context}: {source}")]
+    Connection { context: Cow<'static, str>, #[source] source: Arc<std::io::Error> },
     // ===== 协议与参数 =====
     #[error("无效的URL: {0}")]
     InvalidUrl(Cow<'static, str>),
@@ -40,11 +49,20 @@ pub enum DownloadError {
     #[error("文件大小不匹配: 预期 {expected} 字节, 实际 {actual} 字节")]
     #[allow(dead_code)]
     SizeMismatch { expected: u64, actual: u64 },
+    #[error("任务超过总时间限制: {0} 秒")]
+    DeadlineExceeded(u64),
+    #[error("文件大小超过限制: {size} 字节, 上限 {limit} 字节")]
+    SizeLimitExceeded { size: u64, limit: u64 },
+    #[error("超出本次运行的下载配额: 需要 {required} 字节, 剩余 {remaining} 字节")]
+    QuotaExceeded { required: u64, remaining: u64 },
     #[error("校验和不匹配: 预期 {expected}, 实际 {actual}")]
     #[allow(dead_code)]
     ChecksumMismatch { expected: String, actual: String },
     #[error("服务器错误: {0}")]
     ServerError(Cow<'static, str>),
+    /// 服务器返回了错误的状态码；`chunk` 为分块下载时出错的块
+    #[error("服务器错误: HTTP {status}{}{}", host.as_ref().map(|h| format!(" ({})", h)).unwrap_or_default(), chunk.map(|c| format!(", 分块 {}", c)).unwrap_or_default())]
+    HttpStatus { status: u16, host: Option<String>, chunk: Option<usize> },
     // ===== actix相关 =====
     #[error("Actix邮箱错误: {0}")]
     MailboxError(Cow<'static, str>),
@@ -130,9 +148,22 @@ impl DownloadError {
 
     // ===== 新增优化方法 =====
 
-    /// 创建带上下文的IO错误
-    pub fn io_error_with_context(context: &str, error: impl std::error::Error) -> Self {
-        DownloadError::IoError(format!("{}: {}", context, error).into())
+    /// 创建带上下文的IO错误，保留原始错误
+    pub fn io_error_with_context(context: impl Into<Cow<'static, str>>, error: std::io::Error) -> Self {
+        DownloadError::Io { context: context.into(), source: Arc::new(error) }
+    }
+
+    /// 创建带上下文的连接错误，保留原始错误
+    pub fn connection_error(context: impl Into<Cow<'static, str>>, error: std::io::Error) -> Self {
+        DownloadError::Connection { context: context.into(), source: Arc::new(error) }
+    }
+
+    /// 底层的 IO 错误类型，只对保留了原始错误的 `Io` 和 `Connection` 有值
+    pub fn io_kind(&self) -> Option<ErrorKind> {
+        match self {
+            DownloadError::Io { source, .. } | DownloadError::Connection { source, .. } => Some(source.kind()),
+            _ => None,
+        }
     }
     
     /// 创建带上下文的网络错误
@@ -145,22 +176,92 @@ impl DownloadError {
         DownloadError::ServerError(format!("{}: HTTP {}", context, status).into())
     }
 
+    /// 服务器对 `url` 的请求返回了错误的状态码
+    pub fn http_status(status: u16, url: &str) -> Self {
+        let host = crate::core::task::tuning::host_key(url);
+        DownloadError::HttpStatus { status, host: (!host.is_empty()).then_some(host), chunk: None }
+    }
+
+    /// 记录出错的分块，只对 `HttpStatus` 生效
+    pub fn with_chunk(self, index: usize) -> Self {
+        match self {
+            DownloadError::HttpStatus { status, host, .. } => DownloadError::HttpStatus { status, host, chunk: Some(index) },
+            other => other,
+        }
+    }
+
+    /// 错误类别的稳定英文标识，用于持久化、报告和退出码判断
+    pub fn kind(&self) -> &'static str {
+        match self {
+            DownloadError::NetworkError(_) => "network",
+            DownloadError::IoError(_) => "io",
+            DownloadError::Io { source, .. } => match source.kind() {
+                ErrorKind::PermissionDenied | ErrorKind::ReadOnlyFilesystem => "permission",
+                ErrorKind::StorageFull | ErrorKind::QuotaExceeded => "insufficient_space",
+                _ => "io",
+            },
+            DownloadError::Connection { source, .. } if source.kind() == ErrorKind::TimedOut => "timeout",
+            DownloadError::Connection { .. } => "network",
+            DownloadError::InvalidUrl(_) => "invalid_url",
+            DownloadError::UnsupportedProtocol(_) => "unsupported_protocol",
+            DownloadError::FileExists(_) => "file_exists",
+            DownloadError::InsufficientSpace { .. } => "insufficient_space",
+            DownloadError::PermissionError(_) => "permission",
+            DownloadError::Timeout => "timeout",
+            DownloadError::Cancelled => "cancelled",
+            DownloadError::Paused => "paused",
+            DownloadError::MaxRetriesExceeded(_) => "max_retries_exceeded",
+            DownloadError::SizeMismatch { .. } => "size_mismatch",
+            DownloadError::DeadlineExceeded(_) => "deadline_exceeded",
+            DownloadError::SizeLimitExceeded { .. } => "size_limit",
+            DownloadError::QuotaExceeded { .. } => "quota_exceeded",
+            DownloadError::ChecksumMismatch { .. } => "checksum_mismatch",
+            DownloadError::ServerError(_) | DownloadError::HttpStatus { .. } => "server",
+            DownloadError::MailboxError(_) => "mailbox",
+            DownloadError::SendError(_) => "send",
+            DownloadError::Unknown(_) => "unknown",
+            DownloadError::ResumeFailed(_) => "resume_failed",
+        }
+    }
+
+    /// 判断错误是否属于网络层面（连接失败、超时）
+    pub fn is_network(&self) -> bool {
+        matches!(self, DownloadError::NetworkError(_) | DownloadError::Connection { .. } | DownloadError::Timeout)
+    }
+
     /// 判断错误是否可重试
     pub fn is_retryable(&self) -> bool {
+        if self.is_fatal() {
+            return false;
+        }
         matches!(self, 
             DownloadError::NetworkError(_) |
+            DownloadError::Connection { .. } |
+            DownloadError::Io { .. } |
             DownloadError::Timeout |
             DownloadError::ServerError(_) |
+            DownloadError::HttpStatus { .. } |
             DownloadError::IoError(_) // 某些IO错误可能可重试
         )
     }
     
     /// 判断错误是否为致命错误（不可重试）
+    ///
+    /// 保留了原始 IO 错误时，没有权限、磁盘已满这类重试也不会好转的错误同样是致命错误。
     pub fn is_fatal(&self) -> bool {
+        if let DownloadError::Io { source, .. } = self {
+            return matches!(
+                source.kind(),
+                ErrorKind::PermissionDenied | ErrorKind::ReadOnlyFilesystem | ErrorKind::StorageFull | ErrorKind::QuotaExceeded
+            );
+        }
         matches!(self,
             DownloadError::InvalidUrl(_) |
             DownloadError::FileExists(_) |
             DownloadError::SizeMismatch { .. } |
+            DownloadError::SizeLimitExceeded { .. } |
+            DownloadError::QuotaExceeded { .. } |
+            DownloadError::DeadlineExceeded(_) |
             DownloadError::ChecksumMismatch { .. } |
             DownloadError::ResumeFailed(_) |
             DownloadError::PermissionError(_) |
@@ -170,13 +271,84 @@ impl DownloadError {
 
     /// 判断错误是否为临时错误（可重试）
     pub fn is_temporary(&self) -> bool {
+        if let DownloadError::Io { source, .. } = self {
+            return matches!(source.kind(), ErrorKind::Interrupted | ErrorKind::WouldBlock | ErrorKind::TimedOut);
+        }
         matches!(self,
             DownloadError::Timeout |
             DownloadError::NetworkError(_) |
-            DownloadError::ServerError(_)
+            DownloadError::Connection { .. } |
+            DownloadError::ServerError(_) |
+            DownloadError::HttpStatus { .. }
         )
     }
 
+    /// 服务器错误中的 HTTP 状态码（`ServerError` 取消息末尾的三位数字）
+    pub fn status_code(&self) -> Option<u16> {
+        match self {
+            DownloadError::HttpStatus { status, .. } => Some(*status),
+            DownloadError::ServerError(msg) => {
+                msg.rsplit([' ', ':']).next()?.parse().ok().filter(|code| (100..600).contains(code))
+            }
+            _ => None,
+        }
+    }
+
+    /// 稳定的数字错误码，见 [`DownloadError::code_for_kind`]
+    pub fn code(&self) -> u16 {
+        Self::code_for_kind(self.kind())
+    }
+
+    /// 根据错误类别（见 `kind`）获取稳定的数字错误码，写入 JSON 输出和报告，供脚本判断
+    ///
+    /// 1xx 网络和协议，2xx 本地文件和资源，3xx 下载流程，9xx 内部错误；已发布的错误码不再改变。
+    pub fn code_for_kind(kind: &str) -> u16 {
+        match kind {
+            "network" => 101,
+            "timeout" => 102,
+            "server" => 103,
+            "invalid_url" => 111,
+            "unsupported_protocol" => 112,
+            "io" => 201,
+            "file_exists" => 202,
+            "insufficient_space" => 203,
+            "permission" => 204,
+            "cancelled" => 301,
+            "paused" => 302,
+            "max_retries_exceeded" => 303,
+            "size_mismatch" => 304,
+            "deadline_exceeded" => 305,
+            "size_limit" => 306,
+            "quota_exceeded" => 307,
+            "checksum_mismatch" => 308,
+            "resume_failed" => 309,
+            "mailbox" => 901,
+            "send" => 902,
+            _ => 999,
+        }
+    }
+
+    /// 重试策略使用的错误分类，致命错误和其他无法归类的错误返回 None（不重试）
+    pub fn retry_class(&self) -> Option<ErrorClass> {
+        if self.severity() == ErrorSeverity::Fatal {
+            return None;
+        }
+        match self {
+            DownloadError::NetworkError(_) => Some(ErrorClass::Network),
+            DownloadError::Timeout => Some(ErrorClass::Timeout),
+            DownloadError::Connection { source, .. } if source.kind() == ErrorKind::TimedOut => Some(ErrorClass::Timeout),
+            DownloadError::Connection { .. } => Some(ErrorClass::Network),
+            DownloadError::IoError(_) | DownloadError::Io { .. } => Some(ErrorClass::Io),
+            DownloadError::ServerError(_) | DownloadError::HttpStatus { .. } => match self.status_code() {
+                // 请求超时和限流是暂时的，按服务器错误处理
+                Some(408) | Some(429) => Some(ErrorClass::Server),
+                Some(code) if (400..500).contains(&code) => Some(ErrorClass::Client),
+                _ => Some(ErrorClass::Server),
+            },
+            _ => None,
+        }
+    }
+
     /// 获取错误严重程度
     pub fn severity(&self) -> ErrorSeverity {
         if self.is_fatal() {
@@ -204,17 +376,92 @@ impl DownloadError {
 
     /// 获取错误建议的解决方案
     pub fn get_suggestion(&self) -> Option<&'static str> {
+        Self::suggestion_for_kind(self.kind(), self.status_code())
+    }
+
+    /// 根据错误类别（见 `kind`）和 HTTP 状态码获取建议的解决方案；
+    /// 与 `retry_class` 一致，408、429 以外的 4xx 是请求本身的问题，重试没有用
+    pub fn suggestion_for_kind(kind: &str, status: Option<u16>) -> Option<&'static str> {
+        let msg = match kind {
+            "network" => Msg::SuggestNetwork,
+            "timeout" => Msg::SuggestTimeout,
+            "server" => match status {
+                Some(408) | Some(429) => Msg::SuggestServer,
+                Some(code) if (400..500).contains(&code) => Msg::SuggestClientStatus,
+                _ => Msg::SuggestServer,
+            },
+            "invalid_url" => Msg::SuggestInvalidUrl,
+            "file_exists" => Msg::SuggestFileExists,
+            "permission" => Msg::SuggestPermission,
+            "insufficient_space" => Msg::SuggestInsufficientSpace,
+            "size_mismatch" => Msg::SuggestSizeMismatch,
+            "size_limit" => Msg::SuggestSizeLimit,
+            "deadline_exceeded" => Msg::SuggestDeadlineExceeded,
+            "quota_exceeded" => Msg::SuggestQuotaExceeded,
+            "resume_failed" => Msg::SuggestResumeFailed,
+            _ => return None,
+        };
+        Some(t(msg))
+    }
+
+    /// 错误类别的显示名称（随界面语言变化）
+    pub fn kind_label(kind: &str) -> &'static str {
+        t(match kind {
+            "network" => Msg::KindNetwork,
+            "io" => Msg::KindIo,
+            "invalid_url" => Msg::KindInvalidUrl,
+            "unsupported_protocol" => Msg::KindUnsupportedProtocol,
+            "file_exists" => Msg::KindFileExists,
+            "insufficient_space" => Msg::KindInsufficientSpace,
+            "permission" => Msg::KindPermission,
+            "timeout" => Msg::KindTimeout,
+            "cancelled" => Msg::KindCancelled,
+            "paused" => Msg::KindPaused,
+            "max_retries_exceeded" => Msg::KindMaxRetriesExceeded,
+            "size_mismatch" => Msg::KindSizeMismatch,
+            "size_limit" => Msg::KindSizeLimit,
+            "deadline_exceeded" => Msg::KindDeadlineExceeded,
+            "quota_exceeded" => Msg::KindQuotaExceeded,
+            "checksum_mismatch" => Msg::KindChecksumMismatch,
+            "server" => Msg::KindServer,
+            "resume_failed" => Msg::KindResumeFailed,
+            _ => Msg::KindUnknown,
+        })
+    }
+}
+
+/// 重试策略的错误分类，见配置项 `retry_policies`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorClass {
+    Network,
+    Timeout,
+    Server,
+    Client,
+    Io,
+}
+
+impl ErrorClass {
+    pub const ALL: [ErrorClass; 5] = [
+        ErrorClass::Network,
+        ErrorClass::Timeout,
+        ErrorClass::Server,
+        ErrorClass::Client,
+        ErrorClass::Io,
+    ];
+
+    /// 按配置文件中的名称查找
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|class| class.as_str() == name)
+    }
+
+    /// 配置文件中的名称
+    pub fn as_str(self) -> &'static str {
         match self {
-            DownloadError::NetworkError(_) => Some("检查网络连接，稍后重试"),
-            DownloadError::Timeout => Some("网络超时，请检查网络连接或增加超时时间"),
-            DownloadError::ServerError(_) => Some("服务器暂时不可用，请稍后重试"),
-            DownloadError::InvalidUrl(_) => Some("请检查URL格式是否正确"),
-            DownloadError::FileExists(_) => Some("文件已存在，请删除或重命名"),
-            DownloadError::PermissionError(_) => Some("权限不足，请检查文件权限或使用管理员权限"),
-            DownloadError::InsufficientSpace { .. } => Some("磁盘空间不足，请清理磁盘空间"),
-            DownloadError::SizeMismatch { .. } => Some("文件大小不匹配，可能是下载不完整"),
-            DownloadError::ResumeFailed(_) => Some("断点续传失败，将重新下载"),
-            _ => None,
+            ErrorClass::Network => "network",
+            ErrorClass::Timeout => "timeout",
+            ErrorClass::Server => "server",
+            ErrorClass::Client => "client",
+            ErrorClass::Io => "io",
         }
     }
 }
@@ -255,6 +502,18 @@ mod tests {
         assert!(matches!(error, DownloadError::Unknown(Cow::Owned(_))));
     }
 
+    #[test]
+    fn test_retry_class() {
+        assert_eq!(DownloadError::Timeout.retry_class(), Some(ErrorClass::Timeout));
+        assert_eq!(DownloadError::network_error("连接被重置").retry_class(), Some(ErrorClass::Network));
+        assert_eq!(DownloadError::server_error("服务器错误: 503").retry_class(), Some(ErrorClass::Server));
+        assert_eq!(DownloadError::server_error_with_context("HEAD", 429).retry_class(), Some(ErrorClass::Server));
+        assert_eq!(DownloadError::server_error("服务器错误: 403").retry_class(), Some(ErrorClass::Client));
+        assert_eq!(DownloadError::server_error("服务器错误: 403").status_code(), Some(403));
+        assert_eq!(DownloadError::invalid_url("x").retry_class(), None);
+        assert_eq!(DownloadError::unknown("合并失败").retry_class(), None);
+    }
+
     #[test]
     fn test_error_classification() {
         // 测试可重试错误
@@ -288,13 +547,58 @@ mod tests {
         assert!(DownloadError::network_error("test").get_suggestion().is_some());
         assert!(DownloadError::invalid_url("test").get_suggestion().is_some());
         assert!(DownloadError::Timeout.get_suggestion().is_some());
+        let server = DownloadError::http_status(503, "https://a.com/x").get_suggestion();
+        let client = DownloadError::http_status(404, "https://a.com/x").get_suggestion();
+        assert_eq!(DownloadError::http_status(429, "https://a.com/x").get_suggestion(), server);
+        assert!(client.is_some() && client != server);
+    }
+
+    #[test]
+    fn test_error_kind() {
+        assert_eq!(DownloadError::network_error("test").kind(), "network");
+        assert_eq!(DownloadError::Timeout.kind(), "timeout");
+        assert_eq!(DownloadError::server_error("test").kind(), "server");
+        assert!(DownloadError::Timeout.is_network());
+        assert!(!DownloadError::server_error("test").is_network());
+    }
+
+    #[test]
+    fn test_http_status() {
+        let error = DownloadError::http_status(503, "https://cdn.example.com:8443/a.bin").with_chunk(7);
+        assert_eq!(error.to_string(), "服务器错误: HTTP 503 (cdn.example.com:8443), 分块 7");
+        assert_eq!(error.status_code(), Some(503));
+        assert_eq!(error.retry_class(), Some(ErrorClass::Server));
+        assert_eq!((error.kind(), error.code()), ("server", 103));
+        assert_eq!(DownloadError::http_status(404, "http://example.com/").retry_class(), Some(ErrorClass::Client));
+        assert_eq!(DownloadError::Timeout.code(), 102);
+        assert_eq!(DownloadError::code_for_kind("something_new"), 999);
     }
 
     #[test]
     fn test_error_with_context() {
         let io_error = std::io::Error::new(std::io::ErrorKind::NotFound, "文件不存在");
         let error = DownloadError::io_error_with_context("写入文件", io_error);
-        assert!(matches!(error, DownloadError::IoError(_)));
+        assert!(matches!(error, DownloadError::Io { .. }));
         assert!(error.to_string().contains("写入文件"));
+        assert_eq!(error.io_kind(), Some(ErrorKind::NotFound));
+        assert!(std::error::Error::source(&error).is_some());
+    }
+
+    #[test]
+    fn test_io_kind_classification() {
+        // 分类只看原始错误的类型，与错误信息的语言无关
+        let io = |kind| DownloadError::io_error_with_context("写入块文件", std::io::Error::new(kind, "fehlgeschlagen"));
+        let full = io(ErrorKind::StorageFull);
+        assert!(full.is_fatal() && !full.is_retryable());
+        assert_eq!((full.kind(), full.retry_class()), ("insufficient_space", None));
+        assert_eq!(io(ErrorKind::PermissionDenied).kind(), "permission");
+        assert_eq!(io(ErrorKind::Interrupted).severity(), ErrorSeverity::Temporary);
+        assert_eq!(io(ErrorKind::Other).retry_class(), Some(ErrorClass::Io));
+
+        let conn = |kind| DownloadError::connection_error("连接失败", std::io::Error::new(kind, "x"));
+        assert_eq!(conn(ErrorKind::ConnectionReset).retry_class(), Some(ErrorClass::Network));
+        assert_eq!(conn(ErrorKind::TimedOut).retry_class(), Some(ErrorClass::Timeout));
+        assert!(conn(ErrorKind::ConnectionRefused).is_network());
+        assert_eq!(conn(ErrorKind::ConnectionRefused).code(), 101);
     }
 } 
\ No newline at end of file
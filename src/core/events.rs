@@ -0,0 +1,151 @@
+//! 任务事件：供库的使用者（自定义界面、服务端）订阅任务的生命周期和进度
+//!
+//! ```ignore
+//! let manager = DownloadManagerActor::new(config);
+//! let events = manager.events();
+//! let addr = manager.start();
+//! let mut stream = events.task_stream(task_id);
+//! while let Some(event) = stream.next().await { ... }
+//! ```
+//!
+//! 事件通过 `tokio::sync::broadcast` 分发，没有订阅者时直接丢弃；订阅者处理太慢时会跳过
+//! 积压的旧事件（进度事件很频繁，界面只需要最新的状态）。
+
+use futures::Stream;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::core::task::{TaskPhase, TaskStatus};
+
+/// 事件通道的容量，订阅者落后超过这么多条时跳过旧事件
+const CAPACITY: usize = 1024;
+
+/// 任务事件
+#[derive(Debug, Clone, PartialEq)]
+pub enum TaskEvent {
+    /// 任务已创建（包括导入的任务）
+    Created { task_id: Uuid, url: String, file: String },
+    /// 任务拿到下载名额，开始下载
+    Started { task_id: Uuid },
+    /// 进度更新，`total` 未知时为 0
+    Progress { task_id: Uuid, downloaded: u64, total: u64, speed: u64 },
+    /// 分块重试用完后失败，之后可能整体重新调度
+    ChunkFailed { task_id: Uuid, chunk_index: usize, error: String },
+    /// 下载出错，即将重试
+    Retrying { task_id: Uuid, error_kind: Option<&'static str> },
+    /// 任务完成
+    Completed { task_id: Uuid },
+    /// 任务失败
+    Failed { task_id: Uuid, error: String },
+    /// 任务状态发生变化，每次经状态机转换都会发布
+    StatusChanged { task_id: Uuid, from: TaskStatus, to: TaskStatus },
+    /// 收尾阶段（合并、校验）的进度，`progress` 为 0-100
+    Phase { task_id: Uuid, phase: TaskPhase, progress: f32 },
+    /// 任务已从会话中删除
+    Removed { task_id: Uuid },
+    /// 任务的保存路径已修改，已下载的数据随之移动
+    Moved { task_id: Uuid, file: String },
+}
+
+impl TaskEvent {
+    pub fn task_id(&self) -> Uuid {
+        match self {
+            TaskEvent::Created { task_id, .. }
+            | TaskEvent::Started { task_id }
+            | TaskEvent::Progress { task_id, .. }
+            | TaskEvent::ChunkFailed { task_id, .. }
+            | TaskEvent::Retrying { task_id, .. }
+            | TaskEvent::Completed { task_id }
+            | TaskEvent::Failed { task_id, .. }
+            | TaskEvent::StatusChanged { task_id, .. }
+            | TaskEvent::Phase { task_id, .. }
+            | TaskEvent::Moved { task_id, .. } => *task_id,
+            TaskEvent::Removed { task_id } => *task_id,
+        }
+    }
+}
+
+/// 事件总线，可以随意克隆，所有副本共享同一个通道
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<TaskEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self { sender: broadcast::channel(CAPACITY).0 }
+    }
+}
+
+impl EventBus {
+    /// 发布事件，没有订阅者时丢弃
+    pub fn emit(&self, event: TaskEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    /// 订阅之后发布的所有事件
+    pub fn subscribe(&self) -> broadcast::Receiver<TaskEvent> {
+        self.sender.subscribe()
+    }
+
+    /// 以 `Stream` 的形式订阅所有任务的事件，通道关闭（管理器停止）时结束
+    pub fn stream(&self) -> impl Stream<Item = TaskEvent> + Unpin {
+        Box::pin(futures::stream::unfold(self.subscribe(), |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some((event, receiver)),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!(skipped, "事件订阅者处理太慢，跳过旧事件");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        }))
+    }
+
+    /// 只订阅一个任务的事件，任务完成、失败或被删除后结束
+    pub fn task_stream(&self, task_id: Uuid) -> impl Stream<Item = TaskEvent> + Unpin {
+        use futures::StreamExt;
+        let events = self.stream().filter(move |event| futures::future::ready(event.task_id() == task_id));
+        // 收到结束事件后立即结束，不再等待下一个事件
+        Box::pin(futures::stream::unfold(Some(events), |events| async move {
+            let mut events = events?;
+            let event = events.next().await?;
+            let finished = matches!(event, TaskEvent::Completed { .. } | TaskEvent::Failed { .. } | TaskEvent::Removed { .. });
+            Some((event, (!finished).then_some(events)))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    #[actix_rt::test]
+    async fn test_task_stream() {
+        let bus = EventBus::default();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let all = bus.stream();
+        let only_a = bus.task_stream(a);
+        let mut only_b = bus.task_stream(b);
+
+        bus.emit(TaskEvent::Started { task_id: a });
+        bus.emit(TaskEvent::Started { task_id: b });
+        bus.emit(TaskEvent::Progress { task_id: a, downloaded: 1, total: 2, speed: 0 });
+        bus.emit(TaskEvent::Completed { task_id: a });
+        bus.emit(TaskEvent::Started { task_id: a });
+        bus.emit(TaskEvent::Failed { task_id: b, error: "HTTP 404".to_string() });
+
+        // 结束事件之后立即结束，不需要等到总线关闭
+        assert_eq!(only_b.next().await, Some(TaskEvent::Started { task_id: b }));
+        assert!(matches!(only_b.next().await, Some(TaskEvent::Failed { .. })));
+        assert_eq!(only_b.next().await, None);
+        drop(bus);
+
+        let events: Vec<TaskEvent> = only_a.collect().await;
+        assert_eq!(events.len(), 3);
+        assert_eq!(events.last(), Some(&TaskEvent::Completed { task_id: a }));
+        assert_eq!(all.count().await, 6);
+    }
+}
@@ -0,0 +1,195 @@
+//! 下载历史记录
+//!
+//! 每个结束的任务（成功或失败）追加一行 JSON 到会话目录中的 `history.jsonl`，
+//! 供 `multidown history` 查询、`multidown verify` 校验已下载的文件，以及 `--no-redownload` 跳过已成功下载的 URL。
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::core::error::DownloadError;
+use crate::core::task::util::{throttle_blocking, throttle_step, SpeedLimiter};
+
+/// 历史记录文件名，位于会话目录中
+pub const HISTORY_FILE: &str = "history.jsonl";
+
+/// 一条历史记录
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryEntry {
+    /// 任务 ID，旧版本写入的记录没有
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<Uuid>,
+    pub url: String,
+    pub path: String,
+    pub status: String,
+    pub size: u64,
+    pub duration_secs: f64,
+    /// 文件的 SHA-256（十六进制），失败或无法读取时为空
+    pub checksum: Option<String>,
+    /// 下载时服务器返回的 ETag
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    /// 下载时服务器返回的 Last-Modified
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+    pub error: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl HistoryEntry {
+    pub fn is_success(&self) -> bool {
+        self.status == "completed"
+    }
+}
+
+/// 历史记录存储
+#[derive(Debug, Clone)]
+pub struct HistoryStore {
+    path: PathBuf,
+}
+
+impl HistoryStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// 追加一条记录
+    pub fn append(&self, entry: &HistoryEntry) -> Result<(), DownloadError> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| DownloadError::io_error_with_context("创建历史记录目录", e))?;
+        }
+        let line = serde_json::to_string(entry)
+            .map_err(|e| DownloadError::unknown(format!("序列化历史记录失败: {}", e)))?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| DownloadError::io_error_with_context("打开历史记录", e))?;
+        writeln!(file, "{}", line).map_err(|e| DownloadError::io_error_with_context("写入历史记录", e))
+    }
+
+    /// 读取所有记录（按写入顺序），跳过无法解析的行
+    pub fn load(&self) -> Vec<HistoryEntry> {
+        fs::read_to_string(&self.path)
+            .map(|content| {
+                content
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// 按 URL 或路径中的关键字搜索（不区分大小写）
+    pub fn search(&self, keyword: &str) -> Vec<HistoryEntry> {
+        let keyword = keyword.to_lowercase();
+        self.load()
+            .into_iter()
+            .filter(|e| e.url.to_lowercase().contains(&keyword) || e.path.to_lowercase().contains(&keyword))
+            .collect()
+    }
+
+    /// URL 是否已经成功下载过
+    pub fn was_downloaded(&self, url: &str) -> bool {
+        self.successful_urls().contains(url)
+    }
+
+    /// 所有成功下载过的 URL
+    pub fn successful_urls(&self) -> HashSet<String> {
+        self.load()
+            .into_iter()
+            .filter(HistoryEntry::is_success)
+            .map(|e| e.url)
+            .collect()
+    }
+}
+
+/// 计算文件的 SHA-256（十六进制小写）
+pub fn sha256_file(path: impl AsRef<Path>) -> Result<String, DownloadError> {
+    sha256_file_limited(path, &mut SpeedLimiter::new(0), |_| {})
+}
+
+/// 计算文件的 SHA-256，按 `limiter` 限制读取速度，每读一段调用 `progress(已读取的字节数)`
+pub fn sha256_file_limited(
+    path: impl AsRef<Path>,
+    limiter: &mut SpeedLimiter,
+    mut progress: impl FnMut(u64),
+) -> Result<String, DownloadError> {
+    let mut file = fs::File::open(path.as_ref())
+        .map_err(|e| DownloadError::io_error_with_context("打开文件计算校验和", e))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; throttle_step(limiter)];
+    let mut read = 0u64;
+    loop {
+        let n = file.read(&mut buf)
+            .map_err(|e| DownloadError::io_error_with_context("读取文件计算校验和", e))?;
+        if n == 0 {
+            break;
+        }
+        throttle_blocking(limiter, n as u64);
+        hasher.update(&buf[..n]);
+        read += n as u64;
+        progress(read);
+    }
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// 十六进制小写
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(url: &str, status: &str) -> HistoryEntry {
+        HistoryEntry {
+            task_id: None,
+            url: url.to_string(),
+            path: "downloads/file.zip".to_string(),
+            status: status.to_string(),
+            size: 3,
+            duration_secs: 1.0,
+            checksum: None,
+            etag: None,
+            last_modified: None,
+            error: None,
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_history_append_search() {
+        let path = "test_history.jsonl";
+        let _ = fs::remove_file(path);
+        let store = HistoryStore::new(path);
+        store.append(&entry("https://example.com/a.zip", "completed")).unwrap();
+        store.append(&entry("https://example.com/b.zip", "failed")).unwrap();
+
+        assert_eq!(store.load().len(), 2);
+        assert_eq!(store.search("A.ZIP").len(), 1);
+        assert!(store.was_downloaded("https://example.com/a.zip"));
+        assert!(!store.was_downloaded("https://example.com/b.zip"));
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_sha256_file() {
+        let path = "test_sha256.bin";
+        fs::write(path, b"abc").unwrap();
+        assert_eq!(
+            sha256_file(path).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,547 @@
+//! 本地控制通道：守护进程（`multidown serve`）与命令行子命令、第三方工具之间的通信
+//!
+//! 每条消息是 4 字节大端长度加 UTF-8 编码的 JSON，一个请求对应一个响应，同一连接可以连续发送多个请求：
+//!
+//! ```text
+//! {"method":"add","url":"https://example.com/a.iso","file_name":"iso/a.iso","headers":{"Referer":"..."},"tags":["iso"]}
+//! {"method":"list","tag":"nightly"}
+//! {"method":"stats"}
+//! {"method":"pause","task_id":"1a2b3c4d"}
+//! {"method":"pause_tagged","tag":"nightly"}
+//! {"method":"resume","task_id":"1a2b3c4d"}
+//! {"method":"cancel","task_id":"1a2b3c4d"}
+//! {"method":"remove","task_id":"1a2b3c4d","with_data":false}
+//! {"method":"move","task_id":"1a2b3c4d","path":"iso/a.iso","temp_dir":null}
+//! ```
+//!
+//! 任务 ID 与命令行一样可以只写前 8 位。响应为 `{"ok":true,"result":...}` 或 `{"ok":false,"error":"..."}`。
+//! 请求可以带 `api_key` 字段，以配置中对应客户端的身份操作（见 [`Dispatcher`]）。
+//!
+//! 通道不另做认证，靠文件系统权限限制访问：Unix 上是会话目录中的 `multidown.sock`，权限 0600，
+//! 只有启动守护进程的用户可以连接；Windows 上是按会话目录命名的命名管道，拒绝远程客户端，
+//! 默认的安全描述符只允许创建者、管理员和 SYSTEM 写入。
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+use actix::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::config::clients::ClientKeys;
+use crate::core::actor_manager::{
+    find_tasks, CancelTask, CreateTask, DownloadManagerActor, DownloadTaskMeta, GetStats, ListTasks, MoveTask, PauseTask,
+    QueryTaskDetail, RemoveTask, StartTaskFromMeta,
+};
+use crate::core::error::DownloadError;
+use crate::core::task::{DownloadRequest, TaskStatus};
+use crate::utils::validator;
+
+/// Unix 上的套接字文件名，与会话文件放在同一目录
+pub const SOCKET_FILE: &str = "multidown.sock";
+
+/// 单条消息的最大长度，超过时断开连接
+pub const MAX_FRAME_LEN: usize = 16 << 20;
+
+/// 控制请求
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum Request {
+    /// 添加任务并立即开始，`file_name` 是下载目录下的相对路径，默认取 URL 的最后一段
+    Add {
+        url: String,
+        #[serde(default)]
+        file_name: Option<String>,
+        #[serde(default)]
+        headers: BTreeMap<String, String>,
+        #[serde(default)]
+        tags: Vec<String>,
+    },
+    /// 会话中的任务，指定 `tag` 时只返回带有该标签的任务
+    List {
+        #[serde(default)]
+        tag: Option<String>,
+    },
+    /// 任务统计
+    Stats,
+    /// 暂停任务，数据保留
+    Pause { task_id: String },
+    /// 暂停所有带有 `tag` 标签且尚未结束的任务
+    PauseTagged { tag: String },
+    /// 继续暂停或失败的任务
+    Resume { task_id: String },
+    /// 取消任务并删除临时数据
+    Cancel { task_id: String },
+    /// 从会话中删除任务
+    Remove {
+        task_id: String,
+        #[serde(default)]
+        with_data: bool,
+    },
+    /// 修改未完成任务的保存路径
+    Move {
+        task_id: String,
+        path: String,
+        #[serde(default)]
+        temp_dir: Option<String>,
+    },
+}
+
+/// 一条请求：`api_key` 标识发出请求的客户端（见 [`crate::config::clients`]），其余字段为 [`Request`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Envelope {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    #[serde(flatten)]
+    pub request: Request,
+}
+
+/// 控制响应
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl From<Result<Value, DownloadError>> for Response {
+    fn from(result: Result<Value, DownloadError>) -> Self {
+        match result {
+            Ok(value) => Self { ok: true, result: Some(value), error: None },
+            Err(e) => Self::error(e.to_string()),
+        }
+    }
+}
+
+impl Response {
+    pub fn error(message: String) -> Self {
+        Self { ok: false, result: None, error: Some(message) }
+    }
+
+    fn into_result(self) -> Result<Value, CallError> {
+        match self.ok {
+            true => Ok(self.result.unwrap_or(Value::Null)),
+            false => Err(CallError::Remote(self.error.unwrap_or_default())),
+        }
+    }
+}
+
+/// 调用守护进程失败的原因
+#[derive(Debug, thiserror::Error)]
+pub enum CallError {
+    /// 连接中断或响应无法解析
+    #[error("与守护进程通信失败: {0}")]
+    Io(#[from] io::Error),
+    /// 守护进程处理请求时出错
+    #[error("{0}")]
+    Remote(String),
+}
+
+/// 编码一条消息：长度前缀加 JSON
+pub fn encode(value: &impl Serialize) -> io::Result<Vec<u8>> {
+    let body = serde_json::to_vec(value)?;
+    if body.len() > MAX_FRAME_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "消息过长"));
+    }
+    let mut frame = Vec::with_capacity(4 + body.len());
+    frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&body);
+    Ok(frame)
+}
+
+fn frame_len(header: [u8; 4]) -> io::Result<usize> {
+    let len = u32::from_be_bytes(header) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("消息过长: {} 字节", len)));
+    }
+    Ok(len)
+}
+
+/// 读取一条消息的 JSON 部分
+pub fn read_frame(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut header = [0u8; 4];
+    reader.read_exact(&mut header)?;
+    let mut body = vec![0u8; frame_len(header)?];
+    reader.read_exact(&mut body)?;
+    Ok(body)
+}
+
+/// 异步读取一条消息，对方在消息之间关闭连接时返回 None
+async fn read_frame_async(reader: &mut (impl AsyncRead + Unpin)) -> io::Result<Option<Vec<u8>>> {
+    let mut header = [0u8; 4];
+    match reader.read_exact(&mut header).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let mut body = vec![0u8; frame_len(header)?];
+    reader.read_exact(&mut body).await?;
+    Ok(Some(body))
+}
+
+/// 会话目录 `state_dir` 的控制通道地址：Unix 上是套接字文件，Windows 上是由会话目录的绝对路径推导出的命名管道
+pub fn endpoint(state_dir: &Path) -> String {
+    #[cfg(windows)]
+    {
+        let dir = std::path::absolute(state_dir).unwrap_or_else(|_| state_dir.to_path_buf());
+        format!(r"\\.\pipe\multidown-{:08x}", crc32fast::hash(dir.to_string_lossy().to_lowercase().as_bytes()))
+    }
+    #[cfg(not(windows))]
+    {
+        state_dir.join(SOCKET_FILE).to_string_lossy().into_owned()
+    }
+}
+
+/// 同步客户端，命令行子命令和第三方工具使用
+pub struct Client {
+    #[cfg(unix)]
+    stream: std::os::unix::net::UnixStream,
+    #[cfg(windows)]
+    stream: std::fs::File,
+    api_key: Option<String>,
+}
+
+impl Client {
+    /// 连接守护进程；没有守护进程在运行时返回错误（套接字不存在或拒绝连接）
+    pub fn connect(endpoint: &str) -> io::Result<Self> {
+        #[cfg(unix)]
+        let stream = std::os::unix::net::UnixStream::connect(endpoint)?;
+        #[cfg(windows)]
+        let stream = std::fs::OpenOptions::new().read(true).write(true).open(endpoint)?;
+        Ok(Self { stream, api_key: None })
+    }
+
+    /// 之后的请求都带上 API 密钥，以对应客户端的身份操作
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// 发送请求并等待响应
+    pub fn call(&mut self, request: &Request) -> Result<Value, CallError> {
+        let envelope = Envelope { api_key: self.api_key.clone(), request: request.clone() };
+        self.stream.write_all(&encode(&envelope)?)?;
+        let response: Response = serde_json::from_slice(&read_frame(&mut self.stream)?).map_err(io::Error::from)?;
+        response.into_result()
+    }
+}
+
+/// 守护进程一侧的监听器
+pub struct Server {
+    #[cfg(unix)]
+    listener: tokio::net::UnixListener,
+    #[cfg(unix)]
+    path: std::path::PathBuf,
+    #[cfg(windows)]
+    name: String,
+    #[cfg(windows)]
+    next: tokio::net::windows::named_pipe::NamedPipeServer,
+}
+
+impl Server {
+    /// 开始监听；Unix 上残留的套接字文件（上次守护进程崩溃）被替换，新文件权限为 0600
+    ///
+    /// 先在临时名称上监听并设置权限，再改名为正式路径，正式路径上不会出现权限还没有收紧的套接字。
+    #[cfg(unix)]
+    pub fn bind(endpoint: &str) -> Result<Self, DownloadError> {
+        use std::os::unix::fs::PermissionsExt;
+        let path = Path::new(endpoint).to_path_buf();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(|e| DownloadError::io_error_with_context("创建会话目录", e))?;
+        }
+        let temp = path.with_extension(format!("sock.{}", std::process::id()));
+        let _ = std::fs::remove_file(&temp);
+        let listener = tokio::net::UnixListener::bind(&temp)
+            .map_err(|e| DownloadError::io_error_with_context(format!("无法监听 {}", temp.display()), e))?;
+        std::fs::set_permissions(&temp, std::fs::Permissions::from_mode(0o600))
+            .and_then(|()| std::fs::rename(&temp, &path))
+            .map_err(|e| {
+                let _ = std::fs::remove_file(&temp);
+                DownloadError::io_error_with_context(format!("无法创建 {}", path.display()), e)
+            })?;
+        Ok(Self { listener, path })
+    }
+
+    /// 创建命名管道的第一个实例；同名管道已存在（另一个守护进程）时失败
+    #[cfg(windows)]
+    pub fn bind(endpoint: &str) -> Result<Self, DownloadError> {
+        use tokio::net::windows::named_pipe::ServerOptions;
+        let next = ServerOptions::new()
+            .first_pipe_instance(true)
+            .reject_remote_clients(true)
+            .create(endpoint)
+            .map_err(|e| DownloadError::io_error_with_context(format!("无法创建命名管道 {}", endpoint), e))?;
+        Ok(Self { name: endpoint.to_string(), next })
+    }
+
+    /// 接受连接并把请求交给 `dispatcher`，直到 `stop` 被取消
+    pub async fn run(self, dispatcher: Dispatcher, stop: CancellationToken) {
+        let dispatcher = Rc::new(dispatcher);
+        #[cfg(unix)]
+        loop {
+            let stream = tokio::select! {
+                _ = stop.cancelled() => break,
+                accepted = self.listener.accept() => match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        tracing::warn!(error = %e, "接受控制连接失败");
+                        continue;
+                    }
+                },
+            };
+            actix::spawn(serve_connection(stream, dispatcher.clone()));
+        }
+        #[cfg(windows)]
+        {
+            let mut server = self;
+            loop {
+                tokio::select! {
+                    _ = stop.cancelled() => break,
+                    connected = server.next.connect() => {
+                        if let Err(e) = connected {
+                            tracing::warn!(error = %e, "接受控制连接失败");
+                            continue;
+                        }
+                    }
+                }
+                // 先创建下一个实例再处理当前连接，其他客户端不会在间隙中找不到管道
+                let next = match tokio::net::windows::named_pipe::ServerOptions::new().reject_remote_clients(true).create(&server.name) {
+                    Ok(next) => next,
+                    Err(e) => {
+                        tracing::warn!(error = %e, "创建命名管道实例失败");
+                        break;
+                    }
+                };
+                let stream = std::mem::replace(&mut server.next, next);
+                actix::spawn(serve_connection(stream, dispatcher.clone()));
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// 处理一个连接上的所有请求
+async fn serve_connection(mut stream: impl AsyncRead + AsyncWrite + Unpin, dispatcher: Rc<Dispatcher>) {
+    loop {
+        let body = match read_frame_async(&mut stream).await {
+            Ok(Some(body)) => body,
+            Ok(None) => break,
+            Err(e) => {
+                tracing::debug!(error = %e, "读取控制请求失败");
+                break;
+            }
+        };
+        let response = match serde_json::from_slice::<Envelope>(&body) {
+            Ok(envelope) => dispatcher.dispatch(envelope).await,
+            Err(e) => Response::error(format!("无效的请求: {}", e)),
+        };
+        let written = match encode(&response) {
+            Ok(frame) => stream.write_all(&frame).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = written {
+            tracing::debug!(error = %e, "发送控制响应失败");
+            break;
+        }
+    }
+}
+
+fn to_value(value: impl Serialize) -> Result<Value, DownloadError> {
+    serde_json::to_value(value).map_err(|e| DownloadError::unknown(e.to_string()))
+}
+
+/// 把控制请求转发给下载管理器
+///
+/// 带 `api_key` 的请求属于对应的客户端：添加的任务记在它名下，只能查看和操作它自己的任务，
+/// `stats` 只返回它自己的统计；不带密钥的请求属于守护进程的所有者，没有这些限制。
+#[derive(Clone)]
+pub struct Dispatcher {
+    pub manager: Addr<DownloadManagerActor>,
+    pub download_dir: String,
+    pub keys: ClientKeys,
+}
+
+impl Dispatcher {
+    async fn dispatch(&self, envelope: Envelope) -> Response {
+        let client = match &envelope.api_key {
+            Some(key) => match self.keys.authenticate(key) {
+                Some(client) => Some(client),
+                None => return Response::error("无效的 API 密钥".to_string()),
+            },
+            None => None,
+        };
+        Response::from(self.handle(client, envelope.request).await)
+    }
+
+    /// 按完整 ID 或前缀（至少 8 位）查找 `client` 可以操作的任务
+    async fn resolve(&self, id: &str, client: Option<&str>) -> Result<Uuid, DownloadError> {
+        let metas = self.tasks(client).await?;
+        match find_tasks(&metas, id).as_slice() {
+            [meta] => Ok(meta.id),
+            [] => Err(DownloadError::unknown(format!("任务不存在: {}", id))),
+            _ => Err(DownloadError::unknown(format!("任务 ID {} 对应多个任务", id))),
+        }
+    }
+
+    /// `client` 可以看到的任务
+    async fn tasks(&self, client: Option<&str>) -> Result<Vec<DownloadTaskMeta>, DownloadError> {
+        let mut metas = self.manager.send(ListTasks).await?;
+        if client.is_some() {
+            metas.retain(|meta| meta.client.as_deref() == client);
+        }
+        Ok(metas)
+    }
+
+    /// 以 `client` 的身份（None 为守护进程的所有者）处理请求，认证由调用者完成
+    pub async fn handle(&self, client: Option<&str>, request: Request) -> Result<Value, DownloadError> {
+        let manager = &self.manager;
+        match request {
+            Request::Add { url, file_name, headers, tags } => {
+                let url = validator::parse_url(&url)?;
+                let file_name = file_name.unwrap_or_else(|| validator::file_name_from_url(&url));
+                let mut builder = DownloadRequest::builder()
+                    .url(url.to_string())
+                    .output(Path::new(&self.download_dir).join(file_name).to_string_lossy())
+                    .tags(tags);
+                for (name, value) in headers {
+                    builder = builder.header(name, value);
+                }
+                if let Some(client) = client {
+                    builder = builder.client(client);
+                }
+                let task_id = manager.send(CreateTask(builder.build()?)).await??;
+                manager.do_send(StartTaskFromMeta { task_id });
+                tracing::info!(task_id = %task_id, url = %url, client = client.unwrap_or("-"), "通过本地控制通道添加任务");
+                let path = manager.send(QueryTaskDetail(task_id)).await?.map(|meta| meta.file).unwrap_or_default();
+                Ok(json!({ "task_id": task_id, "path": path }))
+            }
+            Request::List { tag } => {
+                let mut metas = self.tasks(client).await?;
+                metas.retain(|meta| meta.has_tag(tag.as_deref()));
+                to_value(metas)
+            }
+            Request::Stats => {
+                let stats = manager.send(GetStats).await?;
+                match client {
+                    Some(client) => to_value(stats.clients.get(client).cloned().unwrap_or_default()),
+                    None => to_value(stats),
+                }
+            }
+            Request::Pause { task_id } => {
+                let task_id = self.resolve(&task_id, client).await?;
+                manager.send(PauseTask(task_id)).await?;
+                Ok(json!({ "task_id": task_id }))
+            }
+            Request::PauseTagged { tag } => {
+                let mut task_ids = Vec::new();
+                for meta in self.tasks(client).await? {
+                    if meta.has_tag(Some(&tag)) && meta.status.can_transition_to(&TaskStatus::Paused) {
+                        manager.send(PauseTask(meta.id)).await?;
+                        task_ids.push(meta.id);
+                    }
+                }
+                tracing::info!(tag = %tag, count = task_ids.len(), "通过本地控制通道按标签暂停任务");
+                Ok(json!({ "task_ids": task_ids }))
+            }
+            Request::Resume { task_id } => {
+                let task_id = self.resolve(&task_id, client).await?;
+                manager.send(StartTaskFromMeta { task_id }).await?;
+                Ok(json!({ "task_id": task_id }))
+            }
+            Request::Cancel { task_id } => {
+                let task_id = self.resolve(&task_id, client).await?;
+                manager.send(CancelTask(task_id)).await?;
+                Ok(json!({ "task_id": task_id }))
+            }
+            Request::Remove { task_id, with_data } => {
+                let task_id = self.resolve(&task_id, client).await?;
+                let file = manager.send(QueryTaskDetail(task_id)).await?.map(|meta| meta.file).unwrap_or_default();
+                manager.send(RemoveTask { task_id, with_data }).await?;
+                tracing::info!(task_id = %task_id, "通过本地控制通道删除任务");
+                Ok(json!({ "task_id": task_id, "file": file }))
+            }
+            Request::Move { task_id, path, temp_dir } => {
+                let task_id = self.resolve(&task_id, client).await?;
+                let from = manager.send(QueryTaskDetail(task_id)).await?.map(|meta| meta.file).unwrap_or_default();
+                let path = manager.send(MoveTask { task_id, path, temp_dir }).await??;
+                Ok(json!({ "task_id": task_id, "from": from, "path": path }))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_roundtrip() {
+        let request = Request::Remove { task_id: "1a2b3c4d".to_string(), with_data: true };
+        let frame = encode(&request).unwrap();
+        assert_eq!(u32::from_be_bytes(frame[..4].try_into().unwrap()) as usize, frame.len() - 4);
+        let body = read_frame(&mut &frame[..]).unwrap();
+        assert_eq!(serde_json::from_slice::<Request>(&body).unwrap(), request);
+
+        // api_key 与请求字段写在同一层
+        let envelope: Envelope = serde_json::from_str(r#"{"api_key":"k","method":"pause","task_id":"1a2b3c4d"}"#).unwrap();
+        assert_eq!(envelope, Envelope { api_key: Some("k".to_string()), request: Request::Pause { task_id: "1a2b3c4d".to_string() } });
+
+        // 可选字段可以省略
+        let add: Request = serde_json::from_str(r#"{"method":"add","url":"https://example.com/a.iso"}"#).unwrap();
+        assert_eq!(add, Request::Add { url: "https://example.com/a.iso".to_string(), file_name: None, headers: BTreeMap::new(), tags: vec![] });
+        let list: Request = serde_json::from_str(r#"{"method":"list"}"#).unwrap();
+        assert_eq!(list, Request::List { tag: None });
+        let pause = Request::PauseTagged { tag: "nightly".to_string() };
+        assert_eq!(serde_json::to_value(&pause).unwrap(), json!({ "method": "pause_tagged", "tag": "nightly" }));
+
+        // 超长的长度前缀直接拒绝，不会按它分配内存
+        let oversized = ((MAX_FRAME_LEN + 1) as u32).to_be_bytes();
+        assert_eq!(read_frame(&mut &oversized[..]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        let error = Response::from(Err(DownloadError::unknown("任务不存在: 1a2b3c4d")));
+        assert_eq!(serde_json::to_value(&error).unwrap(), json!({ "ok": false, "error": "未知错误: 任务不存在: 1a2b3c4d" }));
+        assert!(matches!(error.into_result(), Err(CallError::Remote(e)) if e == "未知错误: 任务不存在: 1a2b3c4d"));
+    }
+
+    #[cfg(unix)]
+    #[actix_rt::test]
+    async fn test_unix_socket() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("multidown_ipc_{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let endpoint = dir.join("multidown.sock").to_string_lossy().into_owned();
+        // 上次崩溃残留的文件被替换
+        std::fs::write(&endpoint, b"stale").unwrap();
+        let server = Server::bind(&endpoint).unwrap();
+        assert_eq!(std::fs::metadata(&endpoint).unwrap().permissions().mode() & 0o777, 0o600);
+
+        let client = {
+            let endpoint = endpoint.clone();
+            std::thread::spawn(move || Client::connect(&endpoint).unwrap().call(&Request::Stats))
+        };
+        let (mut stream, _) = server.listener.accept().await.unwrap();
+        let body = read_frame_async(&mut stream).await.unwrap().unwrap();
+        assert_eq!(serde_json::from_slice::<Envelope>(&body).unwrap().request, Request::Stats);
+        stream.write_all(&encode(&Response::from(Ok(json!({ "total": 1 })))).unwrap()).await.unwrap();
+        assert_eq!(client.join().unwrap().unwrap()["total"], 1);
+        // 客户端关闭连接后读到 None
+        assert!(read_frame_async(&mut stream).await.unwrap().is_none());
+
+        drop(server);
+        assert!(!Path::new(&endpoint).exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,250 @@
+//! Git LFS 指针文件：`multidown lfs <指针文件>... --repo <仓库地址>`
+//!
+//! 模型权重等大文件在 Git 仓库（GitHub、GitLab、Hugging Face）中只保存一个指针文件：
+//!
+//! ```text
+//! version https://git-lfs.github.com/spec/v1
+//! oid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393
+//! size 12345
+//! ```
+//!
+//! 克隆时跳过 LFS（`GIT_LFS_SKIP_SMUDGE=1`）后，用指针向仓库的 LFS 批量接口
+//! （`<仓库>.git/info/lfs/objects/batch`）一次查询所有对象的存储地址，
+//! 再作为普通任务分块下载、断点续传，完成后按指针中的 SHA-256 校验。
+
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+
+use crate::config::Config;
+use crate::core::error::DownloadError;
+use crate::core::task::AwcTransport;
+
+/// 指针文件第一行的版本标识
+const POINTER_VERSION: &str = "version https://git-lfs.github.com/spec/v1";
+
+/// LFS 批量接口使用的媒体类型
+const LFS_MEDIA_TYPE: &str = "application/vnd.git-lfs+json";
+
+/// 指针文件的大小上限，更大的文件不可能是指针
+pub const MAX_POINTER_SIZE: u64 = 1024;
+
+/// 指针文件中记录的对象
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LfsPointer {
+    /// 对象内容的 SHA-256（小写十六进制）
+    pub oid: String,
+    pub size: u64,
+}
+
+impl LfsPointer {
+    /// 解析指针文件的内容
+    pub fn parse(text: &str) -> Result<Self, DownloadError> {
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+        if lines.next() != Some(POINTER_VERSION) {
+            return Err(DownloadError::unknown("不是 Git LFS 指针文件"));
+        }
+        let (mut oid, mut size) = (None, None);
+        for line in lines {
+            match line.split_once(' ') {
+                Some(("oid", value)) => oid = value.strip_prefix("sha256:").map(str::to_ascii_lowercase),
+                Some(("size", value)) => size = value.parse::<u64>().ok(),
+                _ => {}
+            }
+        }
+        match (oid, size) {
+            (Some(oid), Some(size)) if oid.len() == 64 && oid.chars().all(|c| c.is_ascii_hexdigit()) => Ok(Self { oid, size }),
+            _ => Err(DownloadError::unknown("LFS 指针缺少有效的 oid（sha256）或 size")),
+        }
+    }
+}
+
+/// 对象在下载目录下的保存路径：相对路径原样保留（同名的 `unet/model.safetensors`、`vae/model.safetensors` 不会冲突），
+/// 绝对路径或包含 `..` 时只取文件名
+pub fn output_name(pointer_path: &str) -> String {
+    let path = std::path::Path::new(pointer_path);
+    let relative = path.components().all(|c| matches!(c, std::path::Component::Normal(_) | std::path::Component::CurDir));
+    match path.file_name() {
+        Some(name) if !relative => name.to_string_lossy().into_owned(),
+        _ => pointer_path.trim_start_matches("./").to_string(),
+    }
+}
+
+/// 对象的下载地址和下载时需要附带的请求头
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LfsObject {
+    pub href: String,
+    pub headers: Vec<(String, String)>,
+}
+
+/// 仓库地址对应的 LFS 批量接口：`https://huggingface.co/org/model` → `https://huggingface.co/org/model.git/info/lfs/objects/batch`
+pub fn batch_url(repo: &str) -> Result<String, DownloadError> {
+    let mut url = url::Url::parse(repo.trim()).map_err(|e| DownloadError::invalid_url(format!("{} ({})", repo, e)))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(DownloadError::UnsupportedProtocol(url.scheme().to_string().into()));
+    }
+    let path = url.path().trim_end_matches('/');
+    let path = if path.ends_with(".git") { path.to_string() } else { format!("{}.git", path) };
+    url.set_path(&format!("{}/info/lfs/objects/batch", path));
+    url.set_query(None);
+    url.set_fragment(None);
+    Ok(url.to_string())
+}
+
+#[derive(Deserialize)]
+struct BatchResponse {
+    objects: Vec<BatchObject>,
+}
+
+#[derive(Deserialize)]
+struct BatchObject {
+    oid: String,
+    #[serde(default)]
+    actions: Option<BatchActions>,
+    #[serde(default)]
+    error: Option<BatchError>,
+}
+
+#[derive(Deserialize)]
+struct BatchActions {
+    download: Option<BatchAction>,
+}
+
+#[derive(Deserialize)]
+struct BatchAction {
+    href: String,
+    #[serde(default)]
+    header: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct BatchError {
+    code: u16,
+    message: String,
+}
+
+/// 解析批量接口的响应，按 `pointers` 的顺序返回每个对象的下载地址或错误
+fn parse_batch_response(body: &[u8], pointers: &[LfsPointer]) -> Result<Vec<Result<LfsObject, DownloadError>>, DownloadError> {
+    let response: BatchResponse =
+        serde_json::from_slice(body).map_err(|e| DownloadError::unknown(format!("LFS 接口返回的内容无法解析: {}", e)))?;
+    let mut objects: HashMap<String, BatchObject> = response.objects.into_iter().map(|o| (o.oid.to_ascii_lowercase(), o)).collect();
+    Ok(pointers
+        .iter()
+        .map(|pointer| match objects.remove(&pointer.oid) {
+            Some(BatchObject { error: Some(error), .. }) => {
+                Err(DownloadError::ServerError(format!("LFS 对象 {}: {} ({})", pointer.oid, error.message, error.code).into()))
+            }
+            Some(BatchObject { actions: Some(BatchActions { download: Some(action) }), .. }) => {
+                let mut headers: Vec<(String, String)> = action.header.into_iter().collect();
+                headers.sort();
+                Ok(LfsObject { href: action.href, headers })
+            }
+            _ => Err(DownloadError::ServerError(format!("LFS 接口没有返回对象 {} 的下载地址", pointer.oid).into())),
+        })
+        .collect())
+}
+
+/// 向仓库的 LFS 批量接口查询所有对象的下载地址，`token` 作为 Bearer 令牌（Hugging Face 私有仓库等）；
+/// 仓库地址中的用户名和密码按 Basic 认证发送
+pub async fn resolve(
+    repo: &str,
+    pointers: &[LfsPointer],
+    token: Option<&str>,
+    config: &Config,
+) -> Result<Vec<Result<LfsObject, DownloadError>>, DownloadError> {
+    let endpoint = batch_url(repo)?;
+    let mut url = url::Url::parse(&endpoint).map_err(|e| DownloadError::invalid_url(e.to_string()))?;
+    let credentials = (!url.username().is_empty()).then(|| (url.username().to_string(), url.password().map(str::to_string)));
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+
+    let transport = AwcTransport::new(config);
+    let mut request = transport
+        .clients
+        .get(url.as_str())
+        .post(url.as_str())
+        .timeout(std::time::Duration::from_secs(config.timeout))
+        .insert_header(("Accept", LFS_MEDIA_TYPE))
+        .insert_header(("Content-Type", LFS_MEDIA_TYPE))
+        .insert_header(("User-Agent", config.user_agent.as_str()));
+    match (token, credentials) {
+        (Some(token), _) => request = request.bearer_auth(token),
+        (None, Some((user, password))) => request = request.basic_auth(user, password.as_deref().unwrap_or_default()),
+        (None, None) => {}
+    }
+    let body = json!({
+        "operation": "download",
+        "transfers": ["basic"],
+        "objects": pointers.iter().map(|p| json!({ "oid": p.oid, "size": p.size })).collect::<Vec<_>>(),
+    });
+    let mut response = request
+        .send_body(body.to_string())
+        .await
+        .map_err(|e| DownloadError::network_error(format!("请求 LFS 接口失败: {}", e)))?;
+    let status = response.status().as_u16();
+    if !response.status().is_success() {
+        return Err(DownloadError::HttpStatus { status, host: url.host_str().map(str::to_string), chunk: None });
+    }
+    let body = response
+        .body()
+        .limit(16 * 1024 * 1024)
+        .await
+        .map_err(|e| DownloadError::network_error(format!("读取 LFS 接口响应失败: {}", e)))?;
+    parse_batch_response(&body, pointers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OID: &str = "4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393";
+
+    #[test]
+    fn test_parse_pointer() {
+        let text = format!("{}\noid sha256:{}\nsize 12345\n", POINTER_VERSION, OID.to_ascii_uppercase());
+        assert_eq!(LfsPointer::parse(&text).unwrap(), LfsPointer { oid: OID.to_string(), size: 12345 });
+        assert!(LfsPointer::parse("hello world").is_err());
+        assert!(LfsPointer::parse(&format!("{}\noid sha256:abc\nsize 1\n", POINTER_VERSION)).is_err());
+        assert!(LfsPointer::parse(&format!("{}\noid sha256:{}\n", POINTER_VERSION, OID)).is_err());
+
+        assert_eq!(output_name("unet/model.safetensors"), "unet/model.safetensors");
+        assert_eq!(output_name("./model.bin"), "model.bin");
+        assert_eq!(output_name("../other/model.bin"), "model.bin");
+        assert_eq!(output_name("/data/repo/model.bin"), "model.bin");
+    }
+
+    #[test]
+    fn test_batch_url_and_response() {
+        assert_eq!(
+            batch_url("https://huggingface.co/org/model").unwrap(),
+            "https://huggingface.co/org/model.git/info/lfs/objects/batch"
+        );
+        assert_eq!(
+            batch_url("https://github.com/org/repo.git/").unwrap(),
+            "https://github.com/org/repo.git/info/lfs/objects/batch"
+        );
+        assert!(batch_url("ssh://git@github.com/org/repo.git").is_err());
+
+        let missing = "0".repeat(64);
+        let pointers = [
+            LfsPointer { oid: OID.to_string(), size: 3 },
+            LfsPointer { oid: missing.clone(), size: 1 },
+            LfsPointer { oid: "1".repeat(64), size: 1 },
+        ];
+        let body = json!({
+            "transfer": "basic",
+            "objects": [
+                { "oid": OID, "size": 3, "actions": { "download": {
+                    "href": "https://cdn.example.com/a?sig=1", "header": { "X-Token": "t" } } } },
+                { "oid": missing, "size": 1, "error": { "code": 404, "message": "Object does not exist" } },
+            ]
+        });
+        let objects = parse_batch_response(body.to_string().as_bytes(), &pointers).unwrap();
+        assert_eq!(
+            objects[0].as_ref().unwrap(),
+            &LfsObject { href: "https://cdn.example.com/a?sig=1".to_string(), headers: vec![("X-Token".to_string(), "t".to_string())] }
+        );
+        assert!(objects[1].as_ref().unwrap_err().to_string().contains("Object does not exist"));
+        assert!(objects[2].is_err());
+    }
+}
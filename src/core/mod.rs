@@ -1,5 +1,22 @@
 //! Core: 下载任务的actor管理、任务调度、错误处理等核心逻辑模块
 
 pub mod actor_manager;
+pub mod bandwidth;
+pub mod bench;
+pub mod check;
+pub mod embedded;
 pub mod error;
-pub mod task; 
\ No newline at end of file
+pub mod events;
+pub mod history;
+pub mod ipc;
+pub mod lfs;
+pub mod oci;
+pub mod probe_cache;
+pub mod queue;
+pub mod relocate;
+pub mod script;
+pub mod session_lock;
+pub mod stream;
+pub mod task;
+pub mod usage;
+pub mod verify;
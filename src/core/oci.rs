@@ -0,0 +1,457 @@
+//! 从 Docker/OCI 镜像仓库下载镜像：`multidown docker://registry-1.docker.io/library/alpine@sha256:...`
+//!
+//! 不需要 docker 守护进程，下载结果写成 OCI 镜像布局目录（`oci-layout`、`index.json`、`blobs/sha256/`），
+//! 可以用 `skopeo copy oci:<目录> ...`、`podman load` 等工具导入：
+//!
+//! - 先按仓库返回的 `WWW-Authenticate` 质询获取拉取令牌（匿名，或使用地址中的用户名和密码）；
+//! - 取得清单（多平台镜像先按 `linux/<本机架构>` 选出对应的清单），校验清单的摘要，写入布局目录；
+//! - 配置和各层作为普通任务并行下载，分块、重试、断点续传与其他任务相同，完成后按摘要校验 SHA-256。
+//!
+//! 仓库地址省略时使用 Docker Hub，官方镜像可以省略 `library/`；`localhost` 和 `127.0.0.1` 上的仓库使用 HTTP。
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+use crate::config::Config;
+use crate::core::error::DownloadError;
+use crate::core::task::AwcTransport;
+
+/// 镜像地址的前缀
+pub const SCHEME_PREFIX: &str = "docker://";
+
+/// 获取清单时接受的媒体类型：OCI 和 Docker 的单平台清单与多平台索引
+const MANIFEST_MEDIA_TYPES: &str = "application/vnd.oci.image.index.v1+json, application/vnd.oci.image.manifest.v1+json, \
+     application/vnd.docker.distribution.manifest.list.v2+json, application/vnd.docker.distribution.manifest.v2+json";
+
+/// 清单和令牌响应的大小上限
+const MAX_MANIFEST_SIZE: usize = 4 * 1024 * 1024;
+
+const DOCKER_HUB: &str = "registry-1.docker.io";
+
+/// 是否为 `docker://` 镜像地址
+pub fn is_reference(url: &str) -> bool {
+    url.get(..SCHEME_PREFIX.len()).is_some_and(|prefix| prefix.eq_ignore_ascii_case(SCHEME_PREFIX))
+}
+
+/// 镜像地址：`docker://[用户名:密码@]仓库地址/镜像名[:标签|@sha256:摘要]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageReference {
+    pub registry: String,
+    pub repository: String,
+    /// 标签或 `sha256:` 摘要，都没有时为 `latest`
+    pub reference: String,
+    pub credentials: Option<(String, String)>,
+}
+
+impl ImageReference {
+    pub fn parse(input: &str) -> Result<Self, DownloadError> {
+        let invalid = |reason: &str| DownloadError::invalid_url(format!("{} ({})", input, reason));
+        let rest = input.get(SCHEME_PREFIX.len()..).filter(|_| is_reference(input)).ok_or_else(|| invalid("应以 docker:// 开头"))?;
+        let (credentials, rest) = match rest.split_once('/').map(|(head, _)| head.rsplit_once('@')) {
+            Some(Some((userinfo, _))) => {
+                let (user, password) = userinfo.split_once(':').unwrap_or((userinfo, ""));
+                (Some((user.to_string(), password.to_string())), &rest[userinfo.len() + 1..])
+            }
+            _ => (None, rest),
+        };
+        // 第一段包含 `.`、`:` 或为 localhost 时是仓库地址，否则是 Docker Hub 上的镜像名
+        let (registry, name) = match rest.split_once('/') {
+            Some((host, name)) if host.contains(['.', ':']) || host == "localhost" => (host.to_string(), name),
+            _ => (DOCKER_HUB.to_string(), rest),
+        };
+        let registry = if registry == "docker.io" { DOCKER_HUB.to_string() } else { registry };
+        let (repository, reference) = match name.split_once('@') {
+            Some((repository, digest)) => {
+                digest_hex(digest)?;
+                (repository, digest.to_string())
+            }
+            None => match name.rsplit_once(':') {
+                Some((repository, tag)) if !tag.contains('/') => (repository, tag.to_string()),
+                _ => (name, "latest".to_string()),
+            },
+        };
+        if repository.is_empty() || reference.is_empty() {
+            return Err(invalid("缺少镜像名"));
+        }
+        let repository = if registry == DOCKER_HUB && !repository.contains('/') {
+            format!("library/{}", repository)
+        } else {
+            repository.to_string()
+        };
+        Ok(Self { registry, repository, reference, credentials })
+    }
+
+    /// 仓库 API 的地址：`https://<仓库地址>/v2/<镜像名>`
+    pub fn api_base(&self) -> String {
+        let host = self.registry.split(':').next().unwrap_or_default();
+        let scheme = if matches!(host, "localhost" | "127.0.0.1") { "http" } else { "https" };
+        format!("{}://{}/v2/{}", scheme, self.registry, self.repository)
+    }
+
+    /// 布局目录的默认名称：`alpine-3.19`、`alpine-sha256-0123456789ab`
+    pub fn layout_name(&self) -> String {
+        let name = self.repository.rsplit('/').next().unwrap_or_default();
+        match self.reference.strip_prefix("sha256:") {
+            Some(hex) => format!("{}-sha256-{}", name, &hex[..12]),
+            None => format!("{}-{}", name, self.reference),
+        }
+    }
+}
+
+/// `sha256:<64 位十六进制>` 中的十六进制部分，只支持 SHA-256
+pub fn digest_hex(digest: &str) -> Result<&str, DownloadError> {
+    match digest.strip_prefix("sha256:") {
+        Some(hex) if hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()) => Ok(hex),
+        _ => Err(DownloadError::invalid_url(format!("不支持的摘要: {}（只支持 sha256）", digest))),
+    }
+}
+
+/// 清单中引用的内容（配置、层、平台清单）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Descriptor {
+    pub media_type: String,
+    pub digest: String,
+    pub size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platform: Option<Platform>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Platform {
+    pub architecture: String,
+    pub os: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    #[serde(default)]
+    media_type: Option<String>,
+    #[serde(default)]
+    config: Option<Descriptor>,
+    #[serde(default)]
+    layers: Vec<Descriptor>,
+    #[serde(default)]
+    manifests: Vec<Descriptor>,
+}
+
+/// 解析好的镜像：选中平台的清单，以及需要下载的配置和层
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub manifest: Descriptor,
+    pub manifest_bytes: Vec<u8>,
+    /// 配置和各层，已去掉重复的摘要
+    pub blobs: Vec<Descriptor>,
+    /// 下载内容时使用的令牌
+    pub token: Option<String>,
+}
+
+/// 本机对应的 OCI 架构名
+fn host_architecture() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "x86" => "386",
+        arch => arch,
+    }
+}
+
+/// 解析 `WWW-Authenticate: Bearer realm="...",service="...",scope="..."`
+fn parse_challenge(header: &str) -> Option<Vec<(String, String)>> {
+    let params = header.trim().strip_prefix("Bearer ").or_else(|| header.trim().strip_prefix("bearer "))?;
+    let mut result = Vec::new();
+    let mut rest = params.trim();
+    while let Some((key, value)) = rest.split_once('=') {
+        let value = value.trim_start();
+        let (value, tail) = match value.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => value.split_once(',').unwrap_or((value, "")),
+        };
+        result.push((key.trim().to_string(), value.to_string()));
+        rest = tail.trim_start_matches([',', ' ']);
+    }
+    result.iter().any(|(key, _)| key == "realm").then_some(result)
+}
+
+/// 内容的摘要：`sha256:<十六进制>`
+fn sha256_digest(data: &[u8]) -> String {
+    let hex: String = Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect();
+    format!("sha256:{}", hex)
+}
+
+/// 解析清单：多平台索引按 `os/architecture` 选出平台清单的描述，单平台清单返回配置和各层
+enum Parsed {
+    Index(Descriptor),
+    Image(Vec<Descriptor>),
+}
+
+fn parse_manifest(body: &[u8], os: &str, architecture: &str) -> Result<Parsed, DownloadError> {
+    let manifest: Manifest =
+        serde_json::from_slice(body).map_err(|e| DownloadError::unknown(format!("镜像清单无法解析: {}", e)))?;
+    if !manifest.manifests.is_empty() {
+        let matches = |d: &&Descriptor| d.platform.as_ref().is_some_and(|p| p.os == os && p.architecture == architecture);
+        return match manifest.manifests.iter().find(matches) {
+            Some(descriptor) => Ok(Parsed::Index(descriptor.clone())),
+            None => {
+                let available: Vec<String> = manifest
+                    .manifests
+                    .iter()
+                    .filter_map(|d| d.platform.as_ref())
+                    .map(|p| format!("{}/{}", p.os, p.architecture))
+                    .collect();
+                Err(DownloadError::unknown(format!(
+                    "镜像没有 {}/{} 平台的版本（可用: {}）",
+                    os,
+                    architecture,
+                    available.join(", ")
+                )))
+            }
+        };
+    }
+    let config = manifest
+        .config
+        .ok_or_else(|| DownloadError::unknown(format!("镜像清单缺少 config（{}）", manifest.media_type.unwrap_or_default())))?;
+    let mut blobs = vec![config];
+    for layer in manifest.layers {
+        if !blobs.iter().any(|b| b.digest == layer.digest) {
+            blobs.push(layer);
+        }
+    }
+    for blob in &blobs {
+        digest_hex(&blob.digest)?;
+    }
+    Ok(Parsed::Image(blobs))
+}
+
+/// 访问一个镜像仓库，收到 401 时按质询获取一次令牌后重试
+struct Registry<'a> {
+    image: &'a ImageReference,
+    transport: AwcTransport,
+    config: &'a Config,
+    token: Option<String>,
+}
+
+impl Registry<'_> {
+    /// 获取清单，返回内容和媒体类型
+    async fn manifest(&mut self, reference: &str) -> Result<(Vec<u8>, String), DownloadError> {
+        let url = format!("{}/manifests/{}", self.image.api_base(), reference);
+        loop {
+            let mut request = self
+                .transport
+                .clients
+                .get(&url)
+                .get(&url)
+                .timeout(std::time::Duration::from_secs(self.config.timeout))
+                .insert_header(("Accept", MANIFEST_MEDIA_TYPES))
+                .insert_header(("User-Agent", self.config.user_agent.as_str()));
+            if let Some(token) = &self.token {
+                request = request.bearer_auth(token);
+            }
+            let mut response =
+                request.send().await.map_err(|e| DownloadError::network_error(format!("请求镜像清单失败: {}", e)))?;
+            let status = response.status().as_u16();
+            if status == 401 && self.token.is_none() {
+                let challenge = response
+                    .headers()
+                    .get("WWW-Authenticate")
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(parse_challenge);
+                if let Some(challenge) = challenge {
+                    self.token = Some(self.fetch_token(&challenge).await?);
+                    continue;
+                }
+            }
+            if !response.status().is_success() {
+                return Err(DownloadError::HttpStatus { status, host: Some(self.image.registry.clone()), chunk: None });
+            }
+            let media_type = response
+                .headers()
+                .get("Content-Type")
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.split(';').next().unwrap_or_default().trim().to_string())
+                .unwrap_or_default();
+            let body = response
+                .body()
+                .limit(MAX_MANIFEST_SIZE)
+                .await
+                .map_err(|e| DownloadError::network_error(format!("读取镜像清单失败: {}", e)))?;
+            return Ok((body.to_vec(), media_type));
+        }
+    }
+
+    /// 按质询向认证服务获取拉取令牌，地址中有用户名和密码时按 Basic 认证发送
+    async fn fetch_token(&self, challenge: &[(String, String)]) -> Result<String, DownloadError> {
+        let param = |name: &str| challenge.iter().find(|(key, _)| key == name).map(|(_, value)| value.clone());
+        let realm = param("realm").unwrap_or_default();
+        let scope = param("scope").unwrap_or_else(|| format!("repository:{}:pull", self.image.repository));
+        let mut query = vec![("scope", scope)];
+        if let Some(service) = param("service") {
+            query.push(("service", service));
+        }
+        let url = url::Url::parse_with_params(&realm, &query)
+            .map_err(|e| DownloadError::invalid_url(format!("{} ({})", realm, e)))?;
+        let mut request = self
+            .transport
+            .clients
+            .get(url.as_str())
+            .get(url.as_str())
+            .timeout(std::time::Duration::from_secs(self.config.timeout))
+            .insert_header(("User-Agent", self.config.user_agent.as_str()));
+        if let Some((user, password)) = &self.image.credentials {
+            request = request.basic_auth(user, password);
+        }
+        let mut response =
+            request.send().await.map_err(|e| DownloadError::network_error(format!("获取镜像仓库令牌失败: {}", e)))?;
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            return Err(DownloadError::HttpStatus { status, host: url.host_str().map(str::to_string), chunk: None });
+        }
+        let body: serde_json::Value = response
+            .json()
+            .limit(MAX_MANIFEST_SIZE)
+            .await
+            .map_err(|e| DownloadError::network_error(format!("读取镜像仓库令牌失败: {}", e)))?;
+        body.get("token")
+            .or_else(|| body.get("access_token"))
+            .and_then(|token| token.as_str())
+            .map(str::to_string)
+            .ok_or_else(|| DownloadError::unknown("认证服务没有返回令牌"))
+    }
+}
+
+/// 获取镜像的清单并校验摘要，多平台镜像选出 `linux/<本机架构>` 的清单
+pub async fn resolve(image: &ImageReference, config: &Config) -> Result<Image, DownloadError> {
+    let mut registry = Registry { image, transport: AwcTransport::new(config), config, token: None };
+    let (mut body, mut media_type) = registry.manifest(&image.reference).await?;
+    let mut expected = image.reference.starts_with("sha256:").then(|| image.reference.clone());
+    loop {
+        let digest = sha256_digest(&body);
+        if let Some(expected) = &expected {
+            if &digest != expected {
+                return Err(DownloadError::unknown(format!("镜像清单摘要不匹配: 期望 {}，实际 {}", expected, digest)));
+            }
+        }
+        match parse_manifest(&body, "linux", host_architecture())? {
+            Parsed::Index(descriptor) => {
+                digest_hex(&descriptor.digest)?;
+                (body, media_type) = registry.manifest(&descriptor.digest).await?;
+                expected = Some(descriptor.digest);
+            }
+            Parsed::Image(blobs) => {
+                if media_type.is_empty() {
+                    let manifest: Manifest = serde_json::from_slice(&body).map_err(|e| DownloadError::unknown(e.to_string()))?;
+                    media_type = manifest.media_type.unwrap_or_else(|| "application/vnd.oci.image.manifest.v1+json".to_string());
+                }
+                let manifest = Descriptor { media_type, digest, size: body.len() as u64, platform: None };
+                return Ok(Image { manifest, manifest_bytes: body, blobs, token: registry.token });
+            }
+        }
+    }
+}
+
+/// 写出 OCI 镜像布局：`oci-layout`、指向清单的 `index.json` 和清单本身，配置和各层由下载任务写入 `blobs/sha256/`
+pub fn write_layout(dir: &Path, image: &ImageReference, resolved: &Image) -> Result<(), DownloadError> {
+    let blobs = dir.join("blobs").join("sha256");
+    std::fs::create_dir_all(&blobs).map_err(|e| DownloadError::io_error_with_context("创建镜像目录", e))?;
+    let write = |path: &Path, data: &[u8]| std::fs::write(path, data).map_err(|e| DownloadError::io_error_with_context("写入镜像布局", e));
+    write(&dir.join("oci-layout"), json!({ "imageLayoutVersion": "1.0.0" }).to_string().as_bytes())?;
+    write(&blobs.join(digest_hex(&resolved.manifest.digest)?), &resolved.manifest_bytes)?;
+    let mut manifest = serde_json::to_value(&resolved.manifest).map_err(|e| DownloadError::unknown(e.to_string()))?;
+    if !image.reference.starts_with("sha256:") {
+        manifest["annotations"] = json!({ "org.opencontainers.image.ref.name": image.reference });
+    }
+    let index = json!({ "schemaVersion": 2, "manifests": [manifest] });
+    let index = serde_json::to_string_pretty(&index).map_err(|e| DownloadError::unknown(e.to_string()))?;
+    write(&dir.join("index.json"), index.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEX: &str = "b1b6f0e4d3e7c5a2f9e8d7c6b5a4f3e2d1c0b9a8f7e6d5c4b3a2f1e0d9c8b7a6";
+
+    #[test]
+    fn test_parse_reference() {
+        let image = ImageReference::parse("docker://alpine").unwrap();
+        assert_eq!((image.registry.as_str(), image.repository.as_str(), image.reference.as_str()), (DOCKER_HUB, "library/alpine", "latest"));
+        assert_eq!(image.api_base(), "https://registry-1.docker.io/v2/library/alpine");
+        assert_eq!(image.layout_name(), "alpine-latest");
+
+        let image = ImageReference::parse(&format!("docker://docker.io/library/alpine@sha256:{}", HEX)).unwrap();
+        assert_eq!((image.registry.as_str(), image.reference.clone()), (DOCKER_HUB, format!("sha256:{}", HEX)));
+        assert_eq!(image.layout_name(), "alpine-sha256-b1b6f0e4d3e7");
+
+        let image = ImageReference::parse("docker://user:pw@localhost:5000/team/app:1.2").unwrap();
+        assert_eq!(image.credentials, Some(("user".to_string(), "pw".to_string())));
+        assert_eq!(image.api_base(), "http://localhost:5000/v2/team/app");
+        assert_eq!(image.reference, "1.2");
+
+        let image = ImageReference::parse("docker://ghcr.io/org/tool").unwrap();
+        assert_eq!((image.registry.as_str(), image.repository.as_str()), ("ghcr.io", "org/tool"));
+
+        assert!(ImageReference::parse("docker://alpine@sha256:abc").is_err());
+        assert!(ImageReference::parse("docker://ghcr.io/").is_err());
+        assert!(ImageReference::parse("https://ghcr.io/org/tool").is_err());
+        assert!(is_reference("DOCKER://alpine"));
+    }
+
+    #[test]
+    fn test_parse_challenge() {
+        let header = r#"Bearer realm="https://auth.docker.io/token",service="registry.docker.io",scope="repository:library/alpine:pull""#;
+        let challenge = parse_challenge(header).unwrap();
+        assert_eq!(challenge[0], ("realm".to_string(), "https://auth.docker.io/token".to_string()));
+        assert_eq!(challenge[2], ("scope".to_string(), "repository:library/alpine:pull".to_string()));
+        assert!(parse_challenge(r#"Basic realm="registry""#).is_none());
+    }
+
+    #[test]
+    fn test_parse_manifest_and_layout() {
+        let descriptor = |media: &str, hex: &str, size: u64| json!({ "mediaType": media, "digest": format!("sha256:{}", hex), "size": size });
+        let index = json!({
+            "schemaVersion": 2,
+            "manifests": [
+                { "mediaType": "application/vnd.oci.image.manifest.v1+json", "digest": format!("sha256:{}", "1".repeat(64)), "size": 1,
+                  "platform": { "os": "linux", "architecture": "arm64", "variant": "v8" } },
+                { "mediaType": "application/vnd.oci.image.manifest.v1+json", "digest": format!("sha256:{}", "2".repeat(64)), "size": 2,
+                  "platform": { "os": "linux", "architecture": "amd64" } },
+            ]
+        });
+        match parse_manifest(index.to_string().as_bytes(), "linux", "amd64").unwrap() {
+            Parsed::Index(d) => assert_eq!(d.digest, format!("sha256:{}", "2".repeat(64))),
+            Parsed::Image(_) => panic!("应选出平台清单"),
+        }
+        let error = parse_manifest(index.to_string().as_bytes(), "linux", "s390x").err().unwrap();
+        assert!(error.to_string().contains("linux/arm64, linux/amd64"));
+
+        // 重复的层只下载一次
+        let layer = descriptor("application/vnd.oci.image.layer.v1.tar+gzip", &"4".repeat(64), 40);
+        let manifest = json!({
+            "schemaVersion": 2,
+            "mediaType": "application/vnd.oci.image.manifest.v1+json",
+            "config": descriptor("application/vnd.oci.image.config.v1+json", &"3".repeat(64), 30),
+            "layers": [layer.clone(), layer],
+        });
+        let body = manifest.to_string().into_bytes();
+        let Parsed::Image(blobs) = parse_manifest(&body, "linux", "amd64").unwrap() else { panic!("应为单平台清单") };
+        assert_eq!(blobs.iter().map(|b| b.size).collect::<Vec<_>>(), vec![30, 40]);
+
+        let dir = std::env::temp_dir().join(format!("multidown_oci_{}", uuid::Uuid::new_v4()));
+        let image = ImageReference::parse("docker://localhost:5000/app:v1").unwrap();
+        let digest = sha256_digest(&body);
+        let manifest = Descriptor { media_type: "application/vnd.oci.image.manifest.v1+json".to_string(), digest, size: body.len() as u64, platform: None };
+        let resolved = Image { manifest: manifest.clone(), manifest_bytes: body.clone(), blobs, token: None };
+        write_layout(&dir, &image, &resolved).unwrap();
+        assert_eq!(std::fs::read(dir.join("blobs/sha256").join(digest_hex(&manifest.digest).unwrap())).unwrap(), body);
+        let index: serde_json::Value = serde_json::from_slice(&std::fs::read(dir.join("index.json")).unwrap()).unwrap();
+        assert_eq!(index["manifests"][0]["digest"], manifest.digest.as_str());
+        assert_eq!(index["manifests"][0]["annotations"]["org.opencontainers.image.ref.name"], "v1");
+        assert!(dir.join("oci-layout").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,128 @@
+//! 文件信息探测结果的缓存：会话目录中的 `probes.json`
+//!
+//! 开始下载前要先发 HEAD 获取文件大小、ETag 和是否支持 Range。重新运行同一批 URL、恢复暂停的任务时，
+//! 几百个 HEAD 请求常常比下载本身还慢。探测结果按 URL 缓存，与会话文件一起保存，
+//! `probe_cache_ttl` 秒内再次下载同一个 URL 时直接使用：
+//!
+//! - 缓存的 ETag 和 Last-Modified 照常用于校验断点续传信息和 If-Range，
+//!   文件在此期间发生变化时分块请求得不到 206，任务失败而不会拼出错误的文件；
+//! - 任务失败时删除它的缓存，重试时重新探测。
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::core::error::DownloadError;
+use crate::core::task::FileInfo;
+
+/// 缓存文件名，与会话文件放在一起
+pub const PROBE_CACHE_FILE: &str = "probes.json";
+
+/// 一个 URL 的探测结果
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct CachedProbe {
+    #[serde(flatten)]
+    info: FileInfo,
+    /// 探测时返回 403 后换用成功的 User-Agent，下载时沿用
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    user_agent: Option<String>,
+    probed_at: DateTime<Utc>,
+}
+
+impl CachedProbe {
+    fn is_fresh(&self, ttl: u64, now: DateTime<Utc>) -> bool {
+        ttl > 0 && now.signed_duration_since(self.probed_at).num_seconds() < ttl as i64
+    }
+}
+
+/// 探测结果缓存，由管理器加载并共享给所有任务
+#[derive(Debug, Default)]
+pub struct ProbeCache {
+    path: PathBuf,
+    entries: Mutex<HashMap<String, CachedProbe>>,
+    dirty: AtomicBool, // 有未写回文件的变化
+}
+
+impl ProbeCache {
+    /// 读取缓存文件，不存在或格式错误时从空缓存开始
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let entries = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+        Self { path, entries: Mutex::new(entries), dirty: AtomicBool::new(false) }
+    }
+
+    /// `ttl` 秒内探测过的结果和当时换用的 User-Agent，`ttl` 为 0 时不使用缓存
+    pub fn get(&self, url: &str, ttl: u64) -> Option<(FileInfo, Option<String>)> {
+        let entries = self.entries.lock().unwrap();
+        let probe = entries.get(url).filter(|probe| probe.is_fresh(ttl, Utc::now()))?;
+        Some((probe.info.clone(), probe.user_agent.clone()))
+    }
+
+    pub fn insert(&self, url: &str, info: &FileInfo, user_agent: Option<String>) {
+        let probe = CachedProbe { info: info.clone(), user_agent, probed_at: Utc::now() };
+        self.entries.lock().unwrap().insert(url.to_string(), probe);
+        self.dirty.store(true, Ordering::SeqCst);
+    }
+
+    pub fn remove(&self, url: &str) {
+        if self.entries.lock().unwrap().remove(url).is_some() {
+            self.dirty.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// 有变化时写回缓存文件，先去掉超过 `ttl` 秒的结果
+    pub fn save(&self, ttl: u64) -> Result<(), DownloadError> {
+        if !self.dirty.swap(false, Ordering::SeqCst) {
+            return Ok(());
+        }
+        let json = {
+            let mut entries = self.entries.lock().unwrap();
+            let now = Utc::now();
+            entries.retain(|_, probe| probe.is_fresh(ttl, now));
+            serde_json::to_string_pretty(&*entries).map_err(|e| DownloadError::unknown(format!("序列化失败: {}", e)))?
+        };
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| DownloadError::io_error_with_context("创建会话目录", e))?;
+        }
+        std::fs::write(&self.path, json).map_err(|e| DownloadError::io_error_with_context("写入探测缓存", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_cache_roundtrip_and_ttl() {
+        let path = std::env::temp_dir().join(format!("multidown_probes_{}.json", uuid::Uuid::new_v4()));
+        let info = FileInfo { size: 42, supports_range: true, last_modified: None, etag: Some("\"v1\"".to_string()) };
+        let cache = ProbeCache::load(&path);
+        cache.insert("http://example.com/a", &info, Some("Mozilla/5.0".to_string()));
+        cache.insert("http://example.com/b", &info, None);
+        cache.remove("http://example.com/b");
+        cache.save(3600).unwrap();
+
+        let cache = ProbeCache::load(&path);
+        let (cached, user_agent) = cache.get("http://example.com/a", 3600).unwrap();
+        assert_eq!((cached.size, cached.etag.as_deref()), (42, Some("\"v1\"")));
+        assert_eq!(user_agent.as_deref(), Some("Mozilla/5.0"));
+        assert!(cache.get("http://example.com/b", 3600).is_none());
+        // ttl 为 0 时不使用缓存
+        assert!(cache.get("http://example.com/a", 0).is_none());
+
+        // 过期的结果不使用，保存时去掉
+        cache.entries.lock().unwrap().get_mut("http://example.com/a").unwrap().probed_at = Utc::now() - chrono::Duration::hours(2);
+        assert!(cache.get("http://example.com/a", 3600).is_none());
+        cache.insert("http://example.com/c", &info, None);
+        cache.save(3600).unwrap();
+        let cache = ProbeCache::load(&path);
+        assert!(cache.entries.lock().unwrap().keys().eq(["http://example.com/c"]));
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,177 @@
+//! 导出和导入任务队列：`multidown export-queue queue.json`、`multidown import-queue queue.json`
+//!
+//! 把会话中未完成的任务（含任务选项和进度）写成一个 JSON 文件，可以拷贝到另一台机器继续下载，
+//! 或者放进运维手册里反复使用。保存路径按下载目录写成相对路径，导入时放到本机的下载目录下；
+//! 任务 ID 保持不变，连同临时目录中的分块一起拷贝过去时可以接着已下载的部分续传。
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use uuid::Uuid;
+
+use crate::core::actor_manager::DownloadTaskMeta;
+use crate::core::error::DownloadError;
+use crate::core::task::{TaskOptions, TaskStatus};
+
+/// 队列文件格式版本
+const VERSION: u32 = 1;
+
+/// 队列文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueFile {
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub tasks: Vec<QueuedTask>,
+}
+
+/// 队列中的一个任务
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueuedTask {
+    /// 原任务 ID，手写的队列可以省略
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Uuid>,
+    pub url: String,
+    /// 保存路径，相对于下载目录
+    pub file: String,
+    #[serde(default)]
+    pub options: TaskOptions,
+    /// 导出时已下载的字节数
+    #[serde(default)]
+    pub downloaded: u64,
+    /// 文件总大小，未知时为 0
+    #[serde(default)]
+    pub total: u64,
+    /// 任务标签
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+/// 导出会话中未完成（等待、下载中、暂停、失败）的任务，可以只导出带有 `tag` 标签的任务；
+/// `download_dir` 用于把保存路径转成相对路径
+pub fn export(metas: &[DownloadTaskMeta], download_dir: &str, tag: Option<&str>) -> QueueFile {
+    let mut metas: Vec<&DownloadTaskMeta> = metas
+        .iter()
+        .filter(|m| !matches!(m.status, TaskStatus::Completed | TaskStatus::Cancelled) && m.has_tag(tag))
+        .collect();
+    metas.sort_by(|a, b| a.started_at.cmp(&b.started_at).then_with(|| a.file.cmp(&b.file)));
+    QueueFile {
+        version: VERSION,
+        exported_at: Utc::now(),
+        tasks: metas
+            .into_iter()
+            .map(|m| QueuedTask {
+                id: Some(m.id),
+                url: m.url.clone(),
+                file: Path::new(&m.file)
+                    .strip_prefix(download_dir)
+                    .map_or_else(|_| m.file.clone(), |p| p.to_string_lossy().to_string()),
+                options: m.options.clone(),
+                downloaded: m.downloaded,
+                total: m.total,
+                tags: m.tags.clone(),
+            })
+            .collect(),
+    }
+}
+
+impl QueuedTask {
+    /// 本机上的保存路径
+    pub fn local_path(&self, download_dir: &str) -> String {
+        Path::new(download_dir).join(&self.file).to_string_lossy().to_string()
+    }
+
+    /// 与会话中的任务重复：ID 相同，或者 URL 和保存路径都相同且该任务还没有完成
+    pub fn is_duplicate(&self, file: &str, meta: &DownloadTaskMeta) -> bool {
+        self.id == Some(meta.id)
+            || meta.url == self.url && meta.file == file && !matches!(meta.status, TaskStatus::Completed | TaskStatus::Cancelled)
+    }
+}
+
+/// 读取队列文件
+pub fn read(path: &str) -> Result<QueueFile, DownloadError> {
+    let data = std::fs::read_to_string(path).map_err(|e| DownloadError::io_error_with_context("读取队列文件", e))?;
+    let queue: QueueFile = serde_json::from_str(&data)
+        .map_err(|e| DownloadError::Unknown(format!("队列文件格式错误: {}", e).into()))?;
+    if queue.version > VERSION {
+        return Err(DownloadError::Unknown(format!("不支持的队列文件版本: {}", queue.version).into()));
+    }
+    Ok(queue)
+}
+
+/// 写入队列文件
+pub fn write(path: &str, queue: &QueueFile) -> Result<(), DownloadError> {
+    let json = serde_json::to_string_pretty(queue).map_err(|e| DownloadError::Unknown(e.to_string().into()))?;
+    std::fs::write(path, json).map_err(|e| DownloadError::io_error_with_context("写入队列文件", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(file: &str, status: TaskStatus) -> DownloadTaskMeta {
+        DownloadTaskMeta {
+            id: Uuid::new_v4(),
+            url: format!("https://example.com/{}", file),
+            file: format!("downloads/{}", file),
+            status,
+            progress: 50.0,
+            downloaded: 512,
+            total: 1024,
+            speed: 0,
+            started_at: None,
+            finished_at: None,
+            retries: 0,
+            error_kind: None,
+            error_code: None,
+            http_status: None,
+            options: TaskOptions { thread_count: Some(2), ..Default::default() },
+            metrics: None,
+            tags: vec!["nightly".to_string()],
+            phase: None,
+            phase_progress: None,
+            client: None,
+        }
+    }
+
+    #[test]
+    fn test_export_and_roundtrip() {
+        let metas = vec![
+            meta("a.bin", TaskStatus::Paused),
+            meta("b.bin", TaskStatus::Completed),
+            meta("c.bin", TaskStatus::Failed("timeout".to_string())),
+        ];
+        let queue = export(&metas, "downloads", None);
+        let files: Vec<&str> = queue.tasks.iter().map(|t| t.file.as_str()).collect();
+        assert_eq!(files, vec!["a.bin", "c.bin"]);
+        assert_eq!(queue.tasks[0].tags, vec!["nightly"]);
+        assert_eq!(export(&metas, "downloads", Some("nightly")).tasks.len(), 2);
+        assert!(export(&metas, "downloads", Some("weekly")).tasks.is_empty());
+        assert_eq!(queue.tasks[0].id, Some(metas[0].id));
+        assert_eq!(queue.tasks[0].options.thread_count, Some(2));
+        assert_eq!((queue.tasks[0].downloaded, queue.tasks[0].total), (512, 1024));
+
+        let path = "test_queue.json";
+        write(path, &queue).unwrap();
+        let read_back = read(path).unwrap();
+        let _ = std::fs::remove_file(path);
+        assert_eq!(read_back.tasks, queue.tasks);
+
+        // 导入到另一个下载目录，按 URL 和路径识别重复的任务
+        let task = &read_back.tasks[0];
+        let local = task.local_path("/data/dl");
+        assert_eq!(Path::new(&local), Path::new("/data/dl/a.bin"));
+        assert!(task.is_duplicate(&local, &metas[0]));
+        let mut other = metas[0].clone();
+        other.id = Uuid::new_v4();
+        assert!(!task.is_duplicate(&local, &other));
+        other.file = local.clone();
+        assert!(task.is_duplicate(&local, &other));
+        other.status = TaskStatus::Completed;
+        assert!(!task.is_duplicate(&local, &other));
+
+        // 手写的队列只需要 URL 和路径
+        let minimal: QueuedTask = serde_json::from_str(r#"{"url":"https://example.com/d.bin","file":"d.bin"}"#).unwrap();
+        assert_eq!(minimal.id, None);
+        assert_eq!(minimal.options, TaskOptions::default());
+    }
+}
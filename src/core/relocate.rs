@@ -0,0 +1,199 @@
+//! 修改未完成任务的保存路径：`multidown move <任务ID> <新路径>`，库中发送 `MoveTask`
+//!
+//! 下载到一半磁盘快满时，把任务换到另一个位置继续，已下载的数据一起移动：
+//!
+//! - 已写出的部分输出文件（不分块的下载、续传的文件）和按文件顺序下载时的 `<文件>.part`；
+//! - 临时目录中的分块目录和协议处理器的部分文件（名称由保存路径得出，路径变了也要改名）、断点续传信息，
+//!   指定了新的临时目录时整个搬过去；
+//! - 断点续传信息中记录的保存路径改为新路径，先写临时文件再重命名，中途失败不会留下半个文件。
+//!
+//! 同一文件系统内用重命名，跨文件系统时复制后删除原文件。
+
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::core::error::DownloadError;
+use crate::core::task::chunk_manager::ChunkedDownloadManager;
+use crate::core::task::{protocol, resume};
+use crate::utils::validator;
+
+/// 一次移动：任务的保存路径和临时目录从哪里换到哪里
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relocation {
+    pub task_id: Uuid,
+    pub from: String,
+    pub to: String,
+    pub temp_from: PathBuf,
+    pub temp_to: PathBuf,
+}
+
+/// 新的保存路径：相对路径放在下载目录下，且不能逃出下载目录；绝对路径原样使用（可以换到其他磁盘）
+pub fn target_path(download_dir: &str, path: &str) -> Result<String, DownloadError> {
+    if Path::new(path).is_absolute() {
+        return Ok(path.to_string());
+    }
+    validator::output_path(download_dir, &Path::new(download_dir).join(path).to_string_lossy())
+}
+
+impl Relocation {
+    /// 移动任务的数据（阻塞，跨文件系统时较慢），调用前任务必须已经停止写入。
+    /// 先检查所有目标都不存在；中途失败时把已经移动的部分按相反顺序移回去，数据留在原处
+    pub fn run(&self) -> Result<(), DownloadError> {
+        if self.to != self.from && Path::new(&self.to).exists() {
+            return Err(DownloadError::file_exists(self.to.clone()));
+        }
+        let moves = self.moves();
+        if let Some((_, to)) = moves.iter().find(|(from, to)| from != to && from.exists() && to.exists()) {
+            return Err(DownloadError::file_exists(to.to_string_lossy().into_owned()));
+        }
+        let mut done = Vec::new();
+        let result = moves
+            .iter()
+            .try_for_each(|(from, to)| {
+                let existed = from != to && from.exists();
+                move_path(from, to)?;
+                if existed {
+                    done.push((from, to));
+                }
+                Ok(())
+            })
+            .and_then(|()| self.move_resume_info());
+        if result.is_err() {
+            for (from, to) in done.into_iter().rev() {
+                if let Err(e) = move_path(to, from) {
+                    tracing::error!(from = %to.display(), to = %from.display(), error = %e, "移动失败后移回任务数据失败");
+                }
+            }
+        }
+        result
+    }
+
+    /// 要移动的数据：输出文件、按顺序下载时的 `.part` 文件、分块目录和协议处理器的部分文件
+    fn moves(&self) -> [(PathBuf, PathBuf); 4] {
+        let chunks = |root: &Path, file: &str| root.join(validator::sanitize_file_name(file));
+        [
+            (PathBuf::from(&self.from), PathBuf::from(&self.to)),
+            (PathBuf::from(format!("{}.part", self.from)), PathBuf::from(format!("{}.part", self.to))),
+            (chunks(&self.temp_from, &self.from), chunks(&self.temp_to, &self.to)),
+            (protocol::partial_path(&self.temp_from, &self.from), protocol::partial_path(&self.temp_to, &self.to)),
+        ]
+    }
+
+    /// 断点续传信息写到新的临时目录，保存路径改为新路径，再删除原来的文件
+    fn move_resume_info(&self) -> Result<(), DownloadError> {
+        let Some(info) = ChunkedDownloadManager::read_resume_info(&self.temp_from, self.task_id) else {
+            return Ok(());
+        };
+        let mut info = info?;
+        info.file = self.to.clone();
+        std::fs::create_dir_all(&self.temp_to).map_err(|e| DownloadError::io_error_with_context("创建临时目录", e))?;
+        let path = self.temp_to.join(format!("resume_{}.bin", self.task_id));
+        resume::write(&path, &info)?;
+        for old in ChunkedDownloadManager::temp_paths(&self.temp_from, self.task_id, &self.from).iter().skip(1).take(2) {
+            if *old != path {
+                let _ = std::fs::remove_file(old);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 移动文件或目录，不存在时什么也不做
+fn move_path(from: &Path, to: &Path) -> Result<(), DownloadError> {
+    if from == to || !from.exists() {
+        return Ok(());
+    }
+    if to.exists() {
+        return Err(DownloadError::file_exists(to.to_string_lossy().into_owned()));
+    }
+    if let Some(parent) = to.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).map_err(|e| DownloadError::io_error_with_context("创建目标目录", e))?;
+    }
+    match std::fs::rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            copy_recursive(from, to)?;
+            let removed = if from.is_dir() { std::fs::remove_dir_all(from) } else { std::fs::remove_file(from) };
+            removed.map_err(|e| DownloadError::io_error_with_context("删除原来的数据", e))
+        }
+        Err(e) => Err(DownloadError::io_error_with_context("移动任务数据", e)),
+    }
+}
+
+fn copy_recursive(from: &Path, to: &Path) -> Result<(), DownloadError> {
+    let copy_error = |e| DownloadError::io_error_with_context("复制任务数据", e);
+    if !from.is_dir() {
+        return std::fs::copy(from, to).map(|_| ()).map_err(copy_error);
+    }
+    std::fs::create_dir_all(to).map_err(copy_error)?;
+    for entry in std::fs::read_dir(from).map_err(copy_error)? {
+        let entry = entry.map_err(copy_error)?;
+        copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::actor_manager::ResumeInfo;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_relocate_task_data() {
+        let root = std::env::temp_dir().join(format!("multidown_move_{}", Uuid::new_v4()));
+        let task_id = Uuid::new_v4();
+        let from = root.join("dl/a.bin").to_string_lossy().into_owned();
+        let to = root.join("other/disk/b.bin").to_string_lossy().into_owned();
+        let (temp_from, temp_to) = (root.join("dl/.multidown"), root.join("other/.tmp"));
+        let chunk_dir = temp_from.join(validator::sanitize_file_name(&from));
+        std::fs::create_dir_all(&chunk_dir).unwrap();
+        std::fs::write(chunk_dir.join("chunk_0000"), b"ab").unwrap();
+        std::fs::write(&from, b"partial").unwrap();
+        let info = ResumeInfo {
+            task_id,
+            url: "http://example.com/a.bin".to_string(),
+            file: from.clone(),
+            downloaded_chunks: vec![(0, 1)],
+            total_size: 4,
+            chunk_size: 2,
+            chunks: vec![(0, 1), (2, 3)],
+            checksums: HashMap::new(),
+            last_modified: None,
+            etag: None,
+        };
+        resume::write(&temp_from.join(format!("resume_{}.bin", task_id)), &info).unwrap();
+
+        let relocation = Relocation { task_id, from: from.clone(), to: to.clone(), temp_from: temp_from.clone(), temp_to: temp_to.clone() };
+        relocation.run().unwrap();
+        assert_eq!(std::fs::read(&to).unwrap(), b"partial");
+        assert!(!Path::new(&from).exists() && !chunk_dir.exists());
+        let moved = temp_to.join(validator::sanitize_file_name(&to)).join("chunk_0000");
+        assert_eq!(std::fs::read(moved).unwrap(), b"ab");
+        let info = ChunkedDownloadManager::read_resume_info(&temp_to, task_id).unwrap().unwrap();
+        assert_eq!((info.file.as_str(), info.downloaded_chunks), (to.as_str(), vec![(0, 1)]));
+        assert!(ChunkedDownloadManager::read_resume_info(&temp_from, task_id).is_none());
+
+        // 目标已存在时拒绝
+        std::fs::write(&from, b"x").unwrap();
+        assert!(matches!(relocation.run(), Err(DownloadError::FileExists(_))));
+
+        // 分块目录的目标已存在时在移动任何数据之前拒绝，输出文件留在原处
+        let back = Relocation { task_id, from: to.clone(), to: root.join("dl/c.bin").to_string_lossy().into_owned(), temp_from: temp_to.clone(), temp_to: temp_from.clone() };
+        std::fs::create_dir_all(temp_from.join(validator::sanitize_file_name(&back.to))).unwrap();
+        assert!(matches!(back.run(), Err(DownloadError::FileExists(_))));
+        assert_eq!(std::fs::read(&to).unwrap(), b"partial");
+        assert!(!Path::new(&back.to).exists());
+
+        // 续传信息读不出来时已经移动的数据移回原处
+        std::fs::remove_dir_all(temp_from.join(validator::sanitize_file_name(&back.to))).unwrap();
+        std::fs::write(temp_to.join(format!("resume_{}.bin", task_id)), b"broken").unwrap();
+        assert!(back.run().is_err());
+        assert_eq!(std::fs::read(&to).unwrap(), b"partial");
+        assert_eq!(std::fs::read(temp_to.join(validator::sanitize_file_name(&to)).join("chunk_0000")).unwrap(), b"ab");
+        assert!(!Path::new(&back.to).exists() && !temp_from.join(validator::sanitize_file_name(&back.to)).exists());
+        assert!(target_path("downloads", "../escape.bin").is_err());
+        assert_eq!(Path::new(&target_path("downloads", "sub/a.bin").unwrap()), Path::new("downloads/sub/a.bin"));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
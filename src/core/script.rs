@@ -0,0 +1,175 @@
+//! 脚本钩子：`script = "hooks.rhai"`
+//!
+//! 站点相关的逻辑（改写镜像地址、按路径加令牌、跳过不需要的文件、完成后归档）不适合写进程序，
+//! 可以写成 [Rhai](https://rhai.rs) 脚本。脚本在管理器启动时编译一次，定义了哪些函数就调用哪些：
+//!
+//! ```rhai
+//! // 创建任务时依次调用，返回 () 表示不修改
+//! fn rewrite_url(url) { url.replace("http://", "https://") }
+//! fn headers(url) { if url.contains("internal.example.com") { #{ "X-Team": "infra" } } }
+//! fn allow(url, file) { if file.ends_with(".tmp") { "不下载临时文件" } else { true } }
+//!
+//! // 任务结束时调用，task 包含 id、url、path、status、error
+//! fn on_complete(task) { print(`完成 ${task.path}`); }
+//! fn on_failure(task) { print(`失败 ${task.url}: ${task.error}`); }
+//! ```
+//!
+//! - `allow` 返回 `false` 或一段说明时拒绝创建任务；
+//! - 脚本中可以使用 `regex_match(text, pattern)`、`regex_replace(text, pattern, replacement)`，
+//!   `print` 的内容写入日志；
+//! - 脚本在管理器线程中执行，每次调用最多执行 [`MAX_OPERATIONS`] 步，出错时记录日志并当作没有修改，不影响下载。
+
+use rhai::{Dynamic, Engine, Map, Scope, AST};
+use std::path::Path;
+
+use crate::core::error::DownloadError;
+use crate::utils::hooks::HookContext;
+
+/// 每次调用脚本函数最多执行的操作数，避免死循环卡住管理器
+pub const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// 创建任务时脚本给出的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptedTask {
+    pub url: String,
+    /// 附加的请求头
+    pub headers: Vec<(String, String)>,
+}
+
+/// 编译好的脚本
+pub struct ScriptHooks {
+    engine: Engine,
+    ast: AST,
+}
+
+impl ScriptHooks {
+    /// 读取并编译脚本，`path` 为空时没有脚本
+    pub fn load(path: &str) -> Result<Option<Self>, DownloadError> {
+        if path.is_empty() {
+            return Ok(None);
+        }
+        let source = std::fs::read_to_string(Path::new(path))
+            .map_err(|e| DownloadError::io_error_with_context(format!("读取脚本 {}", path), e))?;
+        Self::compile(&source).map(Some).map_err(|e| DownloadError::unknown(format!("脚本 {} 无效: {}", path, e)))
+    }
+
+    fn compile(source: &str) -> Result<Self, String> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.on_print(|text| tracing::info!(target: "multidown::script", "{}", text));
+        engine.on_debug(|text, _, pos| tracing::debug!(target: "multidown::script", position = %pos, "{}", text));
+        engine.register_fn("regex_match", |text: &str, pattern: &str| match regex::Regex::new(pattern) {
+            Ok(re) => re.is_match(text),
+            Err(e) => {
+                tracing::warn!(pattern, error = %e, "脚本中的正则表达式无效");
+                false
+            }
+        });
+        engine.register_fn("regex_replace", |text: &str, pattern: &str, replacement: &str| match regex::Regex::new(pattern) {
+            Ok(re) => re.replace_all(text, replacement).into_owned(),
+            Err(e) => {
+                tracing::warn!(pattern, error = %e, "脚本中的正则表达式无效");
+                text.to_string()
+            }
+        });
+        let ast = engine.compile(source).map_err(|e| e.to_string())?;
+        Ok(Self { engine, ast })
+    }
+
+    fn defines(&self, name: &str, params: usize) -> bool {
+        self.ast.iter_functions().any(|f| f.name == name && f.params.len() == params)
+    }
+
+    /// 调用脚本中的函数，没有定义或出错时返回 None
+    fn call(&self, name: &str, args: impl rhai::FuncArgs) -> Option<Dynamic> {
+        match self.engine.call_fn::<Dynamic>(&mut Scope::new(), &self.ast, name, args) {
+            Ok(result) => Some(result),
+            Err(e) => {
+                tracing::warn!(function = name, error = %e, "脚本执行出错，忽略");
+                None
+            }
+        }
+    }
+
+    /// 创建任务前调用 `rewrite_url`、`allow`、`headers`，被 `allow` 拒绝时返回错误
+    pub fn before_task(&self, url: &str, file: &str) -> Result<ScriptedTask, DownloadError> {
+        let mut task = ScriptedTask { url: url.to_string(), headers: Vec::new() };
+        if self.defines("rewrite_url", 1) {
+            if let Some(rewritten) = self.call("rewrite_url", (url.to_string(),)).and_then(|r| r.into_string().ok()) {
+                if rewritten != url {
+                    tracing::info!(from = url, to = %rewritten, "脚本改写了下载地址");
+                }
+                task.url = rewritten;
+            }
+        }
+        if self.defines("allow", 2) {
+            let verdict = self.call("allow", (task.url.clone(), file.to_string())).unwrap_or(Dynamic::TRUE);
+            let reason = match verdict.as_bool() {
+                Ok(false) => Some("脚本拒绝了该任务".to_string()),
+                Ok(true) => None,
+                Err(_) => verdict.into_string().ok().map(|reason| format!("脚本拒绝了该任务: {}", reason)),
+            };
+            if let Some(reason) = reason {
+                return Err(DownloadError::unknown(reason));
+            }
+        }
+        if self.defines("headers", 1) {
+            if let Some(headers) = self.call("headers", (task.url.clone(),)).and_then(|r| r.try_cast::<Map>()) {
+                task.headers = headers.into_iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+            }
+        }
+        Ok(task)
+    }
+
+    /// 任务结束后调用 `on_complete` 或 `on_failure`
+    pub fn after_task(&self, id: &str, ctx: &HookContext) {
+        let name = if ctx.status == "completed" { "on_complete" } else { "on_failure" };
+        if !self.defines(name, 1) {
+            return;
+        }
+        let mut task = Map::new();
+        for (key, value) in [("id", id), ("url", &ctx.url), ("path", &ctx.path), ("status", &ctx.status), ("error", &ctx.error)] {
+            task.insert(key.into(), value.into());
+        }
+        self.call(name, (task,));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCRIPT: &str = r#"
+        fn rewrite_url(url) { regex_replace(url, "^http://mirror-\\d+\\.example\\.com/", "https://cdn.example.com/") }
+        fn allow(url, file) {
+            if file.ends_with(".tmp") { return "不下载临时文件"; }
+            !url.contains("blocked")
+        }
+        fn headers(url) { if regex_match(url, "cdn\\.example\\.com") { #{ "X-Team": "infra", "X-Retry": 3 } } }
+        fn on_complete(task) { loop {} }
+    "#;
+
+    #[test]
+    fn test_script_hooks() {
+        let hooks = ScriptHooks::compile(SCRIPT).unwrap();
+        let task = hooks.before_task("http://mirror-3.example.com/a.iso", "a.iso").unwrap();
+        assert_eq!(task.url, "https://cdn.example.com/a.iso");
+        assert_eq!(task.headers, vec![("X-Retry".to_string(), "3".to_string()), ("X-Team".to_string(), "infra".to_string())]);
+
+        // 没有改写的地址原样保留，headers 返回 () 时不加请求头
+        let task = hooks.before_task("https://example.com/b.zip", "b.zip").unwrap();
+        assert_eq!(task, ScriptedTask { url: "https://example.com/b.zip".to_string(), headers: vec![] });
+
+        // allow 返回 false 或说明时拒绝
+        assert!(hooks.before_task("https://example.com/blocked.zip", "blocked.zip").is_err());
+        let error = hooks.before_task("https://example.com/c", "c.tmp").unwrap_err().to_string();
+        assert!(error.contains("不下载临时文件"), "{}", error);
+
+        // 死循环在操作数用完后中止，不会卡住
+        let ctx = HookContext { path: "a.iso".into(), url: "u".into(), status: "completed".into(), error: String::new() };
+        hooks.after_task("1", &ctx);
+
+        assert!(ScriptHooks::compile("fn broken( {").is_err());
+        assert!(ScriptHooks::load("").unwrap().is_none());
+    }
+}
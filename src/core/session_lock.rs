@@ -0,0 +1,126 @@
+//! 会话锁：同一个会话目录同时只允许一个 multidown 进程下载
+//!
+//! 两个进程同时写会话目录中的 `tasks.json` 和相同的临时目录会互相破坏数据。
+//! 下载前对会话目录中的 `multidown.lock` 加操作系统级的文件锁，文件中记录持有者的 PID；
+//! 进程退出（包括崩溃）时锁由系统自动释放，残留的记录会被识别为过期锁并接管。
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::core::error::DownloadError;
+
+/// 锁文件名，与会话文件放在同一目录
+pub const LOCK_FILE: &str = "multidown.lock";
+
+/// 锁文件中记录的持有者
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LockOwner {
+    pub pid: u32,
+    pub started_at: DateTime<Utc>,
+}
+
+/// 持有中的会话锁，drop 时释放
+#[derive(Debug)]
+pub struct SessionLock {
+    file: File,
+    path: PathBuf,
+}
+
+/// 获取会话锁失败的原因
+#[derive(Debug)]
+pub enum LockError {
+    /// 另一个进程正在使用会话
+    Held(Option<LockOwner>),
+    /// 无法创建或写入锁文件
+    Io(DownloadError),
+}
+
+impl SessionLock {
+    /// 获取会话锁；文件系统不支持文件锁时记录警告并继续（不加锁）
+    pub fn acquire(path: impl AsRef<Path>) -> Result<Self, LockError> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| LockError::Io(DownloadError::io_error_with_context("创建会话目录", e)))?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(|e| LockError::Io(DownloadError::io_error_with_context("打开会话锁文件", e)))?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => return Err(LockError::Held(read_owner(&mut file))),
+            Err(TryLockError::Error(e)) => {
+                tracing::warn!(path = %path.display(), error = %e, "文件系统不支持文件锁，跳过会话锁");
+            }
+        }
+
+        // 拿到锁但文件里还有记录，说明上一个持有者没有正常退出
+        if let Some(stale) = read_owner(&mut file) {
+            tracing::warn!(pid = stale.pid, started_at = %stale.started_at, "发现过期的会话锁，已接管");
+        }
+        let owner = LockOwner { pid: std::process::id(), started_at: Utc::now() };
+        let json = serde_json::to_string(&owner)
+            .map_err(|e| LockError::Io(DownloadError::unknown(format!("序列化会话锁失败: {}", e))))?;
+        file.set_len(0)
+            .and_then(|()| file.seek(SeekFrom::Start(0)))
+            .and_then(|_| file.write_all(json.as_bytes()))
+            .and_then(|()| file.flush())
+            .map_err(|e| LockError::Io(DownloadError::io_error_with_context("写入会话锁文件", e)))?;
+        Ok(Self { file, path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for SessionLock {
+    fn drop(&mut self) {
+        // 正常退出时清空记录，下次启动就不会被当成过期锁；文件本身保留，避免删除与加锁之间的竞争
+        let _ = self.file.set_len(0);
+        let _ = self.file.unlock();
+    }
+}
+
+fn read_owner(file: &mut File) -> Option<LockOwner> {
+    let mut content = String::new();
+    file.seek(SeekFrom::Start(0)).ok()?;
+    file.read_to_string(&mut content).ok()?;
+    serde_json::from_str(content.trim()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_lock() {
+        let path = "test_session_lock/multidown.lock";
+        let _ = fs::remove_dir_all("test_session_lock");
+
+        let lock = SessionLock::acquire(path).unwrap();
+        match SessionLock::acquire(path) {
+            Err(LockError::Held(Some(owner))) => assert_eq!(owner.pid, std::process::id()),
+            other => panic!("第二次获取应该失败: {:?}", other),
+        }
+        drop(lock);
+        assert_eq!(fs::read_to_string(path).unwrap(), "");
+
+        // 模拟崩溃残留的记录：文件未加锁，可以直接接管
+        fs::write(path, r#"{"pid":1,"started_at":"2024-01-01T00:00:00Z"}"#).unwrap();
+        let lock = SessionLock::acquire(path).unwrap();
+        let owner: LockOwner = serde_json::from_str(&fs::read_to_string(lock.path()).unwrap()).unwrap();
+        assert_eq!(owner.pid, std::process::id());
+        drop(lock);
+
+        let _ = fs::remove_dir_all("test_session_lock");
+    }
+}
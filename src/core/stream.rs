@@ -0,0 +1,146 @@
+//! 管道模式：`multidown -O - <url> | tar x`
+//!
+//! 单连接按顺序把文件写到标准输出（或任意 `Write`），不经过任务管理器，不写会话和下载历史。
+//! 服务器支持 Range 请求且大小已知时，连接中断后从已写出的位置继续；否则中断即失败，
+//! 因为已经写出的数据无法收回。
+
+use futures::StreamExt;
+use std::io::Write;
+
+use crate::config::Config;
+use crate::core::error::DownloadError;
+use crate::core::task::handlers::get_file_info_with_fallback;
+use crate::core::task::retry::RetryContext;
+use crate::core::task::transport::{HttpRequest, HttpTransport, RequestSettings};
+use crate::core::task::util::SpeedLimiter;
+use crate::core::task::ByteRange;
+
+/// 下载 `url`（或其中的 `range` 部分）并按顺序写入 `out`，返回写出的字节数
+///
+/// `progress` 在每收到一段数据后调用，参数为已写出的字节数和总大小（未知时为 0）。
+#[allow(clippy::too_many_arguments)]
+pub async fn stream_to<W: Write>(
+    transport: &dyn HttpTransport,
+    url: &str,
+    settings: &mut RequestSettings,
+    config: &Config,
+    range: Option<ByteRange>,
+    out: &mut W,
+    mut progress: impl FnMut(u64, u64),
+) -> Result<u64, DownloadError> {
+    let info = get_file_info_with_fallback(transport, url, settings).await?;
+    let (offset, total) = match range {
+        Some(_) if !info.supports_range => {
+            return Err(DownloadError::unknown("服务器不支持 Range 请求，无法只下载部分内容"));
+        }
+        Some(range) => range.resolve(info.size).map(|(start, end)| (start, end - start))?,
+        None => (0, info.size),
+    };
+    if config.max_file_size > 0 && total > config.max_file_size {
+        return Err(DownloadError::SizeLimitExceeded { size: total, limit: config.max_file_size });
+    }
+    let resumable = info.supports_range && total > 0;
+    let mut limiter = SpeedLimiter::new(config.speed_limit_kb * 1024);
+    let mut retry = RetryContext::from_config(config).for_url(url);
+    let mut written = 0u64;
+
+    loop {
+        let result = async {
+            let mut request = HttpRequest::get(url, settings);
+            let partial = range.is_some() || written > 0;
+            if partial {
+                request = request.range(offset + written, offset + total - 1);
+            }
+            let mut response = transport.send(request).await?;
+            if !response.is_success() {
+                return Err(DownloadError::http_status(response.status, url));
+            }
+            if partial && response.status != 206 {
+                return Err(DownloadError::unknown("服务器没有按 Range 请求返回部分内容"));
+            }
+            while let Some(chunk) = response.body.next().await {
+                let bytes = chunk?;
+                let wait = limiter.wait_if_needed(bytes.len() as u64);
+                if !wait.is_zero() {
+                    tokio::time::sleep(wait).await;
+                }
+                // 服务器多发的数据不写出
+                let take = if total > 0 { (bytes.len() as u64).min(total - written) as usize } else { bytes.len() };
+                if config.max_file_size > 0 && written + take as u64 > config.max_file_size {
+                    return Err(DownloadError::SizeLimitExceeded { size: written + take as u64, limit: config.max_file_size });
+                }
+                out.write_all(&bytes[..take]).map_err(|e| DownloadError::io_error_with_context("写出数据失败", e))?;
+                written += take as u64;
+                progress(written, total);
+                if total > 0 && written == total {
+                    break;
+                }
+            }
+            if total > 0 && written < total {
+                return Err(DownloadError::network_error(format!("连接提前关闭: 已收到 {} / {} 字节", written, total)));
+            }
+            Ok(())
+        }
+        .await;
+
+        match result {
+            Ok(()) => break,
+            // 已经写出的数据无法收回，不能续传时只能失败
+            Err(e) if written > 0 && !resumable => return Err(e),
+            Err(e) => match retry.next_retry(&e) {
+                Some(delay) => {
+                    tracing::warn!(error = %e, written, delay_ms = delay.as_millis() as u64, "管道下载中断，准备重试");
+                    tokio::time::sleep(delay).await;
+                }
+                None => return Err(e),
+            },
+        }
+    }
+    out.flush().map_err(|e| DownloadError::io_error_with_context("写出数据失败", e))?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::task::{AwcTransport, TaskOptions};
+    use std::io::Read;
+
+    /// 按顺序对每个连接返回一个响应
+    fn serve(responses: Vec<&'static str>) -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf);
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        base
+    }
+
+    #[actix_rt::test]
+    async fn test_stream_resumes_in_order() {
+        // 第一次连接只发送了一部分，第二次从断开的位置继续
+        let base = serve(vec![
+            "HTTP/1.1 200 OK\r\nContent-Length: 10\r\nAccept-Ranges: bytes\r\nConnection: close\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Length: 10\r\nConnection: close\r\n\r\n01234",
+            "HTTP/1.1 206 Partial Content\r\nContent-Length: 5\r\nContent-Range: bytes 5-9/10\r\nConnection: close\r\n\r\n56789",
+        ]);
+        let config = Config { retry_delay: 0, ..Default::default() };
+        let mut settings = RequestSettings::new(&config, &TaskOptions::default());
+        let mut out = Vec::new();
+        let mut reported = 0;
+        let written = stream_to(&AwcTransport::new(&config), &format!("{}/a.bin", base), &mut settings, &config, None, &mut out, |n, total| {
+            assert_eq!(total, 10);
+            reported = n;
+        })
+        .await
+        .unwrap();
+        assert_eq!(written, 10);
+        assert_eq!(reported, 10);
+        assert_eq!(out, b"0123456789");
+    }
+}
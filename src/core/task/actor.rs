@@ -1,16 +1,28 @@
 use actix::prelude::*;
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{Instant, Duration};
 use uuid::Uuid;
-use tokio::sync::OwnedSemaphorePermit;
+use super::messages::DownloadPermit;
 
 use crate::config::Config;
 use crate::core::error::DownloadError;
-use super::chunk_manager::ChunkedDownloadManager;
-use super::state::TaskStatus;
+use crate::core::probe_cache::ProbeCache;
+use crate::core::usage::{UsageEntry, UsageStore, USAGE_FILE};
+use super::chunk_manager::{ChunkedDownloadManager, MIN_STEAL_SIZE};
+use super::download::{PROGRESS_REPORT_BYTES, PROGRESS_REPORT_INTERVAL};
+use super::transport::{AwcTransport, HttpTransport};
+use super::resolver::UrlResolvers;
+use super::protocol::ProtocolHandlers;
+use super::options::TaskOptions;
+use super::state::{TaskPhase, TaskStatus};
 use super::util::FileInfo;
-use super::util::SpeedLimiter;
+use super::tuning::{ThroughputController, TuningStore, TUNING_FILE};
+use super::util::{DownloadQuota, ProgressThrottle, SpeedLimiter, StopSignal};
+
+/// 流量统计写入间隔，进程被中断时最多丢失这段时间内的统计
+const USAGE_FLUSH_INTERVAL: Duration = Duration::from_secs(30);
 
 /// 单任务 Actor
 pub struct DownloadTaskActor {
@@ -18,39 +30,64 @@ pub struct DownloadTaskActor {
     pub url: String,
     pub file: String,
     pub progress: f32,
-    pub is_paused: Arc<AtomicBool>,
-    pub is_cancelled: Arc<AtomicBool>,
+    pub stop: StopSignal, // 暂停和取消信号，进行中的请求收到后立即中止
     pub status: TaskStatus,
     pub total_size: u64,
     pub downloaded: u64,
     pub speed: u64, // B/s
     pub start_time: Option<Instant>,
     pub manager_addr: Option<Addr<crate::core::actor_manager::DownloadManagerActor>>,
-    pub permit: Option<OwnedSemaphorePermit>,
+    pub permit: Option<DownloadPermit>,
     pub config: Config,
     pub chunk_manager: Option<ChunkedDownloadManager>,
     pub file_info: Option<FileInfo>,
-    pub global_limiter: Option<Arc<Mutex<SpeedLimiter>>>,
+    pub global_limiter: Arc<Mutex<SpeedLimiter>>, // 任务内所有连接共享，热重载时原地修改
+    pub options: TaskOptions,
+    pub span: tracing::Span, // 任务 span，任务和分块的日志都挂在它下面
+    pub transferred: Arc<AtomicU64>, // 实际收到但尚未写入流量统计的字节数（含重试）
+    pub synced: bool, // 完成后的文件是否已经 fsync 落盘
+    pub merging: bool, // 最终合并正在阻塞线程池中进行，暂停后恢复时不再重复合并
+    pub transport: Rc<dyn HttpTransport>, // 发送请求的 HTTP 后端，通常由管理器共享
+    pub protocols: Rc<ProtocolHandlers>, // 非 HTTP 协议（rsync:// 等）的处理器，通常由管理器共享
+    pub resolvers: Rc<UrlResolvers>, // 分享链接解析器，通常由管理器共享
+    pub download_url: Option<String>, // 分享链接解析出的直链，分块请求使用它，None 表示直接请求 url
+    pub progress_throttle: ProgressThrottle, // 分块完成时的进度上报节流
+    pub tuning: Option<ThroughputController>, // 自适应调整，未启用或未分块下载时为 None
+    pub quota: Arc<DownloadQuota>, // 本次运行的下载配额，由管理器共享
+    pub quota_reserved: Arc<AtomicU64>, // 本任务已预留的配额，失败或取消时归还
+    pub probes: Arc<ProbeCache>, // 文件信息探测结果的缓存，由管理器共享
+    pub deadline: Option<SpawnHandle>, // 总时间限制的定时器，开始下载时启动，暂停时停止
+    pub deadline_left: Option<Duration>, // 总时间限制剩余的时间，暂停时记下，None 表示还没开始计时
+    pub range_offset: u64, // 只下载部分内容时范围的起点，分块位置相对于它
+    pub scheduler: Option<SpawnHandle>, // 定期调度块下载的定时器，暂停时停止
+    pub bandwidth_share: u64, // 按总限速分到的带宽（B/s），0 表示没有总限速
 }
 
 impl Actor for DownloadTaskActor {
     type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(USAGE_FLUSH_INTERVAL, |act, _ctx| act.flush_usage());
+    }
+
+    fn stopping(&mut self, _ctx: &mut Self::Context) -> Running {
+        self.flush_usage();
+        Running::Stop
+    }
 }
 
 impl DownloadTaskActor {
-    pub fn new(config: Config, url: String, file: String) -> Self {
-        let global_limiter = if config.speed_limit_kb > 0 {
-            Some(Arc::new(Mutex::new(SpeedLimiter::new(config.speed_limit_kb * 1024))))
-        } else {
-            None
-        };
+    pub fn new(id: Uuid, config: Config, url: String, file: String) -> Self {
+        let global_limiter = Arc::new(Mutex::new(SpeedLimiter::new(config.speed_limit_kb * 1024)));
+        let span = tracing::info_span!("task", task_id = %id, url = %url);
+        let protocols = Rc::new(ProtocolHandlers::new(&config));
+        let resolvers = Rc::new(UrlResolvers::new(&config));
         Self {
-            id: Uuid::new_v4(),
+            id,
             url,
             file,
             progress: 0.0,
-            is_paused: Arc::new(AtomicBool::new(false)),
-            is_cancelled: Arc::new(AtomicBool::new(false)),
+            stop: StopSignal::default(),
             status: TaskStatus::Pending,
             total_size: 0,
             downloaded: 0,
@@ -62,32 +99,173 @@ impl DownloadTaskActor {
             chunk_manager: None,
             file_info: None,
             global_limiter,
+            options: TaskOptions::default(),
+            span,
+            transferred: Arc::new(AtomicU64::new(0)),
+            synced: false,
+            merging: false,
+            transport: Rc::new(AwcTransport::default()),
+            protocols,
+            resolvers,
+            download_url: None,
+            progress_throttle: ProgressThrottle::new(PROGRESS_REPORT_INTERVAL, PROGRESS_REPORT_BYTES),
+            tuning: None,
+            quota: Arc::new(DownloadQuota::default()),
+            quota_reserved: Arc::new(AtomicU64::new(0)),
+            probes: Arc::new(ProbeCache::default()),
+            deadline: None,
+            deadline_left: None,
+            range_offset: 0,
+            scheduler: None,
+            bandwidth_share: 0,
+        }
+    }
+
+    /// 设置任务选项（请求头、校验和等），限速和线程数应已合并进 `config`
+    pub fn with_options(mut self, options: TaskOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// 使用管理器共享的 HTTP 后端，与其他任务复用同一主机的连接
+    pub fn with_transport(mut self, transport: Rc<dyn HttpTransport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// 使用管理器共享的协议处理器
+    pub fn with_protocols(mut self, protocols: Rc<ProtocolHandlers>) -> Self {
+        self.protocols = protocols;
+        self
+    }
+
+    /// 使用管理器共享的分享链接解析器
+    pub fn with_resolvers(mut self, resolvers: Rc<UrlResolvers>) -> Self {
+        self.resolvers = resolvers;
+        self
+    }
+
+    /// 使用管理器共享的下载配额
+    pub fn with_quota(mut self, quota: Arc<DownloadQuota>) -> Self {
+        self.quota = quota;
+        self
+    }
+
+    /// 使用管理器共享的探测结果缓存
+    pub fn with_probe_cache(mut self, probes: Arc<ProbeCache>) -> Self {
+        self.probes = probes;
+        self
+    }
+
+    /// 实际生效的限速（B/s）：任务自己的限速和分到的总限速份额中较小的一个，0 表示不限速
+    pub fn speed_limit(&self) -> u64 {
+        let own = self.config.speed_limit_kb * 1024;
+        match (own, self.bandwidth_share) {
+            (0, share) | (share, 0) => share,
+            (own, share) => own.min(share),
+        }
+    }
+
+    /// 归还本任务预留的配额
+    pub fn release_quota(&self) {
+        self.quota.release(self.quota_reserved.swap(0, Ordering::SeqCst));
+    }
+
+    /// 按 `timeout_total` 启动总时间限制的定时器，恢复时从暂停前剩余的时间继续计时
+    pub fn arm_deadline(&mut self, ctx: &mut Context<Self>) {
+        if self.config.timeout_total == 0 || self.deadline.is_some() {
+            return;
+        }
+        let secs = self.config.timeout_total;
+        let left = *self.deadline_left.get_or_insert(Duration::from_secs(secs));
+        self.deadline = Some(ctx.run_later(left, move |act, ctx| {
+            if act.status != TaskStatus::Running {
+                return;
+            }
+            // 单线程下载收到取消信号后停止并删除不完整的文件；停止 Actor 会丢弃进行中的分块请求，
+            // 已完成的分块和续传信息保留，retry-failed 时继续
+            act.span.in_scope(|| tracing::warn!(timeout_total = secs, "超过总时间限制，停止下载"));
+            act.stop.cancel();
+            act.handle(super::messages::MarkFailed { error: DownloadError::DeadlineExceeded(secs) }, ctx);
+            ctx.stop();
+        }));
+    }
+
+    /// 暂停时停止总时间限制的定时器并记下剩余时间。
+    /// 暂停的任务不能直接转为失败，定时器留着的话会在暂停期间停止 Actor，恢复后任务一直排队
+    pub fn suspend_deadline(&mut self, ctx: &mut Context<Self>) {
+        let Some(handle) = self.deadline.take() else {
+            return;
+        };
+        ctx.cancel_future(handle);
+        if let (Some(left), Some(start)) = (self.deadline_left, self.start_time) {
+            self.deadline_left = Some(left.saturating_sub(start.elapsed()));
+        }
+    }
+
+    /// 把累计的流量写入流量统计
+    pub fn flush_usage(&self) {
+        let bytes = self.transferred.swap(0, Ordering::SeqCst);
+        if bytes == 0 {
+            return;
+        }
+        if let Err(e) = UsageStore::new(self.config.state_path(USAGE_FILE)).append(&UsageEntry::today(&self.url, bytes)) {
+            self.span.in_scope(|| tracing::warn!(error = %e, bytes, "写入流量统计失败"));
         }
     }
 
     pub fn notify_manager_progress(&self) {
         if let Some(manager_addr) = &self.manager_addr {
-            let _ = manager_addr.do_send(crate::core::actor_manager::UpdateTaskProgress {
+            manager_addr.do_send(crate::core::actor_manager::UpdateTaskProgress {
                 task_id: self.id,
                 progress: self.progress,
                 downloaded: self.downloaded,
                 total: self.total_size,
                 speed: self.speed,
+                chunks: self.chunk_manager.as_ref().map(|m| m.get_stats()),
             });
         }
     }
 
     pub fn notify_manager_completed(&self) {
+        self.flush_usage();
         if let Some(manager_addr) = &self.manager_addr {
-            let _ = manager_addr.do_send(crate::core::actor_manager::MarkTaskCompleted {
+            manager_addr.do_send(crate::core::actor_manager::MarkTaskCompleted {
                 task_id: self.id,
+                file_info: self.file_info.clone(),
+            });
+        }
+    }
+
+    pub fn notify_manager_retry(&self, error_kind: Option<&'static str>) {
+        if let Some(manager_addr) = &self.manager_addr {
+            manager_addr.do_send(crate::core::actor_manager::RecordTaskRetry {
+                task_id: self.id,
+                error_kind,
+            });
+        }
+    }
+
+    pub fn notify_manager_started(&self) {
+        if let Some(manager_addr) = &self.manager_addr {
+            manager_addr.do_send(crate::core::actor_manager::MarkTaskStarted { task_id: self.id });
+        }
+    }
+
+    pub fn notify_manager_chunk_failed(&self, chunk_index: usize, error: &DownloadError) {
+        if let Some(manager_addr) = &self.manager_addr {
+            manager_addr.do_send(crate::core::actor_manager::RecordChunkFailed {
+                task_id: self.id,
+                chunk_index,
+                error: error.to_string(),
             });
         }
     }
 
     pub fn notify_manager_failed(&self, error: DownloadError) {
+        self.flush_usage();
         if let Some(manager_addr) = &self.manager_addr {
-            let _ = manager_addr.do_send(crate::core::actor_manager::MarkTaskFailed {
+            manager_addr.do_send(crate::core::actor_manager::MarkTaskFailed {
                 task_id: self.id,
                 error,
             });
@@ -96,7 +274,7 @@ impl DownloadTaskActor {
     
     /// 启动所有可用的块下载
     pub fn start_available_chunks(&mut self, ctx: &mut Context<Self>, url: &str, file: &str, task_id: Uuid) {
-        if self.is_paused.load(std::sync::atomic::Ordering::SeqCst) {
+        if self.stop.is_paused() {
             return;
         }
         if let Some(chunk_manager) = &mut self.chunk_manager {
@@ -110,47 +288,116 @@ impl DownloadTaskActor {
                     task_id,
                 });
             }
+            // 没有剩下的块而还有空闲连接时，分割剩余最多的块交给空闲连接
+            while let Some(chunk_index) = chunk_manager.split_largest_active(MIN_STEAL_SIZE) {
+                let chunk = &chunk_manager.chunks[chunk_index];
+                self.span.in_scope(|| tracing::info!(chunk_index, start = chunk.start, end = chunk.end, "分割剩余最多的块"));
+                chunk_manager.active_chunks.lock().unwrap().push(chunk_index);
+                ctx.address().do_send(super::messages::DownloadChunkMsg {
+                    chunk_index,
+                    url: url.to_string(),
+                    file: file.to_string(),
+                    start: chunk.start,
+                    end: chunk.end,
+                    task_id,
+                });
+            }
         }
     }
 
     /// 定期调度块下载（处理失败和新块）
     pub fn schedule_chunk_downloads(&mut self, ctx: &mut Context<Self>, url: String, file: String, task_id: Uuid) {
-        ctx.run_interval(Duration::from_secs(1), move |act, ctx| {
+        if let Some(handle) = self.scheduler.take() {
+            ctx.cancel_future(handle);
+        }
+        self.scheduler = Some(ctx.run_interval(Duration::from_secs(1), move |act, ctx| {
             if act.status == TaskStatus::Running {
-                if act.is_paused.load(std::sync::atomic::Ordering::SeqCst) {
+                if act.stop.is_paused() {
                     return;
                 }
                 act.start_available_chunks(ctx, &url, &file, task_id);
                 act.check_download_status_and_retry(ctx);
             }
-        });
+        }));
     }
 
     /// 合并块并完成任务
-    pub fn merge_chunks_and_complete(&mut self) {
-        if let Some(chunk_manager) = &self.chunk_manager {
-            match chunk_manager.merge_chunks(&self.file) {
-                Ok(_) => {
-                    self.status = TaskStatus::Completed;
-                    println!("[actor_task] merge_chunks_and_complete: 任务已完成，通知 manager");
-                    self.notify_manager_completed();
-                },
+    ///
+    /// 合并在阻塞线程池中按 `finalize_speed_limit_kb` 限速进行，期间向管理器报告合并进度。
+    pub fn merge_chunks_and_complete(&mut self, ctx: &mut Context<Self>) {
+        let Some(chunk_manager) = &self.chunk_manager else { return };
+        if self.merging {
+            return;
+        }
+        self.merging = true;
+        let plan = chunk_manager.merge_plan(&self.file);
+        let limit = self.config.finalize_speed_limit_kb * 1024;
+        let progress = self.phase_reporter(TaskPhase::Merging);
+        let merge = async move {
+            tokio::task::spawn_blocking(move || plan.run(&mut SpeedLimiter::new(limit), progress)).await
+        }
+        .into_actor(self)
+        .map(|result, act, ctx| {
+            act.merging = false;
+            // 合并期间被取消时临时目录已经删除，结果不再有意义
+            if act.status == TaskStatus::Cancelled {
+                return;
+            }
+            let result = result
+                .map_err(|e| DownloadError::Unknown(format!("合并任务异常: {}", e).into()))
+                .and_then(|r| r);
+            match result {
+                Ok(()) => {
+                    if let Some(chunk_manager) = &act.chunk_manager {
+                        chunk_manager.remove_resume_info(act.id);
+                    }
+                    if let Some(tuning) = &act.tuning {
+                        if let Err(e) = TuningStore::new(act.config.state_path(TUNING_FILE)).put(&tuning.host, tuning.tuning) {
+                            act.span.in_scope(|| tracing::warn!(error = %e, "保存自适应调整结果失败"));
+                        }
+                    }
+                    ctx.address().do_send(super::messages::MarkCompleted);
+                }
                 Err(e) => {
-                    self.status = TaskStatus::Failed(e.to_string());
-                    self.notify_manager_failed(e);
+                    act.status = TaskStatus::Failed(e.to_string());
+                    act.notify_manager_failed(e);
                 }
             }
+        });
+        ctx.spawn(merge);
+    }
+
+    /// 收尾阶段的进度回调，可以在阻塞线程中调用，参数为已处理的字节数和总字节数；
+    /// 进度每变化 1% 通知一次管理器
+    pub fn phase_reporter(&self, phase: TaskPhase) -> impl FnMut(u64, u64) + Send + 'static {
+        let manager_addr = self.manager_addr.clone();
+        let task_id = self.id;
+        let mut last = None;
+        move |done, total| {
+            let percent = (done.min(total) * 100).checked_div(total).unwrap_or(0);
+            if last == Some(percent) {
+                return;
+            }
+            last = Some(percent);
+            if let Some(manager_addr) = &manager_addr {
+                manager_addr.do_send(crate::core::actor_manager::UpdateTaskPhase {
+                    task_id,
+                    phase,
+                    progress: percent as f32,
+                });
+            }
         }
     }
     
     /// 调度重试失败的块
     pub fn schedule_retry_failed_chunks(&mut self, ctx: &mut Context<Self>) {
         if let Some(chunk_manager) = &mut self.chunk_manager {
-            if chunk_manager.should_retry_failed_chunks() {
-                let delay = chunk_manager.retry_context.get_next_delay();
+            let (chunks, delay) = chunk_manager.get_failed_chunks_for_retry();
+            if !chunks.is_empty() {
                 ctx.run_later(delay, move |act, ctx| {
+                    act.notify_manager_retry(None);
                     if let Some(chunk_manager) = &mut act.chunk_manager {
-                        chunk_manager.retry_failed_chunks(ctx, &act.url, &act.file, act.id);
+                        chunk_manager.retry_failed_chunks(ctx, chunks, act.download_url.as_ref().unwrap_or(&act.url), &act.file, act.id);
                     }
                 });
             }
@@ -160,17 +407,17 @@ impl DownloadTaskActor {
     /// 检查下载状态并处理重试
     pub fn check_download_status_and_retry(&mut self, ctx: &mut Context<Self>) {
         if let Some(chunk_manager) = &mut self.chunk_manager {
-            let stats = chunk_manager.get_stats();
-            let should_retry = chunk_manager.should_retry_failed_chunks();
-            
-            if stats.failed_chunks == stats.total_chunks && !should_retry {
+            // 有块按重试规则不能再重试时文件无法完整下载，中止其余的块，任务失败
+            if let Some(error) = chunk_manager.unretryable_error().cloned() {
                 let retry_stats = chunk_manager.get_retry_stats();
-                println!("[chunked_download] 所有块都失败了，重试统计: {:?}", retry_stats);
-                self.notify_manager_failed(DownloadError::Unknown(std::borrow::Cow::Borrowed("所有块下载失败")));
+                self.span.in_scope(|| tracing::error!(error = %error, retry_stats = ?retry_stats, "分块下载失败且不能再重试"));
+                self.stop.cancel();
+                self.status = TaskStatus::Failed(error.to_string());
+                self.notify_manager_failed(error);
                 return;
             }
             
-            if stats.failed_chunks > 0 && should_retry {
+            if chunk_manager.should_retry_failed_chunks() {
                 self.schedule_retry_failed_chunks(ctx);
             }
         }
@@ -0,0 +1,160 @@
+//! 按主机熔断：`circuit_breaker_threshold`、`circuit_breaker_cooldown`
+//!
+//! 同一主机在所有任务中连续失败（连接失败、超时、5xx、408、429）达到阈值后，之后发往该主机的请求
+//! 先等待冷却时间结束再发出，避免排队的任务各自对着一台宕机的服务器把重试次数耗光。
+//! 冷却结束后放行的请求成功一次即恢复；再次失败时重新开始冷却。
+
+use async_trait::async_trait;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::core::error::{DownloadError, ErrorClass};
+use super::transport::{HttpRequest, HttpResponse, HttpTransport};
+use super::tuning::host_key;
+
+/// 一个主机的熔断状态
+#[derive(Debug, Default)]
+struct HostState {
+    /// 连续失败次数
+    failures: usize,
+    /// 冷却结束的时间
+    open_until: Option<Instant>,
+}
+
+/// 包装另一个 `HttpTransport`，按主机统计连续失败并在熔断期间推迟请求
+pub struct CircuitBreakerTransport {
+    inner: Rc<dyn HttpTransport>,
+    threshold: usize,
+    cooldown: Duration,
+    hosts: RefCell<HashMap<String, HostState>>,
+}
+
+impl CircuitBreakerTransport {
+    /// 按配置包装 `inner`，`circuit_breaker_threshold` 为 0 时原样返回
+    pub fn wrap(inner: Rc<dyn HttpTransport>, config: &Config) -> Rc<dyn HttpTransport> {
+        if config.circuit_breaker_threshold == 0 {
+            return inner;
+        }
+        Rc::new(Self {
+            inner,
+            threshold: config.circuit_breaker_threshold,
+            cooldown: Duration::from_secs(config.circuit_breaker_cooldown),
+            hosts: RefCell::new(HashMap::new()),
+        })
+    }
+
+    /// 主机还在冷却中时返回剩余的等待时间
+    fn remaining(&self, host: &str) -> Option<Duration> {
+        let hosts = self.hosts.borrow();
+        let open_until = hosts.get(host)?.open_until?;
+        open_until.checked_duration_since(Instant::now()).filter(|d| !d.is_zero())
+    }
+
+    fn record(&self, host: &str, failed: bool) {
+        let mut hosts = self.hosts.borrow_mut();
+        if !failed {
+            hosts.remove(host);
+            return;
+        }
+        let state = hosts.entry(host.to_string()).or_default();
+        state.failures += 1;
+        if state.failures >= self.threshold {
+            tracing::warn!(
+                host,
+                failures = state.failures,
+                cooldown_secs = self.cooldown.as_secs(),
+                "主机连续失败，暂停发往该主机的请求"
+            );
+            state.open_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+}
+
+/// 计入熔断的失败：主机不可用的迹象，而不是请求本身的问题
+fn is_host_failure(result: &Result<HttpResponse, DownloadError>) -> Option<bool> {
+    match result {
+        Ok(response) => Some(response.status >= 500 || matches!(response.status, 408 | 429)),
+        Err(e) => match e.retry_class() {
+            Some(ErrorClass::Network | ErrorClass::Timeout | ErrorClass::Server) => Some(true),
+            // 本地错误等与主机无关，不影响计数
+            _ => None,
+        },
+    }
+}
+
+#[async_trait(?Send)]
+impl HttpTransport for CircuitBreakerTransport {
+    async fn send(&self, request: HttpRequest<'_>) -> Result<HttpResponse, DownloadError> {
+        let host = host_key(request.url);
+        // 等待期间其他请求可能再次触发熔断，醒来后重新检查
+        while let Some(wait) = self.remaining(&host) {
+            tracing::debug!(host = %host, wait_ms = wait.as_millis() as u64, "主机熔断中，等待冷却结束");
+            tokio::time::sleep(wait).await;
+        }
+        let result = self.inner.send(request).await;
+        if let Some(failed) = is_host_failure(&result) {
+            self.record(&host, failed);
+        }
+        result
+    }
+
+    fn prefetch(&self, url: &str) {
+        self.inner.prefetch(url);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::task::transport::RequestSettings;
+    use crate::core::task::TaskOptions;
+    use futures::StreamExt;
+    use std::cell::Cell;
+
+    /// 返回固定状态码，记录收到的请求数
+    struct StatusTransport {
+        status: Cell<u16>,
+        calls: Cell<usize>,
+    }
+
+    #[async_trait(?Send)]
+    impl HttpTransport for StatusTransport {
+        async fn send(&self, _request: HttpRequest<'_>) -> Result<HttpResponse, DownloadError> {
+            self.calls.set(self.calls.get() + 1);
+            Ok(HttpResponse { status: self.status.get(), headers: Vec::new(), body: futures::stream::empty().boxed_local() })
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_opens_after_consecutive_failures() {
+        let inner = Rc::new(StatusTransport { status: Cell::new(503), calls: Cell::new(0) });
+        let config = Config { circuit_breaker_threshold: 2, circuit_breaker_cooldown: 3600, ..Default::default() };
+        let transport = CircuitBreakerTransport::wrap(inner.clone(), &config);
+        let settings = RequestSettings::new(&config, &TaskOptions::default());
+
+        // 中间有一次成功时重新计数
+        transport.send(HttpRequest::get("http://down.example.com/a", &settings)).await.unwrap();
+        inner.status.set(200);
+        transport.send(HttpRequest::get("http://down.example.com/a", &settings)).await.unwrap();
+        inner.status.set(503);
+        for _ in 0..2 {
+            transport.send(HttpRequest::get("http://down.example.com/a", &settings)).await.unwrap();
+        }
+        // 熔断后发往该主机的请求等待冷却，其他主机不受影响
+        let blocked = tokio::time::timeout(
+            Duration::from_millis(50),
+            transport.send(HttpRequest::get("http://down.example.com/b", &settings)),
+        );
+        assert!(blocked.await.is_err());
+        transport.send(HttpRequest::get("http://up.example.com/a", &settings)).await.unwrap();
+        assert_eq!(inner.calls.get(), 5);
+
+        // 阈值为 0 时不包装
+        let disabled = CircuitBreakerTransport::wrap(inner.clone(), &Config { circuit_breaker_threshold: 0, ..config });
+        disabled.send(HttpRequest::get("http://down.example.com/c", &settings)).await.unwrap();
+        assert_eq!(inner.calls.get(), 6);
+    }
+}
@@ -0,0 +1,83 @@
+//! 从校验和文件读取期望的 SHA-256：`checksum_url` 配置（通常写在 URL 规则里）给出校验和文件的地址，
+//! `{url}` 替换为下载地址，例如 `{url}.sha256`，或发行版镜像的 `https://mirror.example.org/SHA256SUMS`
+//!
+//! 文件可以只有一个校验和（`<sha256>` 或 `<sha256>  <文件名>`），也可以是 `sha256sum` 输出的多行列表，
+//! 多行时取文件名与下载地址最后一段相同的一行。
+
+use futures::StreamExt;
+use url::Url;
+
+use super::transport::{HttpRequest, HttpTransport, RequestSettings};
+use crate::core::error::DownloadError;
+use crate::utils::validator;
+
+/// 校验和文件的最大长度，超过时只读取开头
+const MAX_CHECKSUM_FILE_SIZE: usize = 1 << 20;
+
+/// 按模板得出 `url` 对应的校验和文件地址
+pub fn checksum_url(template: &str, url: &str) -> String {
+    template.replace("{url}", url)
+}
+
+/// 在校验和文件的内容中查找 `file_name` 的 SHA-256
+pub fn parse(text: &str, file_name: &str) -> Option<String> {
+    let entries: Vec<(&str, Option<&str>)> = text
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let hash = fields.next().filter(|hash| hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit()))?;
+            // sha256sum 的二进制模式在文件名前加 `*`
+            Some((hash, fields.next().map(|name| name.trim_start_matches('*'))))
+        })
+        .collect();
+    let matched = entries.iter().find(|(_, name)| name.is_some_and(|name| name.rsplit('/').next() == Some(file_name)));
+    match (matched, entries.as_slice()) {
+        // 只有一个校验和时不要求文件名相同
+        (Some((hash, _)), _) | (None, [(hash, _)]) => Some(hash.to_ascii_lowercase()),
+        _ => None,
+    }
+}
+
+/// 下载校验和文件并取出 `url` 的 SHA-256
+pub async fn fetch(
+    transport: &dyn HttpTransport,
+    template: &str,
+    url: &str,
+    settings: &RequestSettings,
+) -> Result<String, DownloadError> {
+    let checksum_url = checksum_url(template, url);
+    let mut response = transport.send(HttpRequest::get(&checksum_url, settings)).await?;
+    if !response.is_success() {
+        let host = Url::parse(&checksum_url).ok().and_then(|u| u.host_str().map(str::to_string));
+        return Err(DownloadError::HttpStatus { status: response.status, host, chunk: None });
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.body.next().await {
+        body.extend_from_slice(&chunk?);
+        if body.len() >= MAX_CHECKSUM_FILE_SIZE {
+            break;
+        }
+    }
+    let file_name = Url::parse(url).map(|u| validator::file_name_from_url(&u)).unwrap_or_default();
+    parse(&String::from_utf8_lossy(&body), &file_name)
+        .ok_or_else(|| DownloadError::unknown(format!("校验和文件 {} 中没有 {} 的 SHA-256", checksum_url, file_name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_checksum_file() {
+        let a = "a".repeat(64);
+        let b = "B".repeat(64);
+        assert_eq!(checksum_url("{url}.sha256", "https://example.com/x.iso"), "https://example.com/x.iso.sha256");
+
+        assert_eq!(parse(&format!("{}\n", a), "x.iso"), Some(a.clone()));
+        assert_eq!(parse(&format!("{}  other.iso\n", a), "x.iso"), Some(a.clone()));
+        let sums = format!("{}  other.iso\n{} *images/x.iso\n", a, b);
+        assert_eq!(parse(&sums, "x.iso"), Some(b.to_ascii_lowercase()));
+        assert_eq!(parse(&sums, "missing.iso"), None);
+        assert_eq!(parse("not a checksum", "x.iso"), None);
+    }
+}
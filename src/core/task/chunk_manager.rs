@@ -1,3 +1,7 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::io::{Seek, SeekFrom};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use uuid::Uuid;
@@ -7,7 +11,8 @@ use std::borrow::Cow;
 use crate::core::error::DownloadError;
 use crate::core::actor_manager::ResumeInfo;
 use super::retry::{RetryContext, RetryStats};
-use super::util::FileInfo;
+use super::resume;
+use super::util::{append_file, append_file_limited, ChunkChecksum, FileInfo, SpeedLimiter};
 
 use actix::{Context, AsyncContext};
 use super::actor::DownloadTaskActor;
@@ -22,6 +27,38 @@ pub struct DownloadChunk {
     pub completed: bool,
 }
 
+/// 剩余字节数不到这个值两倍的块不再分割
+pub const MIN_STEAL_SIZE: u64 = 1024 * 1024;
+
+/// 正在下载的块的实时进度，由下载循环更新
+///
+/// 分割时直接调低 `end`，下载循环写到新的结束位置就停止，剩下的部分交给新的块。
+#[derive(Debug)]
+pub struct ChunkProgress {
+    pub start: u64,
+    pub end: AtomicU64,
+    pub written: AtomicU64,
+}
+
+impl ChunkProgress {
+    /// 还没有写入的字节数
+    pub fn remaining(&self) -> u64 {
+        (self.end.load(Ordering::SeqCst) + 1).saturating_sub(self.start + self.written.load(Ordering::SeqCst))
+    }
+}
+
+/// 块分布图的格数，每格对应文件中等长的一段
+pub const CHUNK_MAP_CELLS: usize = 48;
+
+/// 块分布图中一格的状态，一格覆盖多个块时显示其中最需要关注的状态（失败 > 下载中 > 等待 > 完成）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ChunkState {
+    Done,
+    Pending,
+    Active,
+    Failed,
+}
+
 /// 分块下载统计信息
 #[derive(Debug, Clone)]
 pub struct ChunkDownloadStats {
@@ -31,6 +68,8 @@ pub struct ChunkDownloadStats {
     pub failed_chunks: usize,
     pub pending_chunks: usize,
     pub progress: f32,
+    /// 按文件位置划分的块分布图，共 `CHUNK_MAP_CELLS` 格
+    pub map: Vec<ChunkState>,
 }
 
 /// 分块下载管理器
@@ -38,18 +77,30 @@ pub struct ChunkDownloadStats {
 pub struct ChunkedDownloadManager {
     pub chunks: Vec<DownloadChunk>,
     pub total_size: u64,
+    pub chunk_size: u64,
     pub temp_dir: String,
+    pub temp_root: PathBuf, // 临时目录根（配置中的 temp_dir），断点续传信息也放在这里
     pub file_name: String,
     pub active_chunks: Arc<Mutex<Vec<usize>>>,
     pub completed_chunks: Arc<Mutex<Vec<usize>>>,
     pub failed_chunks: Arc<Mutex<Vec<usize>>>,
+    chunk_errors: HashMap<usize, DownloadError>, // 失败块最后一次的错误，已安排重试的块不在其中
+    pub live: HashMap<usize, Arc<ChunkProgress>>, // 正在下载的块的实时进度
     pub max_concurrent_chunks: usize,
     pub retry_context: RetryContext,
+    pub sequential: bool, // 按文件顺序下载（`--stream-order`），已完成的开头部分边下载边写入预览文件
+    pub merged: u64, // 已经写入合并文件的开头部分的字节数
+    pub append_output: bool, // 续传已有的部分文件：合并结果追加到输出文件末尾
+    pub checksums: HashMap<u64, ChunkChecksum>, // 已完成块的校验值，按块的起点索引
+    pub resumed: bool, // 分块边界来自续传信息，临时目录中的块文件与之对应
+    resume_layout: Option<usize>, // 续传信息文件头对应的块数，分块边界变化后需要重写整个文件
+    resume_saved: HashSet<usize>, // 已经写入续传信息的已完成块
 }
 
 impl ChunkedDownloadManager {
-    pub fn new(total_size: u64, chunk_size: u64, file_name: String) -> Self {
-        let num_chunks = ((total_size + chunk_size - 1) / chunk_size) as usize;
+    pub fn new(total_size: u64, chunk_size: u64, file_name: String, temp_root: impl Into<PathBuf>) -> Self {
+        let temp_root = temp_root.into();
+        let num_chunks = total_size.div_ceil(chunk_size) as usize;
         let mut chunks = Vec::new();
         
         for i in 0..num_chunks {
@@ -69,19 +120,53 @@ impl ChunkedDownloadManager {
         }
         
         // 使用文件名作为临时目录名，避免路径过长
-        let temp_dir = format!("downloads/temp/{}", file_name.replace("/", "_").replace("\\", "_"));
+        let temp_dir = temp_root
+            .join(crate::utils::validator::sanitize_file_name(&file_name))
+            .to_string_lossy()
+            .into_owned();
         std::fs::create_dir_all(&temp_dir).ok();
         
         Self {
             chunks,
             total_size,
+            chunk_size,
             temp_dir,
+            temp_root,
             file_name,
             active_chunks: Arc::new(Mutex::new(Vec::new())),
             completed_chunks: Arc::new(Mutex::new(Vec::new())),
             failed_chunks: Arc::new(Mutex::new(Vec::new())),
+            chunk_errors: HashMap::new(),
+            live: HashMap::new(),
             max_concurrent_chunks: 3, // 默认最大并发块数
             retry_context: RetryContext::new(3, Duration::from_secs(1), Duration::from_secs(60)),
+            sequential: false,
+            merged: 0,
+            append_output: false,
+            checksums: HashMap::new(),
+            resumed: false,
+            resume_layout: None,
+            resume_saved: HashSet::new(),
+        }
+    }
+
+    /// 按文件顺序下载：只在播放位置之后的窗口内开始新块，分割时优先帮助最前面的块，
+    /// 已完成的开头部分写入 `<输出文件>.part`，可以边下载边用播放器打开
+    pub fn set_sequential(&mut self, sequential: bool) {
+        self.sequential = sequential;
+    }
+
+    /// 第一个未完成的字节的位置（按文件顺序下载时的“播放位置”）
+    pub fn first_incomplete_byte(&self) -> u64 {
+        self.chunks.iter().filter(|c| !c.completed).map(|c| c.start).min().unwrap_or(self.total_size)
+    }
+
+    /// 合并文件的路径：按文件顺序下载时放在输出文件旁边供预览，否则放在临时目录中
+    pub fn merged_path(&self, output_path: &str) -> String {
+        if self.sequential {
+            format!("{}.part", output_path)
+        } else {
+            format!("{}/merged.part", self.temp_dir)
         }
     }
     
@@ -106,6 +191,13 @@ impl ChunkedDownloadManager {
                 available_indices.push(i);
             }
         }
+        // 按文件顺序下载时，离播放位置太远的块先不开始，连接留给前面的块
+        if self.sequential {
+            let window = self.chunk_size * self.max_concurrent_chunks as u64 * 2;
+            let head = self.first_incomplete_byte();
+            available_indices.sort_by_key(|&i| self.chunks[i].start);
+            available_indices.retain(|&i| self.chunks[i].start < head.saturating_add(window));
+        }
         
         // 如果有可用块，返回第一个
         if let Some(&chunk_index) = available_indices.first() {
@@ -120,6 +212,52 @@ impl ChunkedDownloadManager {
         None
     }
     
+    /// 开始下载一个块（包括重试），返回与下载循环共享的实时进度
+    pub fn begin_chunk(&mut self, chunk_index: usize) -> Option<Arc<ChunkProgress>> {
+        let chunk = self.chunks.get(chunk_index)?;
+        let progress = Arc::new(ChunkProgress {
+            start: chunk.start,
+            end: AtomicU64::new(chunk.end),
+            written: AtomicU64::new(0),
+        });
+        self.live.insert(chunk_index, progress.clone());
+        Some(progress)
+    }
+
+    /// 没有等待下载的块时，把剩余最多的活跃块的后一半分给一个新块（aria2 式分割）
+    ///
+    /// 避免大文件最后只剩一个慢连接在下载。新块追加在末尾，返回它的索引；
+    /// 合并按起始位置排序，分块边界随断点续传信息一起保存。
+    pub fn split_largest_active(&mut self, min_size: u64) -> Option<usize> {
+        let has_pending = self.chunks.iter().enumerate()
+            .any(|(i, c)| !c.completed && !self.is_chunk_active(i) && !self.is_chunk_failed(i));
+        if has_pending || self.active_chunks.lock().unwrap().len() >= self.max_concurrent_chunks {
+            return None;
+        }
+        let active = self.live.iter().filter(|(i, _)| self.is_chunk_active(**i));
+        // 按文件顺序下载时分割最前面的块，让播放位置尽快前进
+        let (index, progress) = if self.sequential {
+            active
+                .filter(|(_, p)| p.remaining() >= min_size * 2)
+                .min_by_key(|(_, p)| p.start)
+                .map(|(i, p)| (*i, p.clone()))?
+        } else {
+            active.max_by_key(|(_, p)| p.remaining()).map(|(i, p)| (*i, p.clone()))?
+        };
+        let remaining = progress.remaining();
+        if remaining < min_size * 2 {
+            return None;
+        }
+        let end = progress.end.load(Ordering::SeqCst);
+        let split_at = end + 1 - remaining / 2;
+        progress.end.store(split_at - 1, Ordering::SeqCst);
+        self.chunks[index].end = split_at - 1;
+        self.chunks.push(DownloadChunk { start: split_at, end, downloaded: 0, completed: false });
+        // 同一索引的块文件可能是上次分割留下的（对应别的位置），不能接着写
+        let _ = std::fs::remove_file(self.get_chunk_file_path(self.chunks.len() - 1));
+        Some(self.chunks.len() - 1)
+    }
+
     /// 检查块是否正在下载
     pub fn is_chunk_active(&self, chunk_index: usize) -> bool {
         self.active_chunks.lock().unwrap().contains(&chunk_index)
@@ -141,6 +279,7 @@ impl ChunkedDownloadManager {
         if let Ok(mut active) = self.active_chunks.lock() {
             active.retain(|&x| x != chunk_index);
         }
+        self.live.remove(&chunk_index);
         
         // 添加到完成列表
         if let Ok(mut completed) = self.completed_chunks.lock() {
@@ -151,14 +290,58 @@ impl ChunkedDownloadManager {
         if let Ok(mut failed) = self.failed_chunks.lock() {
             failed.retain(|&x| x != chunk_index);
         }
+        self.chunk_errors.remove(&chunk_index);
     }
     
-    /// 标记块为失败
-    pub fn mark_chunk_failed(&mut self, chunk_index: usize) {
+    /// 记录块下载完成时写入内容的校验值，保存在断点续传信息中
+    pub fn record_checksum(&mut self, chunk_index: usize, checksum: ChunkChecksum) {
+        if let Some(chunk) = self.chunks.get(chunk_index) {
+            self.checksums.insert(chunk.start, checksum);
+        }
+    }
+
+    /// 需要校验的已完成块：（块索引，块文件路径，大小，记录的校验值）
+    pub fn completed_chunk_files(&self) -> Vec<(usize, String, u64, Option<ChunkChecksum>)> {
+        self.chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.completed)
+            .map(|(i, c)| (i, self.get_chunk_file_path(i), c.end - c.start + 1, self.checksums.get(&c.start).cloned()))
+            .collect()
+    }
+
+    /// 把已完成的块重新标记为等待下载（块文件损坏时）
+    pub fn reset_chunk(&mut self, chunk_index: usize) {
+        let Some(chunk) = self.chunks.get_mut(chunk_index) else { return };
+        chunk.completed = false;
+        chunk.downloaded = 0;
+        self.checksums.remove(&chunk.start);
+        self.resume_layout = None;
+        if let Ok(mut completed) = self.completed_chunks.lock() {
+            completed.retain(|&x| x != chunk_index);
+        }
+        let _ = std::fs::remove_file(self.get_chunk_file_path(chunk_index));
+    }
+
+    /// 暂停或取消时把正在下载的块放回等待状态（不计为失败），记下已写入块文件的字节数
+    pub fn release_chunk(&mut self, chunk_index: usize) {
+        if let Ok(mut active) = self.active_chunks.lock() {
+            active.retain(|&x| x != chunk_index);
+        }
+        if let Some(progress) = self.live.remove(&chunk_index) {
+            if let Some(chunk) = self.chunks.get_mut(chunk_index) {
+                chunk.downloaded = progress.written.load(Ordering::SeqCst);
+            }
+        }
+    }
+
+    /// 标记块为失败，记录失败原因，之后按它决定能否重试
+    pub fn mark_chunk_failed(&mut self, chunk_index: usize, error: DownloadError) {
         // 从活跃列表中移除
         if let Ok(mut active) = self.active_chunks.lock() {
             active.retain(|&x| x != chunk_index);
         }
+        self.live.remove(&chunk_index);
         
         // 添加到失败列表
         if let Ok(mut failed) = self.failed_chunks.lock() {
@@ -166,20 +349,36 @@ impl ChunkedDownloadManager {
                 failed.push(chunk_index);
             }
         }
+        self.chunk_errors.insert(chunk_index, error);
     }
     
-    /// 获取失败的重试块
-    pub fn get_failed_chunks_for_retry(&mut self) -> Vec<usize> {
-        let failed_chunks = self.failed_chunks.lock().unwrap().clone();
+    /// 为可以重试的失败块记录一次重试，返回这些块和需要等待的时间；
+    /// 块在重新下载前仍留在失败列表中，不会被提前分配
+    pub fn get_failed_chunks_for_retry(&mut self) -> (Vec<usize>, Duration) {
+        let mut indices: Vec<usize> = self.chunk_errors.keys().copied().collect();
+        indices.sort_unstable();
         let mut retry_chunks = Vec::new();
+        let mut delay = Duration::ZERO;
         
-        for chunk_index in failed_chunks {
-            if self.retry_context.should_retry(&DownloadError::Unknown(Cow::Borrowed("chunk_download_failed"))) {
+        for chunk_index in indices {
+            if let Some(wait) = self.retry_context.next_retry(&self.chunk_errors[&chunk_index]) {
+                self.chunk_errors.remove(&chunk_index);
                 retry_chunks.push(chunk_index);
+                delay = delay.max(wait);
             }
         }
         
-        retry_chunks
+        (retry_chunks, delay)
+    }
+
+    /// 按重试规则已经不能再重试的失败块的错误
+    pub fn unretryable_error(&self) -> Option<&DownloadError> {
+        let mut indices: Vec<&usize> = self.chunk_errors.keys().collect();
+        indices.sort_unstable();
+        indices
+            .into_iter()
+            .map(|i| &self.chunk_errors[i])
+            .find(|error| !self.retry_context.should_retry(error))
     }
     
     /// 更新块下载进度
@@ -219,30 +418,125 @@ impl ChunkedDownloadManager {
             failed_chunks: failed_count,
             pending_chunks: pending_count,
             progress: self.get_total_progress(),
+            map: self.chunk_map(CHUNK_MAP_CELLS),
         }
     }
+
+    /// 把文件等分成 `cells` 格，标出每格中块的状态；下载中的块已写入的部分算作完成
+    pub fn chunk_map(&self, cells: usize) -> Vec<ChunkState> {
+        let mut map = vec![ChunkState::Done; cells];
+        if self.total_size == 0 || cells == 0 {
+            return map;
+        }
+        let cell_of = |pos: u64| ((pos as u128 * cells as u128 / self.total_size as u128) as usize).min(cells - 1);
+        let mut mark = |start: u64, end: u64, state: ChunkState| {
+            if start > end {
+                return;
+            }
+            for cell in &mut map[cell_of(start)..=cell_of(end)] {
+                *cell = (*cell).max(state);
+            }
+        };
+        for (i, chunk) in self.chunks.iter().enumerate() {
+            if chunk.completed {
+                continue;
+            }
+            if self.is_chunk_failed(i) {
+                mark(chunk.start, chunk.end, ChunkState::Failed);
+            } else if self.is_chunk_active(i) {
+                let written = self.live.get(&i).map_or(0, |p| p.written.load(Ordering::SeqCst));
+                mark(chunk.start + written, chunk.end, ChunkState::Active);
+            } else {
+                mark(chunk.start + chunk.downloaded, chunk.end, ChunkState::Pending);
+            }
+        }
+        map
+    }
     
     pub fn get_chunk_file_path(&self, chunk_index: usize) -> String {
         format!("{}/chunk_{:04}", self.temp_dir, chunk_index)
     }
     
-    pub fn merge_chunks(&self, output_path: &str) -> Result<(), DownloadError> {
-        let mut output_file = std::fs::File::create(output_path)
-            .map_err(|e| DownloadError::IoError(e.to_string().into()))?;
-        
-        for (i, _chunk) in self.chunks.iter().enumerate() {
+    /// 断点续传信息文件的路径
+    pub fn resume_info_path(&self, task_id: Uuid) -> PathBuf {
+        self.temp_root.join(format!("resume_{}.bin", task_id))
+    }
+
+    /// 读取断点续传信息，兼容旧版本保存的 `resume_<任务ID>.json`；都不存在时返回 `None`
+    pub(crate) fn read_resume_info(temp_root: &Path, task_id: Uuid) -> Option<Result<ResumeInfo, DownloadError>> {
+        if let Ok(bytes) = std::fs::read(temp_root.join(format!("resume_{}.bin", task_id))) {
+            return Some(resume::decode(&bytes));
+        }
+        let content = std::fs::read_to_string(temp_root.join(format!("resume_{}.json", task_id))).ok()?;
+        Some(serde_json::from_str(&content).map_err(|e| DownloadError::Unknown(format!("反序列化失败: {}", e).into())))
+    }
+
+    /// 把已完成的开头部分（从上次合并到的位置起连续完成的块）追加到合并文件，返回合并到的位置
+    pub fn merge_ready_prefix(&mut self, merged_path: &str) -> Result<u64, DownloadError> {
+        let mut output_file = std::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(self.merged == 0)
+            .open(merged_path)
+            .map_err(|e| DownloadError::io_error_with_context("打开合并文件", e))?;
+        output_file
+            .seek(SeekFrom::Start(self.merged))
+            .map_err(|e| DownloadError::io_error_with_context("定位合并文件", e))?;
+
+        // 分割出的块追加在末尾，按起始位置合并
+        let mut order: Vec<usize> = (0..self.chunks.len()).collect();
+        order.sort_by_key(|&i| self.chunks[i].start);
+        for i in order {
+            let chunk = &self.chunks[i];
+            if chunk.end < self.merged {
+                continue;
+            }
+            if !chunk.completed || chunk.start != self.merged {
+                break;
+            }
             let chunk_path = self.get_chunk_file_path(i);
             if let Ok(mut chunk_file) = std::fs::File::open(&chunk_path) {
-                std::io::copy(&mut chunk_file, &mut output_file)
-                    .map_err(|e| DownloadError::IoError(e.to_string().into()))?;
+                self.merged += append_file(&mut chunk_file, &mut output_file, self.merged)
+                    .map_err(|e| DownloadError::io_error_with_context("合并块文件", e))?;
             } else {
                 return Err(DownloadError::Unknown(format!("无法打开块文件: {}", chunk_path).into()));
             }
         }
-        
-        // 清理临时文件
-        self.cleanup_temp_files();
-        Ok(())
+        Ok(self.merged)
+    }
+
+    /// 合并成完整文件后移动到目标位置（同一文件系统时只是一次重命名），
+    /// 续传已有的部分文件时追加到它的末尾
+    pub fn merge_chunks(&mut self, output_path: &str) -> Result<(), DownloadError> {
+        self.merge_plan(output_path).run(&mut SpeedLimiter::new(0), |_, _| {})
+    }
+
+    /// 最终合并要做的事情，交给 [`MergePlan::run`] 在阻塞线程中执行
+    pub fn merge_plan(&self, output_path: &str) -> MergePlan {
+        let mut order: Vec<usize> = (0..self.chunks.len()).collect();
+        order.sort_by_key(|&i| self.chunks[i].start);
+        let mut next = self.merged;
+        let mut chunk_files = Vec::new();
+        for i in order {
+            let chunk = &self.chunks[i];
+            if chunk.end < self.merged {
+                continue;
+            }
+            if !chunk.completed || chunk.start != next {
+                break;
+            }
+            chunk_files.push(self.get_chunk_file_path(i));
+            next = chunk.end + 1;
+        }
+        MergePlan {
+            merged_path: self.merged_path(output_path),
+            output_path: output_path.to_string(),
+            chunk_files,
+            merged: self.merged,
+            total_size: self.total_size,
+            append_output: self.append_output,
+            temp_dir: self.temp_dir.clone(),
+        }
     }
     
     pub fn cleanup_temp_files(&self) {
@@ -251,7 +545,27 @@ impl ChunkedDownloadManager {
         }
     }
     
-    pub fn save_resume_info(&self, task_id: Uuid, url: &str, file_info: &FileInfo) -> Result<(), DownloadError> {
+    /// 保存断点续传信息：分块边界没有变化时只追加新完成的块，否则重写整个文件
+    pub fn save_resume_info(&mut self, task_id: Uuid, url: &str, file_info: &FileInfo) -> Result<(), DownloadError> {
+        let path = self.resume_info_path(task_id);
+        if self.resume_layout == Some(self.chunks.len()) && path.exists() {
+            let new: Vec<usize> = (0..self.chunks.len())
+                .filter(|i| self.chunks[*i].completed && !self.resume_saved.contains(i))
+                .collect();
+            if new.is_empty() {
+                return Ok(());
+            }
+            let records: Vec<_> = new.iter()
+                .map(|&i| {
+                    let chunk = &self.chunks[i];
+                    resume::encode_record(chunk.start, chunk.end, self.checksums.get(&chunk.start))
+                })
+                .collect();
+            resume::append(&path, &records)?;
+            self.resume_saved.extend(new);
+            return Ok(());
+        }
+
         let resume_info = ResumeInfo {
             task_id,
             url: url.to_string(),
@@ -262,28 +576,28 @@ impl ChunkedDownloadManager {
                 .map(|(_, chunk)| (chunk.start, chunk.end))
                 .collect(),
             total_size: self.total_size,
+            chunk_size: self.chunk_size,
+            chunks: self.chunks.iter().map(|c| (c.start, c.end)).collect(),
+            checksums: self.chunks.iter()
+                .filter(|c| c.completed)
+                .filter_map(|c| self.checksums.get(&c.start).map(|d| (c.start, d.clone())))
+                .collect(),
             last_modified: file_info.last_modified.clone(),
             etag: file_info.etag.clone(),
         };
         
-        let path = format!("downloads/resume_{}.json", task_id);
-        let json = serde_json::to_string_pretty(&resume_info)
-            .map_err(|e| DownloadError::Unknown(format!("序列化失败: {}", e).into()))?;
-        
-        std::fs::write(path, json)
-            .map_err(|e| DownloadError::IoError(e.to_string().into()))?;
+        resume::write(&path, &resume_info)?;
+        let _ = std::fs::remove_file(self.temp_root.join(format!("resume_{}.json", task_id)));
+        self.resume_layout = Some(self.chunks.len());
+        self.resume_saved = (0..self.chunks.len()).filter(|&i| self.chunks[i].completed).collect();
         Ok(())
     }
     
     pub fn load_and_validate_resume_info(&mut self, task_id: Uuid, current_file_info: &FileInfo) -> Result<(), DownloadError> {
-        let path = format!("downloads/resume_{}.json", task_id);
-        let content = match std::fs::read_to_string(&path) {
-            Ok(c) => c,
-            Err(_) => return Ok(()), // No resume file, not an error, just continue fresh.
+        let resume_info = match Self::read_resume_info(&self.temp_root, task_id) {
+            Some(info) => info?,
+            None => return Ok(()), // No resume file, not an error, just continue fresh.
         };
-        
-        let resume_info: ResumeInfo = serde_json::from_str(&content)
-            .map_err(|e| DownloadError::Unknown(format!("反序列化失败: {}", e).into()))?;
 
         // --- VALIDATION LOGIC ---
         // 1. ETag check (primary)
@@ -311,11 +625,21 @@ impl ChunkedDownloadManager {
         }
 
         // --- RESTORE STATE ---
+        self.resumed = true;
+        // 分割过的块边界与按分块大小切出的不同，先恢复上次的分块边界，块文件按索引对应
+        if !resume_info.chunks.is_empty() {
+            self.chunks = resume_info.chunks.iter()
+                .map(|&(start, end)| DownloadChunk { start, end, downloaded: 0, completed: false })
+                .collect();
+        }
         for (start, end) in &resume_info.downloaded_chunks {
             if let Some(chunk) = self.chunks.iter_mut()
                 .find(|c| c.start == *start && c.end == *end) {
                 chunk.completed = true;
                 chunk.downloaded = end - start + 1;
+                if let Some(checksum) = resume_info.checksums.get(start) {
+                    self.checksums.insert(*start, checksum.clone());
+                }
                 
                 // 添加到完成列表
                 if let Ok(mut completed) = self.completed_chunks.lock() {
@@ -329,11 +653,32 @@ impl ChunkedDownloadManager {
         Ok(())
     }
     
-    /// 重试失败的块
-    pub fn retry_failed_chunks(&mut self, ctx: &mut Context<DownloadTaskActor>, url: &str, file: &str, task_id: Uuid) {
-        let failed_chunks = self.get_failed_chunks_for_retry();
-        
-        for chunk_index in failed_chunks {
+    /// 上次运行保存的分块大小，恢复时必须使用同样的分块边界
+    pub fn saved_chunk_size(temp_root: &Path, task_id: Uuid) -> Option<u64> {
+        let info = Self::read_resume_info(temp_root, task_id)?.ok()?;
+        Some(info.chunk_size).filter(|size| *size > 0)
+    }
+
+    /// 任务在磁盘上的临时数据：分块目录、断点续传信息，以及按文件顺序下载时输出文件旁边的 `.part`
+    pub fn temp_paths(temp_root: &Path, task_id: Uuid, output_path: &str) -> Vec<PathBuf> {
+        vec![
+            temp_root.join(crate::utils::validator::sanitize_file_name(output_path)),
+            temp_root.join(format!("resume_{}.bin", task_id)),
+            temp_root.join(format!("resume_{}.json", task_id)),
+            PathBuf::from(format!("{}.part", output_path)),
+            super::protocol::partial_path(temp_root, output_path),
+        ]
+    }
+
+    /// 删除断点续传信息（任务完成或取消后）
+    pub fn remove_resume_info(&self, task_id: Uuid) {
+        let _ = std::fs::remove_file(self.resume_info_path(task_id));
+        let _ = std::fs::remove_file(self.temp_root.join(format!("resume_{}.json", task_id)));
+    }
+
+    /// 重新下载 [`Self::get_failed_chunks_for_retry`] 选出的块
+    pub fn retry_failed_chunks(&mut self, ctx: &mut Context<DownloadTaskActor>, chunks: Vec<usize>, url: &str, file: &str, task_id: Uuid) {
+        for chunk_index in chunks {
             if let Some(chunk) = self.chunks.get(chunk_index) {
                 // 从失败列表中移除
                 if let Ok(mut failed) = self.failed_chunks.lock() {
@@ -353,10 +698,9 @@ impl ChunkedDownloadManager {
         }
     }
     
-    /// 检查是否需要重试
+    /// 是否有失败块可以按重试规则重试
     pub fn should_retry_failed_chunks(&self) -> bool {
-        let failed_count = self.failed_chunks.lock().unwrap().len();
-        failed_count > 0 && !self.retry_context.is_max_retries_reached()
+        self.chunk_errors.values().any(|error| self.retry_context.should_retry(error))
     }
     
     /// 获取重试统计信息
@@ -371,8 +715,328 @@ impl ChunkedDownloadManager {
     /// 重置重试状态
     pub fn reset_retry_state(&mut self) {
         self.retry_context.reset();
+        self.chunk_errors.clear();
         if let Ok(mut failed) = self.failed_chunks.lock() {
             failed.clear();
         }
     }
-} 
\ No newline at end of file
+}
+
+/// 检查已完成的块文件：大小一致，且记录了校验值时 CRC32 一致（阻塞，读取整个块文件）
+pub fn verify_chunk_file(path: &str, size: u64, checksum: Option<&ChunkChecksum>) -> bool {
+    if std::fs::metadata(path).map(|m| m.len()).ok() != Some(size) {
+        return false;
+    }
+    let Some(checksum) = checksum else {
+        return true;
+    };
+    let crc32 = std::fs::File::open(path).and_then(|mut file| {
+        let mut hasher = crc32fast::Hasher::new();
+        let mut buf = vec![0u8; 1024 * 1024];
+        loop {
+            let n = std::io::Read::read(&mut file, &mut buf)?;
+            if n == 0 {
+                return Ok(hasher.finalize());
+            }
+            hasher.update(&buf[..n]);
+        }
+    });
+    crc32.is_ok_and(|crc32| crc32 == checksum.crc32)
+}
+
+/// 最终合并：把还没写入合并文件的块依次追加进去，再移动到输出位置并清理临时目录
+///
+/// 不借用分块管理器，可以放到阻塞线程池中执行；读写速度由调用方的限速器控制，
+/// 避免大文件合并时占满磁盘带宽、拖慢其他正在下载的任务。
+#[derive(Debug, Clone)]
+pub struct MergePlan {
+    merged_path: String,
+    output_path: String,
+    /// 按文件中的位置排列的块文件
+    chunk_files: Vec<String>,
+    /// 合并文件中已有的字节数
+    merged: u64,
+    total_size: u64,
+    append_output: bool,
+    temp_dir: String,
+}
+
+impl MergePlan {
+    /// 执行合并，`progress(已合并的字节数, 总大小)` 在开始时和每写入一段后调用
+    pub fn run(self, limiter: &mut SpeedLimiter, mut progress: impl FnMut(u64, u64)) -> Result<(), DownloadError> {
+        let mut output_file = std::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(self.merged == 0)
+            .open(&self.merged_path)
+            .map_err(|e| DownloadError::io_error_with_context("打开合并文件", e))?;
+        output_file
+            .seek(SeekFrom::Start(self.merged))
+            .map_err(|e| DownloadError::io_error_with_context("定位合并文件", e))?;
+        let mut merged = self.merged;
+        progress(merged, self.total_size);
+        for chunk_path in &self.chunk_files {
+            let mut chunk_file = std::fs::File::open(chunk_path)
+                .map_err(|_| DownloadError::Unknown(format!("无法打开块文件: {}", chunk_path).into()))?;
+            append_file_limited(&mut chunk_file, &mut output_file, merged, limiter, |n| {
+                merged += n;
+                progress(merged, self.total_size);
+            })
+            .map_err(|e| DownloadError::io_error_with_context("合并块文件", e))?;
+        }
+        drop(output_file);
+        if merged != self.total_size {
+            return Err(DownloadError::SizeMismatch { expected: self.total_size, actual: merged });
+        }
+        if self.append_output {
+            let mut src = std::fs::File::open(&self.merged_path).map_err(|e| DownloadError::io_error_with_context("打开合并后的文件", e))?;
+            let mut dst = std::fs::OpenOptions::new().append(true).open(&self.output_path).map_err(|e| DownloadError::io_error_with_context("打开部分文件", e))?;
+            let offset = dst.metadata().map_err(|e| DownloadError::io_error_with_context("读取部分文件", e))?.len();
+            append_file_limited(&mut src, &mut dst, offset, limiter, |_| {})
+                .map_err(|e| DownloadError::io_error_with_context("追加到部分文件", e))?;
+            let _ = std::fs::remove_file(&self.merged_path);
+        } else {
+            move_file(Path::new(&self.merged_path), Path::new(&self.output_path))?;
+        }
+
+        // 清理临时文件
+        let _ = std::fs::remove_dir_all(&self.temp_dir);
+        Ok(())
+    }
+}
+
+/// 移动文件，跨文件系统时退回到复制后删除
+pub(crate) fn move_file(from: &Path, to: &Path) -> Result<(), DownloadError> {
+    if let Some(parent) = to.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| DownloadError::io_error_with_context("创建目标目录", e))?;
+    }
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    std::fs::copy(from, to).map_err(|e| DownloadError::io_error_with_context("复制合并后的文件", e))?;
+    let _ = std::fs::remove_file(from);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_largest_active() {
+        let root = std::env::temp_dir().join(format!("multidown_split_{}", Uuid::new_v4()));
+        let mb = MIN_STEAL_SIZE;
+        let mut cm = ChunkedDownloadManager::new(8 * mb, 4 * mb, "file.bin".to_string(), &root);
+        cm.set_max_concurrent_chunks(3);
+
+        // 还有等待下载的块时不分割
+        let (first, _) = cm.get_next_available_chunk().unwrap();
+        let fast = cm.begin_chunk(first).unwrap();
+        assert_eq!(cm.split_largest_active(mb), None);
+
+        let (second, _) = cm.get_next_available_chunk().unwrap();
+        let slow = cm.begin_chunk(second).unwrap();
+        fast.written.store(3 * mb, Ordering::SeqCst);
+        slow.written.store(mb, Ordering::SeqCst);
+
+        // 分割剩余最多的块（第二块还剩 3MB），后一半交给新块
+        let new = cm.split_largest_active(mb).unwrap();
+        assert_eq!(new, 2);
+        let split_at = 4 * mb + mb + 3 * mb / 2;
+        assert_eq!(cm.chunks[second].end, split_at - 1);
+        assert_eq!(slow.end.load(Ordering::SeqCst), split_at - 1);
+        assert_eq!((cm.chunks[new].start, cm.chunks[new].end), (split_at, 8 * mb - 1));
+
+        // 新块开始下载后达到并发上限；剩余不足两倍最小值时也不再分割
+        cm.active_chunks.lock().unwrap().push(new);
+        assert_eq!(cm.split_largest_active(mb), None);
+        cm.set_max_concurrent_chunks(4);
+        slow.written.store(2 * mb, Ordering::SeqCst);
+        fast.written.store(4 * mb - 1, Ordering::SeqCst);
+        assert_eq!(cm.split_largest_active(mb), None);
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_sequential_order() {
+        let root = std::env::temp_dir().join(format!("multidown_seq_{}", Uuid::new_v4()));
+        let mut cm = ChunkedDownloadManager::new(20, 2, "file.bin".to_string(), &root);
+        cm.set_max_concurrent_chunks(2);
+        cm.set_sequential(true);
+        let data: Vec<u8> = (b'a'..=b't').collect();
+        let finish = |cm: &mut ChunkedDownloadManager, i: usize| {
+            let start = cm.chunks[i].start as usize;
+            std::fs::write(cm.get_chunk_file_path(i), &data[start..start + 2]).unwrap();
+            cm.mark_chunk_completed(i);
+        };
+
+        // 窗口为 2 * 并发数 * 分块大小：播放位置停在 0 时不开始 8 字节之后的块
+        for i in 1..4 {
+            finish(&mut cm, i);
+        }
+        assert_eq!(cm.get_next_available_chunk().unwrap().0, 0);
+        assert!(cm.get_next_available_chunk().is_none());
+
+        // 只有连续完成的开头部分写入预览文件
+        let output = root.join("out.bin").to_string_lossy().into_owned();
+        let preview = cm.merged_path(&output);
+        assert_eq!(cm.merge_ready_prefix(&preview).unwrap(), 0);
+        finish(&mut cm, 0);
+        assert_eq!(cm.merge_ready_prefix(&preview).unwrap(), 8);
+        assert_eq!(std::fs::read(&preview).unwrap(), &data[..8]);
+        assert_eq!(cm.first_incomplete_byte(), 8);
+        assert_eq!(cm.get_next_available_chunk().unwrap().0, 4);
+
+        for i in 4..10 {
+            finish(&mut cm, i);
+        }
+        cm.merge_chunks(&output).unwrap();
+        assert_eq!(std::fs::read(&output).unwrap(), data);
+        assert!(!Path::new(&preview).exists());
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_limited_merge_reports_progress() {
+        let root = std::env::temp_dir().join(format!("multidown_merge_{}", Uuid::new_v4()));
+        let mut cm = ChunkedDownloadManager::new(24, 8, "file.bin".to_string(), &root);
+        let data: Vec<u8> = (b'a'..b'a' + 24).collect();
+        for i in 0..3 {
+            std::fs::write(cm.get_chunk_file_path(i), &data[i * 8..i * 8 + 8]).unwrap();
+            cm.mark_chunk_completed(i);
+        }
+        let output = root.join("out.bin").to_string_lossy().into_owned();
+
+        // 每秒 16 字节：第一秒的额度用完后要等到下一秒，每次最多处理 4 字节
+        let mut reported = Vec::new();
+        let started = std::time::Instant::now();
+        cm.merge_plan(&output).run(&mut SpeedLimiter::new(16), |done, total| reported.push((done, total))).unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(500));
+        assert_eq!(std::fs::read(&output).unwrap(), data);
+        assert_eq!(reported.first(), Some(&(0, 24)));
+        assert_eq!(reported.last(), Some(&(24, 24)));
+        assert_eq!(reported.len(), 1 + 24 / 4);
+        assert!(!Path::new(&cm.temp_dir).exists());
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_verify_resumed_chunks() {
+        let root = std::env::temp_dir().join(format!("multidown_verify_{}", Uuid::new_v4()));
+        let task_id = Uuid::new_v4();
+        let info = FileInfo { size: 6, supports_range: true, last_modified: None, etag: Some("\"v1\"".to_string()) };
+        let mut cm = ChunkedDownloadManager::new(6, 2, "file.bin".to_string(), &root);
+        for (i, data) in [(0, b"ab"), (1, b"cd")] {
+            std::fs::write(cm.get_chunk_file_path(i), data).unwrap();
+            cm.mark_chunk_completed(i);
+            cm.record_checksum(i, ChunkChecksum::of(data));
+        }
+        cm.save_resume_info(task_id, "http://example.com/file.bin", &info).unwrap();
+
+        // 上次崩溃时第二块被写坏
+        std::fs::write(cm.get_chunk_file_path(1), b"cX").unwrap();
+        let mut resumed = ChunkedDownloadManager::new(6, 2, "file.bin".to_string(), &root);
+        resumed.load_and_validate_resume_info(task_id, &info).unwrap();
+        let corrupted: Vec<usize> = resumed
+            .completed_chunk_files()
+            .into_iter()
+            .filter(|(_, path, size, checksum)| !verify_chunk_file(path, *size, checksum.as_ref()))
+            .map(|(i, ..)| i)
+            .collect();
+        assert_eq!(corrupted, vec![1]);
+        let checksum = &resumed.checksums[&0];
+        assert!(checksum.matches(b"ab") && !checksum.matches(b"aX"));
+        assert_eq!(checksum.crc32, crc32fast::hash(b"ab"));
+        resumed.reset_chunk(1);
+        assert!(resumed.chunks[0].completed && !resumed.chunks[1].completed);
+        assert_eq!(resumed.get_next_available_chunk().unwrap().0, 1);
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_resume_info_appends() {
+        let root = std::env::temp_dir().join(format!("multidown_resume_{}", Uuid::new_v4()));
+        let task_id = Uuid::new_v4();
+        let info = FileInfo { size: 6, supports_range: true, last_modified: None, etag: None };
+        let mut cm = ChunkedDownloadManager::new(6, 2, "file.bin".to_string(), &root);
+        cm.mark_chunk_completed(0);
+        cm.save_resume_info(task_id, "http://example.com/file.bin", &info).unwrap();
+        let path = cm.resume_info_path(task_id);
+        let header_len = std::fs::metadata(&path).unwrap().len();
+
+        // 分块边界不变时每完成一块只追加一条记录
+        cm.mark_chunk_completed(2);
+        cm.record_checksum(2, ChunkChecksum::of(b"ef"));
+        cm.save_resume_info(task_id, "http://example.com/file.bin", &info).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), header_len + resume::RECORD_SIZE as u64);
+
+        let mut resumed = ChunkedDownloadManager::new(6, 2, "file.bin".to_string(), &root);
+        resumed.load_and_validate_resume_info(task_id, &info).unwrap();
+        assert!(resumed.chunks[0].completed && !resumed.chunks[1].completed && resumed.chunks[2].completed);
+        assert_eq!(resumed.checksums.get(&4), Some(&ChunkChecksum::of(b"ef")));
+        assert_eq!(ChunkedDownloadManager::saved_chunk_size(&root, task_id), Some(2));
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_chunk_map() {
+        let root = std::env::temp_dir().join(format!("multidown_map_{}", Uuid::new_v4()));
+        let mut cm = ChunkedDownloadManager::new(800, 100, "file.bin".to_string(), &root);
+        cm.set_max_concurrent_chunks(8);
+        use ChunkState::*;
+        assert_eq!(cm.chunk_map(8), vec![Pending; 8]);
+
+        cm.mark_chunk_completed(0);
+        let (first, _) = cm.get_next_available_chunk().unwrap();
+        cm.begin_chunk(first).unwrap().written.store(50, Ordering::SeqCst);
+        let (second, _) = cm.get_next_available_chunk().unwrap();
+        cm.mark_chunk_failed(second, DownloadError::network_error("connection reset"));
+        assert_eq!(cm.chunk_map(8), vec![Done, Active, Failed, Pending, Pending, Pending, Pending, Pending]);
+
+        // 一格覆盖两个块时显示更需要关注的状态；下载中的块已写入的部分算作完成
+        assert_eq!(cm.chunk_map(4), vec![Active, Failed, Pending, Pending]);
+        assert_eq!(cm.chunk_map(16)[2..4], [Done, Active]);
+        assert_eq!(cm.get_stats().map.len(), CHUNK_MAP_CELLS);
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_failed_chunk_retry() {
+        let root = std::env::temp_dir().join(format!("multidown_retry_{}", Uuid::new_v4()));
+        let mut cm = ChunkedDownloadManager::new(300, 100, "file.bin".to_string(), &root);
+        cm.retry_context = RetryContext::new(2, Duration::from_millis(10), Duration::from_secs(1));
+        cm.set_max_concurrent_chunks(3);
+        let (first, _) = cm.get_next_available_chunk().unwrap();
+        let (second, _) = cm.get_next_available_chunk().unwrap();
+
+        // 网络错误按策略重试，块在重新下载前不会被重新分配
+        cm.mark_chunk_failed(first, DownloadError::network_error("connection reset"));
+        assert!(cm.should_retry_failed_chunks() && cm.unretryable_error().is_none());
+        let (chunks, _) = cm.get_failed_chunks_for_retry();
+        assert_eq!(chunks, vec![first]);
+        assert!(cm.is_chunk_failed(first) && !cm.should_retry_failed_chunks());
+        cm.mark_chunk_failed(first, DownloadError::network_error("connection reset"));
+        assert_eq!(cm.get_failed_chunks_for_retry().0, vec![first]);
+
+        // 重试次数用完后不能再重试，任务应当失败
+        cm.mark_chunk_failed(first, DownloadError::network_error("connection reset"));
+        assert!(!cm.should_retry_failed_chunks());
+        assert!(cm.get_failed_chunks_for_retry().0.is_empty());
+        assert!(cm.unretryable_error().is_some_and(|e| e.is_network()));
+
+        // 客户端错误不重试
+        cm.reset_retry_state();
+        cm.mark_chunk_failed(second, DownloadError::http_status(404, "http://example.com/file.bin").with_chunk(second));
+        assert!(!cm.should_retry_failed_chunks());
+        assert_eq!(cm.unretryable_error().and_then(|e| e.status_code()), Some(404));
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,241 @@
+//! 主机名解析与缓存
+//!
+//! 所有连接都通过 `Resolver` 解析主机名。结果（包括解析失败）按主机缓存，同一主机同时只有
+//! 一次解析在进行；任务排队时管理器调用 `prefetch` 提前解析，大批任务启动时不必逐个等待 DNS。
+//! 配置项 `dns_resolver` 为 DoH 地址时通过 DNS-over-HTTPS（JSON 格式）解析，否则使用系统解析器。
+
+use futures::future::{LocalBoxFuture, Shared};
+use futures::FutureExt;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+
+/// 解析失败的结果缓存的时间（不超过 `dns_cache_ttl`）
+const NEGATIVE_TTL: Duration = Duration::from_secs(30);
+
+/// DoH 请求的超时时间
+const DOH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 解析结果：地址列表，或者失败原因
+pub type LookupResult = Result<Vec<IpAddr>, String>;
+
+type Lookup = Shared<LocalBoxFuture<'static, LookupResult>>;
+
+struct CacheEntry {
+    result: LookupResult,
+    expires: Instant,
+}
+
+#[derive(Default)]
+struct Inner {
+    cache: HashMap<String, CacheEntry>,
+    /// 正在进行的解析，同一主机的请求共用一个
+    pending: HashMap<String, Lookup>,
+}
+
+/// 带缓存的解析器，克隆出的解析器共享同一个缓存（只能在创建它的 Arbiter 上使用）
+#[derive(Clone)]
+pub struct Resolver {
+    inner: Rc<RefCell<Inner>>,
+    /// DoH 服务地址，None 表示使用系统解析器
+    doh: Option<Rc<str>>,
+    /// 解析成功的结果缓存的时间，0 表示不缓存
+    ttl: Duration,
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new(&Config::default())
+    }
+}
+
+impl Resolver {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            inner: Rc::default(),
+            doh: (!config.dns_resolver.is_empty()).then(|| config.dns_resolver.as_str().into()),
+            ttl: Duration::from_secs(config.dns_cache_ttl),
+        }
+    }
+
+    /// 解析主机名，IP 字面量直接返回
+    pub async fn resolve(&self, host: &str) -> LookupResult {
+        // IPv6 字面量的主机名带有方括号
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return Ok(vec![ip]);
+        }
+        let key = host.to_ascii_lowercase();
+        if let Some(result) = self.cached(&key) {
+            return result;
+        }
+        self.lookup(key).await
+    }
+
+    /// 在后台提前解析 URL 中的主机名，已经缓存或正在解析时什么都不做
+    pub fn prefetch(&self, url: &str) {
+        let Some(host) = url::Url::parse(url).ok().and_then(|u| u.host_str().map(|h| h.to_ascii_lowercase())) else {
+            return;
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']').to_string();
+        if host.parse::<IpAddr>().is_ok() || self.cached(&host).is_some() || self.inner.borrow().pending.contains_key(&host) {
+            return;
+        }
+        tracing::debug!(host = %host, "预解析主机名");
+        actix::spawn(self.lookup(host).map(|_| ()));
+    }
+
+    /// 未过期的缓存结果
+    fn cached(&self, host: &str) -> Option<LookupResult> {
+        let inner = self.inner.borrow();
+        let entry = inner.cache.get(host)?;
+        (entry.expires > Instant::now()).then(|| entry.result.clone())
+    }
+
+    /// 取得正在进行的解析，没有时发起一个；解析完成时写入缓存
+    fn lookup(&self, host: String) -> Lookup {
+        if let Some(lookup) = self.inner.borrow().pending.get(&host) {
+            return lookup.clone();
+        }
+        let inner = self.inner.clone();
+        let doh = self.doh.clone();
+        let ttl = self.ttl;
+        let key = host.clone();
+        let lookup = async move {
+            let started = Instant::now();
+            let result = match &doh {
+                Some(endpoint) => doh_lookup(endpoint, &host).await,
+                None => system_lookup(&host).await.map(|addrs| (addrs, None)),
+            };
+            let (result, ttl) = match result {
+                Ok((addrs, _)) if addrs.is_empty() => (Err("没有 DNS 记录".to_string()), NEGATIVE_TTL.min(ttl)),
+                Ok((addrs, record_ttl)) => (Ok(addrs), record_ttl.map_or(ttl, |t| t.min(ttl))),
+                Err(e) => (Err(e), NEGATIVE_TTL.min(ttl)),
+            };
+            match &result {
+                Ok(addrs) => tracing::debug!(
+                    host = %host,
+                    addrs = addrs.len(),
+                    elapsed_ms = started.elapsed().as_millis() as u64,
+                    "主机名解析完成"
+                ),
+                Err(e) => tracing::warn!(host = %host, error = %e, "主机名解析失败"),
+            }
+            let mut inner = inner.borrow_mut();
+            inner.pending.remove(&host);
+            if !ttl.is_zero() {
+                inner.cache.insert(host, CacheEntry { result: result.clone(), expires: Instant::now() + ttl });
+            }
+            result
+        }
+        .boxed_local()
+        .shared();
+        self.inner.borrow_mut().pending.insert(key, lookup.clone());
+        lookup
+    }
+}
+
+async fn system_lookup(host: &str) -> LookupResult {
+    tokio::net::lookup_host((host, 0))
+        .await
+        .map(|addrs| addrs.map(|addr| addr.ip()).collect())
+        .map_err(|e| e.to_string())
+}
+
+/// 通过 DoH 同时查询 A 和 AAAA 记录，返回地址和最短的 TTL
+async fn doh_lookup(endpoint: &str, host: &str) -> Result<(Vec<IpAddr>, Option<Duration>), String> {
+    let client = awc::Client::default();
+    let (a, aaaa) = futures::join!(
+        doh_query(&client, endpoint, host, "A"),
+        doh_query(&client, endpoint, host, "AAAA")
+    );
+    let records = match (a, aaaa) {
+        (Err(e), Err(_)) => return Err(e),
+        (a, aaaa) => a.unwrap_or_default().into_iter().chain(aaaa.unwrap_or_default()).collect::<Vec<_>>(),
+    };
+    let ttl = records.iter().map(|(_, ttl)| Duration::from_secs(*ttl as u64)).min();
+    Ok((records.into_iter().map(|(ip, _)| ip).collect(), ttl))
+}
+
+async fn doh_query(client: &awc::Client, endpoint: &str, host: &str, record_type: &str) -> Result<Vec<(IpAddr, u32)>, String> {
+    let mut url = url::Url::parse(endpoint).map_err(|e| format!("DoH 地址无效: {}", e))?;
+    url.query_pairs_mut().append_pair("name", host).append_pair("type", record_type);
+    let mut response = client
+        .get(url.as_str())
+        .insert_header(("Accept", "application/dns-json"))
+        .timeout(DOH_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| format!("DoH 请求失败: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("DoH 服务器返回 {}", response.status()));
+    }
+    let body = response.body().await.map_err(|e| format!("读取 DoH 响应失败: {}", e))?;
+    parse_doh_answer(&body)
+}
+
+/// 解析 DoH JSON 响应中的 A/AAAA 记录（忽略 CNAME 等其他记录）
+fn parse_doh_answer(body: &[u8]) -> Result<Vec<(IpAddr, u32)>, String> {
+    let json: serde_json::Value = serde_json::from_slice(body).map_err(|e| format!("DoH 响应格式错误: {}", e))?;
+    let status = json["Status"].as_u64().unwrap_or(0);
+    if status != 0 {
+        // 3 为 NXDOMAIN
+        return Err(format!("DNS 响应码 {}", status));
+    }
+    Ok(json["Answer"]
+        .as_array()
+        .map(|answers| {
+            answers
+                .iter()
+                .filter(|a| matches!(a["type"].as_u64(), Some(1) | Some(28)))
+                .filter_map(|a| {
+                    let ip = a["data"].as_str()?.parse().ok()?;
+                    Some((ip, a["TTL"].as_u64().unwrap_or(0) as u32))
+                })
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_doh_answer() {
+        let body = br#"{"Status":0,"Answer":[
+            {"name":"example.com","type":5,"TTL":60,"data":"cdn.example.com."},
+            {"name":"cdn.example.com","type":1,"TTL":120,"data":"93.184.216.34"},
+            {"name":"cdn.example.com","type":28,"TTL":300,"data":"2606:2800:220:1::248"}]}"#;
+        let records = parse_doh_answer(body).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0], ("93.184.216.34".parse().unwrap(), 120));
+        assert!(parse_doh_answer(br#"{"Status":3}"#).is_err());
+        assert_eq!(parse_doh_answer(br#"{"Status":0}"#).unwrap(), vec![]);
+    }
+
+    #[actix_rt::test]
+    async fn test_resolver_cache() {
+        let resolver = Resolver::default();
+        assert_eq!(resolver.resolve("[::1]").await.unwrap(), vec!["::1".parse::<IpAddr>().unwrap()]);
+
+        let addrs = resolver.resolve("LocalHost").await.unwrap();
+        assert!(!addrs.is_empty());
+        assert_eq!(resolver.cached("localhost"), Some(Ok(addrs)));
+        assert!(resolver.inner.borrow().pending.is_empty());
+
+        // 失败的结果同样缓存
+        let result = resolver.resolve("multidown.invalid").await;
+        assert!(result.is_err());
+        assert_eq!(resolver.cached("multidown.invalid"), Some(result));
+
+        // 不缓存
+        let resolver = Resolver::new(&Config { dns_cache_ttl: 0, ..Default::default() });
+        resolver.resolve("localhost").await.unwrap();
+        assert!(resolver.cached("localhost").is_none());
+    }
+}
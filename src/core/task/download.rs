@@ -1,127 +1,146 @@
 use actix::Addr;
 use futures::StreamExt;
-use std::time::Instant;
+use std::rc::Rc;
+use std::time::Duration;
 use std::sync::{Arc, Mutex};
-use std::borrow::Cow;
+use std::io::Read;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use crate::config::Config;
 use crate::core::error::DownloadError;
 use super::actor::DownloadTaskActor;
-use super::messages::{MarkCompleted, MarkFailed, UpdateProgress};
+use super::chunk_manager::ChunkProgress;
+use super::messages::{MarkCompleted, MarkFailed, RecordRetry, UpdateProgress};
 use super::retry::RetryContext;
-use super::util::{BufferManager, SpeedLimiter};
+use super::transport::{HttpRequest, HttpTransport, RequestSettings};
+use super::util::{BufferManager, ChunkChecksum, ChunkHasher, DownloadQuota, ProgressThrottle, SpeedLimiter, StopSignal};
 
-/// 带重试的单线程下载函数
-pub async fn start_single_download_with_retry(
-    actor_addr: Addr<DownloadTaskActor>,
-    url: String,
-    file: String,
-    _total_size: u64,
-    config: Config,
-) {
-    let progress_addr = actor_addr.clone();
-    let error_addr = actor_addr.clone();
-    
-    // 创建重试上下文
-    let mut retry_context = RetryContext::new(
-        config.retry_count as u32,
-        std::time::Duration::from_secs(config.retry_delay),
-        std::time::Duration::from_secs(config.retry_max_delay)
-    );
-    
-    // 在单独的线程中运行 awc 下载
-    let handle = std::thread::spawn(move || {
-        let rt = tokio::runtime::Builder::new_current_thread()
-            .enable_all()
-            .build()
-            .unwrap();
-        rt.block_on(async {
-            loop {
-                match perform_single_download(&url, &file, &progress_addr, &config).await {
-                    Ok(()) => {
-                        println!("[actor_task] 单线程下载完成");
-                        actor_addr.do_send(MarkCompleted);
-                        break;
-                    },
-                    Err(error) => {
-                        println!("[actor_task] 单线程下载失败: {:?}", error);
-                        log::error!("单线程下载失败: {:?}", error);
-                        if retry_context.should_retry(&error) {
-                            retry_context.record_retry();
-                            let delay = retry_context.get_next_delay();
-                            println!("[actor_task] 将在 {} 秒后重试下载 (第 {} 次重试)", delay.as_secs(), retry_context.current_retries());
-                            tokio::time::sleep(delay).await;
-                        } else {
-                            actor_addr.do_send(MarkFailed { error });
-                            break;
-                        }
-                    }
+/// 进度上报的最小间隔，期间收到的数据累计后一起上报
+pub const PROGRESS_REPORT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 累计收到这么多字节时不等间隔到期也上报一次
+pub const PROGRESS_REPORT_BYTES: u64 = 64 * 1024 * 1024;
+
+/// 单线程下载（不分块）所需的全部状态
+///
+/// 直接运行在任务 Actor 所在的 Arbiter 上，与分块下载共用管理器的 HTTP 后端；
+/// 暂停或取消时立即中止进行中的请求和重试等待。
+pub struct SingleDownload {
+    pub actor_addr: Addr<DownloadTaskActor>,
+    pub url: String,
+    pub file: String,
+    pub config: Config,
+    pub limiter: Arc<Mutex<SpeedLimiter>>,
+    pub settings: RequestSettings,
+    pub transferred: Arc<AtomicU64>,
+    pub transport: Rc<dyn HttpTransport>,
+    pub stop: StopSignal,
+    pub quota: Arc<DownloadQuota>,
+    pub quota_reserved: Arc<AtomicU64>,
+    /// 只下载部分内容时请求的字节范围（闭区间）
+    pub range: Option<(u64, u64)>,
+    /// 续传已有的部分文件：接在文件末尾写入，每次请求从文件当前的长度开始，停止时保留文件
+    pub append: bool,
+}
+
+impl SingleDownload {
+    /// 带重试地下载，结束时向任务 Actor 报告完成或失败；暂停和取消不报告
+    pub async fn run(self) {
+        let mut retry_context = RetryContext::from_config(&self.config).for_url(&self.url);
+
+        let result = loop {
+            let result = match self.stop.check() {
+                Ok(()) => self.stop.guard(perform_single_download(&self)).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(()) => break Ok(()),
+                Err(error @ (DownloadError::Paused | DownloadError::Cancelled)) => break Err(error),
+                Err(error) => {
+                    tracing::warn!(error = %error, "单线程下载失败");
+                    let Some(delay) = retry_context.next_retry(&error) else {
+                        break Err(error);
+                    };
+                    self.actor_addr.do_send(RecordRetry { error_kind: Some(error.kind()) });
+                    tracing::info!(delay_secs = delay.as_secs(), retry = retry_context.current_retries(), "稍后重试下载");
+                    // 等待期间暂停或取消时提前结束，由下一轮循环返回对应的错误
+                    let _ = self.stop.sleep(delay).await;
                 }
             }
-        });
-    });
-    
-    // 等待下载线程完成
-    if let Err(e) = handle.join() {
-        println!("[actor_task] 下载线程异常: {:?}", e);
-        log::error!("下载线程异常: {:?}", e);
-        error_addr.do_send(MarkFailed { error: DownloadError::Unknown(Cow::Borrowed("下载线程异常")) });
+        };
+
+        match result {
+            Ok(()) => self.actor_addr.do_send(MarkCompleted),
+            Err(error @ (DownloadError::Paused | DownloadError::Cancelled)) => {
+                // 不分块的下载无法续传，删除不完整的文件，恢复时重新下载；
+                // 续传的部分文件只在末尾追加过，保留下来下次接着续传
+                tracing::info!(reason = error.kind(), "单线程下载已停止");
+                if !self.append {
+                    let _ = std::fs::remove_file(&self.file);
+                }
+            }
+            Err(error) => self.actor_addr.do_send(MarkFailed { error }),
+        }
     }
 }
 
 /// 执行单次单线程下载
-async fn perform_single_download(
-    url: &str,
-    file: &str,
-    progress_addr: &Addr<DownloadTaskActor>,
-    config: &Config,
-) -> Result<(), DownloadError> {
-    let client = awc::Client::default();
-    let mut response = client.get(url).send().await
-        .map_err(|e| DownloadError::NetworkError(format!("{:?}", e).into()))?;
+async fn perform_single_download(task: &SingleDownload) -> Result<(), DownloadError> {
+    let SingleDownload { url, file, settings, actor_addr: progress_addr, limiter, transferred, .. } = task;
+    let mut request = HttpRequest::get(url, settings);
+    if let Some((mut start, end)) = task.range {
+        // 重试时从上次写到的位置继续
+        if task.append {
+            start = std::fs::metadata(file).map_err(|e| DownloadError::io_error_with_context("读取部分文件", e))?.len();
+        }
+        request = request.range(start, end);
+    }
+    let mut response = task.transport.send(request).await?;
     
-    if !response.status().is_success() {
-        return Err(DownloadError::ServerError(format!("服务器错误: {}", response.status()).into()));
+    if !response.is_success() {
+        return Err(DownloadError::http_status(response.status, url));
+    }
+    // 服务器忽略 Range 时会返回整个文件
+    if task.range.is_some() && response.status != 206 {
+        return Err(DownloadError::unknown("服务器没有按 Range 请求返回部分内容"));
     }
     
-    let total = response.headers().get("content-length")
-        .and_then(|v| v.to_str().ok())
-        .and_then(|s| s.parse::<u64>().ok())
-        .unwrap_or(0);
+    // 没有 Content-Length（如 chunked 编码）时大小未知，以连接正常结束为准
+    let expected = response.header("content-length").and_then(|s| s.parse::<u64>().ok());
+    let total = expected.unwrap_or(0);
+    let check_size = |size| task.quota.reserve_file(task.config.max_file_size, &task.quota_reserved, size);
+    check_size(total)?;
         
-    let mut buffer_manager = BufferManager::new(file, 1024 * 1024)?;
-    
-    let mut downloaded = 0u64;
-    let mut last_update = Instant::now();
-    let mut limiter = if config.speed_limit_kb > 0 {
-        Some(SpeedLimiter::new(config.speed_limit_kb * 1024))
+    let mut buffer_manager = if task.append {
+        BufferManager::append(file, 1024 * 1024)?
     } else {
-        None
+        BufferManager::new(file, 1024 * 1024)?
     };
     
-    while let Some(chunk) = response.next().await {
+    let mut downloaded = 0u64;
+    let mut throttle = ProgressThrottle::new(PROGRESS_REPORT_INTERVAL, PROGRESS_REPORT_BYTES);
+    while let Some(chunk) = response.body.next().await {
         match chunk {
             Ok(bytes) => {
-                if let Some(ref mut limiter) = limiter {
-                    let wait = limiter.wait_if_needed(bytes.len() as u64);
-                    if !wait.is_zero() {
-                        tokio::time::sleep(wait).await;
-                    }
+                transferred.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+                let wait = limiter.lock().unwrap().wait_if_needed(bytes.len() as u64);
+                if !wait.is_zero() {
+                    tokio::time::sleep(wait).await;
                 }
-                buffer_manager.write(bytes.as_ref())?;
                 downloaded += bytes.len() as u64;
-                let progress = if total > 0 { (downloaded as f32 / total as f32) * 100.0 } else { 0.0 };
-                let now = Instant::now();
-                if now.duration_since(last_update).as_secs_f64() >= 1.0 {
-                    let speed = (downloaded as f64 / now.duration_since(last_update).as_secs_f64()) as u64;
+                // 大小未知或服务器发送的数据超过声明的大小时，到达上限立即中止
+                check_size(downloaded)?;
+                buffer_manager.write(bytes.as_ref())?;
+                if let Some((bytes, elapsed)) = throttle.record(bytes.len() as u64) {
+                    // 速度按上次上报以来新增的字节数计算
+                    let progress = if total > 0 { (downloaded as f32 / total as f32) * 100.0 } else { 0.0 };
+                    let speed = (bytes as f64 / elapsed.as_secs_f64().max(0.001)) as u64;
                     progress_addr.do_send(UpdateProgress { progress, downloaded, total, speed });
-                    last_update = now;
                 }
             },
             Err(e) => {
-                println!("[download] 网络流错误: {:?}", e);
-                log::error!("网络流错误: {:?}", e);
-                return Err(DownloadError::Unknown(format!("网络流错误: {:?}", e).into()));
+                tracing::error!(error = %e, "网络流错误");
+                return Err(e);
             }
         }
     }
@@ -129,69 +148,116 @@ async fn perform_single_download(
     buffer_manager.flush()?;
     
     let final_written = buffer_manager.get_total_written();
-    if final_written >= total && total > 0 {
+    if expected.is_none_or(|total| final_written >= total) {
         Ok(())
     } else {
-        println!("[download] 文件大小不匹配: 预期 {} 实际 {}", total, final_written);
-        log::error!("文件大小不匹配: 预期 {} 实际 {}", total, final_written);
+        tracing::error!(expected = total, actual = final_written, "文件大小不匹配");
         Err(DownloadError::SizeMismatch{ expected: total, actual: final_written })
     }
 }
 
-/// 执行单次块下载
+/// 执行单次块下载，写入 `chunk_path`，返回写入内容的校验值
+///
+/// 下载过程中块可能被分割（`progress.end` 变小），写到新的结束位置就停止。
+/// 块的位置相对于 `offset`（只下载部分内容时范围的起点）。
+///
+/// 块文件中已有的内容（上次暂停或连接中断前写入的部分）保留，只请求剩下的范围。暂停或取消时
+/// 立即中止请求，先把缓冲区写入块文件再返回，恢复时从写到的位置继续。
+#[allow(clippy::too_many_arguments)]
 pub async fn perform_chunk_download(
+    transport: &dyn HttpTransport,
     url: &str,
-    file: &str,
-    chunk_index: usize,
-    start: u64,
-    end: u64,
-    limiter: Option<Arc<Mutex<SpeedLimiter>>>,
-) -> Result<(), DownloadError> {
-    let client = awc::Client::default();
-    let range_header = format!("bytes={}-{}", start, end);
-    
-    let mut response = client.get(url)
-        .insert_header(("Range", range_header))
-        .send()
-        .await
-        .map_err(|e| DownloadError::NetworkError(format!("{:?}", e).into()))?;
-    
-    if !response.status().is_success() && response.status() != 206 {
-        return Err(DownloadError::ServerError(format!("服务器错误: {}", response.status()).into()));
+    settings: &RequestSettings,
+    chunk_path: &str,
+    progress: &ChunkProgress,
+    offset: u64,
+    limiter: Arc<Mutex<SpeedLimiter>>,
+    transferred: &AtomicU64,
+    stop: &StopSignal,
+) -> Result<ChunkChecksum, DownloadError> {
+    let start = progress.start;
+    let expected_size = || progress.end.load(Ordering::SeqCst) - start + 1;
+    let (existing, mut hasher) = resume_chunk_file(chunk_path, expected_size())?;
+    progress.written.store(existing, Ordering::SeqCst);
+    if progress.remaining() == 0 {
+        return Ok(hasher.finish());
     }
+    let request = HttpRequest::get(url, settings).range(offset + start + existing, offset + progress.end.load(Ordering::SeqCst));
+    let mut response = stop.guard(transport.send(request)).await?;
     
-    let chunk_path = format!("downloads/temp/{}/chunk_{:04}", 
-        file.replace("/", "_").replace("\\", "_"), chunk_index);
+    if !response.is_success() {
+        return Err(DownloadError::http_status(response.status, url));
+    }
+    // 服务器忽略 Range（或 If-Range 不匹配）时会返回整个文件，不能写入块
+    if response.status != 206 {
+        return Err(DownloadError::unknown("服务器没有按 Range 请求返回部分内容"));
+    }
     
-    let mut buffer_manager = BufferManager::new(&chunk_path, 256 * 1024)?;
+    let mut buffer_manager = if existing > 0 {
+        BufferManager::append(chunk_path, 256 * 1024)?
+    } else {
+        BufferManager::new(chunk_path, 256 * 1024)?
+    };
     
-    while let Some(chunk) = response.next().await {
-        match chunk {
-            Ok(bytes) => {
-                if let Some(ref limiter) = limiter {
-                    let mut limiter = limiter.lock().unwrap();
-                    let wait = limiter.wait_if_needed(bytes.len() as u64);
-                    drop(limiter);
-                    if !wait.is_zero() {
-                        tokio::time::sleep(wait).await;
-                    }
-                }
-                buffer_manager.write(bytes.as_ref())?;
+    let received = async {
+        loop {
+            let chunk = tokio::select! {
+                biased;
+                error = stop.stopped() => return Err(error),
+                chunk = response.body.next() => chunk,
+            };
+            let Some(bytes) = chunk.transpose()? else { return Ok(()) };
+            transferred.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+            let take = (bytes.len() as u64).min(progress.remaining()) as usize;
+            buffer_manager.write(&bytes[..take])?;
+            hasher.update(&bytes[..take]);
+            progress.written.fetch_add(take as u64, Ordering::SeqCst);
+            if progress.remaining() == 0 {
+                return Ok(());
+            }
+            let wait = limiter.lock().unwrap().wait_if_needed(bytes.len() as u64);
+            if !wait.is_zero() {
+                stop.sleep(wait).await?;
             }
-            Err(e) => return Err(DownloadError::Unknown(format!("网络流错误: {:?}", e).into())),
         }
     }
-    
+    .await;
+    // 出错或暂停时也把已收到的数据写入块文件，下次从这里继续
     buffer_manager.flush()?;
+    received?;
     
-    let final_written = buffer_manager.get_total_written();
-    let expected_size = end - start + 1;
-    if final_written != expected_size {
+    let final_written = existing + buffer_manager.get_total_written();
+    if final_written != expected_size() {
         return Err(DownloadError::SizeMismatch { 
-            expected: expected_size, 
+            expected: expected_size(), 
             actual: final_written 
         });
     }
     
-    Ok(())
+    Ok(hasher.finish())
+}
+
+/// 读取块文件中已有的内容：返回已写入的字节数和这部分内容的校验状态
+///
+/// 块被分割后文件可能比新的块大，截掉多出的部分。
+fn resume_chunk_file(chunk_path: &str, expected_size: u64) -> Result<(u64, ChunkHasher), DownloadError> {
+    let mut hasher = ChunkHasher::default();
+    let existing = std::fs::metadata(chunk_path).map(|m| m.len()).unwrap_or(0);
+    if existing == 0 {
+        return Ok((0, hasher));
+    }
+    let io_error = |e| DownloadError::io_error_with_context("读取块文件", e);
+    let mut file = std::fs::OpenOptions::new().read(true).write(true).open(chunk_path).map_err(io_error)?;
+    if existing > expected_size {
+        file.set_len(expected_size).map_err(io_error)?;
+    }
+    let mut buf = vec![0u8; 256 * 1024];
+    loop {
+        let n = file.read(&mut buf).map_err(io_error)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok((existing.min(expected_size), hasher))
 } 
\ No newline at end of file
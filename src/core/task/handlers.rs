@@ -1,131 +1,387 @@
-use actix::{ActorFutureExt, AsyncContext, Handler, ResponseActFuture, WrapFuture};
+use actix::{ActorContext, ActorFutureExt, AsyncContext, Handler, ResponseActFuture, WrapFuture};
 use std::sync::atomic::Ordering;
 use std::time::Instant;
 use std::path::Path;
+use tracing::Instrument;
 
 use crate::core::error::DownloadError;
 use super::actor::DownloadTaskActor;
-use super::chunk_manager::ChunkedDownloadManager;
-use super::download::{start_single_download_with_retry, perform_chunk_download};
+use super::chunk_manager::{verify_chunk_file, ChunkedDownloadManager};
+use super::download::{perform_chunk_download, SingleDownload};
 use super::messages::*;
-use super::state::TaskStatus;
-use super::util::FileInfo;
+use super::options::ByteRange;
+use super::protocol::HandlerDownload;
+use super::state::{TaskPhase, TaskStatus};
+use super::tuning::{host_key, ThroughputController, TuningStore, TUNING_FILE};
+use super::transport::{HttpRequest, HttpTransport, RequestSettings};
+use super::util::{sync_durable, FileInfo, FileMetadata, SpeedLimiter, StopSignal};
 
-async fn get_file_info(url: &str) -> Result<FileInfo, DownloadError> {
-    let client = awc::Client::default();
-    let response = client.head(url).send().await
-        .map_err(|e| DownloadError::NetworkError(format!("{:?}", e).into()))?;
+pub(crate) async fn get_file_info(transport: &dyn HttpTransport, url: &str, settings: &RequestSettings) -> Result<FileInfo, DownloadError> {
+    let response = transport.send(HttpRequest::head(url, settings)).await?;
     
-    if !response.status().is_success() {
-        return Err(DownloadError::ServerError(format!("服务器错误: {}", response.status()).into()));
+    if !response.is_success() {
+        return Err(DownloadError::http_status(response.status, url));
     }
     
     Ok(FileInfo {
-        size: response.headers().get("content-length")
-            .and_then(|v| v.to_str().ok())
-            .and_then(|s| s.parse::<u64>().ok())
-            .unwrap_or(0),
-        supports_range: response.headers().get("accept-ranges")
-            .and_then(|v| v.to_str().ok())
-            .map(|s| s == "bytes")
-            .unwrap_or(false),
-        last_modified: response.headers().get("last-modified")
-            .and_then(|v| v.to_str().ok())
-            .map(|s| s.to_string()),
-        etag: response.headers().get("etag")
-            .and_then(|v| v.to_str().ok())
-            .map(|s| s.to_string()),
+        size: response.content_length(),
+        supports_range: response.header("accept-ranges") == Some("bytes"),
+        last_modified: response.header("last-modified").map(|s| s.to_string()),
+        etag: response.header("etag").map(|s| s.to_string()),
     })
 }
 
+/// 获取文件信息，返回 403 时依次换用 `fallback_user_agents` 重试；
+/// 换用成功时 `settings` 切换到这个 User-Agent，其他错误立即返回
+pub(crate) async fn get_file_info_with_fallback(
+    transport: &dyn HttpTransport,
+    url: &str,
+    settings: &mut RequestSettings,
+) -> Result<FileInfo, DownloadError> {
+    let error = match get_file_info(transport, url, settings).await {
+        Err(e) if e.status_code() == Some(403) => e,
+        result => return result,
+    };
+    for user_agent in settings.fallback_user_agents.clone() {
+        let candidate = settings.with_user_agent(&user_agent);
+        match get_file_info(transport, url, &candidate).await {
+            Ok(info) => {
+                tracing::info!(user_agent = %user_agent, "服务器返回 403，换用 User-Agent 后请求成功");
+                *settings = candidate;
+                return Ok(info);
+            }
+            Err(e) if e.status_code() == Some(403) => {
+                tracing::debug!(user_agent = %user_agent, "换用 User-Agent 后仍然返回 403");
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Err(error)
+}
+
+/// 实际下载的区域（起点，长度）：整个文件，或 `--range` 指定的部分
+fn download_region(range: Option<ByteRange>, info: &FileInfo) -> Result<(u64, u64), DownloadError> {
+    let Some(range) = range else {
+        return Ok((0, info.size));
+    };
+    if !info.supports_range {
+        return Err(DownloadError::unknown("服务器不支持 Range 请求，无法只下载部分内容"));
+    }
+    let (start, end) = range.resolve(info.size)?;
+    Ok((start, end - start))
+}
+
+/// 续传已有的部分文件（`--continue`），返回继续下载的起点（文件当前的长度）
+///
+/// 需要服务器支持 Range 请求、文件大小已知且大于本地文件；服务器返回的 Last-Modified
+/// 晚于本地文件的修改时间时，说明服务器上的文件在部分文件写入之后更新过，不能拼接。
+pub(crate) fn adopt_partial(file: &str, info: &FileInfo) -> Result<u64, DownloadError> {
+    let refuse = |reason: &str| DownloadError::FileExists(format!("{}（{}）", file, reason).into());
+    let metadata = std::fs::metadata(file).map_err(|e| DownloadError::io_error_with_context("读取部分文件", e))?;
+    let len = metadata.len();
+    if !info.supports_range || info.size == 0 {
+        return Err(refuse("服务器不支持 Range 请求或大小未知，无法续传"));
+    }
+    if len >= info.size {
+        return Err(refuse("本地文件不小于服务器上的文件"));
+    }
+    let remote_modified = info
+        .last_modified
+        .as_deref()
+        .and_then(|s| chrono::DateTime::parse_from_rfc2822(s).ok());
+    if let (Some(remote), Ok(local)) = (remote_modified, metadata.modified()) {
+        if remote > chrono::DateTime::<chrono::Utc>::from(local) {
+            return Err(refuse("服务器上的文件在本地文件写入之后更新过"));
+        }
+    }
+    Ok(len)
+}
+
+/// 续传时附带的 If-Range：文件在获取信息之后发生变化时服务器返回整个文件而不是 206，下载失败而不是拼接出错误的文件
+fn if_range(info: &FileInfo) -> Option<String> {
+    info.etag.clone().filter(|etag| !etag.starts_with("W/")).or_else(|| info.last_modified.clone())
+}
+
 impl Handler<StartTask> for DownloadTaskActor {
     type Result = ();
     fn handle(&mut self, msg: StartTask, ctx: &mut Self::Context) {
-        self.is_paused.store(false, Ordering::SeqCst);
+        // 名额在途时任务已被取消：不再启动，名额随消息一起释放
+        if self.status == TaskStatus::Cancelled {
+            return;
+        }
+        // 暂停时的令牌已经触发，恢复时换一组新的
+        self.stop = StopSignal::default();
         self.status = TaskStatus::Running;
         self.start_time = Some(Instant::now());
         self.permit = Some(msg.permit);
         self.manager_addr = Some(msg.manager_addr);
+        self.notify_manager_started();
+        self.arm_deadline(ctx);
         
         let url = self.url.clone();
         let file = self.file.clone();
         let actor_addr = ctx.address();
         let config = self.config.clone();
+        let limiter = self.global_limiter.clone();
+        let mut settings = RequestSettings::new(&self.config, &self.options);
+        let transferred = self.transferred.clone();
         let task_id = self.id;
+        let span = self.span.clone();
+        let transport = self.transport.clone();
+        let stop = self.stop.clone();
+        let quota = self.quota.clone();
+        let quota_reserved = self.quota_reserved.clone();
+        let probes = self.probes.clone();
+        let protocols = self.protocols.clone();
+        let resolvers = self.resolvers.clone();
+        let bwlimit = self.speed_limit();
+        let range = self.options.range;
         
         actix::spawn(async move {
-            if !crate::utils::validator::is_valid_url(&url) {
-                actor_addr.do_send(MarkFailed { error: DownloadError::InvalidUrl(url.clone().into()) });
+            // 协议处理器认领的地址（rsync:// 等）不按 HTTP 校验
+            let handler = protocols.find(&url);
+            if let Err(error) = handler.is_none().then(|| crate::utils::validator::parse_url(&url)).transpose() {
+                actor_addr.do_send(MarkFailed { error });
                 return;
             }
-            if Path::new(&file).exists() {
+            let existing = Path::new(&file).exists();
+            if existing && (!config.continue_partial || range.is_some()) {
                 actor_addr.do_send(MarkFailed { error: DownloadError::FileExists(file.clone().into()) });
                 return;
             }
             
-            let file_info = match get_file_info(&url).await {
-                Ok(info) => info,
-                Err(e) => {
-                    actor_addr.do_send(MarkFailed { error: e });
+            // 交给协议处理器，不探测、不分块
+            if let Some(handler) = handler {
+                if range.is_some() {
+                    actor_addr.do_send(MarkFailed { error: DownloadError::unknown("该协议的地址不支持只下载部分内容") });
+                    return;
+                }
+                HandlerDownload {
+                    actor_addr, url, file, config, handler, bwlimit, transferred, stop, quota, quota_reserved,
+                }.run().await;
+                return;
+            }
+
+            // 分享链接先转换成直链，之后的探测和下载都请求直链
+            let url = match resolvers.resolve(&url, transport.as_ref(), &settings).await {
+                Ok(url) => url,
+                Err(error) => {
+                    actor_addr.do_send(MarkFailed { error });
                     return;
                 }
             };
+
+            // 小文件模式：不探测文件信息、不分块，直接发送 GET，大小由响应的 Content-Length 得出
+            if config.small_files && !existing && range.is_none() {
+                tracing::debug!("小文件模式，直接下载");
+                SingleDownload {
+                    actor_addr, url, file, config, limiter, settings, transferred, transport, stop,
+                    quota, quota_reserved, range: None, append: false,
+                }.run().await;
+                return;
+            }
+
+            let original_user_agent = settings.user_agent.clone();
+            let file_info = match probes.get(&url, config.probe_cache_ttl) {
+                Some((info, user_agent)) => {
+                    tracing::debug!(size = info.size, "使用缓存的文件信息");
+                    if let Some(user_agent) = user_agent {
+                        settings.user_agent = user_agent;
+                    }
+                    info
+                }
+                None => match get_file_info_with_fallback(transport.as_ref(), &url, &mut settings).await {
+                    Ok(info) => {
+                        if config.probe_cache_ttl > 0 {
+                            let user_agent = Some(settings.user_agent.clone()).filter(|ua| *ua != original_user_agent);
+                            probes.insert(&url, &info, user_agent);
+                        }
+                        info
+                    }
+                    Err(e) => {
+                        actor_addr.do_send(MarkFailed { error: e });
+                        return;
+                    }
+                },
+            };
             
-            let total_size = file_info.size;
-            let use_chunked = config.enable_chunked_download && total_size > config.min_chunk_size as u64;
+            // 只下载部分内容时，大小和分块位置都相对于范围的起点；续传已有的文件时从它的末尾开始
+            let region = if existing {
+                adopt_partial(&file, &file_info).map(|len| {
+                    tracing::info!(existing = len, total = file_info.size, "续传已有的部分文件");
+                    (len, file_info.size - len)
+                })
+            } else {
+                download_region(range, &file_info)
+            };
+            let (offset, total_size) = match region {
+                Ok(region) => region,
+                Err(error) => {
+                    actor_addr.do_send(MarkFailed { error });
+                    return;
+                }
+            };
+            // 大小未知时在下载过程中检查
+            if let Err(error) = quota.reserve_file(config.max_file_size, &quota_reserved, total_size) {
+                actor_addr.do_send(MarkFailed { error });
+                return;
+            }
+            let use_chunked = config.enable_chunked_download && file_info.supports_range && total_size > config.min_chunk_size as u64;
+            tracing::info!(total_size, offset, supports_range = file_info.supports_range, chunked = use_chunked, "开始下载");
             
             if use_chunked {
+                let user_agent = Some(settings.user_agent).filter(|ua| *ua != original_user_agent);
                 actor_addr.do_send(StartChunkedDownload { 
-                    url, file, total_size, task_id, file_info,
+                    url, file, total_size, offset, task_id, file_info, user_agent, append: existing,
                 });
             } else {
-                start_single_download_with_retry(actor_addr, url, file, total_size, config).await;
+                if existing {
+                    settings.headers.extend(if_range(&file_info).map(|v| ("If-Range".to_string(), v)));
+                }
+                SingleDownload {
+                    actor_addr, url, file, config, limiter, settings, transferred, transport, stop,
+                    quota, quota_reserved,
+                    range: (range.is_some() || existing).then(|| (offset, offset + total_size - 1)),
+                    append: existing,
+                }.run().await;
             }
-        });
+        }.instrument(span));
     }
 }
 
 impl Handler<StartChunkedDownload> for DownloadTaskActor {
     type Result = ();
     fn handle(&mut self, msg: StartChunkedDownload, ctx: &mut Self::Context) {
-        let chunk_size = self.config.chunk_size as u64;
-        let mut chunk_manager = ChunkedDownloadManager::new(msg.total_size, chunk_size, msg.file.clone());
+        self.range_offset = msg.offset;
+        self.download_url = (msg.url != self.url).then(|| msg.url.clone());
+        // 换用的 User-Agent 写入任务的请求头，分块请求和热重载后都沿用
+        if let Some(user_agent) = msg.user_agent {
+            self.options.headers.retain(|(n, _)| !n.eq_ignore_ascii_case("user-agent"));
+            self.options.headers.push(("User-Agent".to_string(), user_agent));
+        }
+        if msg.append {
+            self.options.headers.retain(|(n, _)| !n.eq_ignore_ascii_case("if-range"));
+            self.options.headers.extend(if_range(&msg.file_info).map(|v| ("If-Range".to_string(), v)));
+        }
+        // 自适应调整时从该主机上次的结果开始；恢复下载时沿用上次的分块边界
+        let mut chunk_size = self.config.chunk_size as u64;
+        let mut concurrency = self.config.thread_count;
+        if self.config.adaptive_chunking {
+            let store = TuningStore::new(self.config.state_path(TUNING_FILE));
+            let tuning = ThroughputController::new(&self.url, &self.config, store.get(&host_key(&self.url)));
+            chunk_size = tuning.tuning.chunk_size;
+            concurrency = tuning.tuning.concurrency;
+            self.tuning = Some(tuning);
+        }
+        if self.config.enable_resume {
+            if let Some(saved) = ChunkedDownloadManager::saved_chunk_size(&self.config.temp_dir_path(), self.id) {
+                chunk_size = saved;
+            }
+        }
+        let mut chunk_manager = ChunkedDownloadManager::new(msg.total_size, chunk_size, msg.file.clone(), self.config.temp_dir_path());
         
         if self.config.enable_resume {
             if let Err(e) = chunk_manager.load_and_validate_resume_info(self.id, &msg.file_info) {
-                println!("[actor_task] 恢复下载失败: {}, 将重新开始下载", e);
+                self.span.in_scope(|| tracing::warn!(error = %e, "恢复下载失败，将重新开始下载"));
                 chunk_manager.cleanup_temp_files();
-                chunk_manager = ChunkedDownloadManager::new(msg.total_size, chunk_size, msg.file.clone());
+                chunk_manager = ChunkedDownloadManager::new(msg.total_size, chunk_size, msg.file.clone(), self.config.temp_dir_path());
             }
         }
+        // 没有续传信息时分块边界可能与块文件对不上，块文件会被接着写，先清掉
+        if !chunk_manager.resumed {
+            chunk_manager.cleanup_temp_files();
+        }
         
+        // 线程数即同时下载的块数
+        chunk_manager.set_max_concurrent_chunks(concurrency);
+        chunk_manager.retry_context = super::retry::RetryContext::from_config(&self.config).for_url(&self.url);
+        chunk_manager.set_sequential(self.config.stream_order);
+        chunk_manager.append_output = msg.append;
+        let to_verify = if self.config.verify_resume { chunk_manager.completed_chunk_files() } else { Vec::new() };
         self.chunk_manager = Some(chunk_manager);
         self.file_info = Some(msg.file_info);
         self.total_size = msg.total_size;
         
-        let url = self.url.clone();
+        let url = msg.url;
         let file = self.file.clone();
         let id = self.id;
-        self.schedule_chunk_downloads(ctx, url, file, id);
+        if to_verify.is_empty() {
+            self.schedule_chunk_downloads(ctx, url, file, id);
+            return;
+        }
+        // 恢复时重新计算已完成块的 CRC32，与记录不一致（上次崩溃时写坏）的块重新下载；
+        // 校验期间不开始下载
+        let span = self.span.clone();
+        let verify = tokio::task::spawn_blocking(move || {
+            to_verify
+                .into_iter()
+                .filter(|(_, path, size, checksum)| !verify_chunk_file(path, *size, checksum.as_ref()))
+                .map(|(index, ..)| index)
+                .collect::<Vec<_>>()
+        });
+        ctx.wait(verify.into_actor(self).map(move |result, act, ctx| {
+            let corrupted = result.unwrap_or_default();
+            if let Some(cm) = &mut act.chunk_manager {
+                for &index in &corrupted {
+                    cm.reset_chunk(index);
+                }
+                act.downloaded = cm.chunks.iter().map(|c| c.downloaded).sum();
+                act.progress = cm.get_total_progress();
+            }
+            span.in_scope(|| {
+                if corrupted.is_empty() {
+                    tracing::info!("已完成的块校验通过");
+                } else {
+                    tracing::warn!(chunks = ?corrupted, "已完成的块与记录的校验值不一致，重新下载");
+                }
+            });
+            act.schedule_chunk_downloads(ctx, url, file, id);
+        }));
     }
 }
 
 impl Handler<PauseTask> for DownloadTaskActor {
     type Result = ();
-    fn handle(&mut self, _msg: PauseTask, _ctx: &mut Self::Context) {
-        self.is_paused.store(true, Ordering::SeqCst);
+    fn handle(&mut self, _msg: PauseTask, ctx: &mut Self::Context) {
+        // 进行中的请求立即中止（连接随之释放），并归还下载名额让其他任务开始；
+        // 分块边界写入续传信息，恢复时从各块写到的位置继续
+        self.stop.pause();
         self.status = TaskStatus::Paused;
+        self.permit = None;
+        if let Some(handle) = self.scheduler.take() {
+            ctx.cancel_future(handle);
+        }
+        self.suspend_deadline(ctx);
+        if let (Some(cm), Some(fi)) = (&mut self.chunk_manager, &self.file_info) {
+            if self.config.enable_resume {
+                if let Err(e) = cm.save_resume_info(self.id, &self.url, fi) {
+                    self.span.in_scope(|| tracing::warn!(error = %e, "暂停时保存断点续传信息失败"));
+                }
+            }
+        }
+        self.flush_usage();
     }
 }
 
 impl Handler<CancelTask> for DownloadTaskActor {
     type Result = ();
-    fn handle(&mut self, _msg: CancelTask, _ctx: &mut Self::Context) {
-        self.is_cancelled.store(true, Ordering::SeqCst);
+    fn handle(&mut self, _msg: CancelTask, ctx: &mut Self::Context) {
+        self.stop.cancel();
         self.status = TaskStatus::Cancelled;
+        self.permit = None;
+        if let Some(handle) = self.scheduler.take() {
+            ctx.cancel_future(handle);
+        }
+        self.flush_usage();
+        self.release_quota();
         if let Some(cm) = &self.chunk_manager {
             cm.cleanup_temp_files();
+            cm.remove_resume_info(self.id);
+            if cm.sequential {
+                let _ = std::fs::remove_file(cm.merged_path(&self.file));
+            }
         }
+        ctx.stop();
     }
 }
 
@@ -163,84 +419,382 @@ impl Handler<UpdateProgress> for DownloadTaskActor {
 
 impl Handler<MarkCompleted> for DownloadTaskActor {
     type Result = ();
-    fn handle(&mut self, _msg: MarkCompleted, _ctx: &mut Self::Context) {
-        self.status = TaskStatus::Completed;
-        if let Some(permit) = self.permit.take() {
-            drop(permit);
+    fn handle(&mut self, _msg: MarkCompleted, ctx: &mut Self::Context) {
+        // 配置了校验和文件时先读取期望的 SHA-256，再按下面指定了校验和的流程校验
+        if self.options.checksum.is_none() && !self.config.checksum_url.is_empty() && !self.synced {
+            let transport = self.transport.clone();
+            let template = self.config.checksum_url.clone();
+            let url = self.url.clone();
+            let settings = RequestSettings::new(&self.config, &self.options);
+            let fetch = async move { super::checksum::fetch(transport.as_ref(), &template, &url, &settings).await }
+                .into_actor(self)
+                .map(|result, act, ctx| match result {
+                    Ok(expected) => {
+                        act.options.checksum = Some(expected);
+                        ctx.address().do_send(MarkCompleted);
+                    }
+                    Err(error) => ctx.address().do_send(MarkFailed { error }),
+                });
+            ctx.spawn(fetch);
+            return;
         }
-        self.notify_manager_completed();
+        let metadata = FileMetadata::new(&self.config, self.file_info.as_ref());
+        let need_finish = (self.config.fsync_on_complete || !metadata.is_empty()) && !self.synced;
+        if !need_finish && self.options.checksum.is_none() {
+            self.span.in_scope(|| tracing::info!(downloaded = self.downloaded, "下载完成"));
+            self.status = TaskStatus::Completed;
+            if let Some(permit) = self.permit.take() {
+                drop(permit);
+            }
+            self.notify_manager_completed();
+            ctx.stop();
+            return;
+        }
+
+        // 上报完成前在阻塞线程池中完成收尾：指定了校验和时按 `finalize_speed_limit_kb` 限速计算 SHA-256，
+        // 校验通过后设置文件属性（修改时间、权限、只读），最后落盘（fsync 文件和所在目录），都成功才算完成
+        let file = self.file.clone();
+        let expected = self.options.checksum.clone();
+        let limit = self.config.finalize_speed_limit_kb * 1024;
+        let fsync = self.config.fsync_on_complete;
+        let mut progress = self.phase_reporter(TaskPhase::Verifying);
+        let verify = async move {
+            tokio::task::spawn_blocking(move || {
+                let checked = match expected {
+                    Some(expected) => {
+                        let size = std::fs::metadata(&file).map(|m| m.len()).unwrap_or(0);
+                        progress(0, size);
+                        let actual = crate::core::history::sha256_file_limited(&file, &mut SpeedLimiter::new(limit), |read| progress(read, size))?;
+                        if !actual.eq_ignore_ascii_case(expected.trim()) {
+                            return Ok(Some((expected, actual)));
+                        }
+                        Some((expected, actual))
+                    }
+                    None => None,
+                };
+                if need_finish {
+                    metadata.apply(Path::new(&file))?;
+                    if fsync {
+                        sync_durable(Path::new(&file))?;
+                    }
+                }
+                Ok(checked)
+            })
+            .await
+        }
+        .into_actor(self)
+        .map(move |result, act, ctx| {
+            let result = result
+                .map_err(|e| DownloadError::Unknown(format!("校验任务异常: {}", e).into()))
+                .and_then(|r| r);
+            match result {
+                Ok(None) => {
+                    act.synced = true;
+                    ctx.address().do_send(MarkCompleted);
+                }
+                Ok(Some((expected, actual))) if actual.eq_ignore_ascii_case(expected.trim()) => {
+                    act.synced = true;
+                    act.options.checksum = None;
+                    ctx.address().do_send(MarkCompleted);
+                }
+                Ok(Some((expected, actual))) => {
+                    ctx.address().do_send(MarkFailed {
+                        error: DownloadError::ChecksumMismatch { expected, actual },
+                    });
+                }
+                Err(error) => ctx.address().do_send(MarkFailed { error }),
+            }
+        });
+        ctx.spawn(verify);
+    }
+}
+
+impl Handler<UpdateConfig> for DownloadTaskActor {
+    type Result = ();
+    fn handle(&mut self, msg: UpdateConfig, _ctx: &mut Self::Context) {
+        // 限速器被正在进行的连接共享，原地修改即可立即生效
+        let old_limit = self.speed_limit();
+        let speed_limit_kb = msg.0.speed_limit_kb;
+        self.config.speed_limit_kb = speed_limit_kb;
+        if self.speed_limit() != old_limit {
+            self.global_limiter.lock().unwrap().set_max_speed(self.speed_limit());
+        }
+        // 自适应调整时保留已有的调整结果，只按新配置重新限制范围
+        self.tuning = match self.tuning.take() {
+            Some(tuning) if msg.0.adaptive_chunking => Some(ThroughputController::new(&self.url, &msg.0, Some(tuning.tuning))),
+            _ => None,
+        };
+        if let Some(cm) = &mut self.chunk_manager {
+            let concurrency = self.tuning.as_ref().map_or(msg.0.thread_count, |t| t.tuning.concurrency);
+            cm.set_max_concurrent_chunks(concurrency);
+        }
+        self.config = msg.0;
+    }
+}
+
+impl Handler<SetBandwidthShare> for DownloadTaskActor {
+    type Result = ();
+    fn handle(&mut self, msg: SetBandwidthShare, _ctx: &mut Self::Context) {
+        let old_limit = self.speed_limit();
+        self.bandwidth_share = msg.0;
+        if self.speed_limit() != old_limit {
+            self.global_limiter.lock().unwrap().set_max_speed(self.speed_limit());
+        }
+    }
+}
+
+impl Handler<RecordRetry> for DownloadTaskActor {
+    type Result = ();
+    fn handle(&mut self, msg: RecordRetry, _ctx: &mut Self::Context) {
+        self.notify_manager_retry(msg.error_kind);
     }
 }
 
 impl Handler<MarkFailed> for DownloadTaskActor {
     type Result = ();
-    fn handle(&mut self, msg: MarkFailed, _ctx: &mut Self::Context) {
+    fn handle(&mut self, msg: MarkFailed, ctx: &mut Self::Context) {
+        // 暂停或取消后中止的请求返回的错误不算失败，暂停的任务之后还要恢复
+        if matches!(self.status, TaskStatus::Paused | TaskStatus::Cancelled) {
+            return;
+        }
+        self.span.in_scope(|| tracing::error!(error = %msg.error, kind = msg.error.kind(), "下载失败"));
         self.status = TaskStatus::Failed(msg.error.to_string());
+        self.release_quota();
         if let Some(permit) = self.permit.take() {
             drop(permit);
         }
         self.notify_manager_failed(msg.error);
+        ctx.stop();
     }
 }
 
 impl Handler<DownloadChunkMsg> for DownloadTaskActor {
     type Result = ResponseActFuture<Self, Result<(), DownloadError>>;
     
-    fn handle(&mut self, msg: DownloadChunkMsg, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, msg: DownloadChunkMsg, ctx: &mut Self::Context) -> Self::Result {
+        let actor_addr = ctx.address();
         let config = self.config.clone();
-        let is_paused = self.is_paused.clone();
+        let stop = self.stop.clone();
         let limiter = self.global_limiter.clone();
+        let settings = RequestSettings::new(&self.config, &self.options);
+        let transferred = self.transferred.clone();
+        let transport = self.transport.clone();
+        let offset = self.range_offset;
+        let (chunk_path, progress) = match &mut self.chunk_manager {
+            Some(cm) => match cm.begin_chunk(msg.chunk_index) {
+                Some(progress) => (cm.get_chunk_file_path(msg.chunk_index), progress),
+                None => return Box::pin(actix::fut::ready(Err(DownloadError::unknown("分块不存在")))),
+            },
+            None => return Box::pin(actix::fut::ready(Err(DownloadError::unknown("分块管理器未初始化")))),
+        };
+        let final_range = progress.clone();
+        let span = tracing::info_span!(parent: &self.span, "chunk", chunk_index = msg.chunk_index, start = msg.start, end = msg.end);
+        let chunk_index = msg.chunk_index;
         Box::pin(async move {
-            if is_paused.load(Ordering::SeqCst) {
-                return Err(DownloadError::Paused);
-            }
-            let mut retry_context = super::retry::RetryContext::new(
-                config.retry_count as u32,
-                std::time::Duration::from_secs(config.retry_delay),
-                std::time::Duration::from_secs(config.retry_max_delay)
-            );
+            let mut retry_context = super::retry::RetryContext::from_config(&config).for_url(&msg.url);
             loop {
-                if is_paused.load(Ordering::SeqCst) {
-                    return Err(DownloadError::Paused);
-                }
-                match perform_chunk_download(&msg.url, &msg.file, msg.chunk_index, msg.start, msg.end, limiter.clone()).await {
-                    Ok(()) => return Ok(()),
+                stop.check()?;
+                let attempt = Instant::now();
+                // 暂停或取消时立即中止进行中的请求，已收到的数据保留在块文件中
+                match perform_chunk_download(transport.as_ref(), &msg.url, &settings, &chunk_path, &progress, offset, limiter.clone(), &transferred, &stop).await {
+                    Ok(checksum) => {
+                        tracing::debug!("分块下载完成");
+                        return Ok((attempt.elapsed(), checksum));
+                    }
+                    Err(e @ (DownloadError::Paused | DownloadError::Cancelled)) => return Err(e),
                     Err(e) => {
-                        if retry_context.should_retry(&e) {
-                            retry_context.record_retry();
-                            tokio::time::sleep(retry_context.get_next_delay()).await;
+                        if let Some(delay) = retry_context.next_retry(&e) {
+                            tracing::warn!(error = %e, retry = retry_context.current_retries(), delay_ms = delay.as_millis() as u64, "分块下载失败，准备重试");
+                            actor_addr.do_send(RecordRetry { error_kind: Some(e.kind()) });
+                            stop.sleep(delay).await?;
                         } else {
-                            return Err(e);
+                            tracing::error!(error = %e, "分块下载失败");
+                            return Err(e.with_chunk(chunk_index));
                         }
                     }
                 }
             }
-        }.into_actor(self).map(move |result, act, ctx| {
+        }.instrument(span).into_actor(self).map(move |result, act, ctx| {
             match result {
-                Ok(()) => {
+                Ok((elapsed, checksum)) => {
+                    // 下载期间块可能被分割，按实际的结束位置计算
+                    let bytes = final_range.end.load(Ordering::SeqCst) - final_range.start + 1;
+                    if let Some(tuning) = &mut act.tuning {
+                        if tuning.record_chunk(bytes, elapsed) {
+                            if let Some(cm) = &mut act.chunk_manager {
+                                cm.set_max_concurrent_chunks(tuning.tuning.concurrency);
+                            }
+                        }
+                    }
+                    let mut completed = false;
                     if let Some(cm) = &mut act.chunk_manager {
                         cm.mark_chunk_completed(msg.chunk_index);
+                        cm.record_checksum(msg.chunk_index, checksum);
+                        act.downloaded = cm.chunks.iter().map(|c| c.downloaded).sum();
+                        act.progress = cm.get_total_progress();
                         if act.config.enable_resume {
                             if let Some(fi) = &act.file_info {
                                 cm.save_resume_info(act.id, &act.url, fi).ok();
                             }
                         }
-                        if cm.is_completed() {
-                            act.merge_chunks_and_complete();
+                        completed = cm.is_completed();
+                        // 按文件顺序下载时把新连上的开头部分写入预览文件，最后一块交给合并
+                        if cm.sequential && !completed {
+                            if let Err(e) = cm.merge_ready_prefix(&cm.merged_path(&act.file)) {
+                                act.span.in_scope(|| tracing::warn!(error = %e, "写入预览文件失败"));
+                            }
                         }
                     }
+                    // 分块下载按已完成块上报进度，速度取开始以来的平均值；
+                    // 小分块完成得很快，节流后再通知管理器，最后一块总是上报
+                    act.speed = act.start_time
+                        .map(|t| t.elapsed().as_secs_f64())
+                        .filter(|secs| *secs > 0.0)
+                        .map_or(0, |secs| (act.downloaded as f64 / secs) as u64);
+                    if act.progress_throttle.record(bytes).is_some() || completed {
+                        act.notify_manager_progress();
+                    }
+                    if completed {
+                        act.merge_chunks_and_complete(ctx);
+                    }
                 },
+                // 暂停或取消的块回到等待状态，已写入块文件的部分恢复时继续
+                Err(DownloadError::Paused | DownloadError::Cancelled) => {
+                    if let Some(cm) = &mut act.chunk_manager {
+                        cm.release_chunk(msg.chunk_index);
+                    }
+                }
                 Err(e) => {
                     if let Some(cm) = &mut act.chunk_manager {
-                        cm.mark_chunk_failed(msg.chunk_index);
+                        cm.mark_chunk_failed(msg.chunk_index, e.clone());
                         act.check_download_status_and_retry(ctx);
                     }
-                    if let DownloadError::Paused = e {
-                        act.status = TaskStatus::Paused;
-                    }
+                    act.notify_manager_chunk_failed(msg.chunk_index, &e);
                 }
             }
             Ok(())
         }))
     }
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::core::task::transport::HttpResponse;
+    use crate::core::task::chunk_manager::ChunkProgress;
+    use crate::core::task::util::{ChunkChecksum, SpeedLimiter};
+    use crate::core::task::TaskOptions;
+    use futures::StreamExt;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// 只接受指定 User-Agent 的服务器，其他请求返回 403
+    struct UaTransport {
+        accepted: &'static str,
+    }
+
+    #[async_trait::async_trait(?Send)]
+    impl HttpTransport for UaTransport {
+        async fn send(&self, request: HttpRequest<'_>) -> Result<HttpResponse, DownloadError> {
+            let user_agent = request.settings.headers().into_iter().find(|(n, _)| n == "User-Agent").map(|(_, v)| v);
+            Ok(HttpResponse {
+                status: if user_agent.as_deref() == Some(self.accepted) { 200 } else { 403 },
+                headers: vec![("content-length".to_string(), "10".to_string())],
+                body: futures::stream::empty().boxed_local(),
+            })
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_user_agent_fallback() {
+        let config = Config {
+            fallback_user_agents: vec!["Wget/1.21".to_string(), "curl/8.0".to_string()],
+            ..Default::default()
+        };
+        let transport = UaTransport { accepted: "curl/8.0" };
+        let mut settings = RequestSettings::new(&config, &TaskOptions::default());
+        let info = get_file_info_with_fallback(&transport, "http://example.com/a", &mut settings).await.unwrap();
+        assert_eq!(info.size, 10);
+        assert_eq!(settings.user_agent, "curl/8.0");
+
+        // 没有可用的 User-Agent 时返回原来的 403
+        let mut settings = RequestSettings::new(&Config::default(), &TaskOptions::default());
+        let error = get_file_info_with_fallback(&transport, "http://example.com/a", &mut settings).await.unwrap_err();
+        assert_eq!(error.status_code(), Some(403));
+        assert_eq!(settings.user_agent, "MultiDown/1.0");
+    }
+
+    /// 按 Range 返回固定内容；`stall` 为真时发送前 4 个字节后不再发送，模拟下载中途暂停
+    struct RangeTransport {
+        body: &'static [u8],
+        stall: std::cell::Cell<bool>,
+        ranges: std::cell::RefCell<Vec<(u64, u64)>>,
+    }
+
+    #[async_trait::async_trait(?Send)]
+    impl HttpTransport for RangeTransport {
+        async fn send(&self, request: HttpRequest<'_>) -> Result<HttpResponse, DownloadError> {
+            let (start, end) = request.range.unwrap();
+            self.ranges.borrow_mut().push((start, end));
+            let data = &self.body[start as usize..=end as usize];
+            let body = if self.stall.get() {
+                futures::stream::iter([Ok(bytes::Bytes::from_static(&data[..4]))]).chain(futures::stream::pending()).boxed_local()
+            } else {
+                futures::stream::iter([Ok(bytes::Bytes::from_static(data))]).boxed_local()
+            };
+            Ok(HttpResponse { status: 206, headers: Vec::new(), body })
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_chunk_resumes_after_pause() {
+        let path = std::env::temp_dir().join(format!("multidown_chunk_{}", uuid::Uuid::new_v4()));
+        let chunk_path = path.to_string_lossy().into_owned();
+        let transport = RangeTransport { body: b"0123456789", stall: true.into(), ranges: Default::default() };
+        let settings = RequestSettings::new(&Config::default(), &TaskOptions::default());
+        let progress = ChunkProgress { start: 0, end: 9.into(), written: 0.into() };
+        let limiter = Arc::new(std::sync::Mutex::new(SpeedLimiter::new(0)));
+        let transferred = std::sync::atomic::AtomicU64::new(0);
+
+        // 暂停时立即中止卡住的响应，已收到的数据写入块文件
+        let stop = StopSignal::default();
+        let pause = stop.clone();
+        actix_rt::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            pause.pause();
+        });
+        let result = perform_chunk_download(&transport, "http://example.com/a", &settings, &chunk_path, &progress, 0, limiter.clone(), &transferred, &stop).await;
+        assert!(matches!(result, Err(DownloadError::Paused)));
+        assert_eq!(std::fs::read(&path).unwrap(), b"0123");
+
+        // 恢复时只请求剩下的部分，校验值覆盖整个块
+        transport.stall.set(false);
+        let checksum = perform_chunk_download(&transport, "http://example.com/a", &settings, &chunk_path, &progress, 0, limiter, &transferred, &StopSignal::default()).await.unwrap();
+        assert_eq!(*transport.ranges.borrow(), vec![(0, 9), (4, 9)]);
+        assert_eq!(std::fs::read(&path).unwrap(), b"0123456789");
+        assert_eq!(checksum, ChunkChecksum::of(b"0123456789"));
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_adopt_partial() {
+        let path = std::env::temp_dir().join(format!("multidown_partial_{}", uuid::Uuid::new_v4()));
+        let file = path.to_string_lossy().into_owned();
+        std::fs::write(&path, b"0123").unwrap();
+        let info = |size, last_modified: Option<&str>| FileInfo {
+            size,
+            supports_range: true,
+            last_modified: last_modified.map(str::to_string),
+            etag: Some("\"v1\"".to_string()),
+        };
+
+        assert_eq!(adopt_partial(&file, &info(10, Some("Wed, 21 Oct 2015 07:28:00 GMT"))).unwrap(), 4);
+        assert_eq!(if_range(&info(10, None)).as_deref(), Some("\"v1\""));
+        // 本地文件已经完整、服务器上的文件更新过、不支持 Range 时拒绝
+        assert!(matches!(adopt_partial(&file, &info(4, None)), Err(DownloadError::FileExists(_))));
+        assert!(adopt_partial(&file, &info(10, Some("Fri, 01 Jan 2100 00:00:00 GMT"))).is_err());
+        assert!(adopt_partial(&file, &FileInfo { supports_range: false, ..info(10, None) }).is_err());
+
+        let _ = std::fs::remove_file(path);
+    }
+}
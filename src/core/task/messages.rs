@@ -4,10 +4,16 @@ use uuid::Uuid;
 use crate::core::error::DownloadError;
 use super::util::FileInfo;
 
+/// 下载名额：全局的并发名额，任务所属客户端限制了并发数时还有该客户端的名额，随任务一起释放
+pub struct DownloadPermit {
+    pub slot: OwnedSemaphorePermit,
+    pub client: Option<OwnedSemaphorePermit>,
+}
+
 /// 启动任务
 pub struct StartTask {
     pub manager_addr: Addr<crate::core::actor_manager::DownloadManagerActor>,
-    pub permit: OwnedSemaphorePermit,
+    pub permit: DownloadPermit,
 }
 impl Message for StartTask { type Result = (); }
 
@@ -16,8 +22,14 @@ pub struct StartChunkedDownload {
     pub url: String,
     pub file: String,
     pub total_size: u64,
+    /// 只下载部分内容时范围的起点，分块位置相对于它
+    pub offset: u64,
     pub task_id: Uuid,
     pub file_info: FileInfo,
+    /// 获取文件信息时换用成功的 User-Agent，分块请求沿用它
+    pub user_agent: Option<String>,
+    /// 续传已有的部分文件，合并结果追加到它的末尾
+    pub append: bool,
 }
 impl Message for StartChunkedDownload { type Result = (); }
 
@@ -54,6 +66,21 @@ impl Message for UpdateProgress { type Result = (); }
 pub struct MarkCompleted;
 impl Message for MarkCompleted { type Result = (); }
 
+/// 应用热重载后的配置（限速和线程数立即生效，其余设置用于之后的请求和重试）
+pub struct UpdateConfig(pub crate::config::Config);
+impl Message for UpdateConfig { type Result = (); }
+
+/// 按总限速分到的带宽（B/s），0 表示没有总限速，只按任务自己的限速
+pub struct SetBandwidthShare(pub u64);
+impl Message for SetBandwidthShare { type Result = (); }
+
+/// 记录一次重试
+pub struct RecordRetry {
+    /// 导致重试的错误类别（见 `DownloadError::kind`）
+    pub error_kind: Option<&'static str>,
+}
+impl Message for RecordRetry { type Result = (); }
+
 /// 标记任务为失败
 pub struct MarkFailed {
     pub error: DownloadError,
@@ -8,6 +8,19 @@
 //! - `download`: 实际的下载逻辑
 //! - `chunk_manager`: 分块下载管理器
 //! - `retry`: 重试逻辑
+//! - `resume`: 断点续传信息的紧凑二进制格式
+//! - `http`: HTTP 请求发送和 `--trace-http` 调试跟踪
+//! - `proxy`: 通过 HTTP 代理的 `CONNECT` 隧道连接
+//! - `dns`: 带缓存的主机名解析，支持 DNS-over-HTTPS
+//! - `transport`: HTTP 后端抽象 `HttpTransport`，统一应用超时、User-Agent 和请求头
+//! - `breaker`: 按主机熔断，主机连续失败时推迟发往它的请求
+//! - `options`: 单个任务的选项覆盖 `TaskOptions`
+//! - `checksum`: 按 `checksum_url` 从校验和文件读取期望的 SHA-256
+//! - `tuning`: 根据实测吞吐量自适应调整分块大小和连接数
+//! - `protocol`: 非 HTTP 协议的扩展点 `ProtocolHandler`，库外的 crate 可以注册新的地址格式
+//! - `rsync`: rsync:// 地址交给外部 rsync 程序下载（内置的协议处理器）
+//! - `resolver`: 分享链接解析，开始下载前把网盘链接转换成直链（`UrlResolver`）
+//! - `uring`: io_uring 文件写入（`io-uring` 特性，仅 Linux）
 //! - `util`: 工具类，如 `BufferManager`
 
 pub mod actor;
@@ -17,11 +30,29 @@ pub mod handlers;
 pub mod download;
 pub mod chunk_manager;
 pub mod retry;
+pub mod resume;
+pub mod http;
+pub mod proxy;
+pub mod dns;
+pub mod transport;
+pub mod breaker;
+pub mod options;
+pub mod checksum;
+pub mod tuning;
+pub mod protocol;
+pub mod rsync;
+pub mod resolver;
 pub mod util;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+pub mod uring;
 
 // 导出核心组件，方便外部使用
 pub use actor::DownloadTaskActor;
 pub use messages::{StartTask, PauseTask, CancelTask};
-pub use state::TaskStatus;
-pub use self::util::{FileInfo, BufferManager};
-pub use self::retry::{RetryStrategy, RetryContext, RetryStats}; 
\ No newline at end of file
+pub use state::{TaskPhase, TaskStatus};
+pub use options::{ByteRange, DownloadRequest, DownloadRequestBuilder, TaskOptions};
+pub use transport::{AwcTransport, HttpTransport};
+pub use protocol::{ProtocolHandler, ProtocolHandlers};
+pub use resolver::{UrlResolver, UrlResolvers};
+pub use self::util::{ChunkChecksum, FileInfo, BufferManager};
+pub use self::retry::{RetryContext, RetryStats}; 
\ No newline at end of file
@@ -0,0 +1,283 @@
+//! 单个任务的选项覆盖
+//!
+//! 通过 `CreateTask` 传入，优先级高于全局配置和 URL 规则。命令行和库的调用方都用
+//! [`DownloadRequest::builder`] 构造请求：
+//!
+//! ```ignore
+//! let request = DownloadRequest::builder()
+//!     .url("https://example.com/a.iso")
+//!     .output("downloads/a.iso")
+//!     .header("Authorization", "Bearer {secret:example-token}")
+//!     .checksum("ba7816bf...")
+//!     .priority(TaskPriority::High)
+//!     .build()?;
+//! manager.send(CreateTask(request)).await??;
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::core::actor_manager::TaskPriority;
+use crate::core::error::DownloadError;
+use crate::utils::size::parse_size;
+
+/// 任务选项，未设置的项沿用全局配置
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TaskOptions {
+    /// 线程数（分块下载时同时进行的连接数）
+    pub thread_count: Option<usize>,
+    /// 下载速度限制（KB/s），0 表示不限速
+    pub speed_limit_kb: Option<u64>,
+    /// 附加的请求头
+    pub headers: Vec<(String, String)>,
+    /// 期望的 SHA-256 校验和（十六进制），下载完成后校验
+    pub checksum: Option<String>,
+    /// 输出文件名，替换 `CreateTask::file` 中的文件名部分
+    pub output_name: Option<String>,
+    /// 排队时的优先级，优先级高的任务先获得下载名额，也决定分配总限速时的默认权重
+    pub priority: TaskPriority,
+    /// 分配总限速（`total_speed_limit_kb`）时的权重，未设置时由优先级决定
+    pub weight: Option<u32>,
+    /// 只下载文件的这一部分
+    pub range: Option<ByteRange>,
+    /// 目标文件已存在时从它的末尾续传
+    pub continue_partial: Option<bool>,
+    /// 分块临时文件和断点续传信息的存放目录（`move --temp-dir` 换到其他磁盘时设置）
+    pub temp_dir: Option<String>,
+}
+
+/// 创建任务的请求
+#[derive(Debug, Clone, PartialEq)]
+pub struct DownloadRequest {
+    pub url: String,
+    /// 保存路径
+    pub file: String,
+    pub options: TaskOptions,
+    /// 任务标签
+    pub tags: Vec<String>,
+    /// 提交任务的客户端（守护进程按 API 密钥识别），受该客户端的配额限制
+    pub client: Option<String>,
+}
+
+impl DownloadRequest {
+    pub fn builder() -> DownloadRequestBuilder {
+        DownloadRequestBuilder::default()
+    }
+}
+
+/// [`DownloadRequest`] 的构造器，URL 和保存路径必须设置，其余沿用全局配置
+#[derive(Debug, Clone, Default)]
+pub struct DownloadRequestBuilder {
+    url: Option<String>,
+    output: Option<String>,
+    options: TaskOptions,
+    tags: Vec<String>,
+    client: Option<String>,
+}
+
+impl DownloadRequestBuilder {
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.url = Some(url.into());
+        self
+    }
+
+    /// 保存路径
+    pub fn output(mut self, path: impl Into<String>) -> Self {
+        self.output = Some(path.into());
+        self
+    }
+
+    /// 替换保存路径中的文件名部分
+    pub fn output_name(mut self, name: impl Into<String>) -> Self {
+        self.options.output_name = Some(name.into());
+        self
+    }
+
+    /// 添加一个请求头
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.options.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// 添加多个请求头
+    pub fn headers(mut self, headers: impl IntoIterator<Item = (String, String)>) -> Self {
+        self.options.headers.extend(headers);
+        self
+    }
+
+    /// 期望的 SHA-256 校验和（十六进制）
+    pub fn checksum(mut self, checksum: impl Into<String>) -> Self {
+        self.options.checksum = Some(checksum.into());
+        self
+    }
+
+    pub fn priority(mut self, priority: TaskPriority) -> Self {
+        self.options.priority = priority;
+        self
+    }
+
+    /// 分配总限速时的权重
+    pub fn weight(mut self, weight: u32) -> Self {
+        self.options.weight = Some(weight);
+        self
+    }
+
+    pub fn thread_count(mut self, count: usize) -> Self {
+        self.options.thread_count = Some(count);
+        self
+    }
+
+    /// 下载速度限制（KB/s），0 表示不限速
+    pub fn speed_limit_kb(mut self, limit: u64) -> Self {
+        self.options.speed_limit_kb = Some(limit);
+        self
+    }
+
+    pub fn range(mut self, range: Option<ByteRange>) -> Self {
+        self.options.range = range;
+        self
+    }
+
+    /// 添加一个标签
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    pub fn tags(mut self, tags: impl IntoIterator<Item = String>) -> Self {
+        self.tags.extend(tags);
+        self
+    }
+
+    /// 提交任务的客户端，见 [`crate::config::clients`]
+    pub fn client(mut self, client: impl Into<String>) -> Self {
+        self.client = Some(client.into());
+        self
+    }
+
+    /// 整体替换任务选项（已设置的请求头等会被覆盖）
+    pub fn options(mut self, options: TaskOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn build(self) -> Result<DownloadRequest, DownloadError> {
+        let url = self.url.ok_or_else(|| DownloadError::InvalidUrl("没有设置下载地址".into()))?;
+        let file = self.output.ok_or_else(|| DownloadError::unknown("没有设置保存路径"))?;
+        if file.is_empty() {
+            return Err(DownloadError::unknown("保存路径不能为空"));
+        }
+        Ok(DownloadRequest { url, file, options: self.options, tags: self.tags, client: self.client })
+    }
+}
+
+/// 要下载的字节范围 `[start, end)`，`end` 为空表示到文件末尾
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: Option<u64>,
+}
+
+impl ByteRange {
+    /// 解析 `100M-200M`、`1G-`，包含起点、不包含终点，支持大小单位
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let (start, end) = input
+            .split_once('-')
+            .ok_or_else(|| format!("无效的范围: {}（如 100M-200M 或 1G-）", input))?;
+        let start = if start.trim().is_empty() { 0 } else { parse_size(start)? };
+        let end = match end.trim() {
+            "" => None,
+            end => Some(parse_size(end)?),
+        };
+        if end.is_some_and(|end| end <= start) {
+            return Err(format!("范围的终点必须大于起点: {}", input));
+        }
+        Ok(Self { start, end })
+    }
+
+    /// 按文件大小（0 表示未知）得到实际下载的 `[start, end)`，终点超出文件大小时截到末尾
+    pub fn resolve(&self, size: u64) -> Result<(u64, u64), DownloadError> {
+        let end = match (self.end, size) {
+            (None, 0) => return Err(DownloadError::unknown("文件大小未知，下载部分内容时需要指定范围的终点")),
+            (None, size) => size,
+            (Some(end), 0) => end,
+            (Some(end), size) => end.min(size),
+        };
+        if self.start >= end {
+            return Err(DownloadError::unknown(format!("范围起点 {} 超出文件大小 {}", self.start, size)));
+        }
+        Ok((self.start, end))
+    }
+}
+
+impl TaskOptions {
+    /// 将任务选项覆盖到配置上
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(v) = self.thread_count {
+            config.thread_count = v;
+        }
+        if let Some(v) = self.speed_limit_kb {
+            config.speed_limit_kb = v;
+        }
+        if let Some(v) = self.continue_partial {
+            config.continue_partial = v;
+        }
+        if let Some(v) = &self.temp_dir {
+            config.temp_dir = v.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_task_options() {
+        let options = TaskOptions {
+            thread_count: Some(16),
+            priority: TaskPriority::High,
+            ..Default::default()
+        };
+        let mut config = Config::default();
+        options.apply(&mut config);
+        assert_eq!(config.thread_count, 16);
+        assert_eq!(config.speed_limit_kb, Config::default().speed_limit_kb);
+
+        let json = serde_json::to_string(&options).unwrap();
+        assert!(json.contains("\"high\""));
+
+        let range = ByteRange::parse("100M-200M").unwrap();
+        assert_eq!(range, ByteRange { start: 100 << 20, end: Some(200 << 20) });
+        assert_eq!(range.resolve(150 << 20).unwrap(), (100 << 20, 150 << 20));
+        assert!(range.resolve(50 << 20).is_err());
+        let open = ByteRange::parse("1K-").unwrap();
+        assert_eq!(open.resolve(4096).unwrap(), (1024, 4096));
+        assert!(open.resolve(0).is_err());
+        assert!(ByteRange::parse("200M-100M").is_err());
+        assert!(ByteRange::parse("100M").is_err());
+
+        let request = DownloadRequest::builder()
+            .url("https://example.com/a.iso")
+            .output("downloads/a.iso")
+            .header("Referer", "https://example.com/")
+            .checksum("abc")
+            .priority(TaskPriority::High)
+            .tag("nightly")
+            .build()
+            .unwrap();
+        assert_eq!(request.file, "downloads/a.iso");
+        assert_eq!(request.options.headers, vec![("Referer".to_string(), "https://example.com/".to_string())]);
+        assert_eq!(request.options.checksum.as_deref(), Some("abc"));
+        assert_eq!(request.options.priority, TaskPriority::High);
+        assert_eq!(request.tags, vec!["nightly"]);
+        assert!(DownloadRequest::builder().output("a.iso").build().is_err());
+        assert!(DownloadRequest::builder().url("https://example.com/a.iso").build().is_err());
+
+        // 旧的会话文件中没有 options 字段，按默认值读取
+        let parsed: TaskOptions = serde_json::from_str("{}").unwrap();
+        assert_eq!(parsed, TaskOptions::default());
+        assert_eq!(parsed.priority, TaskPriority::Normal);
+    }
+}
@@ -0,0 +1,217 @@
+//! 非 HTTP 协议的扩展点：`ProtocolHandler`
+//!
+//! HTTP(S) 和 FTP 由内置的分块下载处理，其他协议交给注册在管理器中的协议处理器：处理器只负责把文件写到
+//! 指定位置，排队、进度、重试、限速、暂停和取消仍由任务统一处理，库外的 crate 不需要修改 `core`
+//! 就能加上新的地址格式（例如公司内部的制品库）：
+//!
+//! ```ignore
+//! let manager = DownloadManagerActor::new(config);
+//! manager.register_protocol(Rc::new(ArtifactStore::new()));
+//! let manager = manager.start();
+//! ```
+//!
+//! 开始下载时按解析出的 URL 依次询问 [`ProtocolHandler::handles`]，后注册的优先，
+//! 通常只看 scheme，也可以按主机或路径选择；没有处理器认领的地址按 HTTP 下载。
+//! 内置的 rsync:// 支持（[`RsyncProgram`]）也是一个处理器。
+//!
+//! - 先写到临时目录中的 `<文件名>.partial`，暂停或中断后再次开始时处理器可以以它为基础续传，完成后移动到保存路径；
+//! - 任务每隔一段时间读取这个文件的大小作为进度；不探测文件信息、不分块。
+
+use actix::Addr;
+use async_trait::async_trait;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use url::Url;
+
+use crate::config::Config;
+use crate::core::error::DownloadError;
+use crate::utils::validator;
+use super::actor::DownloadTaskActor;
+use super::chunk_manager::move_file;
+use super::download::PROGRESS_REPORT_INTERVAL;
+use super::messages::{MarkCompleted, MarkFailed, RecordRetry, UpdateProgress};
+use super::retry::RetryContext;
+use super::rsync::RsyncProgram;
+use super::util::{DownloadQuota, StopSignal};
+
+/// 下载过程中的部分文件，放在临时目录中，文件名由保存路径得出
+pub fn partial_path(temp_root: &Path, file: &str) -> PathBuf {
+    temp_root.join(format!("{}.partial", validator::sanitize_file_name(file)))
+}
+
+/// 一种下载协议：由处理器把文件写到指定位置，任务只负责进度和状态
+#[async_trait(?Send)]
+pub trait ProtocolHandler {
+    /// 是否处理这个地址
+    fn handles(&self, url: &Url) -> bool;
+
+    /// 远端文件的大小，不知道时返回 None
+    async fn size(&self, url: &str) -> Result<Option<u64>, DownloadError>;
+
+    /// 把 `url` 下载到 `dest`，`dest` 中已有的内容可以作为续传的基础；`bwlimit` 为限速（B/s），0 表示不限速
+    ///
+    /// 返回的 future 被丢弃（暂停、取消）时应当停止传输。可以重试的错误（网络中断等）返回
+    /// `DownloadError::NetworkError` 或 `Timeout`，按任务的重试设置重新调用。
+    async fn fetch(&self, url: &str, dest: &Path, bwlimit: u64) -> Result<(), DownloadError>;
+}
+
+/// 注册的协议处理器，由管理器创建并共享给所有任务（包括从会话恢复的），注册后所有任务立即可用
+pub struct ProtocolHandlers {
+    builtin: RefCell<Rc<dyn ProtocolHandler>>,
+    registered: RefCell<Vec<Rc<dyn ProtocolHandler>>>,
+}
+
+impl ProtocolHandlers {
+    /// 只有内置的 rsync 处理器
+    pub fn new(config: &Config) -> Self {
+        Self { builtin: RefCell::new(Rc::new(RsyncProgram::new(config))), registered: RefCell::default() }
+    }
+
+    /// 注册处理器，后注册的优先于先注册的和内置的
+    pub fn register(&self, handler: Rc<dyn ProtocolHandler>) {
+        self.registered.borrow_mut().push(handler);
+    }
+
+    /// 配置热重载后重建内置的处理器，注册的处理器保持不变
+    pub fn reconfigure(&self, config: &Config) {
+        *self.builtin.borrow_mut() = Rc::new(RsyncProgram::new(config));
+    }
+
+    /// 认领这个地址的处理器，没有时按 HTTP 下载
+    pub fn find(&self, url: &str) -> Option<Rc<dyn ProtocolHandler>> {
+        let url = Url::parse(url.trim()).ok()?;
+        let registered = self.registered.borrow();
+        let builtin = self.builtin.borrow();
+        registered.iter().rev().chain(std::iter::once(&*builtin)).find(|handler| handler.handles(&url)).cloned()
+    }
+}
+
+/// 交给协议处理器的一个任务的下载，结构与单线程下载相同
+pub struct HandlerDownload {
+    pub actor_addr: Addr<DownloadTaskActor>,
+    pub url: String,
+    pub file: String,
+    pub config: Config,
+    pub handler: Rc<dyn ProtocolHandler>,
+    /// 限速（B/s），0 表示不限速
+    pub bwlimit: u64,
+    pub transferred: Arc<AtomicU64>,
+    pub stop: StopSignal,
+    pub quota: Arc<DownloadQuota>,
+    pub quota_reserved: Arc<AtomicU64>,
+}
+
+impl HandlerDownload {
+    /// 带重试地下载，结束时向任务 Actor 报告完成或失败；暂停时保留部分文件，取消时删除
+    pub async fn run(self) {
+        let partial = partial_path(&self.config.temp_dir_path(), &self.file);
+        let mut retry_context = RetryContext::from_config(&self.config).for_url(&self.url);
+        let result = loop {
+            let result = match self.stop.check() {
+                Ok(()) => self.stop.guard(self.fetch(&partial)).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(()) => break Ok(()),
+                Err(error @ (DownloadError::Paused | DownloadError::Cancelled)) => break Err(error),
+                Err(error) => {
+                    tracing::warn!(error = %error, "下载失败");
+                    let Some(delay) = retry_context.next_retry(&error) else {
+                        break Err(error);
+                    };
+                    self.actor_addr.do_send(RecordRetry { error_kind: Some(error.kind()) });
+                    let _ = self.stop.sleep(delay).await;
+                }
+            }
+        };
+
+        match result {
+            Ok(()) => self.actor_addr.do_send(MarkCompleted),
+            Err(DownloadError::Paused) => tracing::info!("下载已暂停，保留部分文件"),
+            Err(DownloadError::Cancelled) => {
+                let _ = std::fs::remove_file(&partial);
+            }
+            Err(error) => self.actor_addr.do_send(MarkFailed { error }),
+        }
+    }
+
+    /// 执行一次下载，期间按部分文件的大小上报进度，完成后移动到保存路径
+    async fn fetch(&self, partial: &Path) -> Result<(), DownloadError> {
+        let total = self.handler.size(&self.url).await?.unwrap_or(0);
+        self.quota.reserve_file(self.config.max_file_size, &self.quota_reserved, total)?;
+        if let Some(parent) = partial.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| DownloadError::io_error_with_context("创建临时目录", e))?;
+        }
+        let size = || std::fs::metadata(partial).map(|m| m.len()).unwrap_or(0);
+        let mut last = (size(), Instant::now());
+        let fetch = self.handler.fetch(&self.url, partial, self.bwlimit);
+        tokio::pin!(fetch);
+        let mut ticker = tokio::time::interval(PROGRESS_REPORT_INTERVAL);
+        loop {
+            tokio::select! {
+                result = &mut fetch => break result?,
+                _ = ticker.tick() => {
+                    let downloaded = size();
+                    let (previous, since) = std::mem::replace(&mut last, (downloaded, Instant::now()));
+                    let grown = downloaded.saturating_sub(previous);
+                    self.transferred.fetch_add(grown, Ordering::Relaxed);
+                    let speed = (grown as f64 / since.elapsed().as_secs_f64().max(0.001)) as u64;
+                    let progress = if total > 0 { (downloaded as f32 / total as f32 * 100.0).min(100.0) } else { 0.0 };
+                    self.actor_addr.do_send(UpdateProgress { progress, downloaded, total, speed });
+                }
+            }
+        }
+        let downloaded = size();
+        self.transferred.fetch_add(downloaded.saturating_sub(last.0), Ordering::Relaxed);
+        if total > 0 && downloaded != total {
+            return Err(DownloadError::SizeMismatch { expected: total, actual: downloaded });
+        }
+        move_file(partial, Path::new(&self.file))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 按 scheme 认领地址、把地址写进目标文件的处理器
+    struct Echo(&'static str);
+
+    #[async_trait(?Send)]
+    impl ProtocolHandler for Echo {
+        fn handles(&self, url: &Url) -> bool {
+            url.scheme() == self.0
+        }
+
+        async fn size(&self, _url: &str) -> Result<Option<u64>, DownloadError> {
+            Ok(None)
+        }
+
+        async fn fetch(&self, url: &str, dest: &Path, _bwlimit: u64) -> Result<(), DownloadError> {
+            std::fs::write(dest, format!("{} {}", self.0, url)).map_err(|e| DownloadError::io_error_with_context("写入", e))
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_handler_selection() {
+        let handlers = ProtocolHandlers::new(&Config::default());
+        assert!(handlers.find("rsync://mirror.example.com/a.iso").is_some());
+        assert!(handlers.find("https://example.com/a.iso").is_none());
+        assert!(handlers.find("artifact://store/team/app-1.0.tar").is_none());
+
+        handlers.register(Rc::new(Echo("artifact")));
+        // 后注册的优先，可以接管内置的协议
+        handlers.register(Rc::new(Echo("rsync")));
+        handlers.reconfigure(&Config::default());
+        let dest = std::env::temp_dir().join(format!("multidown_protocol_{}", uuid::Uuid::new_v4()));
+        for url in ["artifact://store/team/app-1.0.tar", "rsync://mirror.example.com/a.iso"] {
+            handlers.find(url).unwrap().fetch(url, &dest, 0).await.unwrap();
+            assert_eq!(std::fs::read_to_string(&dest).unwrap(), format!("{} {}", &url[..url.find(':').unwrap()], url));
+        }
+        let _ = std::fs::remove_file(&dest);
+    }
+}
@@ -0,0 +1,146 @@
+//! HTTP 代理：配置项 `proxy = "http://用户名:{secret:proxy-password}@proxy.example.com:3128"`
+//!
+//! 所有请求（包括 http:// 地址）都先用 `CONNECT` 在代理上建立到目标主机的隧道，再在隧道中发送，
+//! HTTPS 的证书校验照常在隧道内完成。代理地址中的用户名和密码按 Basic 认证放在 `Proxy-Authorization` 中，
+//! 密码可以写成 `{secret:<名称>}`，创建客户端时才从密钥环读取。
+
+use base64::Engine;
+use std::io;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use url::Url;
+
+use crate::core::error::DownloadError;
+use crate::utils::{secrets, validator};
+
+/// 代理响应头的最大长度
+const MAX_RESPONSE_HEAD: usize = 16 * 1024;
+
+/// 解析后的代理设置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Proxy {
+    pub host: String,
+    pub port: u16,
+    /// `Proxy-Authorization` 的值，代理地址中没有用户名时为 None
+    pub authorization: Option<String>,
+}
+
+impl Proxy {
+    /// 解析配置中的代理地址，空字符串表示不使用代理；密码中的占位符从密钥环读取
+    pub fn from_setting(setting: &str) -> Result<Option<Self>, DownloadError> {
+        Self::from_setting_with(setting, secrets::get_secret)
+    }
+
+    /// 解析代理地址，密钥由 `lookup` 提供（配置校验时只检查格式）
+    pub fn from_setting_with<F>(setting: &str, lookup: F) -> Result<Option<Self>, DownloadError>
+    where
+        F: FnMut(&str) -> Result<String, DownloadError>,
+    {
+        if setting.is_empty() {
+            return Ok(None);
+        }
+        let invalid = |reason: &str| DownloadError::Unknown(format!("代理地址 '{}' 无效: {}", setting, reason).into());
+        let url = Url::parse(setting).map_err(|e| invalid(&e.to_string()))?;
+        if url.scheme() != "http" {
+            return Err(invalid("只支持 http:// 代理"));
+        }
+        let host = url.host_str().ok_or_else(|| invalid("缺少主机名"))?.to_string();
+        let port = url.port_or_known_default().ok_or_else(|| invalid("缺少端口"))?;
+        let authorization = match url.username() {
+            "" => None,
+            user => {
+                let password = validator::percent_decode(url.password().unwrap_or_default());
+                let credentials = format!("{}:{}", validator::percent_decode(user), secrets::resolve_with(&password, lookup)?);
+                Some(format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(credentials)))
+            }
+        };
+        Ok(Some(Self { host, port, authorization }))
+    }
+
+    /// 在连接到代理的 `stream` 上建立到 `host:port` 的隧道
+    pub async fn tunnel<S>(&self, stream: &mut S, host: &str, port: u16) -> io::Result<()>
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+    {
+        let target = match host.contains(':') && !host.starts_with('[') {
+            true => format!("[{}]:{}", host, port),
+            false => format!("{}:{}", host, port),
+        };
+        let mut request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", target);
+        if let Some(authorization) = &self.authorization {
+            request.push_str(&format!("Proxy-Authorization: {}\r\n", authorization));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes()).await?;
+
+        // 逐字节读取响应头，不能读走隧道里属于目标主机的数据
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            if head.len() >= MAX_RESPONSE_HEAD {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "代理的响应头过长"));
+            }
+            head.push(stream.read_u8().await?);
+        }
+        let status = std::str::from_utf8(&head)
+            .ok()
+            .and_then(|head| head.split_whitespace().nth(1))
+            .and_then(|status| status.parse::<u16>().ok());
+        match status {
+            Some(200..=299) => Ok(()),
+            Some(407) => Err(io::Error::new(io::ErrorKind::PermissionDenied, "代理要求认证或用户名密码错误（HTTP 407）")),
+            Some(status) => Err(io::Error::other(format!("代理拒绝建立到 {} 的隧道（HTTP {}）", target, status))),
+            None => Err(io::Error::new(io::ErrorKind::InvalidData, "代理返回了无法解析的响应")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proxy_setting() {
+        let lookup = |name: &str| match name {
+            "proxy" => Ok("p@ss:word".to_string()),
+            _ => Err(DownloadError::unknown("missing")),
+        };
+        assert_eq!(Proxy::from_setting_with("", lookup).unwrap(), None);
+        let proxy = Proxy::from_setting_with("http://alice:{secret:proxy}@proxy.example.com:3128", lookup).unwrap().unwrap();
+        assert_eq!((proxy.host.as_str(), proxy.port), ("proxy.example.com", 3128));
+        let expected = base64::engine::general_purpose::STANDARD.encode("alice:p@ss:word");
+        assert_eq!(proxy.authorization, Some(format!("Basic {}", expected)));
+
+        let plain = Proxy::from_setting_with("http://10.0.0.1", lookup).unwrap().unwrap();
+        assert_eq!((plain.port, plain.authorization), (80, None));
+        assert!(Proxy::from_setting_with("socks5://10.0.0.1:1080", lookup).is_err());
+        assert!(Proxy::from_setting_with("http://bob:{secret:other}@proxy:3128", lookup).is_err());
+    }
+
+    #[actix_rt::test]
+    async fn test_tunnel() {
+        let proxy = Proxy { host: "proxy".to_string(), port: 3128, authorization: Some("Basic YTpi".to_string()) };
+        let (mut client, mut server) = tokio::io::duplex(4096);
+        let server = tokio::spawn(async move {
+            let mut buf = vec![0; 1024];
+            let n = server.read(&mut buf).await.unwrap();
+            server.write_all(b"HTTP/1.1 200 Connection established\r\n\r\nhello").await.unwrap();
+            String::from_utf8_lossy(&buf[..n]).into_owned()
+        });
+        proxy.tunnel(&mut client, "example.com", 443).await.unwrap();
+        let request = server.await.unwrap();
+        assert!(request.starts_with("CONNECT example.com:443 HTTP/1.1\r\n"));
+        assert!(request.contains("Proxy-Authorization: Basic YTpi\r\n"));
+        // 隧道中的数据留给之后的读取
+        let mut rest = [0; 5];
+        client.read_exact(&mut rest).await.unwrap();
+        assert_eq!(&rest, b"hello");
+
+        let (mut client, mut server) = tokio::io::duplex(4096);
+        tokio::spawn(async move {
+            let mut buf = vec![0; 1024];
+            let _ = server.read(&mut buf).await;
+            let _ = server.write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n").await;
+        });
+        let error = proxy.tunnel(&mut client, "::1", 80).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+    }
+}
@@ -0,0 +1,333 @@
+//! 分享链接解析：开始下载前把网盘等分享页面的链接转换成文件的直链
+//!
+//! 解析器按主机选择（主机本身及其子域名），内置：
+//!
+//! - `google-drive`：`drive.google.com/file/d/<ID>/view` 等链接转换成 `drive.usercontent.google.com` 的下载地址；
+//!   大文件返回“无法扫描病毒”的确认页面时，从页面的表单中取出确认令牌后再下载；
+//! - `dropbox`：分享链接加上 `dl=1`，直接返回文件而不是预览页面；
+//! - `sourceforge`：`sourceforge.net/projects/<项目>/files/<路径>/download` 转换成
+//!   `downloads.sourceforge.net`，由它重定向到就近的镜像。
+//!
+//! 配置中的 `[resolvers]` 为其他主机指定解析器（例如企业内部的 Drive 代理），或用 `"none"` 关闭某个主机的解析；
+//! 库的使用者可以实现 `UrlResolver` 并用 `UrlResolvers::register` 注册。解析结果只用于请求，
+//! 任务的 URL、会话和历史记录中仍是原始链接，每次开始下载时重新解析（确认令牌会过期）。
+
+use async_trait::async_trait;
+use futures::StreamExt;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use url::Url;
+
+use crate::config::Config;
+use crate::core::error::DownloadError;
+use super::transport::{HttpRequest, HttpTransport, RequestSettings};
+
+/// 关闭解析的解析器名称
+pub const NONE: &str = "none";
+
+/// 内置解析器的名称
+pub const BUILTIN: [&str; 3] = ["google-drive", "dropbox", "sourceforge"];
+
+/// 默认的主机和解析器
+const DEFAULT_HOSTS: [(&str, &str); 4] = [
+    ("drive.google.com", "google-drive"),
+    ("docs.google.com", "google-drive"),
+    ("dropbox.com", "dropbox"),
+    ("sourceforge.net", "sourceforge"),
+];
+
+/// 读取确认页面的大小上限
+const MAX_PAGE_SIZE: usize = 512 * 1024;
+
+/// 把分享链接转换成直链
+#[async_trait(?Send)]
+pub trait UrlResolver {
+    /// 返回直链，`None` 表示链接不需要转换（已经是直链或不认识的格式）
+    async fn resolve(&self, url: &Url, transport: &dyn HttpTransport, settings: &RequestSettings) -> Result<Option<String>, DownloadError>;
+}
+
+/// Google Drive 文件的分享链接
+pub struct GoogleDrive;
+
+impl GoogleDrive {
+    /// 从 `/file/d/<ID>/view`、`/open?id=<ID>`、`/uc?id=<ID>` 中取出文件 ID
+    fn file_id(url: &Url) -> Option<String> {
+        let mut segments = url.path_segments()?;
+        while let Some(segment) = segments.next() {
+            if segment == "d" {
+                return segments.next().filter(|id| !id.is_empty()).map(str::to_string);
+            }
+        }
+        url.query_pairs().find(|(key, _)| key == "id").map(|(_, id)| id.into_owned())
+    }
+}
+
+#[async_trait(?Send)]
+impl UrlResolver for GoogleDrive {
+    async fn resolve(&self, url: &Url, transport: &dyn HttpTransport, settings: &RequestSettings) -> Result<Option<String>, DownloadError> {
+        if url.path().contains("/folders/") {
+            return Err(DownloadError::unknown("不支持下载 Google Drive 文件夹，请分别分享其中的文件"));
+        }
+        let Some(id) = Self::file_id(url) else { return Ok(None) };
+        let direct = Url::parse_with_params(
+            "https://drive.usercontent.google.com/download",
+            [("id", id.as_str()), ("export", "download"), ("confirm", "t")],
+        )
+        .map_err(|e| DownloadError::invalid_url(e.to_string()))?;
+
+        // 可以直接下载时返回文件本身；返回网页时是病毒扫描的确认页面，或者文件没有公开分享
+        let mut response = transport.send(HttpRequest::get(direct.as_str(), settings)).await?;
+        if !response.is_success() {
+            return Err(DownloadError::HttpStatus { status: response.status, host: direct.host_str().map(str::to_string), chunk: None });
+        }
+        if !response.header("content-type").is_some_and(|value| value.starts_with("text/html")) {
+            return Ok(Some(direct.to_string()));
+        }
+        let mut page = Vec::new();
+        while let Some(chunk) = response.body.next().await {
+            page.extend_from_slice(&chunk?);
+            if page.len() >= MAX_PAGE_SIZE {
+                break;
+            }
+        }
+        match confirm_form_url(&String::from_utf8_lossy(&page)) {
+            Some(confirmed) => Ok(Some(confirmed)),
+            None => Err(DownloadError::unknown("Google Drive 返回了网页而不是文件（文件不存在、没有公开分享或超出下载配额）")),
+        }
+    }
+}
+
+/// 从 Drive 的确认页面中取出下载表单：`<form id="download-form" action="...">` 和其中的隐藏字段
+fn confirm_form_url(page: &str) -> Option<String> {
+    let form_start = page.find("id=\"download-form\"")?;
+    let form = &page[page[..form_start].rfind("<form")?..];
+    let form = &form[..form.find("</form>").unwrap_or(form.len())];
+    let mut url = Url::parse(&html_unescape(&attribute(form, "action")?)).ok()?;
+    {
+        let mut query = url.query_pairs_mut();
+        for input in form.split("<input").skip(1) {
+            let input = &input[..input.find('>').unwrap_or(input.len())];
+            if attribute(input, "type").as_deref() != Some("hidden") {
+                continue;
+            }
+            if let (Some(name), Some(value)) = (attribute(input, "name"), attribute(input, "value")) {
+                query.append_pair(&html_unescape(&name), &html_unescape(&value));
+            }
+        }
+    }
+    Some(url.to_string())
+}
+
+/// 取出标签中 `name="value"` 形式的属性
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let pattern = format!(" {}=\"", name);
+    let start = tag.find(&pattern)? + pattern.len();
+    let end = tag[start..].find('"')?;
+    Some(tag[start..start + end].to_string())
+}
+
+fn html_unescape(text: &str) -> String {
+    text.replace("&amp;", "&").replace("&quot;", "\"").replace("&#39;", "'").replace("&lt;", "<").replace("&gt;", ">")
+}
+
+/// Dropbox 的分享链接
+pub struct Dropbox;
+
+#[async_trait(?Send)]
+impl UrlResolver for Dropbox {
+    async fn resolve(&self, url: &Url, _transport: &dyn HttpTransport, _settings: &RequestSettings) -> Result<Option<String>, DownloadError> {
+        let shared = ["/s/", "/scl/", "/sh/"].iter().any(|prefix| url.path().starts_with(prefix));
+        if !shared || url.query_pairs().any(|(key, value)| key == "dl" && value == "1") {
+            return Ok(None);
+        }
+        let pairs: Vec<(String, String)> = url.query_pairs().filter(|(key, _)| key != "dl").map(|(k, v)| (k.into_owned(), v.into_owned())).collect();
+        let mut direct = url.clone();
+        direct.query_pairs_mut().clear().extend_pairs(pairs).append_pair("dl", "1");
+        Ok(Some(direct.to_string()))
+    }
+}
+
+/// SourceForge 项目文件的下载页面
+pub struct SourceForge;
+
+#[async_trait(?Send)]
+impl UrlResolver for SourceForge {
+    async fn resolve(&self, url: &Url, _transport: &dyn HttpTransport, _settings: &RequestSettings) -> Result<Option<String>, DownloadError> {
+        let Some(segments) = url.path_segments() else { return Ok(None) };
+        let segments: Vec<&str> = segments.filter(|s| !s.is_empty()).collect();
+        // /projects/<项目>/files/<路径...>/download，latest 由 SourceForge 自己重定向
+        match segments.as_slice() {
+            ["projects", project, "files", path @ .., "download"] if !path.is_empty() && path != ["latest"] => {
+                Ok(Some(format!("https://downloads.sourceforge.net/project/{}/{}", project, path.join("/"))))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// 内置的解析器
+pub fn builtin(name: &str) -> Option<Rc<dyn UrlResolver>> {
+    match name {
+        "google-drive" => Some(Rc::new(GoogleDrive)),
+        "dropbox" => Some(Rc::new(Dropbox)),
+        "sourceforge" => Some(Rc::new(SourceForge)),
+        _ => None,
+    }
+}
+
+/// 按主机选择的解析器，由管理器创建并共享给所有任务，注册后所有任务立即可用
+#[derive(Default)]
+pub struct UrlResolvers {
+    configured: RefCell<BTreeMap<String, Rc<dyn UrlResolver>>>, // 内置的默认主机和配置中的 `[resolvers]`
+    registered: RefCell<BTreeMap<String, Rc<dyn UrlResolver>>>, // 库的使用者注册的，优先于配置
+}
+
+impl UrlResolvers {
+    pub fn new(config: &Config) -> Self {
+        let resolvers = Self::default();
+        resolvers.reconfigure(config);
+        resolvers
+    }
+
+    /// 按配置重建内置的默认主机和 `[resolvers]`，注册的解析器保持不变
+    pub fn reconfigure(&self, config: &Config) {
+        let mut hosts = BTreeMap::new();
+        let configured = config.resolvers.iter().map(|(host, name)| (host.as_str(), name.as_str()));
+        for (host, name) in DEFAULT_HOSTS.into_iter().chain(configured) {
+            match builtin(name) {
+                Some(resolver) => hosts.insert(host.to_ascii_lowercase(), resolver),
+                None => hosts.remove(&host.to_ascii_lowercase()),
+            };
+        }
+        *self.configured.borrow_mut() = hosts;
+    }
+
+    /// 为主机（及其子域名）注册解析器，替换已注册的
+    pub fn register(&self, host: &str, resolver: Rc<dyn UrlResolver>) {
+        self.registered.borrow_mut().insert(host.to_ascii_lowercase(), resolver);
+    }
+
+    /// 主机对应的解析器，精确匹配优先，其次是最近的上级域名
+    fn find(&self, host: &str) -> Option<Rc<dyn UrlResolver>> {
+        let host = host.to_ascii_lowercase();
+        let (registered, configured) = (self.registered.borrow(), self.configured.borrow());
+        let mut candidate = host.as_str();
+        loop {
+            if let Some(resolver) = registered.get(candidate).or_else(|| configured.get(candidate)) {
+                return Some(resolver.clone());
+            }
+            candidate = candidate.split_once('.')?.1;
+        }
+    }
+
+    /// 返回实际请求的地址，没有匹配的解析器或不需要转换时为原地址
+    pub async fn resolve(&self, url: &str, transport: &dyn HttpTransport, settings: &RequestSettings) -> Result<String, DownloadError> {
+        let Ok(parsed) = Url::parse(url) else { return Ok(url.to_string()) };
+        let Some(resolver) = parsed.host_str().and_then(|host| self.find(host)) else { return Ok(url.to_string()) };
+        match resolver.resolve(&parsed, transport, settings).await? {
+            Some(direct) => {
+                tracing::info!(direct = %direct, "分享链接已转换为直链");
+                Ok(direct)
+            }
+            None => Ok(url.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::task::transport::HttpResponse;
+    use crate::core::task::TaskOptions;
+    use bytes::Bytes;
+    use std::cell::RefCell;
+
+    /// 记录请求并返回固定响应的后端
+    struct FakeTransport {
+        content_type: &'static str,
+        body: &'static str,
+        requests: RefCell<Vec<String>>,
+    }
+
+    #[async_trait(?Send)]
+    impl HttpTransport for FakeTransport {
+        async fn send(&self, request: HttpRequest<'_>) -> Result<HttpResponse, DownloadError> {
+            self.requests.borrow_mut().push(request.url.to_string());
+            let body = Bytes::from_static(self.body.as_bytes());
+            Ok(HttpResponse {
+                status: 200,
+                headers: vec![("content-type".to_string(), self.content_type.to_string())],
+                body: futures::stream::once(async move { Ok(body) }).boxed_local(),
+            })
+        }
+    }
+
+    fn fake(content_type: &'static str, body: &'static str) -> FakeTransport {
+        FakeTransport { content_type, body, requests: RefCell::new(Vec::new()) }
+    }
+
+    #[actix_rt::test]
+    async fn test_builtin_resolvers() {
+        let resolvers = UrlResolvers::new(&Config::default());
+        let settings = RequestSettings::new(&Config::default(), &TaskOptions::default());
+        let transport = fake("application/octet-stream", "");
+
+        let cases = [
+            ("https://www.dropbox.com/s/abc/file.zip?dl=0", "https://www.dropbox.com/s/abc/file.zip?dl=1"),
+            ("https://www.dropbox.com/scl/fi/xyz/a.bin?rlkey=k&dl=0", "https://www.dropbox.com/scl/fi/xyz/a.bin?rlkey=k&dl=1"),
+            (
+                "https://sourceforge.net/projects/tool/files/v1.2/tool-1.2.tar.gz/download",
+                "https://downloads.sourceforge.net/project/tool/v1.2/tool-1.2.tar.gz",
+            ),
+            (
+                "https://drive.google.com/file/d/1AbC/view?usp=sharing",
+                "https://drive.usercontent.google.com/download?id=1AbC&export=download&confirm=t",
+            ),
+            // 不需要转换的链接原样返回
+            ("https://sourceforge.net/projects/tool/files/latest/download", "https://sourceforge.net/projects/tool/files/latest/download"),
+            ("https://example.com/s/file.zip", "https://example.com/s/file.zip"),
+        ];
+        for (url, expected) in cases {
+            assert_eq!(resolvers.resolve(url, &transport, &settings).await.unwrap(), expected, "{}", url);
+        }
+        assert!(resolvers.resolve("https://drive.google.com/drive/folders/1AbC", &transport, &settings).await.is_err());
+
+        // 配置中关闭 dropbox、为其他主机指定解析器
+        let config = Config {
+            resolvers: [("dropbox.com", NONE), ("files.example.com", "dropbox")].map(|(h, n)| (h.to_string(), n.to_string())).into(),
+            ..Config::default()
+        };
+        resolvers.reconfigure(&config);
+        let url = "https://www.dropbox.com/s/abc/file.zip?dl=0";
+        assert_eq!(resolvers.resolve(url, &transport, &settings).await.unwrap(), url);
+        let url = "https://files.example.com/s/abc/file.zip";
+        assert_eq!(resolvers.resolve(url, &transport, &settings).await.unwrap(), "https://files.example.com/s/abc/file.zip?dl=1");
+
+        // 注册的解析器优先于配置，重新加载配置后仍然有效
+        resolvers.register("files.example.com", Rc::new(SourceForge));
+        resolvers.reconfigure(&config);
+        assert_eq!(resolvers.resolve(url, &transport, &settings).await.unwrap(), url);
+    }
+
+    #[actix_rt::test]
+    async fn test_google_drive_confirm_page() {
+        let page = r#"<html><body><form id="download-form" action="https://drive.usercontent.google.com/download" method="get">
+            <input type="submit" value="Download anyway"/>
+            <input type="hidden" name="id" value="1AbC"><input type="hidden" name="export" value="download">
+            <input type="hidden" name="confirm" value="t"><input type="hidden" name="uuid" value="u-1&amp;2"></form></body></html>"#;
+        let settings = RequestSettings::new(&Config::default(), &TaskOptions::default());
+        let transport = fake("text/html; charset=utf-8", page);
+        let direct = UrlResolvers::new(&Config::default())
+            .resolve("https://drive.google.com/uc?id=1AbC&export=download", &transport, &settings)
+            .await
+            .unwrap();
+        assert_eq!(direct, "https://drive.usercontent.google.com/download?id=1AbC&export=download&confirm=t&uuid=u-1%262");
+        assert_eq!(transport.requests.borrow().len(), 1);
+
+        // 没有下载表单的网页（没有公开分享）报错
+        let transport = fake("text/html", "<html>Sign in</html>");
+        let result = UrlResolvers::new(&Config::default()).resolve("https://drive.google.com/open?id=1AbC", &transport, &settings).await;
+        assert!(result.is_err());
+    }
+}
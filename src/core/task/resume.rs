@@ -0,0 +1,187 @@
+//! 断点续传信息的紧凑二进制格式：`resume_<任务ID>.bin`
+//!
+//! 几万个块的大文件每完成一块就重写一次 JSON 太慢，这里把文件分成两部分：
+//!
+//! ```text
+//! 文件头："MDRS" | 版本 u8 | 元数据长度 u32 | 元数据 JSON | 块数 u32 | 块数 × (start u64, end u64)
+//! 记录：  start u64 | end u64 | 标志 u8 | crc32 u32 | sha256 [u8; 32]    （每完成一块追加一条）
+//! ```
+//!
+//! 整数均为小端序。块完成时只在末尾追加一条定长记录；分块边界变化（分割、重新下载损坏的块）时
+//! 才重写整个文件。崩溃时写了一半的最后一条记录在读取时忽略。
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+
+use crate::core::actor_manager::ResumeInfo;
+use crate::core::error::DownloadError;
+use super::util::ChunkChecksum;
+
+const MAGIC: &[u8; 4] = b"MDRS";
+const VERSION: u8 = 1;
+/// 单条完成记录的长度
+pub const RECORD_SIZE: usize = 8 + 8 + 1 + 4 + 32;
+/// 记录带有校验值
+const FLAG_CHECKSUM: u8 = 1;
+
+/// 编码文件头：元数据和全部分块边界，不含完成记录
+pub fn encode_header(info: &ResumeInfo) -> Result<Vec<u8>, DownloadError> {
+    let meta = ResumeInfo {
+        downloaded_chunks: Vec::new(),
+        chunks: Vec::new(),
+        checksums: HashMap::new(),
+        ..info.clone()
+    };
+    let meta = serde_json::to_vec(&meta).map_err(|e| DownloadError::unknown(format!("序列化失败: {}", e)))?;
+    let mut buf = Vec::with_capacity(13 + meta.len() + info.chunks.len() * 16);
+    buf.extend_from_slice(MAGIC);
+    buf.push(VERSION);
+    buf.extend_from_slice(&(meta.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&meta);
+    buf.extend_from_slice(&(info.chunks.len() as u32).to_le_bytes());
+    for (start, end) in &info.chunks {
+        buf.extend_from_slice(&start.to_le_bytes());
+        buf.extend_from_slice(&end.to_le_bytes());
+    }
+    Ok(buf)
+}
+
+/// 编码一条块完成记录
+pub fn encode_record(start: u64, end: u64, checksum: Option<&ChunkChecksum>) -> [u8; RECORD_SIZE] {
+    let mut record = [0u8; RECORD_SIZE];
+    record[..8].copy_from_slice(&start.to_le_bytes());
+    record[8..16].copy_from_slice(&end.to_le_bytes());
+    let sha256 = checksum.and_then(|c| decode_hex(&c.sha256));
+    if let (Some(checksum), Some(sha256)) = (checksum, sha256) {
+        record[16] = FLAG_CHECKSUM;
+        record[17..21].copy_from_slice(&checksum.crc32.to_le_bytes());
+        record[21..].copy_from_slice(&sha256);
+    }
+    record
+}
+
+/// 完整编码：文件头加上所有已完成块的记录
+pub fn encode(info: &ResumeInfo) -> Result<Vec<u8>, DownloadError> {
+    let mut buf = encode_header(info)?;
+    for (start, end) in &info.downloaded_chunks {
+        buf.extend_from_slice(&encode_record(*start, *end, info.checksums.get(start)));
+    }
+    Ok(buf)
+}
+
+/// 解码；不是这个格式或文件头损坏时返回错误
+pub fn decode(bytes: &[u8]) -> Result<ResumeInfo, DownloadError> {
+    let invalid = || DownloadError::unknown("断点续传信息格式错误");
+    let mut reader = Reader { bytes, pos: 0 };
+    if reader.take(4).ok_or_else(invalid)? != MAGIC {
+        return Err(invalid());
+    }
+    if reader.take(1).ok_or_else(invalid)?[0] != VERSION {
+        return Err(DownloadError::unknown("不支持的断点续传信息版本"));
+    }
+    let meta_len = reader.u32().ok_or_else(invalid)? as usize;
+    let meta = reader.take(meta_len).ok_or_else(invalid)?;
+    let mut info: ResumeInfo = serde_json::from_slice(meta).map_err(|e| DownloadError::unknown(format!("反序列化失败: {}", e)))?;
+    let count = reader.u32().ok_or_else(invalid)? as usize;
+    info.chunks = (0..count)
+        .map(|_| Some((reader.u64()?, reader.u64()?)))
+        .collect::<Option<_>>()
+        .ok_or_else(invalid)?;
+    // 只读取完整的记录
+    while let Some(record) = reader.take(RECORD_SIZE) {
+        let start = u64::from_le_bytes(record[..8].try_into().unwrap());
+        let end = u64::from_le_bytes(record[8..16].try_into().unwrap());
+        if record[16] & FLAG_CHECKSUM != 0 {
+            info.checksums.insert(start, ChunkChecksum {
+                crc32: u32::from_le_bytes(record[17..21].try_into().unwrap()),
+                sha256: crate::core::history::to_hex(&record[21..]),
+            });
+        }
+        info.downloaded_chunks.push((start, end));
+    }
+    Ok(info)
+}
+
+/// 写入完整的文件（先写临时文件再重命名，崩溃时不会留下半个文件头）
+pub fn write(path: &Path, info: &ResumeInfo) -> Result<(), DownloadError> {
+    let tmp = path.with_extension("bin.tmp");
+    std::fs::write(&tmp, encode(info)?).map_err(|e| DownloadError::io_error_with_context("写入断点续传信息", e))?;
+    std::fs::rename(&tmp, path).map_err(|e| DownloadError::io_error_with_context("写入断点续传信息", e))
+}
+
+/// 在文件末尾追加块完成记录
+pub fn append(path: &Path, records: &[[u8; RECORD_SIZE]]) -> Result<(), DownloadError> {
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(path)
+        .map_err(|e| DownloadError::io_error_with_context("追加断点续传信息", e))?;
+    file.write_all(&records.concat()).map_err(|e| DownloadError::io_error_with_context("追加断点续传信息", e))
+}
+
+fn decode_hex(hex: &str) -> Option<[u8; 32]> {
+    let mut out = [0u8; 32];
+    if hex.len() != 64 {
+        return None;
+    }
+    for (i, byte) in out.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(out)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let slice = self.bytes.get(self.pos..self.pos.checked_add(n)?)?;
+        self.pos += n;
+        Some(slice)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4).map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.take(8).map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_roundtrip_and_torn_record() {
+        let checksum = ChunkChecksum::of(b"ab");
+        let info = ResumeInfo {
+            task_id: Uuid::new_v4(),
+            url: "http://example.com/a.bin".to_string(),
+            file: "a.bin".to_string(),
+            downloaded_chunks: vec![(0, 1)],
+            total_size: 6,
+            chunk_size: 2,
+            chunks: vec![(0, 1), (2, 3), (4, 5)],
+            checksums: HashMap::from([(0, checksum.clone())]),
+            last_modified: None,
+            etag: Some("\"v1\"".to_string()),
+        };
+        let mut bytes = encode(&info).unwrap();
+        // 追加一条没有校验值的记录，再模拟崩溃时写了一半的记录
+        bytes.extend_from_slice(&encode_record(2, 3, None));
+        bytes.extend_from_slice(&encode_record(4, 5, Some(&checksum))[..10]);
+
+        let decoded = decode(&bytes).unwrap();
+        assert_eq!(decoded.chunks, info.chunks);
+        assert_eq!(decoded.downloaded_chunks, vec![(0, 1), (2, 3)]);
+        assert_eq!(decoded.checksums.get(&0), Some(&checksum));
+        assert!(!decoded.checksums.contains_key(&2));
+        assert_eq!(decoded.etag, info.etag);
+        assert!(decode(b"{\"task_id\":1}").is_err());
+    }
+}
@@ -1,95 +1,13 @@
+use std::collections::HashMap;
 use std::time::{Duration, Instant};
-use crate::core::error::DownloadError;
-
-/// 重试策略
-#[derive(Debug, Clone)]
-pub struct RetryStrategy {
-    pub max_retries: usize,
-    pub base_delay: Duration,
-    pub max_delay: Duration,
-    pub backoff_multiplier: f64,
-    pub jitter_factor: f64, // 添加抖动因子避免重试风暴
-    pub retryable_errors: Vec<String>,
-}
-
-#[allow(dead_code)]
-impl Default for RetryStrategy {
-    fn default() -> Self {
-        Self {
-            max_retries: 3,
-            base_delay: Duration::from_secs(1),
-            max_delay: Duration::from_secs(60),
-            backoff_multiplier: 2.0,
-            jitter_factor: 0.1, // 10% 的抖动
-            retryable_errors: vec![
-                "network error".to_string(),
-                "timeout".to_string(),
-                "connection reset".to_string(),
-                "temporary failure".to_string(),
-                "connection refused".to_string(),
-                "connection timeout".to_string(),
-                "dns resolution failed".to_string(),
-                "ssl error".to_string(),
-                "certificate error".to_string(),
-                "server error".to_string(),
-                "gateway timeout".to_string(),
-                "service unavailable".to_string(),
-            ],
-        }
-    }
-}
-
-impl RetryStrategy {
-    pub fn should_retry(&self, error: &DownloadError, retry_count: usize) -> bool {
-        if retry_count >= self.max_retries {
-            return false;
-        }
-        
-        // 根据错误类型判断是否可重试
-        match error {
-            DownloadError::NetworkError(_) => true,
-            DownloadError::ServerError(msg) => {
-                // 服务器错误中，5xx 错误通常可以重试
-                msg.contains("500") || msg.contains("502") || msg.contains("503") || 
-                msg.contains("504") || msg.contains("507") || msg.contains("508")
-            },
-            DownloadError::Timeout => true,
-            DownloadError::IoError(_) => {
-                // IO错误中，网络相关的可以重试
-                let error_str = error.to_string().to_lowercase();
-                self.retryable_errors.iter().any(|retryable| {
-                    error_str.contains(retryable)
-                })
-            },
-            DownloadError::SizeMismatch { .. } => false, // 大小不匹配不重试
-            DownloadError::InvalidUrl(_) => false, // URL无效不重试
-            DownloadError::FileExists(_) => false, // 文件已存在不重试
-            DownloadError::ResumeFailed(_) => false, // 断点续传失败不重试
-            DownloadError::Unknown(msg) => {
-                // 未知错误中，网络相关的可以重试
-                let error_str = msg.to_lowercase();
-                self.retryable_errors.iter().any(|retryable| {
-                    error_str.contains(retryable)
-                })
-            },
-            _ => false, // 其他错误不重试
-        }
-    }
-    
-    pub fn get_delay(&self, retry_count: usize) -> Duration {
-        let delay_secs = self.base_delay.as_secs_f64() * 
-            self.backoff_multiplier.powi(retry_count as i32);
-        
-        // 添加抖动避免重试风暴
-        let jitter = delay_secs * self.jitter_factor * (rand::random::<f64>() - 0.5);
-        let final_delay = delay_secs + jitter;
-        
-        let delay = Duration::from_secs_f64(final_delay.max(0.1)); // 最小延迟100ms
-        delay.min(self.max_delay)
-    }
-}
+use crate::config::retry::{ResolvedPolicy, RetryRule};
+use crate::config::Config;
+use crate::core::error::{DownloadError, ErrorClass};
 
 /// 重试上下文
+///
+/// 每个错误分类（见 `DownloadError::retry_class`，可以用配置项 `retry_rules` 调整）有独立的
+/// 重试次数和退避时间，一个分类的次数用完不影响其他分类。
 #[derive(Debug, Clone)]
 pub struct RetryContext {
     pub max_retries: u32,
@@ -97,53 +15,107 @@ pub struct RetryContext {
     pub base_delay: Duration,
     pub max_delay: Duration,
     pub last_retry_time: Option<Instant>,
+    policies: HashMap<ErrorClass, ResolvedPolicy>,
+    /// 各分类已经重试的次数
+    class_retries: HashMap<ErrorClass, u32>,
+    /// 错误分类规则
+    rules: Vec<RetryRule>,
+    /// 下载的主机，用于筛选只对某些主机生效的规则
+    host: Option<String>,
 }
 
 impl RetryContext {
+    /// 除 4xx 外所有分类使用相同的次数和延迟
     pub fn new(max_retries: u32, base_delay: Duration, max_delay: Duration) -> Self {
+        let policies = ErrorClass::ALL
+            .into_iter()
+            .map(|class| {
+                let count = if class == ErrorClass::Client { 0 } else { max_retries as usize };
+                (class, ResolvedPolicy { count, delay: base_delay, max_delay })
+            })
+            .collect();
+        Self::with_policies(max_retries, base_delay, max_delay, policies)
+    }
+
+    /// 按配置中的 `retry_policies` 为每个分类设置重试参数
+    pub fn from_config(config: &Config) -> Self {
+        let policies: HashMap<_, _> = ErrorClass::ALL
+            .into_iter()
+            .map(|class| (class, config.retry_policies.resolve(class, config)))
+            .collect();
+        let max_retries = policies.values().map(|p| p.count).sum::<usize>() as u32;
+        Self::with_policies(
+            max_retries,
+            Duration::from_secs(config.retry_delay),
+            Duration::from_secs(config.retry_max_delay),
+            policies,
+        )
+        .with_rules(config.retry_rules.clone())
+    }
+
+    /// 使用错误分类规则
+    pub fn with_rules(mut self, rules: Vec<RetryRule>) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// 设置下载的 URL，只对某些主机生效的规则按它的主机匹配
+    pub fn for_url(mut self, url: &str) -> Self {
+        let host = super::tuning::host_key(url);
+        self.host = (!host.is_empty()).then_some(host);
+        self
+    }
+
+    /// 错误所属的重试分类：第一条匹配的规则决定，没有规则匹配时使用内置的分类；`None` 表示不重试
+    pub fn classify(&self, error: &DownloadError) -> Option<ErrorClass> {
+        // 暂停和取消不是失败，规则不能让它们重试
+        if matches!(error, DownloadError::Paused | DownloadError::Cancelled) {
+            return None;
+        }
+        match self.rules.iter().find(|r| r.applies_to(self.host.as_deref()) && r.matches(error)) {
+            Some(rule) => rule.target().ok().flatten(),
+            None => error.retry_class(),
+        }
+    }
+
+    fn with_policies(
+        max_retries: u32,
+        base_delay: Duration,
+        max_delay: Duration,
+        policies: HashMap<ErrorClass, ResolvedPolicy>,
+    ) -> Self {
         Self {
             max_retries,
             current_retries: 0,
             base_delay,
             max_delay,
             last_retry_time: None,
+            policies,
+            class_retries: HashMap::new(),
+            rules: Vec::new(),
+            host: None,
         }
     }
 
-    /// 判断是否应该重试
+    /// 判断是否应该重试：错误所属分类的次数还没有用完
     pub fn should_retry(&self, error: &DownloadError) -> bool {
-        if self.current_retries >= self.max_retries {
-            return false;
-        }
+        let Some(class) = self.classify(error) else { return false };
+        let used = self.class_retries.get(&class).copied().unwrap_or(0);
+        self.policies.get(&class).is_some_and(|p| (used as usize) < p.count)
+    }
 
-        // 检查错误类型是否可重试
-        self.is_retryable_error(error)
-    }
-
-    /// 判断错误是否可重试
-    fn is_retryable_error(&self, error: &DownloadError) -> bool {
-        // 定义可重试的错误类型
-        static RETRYABLE_ERRORS: &[&str] = &[
-            "network error",
-            "timeout",
-            "connection reset",
-            "temporary failure",
-            "connection refused",
-            "connection timeout",
-            "dns resolution failed",
-            "ssl error",
-            "certificate error",
-            "server error",
-            "gateway timeout",
-            "service unavailable",
-        ];
-
-        let error_str = error.to_string().to_lowercase();
-        
-        // 检查错误消息是否包含可重试的错误类型
-        RETRYABLE_ERRORS.iter().any(|&retryable| {
-            error_str.contains(retryable)
-        })
+    /// 可以重试时记录一次重试并返回按该分类的策略退避后的等待时间，否则返回 None
+    pub fn next_retry(&mut self, error: &DownloadError) -> Option<Duration> {
+        if !self.should_retry(error) {
+            return None;
+        }
+        let class = self.classify(error)?;
+        let policy = self.policies[&class];
+        let used = self.class_retries.entry(class).or_insert(0);
+        let delay = policy.delay.saturating_mul(2_u32.saturating_pow(*used)).min(policy.max_delay);
+        *used += 1;
+        self.record_retry();
+        Some(delay)
     }
 
     /// 获取下次重试延迟
@@ -162,6 +134,7 @@ impl RetryContext {
     pub fn reset(&mut self) {
         self.current_retries = 0;
         self.last_retry_time = None;
+        self.class_retries.clear();
     }
 
     /// 获取当前重试次数
@@ -208,6 +181,26 @@ mod tests {
         assert_eq!(context.current_retries(), 0);
     }
 
+    #[test]
+    fn test_retry_policies_per_class() {
+        let config: Config = toml::from_str(
+            "retry_count = 1\nretry_delay = 1\n[retry_policies.network]\ncount = 3\ndelay = 2\nmax_delay = 5\n",
+        )
+        .unwrap();
+        let mut context = RetryContext::from_config(&config);
+        let network = DownloadError::network_error("连接被重置");
+        assert_eq!(context.next_retry(&network), Some(Duration::from_secs(2)));
+        assert_eq!(context.next_retry(&network), Some(Duration::from_secs(4)));
+        assert_eq!(context.next_retry(&network), Some(Duration::from_secs(5)));
+        assert_eq!(context.next_retry(&network), None);
+
+        // 其他分类有自己的次数
+        assert_eq!(context.next_retry(&DownloadError::Timeout), Some(Duration::from_secs(1)));
+        assert_eq!(context.next_retry(&DownloadError::Timeout), None);
+        assert_eq!(context.next_retry(&DownloadError::server_error("服务器错误: 403")), None);
+        assert_eq!(context.current_retries(), 4);
+    }
+
     #[test]
     fn test_retryable_errors() {
         let context = RetryContext::new(3, Duration::from_secs(1), Duration::from_secs(10));
@@ -220,4 +213,25 @@ mod tests {
         let non_retryable_error = DownloadError::invalid_url("invalid url");
         assert!(!context.should_retry(&non_retryable_error));
     }
+
+    #[test]
+    fn test_retry_rules() {
+        let config: Config = toml::from_str(
+            "[[retry_rules]]\nhosts = [\"cdn.example.com\"]\nstatus = [\"403\"]\nclass = \"server\"\n\n\
+             [[retry_rules]]\nstatus = [\"500-599\"]\nkinds = [\"cancelled\"]\nclass = \"never\"\n",
+        )
+        .unwrap();
+        let forbidden = DownloadError::http_status(403, "https://cdn.example.com/a.bin");
+        let mut cdn = RetryContext::from_config(&config).for_url("https://cdn.example.com/a.bin");
+        assert_eq!(cdn.classify(&forbidden), Some(ErrorClass::Server));
+        assert!(cdn.next_retry(&forbidden).is_some());
+        assert_eq!(cdn.classify(&DownloadError::http_status(503, "https://cdn.example.com/a.bin")), None);
+        assert_eq!(cdn.classify(&DownloadError::Cancelled), None);
+        // 没有规则匹配时使用内置分类
+        assert_eq!(cdn.classify(&DownloadError::Timeout), Some(ErrorClass::Timeout));
+
+        let other = RetryContext::from_config(&config).for_url("https://example.com/a.bin");
+        assert_eq!(other.classify(&forbidden), Some(ErrorClass::Client));
+        assert!(!other.should_retry(&forbidden));
+    }
 } 
\ No newline at end of file
@@ -1,12 +1,97 @@
 use serde::{Serialize, Deserialize};
 
 /// 下载任务状态
+///
+/// 管理器按以下状态机维护任务状态，其他转换一律拒绝：
+///
+/// ```text
+/// Pending ──► Queued ──► Running ──► Completed / Failed
+///    │          │           │
+///    └──────────┴───────────┴──► Paused ──► Queued
+///                                Paused ──► Completed（暂停时最后的合并已经完成）
+///                                Failed ──► Pending（重试）
+/// 除 Completed、Cancelled 外的任何状态都可以转为 Cancelled
+/// ```
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TaskStatus {
+    /// 已创建，还没有排队
     Pending,
+    /// 排队等待下载名额
+    Queued,
     Running,
     Completed,
     Failed(String),
     Paused,
     Cancelled,
-} 
\ No newline at end of file
+} 
+impl TaskStatus {
+    /// 状态的稳定英文标识，用于报告和机器可读输出
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskStatus::Pending => "pending",
+            TaskStatus::Queued => "queued",
+            TaskStatus::Running => "running",
+            TaskStatus::Completed => "completed",
+            TaskStatus::Failed(_) => "failed",
+            TaskStatus::Paused => "paused",
+            TaskStatus::Cancelled => "cancelled",
+        }
+    }
+
+    /// 还没有结束：等待、排队、下载中或暂停
+    pub fn is_active(&self) -> bool {
+        matches!(self, TaskStatus::Pending | TaskStatus::Queued | TaskStatus::Running | TaskStatus::Paused)
+    }
+
+    /// 状态机是否允许从当前状态转为 `next`
+    pub fn can_transition_to(&self, next: &TaskStatus) -> bool {
+        use TaskStatus::*;
+        matches!(
+            (self, next),
+            (Pending | Paused, Queued)
+                | (Queued, Running)
+                | (Pending | Queued | Running, Paused)
+                | (Running, Completed | Failed(_))
+                | (Paused, Completed)
+                | (Failed(_), Pending)
+                | (Pending | Queued | Running | Paused | Failed(_), Cancelled)
+        )
+    }
+}
+
+/// 数据下载完后、任务完成前的收尾阶段，状态仍为 `Running`，有单独的进度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskPhase {
+    /// 把分块合并成完整文件
+    Merging,
+    /// 计算校验和
+    Verifying,
+}
+
+impl TaskPhase {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TaskPhase::Merging => "merging",
+            TaskPhase::Verifying => "verifying",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transitions() {
+        use TaskStatus::*;
+        let failed = Failed("timeout".to_string());
+        for (from, to) in [(&Pending, &Queued), (&Queued, &Running), (&Running, &Completed), (&Running, &failed), (&Paused, &Queued), (&failed, &Pending), (&Queued, &Paused)] {
+            assert!(from.can_transition_to(to), "{:?} -> {:?}", from, to);
+        }
+        // 没拿到名额不能开始下载，已结束的任务不能再改变
+        for (from, to) in [(&Pending, &Running), (&Paused, &Running), (&Queued, &Queued), (&Completed, &Queued), (&Cancelled, &Pending), (&Completed, &Cancelled), (&Queued, &Completed)] {
+            assert!(!from.can_transition_to(to), "{:?} -> {:?}", from, to);
+        }
+    }
+}
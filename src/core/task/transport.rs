@@ -0,0 +1,236 @@
+//! HTTP 传输层
+//!
+//! 文件信息、单线程下载和分块下载的请求都通过 `HttpTransport` 发送，
+//! 超时、User-Agent 和任务的附加请求头在 `RequestSettings` 中统一应用。
+//! 默认实现 `AwcTransport` 基于 awc，换用其他 HTTP 库时实现同一个 trait 即可。
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::LocalBoxStream;
+use futures::StreamExt;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::core::error::DownloadError;
+use super::dns::Resolver;
+use super::http::{self, ClientPool, SocketOptions};
+use super::options::TaskOptions;
+
+/// `fallback_user_agents` 中 `"browser"` 对应的 User-Agent
+pub const BROWSER_USER_AGENT: &str =
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36";
+
+/// 请求方法，下载只用到这两种
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpMethod {
+    Head,
+    Get,
+}
+
+/// 每个请求都要带上的设置，由任务的配置和选项合并得到
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestSettings {
+    /// 等待响应头的超时时间，不限制响应体的读取
+    pub timeout: Duration,
+    pub user_agent: String,
+    /// 任务的附加请求头，同名时覆盖 User-Agent
+    pub headers: Vec<(String, String)>,
+    /// 返回 403 时依次换用的 User-Agent（`"browser"` 已替换为实际的值）
+    pub fallback_user_agents: Vec<String>,
+}
+
+impl RequestSettings {
+    pub fn new(config: &Config, options: &TaskOptions) -> Self {
+        Self {
+            timeout: Duration::from_secs(config.timeout),
+            user_agent: config.user_agent.clone(),
+            headers: options.headers.clone(),
+            fallback_user_agents: config
+                .fallback_user_agents
+                .iter()
+                .map(|ua| if ua == "browser" { BROWSER_USER_AGENT.to_string() } else { ua.clone() })
+                .collect(),
+        }
+    }
+
+    /// 换用另一个 User-Agent，任务附加请求头中的 User-Agent 一并去掉
+    pub fn with_user_agent(&self, user_agent: &str) -> Self {
+        let mut settings = self.clone();
+        settings.user_agent = user_agent.to_string();
+        settings.headers.retain(|(n, _)| !n.eq_ignore_ascii_case("user-agent"));
+        settings
+    }
+
+    /// 实际发送的请求头：附加请求头在前，没有自定义 User-Agent 时补上配置中的值
+    pub fn headers(&self) -> Vec<(String, String)> {
+        let mut headers = self.headers.clone();
+        if !self.user_agent.is_empty() && !headers.iter().any(|(n, _)| n.eq_ignore_ascii_case("user-agent")) {
+            headers.push(("User-Agent".to_string(), self.user_agent.clone()));
+        }
+        headers
+    }
+}
+
+/// 一次 HTTP 请求
+#[derive(Debug, Clone)]
+pub struct HttpRequest<'a> {
+    pub method: HttpMethod,
+    pub url: &'a str,
+    /// 请求的字节范围（闭区间）
+    pub range: Option<(u64, u64)>,
+    pub settings: &'a RequestSettings,
+}
+
+impl<'a> HttpRequest<'a> {
+    pub fn head(url: &'a str, settings: &'a RequestSettings) -> Self {
+        Self { method: HttpMethod::Head, url, range: None, settings }
+    }
+
+    pub fn get(url: &'a str, settings: &'a RequestSettings) -> Self {
+        Self { method: HttpMethod::Get, url, range: None, settings }
+    }
+
+    pub fn range(mut self, start: u64, end: u64) -> Self {
+        self.range = Some((start, end));
+        self
+    }
+}
+
+/// 收到的响应，响应体以字节流的形式读取
+pub struct HttpResponse {
+    pub status: u16,
+    /// 响应头，名称统一为小写
+    pub headers: Vec<(String, String)>,
+    pub body: LocalBoxStream<'static, Result<Bytes, DownloadError>>,
+}
+
+impl HttpResponse {
+    /// 按名称（不区分大小写）取响应头
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// `Content-Length`，缺失或无法解析时为 0
+    pub fn content_length(&self) -> u64 {
+        self.header("content-length").and_then(|s| s.parse().ok()).unwrap_or(0)
+    }
+}
+
+/// HTTP 后端
+///
+/// 实现者负责应用 `RequestSettings` 并把后端自己的错误转换为 `DownloadError`，
+/// 响应头超时返回 `DownloadError::Timeout`，其他连接错误返回 `NetworkError`。
+#[async_trait(?Send)]
+pub trait HttpTransport {
+    async fn send(&self, request: HttpRequest<'_>) -> Result<HttpResponse, DownloadError>;
+
+    /// 任务排队时调用，后端可以提前做好连接准备（如解析主机名），默认什么都不做
+    fn prefetch(&self, _url: &str) {}
+}
+
+/// 基于 awc 的默认实现，同一主机的请求复用 `ClientPool` 中的客户端
+#[derive(Clone, Default)]
+pub struct AwcTransport {
+    pub clients: ClientPool,
+}
+
+impl AwcTransport {
+    /// 按配置中的 TCP 选项和 DNS 设置建立连接
+    pub fn new(config: &Config) -> Self {
+        Self { clients: ClientPool::new(SocketOptions::new(config), Resolver::new(config)) }
+    }
+}
+
+#[async_trait(?Send)]
+impl HttpTransport for AwcTransport {
+    async fn send(&self, request: HttpRequest<'_>) -> Result<HttpResponse, DownloadError> {
+        let client = self.clients.get(request.url);
+        let mut builder = match request.method {
+            HttpMethod::Head => client.head(request.url),
+            HttpMethod::Get => client.get(request.url),
+        }
+        .timeout(request.settings.timeout);
+        for (name, value) in request.settings.headers() {
+            builder = builder.insert_header((name, value));
+        }
+        if let Some((start, end)) = request.range {
+            builder = builder.insert_header(("Range", format!("bytes={}-{}", start, end)));
+        }
+
+        let response = http::send(builder, &self.clients).await.map_err(send_error)?;
+        let headers = response
+            .headers()
+            .iter()
+            .filter_map(|(n, v)| Some((n.as_str().to_ascii_lowercase(), v.to_str().ok()?.to_string())))
+            .collect();
+        Ok(HttpResponse {
+            status: response.status().as_u16(),
+            headers,
+            body: response
+                .map(|chunk| chunk.map_err(payload_error))
+                .boxed_local(),
+        })
+    }
+
+    fn prefetch(&self, url: &str) {
+        self.clients.resolver().prefetch(url);
+    }
+}
+
+/// 把 awc 的发送错误转成 `DownloadError`，底层是 IO 错误时保留原始错误
+fn send_error(e: awc::error::SendRequestError) -> DownloadError {
+    use awc::error::{ConnectError, SendRequestError};
+    match e {
+        SendRequestError::Timeout | SendRequestError::Connect(ConnectError::Timeout) => DownloadError::Timeout,
+        SendRequestError::Connect(ConnectError::Io(e)) => DownloadError::connection_error("连接失败", e),
+        SendRequestError::Send(e) => DownloadError::connection_error("发送请求失败", e),
+        SendRequestError::Url(e) => DownloadError::invalid_url(e.to_string()),
+        e => DownloadError::NetworkError(format!("{:?}", e).into()),
+    }
+}
+
+/// 把响应体的读取错误转成 `DownloadError`
+fn payload_error(e: awc::error::PayloadError) -> DownloadError {
+    use awc::error::PayloadError;
+    match e {
+        PayloadError::Io(e) | PayloadError::Incomplete(Some(e)) => DownloadError::connection_error("网络流错误", e),
+        e => DownloadError::NetworkError(format!("网络流错误: {:?}", e).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_settings() {
+        let config = Config { timeout: 10, user_agent: "curl/8.0".to_string(), ..Default::default() };
+        let settings = RequestSettings::new(&config, &TaskOptions::default());
+        assert_eq!(settings.timeout, Duration::from_secs(10));
+        assert_eq!(settings.headers(), vec![("User-Agent".to_string(), "curl/8.0".to_string())]);
+
+        // 任务自己的 User-Agent 优先
+        let options = TaskOptions {
+            headers: vec![("user-agent".to_string(), "custom".to_string()), ("X-Token".to_string(), "t".to_string())],
+            ..Default::default()
+        };
+        let settings = RequestSettings::new(&config, &options);
+        assert_eq!(settings.headers(), options.headers);
+
+        // 换用 User-Agent 时替换任务自己的 User-Agent
+        let config = Config { fallback_user_agents: vec!["browser".to_string()], ..config };
+        let settings = RequestSettings::new(&config, &options);
+        assert_eq!(settings.fallback_user_agents, vec![BROWSER_USER_AGENT.to_string()]);
+        assert_eq!(
+            settings.with_user_agent("Wget/1.21").headers(),
+            vec![("X-Token".to_string(), "t".to_string()), ("User-Agent".to_string(), "Wget/1.21".to_string())]
+        );
+    }
+}
@@ -0,0 +1,269 @@
+//! 根据实测吞吐量自适应调整分块大小和连接数
+//!
+//! 每个分块下载完成时记录它的字节数和耗时，每隔 `TUNING_WINDOW` 评估一次：
+//! - 分块平均耗时过短时加大分块（请求开销占比太高），过长时减小分块（失败重试的代价太大）；
+//! - 连接数逐个增加，只要总速度还在明显提高就继续，否则退回上一步并停止增加。
+//!
+//! 调整结果按主机保存到会话目录中的 `tuning.json`，下次下载同一主机时从这里开始。
+//! 分块边界在任务开始时确定，分块大小的调整从下一次下载生效；连接数立即生效。
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::core::error::DownloadError;
+
+/// 调整结果文件名，位于会话目录中
+pub const TUNING_FILE: &str = "tuning.json";
+
+/// 两次评估之间至少间隔的时间
+pub const TUNING_WINDOW: Duration = Duration::from_secs(3);
+
+/// 分块平均耗时低于这个值时加大分块
+const FAST_CHUNK: Duration = Duration::from_secs(1);
+
+/// 分块平均耗时高于这个值时减小分块
+const SLOW_CHUNK: Duration = Duration::from_secs(10);
+
+/// 增加一个连接后总速度至少提高这么多（比例）才继续增加
+const MIN_GAIN: f64 = 0.1;
+
+/// 某个主机的调整结果
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HostTuning {
+    pub chunk_size: u64,
+    pub concurrency: usize,
+    /// 观测到的最高总速度（B/s）
+    pub best_speed: u64,
+}
+
+/// 调整结果存储，按主机名保存
+#[derive(Debug, Clone)]
+pub struct TuningStore {
+    path: PathBuf,
+}
+
+impl TuningStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// 读取全部调整结果，文件不存在或格式错误时为空
+    pub fn load(&self) -> BTreeMap<String, HostTuning> {
+        std::fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn get(&self, host: &str) -> Option<HostTuning> {
+        self.load().get(host).copied()
+    }
+
+    /// 保存某个主机的调整结果
+    pub fn put(&self, host: &str, tuning: HostTuning) -> Result<(), DownloadError> {
+        let mut all = self.load();
+        all.insert(host.to_string(), tuning);
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| DownloadError::io_error_with_context("创建调整结果目录", e))?;
+        }
+        let json = serde_json::to_string_pretty(&all)
+            .map_err(|e| DownloadError::unknown(format!("序列化调整结果失败: {}", e)))?;
+        std::fs::write(&self.path, json).map_err(|e| DownloadError::io_error_with_context("写入调整结果", e))
+    }
+}
+
+/// URL 中的主机名（含端口），调整结果以此为键
+pub fn host_key(url: &str) -> String {
+    url::Url::parse(url)
+        .ok()
+        .and_then(|u| {
+            let host = u.host_str()?.to_string();
+            Some(match u.port() {
+                Some(port) => format!("{}:{}", host, port),
+                None => host,
+            })
+        })
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// 单个任务的吞吐量控制器
+#[derive(Debug, Clone)]
+pub struct ThroughputController {
+    pub host: String,
+    pub tuning: HostTuning,
+    min_chunk_size: u64,
+    max_chunk_size: u64,
+    max_concurrency: usize,
+    window_start: Instant,
+    window_bytes: u64,
+    window_chunks: u32,
+    window_chunk_time: Duration,
+    /// 上一个窗口的总速度
+    last_speed: Option<u64>,
+    /// 上一次评估是否增加了连接数
+    ramping: bool,
+    /// 增加连接数已经不再提速，不再增加
+    settled: bool,
+}
+
+impl ThroughputController {
+    /// 从保存的调整结果（没有时用配置）开始，结果限制在配置允许的范围内
+    pub fn new(url: &str, config: &Config, saved: Option<HostTuning>) -> Self {
+        let min_chunk_size = config.chunk_size as u64;
+        let max_chunk_size = (config.max_chunk_size as u64).max(min_chunk_size);
+        let max_concurrency = config.max_thread_count.max(1);
+        let tuning = saved.unwrap_or(HostTuning {
+            chunk_size: min_chunk_size,
+            concurrency: config.thread_count,
+            best_speed: 0,
+        });
+        Self {
+            host: host_key(url),
+            tuning: HostTuning {
+                chunk_size: tuning.chunk_size.clamp(min_chunk_size, max_chunk_size),
+                concurrency: tuning.concurrency.clamp(1, max_concurrency),
+                best_speed: tuning.best_speed,
+            },
+            min_chunk_size,
+            max_chunk_size,
+            max_concurrency,
+            window_start: Instant::now(),
+            window_bytes: 0,
+            window_chunks: 0,
+            window_chunk_time: Duration::ZERO,
+            last_speed: None,
+            ramping: false,
+            settled: false,
+        }
+    }
+
+    /// 记录一个完成的分块，到了评估时间时进行调整，调整了返回 true
+    pub fn record_chunk(&mut self, bytes: u64, elapsed: Duration) -> bool {
+        self.window_bytes += bytes;
+        self.window_chunks += 1;
+        self.window_chunk_time += elapsed;
+        let window = self.window_start.elapsed();
+        if window < TUNING_WINDOW {
+            return false;
+        }
+        self.evaluate(window)
+    }
+
+    /// 用当前窗口的数据做一次评估并开始新窗口
+    pub fn evaluate(&mut self, window: Duration) -> bool {
+        if self.window_chunks == 0 || window.is_zero() {
+            return false;
+        }
+        let speed = (self.window_bytes as f64 / window.as_secs_f64()) as u64;
+        let avg_chunk = self.window_chunk_time / self.window_chunks;
+        let before = self.tuning;
+        self.tuning.best_speed = self.tuning.best_speed.max(speed);
+
+        if avg_chunk < FAST_CHUNK {
+            self.tuning.chunk_size = (self.tuning.chunk_size * 2).min(self.max_chunk_size);
+        } else if avg_chunk > SLOW_CHUNK {
+            self.tuning.chunk_size = (self.tuning.chunk_size / 2).max(self.min_chunk_size);
+        }
+
+        let improved = self.last_speed.is_none_or(|last| speed as f64 > last as f64 * (1.0 + MIN_GAIN));
+        if self.ramping && !improved {
+            // 上一次增加的连接没有带来提速，退回并停止增加
+            self.tuning.concurrency = (self.tuning.concurrency - 1).max(1);
+            self.ramping = false;
+            self.settled = true;
+        } else if !self.settled && self.tuning.concurrency < self.max_concurrency {
+            self.tuning.concurrency += 1;
+            self.ramping = true;
+        } else {
+            self.ramping = false;
+        }
+        self.last_speed = Some(speed);
+
+        self.window_start = Instant::now();
+        self.window_bytes = 0;
+        self.window_chunks = 0;
+        self.window_chunk_time = Duration::ZERO;
+
+        let changed = self.tuning.chunk_size != before.chunk_size || self.tuning.concurrency != before.concurrency;
+        if changed {
+            tracing::info!(
+                host = %self.host,
+                speed,
+                avg_chunk_ms = avg_chunk.as_millis() as u64,
+                chunk_size = self.tuning.chunk_size,
+                concurrency = self.tuning.concurrency,
+                "自适应调整分块大小和连接数"
+            );
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn controller(saved: Option<HostTuning>) -> ThroughputController {
+        let config = Config {
+            chunk_size: 1024,
+            max_chunk_size: 8192,
+            thread_count: 2,
+            max_thread_count: 4,
+            ..Default::default()
+        };
+        ThroughputController::new("https://example.com:8443/a.zip", &config, saved)
+    }
+
+    fn window(c: &mut ThroughputController, bytes: u64, chunk: Duration) -> bool {
+        c.window_bytes = bytes;
+        c.window_chunks = 1;
+        c.window_chunk_time = chunk;
+        c.evaluate(Duration::from_secs(1))
+    }
+
+    #[test]
+    fn test_controller_ramps_until_no_gain() {
+        let mut c = controller(None);
+        assert_eq!(c.host, "example.com:8443");
+        assert_eq!((c.tuning.chunk_size, c.tuning.concurrency), (1024, 2));
+
+        // 分块很快：分块加倍，连接数逐个增加
+        assert!(window(&mut c, 1000, Duration::from_millis(100)));
+        assert_eq!((c.tuning.chunk_size, c.tuning.concurrency), (2048, 3));
+        assert!(window(&mut c, 2000, Duration::from_millis(100)));
+        assert_eq!((c.tuning.chunk_size, c.tuning.concurrency), (4096, 4));
+
+        // 速度不再提高：退回一个连接，之后不再增加；分块大小不超过上限
+        assert!(window(&mut c, 2050, Duration::from_millis(100)));
+        assert_eq!((c.tuning.chunk_size, c.tuning.concurrency), (8192, 3));
+        assert!(!window(&mut c, 5000, Duration::from_secs(5)));
+        assert_eq!(c.tuning.best_speed, 5000);
+
+        // 分块太慢：减半，不低于 chunk_size
+        assert!(window(&mut c, 5000, Duration::from_secs(20)));
+        assert_eq!(c.tuning.chunk_size, 4096);
+    }
+
+    #[test]
+    fn test_controller_clamps_saved_tuning() {
+        let c = controller(Some(HostTuning { chunk_size: 1 << 30, concurrency: 64, best_speed: 10 }));
+        assert_eq!((c.tuning.chunk_size, c.tuning.concurrency), (8192, 4));
+    }
+
+    #[test]
+    fn test_tuning_store() {
+        let path = std::env::temp_dir().join(format!("multidown_tuning_{}.json", uuid::Uuid::new_v4()));
+        let store = TuningStore::new(&path);
+        assert_eq!(store.get("example.com"), None);
+        let tuning = HostTuning { chunk_size: 4096, concurrency: 3, best_speed: 100 };
+        store.put("example.com", tuning).unwrap();
+        store.put("mirror.example.com", HostTuning { concurrency: 5, ..tuning }).unwrap();
+        assert_eq!(store.get("example.com"), Some(tuning));
+        assert_eq!(store.load().len(), 2);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -1,7 +1,10 @@
 use std::time::{Duration, Instant};
 use crate::core::error::DownloadError;
 use std::io::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
 use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
 
 /// 文件信息结构
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,13 +16,236 @@ pub struct FileInfo {
     pub etag: Option<String>,
 }
 
+/// 单个块内容的校验值，记录在断点续传信息中
+///
+/// 恢复时用 CRC32 快速检查块文件是否损坏；SHA-256 用于严格比较某个范围的内容
+/// （例如换到其他镜像后重新下载的同一范围），都不需要读整个文件。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkChecksum {
+    pub crc32: u32,
+    pub sha256: String,
+}
+
+impl ChunkChecksum {
+    /// 计算一段数据的校验值
+    pub fn of(data: &[u8]) -> Self {
+        let mut hasher = ChunkHasher::default();
+        hasher.update(data);
+        hasher.finish()
+    }
+
+    /// 与一段数据的内容是否一致
+    pub fn matches(&self, data: &[u8]) -> bool {
+        *self == Self::of(data)
+    }
+}
+
+/// 边下载边计算块的校验值
+#[derive(Default)]
+pub struct ChunkHasher {
+    crc32: crc32fast::Hasher,
+    sha256: sha2::Sha256,
+}
+
+impl ChunkHasher {
+    pub fn update(&mut self, data: &[u8]) {
+        use sha2::Digest;
+        self.crc32.update(data);
+        self.sha256.update(data);
+    }
+
+    pub fn finish(self) -> ChunkChecksum {
+        use sha2::Digest;
+        ChunkChecksum {
+            crc32: self.crc32.finalize(),
+            sha256: crate::core::history::to_hex(&self.sha256.finalize()),
+        }
+    }
+}
+
+/// 把文件及其所在目录的目录项刷到磁盘，之后即使断电或崩溃文件也不会丢失
+///
+/// 目录只在 Unix 上同步，Windows 不支持以这种方式打开目录，NTFS 的元数据由日志保证。
+pub fn sync_durable(path: &std::path::Path) -> Result<(), DownloadError> {
+    std::fs::File::open(path)
+        .and_then(|f| f.sync_all())
+        .map_err(|e| DownloadError::io_error_with_context("同步文件到磁盘", e))?;
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        let parent = if parent.as_os_str().is_empty() { std::path::Path::new(".") } else { parent };
+        std::fs::File::open(parent)
+            .and_then(|d| d.sync_all())
+            .map_err(|e| DownloadError::io_error_with_context("同步目录到磁盘", e))?;
+    }
+    Ok(())
+}
+
+/// 下载完成后设置到输出文件上的属性，见配置项 `preserve_mtime`、`output_file_mode`、`read_only_output`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileMetadata {
+    /// 修改时间，取自服务器返回的 Last-Modified
+    pub modified: Option<std::time::SystemTime>,
+    /// Unix 权限位
+    pub mode: Option<u32>,
+    pub read_only: bool,
+}
+
+impl FileMetadata {
+    pub fn new(config: &crate::config::Config, info: Option<&FileInfo>) -> Self {
+        let modified = info
+            .and_then(|info| info.last_modified.as_deref())
+            .filter(|_| config.preserve_mtime)
+            .and_then(|s| chrono::DateTime::parse_from_rfc2822(s).ok())
+            .map(std::time::SystemTime::from);
+        Self { modified, mode: config.file_mode().ok().flatten(), read_only: config.read_only_output }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// 依次设置修改时间、权限和只读
+    pub fn apply(&self, path: &std::path::Path) -> Result<(), DownloadError> {
+        if let Some(modified) = self.modified {
+            std::fs::File::options()
+                .write(true)
+                .open(path)
+                .and_then(|f| f.set_modified(modified))
+                .map_err(|e| DownloadError::io_error_with_context("设置文件修改时间", e))?;
+        }
+        #[cfg(unix)]
+        if let Some(mode) = self.mode {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+                .map_err(|e| DownloadError::io_error_with_context("设置文件权限", e))?;
+        }
+        if self.read_only {
+            let mut permissions = std::fs::metadata(path)
+                .map_err(|e| DownloadError::io_error_with_context("读取文件权限", e))?
+                .permissions();
+            permissions.set_readonly(true);
+            std::fs::set_permissions(path, permissions).map_err(|e| DownloadError::io_error_with_context("设置文件只读", e))?;
+        }
+        Ok(())
+    }
+}
+
+/// 池中最多保留的空闲缓冲区数
+const MAX_POOLED_BUFFERS: usize = 32;
+
+/// 空闲的写缓冲区，`BufferManager` 创建时从这里取、销毁时放回，避免每个块都重新分配
+static BUFFER_POOL: Mutex<Vec<Vec<u8>>> = Mutex::new(Vec::new());
+
+/// 从池中取一个容量至少为 `size` 的空缓冲区，没有时新分配
+fn take_buffer(size: usize) -> Vec<u8> {
+    let mut pool = BUFFER_POOL.lock().unwrap();
+    match pool.iter().position(|b| b.capacity() >= size) {
+        Some(i) => pool.swap_remove(i),
+        None => Vec::with_capacity(size),
+    }
+}
+
+/// 把缓冲区放回池中
+fn return_buffer(mut buffer: Vec<u8>) {
+    buffer.clear();
+    let mut pool = BUFFER_POOL.lock().unwrap();
+    if pool.len() < MAX_POOLED_BUFFERS {
+        pool.push(buffer);
+    }
+}
+
+/// 依次完整写入两段数据，尽量用一次 vectored 写完成
+fn write_all_vectored(file: &mut std::fs::File, mut first: &[u8], mut second: &[u8]) -> std::io::Result<()> {
+    while !first.is_empty() || !second.is_empty() {
+        let n = match file.write_vectored(&[std::io::IoSlice::new(first), std::io::IoSlice::new(second)]) {
+            Ok(0) => return Err(std::io::ErrorKind::WriteZero.into()),
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        let from_first = n.min(first.len());
+        first = &first[from_first..];
+        second = &second[n - from_first..];
+    }
+    Ok(())
+}
+
+/// 在文件的 `offset` 处（即当前末尾）依次写入两段数据
+///
+/// 启用 `io-uring` 特性且内核支持时通过 io_uring 一次提交，否则用普通的 vectored 写。
+/// 同一线程上 io_uring 是否可用不会变化，同一个文件不会混用两种方式。
+fn write_parts(file: &mut std::fs::File, offset: u64, first: &[u8], second: &[u8]) -> std::io::Result<()> {
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    if let Some(result) = super::uring::write_all_at(file, offset, &[first, second]) {
+        return result;
+    }
+    let _ = offset;
+    write_all_vectored(file, first, second)
+}
+
+/// 把 `src` 的全部内容追加到 `dst` 的 `offset` 处（合并分块时使用），返回复制的字节数
+pub fn append_file(src: &mut std::fs::File, dst: &mut std::fs::File, offset: u64) -> std::io::Result<u64> {
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    if let Some(result) = super::uring::copy_file(src, dst, offset) {
+        return result;
+    }
+    let _ = offset;
+    std::io::copy(src, dst)
+}
+
+/// 与 `append_file` 相同，但按 `limiter` 限制速度（合并分块时使用），每复制一段调用 `progress(本段字节数)`
+pub fn append_file_limited(
+    src: &mut std::fs::File,
+    dst: &mut std::fs::File,
+    offset: u64,
+    limiter: &mut SpeedLimiter,
+    mut progress: impl FnMut(u64),
+) -> std::io::Result<u64> {
+    if limiter.max_speed == 0 {
+        let copied = append_file(src, dst, offset)?;
+        progress(copied);
+        return Ok(copied);
+    }
+    let mut buf = vec![0u8; throttle_step(limiter)];
+    let mut copied = 0u64;
+    loop {
+        let n = std::io::Read::read(src, &mut buf)?;
+        if n == 0 {
+            return Ok(copied);
+        }
+        throttle_blocking(limiter, n as u64);
+        dst.write_all(&buf[..n])?;
+        copied += n as u64;
+        progress(n as u64);
+    }
+}
+
+/// 限速读写时每次处理的字节数：不超过每秒限额的四分之一，否则令牌桶永远攒不够一次的量
+pub fn throttle_step(limiter: &SpeedLimiter) -> usize {
+    if limiter.max_speed == 0 {
+        return 1024 * 1024;
+    }
+    (limiter.max_speed as usize / 4).clamp(1, 1024 * 1024)
+}
+
+/// 在阻塞线程中按 `limiter` 限速，超出限额时睡眠到下一个时间窗口
+pub fn throttle_blocking(limiter: &mut SpeedLimiter, bytes: u64) {
+    let wait = limiter.wait_if_needed(bytes);
+    if !wait.is_zero() {
+        std::thread::sleep(wait);
+    }
+}
+
 /// 缓冲区管理器
+///
+/// 小块数据先攒在缓冲区中；放不下时把缓冲区和新数据一起写入文件，不再拷贝新数据。
+/// 缓冲区来自全局的缓冲区池，销毁时归还。
 #[allow(dead_code)]
 pub struct BufferManager {
     buffer: Vec<u8>,
     buffer_size: usize,
-    current_pos: usize,
     file_handle: std::fs::File,
+    start_offset: u64, // 开始写入时文件的长度，追加写入时不为 0
     total_written: u64,
     flush_count: u64,
 }
@@ -28,14 +254,35 @@ pub struct BufferManager {
 impl BufferManager {
     /// 创建新的 BufferManager
     pub fn new(file_path: &str, buffer_size: usize) -> Result<Self, DownloadError> {
+        if let Some(parent) = std::path::Path::new(file_path).parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(|e| DownloadError::io_error_with_context("创建目录", e))?;
+        }
         let file = std::fs::File::create(file_path)
-            .map_err(|e| DownloadError::IoError(e.to_string().into()))?;
+            .map_err(|e| DownloadError::io_error_with_context("创建文件", e))?;
+
+        Ok(Self {
+            buffer: take_buffer(buffer_size),
+            buffer_size,
+            file_handle: file,
+            start_offset: 0,
+            total_written: 0,
+            flush_count: 0,
+        })
+    }
+
+    /// 接在已有文件的末尾写入（续传已有的部分文件），`get_total_written` 只统计新写入的部分
+    pub fn append(file_path: &str, buffer_size: usize) -> Result<Self, DownloadError> {
+        let file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(file_path)
+            .map_err(|e| DownloadError::io_error_with_context("打开文件", e))?;
+        let start_offset = file.metadata().map_err(|e| DownloadError::io_error_with_context("读取文件信息", e))?.len();
 
         Ok(Self {
-            buffer: vec![0; buffer_size],
+            buffer: take_buffer(buffer_size),
             buffer_size,
-            current_pos: 0,
             file_handle: file,
+            start_offset,
             total_written: 0,
             flush_count: 0,
         })
@@ -43,33 +290,25 @@ impl BufferManager {
 
     /// 向缓冲区写入数据
     pub fn write(&mut self, data: &[u8]) -> Result<(), DownloadError> {
-        let mut bytes_written = 0;
-        while bytes_written < data.len() {
-            let space_left = self.buffer_size - self.current_pos;
-            let to_copy = std::cmp::min(space_left, data.len() - bytes_written);
-
-            if to_copy > 0 {
-                self.buffer[self.current_pos..self.current_pos + to_copy]
-                    .copy_from_slice(&data[bytes_written..bytes_written + to_copy]);
-                self.current_pos += to_copy;
-                bytes_written += to_copy;
-            }
-
-            if self.current_pos == self.buffer_size {
-                self.flush()?;
-            }
+        if self.buffer.len() + data.len() < self.buffer_size {
+            self.buffer.extend_from_slice(data);
+            return Ok(());
         }
+        write_parts(&mut self.file_handle, self.start_offset + self.total_written, &self.buffer, data)
+            .map_err(|e| DownloadError::io_error_with_context("写入文件", e))?;
+        self.total_written += (self.buffer.len() + data.len()) as u64;
+        self.buffer.clear();
+        self.flush_count += 1;
         Ok(())
     }
 
     /// 将缓冲区内容刷入文件
     pub fn flush(&mut self) -> Result<(), DownloadError> {
-        if self.current_pos > 0 {
-            self.file_handle
-                .write_all(&self.buffer[..self.current_pos])
-                .map_err(|e| DownloadError::IoError(e.to_string().into()))?;
-            self.total_written += self.current_pos as u64;
-            self.current_pos = 0;
+        if !self.buffer.is_empty() {
+            write_parts(&mut self.file_handle, self.start_offset + self.total_written, &self.buffer, &[])
+                .map_err(|e| DownloadError::io_error_with_context("写入文件", e))?;
+            self.total_written += self.buffer.len() as u64;
+            self.buffer.clear();
             self.flush_count += 1;
         }
         Ok(())
@@ -77,7 +316,7 @@ impl BufferManager {
     
     /// 获取缓冲区使用情况
     pub fn get_buffer_usage(&self) -> (usize, usize) {
-        (self.current_pos, self.buffer_size)
+        (self.buffer.len(), self.buffer_size)
     }
 
     /// 获取总写入字节数
@@ -92,24 +331,30 @@ impl BufferManager {
     
     /// 缓冲区是否为空
     pub fn is_empty(&self) -> bool {
-        self.current_pos == 0
+        self.buffer.is_empty()
     }
 
     /// 缓冲区是否已满
     pub fn is_full(&self) -> bool {
-        self.current_pos == self.buffer_size
+        self.buffer.len() >= self.buffer_size
     }
 
     /// 缓冲区可用空间
     pub fn available_space(&self) -> usize {
-        self.buffer_size - self.current_pos
+        self.buffer_size.saturating_sub(self.buffer.len())
+    }
+}
+
+impl Drop for BufferManager {
+    fn drop(&mut self) {
+        return_buffer(std::mem::take(&mut self.buffer));
     }
 }
 
 /// 速度限制器
 #[allow(dead_code)]
 pub struct SpeedLimiter {
-    pub max_speed: u64, // B/s
+    pub max_speed: u64, // B/s，0 表示不限速
     pub window_size: Duration,
     pub tokens: u64,
     pub last_refill: Instant,
@@ -126,10 +371,21 @@ impl SpeedLimiter {
         }
     }
     
+    /// 修改限速（热重载配置时使用），新的限速立即生效
+    pub fn set_max_speed(&mut self, max_speed: u64) {
+        self.max_speed = max_speed;
+        self.tokens = max_speed;
+        self.last_refill = Instant::now();
+    }
+
     pub fn consume(&mut self, bytes: u64) -> bool {
+        if self.max_speed == 0 {
+            return true;
+        }
         self.refill_tokens();
-        if self.tokens >= bytes {
-            self.tokens -= bytes;
+        // 一次收到的数据比整个窗口的配额还多（限速很低）时，等到配额攒满后放行，不会一直等下去
+        if self.tokens >= bytes || self.tokens == self.max_speed {
+            self.tokens = self.tokens.saturating_sub(bytes);
             true
         } else {
             false
@@ -157,4 +413,262 @@ impl SpeedLimiter {
         }
         Duration::from_secs(0)
     }
-} 
\ No newline at end of file
+}
+
+/// 本次运行的下载配额，由管理器创建并在所有任务间共享
+///
+/// 任务知道文件大小时一次预留，大小未知时边下载边预留；任务失败或取消时归还预留的配额。
+#[derive(Debug, Default)]
+pub struct DownloadQuota {
+    limit: AtomicU64, // 0 表示不限制，热重载时原地修改
+    used: AtomicU64,
+}
+
+impl DownloadQuota {
+    pub fn new(limit: u64) -> Self {
+        Self { limit: AtomicU64::new(limit), used: AtomicU64::new(0) }
+    }
+
+    pub fn set_limit(&self, limit: u64) {
+        self.limit.store(limit, Ordering::SeqCst);
+    }
+
+    /// 预留 `bytes`，剩余配额不足时不预留并返回错误
+    pub fn reserve(&self, bytes: u64) -> Result<(), DownloadError> {
+        let limit = self.limit.load(Ordering::SeqCst);
+        self.used
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                (limit == 0 || used.saturating_add(bytes) <= limit).then_some(used.saturating_add(bytes))
+            })
+            .map(|_| ())
+            .map_err(|used| DownloadError::QuotaExceeded { required: bytes, remaining: limit.saturating_sub(used) })
+    }
+
+    pub fn release(&self, bytes: u64) {
+        let _ = self.used.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| Some(used.saturating_sub(bytes)));
+    }
+
+    /// 任务要下载 `size` 字节：检查单个文件的大小上限（0 表示不限制），
+    /// 并把任务的预留量 `reserved` 提高到 `size`，已经预留的部分不重复计算
+    pub fn reserve_file(&self, max_file_size: u64, reserved: &AtomicU64, size: u64) -> Result<(), DownloadError> {
+        if max_file_size > 0 && size > max_file_size {
+            return Err(DownloadError::SizeLimitExceeded { size, limit: max_file_size });
+        }
+        let current = reserved.load(Ordering::SeqCst);
+        if size > current {
+            self.reserve(size - current)?;
+            reserved.store(size, Ordering::SeqCst);
+        }
+        Ok(())
+    }
+}
+
+/// 进度上报节流：累计收到的字节数，达到时间间隔或字节数阈值时才上报一次
+///
+/// 高速下载时每个网络数据块都发送消息会让任务和管理器 Actor 的邮箱积压。
+pub struct ProgressThrottle {
+    interval: Duration,
+    max_bytes: u64,
+    last_report: Instant,
+    pending: u64,
+}
+
+impl ProgressThrottle {
+    pub fn new(interval: Duration, max_bytes: u64) -> Self {
+        Self { interval, max_bytes, last_report: Instant::now(), pending: 0 }
+    }
+
+    /// 记录新收到的字节数；需要上报时返回上次上报以来的字节数和经过的时间，并重新开始计数
+    pub fn record(&mut self, bytes: u64) -> Option<(u64, Duration)> {
+        self.pending += bytes;
+        let elapsed = self.last_report.elapsed();
+        if elapsed < self.interval && self.pending < self.max_bytes {
+            return None;
+        }
+        self.last_report = Instant::now();
+        Some((std::mem::take(&mut self.pending), elapsed))
+    }
+}
+
+/// 任务的暂停和取消信号
+///
+/// 下载中的请求用 [`StopSignal::guard`] 包起来，暂停或取消时立即丢弃进行中的请求（包括正在读取的
+/// 响应体），不必等到下一段数据到达。取消后的令牌不能复位，恢复下载时换一组新的令牌。
+#[derive(Debug, Clone, Default)]
+pub struct StopSignal {
+    pause: CancellationToken,
+    cancel: CancellationToken,
+}
+
+impl StopSignal {
+    pub fn pause(&self) {
+        self.pause.cancel();
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.pause.is_cancelled()
+    }
+
+    /// 已经暂停或取消时返回对应的错误
+    pub fn check(&self) -> Result<(), DownloadError> {
+        if self.cancel.is_cancelled() {
+            Err(DownloadError::Cancelled)
+        } else if self.pause.is_cancelled() {
+            Err(DownloadError::Paused)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// 等到暂停或取消，返回对应的错误
+    pub async fn stopped(&self) -> DownloadError {
+        tokio::select! {
+            biased;
+            _ = self.cancel.cancelled() => DownloadError::Cancelled,
+            _ = self.pause.cancelled() => DownloadError::Paused,
+        }
+    }
+
+    /// 运行 `future`，暂停或取消时立即丢弃它并返回对应的错误
+    pub async fn guard<T>(&self, future: impl std::future::Future<Output = Result<T, DownloadError>>) -> Result<T, DownloadError> {
+        tokio::select! {
+            biased;
+            error = self.stopped() => Err(error),
+            result = future => result,
+        }
+    }
+
+    /// 等待 `delay`，期间暂停或取消时提前返回错误
+    pub async fn sleep(&self, delay: Duration) -> Result<(), DownloadError> {
+        self.guard(async {
+            tokio::time::sleep(delay).await;
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_metadata() {
+        let path = std::env::temp_dir().join(format!("multidown_meta_{}", uuid::Uuid::new_v4()));
+        std::fs::write(&path, b"data").unwrap();
+        let config = crate::config::Config {
+            preserve_mtime: true,
+            output_file_mode: "640".to_string(),
+            read_only_output: true,
+            ..Default::default()
+        };
+        let info = FileInfo { size: 4, supports_range: true, last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".to_string()), etag: None };
+        let metadata = FileMetadata::new(&config, Some(&info));
+        assert_eq!(metadata.mode, Some(0o640));
+        metadata.apply(&path).unwrap();
+
+        let after = std::fs::metadata(&path).unwrap();
+        let modified = chrono::DateTime::<chrono::Utc>::from(after.modified().unwrap());
+        assert_eq!(modified.to_rfc2822(), "Wed, 21 Oct 2015 07:28:00 +0000");
+        assert!(after.permissions().readonly());
+        #[cfg(unix)]
+        assert_eq!(std::os::unix::fs::PermissionsExt::mode(&after.permissions()) & 0o777, 0o440);
+
+        // 没有 Last-Modified 或未启用时不修改
+        assert!(FileMetadata::new(&crate::config::Config::default(), Some(&info)).is_empty());
+        assert!(crate::config::Config { output_file_mode: "9".to_string(), ..Default::default() }.validate().is_err());
+
+        let mut permissions = after.permissions();
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        std::fs::set_permissions(&path, permissions).unwrap();
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_buffer_manager() {
+        let path = std::env::temp_dir().join(format!("multidown_buffer_{}", uuid::Uuid::new_v4()));
+        let path_str = path.to_string_lossy().to_string();
+        let mut expected = Vec::new();
+        {
+            let mut buffer = BufferManager::new(&path_str, 8).unwrap();
+            for data in [&b"abc"[..], b"de", b"fghijklmnop", b"q", b"rstuvwx", b"yz"] {
+                buffer.write(data).unwrap();
+                expected.extend_from_slice(data);
+            }
+            assert!(buffer.get_flush_count() >= 2);
+            buffer.flush().unwrap();
+            assert!(buffer.is_empty());
+            assert_eq!(buffer.get_total_written(), expected.len() as u64);
+        }
+        assert_eq!(std::fs::read(&path).unwrap(), expected);
+
+        // 销毁后缓冲区回到池中，下次可以复用
+        assert!(take_buffer(8).capacity() >= 8);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_speed_limiter_oversized_read() {
+        let mut limiter = SpeedLimiter::new(16 * 1024);
+        // 一次读到的数据比整个窗口的配额还多时，配额满着就放行，之后等下一个窗口
+        assert!(limiter.consume(64 * 1024));
+        assert!(!limiter.consume(64 * 1024));
+        assert!(!limiter.wait_if_needed(1).is_zero());
+    }
+
+    #[test]
+    fn test_download_quota() {
+        let quota = DownloadQuota::new(100);
+        let (a, b) = (AtomicU64::new(0), AtomicU64::new(0));
+        quota.reserve_file(0, &a, 60).unwrap();
+        // 大小未知的下载逐步预留，已预留的部分不重复计算
+        quota.reserve_file(0, &b, 20).unwrap();
+        quota.reserve_file(0, &b, 40).unwrap();
+        assert!(matches!(
+            quota.reserve_file(0, &b, 41),
+            Err(DownloadError::QuotaExceeded { required: 1, remaining: 0 })
+        ));
+        assert!(matches!(quota.reserve_file(50, &b, 51), Err(DownloadError::SizeLimitExceeded { size: 51, limit: 50 })));
+
+        // 归还后可以继续预留
+        quota.release(a.swap(0, Ordering::SeqCst));
+        quota.reserve_file(0, &b, 90).unwrap();
+        quota.set_limit(0);
+        quota.reserve(u64::MAX).unwrap();
+    }
+
+    #[test]
+    fn test_progress_throttle() {
+        let mut throttle = ProgressThrottle::new(Duration::from_secs(3600), 100);
+        assert_eq!(throttle.record(40), None);
+        assert_eq!(throttle.record(40), None);
+        assert_eq!(throttle.record(40).map(|(bytes, _)| bytes), Some(120));
+        assert_eq!(throttle.record(10), None);
+
+        let mut throttle = ProgressThrottle::new(Duration::ZERO, u64::MAX);
+        assert_eq!(throttle.record(1).map(|(bytes, _)| bytes), Some(1));
+        assert_eq!(throttle.record(2).map(|(bytes, _)| bytes), Some(2));
+    }
+
+    #[actix_rt::test]
+    async fn test_stop_signal_aborts_pending_future() {
+        let stop = StopSignal::default();
+        assert!(stop.check().is_ok());
+        let signal = stop.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            signal.pause();
+        });
+        // 永远不会完成的读取在暂停时立即返回
+        let result = stop.guard(futures::future::pending::<Result<(), DownloadError>>()).await;
+        assert!(matches!(result, Err(DownloadError::Paused)));
+        stop.cancel();
+        assert!(matches!(stop.check(), Err(DownloadError::Cancelled)));
+        assert!(matches!(stop.sleep(Duration::from_secs(3600)).await, Err(DownloadError::Cancelled)));
+    }
+}
@@ -0,0 +1,168 @@
+//! 流量统计
+//!
+//! 下载过程中实际收到的字节数（包括重试和最终失败的任务）按“日期 + 主机”
+//! 追加到会话目录中的 `usage.jsonl`，供 `multidown stats` 汇总，方便按流量计费的用户控制用量。
+
+use chrono::{Duration, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::core::error::DownloadError;
+
+/// 流量统计文件名，位于会话目录中
+pub const USAGE_FILE: &str = "usage.jsonl";
+
+/// 一条流量记录，同一天同一主机可以有多条，读取时累加
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UsageEntry {
+    /// 本地日期
+    pub date: NaiveDate,
+    pub host: String,
+    pub bytes: u64,
+}
+
+impl UsageEntry {
+    /// 以今天的日期和 URL 中的主机名创建记录
+    pub fn today(url: &str, bytes: u64) -> Self {
+        Self {
+            date: Local::now().date_naive(),
+            host: url::Url::parse(url)
+                .ok()
+                .and_then(|u| u.host_str().map(|h| h.to_string()))
+                .unwrap_or_else(|| "unknown".to_string()),
+            bytes,
+        }
+    }
+}
+
+/// 按主机和按天汇总的流量
+#[derive(Debug, Default, PartialEq)]
+pub struct UsageSummary {
+    pub by_host: BTreeMap<String, u64>,
+    pub by_day: BTreeMap<NaiveDate, u64>,
+    pub total: u64,
+}
+
+/// 流量统计存储
+#[derive(Debug, Clone)]
+pub struct UsageStore {
+    path: PathBuf,
+}
+
+impl UsageStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// 追加一条记录
+    pub fn append(&self, entry: &UsageEntry) -> Result<(), DownloadError> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| DownloadError::io_error_with_context("创建流量统计目录", e))?;
+        }
+        let line = serde_json::to_string(entry)
+            .map_err(|e| DownloadError::unknown(format!("序列化流量记录失败: {}", e)))?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| DownloadError::io_error_with_context("打开流量统计", e))?;
+        writeln!(file, "{}", line).map_err(|e| DownloadError::io_error_with_context("写入流量统计", e))
+    }
+
+    /// 读取所有记录，跳过无法解析的行
+    pub fn load(&self) -> Vec<UsageEntry> {
+        fs::read_to_string(&self.path)
+            .map(|content| {
+                content
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// 汇总 `since` 当天及之后的流量
+    pub fn summary(&self, since: NaiveDate) -> UsageSummary {
+        summarize(&self.load(), since)
+    }
+}
+
+/// 汇总 `since` 当天及之后的记录
+pub fn summarize(entries: &[UsageEntry], since: NaiveDate) -> UsageSummary {
+    let mut summary = UsageSummary::default();
+    for entry in entries.iter().filter(|e| e.date >= since) {
+        *summary.by_host.entry(entry.host.clone()).or_default() += entry.bytes;
+        *summary.by_day.entry(entry.date).or_default() += entry.bytes;
+        summary.total += entry.bytes;
+    }
+    summary
+}
+
+/// 解析 `--since`：`7d`（最近 7 天，含今天）、`2w`，或日期 `2024-05-01`
+pub fn parse_since(text: &str, today: NaiveDate) -> Result<NaiveDate, DownloadError> {
+    let text = text.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return Ok(date);
+    }
+    let invalid = || DownloadError::Unknown(Cow::Owned(format!("无效的时间范围: {}（示例: 7d、2w、2024-05-01）", text)));
+    let (number, unit) = text.split_at(text.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?);
+    let number: i64 = number.parse().map_err(|_| invalid())?;
+    let days = match unit.to_ascii_lowercase().as_str() {
+        "d" => number,
+        "w" => number.checked_mul(7).ok_or_else(invalid)?,
+        _ => return Err(invalid()),
+    };
+    if days == 0 {
+        return Err(invalid());
+    }
+    Duration::try_days(days - 1).and_then(|back| today.checked_sub_signed(back)).ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(date: &str, host: &str, bytes: u64) -> UsageEntry {
+        UsageEntry { date: date.parse().unwrap(), host: host.to_string(), bytes }
+    }
+
+    #[test]
+    fn test_usage_summary() {
+        let path = "test_usage.jsonl";
+        let _ = fs::remove_file(path);
+        let store = UsageStore::new(path);
+        store.append(&entry("2024-05-01", "a.com", 100)).unwrap();
+        store.append(&entry("2024-05-02", "a.com", 50)).unwrap();
+        store.append(&entry("2024-05-02", "b.com", 25)).unwrap();
+
+        let summary = store.summary("2024-05-02".parse().unwrap());
+        assert_eq!(summary.total, 75);
+        assert_eq!(summary.by_host["a.com"], 50);
+        assert_eq!(summary.by_day.len(), 1);
+        assert_eq!(store.summary(NaiveDate::MIN).total, 175);
+
+        assert_eq!(UsageEntry::today("https://cdn.example.com/x", 1).host, "cdn.example.com");
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_parse_since() {
+        let today: NaiveDate = "2024-05-10".parse().unwrap();
+        assert_eq!(parse_since("7d", today).unwrap(), "2024-05-04".parse().unwrap());
+        assert_eq!(parse_since("1d", today).unwrap(), today);
+        assert_eq!(parse_since("2w", today).unwrap(), "2024-04-27".parse().unwrap());
+        assert_eq!(parse_since("2024-01-01", today).unwrap(), "2024-01-01".parse().unwrap());
+        assert!(parse_since("0d", today).is_err());
+        assert!(parse_since("7", today).is_err());
+        assert!(parse_since("7y", today).is_err());
+        assert!(parse_since("99999999999w", today).is_err());
+        assert!(parse_since("999999999d", today).is_err());
+        assert!(parse_since("99999999999999999999d", today).is_err());
+    }
+}
@@ -0,0 +1,159 @@
+//! 校验已下载的文件：`multidown verify [<路径|任务 ID>...]`
+//!
+//! 按下载历史中记录的大小和 SHA-256 检查本地文件是否损坏；加上 `--remote` 时再向源站发 HEAD 请求，
+//! 用 ETag、Last-Modified 和大小判断服务器上的文件是否已经更新（本地文件过期）。
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::core::history::{self, HistoryEntry};
+use crate::core::task::FileInfo;
+
+/// 单个文件的校验结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyStatus {
+    /// 与记录一致
+    Ok,
+    /// 本地文件不存在
+    Missing,
+    /// 大小与记录不一致
+    SizeMismatch { expected: u64, actual: u64 },
+    /// SHA-256 与记录不一致
+    ChecksumMismatch,
+    /// 源站的文件已经更新
+    Stale(String),
+    /// 无法完成校验（读取文件或请求源站失败）
+    Error(String),
+}
+
+impl VerifyStatus {
+    pub fn is_ok(&self) -> bool {
+        matches!(self, VerifyStatus::Ok)
+    }
+}
+
+/// 按路径或任务 ID（可以只写前缀，例如 `status` 中显示的 8 位）查找最近一次成功下载的记录
+pub fn find_entry<'a>(entries: &'a [HistoryEntry], target: &str) -> Option<&'a HistoryEntry> {
+    let canonical = Path::new(target).canonicalize().ok();
+    entries.iter().rev().filter(|e| e.is_success()).find(|e| {
+        let id_matches = target.len() >= 8
+            && e.task_id.is_some_and(|id| id.to_string().starts_with(&target.to_ascii_lowercase()));
+        let path_matches = e.path == target
+            || canonical.is_some() && Path::new(&e.path).canonicalize().ok() == canonical;
+        id_matches || path_matches
+    })
+}
+
+/// 每个路径最近一次成功下载的记录（按下载时间从新到旧）
+pub fn latest_entries(entries: &[HistoryEntry]) -> Vec<&HistoryEntry> {
+    let mut seen = HashSet::new();
+    entries
+        .iter()
+        .rev()
+        .filter(|e| e.is_success() && seen.insert(e.path.as_str()))
+        .collect()
+}
+
+/// 按记录检查本地文件的大小和 SHA-256（阻塞，文件较大时较慢）
+pub fn check_local(entry: &HistoryEntry) -> VerifyStatus {
+    let actual = match std::fs::metadata(&entry.path) {
+        Ok(metadata) => metadata.len(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return VerifyStatus::Missing,
+        Err(e) => return VerifyStatus::Error(e.to_string()),
+    };
+    if entry.size > 0 && actual != entry.size {
+        return VerifyStatus::SizeMismatch { expected: entry.size, actual };
+    }
+    let Some(expected) = &entry.checksum else {
+        return VerifyStatus::Ok;
+    };
+    match history::sha256_file(&entry.path) {
+        Ok(checksum) if checksum.eq_ignore_ascii_case(expected) => VerifyStatus::Ok,
+        Ok(_) => VerifyStatus::ChecksumMismatch,
+        Err(e) => VerifyStatus::Error(e.to_string()),
+    }
+}
+
+/// 用源站当前的文件信息判断本地文件是否过期；服务器没有返回可比较的信息时视为未过期
+pub fn check_remote(entry: &HistoryEntry, info: &FileInfo) -> VerifyStatus {
+    if let (Some(recorded), Some(current)) = (&entry.etag, &info.etag) {
+        return if recorded == current {
+            VerifyStatus::Ok
+        } else {
+            VerifyStatus::Stale(format!("ETag {} -> {}", recorded, current))
+        };
+    }
+    if let (Some(recorded), Some(current)) = (&entry.last_modified, &info.last_modified) {
+        if recorded != current {
+            return VerifyStatus::Stale(format!("Last-Modified {} -> {}", recorded, current));
+        }
+    }
+    if info.size > 0 && entry.size != info.size {
+        return VerifyStatus::Stale(format!("size {} -> {}", entry.size, info.size));
+    }
+    VerifyStatus::Ok
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    fn entry(path: &str, size: u64, checksum: Option<&str>) -> HistoryEntry {
+        HistoryEntry {
+            task_id: Some(Uuid::new_v4()),
+            url: "https://example.com/a.bin".to_string(),
+            path: path.to_string(),
+            status: "completed".to_string(),
+            size,
+            duration_secs: 1.0,
+            checksum: checksum.map(str::to_string),
+            etag: Some("\"v1\"".to_string()),
+            last_modified: None,
+            error: None,
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_check_local() {
+        let path = "test_verify.bin";
+        std::fs::write(path, b"abc").unwrap();
+        let sha = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+        assert_eq!(check_local(&entry(path, 3, Some(sha))), VerifyStatus::Ok);
+        assert_eq!(check_local(&entry(path, 3, None)), VerifyStatus::Ok);
+        assert_eq!(check_local(&entry(path, 4, Some(sha))), VerifyStatus::SizeMismatch { expected: 4, actual: 3 });
+        std::fs::write(path, b"abd").unwrap();
+        assert_eq!(check_local(&entry(path, 3, Some(sha))), VerifyStatus::ChecksumMismatch);
+        let _ = std::fs::remove_file(path);
+        assert_eq!(check_local(&entry(path, 3, Some(sha))), VerifyStatus::Missing);
+    }
+
+    #[test]
+    fn test_find_and_check_remote() {
+        let old = entry("downloads/a.bin", 3, None);
+        let mut failed = entry("downloads/a.bin", 3, None);
+        failed.status = "failed".to_string();
+        let other = entry("downloads/b.bin", 3, None);
+        let entries = vec![old.clone(), failed, other.clone()];
+
+        assert_eq!(find_entry(&entries, "downloads/a.bin").unwrap().task_id, old.task_id);
+        let id = other.task_id.unwrap().to_string();
+        assert_eq!(find_entry(&entries, &id[..8]).unwrap().path, "downloads/b.bin");
+        assert!(find_entry(&entries, "downloads/c.bin").is_none());
+        assert_eq!(latest_entries(&entries).len(), 2);
+
+        let info = |etag: &str, size| FileInfo {
+            size,
+            supports_range: true,
+            last_modified: None,
+            etag: Some(etag.to_string()),
+        };
+        assert_eq!(check_remote(&old, &info("\"v1\"", 3)), VerifyStatus::Ok);
+        assert!(matches!(check_remote(&old, &info("\"v2\"", 3)), VerifyStatus::Stale(_)));
+        let no_etag = FileInfo { etag: None, ..info("", 5) };
+        assert!(matches!(check_remote(&old, &no_etag), VerifyStatus::Stale(_)));
+    }
+}
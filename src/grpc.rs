@@ -0,0 +1,362 @@
+//! gRPC 控制接口：`multidown serve --listen 127.0.0.1:50051`（需要启用 `grpc` 特性构建）
+//!
+//! 服务定义见 `proto/multidown.proto`，其他语言的客户端可以直接由它生成：
+//!
+//! - `AddTask`：添加任务并立即开始，可以附带请求头和标签；
+//! - `StreamProgress`：订阅一个任务（或所有任务）的进度和状态变化；
+//! - `PauseTask`：暂停任务，数据保留，之后可以继续；
+//! - `GetStats`：会话中所有任务的统计。
+//!
+//! 配置了访问令牌（`serve_token`）或客户端（`[[clients]]`，见 [`crate::config::clients`]）时，每个请求都要在
+//! `authorization: Bearer <令牌>` 或 `x-api-key` 元数据中携带其一，否则返回 UNAUTHENTICATED。持有令牌的请求不受限制；
+//! 客户端只能看到和操作自己添加的任务，`GetStats` 只统计它自己的任务。设置了 `serve_tls_cert` 和 `serve_tls_key`
+//! 时只接受 TLS 连接，见 [`crate::utils::tls`]。
+//!
+//! 服务由守护进程（`multidown serve`）与本地控制通道（见 [`crate::core::ipc`]）一起提供，共用同一个下载管理器。
+
+// 错误类型由 tonic 的接口决定
+#![allow(clippy::result_large_err)]
+
+use std::future::Future;
+use std::io;
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context as TaskContext, Poll};
+use std::time::Duration;
+
+use actix::prelude::*;
+use futures::{Stream, StreamExt};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::TlsAcceptor;
+use tonic::transport::server::{Connected, TcpConnectInfo, TcpIncoming};
+use tonic::{Request, Response, Status};
+use uuid::Uuid;
+
+use crate::config::clients::Credentials;
+use crate::core::actor_manager::{
+    ClientStats, CreateTask, DownloadManagerActor, DownloadTaskMeta, GetStats, PauseTask, QueryTaskDetail, StartTaskFromMeta,
+};
+use crate::core::error::DownloadError;
+use crate::core::events::{EventBus, TaskEvent};
+use crate::core::task::{DownloadRequest, TaskStatus};
+use crate::utils::{tls, validator};
+
+/// 由 `proto/multidown.proto` 生成的消息、服务端和客户端
+pub mod proto {
+    tonic::include_proto!("multidown.v1");
+}
+
+use proto::multidown_server::{Multidown, MultidownServer};
+use proto::{
+    AddTaskRequest, AddTaskResponse, GetStatsRequest, PauseTaskRequest, PauseTaskResponse, ProgressEvent, Stats,
+    StreamProgressRequest,
+};
+
+/// gRPC 服务，把请求转发给下载管理器
+pub struct GrpcService {
+    manager: Addr<DownloadManagerActor>,
+    events: EventBus,
+    download_dir: String,
+    credentials: Credentials,
+}
+
+impl GrpcService {
+    pub fn new(manager: Addr<DownloadManagerActor>, events: EventBus, download_dir: impl Into<String>) -> Self {
+        Self { manager, events, download_dir: download_dir.into(), credentials: Credentials::default() }
+    }
+
+    /// 要求请求携带访问令牌（`authorization: Bearer <令牌>`）或客户端的 API 密钥（`x-api-key`）
+    pub fn with_credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    /// 发出请求的客户端，持有访问令牌或不需要认证时为 None；令牌和密钥都缺失或无效时返回 UNAUTHENTICATED
+    fn client<T>(&self, request: &Request<T>) -> Result<Option<String>, Status> {
+        let metadata = request.metadata();
+        let header = |name| metadata.get(name).and_then(|value| value.to_str().ok());
+        let bearer = header("authorization").and_then(|value| value.strip_prefix("Bearer ")).map(str::trim);
+        match self.credentials.authenticate(&[bearer, header("x-api-key")]) {
+            Some(caller) => Ok(caller.client()),
+            None => Err(Status::unauthenticated("缺少或无效的访问令牌或 API 密钥")),
+        }
+    }
+
+    async fn send<M>(&self, msg: M) -> Result<M::Result, Status>
+    where
+        M: Message + Send + 'static,
+        M::Result: Send,
+        DownloadManagerActor: Handler<M>,
+    {
+        self.manager.send(msg).await.map_err(|e| Status::unavailable(format!("下载管理器已停止: {}", e)))
+    }
+
+    /// 查询任务，不存在时返回 NOT_FOUND
+    /// 查询 `client` 可以操作的任务，不存在或属于其他客户端时返回 NOT_FOUND
+    async fn task(&self, task_id: Uuid, client: Option<&str>) -> Result<DownloadTaskMeta, Status> {
+        self.send(QueryTaskDetail(task_id))
+            .await?
+            .filter(|meta| client.is_none() || meta.client.as_deref() == client)
+            .ok_or_else(|| Status::not_found(format!("任务不存在: {}", task_id)))
+    }
+}
+
+fn parse_task_id(task_id: &str) -> Result<Uuid, Status> {
+    Uuid::parse_str(task_id).map_err(|_| Status::invalid_argument(format!("无效的任务 ID: {}", task_id)))
+}
+
+fn invalid_argument(e: DownloadError) -> Status {
+    Status::invalid_argument(e.to_string())
+}
+
+/// 任务当前状态对应的进度事件
+fn snapshot(meta: &DownloadTaskMeta) -> ProgressEvent {
+    ProgressEvent {
+        task_id: meta.id.to_string(),
+        status: meta.status.as_str().to_string(),
+        downloaded: meta.downloaded,
+        total: meta.total,
+        speed: meta.speed,
+        error: match &meta.status {
+            TaskStatus::Failed(error) => error.clone(),
+            _ => String::new(),
+        },
+    }
+}
+
+/// 把任务事件转换为进度事件，不需要推送的事件返回 None
+///
+/// 状态变化事件只带状态，`downloaded` 等字段由调用者从任务元数据中补上。
+fn progress_event(event: TaskEvent) -> Option<ProgressEvent> {
+    let (task_id, status, error) = match event {
+        TaskEvent::Progress { task_id, downloaded, total, speed } => {
+            return Some(ProgressEvent { task_id: task_id.to_string(), status: "running".to_string(), downloaded, total, speed, error: String::new() });
+        }
+        // 完成、失败之前先发布状态变化，这里只转发状态变化
+        TaskEvent::StatusChanged { task_id, to: TaskStatus::Failed(error), .. } => (task_id, "failed", error),
+        TaskEvent::StatusChanged { task_id, to, .. } => (task_id, to.as_str(), String::new()),
+        TaskEvent::Removed { task_id } => (task_id, "removed", String::new()),
+        _ => return None,
+    };
+    Some(ProgressEvent { task_id: task_id.to_string(), status: status.to_string(), error, ..Default::default() })
+}
+
+#[tonic::async_trait]
+impl Multidown for GrpcService {
+    async fn add_task(&self, request: Request<AddTaskRequest>) -> Result<Response<AddTaskResponse>, Status> {
+        let client = self.client(&request)?;
+        let request = request.into_inner();
+        let url = validator::parse_url(&request.url).map_err(invalid_argument)?;
+        let file_name = match request.file_name.is_empty() {
+            true => validator::file_name_from_url(&url),
+            false => request.file_name,
+        };
+        let mut builder = DownloadRequest::builder()
+            .url(url.to_string())
+            .output(Path::new(&self.download_dir).join(file_name).to_string_lossy())
+            .tags(request.tags);
+        for (name, value) in request.headers {
+            builder = builder.header(name, value);
+        }
+        if let Some(client) = &client {
+            builder = builder.client(client);
+        }
+        let task_id = self.send(CreateTask(builder.build().map_err(invalid_argument)?)).await?.map_err(invalid_argument)?;
+        self.manager.do_send(StartTaskFromMeta { task_id });
+        tracing::info!(task_id = %task_id, url = %url, client = client.as_deref().unwrap_or("-"), "通过 gRPC 添加任务");
+        let path = self.send(QueryTaskDetail(task_id)).await?.map(|meta| meta.file).unwrap_or_default();
+        Ok(Response::new(AddTaskResponse { task_id: task_id.to_string(), path }))
+    }
+
+    type StreamProgressStream = Pin<Box<dyn Stream<Item = Result<ProgressEvent, Status>> + Send>>;
+
+    async fn stream_progress(&self, request: Request<StreamProgressRequest>) -> Result<Response<Self::StreamProgressStream>, Status> {
+        let client = self.client(&request)?;
+        let request = request.into_inner();
+        let events: Pin<Box<dyn Stream<Item = TaskEvent> + Send>> = match request.task_id.is_empty() {
+            true => Box::pin(self.events.stream()),
+            false => {
+                // 先订阅再查询，不会漏掉查询之后的事件；已经结束的任务只推送一次最终状态
+                let task_id = parse_task_id(&request.task_id)?;
+                let events = self.events.task_stream(task_id);
+                let meta = self.task(task_id, client.as_deref()).await?;
+                if matches!(meta.status, TaskStatus::Completed | TaskStatus::Failed(_) | TaskStatus::Cancelled) {
+                    return Ok(Response::new(Box::pin(futures::stream::once(async move { Ok(snapshot(&meta)) }))));
+                }
+                Box::pin(events)
+            }
+        };
+        let manager = self.manager.clone();
+        let stream = events.filter_map(move |event| {
+            let manager = manager.clone();
+            let client = client.clone();
+            async move {
+                let mut progress = progress_event(event)?;
+                // 客户端只收到自己任务的事件；已删除的任务查不到归属，不再推送给客户端
+                let needs_meta = client.is_some() || (progress.status != "running" && progress.status != "removed");
+                if needs_meta {
+                    let id = Uuid::parse_str(&progress.task_id).ok()?;
+                    let meta = manager.send(QueryTaskDetail(id)).await.ok().flatten();
+                    if client.is_some() && meta.as_ref().and_then(|meta| meta.client.as_ref()) != client.as_ref() {
+                        return None;
+                    }
+                    if let Some(meta) = meta.filter(|_| progress.status != "running") {
+                        progress = ProgressEvent { status: progress.status, error: progress.error, ..snapshot(&meta) };
+                    }
+                }
+                Some(Ok(progress))
+            }
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn pause_task(&self, request: Request<PauseTaskRequest>) -> Result<Response<PauseTaskResponse>, Status> {
+        let client = self.client(&request)?;
+        let task_id = parse_task_id(&request.into_inner().task_id)?;
+        self.task(task_id, client.as_deref()).await?;
+        self.send(PauseTask(task_id)).await?;
+        tracing::info!(task_id = %task_id, "通过 gRPC 暂停任务");
+        Ok(Response::new(PauseTaskResponse {}))
+    }
+
+    async fn get_stats(&self, request: Request<GetStatsRequest>) -> Result<Response<Stats>, Status> {
+        let client = self.client(&request)?;
+        let mut stats = self.send(GetStats).await?;
+        let stats = match client {
+            Some(client) => stats.clients.remove(&client).unwrap_or_default(),
+            None => ClientStats {
+                total: stats.total,
+                running: stats.running,
+                completed: stats.completed,
+                failed: stats.failed,
+                paused: stats.paused,
+                total_bytes: stats.total_bytes,
+                downloaded_bytes: stats.downloaded_bytes,
+                speed: stats.speed,
+            },
+        };
+        Ok(Response::new(Stats {
+            total: stats.total as u64,
+            running: stats.running as u64,
+            completed: stats.completed as u64,
+            failed: stats.failed as u64,
+            paused: stats.paused as u64,
+            total_bytes: stats.total_bytes,
+            downloaded_bytes: stats.downloaded_bytes,
+            speed: stats.speed,
+        }))
+    }
+}
+
+/// 握手完成的 TLS 连接；tonic 只为它自己依赖的 tokio-rustls 版本实现了 [`Connected`]，这里包一层
+struct TlsConnection(tokio_rustls::server::TlsStream<TcpStream>);
+
+impl Connected for TlsConnection {
+    type ConnectInfo = TcpConnectInfo;
+
+    fn connect_info(&self) -> TcpConnectInfo {
+        self.0.get_ref().0.connect_info()
+    }
+}
+
+impl AsyncRead for TlsConnection {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TlsConnection {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
+
+/// 接受 TCP 连接并在各自的任务中完成握手，握手成功的连接交给 tonic；握手失败只记录日志
+async fn accept_tls(listener: TcpListener, acceptor: TlsAcceptor, connections: futures::channel::mpsc::UnboundedSender<io::Result<TlsConnection>>) {
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                // 文件描述符耗尽等错误会立即重现，稍等再接受
+                tracing::warn!(error = %e, "接受 gRPC 连接失败");
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        let connections = connections.clone();
+        tokio::spawn(async move {
+            match tls::handshake(&acceptor, stream).await {
+                Ok(stream) => {
+                    let _ = connections.unbounded_send(Ok(TlsConnection(stream)));
+                }
+                Err(e) => tracing::debug!(peer = %peer, error = %e, "TLS 握手失败"),
+            }
+        });
+    }
+}
+
+/// 在 `listener` 上提供 gRPC 服务，直到 `shutdown` 完成；`tls` 为 Some 时只接受 TLS 连接。
+/// 管理器的停止和会话保存由调用者负责
+pub async fn serve(
+    listener: std::net::TcpListener,
+    service: GrpcService,
+    tls: Option<TlsAcceptor>,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), DownloadError> {
+    let listener = listener
+        .set_nonblocking(true)
+        .and_then(|()| TcpListener::from_std(listener))
+        .map_err(|e| DownloadError::io_error_with_context("无法使用监听套接字", e))?;
+    let server = tonic::transport::Server::builder().add_service(MultidownServer::new(service));
+    let result = match tls {
+        None => {
+            let incoming = TcpIncoming::from_listener(listener, true, None)
+                .map_err(|e| DownloadError::network_error(format!("gRPC 服务出错: {}", e)))?;
+            server.serve_with_incoming_shutdown(incoming, shutdown).await
+        }
+        Some(acceptor) => {
+            let (tx, rx) = futures::channel::mpsc::unbounded();
+            let accept = tokio::spawn(accept_tls(listener, acceptor, tx));
+            let result = server.serve_with_incoming_shutdown(rx, shutdown).await;
+            accept.abort();
+            result
+        }
+    };
+    result.map_err(|e| DownloadError::network_error(format!("gRPC 服务出错: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_event() {
+        let task_id = Uuid::new_v4();
+        let progress = progress_event(TaskEvent::Progress { task_id, downloaded: 5, total: 10, speed: 2 }).unwrap();
+        assert_eq!((progress.status.as_str(), progress.downloaded, progress.total, progress.speed), ("running", 5, 10, 2));
+
+        let failed = progress_event(TaskEvent::StatusChanged {
+            task_id,
+            from: TaskStatus::Running,
+            to: TaskStatus::Failed("HTTP 404".to_string()),
+        })
+        .unwrap();
+        assert_eq!((failed.status.as_str(), failed.error.as_str()), ("failed", "HTTP 404"));
+        assert_eq!(failed.task_id, task_id.to_string());
+
+        // 完成、失败事件与之前的状态变化重复，不再推送
+        assert!(progress_event(TaskEvent::Completed { task_id }).is_none());
+        assert!(progress_event(TaskEvent::Started { task_id }).is_none());
+        assert_eq!(progress_event(TaskEvent::Removed { task_id }).unwrap().status, "removed");
+    }
+}
@@ -0,0 +1,221 @@
+//! 消息目录：所有面向用户的界面文本（中文 / 英文）
+
+use super::Lang;
+
+macro_rules! catalog {
+    ($($name:ident => ($zh:expr, $en:expr),)*) => {
+        /// 消息标识
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum Msg {
+            $($name,)*
+        }
+
+        impl Msg {
+            /// 获取指定语言下的消息文本
+            pub fn text(self, lang: Lang) -> &'static str {
+                match (self, lang) {
+                    $(
+                        (Msg::$name, Lang::Zh) => $zh,
+                        (Msg::$name, Lang::En) => $en,
+                    )*
+                }
+            }
+        }
+    };
+}
+
+catalog! {
+    // ===== 启动与参数 =====
+    ParseArgsFailed => ("参数解析失败: {}", "Failed to parse arguments: {}"),
+    GetUrlsFailed => ("获取URL列表失败: {}", "Failed to read URL list: {}"),
+    InvalidReportArg => ("报告参数无效: {}", "Invalid report option: {}"),
+    ScriptLoadFailed => ("加载脚本失败: {}", "Failed to load script: {}"),
+    ConfigParseFailed => ("配置文件 {} 格式错误:\n{}", "Failed to parse config file {}:\n{}"),
+    ConfigBackedUp => ("已将原配置文件备份到 {}，并使用默认配置重新生成", "Backed up the broken config file to {} and regenerated it with defaults"),
+    SessionLocked => (
+        "另一个 multidown 进程（PID {}）正在使用当前会话（{}），请等它结束后再试，可以用 `multidown status` 查看它的进度",
+        "Another multidown process (PID {}) is using this session ({}); wait for it to finish, or run `multidown status` to see its progress"
+    ),
+    SessionLockFailed => ("无法获取会话锁: {}", "Failed to acquire the session lock: {}"),
+    ConfigLoaded => ("配置加载成功", "Configuration loaded"),
+    ConfigSummary => (
+        "配置摘要:\n- 下载目录: {}\n- 线程数: {}\n- 并发数: {}\n- 速度限制: {} KB/s\n- 超时时间: {} 秒\n- 重试次数: {}\n- 断点续传: {}\n- 分块下载: {}",
+        "Configuration summary:\n- Download directory: {}\n- Threads: {}\n- Concurrent downloads: {}\n- Speed limit: {} KB/s\n- Timeout: {} s\n- Retries: {}\n- Resume: {}\n- Chunked download: {}"
+    ),
+    Unlimited => ("不限速", "unlimited"),
+    Enabled => ("启用", "enabled"),
+    Disabled => ("禁用", "disabled"),
+
+    // ===== 任务创建与主循环 =====
+    NoTasks => ("没有可下载的任务", "No tasks to download"),
+    StartDownloadInteractive => (
+        "\n开始下载... (按 'p' 暂停, 'r' 重试失败的任务, '↑'/'↓' 选择任务, 'b' 优先下载选中的任务, 'c' 取消, 'q' 退出)",
+        "\nStarting download... (press 'p' to pause, 'r' to retry failed tasks, '↑'/'↓' to select a task, 'b' to prioritize it, 'c' to cancel, 'q' to quit)"
+    ),
+    StartDownload => ("开始下载...", "Starting download..."),
+    MoreTasks => ("  ……另外 {} 个任务", "  ... and {} more task(s)"),
+    TaskCreated => ("✓ 创建下载任务: {}", "✓ Task created: {}"),
+    TaskResumed => ("↻ 继续上次中断的任务: {}", "↻ Resuming interrupted task: {}"),
+    TaskCreateFailed => ("✗ 创建下载任务失败: {} - {}", "✗ Failed to create task: {} - {}"),
+    TaskSendFailed => ("✗ 发送创建任务消息失败: {} - {}", "✗ Failed to submit task: {} - {}"),
+    UserQuit => ("\n用户退出", "\nQuit by user"),
+    Interrupted => (
+        "\n收到 {} 信号，已暂停所有任务并保存会话，重新运行即可继续下载",
+        "\nReceived {}; all tasks paused and the session saved. Run again to resume"
+    ),
+    AllPaused => ("\n已暂停所有下载任务", "\nAll downloads paused"),
+    AllCancelled => ("\n已取消所有下载任务", "\nAll downloads cancelled"),
+    RetryQueued => ("\n已重新排队 {} 个失败的任务", "\nRe-queued {} failed task(s)"),
+    TaskBoosted => ("\n优先下载选中的任务：使用全部连接且不限速，其他任务暂时降为 1 个连接", "\nPrioritizing the selected task: all connections and no speed limit; other tasks drop to 1 connection"),
+    BoostCleared => ("\n已恢复正常调度", "\nNormal scheduling restored"),
+    NoFailedTasks => ("没有失败的任务", "No failed tasks"),
+    DownloadFinished => ("下载完成", "Download finished"),
+
+    // ===== 结束统计与报告 =====
+    StatsHeader => ("\n下载统计:", "\nDownload statistics:"),
+    StatsTotal => ("  总任务数: {}", "  Total tasks: {}"),
+    StatsCompleted => ("  成功完成: {}", "  Completed: {}"),
+    StatsFailed => ("  失败: {}", "  Failed: {}"),
+    StatsPaused => ("  暂停: {}", "  Paused: {}"),
+    ReportExported => ("汇总报告已导出: {}", "Summary report written: {}"),
+    ReportExportFailed => ("导出汇总报告失败: {}", "Failed to write summary report: {}"),
+    MetricsHeader => ("\n性能指标:", "\nPerformance:"),
+    MetricsLine => (
+        "  {}: 耗时 {}，平均 {}/s，峰值 {}/s，重试 {} 次，错误 {} 次（网络 {}，IO {}，超时 {}）",
+        "  {}: {} elapsed, avg {}/s, peak {}/s, {} retries, {} errors (network {}, io {}, timeout {})"
+    ),
+    FailuresHeader => ("{} 个任务失败: {}", "{} task(s) failed: {}"),
+    FailureSuggestion => ("（建议: {}）", " (suggestion: {})"),
+
+    // ===== 下载历史 =====
+    HistoryEmpty => ("没有下载历史", "No download history"),
+    SkipDownloaded => ("- 跳过已下载: {}", "- Skipping already downloaded: {}"),
+    ConflictPrompt => (
+        "文件已存在: {}\n  [o] 覆盖  [r] 重命名  [c] 续传  [s] 跳过（默认）；输入大写字母应用到之后所有冲突: ",
+        "File already exists: {}\n  [o] overwrite  [r] rename  [c] resume  [s] skip (default); use an uppercase letter to apply to all remaining conflicts: "
+    ),
+    ConflictInvalidChoice => ("请输入 o、r、c 或 s", "Please answer o, r, c or s"),
+    SkipExisting => ("- 跳过已存在的文件: {}", "- Skipping existing file: {}"),
+    AllSkipped => ("没有需要下载的 URL：均已下载过或已跳过", "Nothing to download: all URLs were already downloaded or skipped"),
+
+    // ===== 会话状态 =====
+    ListColumns => ("任务ID\t状态\t进度\t速度\t大小\t文件\tURL\t错误", "ID\tSTATUS\tPERCENT\tSPEED\tSIZE\tFILE\tURL\tERROR"),
+    TaskRemoved => ("已删除任务 {}: {}（删除了 {} 个文件或目录）", "Removed task {}: {} ({} file(s) or directories deleted)"),
+    TaskRemovedByDaemon => ("已删除任务 {}: {}", "Removed task {}: {}"),
+    TaskPaused => ("已暂停任务 {}", "Paused task {}"),
+    PauseNeedsDaemon => ("没有正在运行的守护进程，暂停任务需要先运行 multidown serve", "No daemon is running; pausing tasks requires multidown serve"),
+    TaskMoved => ("已移动任务 {}: {} -> {}", "Moved task {}: {} -> {}"),
+    TaskFinishedNoMove => ("任务 {} 已经结束，请直接移动文件", "Task {} has already finished; move the file directly"),
+    TaskMoveFailed => ("移动任务 {} 失败: {}", "Failed to move task {}: {}"),
+    TaskNotFound => ("会话中没有任务 {}（任务 ID 至少写前 8 位）", "No task {} in the session (use at least the first 8 characters of the ID)"),
+    TaskIdAmbiguous => ("任务 ID {} 对应多个任务，请多写几位", "Task ID {} matches several tasks; use more characters"),
+    SessionSaveFailed => ("保存会话失败: {}", "Failed to save the session: {}"),
+    ListEmpty => ("没有符合条件的任务", "No matching tasks"),
+    StatusEmpty => ("当前会话没有任务", "No tasks in the current session"),
+    StatusClient => ("客户端 {}: {} 个任务，{} 个下载中，{} / {}，{}/s", "Client {}: {} task(s), {} running, {} / {}, {}/s"),
+    StatusHeader => ("任务ID    状态       进度    已下载 / 总大小            速度          文件", "TASK ID   STATUS     PERCENT DOWNLOADED / TOTAL         SPEED         FILE"),
+
+    // ===== 任务队列 =====
+    QueueExported => ("已导出 {} 个未完成的任务: {}", "Exported {} unfinished task(s) to {}"),
+    QueueExportFailed => ("导出任务队列失败: {}", "Failed to export task queue: {}"),
+    QueueReadFailed => ("读取任务队列失败: {}", "Failed to read task queue: {}"),
+    QueueImported => ("已导入 {} 个任务", "Imported {} task(s)"),
+    LfsPointerInvalid => ("跳过 LFS 指针 {}: {}", "Skipping LFS pointer {}: {}"),
+    LfsResolveFailed => ("查询 LFS 对象失败: {}", "Failed to resolve LFS objects: {}"),
+    OciResolveFailed => ("跳过镜像 {}: {}", "Skipping image {}: {}"),
+    OciResolved => ("镜像 {}: {} 个内容需要下载，保存到 {}", "Image {}: {} blob(s) to download into {}"),
+    QueueNothingImported => ("队列中没有可导入的新任务", "No new tasks to import from the queue"),
+
+    // ===== 流量统计 =====
+    UsageEmpty => ("自 {} 起没有下载流量", "No download traffic since {}"),
+    UsageHeader => ("自 {} 起的下载流量:", "Download traffic since {}:"),
+    UsageByHost => ("按主机:", "By host:"),
+    UsageByDay => ("按日期:", "By day:"),
+    UsageTotal => ("合计: {}", "Total: {}"),
+
+    // ===== 配置命令 =====
+    ConfigSet => ("已设置 {} = {}", "Set {} = {}"),
+    ConfigFailed => ("配置操作失败: {}", "Config command failed: {}"),
+
+    // ===== 基准测试 =====
+    BenchNoRange => ("服务器不支持 Range 请求或没有返回文件大小，无法测试", "The server does not support range requests or did not report the file size; cannot benchmark"),
+    BenchStart => ("测试 {}（{}），共 {} 组，每组 {} 秒", "Benchmarking {} ({}): {} combinations, {} s each"),
+    BenchHeader => ("连接数   分块大小         速度   错误", "CONNS    CHUNK SIZE      SPEED   ERRORS"),
+    BenchBest => ("推荐: 连接数 {}，分块大小 {}（{}/s）", "Recommended: {} connections, chunk size {} ({}/s)"),
+    BenchNoResult => ("所有测试均失败，没有可推荐的组合", "Every combination failed; nothing to recommend"),
+    BenchSaved => ("已将推荐值写入 {} 的 URL 规则: {}", "Saved the recommendation to {} as a URL rule: {}"),
+    BenchSaveHint => ("使用 --save 可以把推荐值保存为该主机的 URL 规则", "Use --save to store the recommendation as a URL rule for this host"),
+    BenchFailed => ("基准测试失败: {}", "Benchmark failed: {}"),
+    DaemonListening => ("守护进程已启动，控制通道: {}，按 Ctrl+C 停止", "Daemon started, control channel at {}; press Ctrl+C to stop"),
+    DaemonFailed => ("无法启动守护进程: {}", "Failed to start the daemon: {}"),
+    DaemonStopped => ("收到 {}，已暂停所有任务并保存会话", "Received {}; all tasks paused and the session saved"),
+    DaemonRequestFailed => ("守护进程返回错误: {}", "Daemon request failed: {}"),
+    WebListening => ("网页控制台: {}", "Web dashboard at {}"),
+    GrpcListening => ("gRPC 服务已启动: {}", "gRPC server listening on {}"),
+    GrpcFailed => ("gRPC 服务出错: {}", "gRPC server error: {}"),
+    ServiceInstalled => ("已注册服务 {}，用 sc start {} 启动", "Service {} installed; start it with sc start {}"),
+    ServiceUninstalled => ("已删除服务 {}", "Service {} removed"),
+    ServiceFailed => ("服务操作失败: {}", "Service command failed: {}"),
+    CheckHeader => ("状态  大小        Range  重定向  URL", "STATUS  SIZE        RANGE  REDIRS  URL"),
+    CheckSummary => ("共 {} 个 URL：{} 个可用，{} 个失败", "{} URL(s): {} ok, {} failed"),
+    StreamSingleUrl => ("-O - 只能下载一个 URL", "-O - downloads exactly one URL"),
+    StreamProgress => ("已输出 {} / {}，{}/s", "streamed {} / {}, {}/s"),
+    StreamDone => ("已输出 {}", "streamed {}"),
+    StreamFailed => ("管道下载失败: {}", "stream failed: {}"),
+    VerifyNotFound => ("下载历史中没有 {} 的成功下载记录", "No successful download of {} in the history"),
+    VerifyOk => ("正常", "ok"),
+    VerifyMissing => ("文件不存在", "missing"),
+    VerifySizeMismatch => ("已损坏：大小 {}，记录为 {}", "corrupted: size {}, expected {}"),
+    VerifyChecksumMismatch => ("已损坏：SHA-256 与记录不一致", "corrupted: SHA-256 mismatch"),
+    VerifyStale => ("已过期：{}", "stale: {}"),
+    VerifyError => ("无法校验：{}", "cannot verify: {}"),
+    VerifySummary => ("共 {} 个文件：{} 个正常，{} 个损坏或缺失，{} 个已过期，{} 个无法校验", "{} file(s): {} ok, {} corrupted or missing, {} stale, {} unverified"),
+
+    // ===== 密钥 =====
+    SecretPrompt => ("请输入密钥 {} 的值（不会回显）: ", "Enter the value for secret {} (input is hidden): "),
+    SecretSaved => ("已将密钥 {} 保存到系统密钥环", "Saved secret {} to the OS keyring"),
+    SecretDeleted => ("已删除密钥 {}", "Deleted secret {}"),
+    SecretFound => ("密钥 {} 存在", "Secret {} exists"),
+    SecretFailed => ("密钥操作失败: {}", "Secret command failed: {}"),
+
+    // ===== 桌面通知 =====
+    NotifyCompletedTitle => ("下载完成", "Download complete"),
+    NotifyFailedTitle => ("下载失败", "Download failed"),
+    NotifyFailedBody => ("{}\n{}", "{}\n{}"),
+
+    // ===== 错误类别名称 =====
+    KindNetwork => ("网络错误", "network error"),
+    KindIo => ("IO错误", "I/O error"),
+    KindInvalidUrl => ("无效的URL", "invalid URL"),
+    KindUnsupportedProtocol => ("不支持的协议", "unsupported protocol"),
+    KindFileExists => ("文件已存在", "file already exists"),
+    KindInsufficientSpace => ("磁盘空间不足", "insufficient disk space"),
+    KindPermission => ("权限错误", "permission denied"),
+    KindTimeout => ("下载超时", "timeout"),
+    KindCancelled => ("下载被取消", "cancelled"),
+    KindPaused => ("下载暂停", "paused"),
+    KindMaxRetriesExceeded => ("重试次数超过限制", "retry limit exceeded"),
+    KindSizeMismatch => ("文件大小不匹配", "size mismatch"),
+    KindSizeLimit => ("文件大小超过限制", "file size limit exceeded"),
+    KindDeadlineExceeded => ("超过总时间限制", "time limit exceeded"),
+    KindQuotaExceeded => ("超出下载配额", "download quota exceeded"),
+    KindChecksumMismatch => ("校验和不匹配", "checksum mismatch"),
+    KindServer => ("服务器错误", "server error"),
+    KindResumeFailed => ("续传失败", "resume failed"),
+    KindUnknown => ("未知错误", "unknown error"),
+
+    // ===== 错误建议 =====
+    SuggestNetwork => ("检查网络连接，稍后重试", "check your network connection and try again later"),
+    SuggestTimeout => ("网络超时，请检查网络连接或增加超时时间", "the network timed out; check your connection or increase the timeout"),
+    SuggestServer => ("服务器暂时不可用，请稍后重试", "the server is temporarily unavailable; try again later"),
+    SuggestClientStatus => ("服务器拒绝了请求，请检查URL、认证信息和访问权限", "the server rejected the request; check the URL, credentials and access permissions"),
+    SuggestInvalidUrl => ("请检查URL格式是否正确", "check that the URL is well-formed"),
+    SuggestFileExists => ("文件已存在，请删除或重命名", "the file already exists; delete or rename it"),
+    SuggestPermission => ("权限不足，请检查文件权限或使用管理员权限", "permission denied; check file permissions or run with elevated privileges"),
+    SuggestInsufficientSpace => ("磁盘空间不足，请清理磁盘空间", "not enough disk space; free some space"),
+    SuggestSizeMismatch => ("文件大小不匹配，可能是下载不完整", "size mismatch; the download may be incomplete"),
+    SuggestDeadlineExceeded => ("任务超过 --timeout-total（timeout_total）的限制，调大限制后用 retry-failed 继续", "the task exceeded --timeout-total (timeout_total); raise the limit and continue with retry-failed"),
+    SuggestSizeLimit => ("文件超过 --max-file-size（max_file_size）的限制，确认无误后调大限制再下载", "the file exceeds --max-file-size (max_file_size); raise the limit if the size is expected"),
+    SuggestQuotaExceeded => ("本次运行的下载总量已达到 --quota（run_quota），调大配额后用 retry-failed 继续", "this run reached --quota (run_quota); raise the quota and continue with retry-failed"),
+    SuggestResumeFailed => ("断点续传失败，将重新下载", "resume failed; the file will be downloaded again"),
+}
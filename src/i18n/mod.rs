@@ -0,0 +1,120 @@
+//! i18n: 界面语言选择与消息目录
+//!
+//! ## 语言选择
+//!
+//! 优先级：`--lang` 参数 > 环境变量（`LC_ALL` / `LC_MESSAGES` / `LANG`）> 默认中文。
+//! 环境变量以 `zh` 开头时使用中文，其余非空取值使用英文。
+//!
+//! ## 使用方式
+//!
+//! ```ignore
+//! use multidown::i18n::{t, tf, Msg};
+//! println!("{}", t(Msg::ConfigLoaded));
+//! println!("{}", tf(Msg::TaskCreated, &[&file_name]));
+//! ```
+//!
+//! 消息文本中的 `{}` 按顺序替换为参数。
+
+mod catalog;
+
+pub use catalog::Msg;
+
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+/// 界面语言
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Lang {
+    /// 简体中文
+    Zh,
+    /// English
+    En,
+}
+
+static CURRENT: OnceLock<Lang> = OnceLock::new();
+
+impl Lang {
+    /// 从 locale 字符串（如 `zh_CN.UTF-8`、`en_US`）推断语言
+    pub fn from_locale(locale: &str) -> Option<Lang> {
+        let locale = locale.trim();
+        if locale.is_empty() {
+            None
+        } else if locale.to_ascii_lowercase().starts_with("zh") {
+            Some(Lang::Zh)
+        } else {
+            Some(Lang::En)
+        }
+    }
+
+    /// 根据环境变量检测语言
+    pub fn detect() -> Lang {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|key| std::env::var(key).ok())
+            .find_map(|value| Lang::from_locale(&value))
+            .unwrap_or(Lang::Zh)
+    }
+}
+
+/// 初始化界面语言，只有第一次调用生效
+pub fn init(explicit: Option<Lang>) -> Lang {
+    *CURRENT.get_or_init(|| explicit.unwrap_or_else(Lang::detect))
+}
+
+/// 当前界面语言，未初始化时为中文
+pub fn current() -> Lang {
+    CURRENT.get().copied().unwrap_or(Lang::Zh)
+}
+
+/// 获取当前语言下的消息文本
+pub fn t(msg: Msg) -> &'static str {
+    msg.text(current())
+}
+
+/// 获取当前语言下的消息文本，并按顺序替换其中的 `{}`
+pub fn tf(msg: Msg, args: &[&dyn Display]) -> String {
+    format_template(t(msg), args)
+}
+
+fn format_template(template: &str, args: &[&dyn Display]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut args = args.iter();
+    let mut parts = template.split("{}");
+    if let Some(first) = parts.next() {
+        out.push_str(first);
+    }
+    for part in parts {
+        match args.next() {
+            Some(arg) => out.push_str(&arg.to_string()),
+            None => out.push_str("{}"),
+        }
+        out.push_str(part);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lang_from_locale() {
+        assert_eq!(Lang::from_locale("zh_CN.UTF-8"), Some(Lang::Zh));
+        assert_eq!(Lang::from_locale("en_US.UTF-8"), Some(Lang::En));
+        assert_eq!(Lang::from_locale("C"), Some(Lang::En));
+        assert_eq!(Lang::from_locale(""), None);
+    }
+
+    #[test]
+    fn test_format_template() {
+        assert_eq!(format_template("{} -> {}", &[&"a", &1]), "a -> 1");
+        assert_eq!(format_template("no args", &[]), "no args");
+        assert_eq!(format_template("{} {}", &[&"x"]), "x {}");
+    }
+
+    #[test]
+    fn test_catalog_has_both_languages() {
+        assert_eq!(Msg::ConfigLoaded.text(Lang::Zh), "配置加载成功");
+        assert_eq!(Msg::ConfigLoaded.text(Lang::En), "Configuration loaded");
+    }
+}
@@ -1,5 +1,9 @@
 pub mod cli;
 pub mod config;
 pub mod core;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod i18n;
 pub mod ui;
-pub mod utils; 
\ No newline at end of file
+pub mod utils;
+pub mod web; 
\ No newline at end of file
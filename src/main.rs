@@ -1,102 +1,485 @@
 use multidown::cli;
+use multidown::cli::exit_code::ExitCode;
+use multidown::cli::conflict::{self, ConflictPolicy, ConflictResolver};
+use multidown::cli::url_list::UrlEntry;
 use multidown::core::actor_manager::*;
+use multidown::core::history::{HistoryStore, HISTORY_FILE};
+use multidown::core::queue;
+use multidown::core::script::ScriptHooks;
+use multidown::utils::signal::wait_for_termination;
+use multidown::utils::validator;
+use multidown::core::session_lock::{LockError, SessionLock, LOCK_FILE};
+use multidown::core::task::{DownloadRequest, TaskOptions, TaskStatus};
+use multidown::config::Config;
 use actix::prelude::*;
-use multidown::utils::logger::{LoggerActor, LoggerExt};
-use log::LevelFilter;
+use multidown::utils::logger::{self, LoggerActor, LoggerExt};
+use tracing::level_filters::LevelFilter;
 use std::path::Path;
 use uuid::Uuid;
 use crossterm::{
     cursor, execute, terminal,
-    event::{self, Event, KeyCode},
+    event::{self, Event, KeyCode, KeyModifiers},
 };
-use multidown::ui::ProgressManager;
+use multidown::ui::{ProgressManager, ProgressMode, TaskRow};
+use multidown::ui::report::{self, ReportFormat, TaskReport};
+use multidown::ui::summary;
+use multidown::i18n::{t, tf, Msg};
+use std::io::IsTerminal;
 
 const PROGRESS_UPDATE_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
 const KEYBOARD_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+const CONFIG_WATCH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
 
 #[actix::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let logger = LoggerActor::new("logs/app.log", LevelFilter::Info, 10 * 1024 * 1024)?.start();
+    // 解析参数和配置（日志格式由参数决定，所以先解析再初始化日志）
+    let parsed = cli::Args::parse_args();
+    let log_format = parsed.as_ref().map(|(args, _)| args.log_format).unwrap_or_default();
+    logger::init("logs/app.log", LevelFilter::INFO, 10 * 1024 * 1024, log_format)?;
+    let logger = LoggerActor.start();
     logger.info("程序启动");
 
-    // 解析参数和配置
-    let (args, config) = match cli::Args::parse_args() {
+    let (args, config) = match parsed {
         Ok((args, config)) => (args, config),
         Err(e) => {
             logger.error(&format!("参数解析失败: {}", e));
-            eprintln!("参数解析失败: {}", e);
-            std::process::exit(1);
+            eprintln!("{}", tf(Msg::ParseArgsFailed, &[&e]));
+            std::process::exit(ExitCode::ConfigError.code());
         }
     };
 
-    // 获取下载URL列表
-    let urls = match args.get_urls() {
-        Ok(urls) => urls,
+    multidown::core::task::http::set_trace(args.trace_http);
+
+    // 只有 retry-failed、import-queue、lfs（DownloadCommand）进入下载流程，其余子命令执行后退出
+    let download_command = match &args.command {
+        Some(cli::Subcommands::Command(command)) => {
+            let exit_code = cli::commands::run(command, &args.config, &config).await;
+            std::process::exit(exit_code.code());
+        }
+        Some(cli::Subcommands::Download(command)) => Some(command),
+        None => None,
+    };
+    let retry_failed = matches!(download_command, Some(cli::DownloadCommand::RetryFailed { .. }));
+    let import_queue = match download_command {
+        Some(cli::DownloadCommand::ImportQueue { path }) => match queue::read(path) {
+            Ok(queue) => Some(queue),
+            Err(e) => {
+                logger.error(&format!("读取任务队列失败: {}", e));
+                eprintln!("{}", tf(Msg::QueueReadFailed, &[&e]));
+                std::process::exit(ExitCode::ConfigError.code());
+            }
+        },
+        _ => None,
+    };
+    let lfs_entries = match download_command {
+        Some(cli::DownloadCommand::Lfs { pointers, repo, token }) => {
+            match cli::commands::lfs_entries(pointers, repo, token.as_deref(), &config).await {
+                Ok(entries) => Some(entries),
+                Err(exit_code) => std::process::exit(exit_code.code()),
+            }
+        }
+        _ => None,
+    };
+
+    // 获取下载URL列表，重试失败任务或导入队列时从会话或队列文件中读取
+    let entries = match args.get_entries() {
+        _ if retry_failed || import_queue.is_some() => Vec::new(),
+        _ if lfs_entries.is_some() => lfs_entries.unwrap_or_default(),
+        Ok(entries) => entries,
         Err(e) => {
             logger.error(&format!("获取URL列表失败: {}", e));
-            eprintln!("获取URL列表失败: {}", e);
-            std::process::exit(1);
+            eprintln!("{}", tf(Msg::GetUrlsFailed, &[&e]));
+            std::process::exit(ExitCode::ConfigError.code());
         }
     };
+    // docker:// 镜像先获取清单、写出布局目录，再把配置和各层作为普通任务下载
+    let entries = match cli::commands::oci_entries(entries, &args.download_dir, args.quiet, &config).await {
+        Ok(entries) => entries,
+        Err(exit_code) => std::process::exit(exit_code.code()),
+    };
+    let urls: Vec<String> = entries.iter().map(|entry| entry.url.clone()).collect();
+
+    // 只检查 URL，不创建任务也不占用会话锁
+    if args.check_only {
+        let exit_code = cli::commands::check_urls(&args, &urls, &config).await;
+        std::process::exit(exit_code.code());
+    }
+
+    // 管道模式不创建任务，标准输出只写文件内容
+    if args.output.is_some() {
+        let [url] = urls.as_slice() else {
+            eprintln!("{}", t(Msg::StreamSingleUrl));
+            std::process::exit(ExitCode::ConfigError.code());
+        };
+        let exit_code = cli::commands::stream_to_stdout(&args, url, &config).await;
+        std::process::exit(exit_code.code());
+    }
+
+    // 提前校验报告格式，避免下载结束后才发现无法导出
+    if let Some(path) = &args.report {
+        if let Err(e) = ReportFormat::from_path(path) {
+            logger.error(&format!("报告参数无效: {}", e));
+            eprintln!("{}", tf(Msg::InvalidReportArg, &[&e]));
+            std::process::exit(ExitCode::ConfigError.code());
+        }
+    }
+
+    // 脚本有语法错误时在开始下载前报告
+    if let Err(e) = ScriptHooks::load(&config.script) {
+        logger.error(&format!("加载脚本失败: {}", e));
+        eprintln!("{}", tf(Msg::ScriptLoadFailed, &[&e]));
+        std::process::exit(ExitCode::ConfigError.code());
+    }
 
     logger.info(&format!("解析到的URLs: {:?}", urls));
     logger.info(&format!("配置文件路径: {}", args.config));
     logger.info(&format!("下载目录: {}", args.download_dir));
     logger.info(&format!("配置摘要:\n{}", config.get_summary()));
 
-    println!("配置加载成功");
-    println!("{}", config.get_summary());
+    if !args.quiet {
+        println!("{}", t(Msg::ConfigLoaded));
+        println!("{}", config.get_summary());
+    }
+
+    // 同一会话目录只允许一个进程下载，锁在进程退出时由系统释放
+    let lock_path = config.state_path(LOCK_FILE);
+    let _session_lock = match SessionLock::acquire(&lock_path) {
+        Ok(lock) => lock,
+        Err(LockError::Held(owner)) => {
+            let pid = owner.map_or_else(|| "?".to_string(), |o| o.pid.to_string());
+            logger.error(&format!("会话已被进程 {} 占用", pid));
+            eprintln!("{}", tf(Msg::SessionLocked, &[&pid, &lock_path.display()]));
+            std::process::exit(ExitCode::SessionLocked.code());
+        }
+        Err(LockError::Io(e)) => {
+            logger.error(&format!("无法获取会话锁: {}", e));
+            eprintln!("{}", tf(Msg::SessionLockFailed, &[&e]));
+            std::process::exit(ExitCode::SessionLocked.code());
+        }
+    };
+
+    // 目标文件已存在时的处理，只有在终端中运行且没有 -q 时才询问
+    let mut conflicts = ConflictResolver::new(
+        args.on_conflict,
+        config.continue_partial,
+        !args.quiet && std::io::stdin().is_terminal() && std::io::stdout().is_terminal(),
+    );
+
+    let history = HistoryStore::new(config.state_path(HISTORY_FILE));
 
     // 创建下载管理器
     let download_manager = DownloadManagerActor::new(config).start();
     logger.info("下载管理器已启动");
+    spawn_config_watcher(args.clone(), download_manager.clone(), logger.clone());
 
     // 创建并启动所有下载任务
-    let task_ids = create_and_start_tasks(&download_manager, &args, &urls, &logger).await?;
+    let (task_ids, skipped) = if retry_failed {
+        // 指定标签时只重试带有该标签的任务
+        let task_ids = match &args.command {
+            Some(cli::Subcommands::Download(cli::DownloadCommand::RetryFailed { tag: Some(tag) })) => Some(
+                download_manager
+                    .send(ListTasks)
+                    .await?
+                    .into_iter()
+                    .filter(|m| m.has_tag(Some(tag)))
+                    .map(|m| m.id)
+                    .collect(),
+            ),
+            _ => None,
+        };
+        let task_ids = download_manager.send(RetryFailedTasks { task_ids }).await?;
+        if task_ids.is_empty() {
+            println!("{}", t(Msg::NoFailedTasks));
+            return Ok(());
+        }
+        logger.info(&format!("重新排队 {} 个失败的任务", task_ids.len()));
+        (task_ids, 0)
+    } else if let Some(queue) = import_queue {
+        let task_ids = download_manager.send(ImportQueue(queue.tasks)).await?;
+        if task_ids.is_empty() {
+            println!("{}", t(Msg::QueueNothingImported));
+            return Ok(());
+        }
+        logger.info(&format!("导入 {} 个任务", task_ids.len()));
+        if !args.quiet {
+            println!("{}", tf(Msg::QueueImported, &[&task_ids.len()]));
+        }
+        (task_ids, 0)
+    } else {
+        create_and_start_tasks(&download_manager, &args, &entries, &history, &mut conflicts, &logger).await?
+    };
 
-    if task_ids.is_empty() {
-        eprintln!("没有可下载的任务");
+    if task_ids.is_empty() && skipped > 0 {
+        if !args.quiet {
+            println!("{}", t(Msg::AllSkipped));
+        }
         return Ok(());
     }
 
-    println!("\n开始下载... (按 'p' 暂停, 'c' 取消, 'q' 退出)");
+    if task_ids.is_empty() {
+        eprintln!("{}", t(Msg::NoTasks));
+        std::process::exit(ExitCode::AllFailed.code());
+    }
+    // 运行结束时要汇总这些任务的结果，运行期间不按保留策略清理
+    download_manager.do_send(HoldTasks(task_ids.clone()));
+
+    // 只有 stdin/stdout 都是终端时才启用 raw mode 和键盘控制
+    let mode = ProgressMode::detect(args.quiet, args.no_progress);
+    let interactive = std::io::stdin().is_terminal() && std::io::stdout().is_terminal();
+
+    if !args.quiet {
+        if interactive {
+            println!("{}", t(Msg::StartDownloadInteractive));
+        } else {
+            println!("{}", t(Msg::StartDownload));
+        }
+    }
     logger.info(&format!("开始下载 {} 个任务", task_ids.len()));
 
     // 主循环：处理键盘输入和更新进度
-    run_download_loop(&download_manager, &task_ids, &logger).await?;
+    let interrupted = run_download_loop(&download_manager, &task_ids, &logger, mode, interactive, args.quiet).await?;
+
+    // 按创建顺序收集本次运行的任务
+    let all_metas = download_manager.send(ListTasks).await?;
+    let run_metas: Vec<DownloadTaskMeta> = task_ids
+        .iter()
+        .filter_map(|id| all_metas.iter().find(|m| m.id == *id).cloned())
+        .collect();
+
+    // 等待钩子命令和历史记录写完，避免进程退出时被中断
+    download_manager.send(WaitForBackgroundJobs).await?;
+
+    if !args.quiet {
+        if let Some(text) = summary::format_metrics(&run_metas) {
+            logger.info(&text);
+            print!("{}", text);
+        }
+    }
+
+    // 按错误类型分组列出失败任务（静默模式下同样输出到 stderr）
+    if let Some(text) = summary::format_failures(&summary::group_failures(&run_metas)) {
+        logger.error(&text);
+        eprint!("\n{}", text);
+    }
+
+    if let Some(path) = &args.report {
+        export_report(&run_metas, path, &logger, args.quiet);
+    }
+
+    let exit_code = if interrupted { ExitCode::Interrupted } else { ExitCode::from_tasks(&run_metas) };
+    logger.info(&format!("退出码: {}", exit_code.code()));
+    if exit_code != ExitCode::Success {
+        std::process::exit(exit_code.code());
+    }
 
     Ok(())
 }
 
-/// 创建并启动所有下载任务
+/// 监视配置文件（修改时间变化或 Unix 下收到 SIGHUP）并热重载到下载管理器
+fn spawn_config_watcher(
+    args: cli::Args,
+    download_manager: Addr<DownloadManagerActor>,
+    logger: Addr<LoggerActor>,
+) {
+    let modified_at = |path: &str| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+
+    actix::spawn(async move {
+        let mut last_modified = modified_at(&args.config);
+        let mut interval = tokio::time::interval(CONFIG_WATCH_INTERVAL);
+        #[cfg(unix)]
+        let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()).ok();
+
+        loop {
+            #[cfg(unix)]
+            let forced = tokio::select! {
+                _ = interval.tick() => false,
+                Some(_) = async {
+                    match hangup.as_mut() {
+                        Some(signal) => signal.recv().await,
+                        None => std::future::pending().await,
+                    }
+                } => true,
+            };
+            #[cfg(not(unix))]
+            let forced = {
+                interval.tick().await;
+                false
+            };
+
+            let modified = modified_at(&args.config);
+            if !forced && modified == last_modified {
+                continue;
+            }
+            last_modified = modified;
+
+            match Config::reload(&args.config, &args) {
+                Ok(config) => {
+                    logger.info(&format!("配置已重新加载:\n{}", config.get_summary()));
+                    download_manager.do_send(ReloadConfig(config));
+                }
+                Err(e) => logger.warn(&format!("重新加载配置失败，继续使用当前配置: {}", e)),
+            }
+        }
+    });
+}
+
+/// 交互模式下的终端状态，drop 时恢复（包括出错提前返回和 panic 的情况）
+struct RawTerminal;
+
+impl RawTerminal {
+    fn enable() -> std::io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(std::io::stdout(), cursor::Hide)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = execute!(std::io::stdout(), cursor::Show);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// 导出本次运行的汇总报告
+fn export_report(
+    metas: &[DownloadTaskMeta],
+    path: &str,
+    logger: &Addr<LoggerActor>,
+    quiet: bool,
+) {
+    let reports: Vec<TaskReport> = metas.iter().map(TaskReport::from_meta).collect();
+
+    match report::write_report(path, &reports) {
+        Ok(()) => {
+            logger.info(&format!("汇总报告已导出: {}", path));
+            if !quiet {
+                println!("{}", tf(Msg::ReportExported, &[&path]));
+            }
+        }
+        Err(e) => {
+            logger.error(&format!("导出汇总报告失败: {}", e));
+            eprintln!("{}", tf(Msg::ReportExportFailed, &[&e]));
+        }
+    }
+}
+
+/// 创建并启动所有下载任务，返回创建的任务和跳过的 URL 数量
 async fn create_and_start_tasks(
     download_manager: &Addr<DownloadManagerActor>,
     args: &cli::Args,
-    urls: &[String],
+    entries: &[UrlEntry],
+    history: &HistoryStore,
+    conflicts: &mut ConflictResolver,
     logger: &Addr<LoggerActor>,
-) -> Result<Vec<Uuid>, Box<dyn std::error::Error>> {
+) -> Result<(Vec<Uuid>, usize), Box<dyn std::error::Error>> {
     let mut task_ids = Vec::new();
+    let mut skipped = 0;
+    let downloaded = if args.no_redownload {
+        history.successful_urls()
+    } else {
+        Default::default()
+    };
+    // 上次运行被中断时留在会话中的任务，URL 和保存路径相同时接着下载，而不是从头创建新任务
+    let mut unfinished: Vec<DownloadTaskMeta> = download_manager
+        .send(ListTasks)
+        .await?
+        .into_iter()
+        .filter(|m| matches!(m.status, TaskStatus::Paused | TaskStatus::Pending))
+        .collect();
     
-    for url in urls {
-        let file_name = extract_filename_from_url(url, &args.file_name);
-        let file_path = Path::new(&args.download_dir).join(&file_name);
-        
-        match download_manager.send(CreateTask {
-            url: url.clone(),
-            file: file_path.to_string_lossy().to_string(),
-        }).await {
+    for entry in entries {
+        let url = &entry.url;
+        if downloaded.contains(url) {
+            skipped += 1;
+            logger.info(&format!("跳过已下载的URL: {}", url));
+            if !args.quiet {
+                println!("{}", tf(Msg::SkipDownloaded, &[url]));
+            }
+            continue;
+        }
+
+        let file_name = if let Some(out) = &entry.out {
+            out.clone()
+        } else if args.preserve_path && args.file_name.is_none() {
+            match validator::mirror_path(url) {
+                Ok(path) => path.to_string_lossy().into_owned(),
+                Err(e) => {
+                    logger.error(&format!("创建下载任务失败: {} - {}", url, e));
+                    eprintln!("{}", tf(Msg::TaskCreateFailed, &[url, &e]));
+                    continue;
+                }
+            }
+        } else {
+            extract_filename_from_url(url, &args.file_name)
+        };
+        let file_name = match &entry.dir {
+            Some(dir) => Path::new(dir).join(&file_name).to_string_lossy().into_owned(),
+            None => file_name,
+        };
+        let mut file_path = Path::new(&args.download_dir).join(&file_name);
+        let output = validator::output_path(&args.download_dir, &file_path.to_string_lossy()).ok();
+        if let Some(i) = unfinished.iter().position(|m| m.url == *url && Some(&m.file) == output.as_ref()) {
+            let meta = unfinished.swap_remove(i);
+            task_ids.push(meta.id);
+            logger.info(&format!("继续会话中的任务: {} -> {}", url, file_name));
+            if !args.quiet {
+                println!("{}", tf(Msg::TaskResumed, &[&file_name]));
+            }
+            continue;
+        }
+        let mut options = TaskOptions { checksum: entry.sha256.clone(), weight: entry.weight, ..args.task_options(url) };
+        options.headers.extend(entry.headers.iter().cloned());
+        if file_path.exists() {
+            match conflicts.resolve(&file_path, &mut std::io::stdin().lock(), &mut std::io::stdout()) {
+                ConflictPolicy::Overwrite => {
+                    if let Err(e) = std::fs::remove_file(&file_path) {
+                        logger.error(&format!("无法删除已存在的文件: {} - {}", file_path.display(), e));
+                        eprintln!("{}", tf(Msg::TaskCreateFailed, &[url, &e]));
+                        continue;
+                    }
+                    logger.info(&format!("覆盖已存在的文件: {}", file_path.display()));
+                }
+                ConflictPolicy::Rename => {
+                    file_path = conflict::renamed_path(&file_path);
+                    logger.info(&format!("文件已存在，另存为: {}", file_path.display()));
+                }
+                ConflictPolicy::Resume => options.continue_partial = Some(true),
+                ConflictPolicy::Skip => {
+                    skipped += 1;
+                    logger.info(&format!("跳过已存在的文件: {}", file_path.display()));
+                    if !args.quiet {
+                        println!("{}", tf(Msg::SkipExisting, &[&file_path.display()]));
+                    }
+                    continue;
+                }
+                // 任务开始时以“文件已存在”失败，配置中启用的 continue_partial 也不生效
+                ConflictPolicy::Fail | ConflictPolicy::Ask => options.continue_partial = Some(false),
+            }
+        }
+        let file_name = file_path.strip_prefix(&args.download_dir).unwrap_or(&file_path).to_string_lossy().into_owned();
+
+        let request = DownloadRequest::builder()
+            .url(url.clone())
+            .output(file_path.to_string_lossy())
+            .options(options)
+            .tags(args.tags.clone())
+            .build()?;
+        match download_manager.send(CreateTask(request)).await {
             Ok(Ok(task_id)) => {
                 task_ids.push(task_id);
                 logger.info(&format!("创建下载任务: {} -> {}", url, file_name));
-                println!("✓ 创建下载任务: {}", file_name);
+                if !args.quiet {
+                    println!("{}", tf(Msg::TaskCreated, &[&file_name]));
+                }
             }
             Ok(Err(e)) => {
                 logger.error(&format!("创建下载任务失败: {} - {}", url, e));
-                eprintln!("✗ 创建下载任务失败: {} - {}", url, e);
+                eprintln!("{}", tf(Msg::TaskCreateFailed, &[url, &e]));
             }
             Err(e) => {
                 logger.error(&format!("发送创建任务消息失败: {} - {}", url, e));
-                eprintln!("✗ 发送创建任务消息失败: {} - {}", url, e);
+                eprintln!("{}", tf(Msg::TaskSendFailed, &[url, &e]));
             }
         }
     }
@@ -106,7 +489,7 @@ async fn create_and_start_tasks(
         download_manager.do_send(StartTaskFromMeta { task_id: *task_id });
     }
 
-    Ok(task_ids)
+    Ok((task_ids, skipped))
 }
 
 /// 从URL中提取文件名
@@ -127,29 +510,75 @@ fn extract_filename_from_url(url: &str, custom_name: &Option<String>) -> String
     format!("download_{}", chrono::Utc::now().timestamp())
 }
 
-/// 运行下载主循环
+/// 按创建顺序为正在下载的任务各生成一行；只有一个任务时只在分块下载时显示（用于块分布图）
+async fn task_rows(
+    download_manager: &Addr<DownloadManagerActor>,
+    task_ids: &[Uuid],
+    boosted: Option<Uuid>,
+) -> Result<Vec<TaskRow>, Box<dyn std::error::Error>> {
+    let chunk_stats = download_manager.send(QueryChunkStats).await?;
+    let metas = download_manager.send(ListTasks).await?;
+    Ok(task_ids
+        .iter()
+        .filter_map(|id| {
+            let meta = metas.iter().find(|m| m.id == *id && m.status == TaskStatus::Running)?;
+            let chunk_map = chunk_stats.get(id).map(|stats| stats.map.clone());
+            if task_ids.len() == 1 && chunk_map.is_none() && meta.phase.is_none() {
+                return None;
+            }
+            Some(TaskRow {
+                id: *id,
+                name: Path::new(&meta.file).file_name()?.to_string_lossy().into_owned(),
+                downloaded: meta.downloaded,
+                total: meta.total,
+                speed: meta.speed,
+                chunk_map,
+                phase: meta.phase.zip(meta.phase_progress),
+                boosted: boosted == Some(*id),
+            })
+        })
+        .collect())
+}
+
+/// 运行下载主循环，返回是否被终止信号中断
 async fn run_download_loop(
     download_manager: &Addr<DownloadManagerActor>,
     task_ids: &[Uuid],
     logger: &Addr<LoggerActor>,
-) -> Result<(), Box<dyn std::error::Error>> {
+    mode: ProgressMode,
+    interactive: bool,
+    quiet: bool,
+) -> Result<bool, Box<dyn std::error::Error>> {
     let mut last_update = std::time::Instant::now();
 
     // 设置终端
-    terminal::enable_raw_mode()?;
-    execute!(std::io::stdout(), cursor::Hide)?;
+    let raw_terminal = if interactive { Some(RawTerminal::enable()?) } else { None };
+
+    // 收到终止信号时暂停所有任务并保存会话，再正常退出主循环恢复终端
+    let (signal_tx, mut signal_rx) = tokio::sync::oneshot::channel();
+    let signal_watcher = actix::spawn(async move {
+        let _ = signal_tx.send(wait_for_termination().await);
+    });
+    let mut interrupted = None;
 
     // 创建UI进度管理器
     let stats = download_manager.send(GetStats).await?;
-    let progress = ProgressManager::new(stats.total_bytes);
+    let mut progress = ProgressManager::with_mode(stats.total_bytes, mode);
+    let mut boosted = stats.boosted;
 
     loop {
         // 处理键盘输入
-        if let Ok(true) = event::poll(KEYBOARD_POLL_INTERVAL) {
-            if let Ok(Event::Key(key_event)) = event::read() {
-                match key_event.code {
+        if interactive && matches!(event::poll(KEYBOARD_POLL_INTERVAL), Ok(true)) {
+            match event::read() {
+                Ok(Event::Resize(columns, rows)) => progress.resize(columns, rows),
+                Ok(Event::Key(key_event)) => match key_event.code {
+                    // raw mode 下 Ctrl+C 不会产生 SIGINT，按同样的方式处理
+                    KeyCode::Char('c') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                        interrupted = Some("Ctrl+C");
+                        break;
+                    }
                     KeyCode::Char('q') | KeyCode::Char('Q') => {
-                        println!("\n用户退出");
+                        println!("{}", t(Msg::UserQuit));
                         logger.info("用户主动退出下载");
                         break;
                     }
@@ -158,30 +587,73 @@ async fn run_download_loop(
                         for task_id in task_ids {
                             download_manager.do_send(PauseTask(*task_id));
                         }
-                        println!("\n已暂停所有下载任务");
+                        println!("{}", t(Msg::AllPaused));
                         logger.info("用户暂停所有下载任务");
                     }
+                    KeyCode::Char('r') | KeyCode::Char('R') => {
+                        // 重新排队本次运行中失败的任务
+                        let retried = download_manager
+                            .send(RetryFailedTasks { task_ids: Some(task_ids.to_vec()) })
+                            .await?;
+                        println!("{}", tf(Msg::RetryQueued, &[&retried.len()]));
+                        logger.info(&format!("用户重试 {} 个失败的任务", retried.len()));
+                    }
+                    KeyCode::Up => progress.move_selection(-1),
+                    KeyCode::Down => progress.move_selection(1),
+                    KeyCode::Char('b') | KeyCode::Char('B') => {
+                        // 优先下载选中的任务，再按一次恢复正常调度
+                        if let Some(task_id) = progress.selected_task() {
+                            let target = if boosted == Some(task_id) { None } else { Some(task_id) };
+                            if download_manager.send(BoostTask(target)).await? {
+                                boosted = target;
+                                match target {
+                                    Some(_) => println!("{}", t(Msg::TaskBoosted)),
+                                    None => println!("{}", t(Msg::BoostCleared)),
+                                }
+                                logger.info(&format!("用户设置优先下载的任务: {:?}", target));
+                            }
+                        }
+                    }
                     KeyCode::Char('c') | KeyCode::Char('C') => {
                         // 取消所有任务
                         for task_id in task_ids {
                             download_manager.do_send(CancelTask(*task_id));
                         }
-                        println!("\n已取消所有下载任务");
+                        println!("{}", t(Msg::AllCancelled));
                         logger.info("用户取消所有下载任务");
                         break;
                     }
                     _ => {}
-                }
+                },
+                _ => {}
             }
         }
 
+        if let Ok(signal) = signal_rx.try_recv() {
+            interrupted = Some(signal);
+            break;
+        }
+
         // 更新进度
         if last_update.elapsed() >= PROGRESS_UPDATE_INTERVAL {
             let stats = download_manager.send(GetStats).await?;
+            // 优先的任务结束后管理器自动恢复正常调度
+            boosted = stats.boosted;
+            if mode != ProgressMode::Hidden {
+                progress.set_task_rows(task_rows(download_manager, task_ids, boosted).await?);
+            }
+            // 任务拿到文件信息后才知道总大小
+            progress.total_size = stats.total_bytes;
             progress.update_progress(stats.downloaded_bytes, stats.speed);
 
-            // 检查是否所有任务都完成
-            if stats.completed + stats.failed == stats.total && stats.total > 0 {
+            // 本次运行的任务都结束后退出，会话中其他暂停的任务不影响
+            let metas = download_manager.send(ListTasks).await?;
+            let finished = |id: &Uuid| {
+                metas.iter().find(|m| m.id == *id).is_none_or(|m| {
+                    matches!(m.status, TaskStatus::Completed | TaskStatus::Failed(_) | TaskStatus::Cancelled)
+                })
+            };
+            if task_ids.iter().all(finished) {
                 break;
             }
 
@@ -191,22 +663,32 @@ async fn run_download_loop(
         tokio::time::sleep(std::time::Duration::from_millis(10)).await;
     }
 
+    signal_watcher.abort();
+    if let Some(signal) = interrupted {
+        download_manager.send(PauseAllAndSave).await?;
+        logger.warn(&format!("收到 {} 信号，已暂停所有任务并保存会话", signal));
+    }
+
     // 恢复终端
-    execute!(std::io::stdout(), cursor::Show)?;
-    terminal::disable_raw_mode()?;
+    drop(raw_terminal);
     progress.finish();
+    if let Some(signal) = interrupted {
+        eprintln!("{}", tf(Msg::Interrupted, &[&signal]));
+    }
 
     // 显示最终统计
     let final_stats = download_manager.send(GetStats).await?;
-    println!("\n下载统计:");
-    println!("  总任务数: {}", final_stats.total);
-    println!("  成功完成: {}", final_stats.completed);
-    println!("  失败: {}", final_stats.failed);
-    println!("  暂停: {}", final_stats.paused);
+    if !quiet {
+        println!("{}", t(Msg::StatsHeader));
+        println!("{}", tf(Msg::StatsTotal, &[&final_stats.total]));
+        println!("{}", tf(Msg::StatsCompleted, &[&final_stats.completed]));
+        println!("{}", tf(Msg::StatsFailed, &[&final_stats.failed]));
+        println!("{}", tf(Msg::StatsPaused, &[&final_stats.paused]));
+    }
 
     logger.info(&format!("下载完成 - 成功: {}, 失败: {}", final_stats.completed, final_stats.failed));
 
-    Ok(())
+    Ok(interrupted.is_some())
 }
 
 
@@ -0,0 +1,47 @@
+//! 块分布图：在任务行中画出分块下载的进度，类似 BT 客户端的块视图
+//!
+//! `#` 已完成，`>` 下载中，`.` 等待下载，`x` 失败。一直停在 `>` 或 `x` 的位置就是卡住的范围。
+
+use crate::core::task::chunk_manager::ChunkState;
+
+/// 文件名列的宽度
+pub const NAME_WIDTH: usize = 20;
+
+fn symbol(state: ChunkState) -> char {
+    match state {
+        ChunkState::Done => '#',
+        ChunkState::Active => '>',
+        ChunkState::Pending => '.',
+        ChunkState::Failed => 'x',
+    }
+}
+
+/// 把块分布图画成 `[###>>..x....]`
+pub fn render(map: &[ChunkState]) -> String {
+    std::iter::once('[')
+        .chain(map.iter().map(|s| symbol(*s)))
+        .chain(std::iter::once(']'))
+        .collect()
+}
+
+/// 截短到 `NAME_WIDTH` 个字符，过长时保留结尾（通常带有区分度更高的扩展名和序号）
+pub fn short_name(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    if chars.len() <= NAME_WIDTH {
+        return name.to_string();
+    }
+    std::iter::once('…').chain(chars[chars.len() - (NAME_WIDTH - 1)..].iter().copied()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let map = [ChunkState::Done, ChunkState::Active, ChunkState::Failed, ChunkState::Pending];
+        assert_eq!(render(&map), "[#>x.]");
+        assert_eq!(short_name("a.iso"), "a.iso");
+        assert_eq!(short_name("ubuntu-24.04-desktop-amd64.iso"), "…4-desktop-amd64.iso");
+    }
+}
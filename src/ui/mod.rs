@@ -1,2 +1,6 @@
+pub mod chunk_map;
 mod progress;
-pub use progress::ProgressManager;
\ No newline at end of file
+pub mod report;
+pub mod summary;
+pub mod task_list;
+pub use progress::{human_size, ProgressManager, ProgressMode, TaskRow};
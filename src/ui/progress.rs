@@ -5,46 +5,282 @@
 // use tokio::sync::Mutex;
 // use indicatif::ProgressBar;
 
-use std::time::Instant;
+use std::collections::VecDeque;
+use std::io::IsTerminal;
+use crate::core::task::chunk_manager::ChunkState;
+use crate::core::task::TaskPhase;
+use crate::i18n::{t, tf, Msg};
+use super::chunk_map;
+use uuid::Uuid;
+use std::time::{Duration, Instant};
+
+/// 非终端环境下输出纯文本进度行的间隔
+const PLAIN_PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 显示的速度和 ETA 按最近这段时间内的下载量计算，暂停或慢启动不会影响之后的估计
+const SPEED_WINDOW: Duration = Duration::from_secs(10);
+
+/// 总大小未知时显示的旋转指示符
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+
+/// 旋转指示符每一帧的时长
+const SPINNER_FRAME: Duration = Duration::from_millis(100);
+
+/// 无法获取终端大小（纯文本模式）时使用的大小
+const DEFAULT_TERMINAL_SIZE: (u16, u16) = (120, 24);
+
+/// 进度显示模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressMode {
+    /// 终端模式：单行刷新（ANSI 控制符）
+    Interactive,
+    /// 纯文本模式：定期输出一行进度，适用于 CI、cron、管道
+    Plain,
+    /// 不显示进度
+    Hidden,
+}
+
+impl ProgressMode {
+    /// 根据命令行参数和 stdout 是否为终端选择显示模式
+    pub fn detect(quiet: bool, no_progress: bool) -> Self {
+        if quiet || no_progress {
+            ProgressMode::Hidden
+        } else if std::io::stdout().is_terminal() {
+            ProgressMode::Interactive
+        } else {
+            ProgressMode::Plain
+        }
+    }
+}
+
+/// 总进度行下方的单个任务行
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TaskRow {
+    pub id: Uuid,
+    pub name: String,
+    pub downloaded: u64,
+    /// 总大小，0 表示未知
+    pub total: u64,
+    pub speed: u64,
+    /// 分块下载时的块分布图，终端足够宽时显示在行尾
+    pub chunk_map: Option<Vec<ChunkState>>,
+    /// 收尾阶段（合并、校验）及其进度，此时显示阶段的进度代替下载进度和速度
+    pub phase: Option<(TaskPhase, f32)>,
+    /// 优先下载中（按 `b`），行首显示 `*`
+    pub boosted: bool,
+}
+
+impl TaskRow {
+    /// 渲染成不超过 `width` 个字符的一行；`selected` 时行首显示 `>`
+    fn render(&self, width: usize, selected: bool) -> String {
+        let percent = if let Some((_, progress)) = self.phase {
+            format!("{:>5.1}%", progress)
+        } else if self.total > 0 && self.downloaded <= self.total {
+            format!("{:>5.1}%", self.downloaded as f64 / self.total as f64 * 100.0)
+        } else {
+            "    -".to_string()
+        };
+        let total = if self.total > 0 { human_size(self.total) } else { "?".to_string() };
+        let speed = match self.phase {
+            Some((phase, _)) => format!("{:>12}", phase.as_str()),
+            None => format!("{:>10}/s", human_size(self.speed)),
+        };
+        let mut line = format!(
+            "{}{} {:<name_width$} {} {:>10} / {:<10} {}",
+            if selected { '>' } else { ' ' },
+            if self.boosted { '*' } else { ' ' },
+            chunk_map::short_name(&self.name),
+            percent,
+            human_size(self.downloaded),
+            total,
+            speed,
+            name_width = chunk_map::NAME_WIDTH,
+        );
+        if let Some(map) = &self.chunk_map {
+            let map = chunk_map::render(map);
+            if line.chars().count() + 1 + map.len() <= width {
+                line.push(' ');
+                line.push_str(&map);
+            }
+        }
+        truncate(&line, width)
+    }
+}
+
+/// 截断到 `width` 个字符，终端中换行会打乱光标定位
+fn truncate(line: &str, width: usize) -> String {
+    line.chars().take(width).collect()
+}
 
 pub struct ProgressManager {
     pub total_size: u64,
     pub start_time: Instant,
+    pub mode: ProgressMode,
+    last_plain_line: Option<Instant>,
+    /// 总进度行下方的任务行
+    rows: Vec<TaskRow>,
+    /// 终端的列数和行数，任务行超出终端高度时折叠
+    terminal_size: (u16, u16),
+    /// 终端模式下上一次在进度行下方画的行数
+    drawn_lines: usize,
+    /// 最近 `SPEED_WINDOW` 内的（时间, 已下载字节数）采样
+    samples: VecDeque<(Instant, u64)>,
+    /// 用方向键选中的任务（按 `b` 优先下载），没有选中或已不在列表中时为第一行
+    selected: Option<Uuid>,
 }
 
 impl ProgressManager {
     pub fn new(total_size: u64) -> Self {
+        Self::with_mode(total_size, ProgressMode::Interactive)
+    }
+
+    pub fn with_mode(total_size: u64, mode: ProgressMode) -> Self {
         Self {
             total_size,
             start_time: Instant::now(),
+            mode,
+            last_plain_line: None,
+            rows: Vec::new(),
+            terminal_size: match mode {
+                ProgressMode::Interactive => crossterm::terminal::size().unwrap_or(DEFAULT_TERMINAL_SIZE),
+                _ => DEFAULT_TERMINAL_SIZE,
+            },
+            drawn_lines: 0,
+            samples: VecDeque::new(),
+            selected: None,
+        }
+    }
+
+    /// 记录一次采样，丢弃窗口之外的旧采样（保留一个作为窗口起点）
+    fn record_sample(&mut self, at: Instant, downloaded: u64) {
+        // 已下载量变小（任务重新开始）时之前的采样作废
+        if self.samples.back().is_some_and(|&(_, last)| downloaded < last) {
+            self.samples.clear();
+        }
+        self.samples.push_back((at, downloaded));
+        while self.samples.len() > 2 && at.duration_since(self.samples[1].0) >= SPEED_WINDOW {
+            self.samples.pop_front();
+        }
+    }
+
+    /// 滑动窗口内的平均速度（B/s）
+    pub fn window_speed(&self) -> f64 {
+        match (self.samples.front(), self.samples.back()) {
+            (Some(&(t0, d0)), Some(&(t1, d1))) if t1 > t0 => (d1 - d0) as f64 / t1.duration_since(t0).as_secs_f64(),
+            _ => 0.0,
+        }
+    }
+
+    /// 设置下一次刷新时在总进度行下方显示的任务行
+    pub fn set_task_rows(&mut self, rows: Vec<TaskRow>) {
+        self.rows = rows;
+    }
+
+    /// 选中的任务在任务行中的位置
+    fn selected_index(&self) -> usize {
+        self.selected.and_then(|id| self.rows.iter().position(|row| row.id == id)).unwrap_or(0)
+    }
+
+    /// 当前选中的任务，没有任务行时为 `None`
+    pub fn selected_task(&self) -> Option<Uuid> {
+        self.rows.get(self.selected_index()).map(|row| row.id)
+    }
+
+    /// 上下移动选中的任务（`step` 为负时向上），到头后停住
+    pub fn move_selection(&mut self, step: isize) {
+        if self.rows.is_empty() {
+            return;
+        }
+        let index = self.selected_index().saturating_add_signed(step).min(self.rows.len() - 1);
+        self.selected = Some(self.rows[index].id);
+    }
+
+    /// 终端大小变化（crossterm 的 Resize 事件）时调用，下一次刷新按新的大小折叠和截断
+    pub fn resize(&mut self, columns: u16, rows: u16) {
+        self.terminal_size = (columns, rows);
+    }
+
+    /// 渲染任务行：超出终端高度时只显示能放下的部分（包含选中的任务），最后一行提示还有多少个任务
+    fn render_rows(&self) -> Vec<String> {
+        let (columns, height) = (self.terminal_size.0 as usize, self.terminal_size.1 as usize);
+        // 总进度行和结束提示各占一行
+        let capacity = height.saturating_sub(2).max(1);
+        let shown = if self.rows.len() > capacity { capacity - 1 } else { self.rows.len() };
+        let selected = self.selected_index();
+        let start = (selected + 1).saturating_sub(shown).min(self.rows.len() - shown);
+        // 只有终端模式能用方向键选择；只有一个任务时不需要选择，都不显示选中标记
+        let selectable = self.mode == ProgressMode::Interactive && self.rows.len() > 1;
+        let mut lines: Vec<String> = self.rows[start..start + shown]
+            .iter()
+            .enumerate()
+            .map(|(i, row)| row.render(columns, selectable && start + i == selected))
+            .collect();
+        if shown < self.rows.len() {
+            lines.push(truncate(&tf(Msg::MoreTasks, &[&(self.rows.len() - shown)]), columns));
         }
+        lines
     }
 
     /// 更新总进度，aria2c 风格输出
-    pub fn update_progress(&self, downloaded: u64, _speed: u64) {
-        let percent = if self.total_size > 0 {
-            (downloaded as f64 / self.total_size as f64) * 100.0
-        } else {
-            0.0
-        };
-        let eta = if downloaded > 0 {
-            let elapsed = self.start_time.elapsed().as_secs();
-            let speed = downloaded as f64 / (elapsed.max(1) as f64);
-            let remain = self.total_size.saturating_sub(downloaded);
-            let eta_secs = if speed > 0.0 {
-                (remain as f64 / speed) as u64
-            } else {
-                0
-            };
-            format_time(eta_secs)
-        } else {
-            "--:--:--".to_string()
-        };
-        let speed = if self.start_time.elapsed().as_secs() > 0 {
-            downloaded as f64 / self.start_time.elapsed().as_secs_f64()
-        } else {
-            0.0
-        };
+    pub fn update_progress(&mut self, downloaded: u64, _speed: u64) {
+        self.record_sample(Instant::now(), downloaded);
+        match self.mode {
+            ProgressMode::Interactive => {
+                // raw mode 下换行不会回到行首，所以用 \r\n；画完后光标回到进度行
+                let lines = self.render_rows();
+                let mut out = format!("\r\x1b[2K{}", truncate(&self.render(downloaded), self.terminal_size.0 as usize));
+                for line in &lines {
+                    out.push_str("\r\n\x1b[2K");
+                    out.push_str(line);
+                }
+                // 清掉上一次多画的行
+                for _ in lines.len()..self.drawn_lines {
+                    out.push_str("\r\n\x1b[2K");
+                }
+                let below = lines.len().max(self.drawn_lines);
+                if below > 0 {
+                    out.push_str(&format!("\x1b[{}A", below));
+                }
+                self.drawn_lines = lines.len();
+                print!("{}", out);
+                use std::io::Write;
+                std::io::stdout().flush().ok();
+            }
+            ProgressMode::Plain => {
+                let due = self.last_plain_line
+                    .is_none_or(|t| t.elapsed() >= PLAIN_PROGRESS_INTERVAL);
+                if due {
+                    println!("{}", self.render(downloaded));
+                    for line in self.render_rows() {
+                        println!("{}", line);
+                    }
+                    self.last_plain_line = Some(Instant::now());
+                }
+            }
+            ProgressMode::Hidden => {}
+        }
+    }
+
+    pub fn finish(&self) {
+        match self.mode {
+            ProgressMode::Interactive => {
+                // 光标移到块分布图下方，不覆盖最后一次的显示
+                if self.drawn_lines > 0 {
+                    print!("\x1b[{}B", self.drawn_lines);
+                }
+                println!("\n{}", t(Msg::DownloadFinished))
+            }
+            ProgressMode::Plain => println!("{}", t(Msg::DownloadFinished)),
+            ProgressMode::Hidden => {}
+        }
+    }
+
+    /// 生成一行进度文本（不含控制符）
+    ///
+    /// 总大小未知（服务器没有返回 Content-Length，或者已下载量超过了已知部分的总和）时
+    /// 只显示已下载字节数和速度，终端模式下附带旋转指示符，不显示百分比和 ETA。
+    fn render(&self, downloaded: u64) -> String {
+        let speed = self.window_speed();
         let speed_str = if speed > 1024.0 * 1024.0 {
             format!("{:.2} MiB/s", speed / 1024.0 / 1024.0)
         } else if speed > 1024.0 {
@@ -53,19 +289,30 @@ impl ProgressManager {
             format!("{:.0} B/s", speed)
         };
         let gid = "multidown";
-        let total_str = human_size(self.total_size);
         let down_str = human_size(downloaded);
-        print!("\r\x1b[2K[#{} {}/{} DL:{}][{:>5.1}%] ETA:{}   ", gid, down_str, total_str, speed_str, percent, eta);
-        use std::io::Write;
-        std::io::stdout().flush().ok();
-    }
+        if self.total_size == 0 || downloaded > self.total_size {
+            let line = format!("[#{} {} DL:{}]", gid, down_str, speed_str);
+            if self.mode != ProgressMode::Interactive {
+                return line;
+            }
+            let frame = (self.start_time.elapsed().as_millis() / SPINNER_FRAME.as_millis()) as usize % SPINNER.len();
+            return format!("{} {}", line, SPINNER[frame]);
+        }
 
-    pub fn finish(&self) {
-        println!("\n下载完成");
+        let percent = (downloaded as f64 / self.total_size as f64) * 100.0;
+        let eta = if speed > 0.0 {
+            let remain = self.total_size.saturating_sub(downloaded);
+            format_time((remain as f64 / speed) as u64)
+        } else {
+            "--:--:--".to_string()
+        };
+        let total_str = human_size(self.total_size);
+        format!("[#{} {}/{} DL:{}][{:>5.1}%] ETA:{}", gid, down_str, total_str, speed_str, percent, eta)
     }
 }
 
-fn human_size(bytes: u64) -> String {
+/// 将字节数格式化为易读的大小
+pub fn human_size(bytes: u64) -> String {
     if bytes >= 1024 * 1024 * 1024 {
         format!("{:.2} GiB", bytes as f64 / 1024.0 / 1024.0 / 1024.0)
     } else if bytes >= 1024 * 1024 {
@@ -82,4 +329,93 @@ fn format_time(secs: u64) -> String {
     let m = (secs % 3600) / 60;
     let s = secs % 60;
     format!("{:02}:{:02}:{:02}", h, m, s)
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_speed() {
+        let mut progress = ProgressManager::with_mode(2000, ProgressMode::Hidden);
+        let start = Instant::now();
+        assert_eq!(progress.window_speed(), 0.0);
+
+        // 开始很慢，之后稳定在 100 B/s：窗口滑过之后只反映最近的速度
+        progress.record_sample(start, 0);
+        progress.record_sample(start + Duration::from_secs(5), 10);
+        for i in 1..=10 {
+            progress.record_sample(start + Duration::from_secs(5 + i), 10 + i * 100);
+        }
+        assert!((progress.window_speed() - 100.0).abs() < 1.0);
+        assert!(progress.render(1010).contains("ETA:00:00:09"));
+
+        // 暂停后速度随窗口逐渐降到 0
+        progress.record_sample(start + Duration::from_secs(30), 1010);
+        assert_eq!(progress.window_speed(), 0.0);
+        assert!(progress.render(500).contains("ETA:--:--:--"));
+
+        // 已下载量变小时重新开始计算
+        progress.record_sample(start + Duration::from_secs(31), 0);
+        assert_eq!(progress.samples.len(), 1);
+    }
+
+    #[test]
+    fn test_task_rows_collapse() {
+        let mut progress = ProgressManager::with_mode(0, ProgressMode::Plain);
+        let row = TaskRow {
+            name: "a.iso".to_string(),
+            downloaded: 512,
+            total: 1024,
+            speed: 100,
+            chunk_map: Some(vec![ChunkState::Done, ChunkState::Active]),
+            ..Default::default()
+        };
+        progress.set_task_rows(vec![row.clone(); 5]);
+        progress.resize(200, 10);
+        let lines = progress.render_rows();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].contains(" 50.0%") && lines[0].ends_with("[#>]"));
+
+        // 终端变矮时折叠，变窄时省略块分布图并截断
+        progress.resize(40, 5);
+        let lines = progress.render_rows();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[2], tf(Msg::MoreTasks, &[&3]));
+        assert!(lines[0].chars().count() <= 40 && !lines[0].contains('['));
+
+        assert!(lines[0].starts_with("  "));
+
+        // 选中的任务被折叠时滚动到能看到它的位置
+        progress.mode = ProgressMode::Interactive;
+        let rows: Vec<TaskRow> = (0..5).map(|i| TaskRow { id: Uuid::from_u128(i + 1), boosted: i == 3, ..row.clone() }).collect();
+        progress.set_task_rows(rows);
+        progress.move_selection(3);
+        assert_eq!(progress.selected_task(), Some(Uuid::from_u128(4)));
+        let lines = progress.render_rows();
+        assert!(lines[1].starts_with(">*"), "{:?}", lines);
+        progress.move_selection(10);
+        assert_eq!(progress.selected_task(), Some(Uuid::from_u128(5)));
+        progress.move_selection(-10);
+        assert!(progress.render_rows()[0].starts_with("> "));
+
+        // 合并阶段显示合并进度代替下载进度和速度
+        let merging = TaskRow { phase: Some((TaskPhase::Merging, 25.0)), chunk_map: None, ..row };
+        let line = merging.render(200, false);
+        assert!(line.contains(" 25.0%") && line.ends_with("merging") && !line.contains("/s"));
+    }
+
+    #[test]
+    fn test_unknown_total() {
+        let mut progress = ProgressManager::with_mode(0, ProgressMode::Plain);
+        assert_eq!(progress.render(2048), "[#multidown 2.00 KiB DL:0 B/s]");
+
+        // 已下载量超过已知部分的总和时同样按未知处理
+        progress.total_size = 1024;
+        assert!(!progress.render(2048).contains('%'));
+
+        progress.mode = ProgressMode::Interactive;
+        let line = progress.render(2048);
+        assert!(SPINNER.contains(&line.chars().last().unwrap()));
+    }
+}
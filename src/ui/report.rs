@@ -0,0 +1,152 @@
+//! 下载结束后的汇总报告导出（JSON / CSV）
+
+use serde::Serialize;
+use std::path::Path;
+
+use crate::core::actor_manager::DownloadTaskMeta;
+use crate::core::error::DownloadError;
+use crate::core::task::TaskStatus;
+
+/// 报告格式，根据文件扩展名确定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Json,
+    Csv,
+}
+
+impl ReportFormat {
+    pub fn from_path(path: &str) -> Result<Self, DownloadError> {
+        let ext = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match ext.as_deref() {
+            Some("json") => Ok(ReportFormat::Json),
+            Some("csv") => Ok(ReportFormat::Csv),
+            _ => Err(DownloadError::unknown(format!("不支持的报告格式: {}（仅支持 .json 或 .csv）", path))),
+        }
+    }
+}
+
+/// 单个任务的报告条目
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskReport {
+    pub url: String,
+    pub file: String,
+    pub status: String,
+    pub size: u64,
+    pub duration_secs: f64,
+    pub average_speed: u64, // B/s
+    pub retries: u32,
+    pub error: Option<String>,
+    /// 数字错误码（见 `DownloadError::code_for_kind`），成功时为空
+    pub error_code: Option<u16>,
+    /// 服务器返回的错误状态码
+    pub http_status: Option<u16>,
+}
+
+impl TaskReport {
+    pub fn from_meta(meta: &DownloadTaskMeta) -> Self {
+        let duration_secs = meta.duration_secs();
+        let average_speed = if duration_secs > 0.0 {
+            (meta.downloaded as f64 / duration_secs) as u64
+        } else {
+            0
+        };
+        let error = match &meta.status {
+            TaskStatus::Failed(e) => Some(e.clone()),
+            _ => None,
+        };
+        Self {
+            url: meta.url.clone(),
+            file: meta.file.clone(),
+            status: meta.status.as_str().to_string(),
+            size: meta.total.max(meta.downloaded),
+            duration_secs,
+            average_speed,
+            retries: meta.retries,
+            error_code: error.as_ref().and(meta.error_code),
+            http_status: error.as_ref().and(meta.http_status),
+            error,
+        }
+    }
+}
+
+/// 将报告写入文件，格式由扩展名决定
+pub fn write_report(path: &str, reports: &[TaskReport]) -> Result<(), DownloadError> {
+    let content = match ReportFormat::from_path(path)? {
+        ReportFormat::Json => serde_json::to_string_pretty(reports)
+            .map_err(|e| DownloadError::unknown(format!("序列化报告失败: {}", e)))?,
+        ReportFormat::Csv => to_csv(reports),
+    };
+    if let Some(parent) = Path::new(path).parent() {
+        if !parent.as_os_str().is_empty() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| DownloadError::io_error_with_context("创建报告目录", e))?;
+        }
+    }
+    std::fs::write(path, content)
+        .map_err(|e| DownloadError::io_error_with_context("写入报告", e))
+}
+
+fn to_csv(reports: &[TaskReport]) -> String {
+    let mut out = String::from("url,file,status,size,duration_secs,average_speed,retries,error,error_code,http_status\n");
+    for r in reports {
+        let row = [
+            csv_field(&r.url),
+            csv_field(&r.file),
+            csv_field(&r.status),
+            r.size.to_string(),
+            format!("{:.3}", r.duration_secs),
+            r.average_speed.to_string(),
+            r.retries.to_string(),
+            csv_field(r.error.as_deref().unwrap_or("")),
+            r.error_code.map(|c| c.to_string()).unwrap_or_default(),
+            r.http_status.map(|c| c.to_string()).unwrap_or_default(),
+        ];
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+/// 按 RFC 4180 转义 CSV 字段
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_format_from_path() {
+        assert_eq!(ReportFormat::from_path("out/report.json").unwrap(), ReportFormat::Json);
+        assert_eq!(ReportFormat::from_path("report.CSV").unwrap(), ReportFormat::Csv);
+        assert!(ReportFormat::from_path("report.txt").is_err());
+    }
+
+    #[test]
+    fn test_csv_escaping() {
+        let report = TaskReport {
+            url: "https://example.com/a.zip".to_string(),
+            file: "a.zip".to_string(),
+            status: "failed".to_string(),
+            size: 10,
+            duration_secs: 1.5,
+            average_speed: 6,
+            retries: 2,
+            error: Some("服务器错误: \"503\", 稍后重试".to_string()),
+            error_code: Some(103),
+            http_status: Some(503),
+        };
+        let csv = to_csv(&[report]);
+        let line = csv.lines().nth(1).unwrap();
+        assert!(line.starts_with("https://example.com/a.zip,a.zip,failed,10,1.500,6,2,"));
+        assert!(line.ends_with("\"服务器错误: \"\"503\"\", 稍后重试\",103,503"));
+    }
+}
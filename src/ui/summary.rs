@@ -0,0 +1,201 @@
+//! 运行结束时的汇总：按错误类型分组的失败列表、各任务的性能指标
+
+use std::fmt::Write;
+
+use crate::core::actor_manager::DownloadTaskMeta;
+use crate::core::error::DownloadError;
+use crate::core::task::TaskStatus;
+use crate::i18n::{t, tf, Msg};
+use crate::ui::human_size;
+
+/// 同一类错误的失败任务
+#[derive(Debug, Clone)]
+pub struct FailureGroup {
+    pub label: String,
+    pub suggestion: Option<&'static str>,
+    /// (URL, 错误信息)
+    pub tasks: Vec<(String, String)>,
+}
+
+/// 将失败任务按错误类型分组，数量多的排在前面
+pub fn group_failures(metas: &[DownloadTaskMeta]) -> Vec<FailureGroup> {
+    let mut groups: Vec<FailureGroup> = Vec::new();
+    for meta in metas {
+        let message = match &meta.status {
+            TaskStatus::Failed(e) => e,
+            _ => continue,
+        };
+        let kind = meta.error_kind.as_deref().unwrap_or("unknown");
+        let label = failure_label(kind, message);
+        match groups.iter_mut().find(|g| g.label == label) {
+            Some(group) => group.tasks.push((meta.url.clone(), message.clone())),
+            None => groups.push(FailureGroup {
+                label,
+                suggestion: DownloadError::suggestion_for_kind(kind, meta.http_status.or_else(|| find_http_status(message))),
+                tasks: vec![(meta.url.clone(), message.clone())],
+            }),
+        }
+    }
+    groups.sort_by(|a, b| b.tasks.len().cmp(&a.tasks.len()).then_with(|| a.label.cmp(&b.label)));
+    groups
+}
+
+/// 生成失败列表文本，没有失败任务时返回 None
+pub fn format_failures(groups: &[FailureGroup]) -> Option<String> {
+    let total: usize = groups.iter().map(|g| g.tasks.len()).sum();
+    if total == 0 {
+        return None;
+    }
+    let overview = groups
+        .iter()
+        .map(|g| format!("{} × {}", g.tasks.len(), g.label))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut out = tf(Msg::FailuresHeader, &[&total, &overview]);
+    out.push('\n');
+    for group in groups {
+        let _ = write!(out, "  {} × {}", group.tasks.len(), group.label);
+        if let Some(suggestion) = group.suggestion {
+            out.push_str(&tf(Msg::FailureSuggestion, &[&suggestion]));
+        }
+        out.push('\n');
+        for (url, message) in &group.tasks {
+            let _ = writeln!(out, "    - {}: {}", url, message);
+        }
+    }
+    Some(out)
+}
+
+/// 生成各任务的性能指标文本（耗时、平均/峰值速度、重试和错误次数），没有指标时返回 None
+pub fn format_metrics(metas: &[DownloadTaskMeta]) -> Option<String> {
+    let mut out = String::new();
+    for meta in metas {
+        let Some(m) = &meta.metrics else { continue };
+        let duration = m.get_duration().map_or(0.0, |d| d.num_milliseconds() as f64 / 1000.0);
+        let name = std::path::Path::new(&meta.file)
+            .file_name()
+            .map_or(meta.file.clone(), |n| n.to_string_lossy().into_owned());
+        let _ = writeln!(
+            out,
+            "{}",
+            tf(
+                Msg::MetricsLine,
+                &[
+                    &name,
+                    &format!("{:.1}s", duration),
+                    &human_size(m.average_speed as u64),
+                    &human_size(m.peak_speed),
+                    &m.retry_count,
+                    &m.error_count,
+                    &m.network_errors,
+                    &m.io_errors,
+                    &m.timeouts,
+                ],
+            )
+        );
+    }
+    if out.is_empty() {
+        return None;
+    }
+    Some(format!("{}\n{}", t(Msg::MetricsHeader), out))
+}
+
+/// 服务器错误按 HTTP 状态码细分，其余按错误类别
+fn failure_label(kind: &str, message: &str) -> String {
+    if kind == "server" {
+        if let Some(status) = find_http_status(message) {
+            return format!("HTTP {}", status);
+        }
+    }
+    DownloadError::kind_label(kind).to_string()
+}
+
+/// 从错误信息中提取第一个 4xx/5xx 状态码
+fn find_http_status(message: &str) -> Option<u16> {
+    message
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|s| s.len() == 3)
+        .filter_map(|s| s.parse::<u16>().ok())
+        .find(|code| (400..600).contains(code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::actor_manager::PerformanceMetrics;
+    use uuid::Uuid;
+
+    fn failed(url: &str, kind: &str, message: &str) -> DownloadTaskMeta {
+        DownloadTaskMeta {
+            id: Uuid::new_v4(),
+            url: url.to_string(),
+            file: "file".to_string(),
+            status: TaskStatus::Failed(message.to_string()),
+            progress: 0.0,
+            downloaded: 0,
+            total: 0,
+            speed: 0,
+            started_at: None,
+            finished_at: None,
+            retries: 0,
+            error_kind: Some(kind.to_string()),
+            error_code: None,
+            http_status: None,
+            options: Default::default(),
+            metrics: None,
+            tags: Vec::new(),
+            phase: None,
+            phase_progress: None,
+            client: None,
+        }
+    }
+
+    #[test]
+    fn test_group_failures() {
+        let metas = vec![
+            failed("https://a.com/1", "server", "服务器错误: 服务器错误: 404 Not Found"),
+            failed("https://a.com/2", "checksum_mismatch", "校验和不匹配: 预期 a, 实际 b"),
+            failed("https://a.com/3", "server", "服务器错误: 服务器错误: 404 Not Found"),
+        ];
+        let groups = group_failures(&metas);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].label, "HTTP 404");
+        assert_eq!(groups[0].tasks.len(), 2);
+        assert_eq!(groups[1].label, "校验和不匹配");
+
+        let text = format_failures(&groups).unwrap();
+        assert!(text.starts_with("3 个任务失败: 2 × HTTP 404, 1 × 校验和不匹配"));
+        assert!(text.contains("建议: 服务器拒绝了请求"));
+    }
+
+    #[test]
+    fn test_format_metrics() {
+        let mut meta = failed("https://a.com/big.iso", "network", "网络错误");
+        meta.file = "downloads/big.iso".to_string();
+        assert!(format_metrics(std::slice::from_ref(&meta)).is_none());
+
+        let mut metrics = PerformanceMetrics::new(meta.id);
+        metrics.start_time = chrono::Utc::now() - chrono::Duration::seconds(4);
+        metrics.resumed_bytes = 1024;
+        metrics.downloaded_bytes = 1024 + 4 * 2048;
+        metrics.update_speed(4096);
+        metrics.update_speed(1024);
+        metrics.retry_count = 2;
+        metrics.record_error("network");
+        metrics.record_error("timeout");
+        metrics.finish();
+        assert_eq!(metrics.peak_speed, 4096);
+        assert!((metrics.average_speed - 2048.0).abs() < 10.0);
+        meta.metrics = Some(metrics);
+
+        let text = format_metrics(&[meta]).unwrap();
+        assert!(text.contains("big.iso"));
+        assert!(text.contains("峰值 4.00 KiB/s"));
+        assert!(text.contains("重试 2 次，错误 2 次（网络 1，IO 0，超时 1）"));
+    }
+
+    #[test]
+    fn test_no_failures() {
+        assert!(format_failures(&group_failures(&[])).is_none());
+    }
+}
@@ -0,0 +1,242 @@
+//! `multidown list`：按状态、标签、关键字筛选会话中的任务，排序后输出表格或 JSON
+
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::core::actor_manager::DownloadTaskMeta;
+use crate::core::task::TaskStatus;
+use crate::i18n::{t, Msg};
+use crate::ui::human_size;
+
+/// 可以筛选的状态，`active` 表示所有未结束的任务
+pub const STATUS_NAMES: &[&str] = &["pending", "queued", "running", "paused", "completed", "failed", "cancelled", "active"];
+
+/// 文件和 URL 列的最大宽度，更长的从中间省略
+const MAX_COLUMN_WIDTH: usize = 48;
+
+/// 排序方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SortKey {
+    /// 开始时间，早的在前
+    #[default]
+    Added,
+    /// 文件大小，大的在前
+    Size,
+    /// 进度，高的在前
+    Progress,
+    /// 速度，快的在前
+    Speed,
+    /// 状态
+    Status,
+    /// 保存路径
+    File,
+    /// URL
+    Url,
+}
+
+/// 筛选条件
+#[derive(Debug, Clone, Default)]
+pub struct ListFilter {
+    /// 状态名（见 [`STATUS_NAMES`]），为空时不按状态筛选
+    pub statuses: Vec<String>,
+    pub tag: Option<String>,
+    /// URL 或保存路径中包含的关键字，不区分大小写
+    pub search: Option<String>,
+}
+
+impl ListFilter {
+    fn matches(&self, meta: &DownloadTaskMeta) -> bool {
+        let status = self.statuses.is_empty()
+            || self.statuses.iter().any(|s| s == meta.status.as_str() || s == "active" && meta.status.is_active());
+        let search = self.search.as_ref().is_none_or(|keyword| {
+            let keyword = keyword.to_lowercase();
+            meta.url.to_lowercase().contains(&keyword) || meta.file.to_lowercase().contains(&keyword)
+        });
+        status && search && meta.has_tag(self.tag.as_deref())
+    }
+}
+
+/// 列表中的一行，也是 `--json` 输出的格式
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ListRow {
+    pub id: Uuid,
+    pub url: String,
+    pub file: String,
+    pub status: &'static str,
+    pub progress: f32,
+    pub downloaded: u64,
+    /// 文件总大小，未知时为 0
+    pub total: u64,
+    /// 下载速度（字节/秒），只有下载中的任务不为 0
+    pub speed: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl From<&DownloadTaskMeta> for ListRow {
+    fn from(meta: &DownloadTaskMeta) -> Self {
+        Self {
+            id: meta.id,
+            url: meta.url.clone(),
+            file: meta.file.clone(),
+            status: meta.phase.map_or(meta.status.as_str(), |phase| phase.as_str()),
+            progress: meta.progress,
+            downloaded: meta.downloaded,
+            total: meta.total,
+            speed: if meta.status == TaskStatus::Running { meta.speed } else { 0 },
+            error: match &meta.status {
+                TaskStatus::Failed(e) => Some(e.clone()),
+                _ => None,
+            },
+            tags: meta.tags.clone(),
+        }
+    }
+}
+
+/// 筛选并排序；数值（大小、进度、速度）从大到小，其余从小到大，`reverse` 时反过来
+pub fn select(metas: &[DownloadTaskMeta], filter: &ListFilter, sort: SortKey, reverse: bool) -> Vec<ListRow> {
+    let mut metas: Vec<&DownloadTaskMeta> = metas.iter().filter(|m| filter.matches(m)).collect();
+    metas.sort_by(|a, b| {
+        let order = match sort {
+            SortKey::Added => a.started_at.cmp(&b.started_at),
+            SortKey::Size => b.total.cmp(&a.total),
+            SortKey::Progress => b.progress.total_cmp(&a.progress),
+            SortKey::Speed => b.speed.cmp(&a.speed),
+            SortKey::Status => a.status.as_str().cmp(b.status.as_str()),
+            SortKey::File => a.file.cmp(&b.file),
+            SortKey::Url => a.url.cmp(&b.url),
+        };
+        order.then_with(|| a.file.cmp(&b.file))
+    });
+    if reverse {
+        metas.reverse();
+    }
+    metas.into_iter().map(ListRow::from).collect()
+}
+
+/// 生成表格文本：任务ID、状态、进度、速度、大小、文件、URL、错误
+pub fn format_table(rows: &[ListRow]) -> String {
+    let header: Vec<&str> = t(Msg::ListColumns).split('\t').collect();
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            vec![
+                row.id.to_string()[..8].to_string(),
+                row.status.to_string(),
+                format!("{:.1}%", row.progress),
+                if row.speed > 0 { format!("{}/s", human_size(row.speed)) } else { "-".to_string() },
+                if row.total > 0 { human_size(row.total) } else { "-".to_string() },
+                shorten(&row.file, MAX_COLUMN_WIDTH),
+                shorten(&row.url, MAX_COLUMN_WIDTH),
+                row.error.clone().unwrap_or_default(),
+            ]
+        })
+        .collect();
+    let widths: Vec<usize> = (0..header.len())
+        .map(|i| cells.iter().map(|c| display_width(&c[i])).chain([display_width(header[i])]).max().unwrap_or(0))
+        .collect();
+    // 进度、速度、大小右对齐，最后一列不补空格
+    let format_line = |cells: &[&str]| {
+        let mut line = String::new();
+        for (i, cell) in cells.iter().enumerate() {
+            let pad = " ".repeat(widths[i] - display_width(cell));
+            match i {
+                _ if i == cells.len() - 1 => line.push_str(cell),
+                2..=4 => line.push_str(&format!("{}{}  ", pad, cell)),
+                _ => line.push_str(&format!("{}{}  ", cell, pad)),
+            }
+        }
+        line.trim_end().to_string()
+    };
+    let mut text = format_line(&header);
+    for row in &cells {
+        text.push('\n');
+        text.push_str(&format_line(&row.iter().map(String::as_str).collect::<Vec<_>>()));
+    }
+    text
+}
+
+/// 终端中的显示宽度，中日韩字符按两列计算
+fn display_width(text: &str) -> usize {
+    text.chars().map(|c| if (c as u32) >= 0x1100 { 2 } else { 1 }).sum()
+}
+
+/// 超过 `width` 个字符时保留开头和结尾，中间用 … 代替
+fn shorten(text: &str, width: usize) -> String {
+    let count = text.chars().count();
+    if count <= width {
+        return text.to_string();
+    }
+    let head = (width - 1) / 2;
+    let tail = width - 1 - head;
+    let start: String = text.chars().take(head).collect();
+    let end: String = text.chars().skip(count - tail).collect();
+    format!("{}…{}", start, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::task::TaskOptions;
+
+    fn meta(file: &str, status: TaskStatus, total: u64) -> DownloadTaskMeta {
+        DownloadTaskMeta {
+            id: Uuid::new_v4(),
+            url: format!("https://example.com/{}", file),
+            file: file.to_string(),
+            status,
+            progress: 0.0,
+            downloaded: 0,
+            total,
+            speed: 1024,
+            started_at: None,
+            finished_at: None,
+            retries: 0,
+            error_kind: None,
+            error_code: None,
+            http_status: None,
+            options: TaskOptions::default(),
+            metrics: None,
+            tags: Vec::new(),
+            phase: None,
+            phase_progress: None,
+            client: None,
+        }
+    }
+
+    #[test]
+    fn test_select_and_format() {
+        let metas = vec![
+            meta("a.iso", TaskStatus::Failed("HTTP 404".to_string()), 10),
+            meta("b.zip", TaskStatus::Paused, 30),
+            meta("c.bin", TaskStatus::Failed("timeout".to_string()), 20),
+            meta("d.tar", TaskStatus::Completed, 40),
+        ];
+        let filter = ListFilter { statuses: vec!["failed".to_string()], ..Default::default() };
+        let rows = select(&metas, &filter, SortKey::Size, false);
+        assert_eq!(rows.iter().map(|r| r.file.as_str()).collect::<Vec<_>>(), vec!["c.bin", "a.iso"]);
+        assert_eq!(rows[1].error.as_deref(), Some("HTTP 404"));
+        // 只有下载中的任务显示速度
+        assert_eq!(rows[0].speed, 0);
+
+        let filter = ListFilter { statuses: vec!["active".to_string()], ..Default::default() };
+        assert_eq!(select(&metas, &filter, SortKey::Added, false).len(), 1);
+        let filter = ListFilter { search: Some("TAR".to_string()), ..Default::default() };
+        assert_eq!(select(&metas, &filter, SortKey::Added, false)[0].file, "d.tar");
+        let rows = select(&metas, &ListFilter::default(), SortKey::File, true);
+        assert_eq!(rows[0].file, "d.tar");
+
+        let table = format_table(&select(&metas, &ListFilter::default(), SortKey::File, false));
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[1].contains("failed") && lines[1].ends_with("HTTP 404"));
+        // 各行的文件列对齐
+        let column = |line: &str| line.find(".iso").or(line.find(".zip")).or(line.find(".bin")).or(line.find(".tar"));
+        assert_eq!(column(lines[1]), column(lines[2]));
+
+        assert_eq!(shorten("abcdefghij", 5), "ab…ij");
+        assert_eq!(display_width("任务ID"), 6);
+    }
+}
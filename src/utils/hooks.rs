@@ -0,0 +1,131 @@
+//! 任务结束后执行的用户命令（钩子）
+//!
+//! 命令模板支持以下变量：
+//!
+//! - `{path}`: 保存路径
+//! - `{url}`: 下载地址
+//! - `{status}`: 任务状态（`completed` / `failed`）
+//! - `{error}`: 错误信息（成功时为空）
+//!
+//! 变量值替换时会按当前平台的 shell 规则加引号，避免文件名中的特殊字符被解释；
+//! 同时以环境变量 `MULTIDOWN_PATH`、`MULTIDOWN_URL`、`MULTIDOWN_STATUS`、`MULTIDOWN_ERROR` 提供。
+
+use tokio::process::Command;
+
+/// 钩子模板变量
+#[derive(Debug, Clone)]
+pub struct HookContext {
+    pub path: String,
+    pub url: String,
+    pub status: String,
+    pub error: String,
+}
+
+impl HookContext {
+    /// 用上下文替换模板中的变量
+    ///
+    /// 从左到右只扫描一遍，替换进去的值（例如包含 `{status}` 的 URL）不会再被当作变量。
+    pub fn render(&self, template: &str) -> String {
+        let mut result = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            result.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let value = rest.find('}').and_then(|end| match &rest[1..end] {
+                "path" => Some((end, &self.path)),
+                "url" => Some((end, &self.url)),
+                "status" => Some((end, &self.status)),
+                "error" => Some((end, &self.error)),
+                _ => None,
+            });
+            match value {
+                Some((end, value)) => {
+                    result.push_str(&shell_quote(value));
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    result.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        result.push_str(rest);
+        result
+    }
+}
+
+/// 通过系统 shell 执行钩子命令并等待结束
+pub async fn run_hook(template: &str, ctx: &HookContext) {
+    let command_line = ctx.render(template);
+    let mut command = shell_command(&command_line);
+    command
+        .env("MULTIDOWN_PATH", &ctx.path)
+        .env("MULTIDOWN_URL", &ctx.url)
+        .env("MULTIDOWN_STATUS", &ctx.status)
+        .env("MULTIDOWN_ERROR", &ctx.error);
+    match command.status().await {
+        Ok(status) if status.success() => tracing::info!("钩子命令执行成功: {}", command_line),
+        Ok(status) => tracing::warn!("钩子命令退出码异常 ({}): {}", status, command_line),
+        Err(e) => tracing::error!("钩子命令启动失败: {} - {}", command_line, e),
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn shell_command(command_line: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(command_line);
+    command
+}
+
+#[cfg(target_os = "windows")]
+fn shell_command(command_line: &str) -> Command {
+    let mut command = Command::new("cmd");
+    command.arg("/C").arg(command_line);
+    command
+}
+
+/// 按 POSIX shell 规则加单引号
+#[cfg(not(target_os = "windows"))]
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// 按 cmd 规则加双引号
+#[cfg(target_os = "windows")]
+fn shell_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_render_quotes_values() {
+        let ctx = HookContext {
+            path: "/tmp/it's a file.zip".to_string(),
+            url: "https://example.com/a.zip".to_string(),
+            status: "completed".to_string(),
+            error: String::new(),
+        };
+        assert_eq!(
+            ctx.render("unzip {path} # {status} {error}"),
+            "unzip '/tmp/it'\\''s a file.zip' # 'completed' ''"
+        );
+    }
+
+    #[test]
+    fn test_render_single_pass() {
+        let ctx = HookContext {
+            path: "/tmp/{error}.zip".to_string(),
+            url: "https://example.com/{path}?q={status}".to_string(),
+            status: "failed".to_string(),
+            error: "boom".to_string(),
+        };
+        assert_eq!(
+            ctx.render("notify {url} {path} {unknown} {error"),
+            "notify 'https://example.com/{path}?q={status}' '/tmp/{error}.zip' {unknown} {error"
+        );
+    }
+}
@@ -1,101 +1,119 @@
-use chrono::Local; // 用于获取本地时间
-use log::LevelFilter; // 用于设置日志级别
-use std::io::{Write, BufWriter};
+//! 日志：基于 tracing 的结构化日志
+//!
+//! 每个下载任务和分块都有自己的 span（task_id、url、chunk_index），并发分块的日志可以按任务归类。
+//! 日志写入带轮转的文件，支持文本和 JSON 两种格式；第三方库通过 `log` 输出的日志同样会被收集。
+//! `LoggerActor` 保留原有的 actix 接口，消息转发为 tracing 事件。
+
+use actix::prelude::*;
 use std::fs::{File, OpenOptions};
+use std::io::Write;
 use std::path::Path;
-use actix::prelude::*;
+use std::sync::Mutex;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
 
-/// 日志消息
-pub struct LogMsg {
-    pub level: LevelFilter,
-    pub message: String,
+/// 用于覆盖日志级别的环境变量，语法同 `RUST_LOG`，例如 `MULTIDOWN_LOG=multidown=debug`
+pub const LOG_ENV: &str = "MULTIDOWN_LOG";
+
+/// 日志格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogFormat {
+    /// 文本（默认）
+    #[default]
+    Text,
+    /// 每行一个 JSON 对象，包含 span 字段
+    Json,
 }
-impl Message for LogMsg { type Result = (); }
 
-/// 日志Actor
-pub struct LoggerActor {
-    pub writer: BufWriter<File>,
-    pub level: LevelFilter,
-    pub file_path: String,
-    pub max_size: u64, // 最大文件大小 (bytes)
-    pub current_size: u64,
+/// 初始化全局日志，进程内只需调用一次
+pub fn init(file_path: &str, level: LevelFilter, max_size: u64, format: LogFormat) -> Result<(), std::io::Error> {
+    let writer = Mutex::new(RotatingFile::open(file_path, max_size)?);
+    let filter = EnvFilter::builder()
+        .with_default_directive(level.into())
+        .with_env_var(LOG_ENV)
+        .from_env_lossy();
+    let fmt = tracing_subscriber::fmt::layer().with_writer(writer).with_ansi(false);
+    let layer = match format {
+        LogFormat::Text => fmt.boxed(),
+        LogFormat::Json => fmt.json().with_span_list(true).boxed(),
+    };
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(layer)
+        .try_init()
+        .map_err(|e| std::io::Error::other(e.to_string()))
 }
 
-impl LoggerActor {
-    /// 创建新的日志Actor
-    pub fn new(file_path: &str, level: LevelFilter, max_size: u64) -> Result<Self, std::io::Error> {
+/// 按大小轮转的日志文件，超过上限时旧文件改名为 `<path>.backup`
+pub struct RotatingFile {
+    file: File,
+    file_path: String,
+    max_size: u64, // 最大文件大小 (bytes)
+    current_size: u64,
+}
+
+impl RotatingFile {
+    pub fn open(file_path: &str, max_size: u64) -> Result<Self, std::io::Error> {
         // 确保日志目录存在
         if let Some(parent) = Path::new(file_path).parent() {
             std::fs::create_dir_all(parent)?;
         }
-        
-        let file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(file_path)?;
-        
-        let writer = BufWriter::new(file);
-        
+        let file = OpenOptions::new().create(true).append(true).open(file_path)?;
+        let current_size = file.metadata().map(|m| m.len()).unwrap_or(0);
         Ok(Self {
-            writer,
-            level,
+            file,
             file_path: file_path.to_string(),
             max_size,
-            current_size: 0,
+            current_size,
         })
     }
-    
+
     /// 检查并执行日志轮转
     fn check_rotation(&mut self) -> Result<(), std::io::Error> {
         if self.current_size > self.max_size {
-            // 关闭当前文件
-            self.writer.flush()?;
-            
+            self.file.flush()?;
+
             // 重命名当前日志文件
             let backup_path = format!("{}.backup", self.file_path);
             if Path::new(&backup_path).exists() {
                 std::fs::remove_file(&backup_path)?;
             }
             std::fs::rename(&self.file_path, &backup_path)?;
-            
+
             // 创建新文件
-            let file = OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(&self.file_path)?;
-            
-            self.writer = BufWriter::new(file);
+            self.file = OpenOptions::new().create(true).append(true).open(&self.file_path)?;
             self.current_size = 0;
         }
         Ok(())
     }
-    
-    /// 写入日志并处理错误
-    fn write_log(&mut self, level: LevelFilter, message: &str) -> Result<(), std::io::Error> {
-        if level <= self.level {
-            let log_entry = format!(
-                "{} [{}] - {}\n",
-                Local::now().format("%Y-%m-%d %H:%M:%S"),
-                level,
-                message
-            );
-            
-            // 检查轮转
-            self.check_rotation()?;
-            
-            // 写入日志
-            self.writer.write_all(log_entry.as_bytes())?;
-            self.current_size += log_entry.len() as u64;
-            
-            // 定期刷新缓冲区
-            if self.current_size % 1024 < log_entry.len() as u64 {
-                self.writer.flush()?;
-            }
-        }
-        Ok(())
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.check_rotation()?;
+        // 每条日志直接写入文件，进程通过 process::exit 退出时也不会丢失
+        let written = self.file.write(buf)?;
+        self.current_size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
     }
 }
 
+/// 日志消息
+pub struct LogMsg {
+    pub level: LevelFilter,
+    pub message: String,
+}
+impl Message for LogMsg { type Result = (); }
+
+/// 日志Actor：把消息转发为 tracing 事件
+pub struct LoggerActor;
+
 impl Actor for LoggerActor {
     type Context = Context<Self>;
 }
@@ -103,8 +121,12 @@ impl Actor for LoggerActor {
 impl Handler<LogMsg> for LoggerActor {
     type Result = ();
     fn handle(&mut self, msg: LogMsg, _ctx: &mut Self::Context) {
-        if let Err(e) = self.write_log(msg.level, &msg.message) {
-            eprintln!("日志写入失败: {}", e);
+        match msg.level {
+            LevelFilter::ERROR => tracing::error!("{}", msg.message),
+            LevelFilter::WARN => tracing::warn!("{}", msg.message),
+            LevelFilter::INFO => tracing::info!("{}", msg.message),
+            LevelFilter::DEBUG => tracing::debug!("{}", msg.message),
+            _ => tracing::trace!("{}", msg.message),
         }
     }
 }
@@ -120,29 +142,46 @@ pub trait LoggerExt {
 impl LoggerExt for Addr<LoggerActor> {
     fn info(&self, message: &str) {
         self.do_send(LogMsg {
-            level: LevelFilter::Info,
+            level: LevelFilter::INFO,
             message: message.to_string(),
         });
     }
-    
+
     fn error(&self, message: &str) {
         self.do_send(LogMsg {
-            level: LevelFilter::Error,
+            level: LevelFilter::ERROR,
             message: message.to_string(),
         });
     }
-    
+
     fn warn(&self, message: &str) {
         self.do_send(LogMsg {
-            level: LevelFilter::Warn,
+            level: LevelFilter::WARN,
             message: message.to_string(),
         });
     }
-    
+
     fn debug(&self, message: &str) {
         self.do_send(LogMsg {
-            level: LevelFilter::Debug,
+            level: LevelFilter::DEBUG,
             message: message.to_string(),
         });
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotating_file() {
+        let path = "./test_logs/rotate.log";
+        let _ = std::fs::remove_dir_all("./test_logs");
+        let mut file = RotatingFile::open(path, 16).unwrap();
+        file.write_all(b"0123456789abcdefXYZ\n").unwrap();
+        file.write_all(b"next\n").unwrap();
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "next\n");
+        assert_eq!(std::fs::read_to_string(format!("{}.backup", path)).unwrap(), "0123456789abcdefXYZ\n");
+        let _ = std::fs::remove_dir_all("./test_logs");
+    }
+}
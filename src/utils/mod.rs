@@ -1,3 +1,12 @@
+pub mod hooks;
 pub mod logger;
+pub mod notify;
+pub mod push;
+pub mod secrets;
+pub mod signal;
+pub mod size;
+pub mod systemd;
+pub mod tls;
 pub mod validator;
-// pub use validator::*; 
\ No newline at end of file
+pub mod windows_service;
+// pub use validator::*;
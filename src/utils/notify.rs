@@ -0,0 +1,77 @@
+//! 桌面通知：调用各平台自带的通知工具
+//!
+//! - Linux: `notify-send`
+//! - macOS: `osascript`（`display notification`）
+//! - Windows: PowerShell + Windows Toast
+//!
+//! 通知进程在后台启动，不等待结果；通知工具不存在时静默忽略。
+
+use std::process::{Command, Stdio};
+
+/// 发送一条桌面通知
+pub fn send_notification(title: &str, body: &str) {
+    let mut command = build_command(title, body);
+    command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null());
+    if let Err(e) = command.spawn() {
+        tracing::warn!("发送桌面通知失败: {}", e);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn build_command(title: &str, body: &str) -> Command {
+    let mut command = Command::new("notify-send");
+    command.arg("--app-name=multidown").arg(title).arg(body);
+    command
+}
+
+#[cfg(target_os = "macos")]
+fn build_command(title: &str, body: &str) -> Command {
+    let script = format!(
+        "display notification {} with title {}",
+        applescript_string(body),
+        applescript_string(title)
+    );
+    let mut command = Command::new("osascript");
+    command.arg("-e").arg(script);
+    command
+}
+
+#[cfg(target_os = "windows")]
+fn build_command(title: &str, body: &str) -> Command {
+    let script = format!(
+        "[Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime] > $null;\
+         $xml = [Windows.UI.Notifications.ToastNotificationManager]::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02);\
+         $text = $xml.GetElementsByTagName('text');\
+         $text.Item(0).AppendChild($xml.CreateTextNode({})) > $null;\
+         $text.Item(1).AppendChild($xml.CreateTextNode({})) > $null;\
+         [Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier('multidown').Show([Windows.UI.Notifications.ToastNotification]::new($xml))",
+        powershell_string(title),
+        powershell_string(body)
+    );
+    let mut command = Command::new("powershell");
+    command.args(["-NoProfile", "-NonInteractive", "-Command", &script]);
+    command
+}
+
+/// 转义为 AppleScript 字符串字面量
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn applescript_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// 转义为 PowerShell 单引号字符串字面量
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+fn powershell_string(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_string_escaping() {
+        assert_eq!(applescript_string(r#"a "b" \c"#), r#""a \"b\" \\c""#);
+        assert_eq!(powershell_string("it's"), "'it''s'");
+    }
+}
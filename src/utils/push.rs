@@ -0,0 +1,38 @@
+//! 推送通知的发送，推送目标的配置见 [`crate::config::push`]
+//!
+//! 每个目标发送一次，失败只记录警告，不重试也不影响任务状态。
+
+use crate::config::push::PushTarget;
+use crate::core::error::DownloadError;
+use crate::core::task::transport::AwcTransport;
+use std::time::Duration;
+
+/// 把 `text` 发送到所有目标
+pub async fn send_all(targets: &[PushTarget], text: &str, transport: &AwcTransport, timeout: Duration) {
+    for target in targets {
+        if let Err(e) = send(target, text, transport, timeout).await {
+            tracing::warn!(service = ?target.service, error = %e, "推送通知失败");
+        }
+    }
+}
+
+/// 发送到一个目标，服务返回非 2xx 状态时视为失败
+pub async fn send(target: &PushTarget, text: &str, transport: &AwcTransport, timeout: Duration) -> Result<(), DownloadError> {
+    let (url, body) = target.request(text)?;
+    let mut response = transport
+        .clients
+        .get(&url)
+        .post(&url)
+        .timeout(timeout)
+        .insert_header(("Content-Type", "application/json"))
+        .send_body(body.to_string())
+        .await
+        // 错误信息中不带地址，Telegram 的令牌和 webhook 的密钥都在地址里
+        .map_err(|e| DownloadError::network_error(format!("推送请求失败: {}", e)))?;
+    let status = response.status();
+    if !status.is_success() {
+        let detail = response.body().limit(4096).await.map(|body| String::from_utf8_lossy(&body).into_owned()).unwrap_or_default();
+        return Err(DownloadError::server_error(format!("推送服务返回 {}: {}", status.as_u16(), detail.trim())));
+    }
+    Ok(())
+}
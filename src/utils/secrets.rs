@@ -0,0 +1,102 @@
+//! 系统密钥环中的密钥（macOS 钥匙串、Windows 凭据管理器、Linux 内核密钥环）
+//!
+//! 配置文件中只写密钥名称，例如 URL 规则的请求头
+//! `Authorization = "Bearer {secret:example-token}"`，创建任务时再从密钥环读取，
+//! 密钥本身不会出现在 TOML、会话文件或命令行历史中。
+
+use std::borrow::Cow;
+
+use crate::core::error::DownloadError;
+
+/// 密钥环中的服务名
+const SERVICE: &str = "multidown";
+
+/// 占位符前缀：`{secret:<名称>}`
+pub const PLACEHOLDER_PREFIX: &str = "{secret:";
+
+fn entry(name: &str) -> Result<keyring::Entry, DownloadError> {
+    keyring::Entry::new(SERVICE, name)
+        .map_err(|e| DownloadError::Unknown(format!("无法访问系统密钥环: {}", e).into()))
+}
+
+/// 保存密钥
+pub fn set_secret(name: &str, value: &str) -> Result<(), DownloadError> {
+    entry(name)?
+        .set_password(value)
+        .map_err(|e| DownloadError::Unknown(format!("无法保存密钥 {}: {}", name, e).into()))
+}
+
+/// 读取密钥
+pub fn get_secret(name: &str) -> Result<String, DownloadError> {
+    entry(name)?.get_password().map_err(|e| match e {
+        keyring::Error::NoEntry => DownloadError::Unknown(format!("密钥环中没有名为 {} 的密钥", name).into()),
+        e => DownloadError::Unknown(format!("无法读取密钥 {}: {}", name, e).into()),
+    })
+}
+
+/// 删除密钥
+pub fn delete_secret(name: &str) -> Result<(), DownloadError> {
+    entry(name)?
+        .delete_credential()
+        .map_err(|e| DownloadError::Unknown(format!("无法删除密钥 {}: {}", name, e).into()))
+}
+
+/// 把文本中的 `{secret:<名称>}` 占位符替换为密钥环中的值
+pub fn resolve(text: &str) -> Result<String, DownloadError> {
+    resolve_with(text, get_secret)
+}
+
+/// 替换占位符，密钥由 `lookup` 提供（便于测试）
+pub fn resolve_with<F>(text: &str, mut lookup: F) -> Result<String, DownloadError>
+where
+    F: FnMut(&str) -> Result<String, DownloadError>,
+{
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(PLACEHOLDER_PREFIX) {
+        let after = &rest[start + PLACEHOLDER_PREFIX.len()..];
+        let end = after
+            .find('}')
+            .ok_or(DownloadError::Unknown(Cow::Borrowed("密钥占位符缺少右花括号")))?;
+        result.push_str(&rest[..start]);
+        result.push_str(&lookup(after[..end].trim())?);
+        rest = &after[end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// 只检查占位符的格式（右花括号、名称非空），不访问密钥环，用于配置校验
+pub fn check(text: &str) -> Result<(), DownloadError> {
+    resolve_with(text, |name| match name.is_empty() {
+        true => Err(DownloadError::Unknown(Cow::Borrowed("密钥占位符缺少名称"))),
+        false => Ok(String::new()),
+    })
+    .map(|_| ())
+}
+
+/// 比较令牌或密钥，总是比较完整个值，耗时不随匹配的前缀长度变化
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_placeholders() {
+        let lookup = |name: &str| match name {
+            "token" => Ok("s3cr3t".to_string()),
+            _ => Err(DownloadError::Unknown(Cow::Borrowed("missing"))),
+        };
+        assert_eq!(resolve_with("Bearer {secret:token}", lookup).unwrap(), "Bearer s3cr3t");
+        assert_eq!(resolve_with("plain", lookup).unwrap(), "plain");
+        assert!(resolve_with("{secret:other}", lookup).is_err());
+        assert!(resolve_with("{secret:token", lookup).is_err());
+
+        assert!(check("Bearer {secret:anything}").is_ok());
+        assert!(check("{secret: }").is_err());
+        assert!(check("{secret:token").is_err());
+    }
+}
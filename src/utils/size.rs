@@ -0,0 +1,163 @@
+//! 大小、速率与时长的解析：`2M`、`4MiB`、`1.5G`、`512k/s`、`1h30m` 等
+//!
+//! 单位不区分大小写，K/M/G/T 与 KB/KiB 等写法均按 1024 进制计算，不带单位时为字节；
+//! 时长可以组合 h/m/s，不带单位时为秒。
+//! 命令行参数和配置文件共用这里的解析逻辑。
+
+use serde::{Deserialize, Deserializer};
+
+/// 解析大小，返回字节数
+pub fn parse_size(input: &str) -> Result<u64, String> {
+    let text = input.trim();
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("无效的大小: {}", input))?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        "t" | "tb" | "tib" => 1 << 40,
+        _ => return Err(format!("无效的大小单位: {}（可用 B、K、M、G、T）", input)),
+    };
+    let bytes = number * multiplier as f64;
+    if !bytes.is_finite() || bytes > u64::MAX as f64 {
+        return Err(format!("大小超出范围: {}", input));
+    }
+    Ok(bytes.round() as u64)
+}
+
+/// 解析速率，返回 KB/s
+///
+/// 不带单位的数字按 KB/s 理解（兼容 `-l 1024` 的旧写法），带单位时按字节换算，
+/// 可以带 `/s` 后缀；非零速率至少为 1 KB/s。
+pub fn parse_rate_kb(input: &str) -> Result<u64, String> {
+    let text = input.trim();
+    let text = text
+        .strip_suffix("/s")
+        .or_else(|| text.strip_suffix("/S"))
+        .unwrap_or(text);
+    if let Ok(kb) = text.parse::<u64>() {
+        return Ok(kb);
+    }
+    let bytes = parse_size(text)?;
+    Ok(if bytes == 0 { 0 } else { bytes.div_ceil(1024) })
+}
+
+/// 解析时长，返回秒数，如 `90`、`30m`、`1h30m`
+pub fn parse_duration_secs(input: &str) -> Result<u64, String> {
+    let text = input.trim().to_ascii_lowercase();
+    if let Ok(secs) = text.parse::<u64>() {
+        return Ok(secs);
+    }
+    let invalid = || format!("无效的时长: {}（如 90、30m、1h30m）", input);
+    let mut total = 0u64;
+    let mut number = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let multiplier = match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(invalid()),
+        };
+        let value: u64 = number.parse().map_err(|_| invalid())?;
+        total = value
+            .checked_mul(multiplier)
+            .and_then(|v| total.checked_add(v))
+            .ok_or_else(|| format!("时长超出范围: {}", input))?;
+        number.clear();
+    }
+    if !number.is_empty() || text.is_empty() {
+        return Err(invalid());
+    }
+    Ok(total)
+}
+
+/// 配置文件中的大小：整数（字节）或带单位的字符串
+pub fn deserialize_size<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: TryFrom<u64>,
+{
+    let bytes = match SizeValue::deserialize(deserializer)? {
+        SizeValue::Number(n) => n,
+        SizeValue::Text(s) => parse_size(&s).map_err(serde::de::Error::custom)?,
+    };
+    T::try_from(bytes).map_err(|_| serde::de::Error::custom(format!("大小超出范围: {}", bytes)))
+}
+
+/// 配置文件中的速率：整数（KB/s）或带单位的字符串
+pub fn deserialize_rate_kb<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match SizeValue::deserialize(deserializer)? {
+        SizeValue::Number(n) => Ok(n),
+        SizeValue::Text(s) => parse_rate_kb(&s).map_err(serde::de::Error::custom),
+    }
+}
+
+/// 配置文件中的时长：整数（秒）或带单位的字符串
+pub fn deserialize_duration_secs<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match SizeValue::deserialize(deserializer)? {
+        SizeValue::Number(n) => Ok(n),
+        SizeValue::Text(s) => parse_duration_secs(&s).map_err(serde::de::Error::custom),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SizeValue {
+    Number(u64),
+    Text(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("8192"), Ok(8192));
+        assert_eq!(parse_size("4MiB"), Ok(4 * 1024 * 1024));
+        assert_eq!(parse_size("4 mb"), Ok(4 * 1024 * 1024));
+        assert_eq!(parse_size("1.5G"), Ok(1536 * 1024 * 1024));
+        assert_eq!(parse_size("64k"), Ok(64 * 1024));
+        assert!(parse_size("").is_err());
+        assert!(parse_size("12 parsecs").is_err());
+        assert!(parse_size("-1M").is_err());
+    }
+
+    #[test]
+    fn test_parse_rate_kb() {
+        assert_eq!(parse_rate_kb("1024"), Ok(1024));
+        assert_eq!(parse_rate_kb("2M"), Ok(2048));
+        assert_eq!(parse_rate_kb("512KiB/s"), Ok(512));
+        assert_eq!(parse_rate_kb("100B"), Ok(1));
+        assert_eq!(parse_rate_kb("0"), Ok(0));
+    }
+
+    #[test]
+    fn test_parse_duration_secs() {
+        assert_eq!(parse_duration_secs("90"), Ok(90));
+        assert_eq!(parse_duration_secs("30m"), Ok(1800));
+        assert_eq!(parse_duration_secs("1h30m"), Ok(5400));
+        assert_eq!(parse_duration_secs("2H5S"), Ok(7205));
+        assert!(parse_duration_secs("").is_err());
+        assert!(parse_duration_secs("30").is_ok());
+        assert!(parse_duration_secs("m").is_err());
+        assert!(parse_duration_secs("30x").is_err());
+        assert!(parse_duration_secs("1h30").is_err());
+    }
+}
@@ -0,0 +1,164 @@
+//! systemd 集成：就绪通知、看门狗和套接字激活
+//!
+//! 不依赖 libsystemd，直接实现 `sd_notify(3)` 和 `sd_listen_fds(3)` 的协议：
+//!
+//! - `Type=notify`：服务启动完成后向 `$NOTIFY_SOCKET` 发送 `READY=1`，停止时发送 `STOPPING=1`；
+//! - `WatchdogSec=`：`$WATCHDOG_USEC` 设置时，下载管理器按一半的间隔发送 `WATCHDOG=1`，
+//!   管理器卡住时 systemd 会重启服务；
+//! - 套接字激活：`$LISTEN_FDS` 传入的监听套接字按 `FileDescriptorName=`（`web`、`grpc`）分配给网页控制台和 gRPC。
+//!
+//! 不在 systemd 下运行（环境变量不存在）时这些函数什么都不做。
+
+use std::time::Duration;
+
+/// 向服务管理器发送状态，例如 `READY=1`；没有 `$NOTIFY_SOCKET` 时返回 false
+#[cfg(unix)]
+pub fn notify(state: &str) -> bool {
+    use std::os::unix::net::UnixDatagram;
+
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return false;
+    };
+    let Ok(socket) = UnixDatagram::unbound() else {
+        return false;
+    };
+    let path = path.to_string_lossy();
+    // `@` 开头表示 Linux 的抽象命名空间
+    let sent = match path.strip_prefix('@') {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes())
+                .and_then(|addr| socket.send_to_addr(state.as_bytes(), &addr))
+        }
+        _ => socket.send_to(state.as_bytes(), path.as_ref()),
+    };
+    if let Err(e) = &sent {
+        tracing::debug!(error = %e, "发送 systemd 通知失败");
+    }
+    sent.is_ok()
+}
+
+#[cfg(not(unix))]
+pub fn notify(_state: &str) -> bool {
+    false
+}
+
+/// 看门狗的发送间隔（`$WATCHDOG_USEC` 的一半），没有启用看门狗或看门狗不是给本进程的时返回 None
+pub fn watchdog_interval() -> Option<Duration> {
+    if !is_for_this_process("WATCHDOG_PID") {
+        return None;
+    }
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok().filter(|&usec| usec > 0)?;
+    Some(Duration::from_micros(usec / 2))
+}
+
+/// `$<name>` 未设置，或者等于本进程的 PID
+fn is_for_this_process(name: &str) -> bool {
+    match std::env::var(name) {
+        Ok(pid) => pid.parse::<u32>().is_ok_and(|pid| pid == std::process::id()),
+        Err(_) => true,
+    }
+}
+
+/// 套接字激活传入的监听套接字
+#[derive(Debug, Default)]
+pub struct ListenFds {
+    /// (名称, 套接字)，名称来自 `$LISTEN_FDNAMES`
+    #[cfg(unix)]
+    sockets: Vec<(String, std::os::fd::OwnedFd)>,
+}
+
+impl ListenFds {
+    /// 接管 systemd 传入的套接字并清除相关环境变量，之后启动的钩子命令等子进程不会继承它们
+    #[cfg(unix)]
+    pub fn take() -> Self {
+        use std::os::fd::{FromRawFd, OwnedFd};
+
+        /// 传入的第一个文件描述符（`SD_LISTEN_FDS_START`）
+        const FIRST_FD: i32 = 3;
+
+        let count: i32 = match std::env::var("LISTEN_FDS").ok().and_then(|n| n.parse().ok()) {
+            Some(count) if std::env::var_os("LISTEN_PID").is_some() && is_for_this_process("LISTEN_PID") => count,
+            _ => return Self::default(),
+        };
+        let names = std::env::var("LISTEN_FDNAMES").unwrap_or_default();
+        let mut names = names.split(':');
+        for name in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+            std::env::remove_var(name);
+        }
+        let sockets = (FIRST_FD..FIRST_FD + count)
+            .filter_map(|fd| {
+                let name = names.next().unwrap_or_default().to_string();
+                // SAFETY: systemd 把这些描述符交给本进程，上面的 LISTEN_PID 检查保证只接管一次
+                let inherited = unsafe { OwnedFd::from_raw_fd(fd) };
+                // 复制出的描述符带 CLOEXEC，原来的随之关闭
+                match inherited.try_clone() {
+                    Ok(socket) => Some((name, socket)),
+                    Err(e) => {
+                        tracing::warn!(fd, error = %e, "无法接管 systemd 传入的套接字");
+                        None
+                    }
+                }
+            })
+            .collect();
+        Self { sockets }
+    }
+
+    #[cfg(not(unix))]
+    pub fn take() -> Self {
+        Self::default()
+    }
+
+    /// 取出名为 `name` 的 TCP 监听套接字；只传入一个套接字且没有命名时，`fallback` 为 true 的调用者取得它
+    pub fn listener(&mut self, name: &str, fallback: bool) -> Option<std::net::TcpListener> {
+        #[cfg(unix)]
+        {
+            let index = self.sockets.iter().position(|(n, _)| n == name).or_else(|| {
+                let unnamed = |n: &str| n.is_empty() || n == "unknown" || n.ends_with(".socket");
+                (fallback && self.sockets.len() == 1 && unnamed(&self.sockets[0].0)).then_some(0)
+            })?;
+            Some(std::net::TcpListener::from(self.sockets.remove(index).1))
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = (name, fallback);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn test_notify() {
+        let dir = std::env::temp_dir().join(format!("multidown-notify-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notify.sock");
+        let _ = std::fs::remove_file(&path);
+        let receiver = std::os::unix::net::UnixDatagram::bind(&path).unwrap();
+
+        std::env::set_var("NOTIFY_SOCKET", &path);
+        assert!(notify("READY=1\nSTATUS=ok"));
+        std::env::remove_var("NOTIFY_SOCKET");
+        assert!(!notify("READY=1"));
+
+        let mut buf = [0u8; 64];
+        let n = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"READY=1\nSTATUS=ok");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        // 看门狗给的是其他进程
+        std::env::set_var("WATCHDOG_USEC", "4000000");
+        std::env::set_var("WATCHDOG_PID", "1");
+        assert_eq!(watchdog_interval(), None);
+        std::env::set_var("WATCHDOG_PID", std::process::id().to_string());
+        assert_eq!(watchdog_interval(), Some(Duration::from_secs(2)));
+        std::env::remove_var("WATCHDOG_USEC");
+        std::env::remove_var("WATCHDOG_PID");
+        assert!(ListenFds::take().listener("grpc", true).is_none());
+    }
+}
@@ -1,10 +1,182 @@
-use regex::Regex;
+use std::borrow::Cow;
+use std::path::{Component, Path, PathBuf};
+use url::Url;
 
-/// 校验URL格式（支持http/https/ftp）
+use crate::core::error::DownloadError;
+
+/// 支持的协议
+pub const SUPPORTED_SCHEMES: &[&str] = &["http", "https", "ftp", "rsync"];
+
+/// 解析并规范化下载 URL（支持 http/https/ftp/rsync）
+///
+/// 国际化域名转换为 punycode，IPv6 地址、userinfo 和百分号编码按 URL 标准处理；
+/// 片段（`#...`）不会发送给服务器，解析时直接去掉。
+pub fn parse_url(input: &str) -> Result<Url, DownloadError> {
+    let input = input.trim();
+    let mut url = Url::parse(input).map_err(|e| DownloadError::InvalidUrl(format!("{} ({})", input, e).into()))?;
+    if !SUPPORTED_SCHEMES.contains(&url.scheme()) {
+        return Err(DownloadError::UnsupportedProtocol(Cow::Owned(url.scheme().to_string())));
+    }
+    if url.host_str().is_none_or(str::is_empty) {
+        return Err(DownloadError::InvalidUrl(format!("{} (缺少主机名)", input).into()));
+    }
+    url.set_fragment(None);
+    Ok(url)
+}
+
+/// 校验URL格式（支持http/https/ftp/rsync）
 pub fn is_valid_url(url: &str) -> bool {
-    let re = Regex::new(r"^(https?|ftp)://[\w\-]+(\.[\w\-]+)+(:\d+)?(/[\w\-./?%&=]*)?$")
-        .unwrap();
-    re.is_match(url)
+    parse_url(url).is_ok()
+}
+
+/// 按字面规范化为绝对路径：处理 `.` 和 `..`，不访问文件系统
+fn normalize_path(path: &Path) -> PathBuf {
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().unwrap_or_default().join(path)
+    };
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// 校验输出路径位于下载目录之内
+///
+/// 文件名来自 `--file-name`、任务选项或 URL，可能包含 `../` 或绝对路径，
+/// 规范化后不在下载目录下的一律拒绝。
+pub fn confine_to_dir(dir: &str, file: &str) -> Result<PathBuf, DownloadError> {
+    let base = normalize_path(Path::new(dir));
+    let target = normalize_path(Path::new(file));
+    if target == base || !target.starts_with(&base) {
+        return Err(DownloadError::PermissionError(
+            format!("输出路径 {} 不在下载目录 {} 内", file, dir).into(),
+        ));
+    }
+    Ok(target)
+}
+
+/// `--preserve-path`：按 URL 的主机和路径生成相对于下载目录的保存路径，如 `host/a/b/c.zip`
+///
+/// 路径段中的百分号编码先解码；空段、`.` 和 `..` 去掉，解码出的斜杠替换为 `_`，不会多出或跳出目录；
+/// 路径以 `/` 结尾时文件名为 `index.html`。非默认端口与 wget 一样写成 `host:port`，
+/// Windows 上由 [`output_path`] 把冒号替换掉。查询参数不参与路径。
+pub fn mirror_path(url: &str) -> Result<PathBuf, DownloadError> {
+    let url = parse_url(url)?;
+    let host = url.host_str().unwrap_or_default();
+    let mut path = PathBuf::from(match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    });
+    let segments: Vec<String> = url
+        .path_segments()
+        .map(|segments| segments.map(percent_decode).collect())
+        .unwrap_or_default();
+    for segment in &segments {
+        if !segment.is_empty() && segment != "." && segment != ".." {
+            path.push(segment.replace(['/', '\\'], "_"));
+        }
+    }
+    if segments.last().is_none_or(|s| s.is_empty() || s == "." || s == "..") {
+        path.push("index.html");
+    }
+    Ok(path)
+}
+
+/// 解码百分号编码，不是合法 UTF-8 的字节替换为 U+FFFD
+pub(crate) fn percent_decode(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| u8::from_str_radix(std::str::from_utf8(h).ok()?, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Windows 保留的设备名，不区分大小写，带扩展名（如 `nul.txt`）同样不可用
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "CONIN$", "CONOUT$",
+    "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// 把单级文件名转换为 Windows 上可以创建的形式
+///
+/// 非法字符和控制字符替换为 `_`，去掉结尾的点和空格，设备名（`CON`、`nul.txt` 等）后追加 `_`。
+pub fn sanitize_file_name(name: &str) -> String {
+    let mut name: String = name
+        .chars()
+        .map(|c| if c.is_control() || "<>:\"/\\|?*".contains(c) { '_' } else { c })
+        .collect();
+    name.truncate(name.trim_end_matches(['.', ' ']).len());
+    if name.is_empty() {
+        return "_".to_string();
+    }
+    let stem = name.split('.').next().unwrap_or_default().trim_end();
+    if WINDOWS_RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+        name.insert(stem.len(), '_');
+    }
+    name
+}
+
+/// 转换为 Windows 的扩展长度路径（`\\?\` 前缀），绕过 260 字符的 MAX_PATH 限制
+///
+/// 输入必须是绝对路径；UNC 路径 `\\server\share` 转为 `\\?\UNC\server\share`，已有前缀的原样返回。
+pub fn extended_length_path(path: &str) -> String {
+    if path.starts_with(r"\\?\") {
+        path.to_string()
+    } else if let Some(unc) = path.strip_prefix(r"\\") {
+        format!(r"\\?\UNC\{}", unc)
+    } else {
+        format!(r"\\?\{}", path)
+    }
+}
+
+/// 没有指定文件名时使用 URL 的最后一段，为空时生成一个
+pub fn file_name_from_url(url: &Url) -> String {
+    url.path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|name| !name.is_empty())
+        .map(sanitize_file_name)
+        .unwrap_or_else(|| format!("download_{}", uuid::Uuid::new_v4().simple()))
+}
+
+/// 校验并生成任务最终使用的输出路径
+///
+/// 所有平台都先用 [`confine_to_dir`] 拒绝逃出下载目录的路径。Windows 上再对下载目录内的每一级
+/// 应用 [`sanitize_file_name`]，并转为扩展长度路径，URL 推导出的深层路径不会因为保留名、
+/// 结尾的点或超过 MAX_PATH 而在创建文件时失败；其他平台原样返回。
+pub fn output_path(dir: &str, file: &str) -> Result<String, DownloadError> {
+    let target = confine_to_dir(dir, file)?;
+    if !cfg!(windows) {
+        return Ok(file.to_string());
+    }
+    let base = normalize_path(Path::new(dir));
+    let sanitized = target
+        .strip_prefix(&base)
+        .unwrap_or(&target)
+        .components()
+        .fold(base.clone(), |path, c| path.join(sanitize_file_name(&c.as_os_str().to_string_lossy())));
+    Ok(extended_length_path(&sanitized.to_string_lossy()))
 }
 
 #[cfg(test)]
@@ -16,4 +188,74 @@ mod tests {
         assert!(is_valid_url("http://example.com"));
         assert!(!is_valid_url("invalid-url"));
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_parse_url() {
+        // 国际化域名、IPv6、userinfo、localhost
+        assert_eq!(parse_url("https://münchen.de/datei.zip").unwrap().host_str(), Some("xn--mnchen-3ya.de"));
+        assert_eq!(parse_url("http://[::1]:8080/f.bin").unwrap().port(), Some(8080));
+        assert_eq!(parse_url("https://user:pw@example.com/a").unwrap().username(), "user");
+        assert!(is_valid_url("http://localhost:8080/file"));
+
+        // 片段被去掉，编码字符保留，空格被编码
+        assert_eq!(
+            parse_url(" https://example.com/a%20b/c d.zip?x=1#part ").unwrap().as_str(),
+            "https://example.com/a%20b/c%20d.zip?x=1"
+        );
+
+        assert!(matches!(parse_url("file:///etc/passwd"), Err(DownloadError::UnsupportedProtocol(_))));
+        assert!(matches!(parse_url("mailto:a@example.com"), Err(DownloadError::UnsupportedProtocol(_))));
+        assert!(matches!(parse_url("http://"), Err(DownloadError::InvalidUrl(_))));
+    }
+
+    #[test]
+    fn test_mirror_path() {
+        let mirror = |url| mirror_path(url).unwrap();
+        assert_eq!(mirror("https://host/a/b/c.zip"), Path::new("host/a/b/c.zip"));
+        assert_eq!(mirror("https://host:8443/a/c%20d.zip?v=1"), Path::new("host:8443/a/c d.zip"));
+        assert_eq!(mirror("https://host/docs/"), Path::new("host/docs/index.html"));
+        assert_eq!(mirror("https://host"), Path::new("host/index.html"));
+        // 编码的斜杠和 .. 不会多出或跳出目录
+        assert_eq!(mirror("https://host/a/%2E%2E/b%2Fc.zip"), Path::new("host/b_c.zip"));
+        assert_eq!(mirror("https://host//x//y.bin"), Path::new("host/x/y.bin"));
+        assert!(confine_to_dir("dl", &Path::new("dl").join(mirror("https://host/a/%2e%2e/%2e%2e/etc")).to_string_lossy()).is_ok());
+        assert!(mirror_path("not a url").is_err());
+    }
+
+    #[test]
+    fn test_confine_to_dir() {
+        let dir = "./downloads";
+        assert!(confine_to_dir(dir, "./downloads/file.zip").is_ok());
+        assert!(confine_to_dir(dir, "downloads/sub/./file.zip").is_ok());
+        assert!(confine_to_dir(dir, "downloads/sub/../file.zip").is_ok());
+
+        assert!(confine_to_dir(dir, "downloads/../file.zip").is_err());
+        assert!(confine_to_dir(dir, "downloads/../../etc/passwd").is_err());
+        assert!(confine_to_dir(dir, "/etc/passwd").is_err());
+        assert!(confine_to_dir(dir, "downloads-evil/file.zip").is_err());
+        assert!(confine_to_dir(dir, "downloads").is_err());
+
+        // 绝对路径的文件名拼接后替换掉下载目录，同样被拒绝
+        let joined = Path::new(dir).join(Path::new("/tmp").join("evil"));
+        assert!(confine_to_dir(dir, &joined.to_string_lossy()).is_err());
+    }
+
+    #[test]
+    fn test_sanitize_file_name() {
+        assert_eq!(sanitize_file_name("file.zip"), "file.zip");
+        assert_eq!(sanitize_file_name("CON"), "CON_");
+        assert_eq!(sanitize_file_name("nul.txt"), "nul_.txt");
+        assert_eq!(sanitize_file_name("Com1.tar.gz"), "Com1_.tar.gz");
+        assert_eq!(sanitize_file_name("console.log"), "console.log");
+        assert_eq!(sanitize_file_name("report. . "), "report");
+        assert_eq!(sanitize_file_name("a:b*c?.txt"), "a_b_c_.txt");
+        assert_eq!(sanitize_file_name("..."), "_");
+    }
+
+    #[test]
+    fn test_extended_length_path() {
+        assert_eq!(extended_length_path(r"C:\dl\a.zip"), r"\\?\C:\dl\a.zip");
+        assert_eq!(extended_length_path(r"\\server\share\a.zip"), r"\\?\UNC\server\share\a.zip");
+        assert_eq!(extended_length_path(r"\\?\C:\dl\a.zip"), r"\\?\C:\dl\a.zip");
+    }
+}
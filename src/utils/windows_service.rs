@@ -0,0 +1,323 @@
+//! Windows 服务：把 `multidown serve` 注册为服务，由服务控制管理器（SCM）启动和停止
+//!
+//! `service install` 在 SCM 中登记启动命令 `multidown ... service run`；`service run` 由 SCM 启动，
+//! 连接 SCM 后在服务线程中运行守护进程，依次报告 `START_PENDING`、`RUNNING`、`STOP_PENDING`、`STOPPED`，
+//! 收到停止或关机通知时让守护进程暂停所有任务、保存会话后退出。
+//! 服务没有控制台，标准输出和标准错误重定向到日志文件。
+
+use crate::core::error::DownloadError;
+use std::path::Path;
+use tokio::sync::Notify;
+
+/// 默认的服务名称
+pub const DEFAULT_NAME: &str = "multidown";
+
+/// 停止服务的通知，由 SCM 的控制回调发出
+static STOP: Notify = Notify::const_new();
+
+/// 传给守护进程的服务句柄
+pub struct Service {
+    _private: (),
+}
+
+impl Service {
+    /// 守护进程开始监听后调用，向 SCM 报告服务已运行
+    pub fn ready(&self) {
+        #[cfg(windows)]
+        sys::report(windows_sys::Win32::System::Services::SERVICE_RUNNING, 0);
+    }
+
+    /// 等待 SCM 要求停止服务，返回停止原因
+    pub async fn stopped(&self) -> &'static str {
+        STOP.notified().await;
+        "SERVICE_CONTROL_STOP"
+    }
+}
+
+/// 注册服务，`command_line` 为 SCM 启动服务时执行的完整命令行；`auto_start` 为 false 时需要手动启动
+pub fn install(name: &str, command_line: &str, auto_start: bool) -> Result<(), DownloadError> {
+    #[cfg(windows)]
+    {
+        sys::install(name, command_line, auto_start).map_err(|e| DownloadError::io_error_with_context(format!("无法注册服务 {}", name), e))
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = (name, command_line, auto_start);
+        Err(unsupported())
+    }
+}
+
+/// 停止并删除服务
+pub fn uninstall(name: &str) -> Result<(), DownloadError> {
+    #[cfg(windows)]
+    {
+        sys::uninstall(name).map_err(|e| DownloadError::io_error_with_context(format!("无法删除服务 {}", name), e))
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = name;
+        Err(unsupported())
+    }
+}
+
+/// 作为服务运行：标准输出和标准错误追加到 `log_path`，连接 SCM 后在服务线程中执行 `daemon`，
+/// 其返回值（进程退出码）作为服务的退出码报告给 SCM。阻塞到服务停止
+pub fn run<F>(name: &str, log_path: &Path, daemon: F) -> Result<(), DownloadError>
+where
+    F: FnOnce(Service) -> i32 + Send + 'static,
+{
+    #[cfg(windows)]
+    {
+        sys::redirect_output(log_path).map_err(|e| DownloadError::io_error_with_context(format!("无法打开日志文件 {}", log_path.display()), e))?;
+        sys::run(name, Box::new(move || daemon(Service { _private: () }))).map_err(|e| {
+            if e.raw_os_error() == Some(sys::NOT_STARTED_BY_SCM) {
+                DownloadError::unknown("service run 只能由服务控制管理器启动，在控制台中请使用 multidown serve")
+            } else {
+                DownloadError::io_error_with_context("无法连接服务控制管理器", e)
+            }
+        })
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = (name, log_path, daemon);
+        Err(unsupported())
+    }
+}
+
+#[cfg(not(windows))]
+fn unsupported() -> DownloadError {
+    DownloadError::unknown("Windows 服务只能在 Windows 上使用，Linux 上可以用 systemd 运行 multidown serve")
+}
+
+/// 命令行中的一个参数，按 Windows 的规则加引号
+pub fn quote_arg(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_string();
+    }
+    let mut quoted = String::from("\"");
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                // 引号前的反斜杠和引号本身都要转义
+                quoted.extend(std::iter::repeat_n('\\', backslashes * 2 + 1));
+                quoted.push('"');
+                backslashes = 0;
+                continue;
+            }
+            _ => {}
+        }
+        if c != '\\' {
+            quoted.extend(std::iter::repeat_n('\\', backslashes));
+            backslashes = 0;
+            quoted.push(c);
+        }
+    }
+    // 结尾的反斜杠后面紧跟闭合引号，需要加倍
+    quoted.extend(std::iter::repeat_n('\\', backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::ffi::c_void;
+    use std::io;
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::io::IntoRawHandle;
+    use std::path::Path;
+    use std::sync::atomic::{AtomicPtr, Ordering};
+    use std::sync::Mutex;
+    use windows_sys::Win32::Foundation::{ERROR_CALL_NOT_IMPLEMENTED, ERROR_FAILED_SERVICE_CONTROLLER_CONNECT, ERROR_SERVICE_SPECIFIC_ERROR, NO_ERROR};
+    use windows_sys::Win32::System::Console::{SetStdHandle, STD_ERROR_HANDLE, STD_OUTPUT_HANDLE};
+    use windows_sys::Win32::System::Services::*;
+
+    /// 不是由 SCM 启动时 `StartServiceCtrlDispatcherW` 返回的错误码
+    pub const NOT_STARTED_BY_SCM: i32 = ERROR_FAILED_SERVICE_CONTROLLER_CONNECT as i32;
+
+    /// 删除对象的标准访问权限
+    const DELETE: u32 = 0x0001_0000;
+
+    /// 停止服务（暂停任务、保存会话）预计需要的最长时间
+    const STOP_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+    /// `RegisterServiceCtrlHandlerExW` 返回的状态句柄
+    static STATUS_HANDLE: AtomicPtr<c_void> = AtomicPtr::new(std::ptr::null_mut());
+    /// 服务名称和守护进程，`service_main` 在服务线程中取出
+    static SERVICE: Mutex<Option<(Vec<u16>, Box<dyn FnOnce() -> i32 + Send>)>> = Mutex::new(None);
+
+    fn wide(s: impl AsRef<std::ffi::OsStr>) -> Vec<u16> {
+        s.as_ref().encode_wide().chain(Some(0)).collect()
+    }
+
+    /// 关闭时自动释放的 SCM 句柄
+    struct Handle(SC_HANDLE);
+
+    impl Handle {
+        fn new(handle: SC_HANDLE) -> io::Result<Self> {
+            if handle.is_null() {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(Self(handle))
+            }
+        }
+    }
+
+    impl Drop for Handle {
+        fn drop(&mut self) {
+            // SAFETY: 句柄由 OpenSCManagerW/OpenServiceW/CreateServiceW 返回，只关闭一次
+            unsafe { CloseServiceHandle(self.0) };
+        }
+    }
+
+    fn check(ok: i32) -> io::Result<()> {
+        if ok == 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn install(name: &str, command_line: &str, auto_start: bool) -> io::Result<()> {
+        let (name, command_line) = (wide(name), wide(command_line));
+        let description = wide("MultiDown 下载守护进程");
+        // SAFETY: 所有字符串都以 0 结尾，并且在调用期间有效
+        unsafe {
+            let manager = Handle::new(OpenSCManagerW(std::ptr::null(), std::ptr::null(), SC_MANAGER_CREATE_SERVICE))?;
+            let start_type = if auto_start { SERVICE_AUTO_START } else { SERVICE_DEMAND_START };
+            let service = Handle::new(CreateServiceW(
+                manager.0,
+                name.as_ptr(),
+                name.as_ptr(),
+                SERVICE_ALL_ACCESS,
+                SERVICE_WIN32_OWN_PROCESS,
+                start_type,
+                SERVICE_ERROR_NORMAL,
+                command_line.as_ptr(),
+                std::ptr::null(),
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                std::ptr::null(),
+                std::ptr::null(),
+            ))?;
+            let description = SERVICE_DESCRIPTIONW { lpDescription: description.as_ptr() as *mut u16 };
+            // 描述只用于服务管理器中显示，设置失败不影响使用
+            ChangeServiceConfig2W(service.0, SERVICE_CONFIG_DESCRIPTION, &description as *const _ as *const c_void);
+        }
+        Ok(())
+    }
+
+    pub fn uninstall(name: &str) -> io::Result<()> {
+        let name = wide(name);
+        // SAFETY: 同上；SERVICE_STATUS 是普通的 C 结构体，可以用全零初始化
+        unsafe {
+            let manager = Handle::new(OpenSCManagerW(std::ptr::null(), std::ptr::null(), SC_MANAGER_CONNECT))?;
+            let service = Handle::new(OpenServiceW(manager.0, name.as_ptr(), SERVICE_STOP | SERVICE_QUERY_STATUS | DELETE))?;
+            let mut status: SERVICE_STATUS = std::mem::zeroed();
+            if ControlService(service.0, SERVICE_CONTROL_STOP, &mut status) != 0 {
+                // 等守护进程保存会话后再删除，避免删除后它仍在运行
+                let deadline = std::time::Instant::now() + STOP_TIMEOUT;
+                while status.dwCurrentState != SERVICE_STOPPED && std::time::Instant::now() < deadline {
+                    std::thread::sleep(std::time::Duration::from_millis(500));
+                    check(QueryServiceStatus(service.0, &mut status))?;
+                }
+            }
+            check(DeleteService(service.0))
+        }
+    }
+
+    /// 把标准输出和标准错误换成日志文件；标准库每次写入时都重新读取标准句柄
+    pub fn redirect_output(path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+        let handle = file.into_raw_handle();
+        // SAFETY: 句柄在进程退出前不会关闭，同时作为标准输出和标准错误使用
+        unsafe {
+            check(SetStdHandle(STD_OUTPUT_HANDLE, handle))?;
+            check(SetStdHandle(STD_ERROR_HANDLE, handle))
+        }
+    }
+
+    pub fn run(name: &str, daemon: Box<dyn FnOnce() -> i32 + Send>) -> io::Result<()> {
+        let mut name = wide(name);
+        let table = [
+            SERVICE_TABLE_ENTRYW { lpServiceName: name.as_mut_ptr(), lpServiceProc: Some(service_main) },
+            SERVICE_TABLE_ENTRYW { lpServiceName: std::ptr::null_mut(), lpServiceProc: None },
+        ];
+        *SERVICE.lock().unwrap_or_else(|e| e.into_inner()) = Some((name.clone(), daemon));
+        // SAFETY: 服务表以空项结尾；调用阻塞到服务停止，期间 table 和 name 一直有效
+        check(unsafe { StartServiceCtrlDispatcherW(table.as_ptr()) })
+    }
+
+    /// 向 SCM 报告服务状态，`wait_hint` 为 pending 状态预计持续的毫秒数
+    pub fn report(state: u32, wait_hint: u32) {
+        report_exit(state, wait_hint, 0);
+    }
+
+    fn report_exit(state: u32, wait_hint: u32, exit_code: i32) {
+        let handle = STATUS_HANDLE.load(Ordering::Acquire);
+        if handle.is_null() {
+            return;
+        }
+        let accepted = if state == SERVICE_RUNNING { SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_SHUTDOWN } else { 0 };
+        let status = SERVICE_STATUS {
+            dwServiceType: SERVICE_WIN32_OWN_PROCESS,
+            dwCurrentState: state,
+            dwControlsAccepted: accepted,
+            dwWin32ExitCode: if exit_code == 0 { NO_ERROR } else { ERROR_SERVICE_SPECIFIC_ERROR },
+            dwServiceSpecificExitCode: exit_code as u32,
+            dwCheckPoint: if wait_hint > 0 { 1 } else { 0 },
+            dwWaitHint: wait_hint,
+        };
+        // SAFETY: 句柄来自 RegisterServiceCtrlHandlerExW，在服务停止前有效
+        if unsafe { SetServiceStatus(handle, &status) } == 0 {
+            tracing::warn!(state, error = %io::Error::last_os_error(), "向服务控制管理器报告状态失败");
+        }
+    }
+
+    /// 服务线程的入口：注册控制回调，运行守护进程，结束后报告 `STOPPED`
+    unsafe extern "system" fn service_main(_argc: u32, _argv: *mut windows_sys::core::PWSTR) {
+        let Some((name, daemon)) = SERVICE.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+            return;
+        };
+        let handle = RegisterServiceCtrlHandlerExW(name.as_ptr(), Some(control_handler), std::ptr::null());
+        if handle.is_null() {
+            tracing::error!(error = %io::Error::last_os_error(), "无法注册服务控制回调");
+            return;
+        }
+        STATUS_HANDLE.store(handle, Ordering::Release);
+        report(SERVICE_START_PENDING, 10_000);
+        let exit_code = daemon();
+        report_exit(SERVICE_STOPPED, 0, exit_code);
+    }
+
+    /// SCM 的控制回调：停止和关机时通知守护进程退出，暂停任务和保存会话可能需要一些时间
+    unsafe extern "system" fn control_handler(control: u32, _event_type: u32, _event_data: *mut c_void, _context: *mut c_void) -> u32 {
+        match control {
+            SERVICE_CONTROL_STOP | SERVICE_CONTROL_SHUTDOWN => {
+                report(SERVICE_STOP_PENDING, STOP_TIMEOUT.as_millis() as u32);
+                super::STOP.notify_one();
+                NO_ERROR
+            }
+            SERVICE_CONTROL_INTERROGATE => NO_ERROR,
+            _ => ERROR_CALL_NOT_IMPLEMENTED,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_arg() {
+        assert_eq!(quote_arg("serve"), "serve");
+        assert_eq!(quote_arg(r"C:\Program Files\multidown.exe"), r#""C:\Program Files\multidown.exe""#);
+        assert_eq!(quote_arg(r"C:\My Downloads\"), r#""C:\My Downloads\\""#);
+        assert_eq!(quote_arg(r#"a "b""#), r#""a \"b\"""#);
+        assert_eq!(quote_arg(""), r#""""#);
+    }
+}
@@ -0,0 +1,480 @@
+//! 网页控制台：`multidown serve` 默认在 <http://127.0.0.1:6801> 提供
+//!
+//! 页面（`src/web/` 下的静态文件，编译进程序）显示任务表格、添加任务的表单和每个任务的操作按钮：
+//!
+//! - `GET /`、`/app.js`、`/style.css`：页面本身；
+//! - `POST /api`：控制请求，请求体与本地控制通道相同（见 [`crate::core::ipc::Request`]），响应为 `{"ok":...}`；
+//! - `GET /api/events`：WebSocket，推送 `{"type":"progress",...}`、`{"type":"phase",...}` 和
+//!   `{"type":"changed","task_id":...}`（状态等发生变化，页面重新读取任务列表）。
+//!
+//! 认证与 gRPC 相同（见 [`Credentials`]）：`POST /api` 在 `authorization: Bearer <令牌>` 或 `x-api-key` 请求头中携带
+//! 访问令牌或客户端的 API 密钥；浏览器的 WebSocket 不能设置请求头，改用 `?token=` 查询参数。静态文件不需要认证。
+//! 带 `Origin` 的请求必须与 `Host` 同源，其他网站的页面不能借用户的浏览器提交任务；没有配置认证时
+//! `Host` 还必须是本机地址，防止 DNS 重绑定。
+//!
+//! 每个连接只处理一个 HTTP 请求（`Connection: close`），页面的请求很少，不需要连接复用。
+
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::rc::Rc;
+
+use base64::Engine;
+use futures::StreamExt;
+use serde_json::{json, Value};
+use sha1::{Digest, Sha1};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_rustls::TlsAcceptor;
+use tokio_util::sync::CancellationToken;
+
+use crate::config::clients::{Caller, Credentials};
+use crate::core::actor_manager::QueryTaskDetail;
+use crate::core::error::DownloadError;
+use crate::core::events::{EventBus, TaskEvent};
+use crate::core::ipc::{Dispatcher, Request, Response};
+use crate::utils::tls;
+
+/// 默认监听地址
+pub const DEFAULT_ADDR: &str = "127.0.0.1:6801";
+
+/// 请求行和请求头的最大长度
+const MAX_HEAD_LEN: usize = 16 << 10;
+
+/// 请求体的最大长度
+const MAX_BODY_LEN: usize = 1 << 20;
+
+/// 浏览器发来的 WebSocket 帧的最大长度，页面只发送关闭和 ping
+const MAX_WS_FRAME_LEN: usize = 64 << 10;
+
+/// WebSocket 握手中拼接在 `Sec-WebSocket-Key` 之后的固定字符串（RFC 6455）
+const WS_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const INDEX_HTML: &str = include_str!("web/index.html");
+const APP_JS: &str = include_str!("web/app.js");
+const STYLE_CSS: &str = include_str!("web/style.css");
+
+/// 网页控制台处理请求需要的状态
+pub struct Console {
+    pub dispatcher: Dispatcher,
+    pub events: EventBus,
+    pub credentials: Credentials,
+}
+
+/// 网页控制台的监听器
+pub struct Server {
+    listener: TcpListener,
+    tls: Option<TlsAcceptor>,
+}
+
+impl Server {
+    /// 开始监听；`tls` 为 Some 时只接受 HTTPS 连接
+    pub fn bind(addr: SocketAddr, tls: Option<TlsAcceptor>) -> Result<Self, DownloadError> {
+        let listener = std::net::TcpListener::bind(addr)
+            .map_err(|e| DownloadError::io_error_with_context(format!("无法监听 {}", addr), e))?;
+        Self::from_listener(listener, tls)
+    }
+
+    /// 使用已经在监听的套接字，例如 systemd 套接字激活传入的
+    pub fn from_listener(listener: std::net::TcpListener, tls: Option<TlsAcceptor>) -> Result<Self, DownloadError> {
+        let listener = listener
+            .set_nonblocking(true)
+            .and_then(|()| TcpListener::from_std(listener))
+            .map_err(|e| DownloadError::io_error_with_context("无法使用监听套接字", e))?;
+        Ok(Self { listener, tls })
+    }
+
+    /// 实际监听的地址
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// 页面地址，用于提示用户
+    pub fn url(&self) -> String {
+        let scheme = if self.tls.is_some() { "https" } else { "http" };
+        match self.local_addr() {
+            Ok(addr) => format!("{}://{}", scheme, addr),
+            Err(_) => format!("{}://?", scheme),
+        }
+    }
+
+    /// 接受连接并处理请求，直到 `stop` 被取消
+    pub async fn run(self, console: Console, stop: CancellationToken) {
+        let console = Rc::new(console);
+        loop {
+            let (stream, peer) = tokio::select! {
+                _ = stop.cancelled() => break,
+                accepted = self.listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        tracing::warn!(error = %e, "接受网页控制台连接失败");
+                        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                        continue;
+                    }
+                },
+            };
+            let console = console.clone();
+            let tls = self.tls.clone();
+            let stop = stop.clone();
+            actix::spawn(async move {
+                let served = match tls {
+                    Some(acceptor) => match tls::handshake(&acceptor, stream).await {
+                        Ok(stream) => serve_connection(stream, console, stop).await,
+                        Err(e) => Err(e),
+                    },
+                    None => serve_connection(stream, console, stop).await,
+                };
+                if let Err(e) = served {
+                    tracing::debug!(peer = %peer, error = %e, "网页控制台连接出错");
+                }
+            });
+        }
+    }
+}
+
+/// 解析后的 HTTP 请求
+#[derive(Debug, Default, PartialEq)]
+struct HttpRequest {
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    /// 请求头，名称为小写
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+impl HttpRequest {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+}
+
+/// 读取一个请求，连接在请求头之前关闭时返回 None
+async fn read_request(stream: &mut (impl AsyncRead + Unpin)) -> io::Result<Option<HttpRequest>> {
+    let mut buf = Vec::with_capacity(1024);
+    let head_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos;
+        }
+        if buf.len() > MAX_HEAD_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "请求头过长"));
+        }
+        let mut chunk = [0u8; 4096];
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return match buf.is_empty() {
+                true => Ok(None),
+                false => Err(io::ErrorKind::UnexpectedEof.into()),
+            };
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+    let mut request = parse_head(&buf[..head_end])?;
+    let length: usize = request.header("content-length").and_then(|v| v.trim().parse().ok()).unwrap_or(0);
+    if length > MAX_BODY_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "请求体过长"));
+    }
+    let mut body = buf.split_off(head_end + 4);
+    if body.len() < length {
+        let start = body.len();
+        body.resize(length, 0);
+        stream.read_exact(&mut body[start..]).await?;
+    }
+    body.truncate(length);
+    request.body = body;
+    Ok(Some(request))
+}
+
+/// 解析请求行和请求头
+fn parse_head(head: &[u8]) -> io::Result<HttpRequest> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "无效的 HTTP 请求");
+    let head = std::str::from_utf8(head).map_err(|_| invalid())?;
+    let mut lines = head.split("\r\n");
+    let mut parts = lines.next().ok_or_else(invalid)?.split(' ');
+    let (method, target) = (parts.next().ok_or_else(invalid)?, parts.next().ok_or_else(invalid)?);
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = url::form_urlencoded::parse(query.as_bytes()).into_owned().collect();
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+    Ok(HttpRequest { method: method.to_string(), path: path.to_string(), query, headers, body: Vec::new() })
+}
+
+/// 完整的 HTTP 响应
+fn response(status: &str, content_type: &str, body: &[u8]) -> Vec<u8> {
+    let mut out = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\n\
+         X-Content-Type-Options: nosniff\r\nX-Frame-Options: DENY\r\n\
+         Content-Security-Policy: default-src 'self'; connect-src 'self' ws: wss:\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )
+    .into_bytes();
+    out.extend_from_slice(body);
+    out
+}
+
+fn json_response(status: &str, response: &Response) -> Vec<u8> {
+    let body = serde_json::to_vec(response).unwrap_or_default();
+    self::response(status, "application/json; charset=utf-8", &body)
+}
+
+/// 带 `Origin` 的请求必须与 `Host` 同源；`local_only` 时 `Host` 还必须是本机地址
+fn same_origin(request: &HttpRequest, local_only: bool) -> bool {
+    let Some(host) = request.header("host") else {
+        return false;
+    };
+    if local_only {
+        let name = host.rsplit_once(':').map_or(host, |(name, _)| name);
+        if !matches!(name, "localhost" | "127.0.0.1" | "[::1]") {
+            return false;
+        }
+    }
+    match request.header("origin") {
+        None => true,
+        Some(origin) => url::Url::parse(origin).is_ok_and(|origin| {
+            let origin_host = match (origin.host_str(), origin.port()) {
+                (Some(name), Some(port)) => format!("{}:{}", name, port),
+                (Some(name), None) => name.to_string(),
+                _ => return false,
+            };
+            origin_host.eq_ignore_ascii_case(host)
+        }),
+    }
+}
+
+/// 处理一个连接上的请求
+async fn serve_connection<S>(mut stream: S, console: Rc<Console>, stop: CancellationToken) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin + 'static,
+{
+    let Some(request) = read_request(&mut stream).await? else {
+        return Ok(());
+    };
+    let reply = match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/" | "/index.html") => response("200 OK", "text/html; charset=utf-8", INDEX_HTML.as_bytes()),
+        ("GET", "/app.js") => response("200 OK", "text/javascript; charset=utf-8", APP_JS.as_bytes()),
+        ("GET", "/style.css") => response("200 OK", "text/css; charset=utf-8", STYLE_CSS.as_bytes()),
+        (_, "/api" | "/api/events") if !same_origin(&request, console.credentials.is_open()) => {
+            json_response("403 Forbidden", &Response::error("请求来源与控制台不一致".to_string()))
+        }
+        ("POST", "/api") => api(&request, &console).await,
+        ("GET", "/api/events") => {
+            let presented = [request.query.get("token").map(String::as_str)];
+            let Some(caller) = console.credentials.authenticate(&presented) else {
+                return stream.write_all(&json_response("401 Unauthorized", &unauthorized())).await;
+            };
+            let Some(key) = request.header("sec-websocket-key") else {
+                return stream.write_all(&response("400 Bad Request", "text/plain", b"WebSocket required")).await;
+            };
+            let client = caller.client();
+            stream.write_all(&websocket_accept(key)).await?;
+            return event_stream(stream, console, client, stop).await;
+        }
+        _ => response("404 Not Found", "text/plain; charset=utf-8", b"Not Found"),
+    };
+    stream.write_all(&reply).await?;
+    stream.shutdown().await
+}
+
+fn unauthorized() -> Response {
+    Response::error("缺少或无效的访问令牌或 API 密钥".to_string())
+}
+
+/// `POST /api`：认证后把请求交给 [`Dispatcher`]
+async fn api(request: &HttpRequest, console: &Console) -> Vec<u8> {
+    let bearer = request.header("authorization").and_then(|v| v.strip_prefix("Bearer ")).map(str::trim);
+    let caller = match console.credentials.authenticate(&[bearer, request.header("x-api-key")]) {
+        Some(caller) => caller,
+        None => return json_response("401 Unauthorized", &unauthorized()),
+    };
+    // 只接受 JSON，跨站的表单提交不能绕过浏览器的预检
+    if !request.header("content-type").is_some_and(|v| v.starts_with("application/json")) {
+        return json_response("415 Unsupported Media Type", &Response::error("请求体必须是 JSON".to_string()));
+    }
+    let control: Request = match serde_json::from_slice(&request.body) {
+        Ok(control) => control,
+        Err(e) => return json_response("400 Bad Request", &Response::error(format!("无效的请求: {}", e))),
+    };
+    let client = match caller {
+        Caller::Owner => None,
+        Caller::Client(name) => Some(name),
+    };
+    json_response("200 OK", &Response::from(console.dispatcher.handle(client, control).await))
+}
+
+/// WebSocket 握手的响应
+fn websocket_accept(key: &str) -> Vec<u8> {
+    let digest = Sha1::digest(format!("{}{}", key.trim(), WS_GUID).as_bytes());
+    let accept = base64::engine::general_purpose::STANDARD.encode(digest);
+    format!("HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n", accept)
+        .into_bytes()
+}
+
+/// 编码一个服务端发出的帧（不加掩码）
+fn ws_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len if len < 126 => frame.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// 读取浏览器发来的一个帧，返回 (操作码, 去掉掩码的内容)
+async fn read_ws_frame(reader: &mut (impl AsyncRead + Unpin)) -> io::Result<(u8, Vec<u8>)> {
+    let mut header = [0u8; 2];
+    reader.read_exact(&mut header).await?;
+    let opcode = header[0] & 0x0f;
+    let len = match header[1] & 0x7f {
+        126 => {
+            let mut len = [0u8; 2];
+            reader.read_exact(&mut len).await?;
+            u16::from_be_bytes(len) as usize
+        }
+        127 => {
+            let mut len = [0u8; 8];
+            reader.read_exact(&mut len).await?;
+            u64::from_be_bytes(len) as usize
+        }
+        len => len as usize,
+    };
+    if len > MAX_WS_FRAME_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "WebSocket 帧过长"));
+    }
+    let mut mask = [0u8; 4];
+    if header[1] & 0x80 != 0 {
+        reader.read_exact(&mut mask).await?;
+    }
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload).await?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+    Ok((opcode, payload))
+}
+
+/// 推送给页面的事件，重试等页面不显示的事件返回 None
+fn event_message(event: &TaskEvent) -> Option<Value> {
+    Some(match event {
+        TaskEvent::Progress { task_id, downloaded, total, speed } => {
+            json!({ "type": "progress", "task_id": task_id, "downloaded": downloaded, "total": total, "speed": speed })
+        }
+        TaskEvent::Phase { task_id, phase, progress } => {
+            json!({ "type": "phase", "task_id": task_id, "phase": phase.as_str(), "progress": progress })
+        }
+        TaskEvent::ChunkFailed { .. } | TaskEvent::Retrying { .. } => return None,
+        other => json!({ "type": "changed", "task_id": other.task_id() }),
+    })
+}
+
+/// 浏览器发来的控制帧，交给写入一侧回复
+enum Control {
+    Pong(Vec<u8>),
+    Close,
+}
+
+/// WebSocket 连接：把任务事件推送给页面，客户端只收到自己任务的事件
+async fn event_stream<S>(stream: S, console: Rc<Console>, client: Option<String>, stop: CancellationToken) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin + 'static,
+{
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let (control_tx, mut control_rx) = mpsc::unbounded_channel();
+    // 读取一侧只处理 ping 和关闭，页面不通过 WebSocket 发送请求
+    let read = actix::spawn(async move {
+        loop {
+            match read_ws_frame(&mut reader).await {
+                Ok((0x9, payload)) => {
+                    let _ = control_tx.send(Control::Pong(payload));
+                }
+                Ok((0x8, _)) | Err(_) => {
+                    let _ = control_tx.send(Control::Close);
+                    break;
+                }
+                Ok(_) => {}
+            }
+        }
+    });
+    let mut events = console.events.stream();
+    let result = loop {
+        let frame = tokio::select! {
+            _ = stop.cancelled() => break writer.write_all(&ws_frame(0x8, &[])).await,
+            control = control_rx.recv() => match control {
+                Some(Control::Pong(payload)) => ws_frame(0xA, &payload),
+                Some(Control::Close) | None => break writer.write_all(&ws_frame(0x8, &[])).await,
+            },
+            event = events.next() => {
+                let Some(event) = event else {
+                    break writer.write_all(&ws_frame(0x8, &[])).await;
+                };
+                let Some(message) = event_message(&event) else {
+                    continue;
+                };
+                if client.is_some() {
+                    let meta = console.dispatcher.manager.send(QueryTaskDetail(event.task_id())).await.ok().flatten();
+                    if meta.and_then(|meta| meta.client) != client {
+                        continue;
+                    }
+                }
+                ws_frame(0x1, message.to_string().as_bytes())
+            }
+        };
+        if let Err(e) = writer.write_all(&frame).await {
+            break Err(e);
+        }
+    };
+    read.abort();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_head_and_origin() {
+        let head = b"POST /api/events?token=a%20b HTTP/1.1\r\nHost: 127.0.0.1:6801\r\nOrigin: http://127.0.0.1:6801\r\nX-Api-Key: k";
+        let request = parse_head(head).unwrap();
+        assert_eq!((request.method.as_str(), request.path.as_str()), ("POST", "/api/events"));
+        assert_eq!(request.query.get("token").map(String::as_str), Some("a b"));
+        assert_eq!(request.header("x-api-key"), Some("k"));
+        assert!(same_origin(&request, true));
+
+        let cross = |origin: &str, host: &str| {
+            let head = format!("GET / HTTP/1.1\r\nHost: {}\r\nOrigin: {}", host, origin);
+            same_origin(&parse_head(head.as_bytes()).unwrap(), true)
+        };
+        assert!(!cross("https://evil.example", "127.0.0.1:6801"));
+        // DNS 重绑定：来源与 Host 一致，但不是本机地址
+        assert!(!cross("http://evil.example:6801", "evil.example:6801"));
+        assert!(cross("http://localhost:6801", "localhost:6801"));
+        assert!(parse_head(b"").is_err());
+    }
+
+    #[test]
+    fn test_websocket_frames() {
+        // RFC 6455 第 1.3 节的示例
+        let accept = String::from_utf8(websocket_accept("dGhlIHNhbXBsZSBub25jZQ==")).unwrap();
+        assert!(accept.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+
+        assert_eq!(ws_frame(0x1, b"hi"), vec![0x81, 2, b'h', b'i']);
+        assert_eq!(&ws_frame(0x1, &[0; 300])[..4], &[0x81, 126, 1, 44]);
+
+        // 浏览器发来的帧带掩码
+        let masked = [0x89, 0x82, 1, 2, 3, 4, b'h' ^ 1, b'i' ^ 2];
+        let (opcode, payload) = tokio_test::block_on(read_ws_frame(&mut &masked[..])).unwrap();
+        assert_eq!((opcode, payload.as_slice()), (0x9, &b"hi"[..]));
+    }
+}
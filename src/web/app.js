@@ -0,0 +1,203 @@
+// MultiDown 网页控制台：通过 POST /api 发送控制请求，通过 /api/events 的 WebSocket 接收任务事件
+"use strict";
+
+const STATUS_NAMES = {
+  pending: "等待", queued: "排队", running: "下载中", paused: "已暂停",
+  completed: "已完成", failed: "失败", cancelled: "已取消", merging: "合并中", verifying: "校验中",
+};
+
+let token = localStorage.getItem("multidown-token") || "";
+let tasks = [];
+let refreshTimer = null;
+let socket = null;
+
+const $ = (id) => document.getElementById(id);
+
+function humanSize(bytes) {
+  const units = ["B", "KiB", "MiB", "GiB", "TiB"];
+  let value = bytes, unit = 0;
+  while (value >= 1024 && unit < units.length - 1) { value /= 1024; unit++; }
+  return (unit === 0 ? value : value.toFixed(2)) + " " + units[unit];
+}
+
+function showError(message) {
+  $("error").textContent = message || "";
+  $("error").hidden = !message;
+}
+
+async function api(method, params) {
+  const headers = { "Content-Type": "application/json" };
+  if (token) headers["Authorization"] = "Bearer " + token;
+  const response = await fetch("/api", { method: "POST", headers, body: JSON.stringify({ method, ...params }) });
+  if (response.status === 401) {
+    $("login").hidden = false;
+    throw new Error("需要访问令牌或 API 密钥");
+  }
+  const reply = await response.json();
+  if (!reply.ok) throw new Error(reply.error);
+  return reply.result;
+}
+
+// 任务状态：失败时为 {"Failed": "原因"}，收尾阶段优先显示阶段名称
+function statusOf(task) {
+  if (task.phase) return task.phase;
+  return typeof task.status === "string" ? task.status.toLowerCase() : "failed";
+}
+
+function button(label, onClick) {
+  const b = document.createElement("button");
+  b.textContent = label;
+  b.addEventListener("click", () => onClick().then(scheduleRefresh, (e) => showError(e.message)));
+  return b;
+}
+
+function render() {
+  const body = $("tasks");
+  body.replaceChildren();
+  $("empty").hidden = tasks.length > 0;
+  for (const task of tasks) {
+    const status = statusOf(task);
+    const row = document.createElement("tr");
+    row.id = "task-" + task.id;
+
+    const file = document.createElement("td");
+    file.className = "file";
+    file.textContent = task.file;
+    file.title = task.url;
+    if (task.status && task.status.Failed) {
+      const error = document.createElement("small");
+      error.textContent = task.status.Failed;
+      file.append(error);
+    }
+
+    const state = document.createElement("td");
+    state.className = "status-" + status;
+    state.textContent = STATUS_NAMES[status] || status;
+
+    const progress = document.createElement("td");
+    const bar = document.createElement("progress");
+    bar.max = 100;
+    bar.value = task.phase_progress != null ? task.phase_progress : task.progress;
+    progress.append(bar);
+
+    const speed = document.createElement("td");
+    speed.className = "num speed";
+    speed.textContent = status === "running" ? humanSize(task.speed) + "/s" : "-";
+
+    const size = document.createElement("td");
+    size.className = "num size";
+    size.textContent = humanSize(task.downloaded) + " / " + (task.total ? humanSize(task.total) : "?");
+
+    const actions = document.createElement("td");
+    actions.className = "actions";
+    const id = task.id;
+    if (["pending", "queued", "running"].includes(status)) {
+      actions.append(button("暂停", () => api("pause", { task_id: id })));
+    }
+    if (["paused", "failed"].includes(status)) {
+      actions.append(button("继续", () => api("resume", { task_id: id })));
+    }
+    if (!["completed", "cancelled"].includes(status)) {
+      actions.append(button("取消", () => api("cancel", { task_id: id })));
+    }
+    actions.append(button("删除", () => api("remove", { task_id: id, with_data: false })));
+
+    row.append(file, state, progress, speed, size, actions);
+    body.append(row);
+  }
+}
+
+async function refresh() {
+  refreshTimer = null;
+  try {
+    const [list, stats] = await Promise.all([api("list"), api("stats")]);
+    tasks = list.sort((a, b) => (a.started_at || "").localeCompare(b.started_at || "") || a.file.localeCompare(b.file));
+    $("stats").textContent = `${stats.total} 个任务，${stats.running} 个下载中，${humanSize(stats.speed)}/s`;
+    render();
+    showError("");
+    connect();
+  } catch (e) {
+    showError(e.message);
+    // 守护进程暂时不可用时稍后重试，需要令牌时等用户输入
+    if ($("login").hidden) setTimeout(refresh, 5000);
+  }
+}
+
+// 状态变化往往成批到达，合并成一次刷新
+function scheduleRefresh() {
+  if (!refreshTimer) refreshTimer = setTimeout(refresh, 300);
+}
+
+// 进度事件只更新对应的行，不重新读取列表
+function applyProgress(event) {
+  const task = tasks.find((t) => t.id === event.task_id);
+  if (!task) return scheduleRefresh();
+  task.downloaded = event.downloaded;
+  task.total = event.total;
+  task.speed = event.speed;
+  task.progress = event.total ? (event.downloaded * 100) / event.total : 0;
+  const row = $("task-" + task.id);
+  if (!row) return;
+  row.querySelector("progress").value = task.progress;
+  row.querySelector(".speed").textContent = humanSize(task.speed) + "/s";
+  row.querySelector(".size").textContent = humanSize(task.downloaded) + " / " + (task.total ? humanSize(task.total) : "?");
+}
+
+function connect() {
+  if (socket) return;
+  const scheme = location.protocol === "https:" ? "wss" : "ws";
+  socket = new WebSocket(`${scheme}://${location.host}/api/events?token=${encodeURIComponent(token)}`);
+  socket.onopen = () => {
+    $("connection").textContent = "已连接";
+    $("connection").className = "";
+  };
+  socket.onmessage = (message) => {
+    const event = JSON.parse(message.data);
+    if (event.type === "progress") {
+      applyProgress(event);
+    } else if (event.type === "phase") {
+      const task = tasks.find((t) => t.id === event.task_id);
+      if (task && task.phase === event.phase) {
+        task.phase_progress = event.progress;
+        const row = $("task-" + task.id);
+        if (row) row.querySelector("progress").value = event.progress;
+      } else {
+        scheduleRefresh();
+      }
+    } else {
+      scheduleRefresh();
+    }
+  };
+  socket.onclose = () => {
+    socket = null;
+    $("connection").textContent = "未连接";
+    $("connection").className = "offline";
+    setTimeout(refresh, 2000);
+  };
+}
+
+$("login").addEventListener("submit", (e) => {
+  e.preventDefault();
+  token = $("token").value;
+  localStorage.setItem("multidown-token", token);
+  $("login").hidden = true;
+  if (socket) socket.close();
+  refresh();
+});
+
+$("add").addEventListener("submit", async (e) => {
+  e.preventDefault();
+  const params = { url: $("url").value.trim() };
+  const fileName = $("file-name").value.trim();
+  if (fileName) params.file_name = fileName;
+  try {
+    await api("add", params);
+    $("url").value = "";
+    $("file-name").value = "";
+    scheduleRefresh();
+  } catch (err) {
+    showError(err.message);
+  }
+});
+
+refresh();
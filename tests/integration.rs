@@ -1,7 +1,7 @@
-use multidown::*;
+use multidown::config::Config;
 
 #[test]
 fn test_lib_loads() {
     // 仅测试主模块能被加载
-    assert!(true);
-} 
\ No newline at end of file
+    assert!(Config::default().validate().is_ok());
+}